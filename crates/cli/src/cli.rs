@@ -1,5 +1,17 @@
-use clap::{Parser, Subcommand, ValueEnum};
+use clap::{ArgAction, Parser, Subcommand, ValueEnum};
+use clap_complete::engine::ArgValueCompleter;
+use serde::{Deserialize, Serialize};
+use sheila::runners::{
+    LibtestFormat, RemoteHost, Shard, ShardStrategy, TagExpr, parse_duration, parse_env_var,
+    read_env_file,
+};
+use sheila::schema::SchemaType;
+use sheila::schemas::PackageSelection;
+use std::collections::HashMap;
 use std::path::PathBuf;
+use std::time::Duration;
+
+use crate::commands::completions::{complete_tag_expr, complete_target};
 
 #[derive(Parser)]
 #[command(name = "sheila")]
@@ -8,45 +20,167 @@ use std::path::PathBuf;
 pub struct Cli {
     #[command(subcommand)]
     pub command: Commands,
+
+    /// Log more: `-v` for debug logs, `-vv` for trace logs. `SHEILA_LOG`
+    /// takes a filter like `sheila=debug` and wins over this
+    #[arg(short, long, action = ArgAction::Count, global = true)]
+    pub verbose: u8,
+
+    /// Only log errors
+    #[arg(short, long, global = true, conflicts_with = "verbose")]
+    pub quiet: bool,
 }
 
 #[derive(Subcommand)]
 pub enum Commands {
     /// Run tests according to the specified inputs
-    Test(TestArgs),
+    Test(Box<TestArgs>),
     /// List all available test suites and their tests
     List(ListArgs),
     /// Pretty print a JSON or CSV report
     Report(ReportArgs),
-    /// Stop a headless test running in the background
+    /// Show pass rate, duration and flakiness trends over recent runs
+    History(HistoryArgs),
+    /// Run `#[sheila::bench]` benchmarks and compare them against a baseline
+    Bench(BenchArgs),
+    /// Build a single test with debug info and run it under a debugger
+    Debug(DebugArgs),
+    /// Create test skeletons
+    #[command(subcommand)]
+    Generate(GenerateCommand),
+    /// Work with snapshots saved by `assert_snapshot!`
+    #[command(subcommand)]
+    Snapshots(SnapshotsCommand),
+    /// Work with golden files compared by `golden::compare`
+    #[command(subcommand)]
+    Golden(GoldenCommand),
+    /// Print JSON Schemas for sheila's JSON output
+    #[command(subcommand)]
+    Schema(SchemaCommand),
+    /// List test runs started in the background with `--detach`
+    Status(StatusArgs),
+    /// Print the output of a test run in the background
+    Logs(LogsArgs),
+    /// Stop a test run in the background
     Stop(ControlArgs),
-    /// Pause a headless test running in the background
+    /// Pause a test run in the background
     Pause(ControlArgs),
-    /// Resume a previously paused headless test running in the background
+    /// Resume a previously paused test run in the background
     Resume(ControlArgs),
     /// Clear all caches
     #[command(name = "clear-cache")]
     ClearCache,
+    /// Inspect and prune sheila's caches
+    #[command(subcommand)]
+    Cache(CacheCommand),
+    /// Remove old reports from the output directory
+    #[command(name = "clean-reports")]
+    CleanReports(CleanReportsArgs),
+    /// Print a script enabling shell completion, e.g.
+    /// `source <(sheila completions bash)`
+    Completions(CompletionsArgs),
+}
+
+impl Commands {
+    /// Pass the global `-v` on to the commands with verbose output
+    pub fn set_verbose(&mut self, verbose: bool) {
+        match self {
+            Commands::Test(args) => args.verbose = verbose,
+            Commands::List(args) => args.verbose = verbose,
+            Commands::Report(args) => args.verbose = verbose,
+            _ => {}
+        }
+    }
 }
 
 #[derive(Parser)]
 pub struct TestArgs {
-    /// Path to test file, test file with line number, test function name, or test tag
-    pub target: Option<String>,
+    /// Paths to test files or directories, files with line numbers, test
+    /// function names, test tags (`@tag`) or glob patterns; tests any of
+    /// them select are run
+    #[arg(value_name = "TARGET", add = ArgValueCompleter::new(complete_target))]
+    pub targets: Vec<String>,
+
+    /// Another target, as the positional ones, which can be given any number
+    /// of times
+    #[arg(long = "target", value_name = "TARGET", add = ArgValueCompleter::new(complete_target))]
+    pub target_options: Vec<String>,
 
-    /// Run tests in headless mode (background) and return an ID
-    #[arg(long = "headless")]
-    pub headless: bool,
+    /// Match a test name target against whole path segments rather than any
+    /// substring, so `test_add` doesn't also run `test_add_overflow`
+    #[arg(long)]
+    pub exact: bool,
+
+    /// Only run the tests affected by files changed since a git ref
+    /// (default `HEAD`, i.e. uncommitted changes)
+    #[arg(long, value_name = "BASE", num_args = 0..=1, default_missing_value = "HEAD")]
+    pub changed: Option<String>,
+
+    /// Like `--changed`, also running the tests of packages that depend on
+    /// a changed package
+    #[arg(long)]
+    pub changed_with_deps: bool,
+
+    /// Only build and run the tests of this package; repeat for more
+    #[arg(short, long = "package", value_name = "SPEC")]
+    pub package: Vec<String>,
+
+    /// Build and run the tests of every package in the workspace, which is
+    /// the default when no `--package` is given
+    #[arg(long, conflicts_with = "package")]
+    pub workspace: bool,
+
+    /// Leave this package out of a workspace run; repeat for more
+    #[arg(long, value_name = "SPEC", conflicts_with = "package")]
+    pub exclude: Vec<String>,
 
-    /// Show debug logs from tests/test runner
-    #[arg(short, long, default_value_t = false)]
+    /// Start the run in the background and print its ID, to check on with
+    /// `sheila status`
+    #[arg(long, alias = "headless")]
+    pub detach: bool,
+
+    /// Take defaults from this `[profile.<name>]` section of sheila.toml;
+    /// also read from `SHEILA_PROFILE`
+    #[arg(long, value_name = "NAME")]
+    pub profile: Option<String>,
+
+    /// Show debug logs from tests/test runner, set by the global `-v`
+    #[arg(skip)]
     pub verbose: bool,
 
     /// Output format for test results
     #[arg(short, long, value_enum)]
     pub output: Option<OutputFormat>,
 
-    /// Run tests matching the given grep expression
+    /// How the run is printed: `pretty` for a terminal, or `ci` for plain
+    /// lines, one per test, and a JSON summary at the end
+    #[arg(long, value_enum)]
+    pub output_mode: Option<OutputMode>,
+
+    /// With `--output-mode ci`, write the JSON summary to this file instead
+    /// of stdout
+    #[arg(long, value_name = "FILE")]
+    pub summary_file: Option<PathBuf>,
+
+    /// Also render a report from this Tera template, e.g. `summary.md.tera`
+    #[arg(long, value_name = "FILE")]
+    pub template: Option<PathBuf>,
+
+    /// Export the run as OpenTelemetry spans to this OTLP/HTTP endpoint,
+    /// e.g. `http://localhost:4318`
+    #[arg(long, value_name = "URL")]
+    pub otel_endpoint: Option<String>,
+
+    /// Write each test event to this file as a line of JSON while the run is
+    /// in progress
+    #[arg(long, value_name = "FILE")]
+    pub event_log: Option<PathBuf>,
+
+    /// Push run metrics to this Prometheus pushgateway, e.g. `http://localhost:9091`
+    #[arg(long, value_name = "URL")]
+    pub pushgateway: Option<String>,
+
+    /// Only run tests whose name or one of whose tags matches this regex
     #[arg(short, long)]
     pub grep: Option<String>,
 
@@ -54,29 +188,204 @@ pub struct TestArgs {
     #[arg(long)]
     pub max_concurrent: Option<usize>,
 
+    /// Number of test executables to run concurrently. Above one, each test
+    /// is labelled with the suite it's from
+    #[arg(short, long, value_name = "N")]
+    pub jobs: Option<usize>,
+
     /// Stop on first failure
     #[arg(long)]
     pub fail_fast: bool,
 
+    /// Retry failed tests up to N times, unless the test or suite sets its own retries
+    #[arg(long, value_name = "N")]
+    pub retries: Option<u32>,
+
     /// Stream test output
     #[arg(long, default_value_t = true)]
     pub stream: bool,
 
-    /// Test timeout in seconds
+    /// Set an environment variable for the test executables, as KEY=VALUE;
+    /// repeat for more
+    #[arg(long = "env", value_name = "KEY=VALUE", value_parser = parse_env_var)]
+    pub env: Vec<(String, String)>,
+
+    /// Set the environment variables in a dotenv file, e.g. `.env.test`,
+    /// for the test executables; `--env` wins over the file
+    #[arg(long, value_name = "FILE")]
+    pub env_file: Option<PathBuf>,
+
+    /// Arguments after `--` are passed on to each test executable, e.g.
+    /// `-- --nocapture --test-threads 1`
+    #[arg(last = true, value_name = "ARGS")]
+    pub test_args: Vec<String>,
+
+    /// Timeout in seconds for each test executable, after which it is killed
     #[arg(long)]
     pub timeout: Option<u64>,
 
-    /// Include tests with specific tags
-    #[arg(long, value_delimiter = ',')]
-    pub tags: Vec<String>,
+    /// Memory limit for each test executable, in MiB
+    #[arg(long)]
+    pub max_memory: Option<u64>,
+
+    /// CPU time limit for each test executable, in seconds
+    #[arg(long)]
+    pub max_cpu_time: Option<u64>,
+
+    /// Only run tests whose tags match an expression, e.g. `(api & !slow) | smoke`
+    #[arg(long, value_name = "EXPR", add = ArgValueCompleter::new(complete_tag_expr))]
+    pub tags: Option<TagExpr>,
 
-    /// Exclude tests with specific tags
-    #[arg(long, value_delimiter = ',')]
+    /// Exclude tests with any of these tags
+    #[arg(long, value_delimiter = ',', add = ArgValueCompleter::new(complete_tag_expr))]
     pub exclude_tags: Vec<String>,
 
     /// Output directory for reports
     #[arg(long)]
     pub output_dir: Option<PathBuf>,
+
+    /// Only run one shard of the test set, e.g. `3/8` for the third of eight shards
+    #[arg(long)]
+    pub shard: Option<Shard>,
+
+    /// Only run tests that failed (or were flaky) in the previous run
+    #[arg(long)]
+    pub failed: bool,
+
+    /// Also exit with an error when tests were skipped, passed only after
+    /// retrying, or none were selected; failed tests always fail the run
+    #[arg(long, value_enum, value_delimiter = ',', value_name = "WHAT")]
+    pub fail_on: Vec<FailOn>,
+
+    /// Re-run the tests that failed in the previous run, up to N times
+    /// (default 3) until they pass, and merge the outcomes into its results.
+    /// Tests that pass on a re-run are marked flaky.
+    #[arg(
+        long,
+        value_name = "N",
+        num_args = 0..=1,
+        default_missing_value = "3",
+        value_parser = clap::value_parser!(u32).range(1..),
+        conflicts_with_all = ["failed", "cache", "repeat", "stress", "dry_run"]
+    )]
+    pub rerun_failed: Option<u32>,
+
+    /// Skip tests that passed before against an unchanged test binary and environment
+    #[arg(long)]
+    pub cache: bool,
+
+    /// Run tests on a remote host over SSH, as [user@]host[:port]; repeat to spread tests across hosts
+    #[arg(long, conflicts_with = "cache")]
+    pub remote: Vec<RemoteHost>,
+
+    /// Directory on remote hosts that test executables are copied into
+    #[arg(long, default_value = "/tmp/sheila")]
+    pub remote_dir: String,
+
+    /// Run each test executable in its own container of this image
+    #[arg(long, conflicts_with_all = ["remote", "cache"])]
+    pub container: Option<String>,
+
+    /// Container CLI used with --container (docker or podman)
+    #[arg(long, default_value = "docker")]
+    pub container_engine: String,
+
+    /// Build and list the tests that would run, without running them
+    #[arg(long)]
+    pub dry_run: bool,
+
+    /// Run the test executables of the last build as they are, without
+    /// building them again even if the code changed
+    #[arg(long, conflicts_with = "doc")]
+    pub no_build: bool,
+
+    /// Re-run the selected tests up to N times, stopping at the first failure
+    #[arg(long, value_parser = clap::value_parser!(u32).range(1..), conflicts_with_all = ["stress", "cache"])]
+    pub repeat: Option<u32>,
+
+    /// Keep re-running the selected tests for a duration (e.g. 30s, 10m), stopping at the first failure
+    #[arg(long, value_parser = parse_duration, conflicts_with = "cache")]
+    pub stress: Option<Duration>,
+
+    /// Seed for the random data tests draw, to replay a run; picked at
+    /// random (or read from SHEILA_SEED) when not given
+    #[arg(long)]
+    pub seed: Option<u64>,
+
+    /// Stop the run once it has taken this long (e.g. 30m), skipping tests that haven't finished
+    #[arg(long, value_parser = parse_duration)]
+    pub max_run_duration: Option<Duration>,
+
+    /// Output format requested from test executables (auto, json or pretty)
+    #[arg(long, default_value = "auto")]
+    pub libtest_format: LibtestFormat,
+
+    /// Also run the examples in the doc comments of the selected packages,
+    /// with `cargo test --doc`, after the tests
+    #[arg(long, conflicts_with_all = ["container", "remote"])]
+    pub doc: bool,
+
+    /// How tests are partitioned between shards (count or hash)
+    #[arg(long, default_value = "count", requires = "shard")]
+    pub shard_strategy: ShardStrategy,
+}
+
+impl TestArgs {
+    /// The targets given positionally and with `--target`
+    pub fn all_targets(&self) -> Vec<&str> {
+        self.targets
+            .iter()
+            .chain(&self.target_options)
+            .map(String::as_str)
+            .collect()
+    }
+
+    /// Whether `what` makes the run exit with an error
+    pub fn fails_on(&self, what: FailOn) -> bool {
+        what == FailOn::Failed || self.fail_on.contains(&what)
+    }
+
+    /// The variables to set for the test executables: the `--env-file`'s,
+    /// then each `--env`
+    pub fn environment(&self) -> sheila::Result<HashMap<String, String>> {
+        let mut vars = HashMap::new();
+        if let Some(ref path) = self.env_file {
+            vars.extend(read_env_file(path)?);
+        }
+        vars.extend(self.env.iter().cloned());
+        Ok(vars)
+    }
+
+    /// The packages to build test executables for
+    pub fn packages(&self) -> PackageSelection {
+        PackageSelection {
+            packages: self.package.clone(),
+            workspace: self.workspace,
+            exclude: self.exclude.clone(),
+        }
+    }
+
+    pub fn ci_mode(&self) -> bool {
+        self.output_mode == Some(OutputMode::Ci)
+    }
+
+    /// The git ref to select changed tests against, if any
+    pub fn changed_base(&self) -> Option<&str> {
+        match self.changed {
+            Some(ref base) => Some(base),
+            None if self.changed_with_deps => Some("HEAD"),
+            None => None,
+        }
+    }
+
+    /// The tag expression selecting tests, with `--exclude-tags` folded in
+    pub fn tag_filter(&self) -> Option<TagExpr> {
+        let exclude = TagExpr::any_of(self.exclude_tags.iter().cloned()).map(|tags| !tags);
+        match (self.tags.clone(), exclude) {
+            (Some(tags), Some(exclude)) => Some(tags.and(exclude)),
+            (tags, exclude) => tags.or(exclude),
+        }
+    }
 }
 
 #[derive(Parser)]
@@ -84,17 +393,44 @@ pub struct ListArgs {
     /// Path to test file or directory to list tests from
     pub path: Option<PathBuf>,
 
-    /// Show detailed information about each test
-    #[arg(short, long)]
+    /// Show detailed information about each test, set by the global `-v`
+    #[arg(skip)]
     pub verbose: bool,
 
-    /// Filter tests by tag
-    #[arg(long, value_delimiter = ',')]
-    pub tags: Vec<String>,
+    /// Only list tests whose tags match an expression, e.g. `(api & !slow) | smoke`
+    #[arg(long, value_name = "EXPR", add = ArgValueCompleter::new(complete_tag_expr))]
+    pub tags: Option<TagExpr>,
 
-    /// Output format for the list
+    /// Output format for the list: text, json, csv or junit
     #[arg(short = 'f', long, value_enum, default_value = "text")]
     pub format: OutputFormat,
+
+    /// Group the tests by tag or by crate
+    #[arg(long, value_enum)]
+    pub group_by: Option<ListGroupBy>,
+
+    /// Only print how many tests there are, per group with `--group-by`
+    #[arg(long)]
+    pub count: bool,
+
+    /// List fixtures instead of tests
+    #[arg(long)]
+    pub fixtures: bool,
+
+    /// List before/after hooks instead of tests
+    #[arg(long)]
+    pub hooks: bool,
+
+    /// List the examples in doc comments, which `cargo test --doc` runs,
+    /// instead of tests
+    #[arg(long, conflicts_with_all = ["fixtures", "hooks"])]
+    pub doc: bool,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, ValueEnum)]
+pub enum ListGroupBy {
+    Tag,
+    Crate,
 }
 
 #[derive(Parser)]
@@ -110,9 +446,291 @@ pub struct ReportArgs {
     #[arg(long)]
     pub failures_only: bool,
 
-    /// Show detailed test information
-    #[arg(short, long)]
+    /// Show detailed test information, set by the global `-v`
+    #[arg(skip)]
     pub verbose: bool,
+
+    /// Merge several JSON results into one, e.g. from sharded CI jobs
+    #[arg(long, num_args = 1.., value_name = "FILE", conflicts_with = "path")]
+    pub merge: Vec<PathBuf>,
+
+    /// Write the merged result as JSON to this path
+    #[arg(long, requires = "merge")]
+    pub out: Option<PathBuf>,
+
+    /// Show where the time went: the N slowest tests, time per suite and
+    /// the setup/test/teardown split
+    #[arg(long, value_name = "N")]
+    pub slowest: Option<usize>,
+}
+
+#[derive(Parser)]
+pub struct HistoryArgs {
+    /// Number of most recent runs to show
+    #[arg(short = 'n', long, default_value_t = 20)]
+    pub limit: usize,
+
+    /// Write an HTML trend report with charts to this path instead
+    #[arg(long)]
+    pub html: Option<PathBuf>,
+}
+
+#[derive(Parser)]
+pub struct CleanReportsArgs {
+    /// Directory to clean, instead of the default output directory
+    #[arg(long)]
+    pub output_dir: Option<PathBuf>,
+
+    /// Remove reports older than this many days
+    #[arg(long, value_name = "DAYS")]
+    pub max_age_days: Option<u64>,
+
+    /// Keep at most this many reports
+    #[arg(long, value_name = "N")]
+    pub keep: Option<usize>,
+
+    /// Keep the reports' combined size under this many MiB
+    #[arg(long, value_name = "MIB")]
+    pub max_size_mb: Option<u64>,
+
+    /// List the reports that would be removed without removing them
+    #[arg(long)]
+    pub dry_run: bool,
+}
+
+#[derive(Parser)]
+pub struct BenchArgs {
+    /// Only run benchmarks whose path contains this
+    pub filter: Option<String>,
+
+    /// Save the results as a baseline with this name
+    #[arg(long, value_name = "NAME")]
+    pub save_baseline: Option<String>,
+
+    /// Compare the results against the baseline with this name, failing on
+    /// regressions
+    #[arg(long, value_name = "NAME")]
+    pub baseline: Option<String>,
+
+    /// How much slower than the baseline, in percent, counts as a regression
+    #[arg(long, default_value_t = 5.0, value_name = "PERCENT")]
+    pub threshold: f64,
+
+    /// How long to run each benchmark before measuring, e.g. `500ms`
+    #[arg(long, value_parser = parse_duration, default_value = "1s")]
+    pub warm_up: Duration,
+
+    /// How long to measure each benchmark for, e.g. `5s`
+    #[arg(long, value_parser = parse_duration, default_value = "3s")]
+    pub measurement: Duration,
+
+    /// Number of samples the measurement time is split into
+    #[arg(long, default_value_t = 50, value_parser = clap::value_parser!(u64).range(1..))]
+    pub samples: u64,
+
+    /// Print the results as JSON
+    #[arg(long)]
+    pub json: bool,
+}
+
+#[derive(Parser)]
+pub struct DebugArgs {
+    /// Name of the test, or part of its path
+    pub test: String,
+
+    /// Match the test name against whole path segments rather than any
+    /// substring
+    #[arg(long)]
+    pub exact: bool,
+
+    /// Package to look for the test in
+    #[arg(short, long)]
+    pub package: Option<String>,
+
+    /// Launch the test under this debugger
+    #[arg(long, value_enum)]
+    pub debugger: Option<Debugger>,
+
+    /// Run the test under the debugger's server on this address and wait
+    /// for a debugger to attach
+    #[arg(
+        long,
+        value_name = "ADDR",
+        num_args = 0..=1,
+        default_missing_value = "127.0.0.1:2345"
+    )]
+    pub listen: Option<String>,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, ValueEnum)]
+pub enum Debugger {
+    Gdb,
+    Lldb,
+}
+
+#[derive(Subcommand)]
+pub enum GenerateCommand {
+    /// Add a test for a function to its file's suite, creating the suite if
+    /// there isn't one yet
+    Test(GenerateTestArgs),
+}
+
+#[derive(Parser)]
+pub struct GenerateTestArgs {
+    /// Source file holding the function under test
+    #[arg(long)]
+    pub file: PathBuf,
+
+    /// Name of the function under test
+    #[arg(long = "fn", value_name = "NAME")]
+    pub function: String,
+
+    /// Name of the suite module, instead of the file's name plus the
+    /// configured suffix
+    #[arg(long)]
+    pub suite: Option<String>,
+
+    /// Where the suite goes, instead of the `[generate]` layout in sheila.toml
+    #[arg(long, value_enum)]
+    pub layout: Option<SuiteLayout>,
+
+    /// Leave fixture and hook stubs out of a new suite
+    #[arg(long)]
+    pub no_stubs: bool,
+
+    /// Print the code that would be written instead of writing it
+    #[arg(long)]
+    pub dry_run: bool,
+}
+
+#[derive(Subcommand)]
+pub enum CacheCommand {
+    /// List cached files with their sizes and ages
+    Ls(CacheLsArgs),
+    /// Show how much each kind of cache holds
+    Stats,
+    /// Remove cached files older than a given age
+    Prune(CachePruneArgs),
+    /// Clear all caches
+    Clear,
+}
+
+#[derive(Parser)]
+pub struct CacheLsArgs {
+    /// Only list these kinds of cache
+    #[arg(long, value_enum)]
+    pub kind: Vec<CacheKind>,
+}
+
+#[derive(Parser)]
+pub struct CachePruneArgs {
+    /// Remove files last written longer ago than this, e.g. `7d` or `12h`
+    #[arg(long, value_parser = parse_duration)]
+    pub older_than: Duration,
+
+    /// Only prune these kinds of cache
+    #[arg(long, value_enum)]
+    pub kind: Vec<CacheKind>,
+
+    /// Show what would be removed without removing anything
+    #[arg(long)]
+    pub dry_run: bool,
+}
+
+/// The kinds of file sheila caches
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, ValueEnum)]
+pub enum CacheKind {
+    /// Records and logs of background runs
+    Processes,
+    /// Each project's last run, run history, timings and cached results
+    Results,
+    /// Saved benchmark baselines
+    Baselines,
+}
+
+#[derive(Subcommand)]
+pub enum SnapshotsCommand {
+    /// Go through snapshots that changed, accepting or rejecting each one
+    Review(SnapshotReviewArgs),
+}
+
+#[derive(Parser)]
+pub struct SnapshotReviewArgs {
+    /// Directory to look for snapshots under
+    #[arg(default_value = ".")]
+    pub path: PathBuf,
+
+    /// Accept every changed snapshot without asking
+    #[arg(long, conflicts_with = "reject_all")]
+    pub accept_all: bool,
+
+    /// Reject every changed snapshot without asking
+    #[arg(long)]
+    pub reject_all: bool,
+}
+
+#[derive(Subcommand)]
+pub enum GoldenCommand {
+    /// List golden files, marking those the last run's output didn't match
+    List(GoldenListArgs),
+    /// Replace golden files with the output that didn't match them
+    Update(GoldenUpdateArgs),
+}
+
+#[derive(Parser)]
+pub struct GoldenListArgs {
+    /// Directory to look for golden files under
+    #[arg(default_value = ".")]
+    pub path: PathBuf,
+
+    /// Only list golden files the output didn't match
+    #[arg(long)]
+    pub mismatched: bool,
+}
+
+#[derive(Parser)]
+pub struct GoldenUpdateArgs {
+    /// Directory to look for golden files under
+    #[arg(default_value = ".")]
+    pub path: PathBuf,
+
+    /// Only update golden files whose name (relative to their `golden`
+    /// directory) contains this; repeat for more
+    #[arg(long = "only", value_name = "NAME")]
+    pub only: Vec<String>,
+
+    /// Throw the output away instead, keeping the golden files as they are
+    #[arg(long)]
+    pub discard: bool,
+}
+
+#[derive(Subcommand)]
+pub enum SchemaCommand {
+    /// Print the schemas of run results, reports and process output
+    Dump(SchemaDumpArgs),
+}
+
+#[derive(Parser)]
+pub struct SchemaDumpArgs {
+    /// Only these types: run-result, suite-result, test-result, test-report
+    /// or process-output
+    #[arg(long = "type", value_name = "TYPE")]
+    pub types: Vec<SchemaType>,
+
+    /// Write each schema to `<TYPE>.schema.json` in this directory instead
+    /// of printing them
+    #[arg(short, long)]
+    pub output: Option<PathBuf>,
+}
+
+/// Where `sheila generate` puts a source file's suite
+#[derive(Debug, Clone, Copy, PartialEq, Eq, ValueEnum, Serialize, Deserialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum SuiteLayout {
+    /// A suite module at the end of the source file itself
+    Inline,
+    /// A file named after the source file in the package's `tests` directory
+    TestsDir,
 }
 
 #[derive(Parser)]
@@ -120,9 +738,74 @@ pub struct ControlArgs {
     pub test_id: String,
 }
 
+#[derive(Parser)]
+pub struct LogsArgs {
+    pub test_id: String,
+
+    /// Keep printing new output until the run finishes
+    #[arg(short, long)]
+    pub follow: bool,
+
+    /// Print the run's NDJSON event log instead of its console output
+    #[arg(long)]
+    pub events: bool,
+}
+
+#[derive(Parser)]
+pub struct CompletionsArgs {
+    #[arg(value_enum)]
+    pub shell: CompletionShell,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, ValueEnum)]
+pub enum CompletionShell {
+    Bash,
+    Zsh,
+    Fish,
+    Powershell,
+    Elvish,
+}
+
+#[derive(Parser)]
+pub struct StatusArgs {
+    /// Include runs that have finished
+    #[arg(short, long)]
+    pub all: bool,
+
+    /// Print the runs as JSON
+    #[arg(long)]
+    pub json: bool,
+}
+
+/// What makes `sheila test` exit with an error code
+#[derive(Debug, Clone, Copy, PartialEq, Eq, ValueEnum, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum FailOn {
+    /// A test failed or timed out
+    Failed,
+    /// A test was skipped or ignored
+    Skipped,
+    /// A test only passed after retrying
+    Flaky,
+    /// No tests were selected to run
+    Empty,
+}
+
+/// How `sheila test` prints a run
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, ValueEnum, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum OutputMode {
+    /// Colors, progress bars and a summary table
+    #[default]
+    Pretty,
+    /// Plain lines without colors or redrawing, for CI logs
+    Ci,
+}
+
 /// Ditto of `ReportFormat` from the core crate -- needed
 /// to impl `ValueEnum` and can't use tuple variants in clap
-#[derive(Debug, Clone, Copy, PartialEq, Eq, ValueEnum)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq, ValueEnum, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
 pub enum OutputFormat {
     Text,
     Json,
@@ -130,6 +813,10 @@ pub enum OutputFormat {
     Html,
     Junit,
     Tap,
+    Allure,
+    Sarif,
+    Metrics,
+    Badge,
 }
 
 impl std::fmt::Display for OutputFormat {
@@ -141,6 +828,10 @@ impl std::fmt::Display for OutputFormat {
             OutputFormat::Text => write!(f, "text"),
             OutputFormat::Junit => write!(f, "junit"),
             OutputFormat::Tap => write!(f, "tap"),
+            OutputFormat::Allure => write!(f, "allure"),
+            OutputFormat::Sarif => write!(f, "sarif"),
+            OutputFormat::Metrics => write!(f, "metrics"),
+            OutputFormat::Badge => write!(f, "badge"),
         }
     }
 }
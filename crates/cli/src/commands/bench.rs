@@ -0,0 +1,143 @@
+use colored::*;
+use sheila::bench::{BenchComparison, BenchConfig, BenchResult, run_benches};
+use sheila::cache::RunCache;
+use sheila::schemas::ExecutableBuilder;
+use tiny_gradient::Gradient;
+
+use crate::cli::BenchArgs;
+use crate::helpers::OutputFormatter;
+
+pub fn run(args: BenchArgs) -> color_eyre::Result<()> {
+    let cache = RunCache::current()?;
+    let baseline = match args.baseline {
+        Some(ref name) => Some(cache.load_bench_baseline(name)?.ok_or_else(|| {
+            sheila::Error::invalid_config(format!(
+                "No baseline named '{}'. Save one with `sheila bench --save-baseline {}`",
+                name, name
+            ))
+        })?),
+        None => None,
+    };
+
+    let pb = OutputFormatter::create_spinner(Some("Building benchmarks..."));
+    let builder = ExecutableBuilder::new(
+        None,
+        Some("bench".to_string()),
+        vec![
+            "--features".to_string(),
+            "sheila-proc-macros/__sheila_bench".to_string(),
+        ],
+    );
+    let mut executables = builder.exec()?;
+    // Proc-macro crates can't hold benchmarks, and their test executables
+    // link std dynamically, so only run under cargo
+    executables.retain(|executable| executable.target_kind.as_deref() != Some("proc-macro"));
+
+    let config = BenchConfig {
+        warm_up: args.warm_up,
+        measurement: args.measurement,
+        samples: args.samples as usize,
+    };
+    let mut results: Vec<BenchResult> = Vec::new();
+    for executable in &executables {
+        pb.set_message(format!("Benchmarking {}...", executable.package_name));
+        results.extend(run_benches(executable, args.filter.as_deref(), &config)?);
+    }
+    pb.finish_and_clear();
+
+    if results.is_empty() {
+        println!(
+            "{}",
+            OutputFormatter::format_warning("No benchmarks found. Add some with #[sheila::bench].")
+        );
+        return Ok(());
+    }
+
+    let comparisons = BenchComparison::compare(&results, baseline.as_deref().unwrap_or_default());
+    let threshold = args.threshold / 100.0;
+
+    if args.json {
+        println!("{}", serde_json::to_string_pretty(&results)?);
+    } else {
+        print_comparisons(&comparisons, threshold, baseline.is_some());
+    }
+
+    if let Some(ref name) = args.save_baseline {
+        cache.save_bench_baseline(name, &results)?;
+        if !args.json {
+            println!(
+                "{}",
+                OutputFormatter::format_success(&format!("Saved baseline '{}'", name))
+            );
+        }
+    }
+
+    let regressions = comparisons
+        .iter()
+        .filter(|comparison| comparison.regressed(threshold))
+        .count();
+    if regressions > 0 {
+        eprintln!(
+            "{}",
+            OutputFormatter::format_error(&format!(
+                "{} benchmark(s) regressed by more than {}% against baseline '{}'",
+                regressions,
+                args.threshold,
+                args.baseline.as_deref().unwrap_or_default()
+            ))
+        );
+        std::process::exit(1);
+    }
+
+    Ok(())
+}
+
+fn print_comparisons(comparisons: &[BenchComparison], threshold: f64, with_baseline: bool) {
+    println!(
+        "{}",
+        OutputFormatter::format_header("Benchmarks", Gradient::Mind)
+    );
+
+    for comparison in comparisons {
+        let stats = &comparison.stats;
+        println!("{}", comparison.name.bright_white().bold());
+        println!(
+            "  {} {} ± {}  {} {}  {} {}..{}  {}",
+            "mean".dimmed(),
+            format_nanos(stats.mean),
+            format_nanos(stats.std_dev),
+            "median".dimmed(),
+            format_nanos(stats.median),
+            "range".dimmed(),
+            format_nanos(stats.min),
+            format_nanos(stats.max),
+            format!("({} iterations)", stats.iterations).dimmed(),
+        );
+
+        if let Some(change) = comparison.change() {
+            let percent = format!("{:+.2}%", change * 100.0);
+            let percent = if change > threshold {
+                format!("{} regressed", percent).red().bold()
+            } else if change < -threshold {
+                format!("{} improved", percent).green().bold()
+            } else {
+                format!("{} no change", percent).dimmed()
+            };
+            println!("  {} {}", "change".dimmed(), percent);
+        } else if with_baseline && comparison.baseline.is_none() {
+            println!("  {}", "new, not in baseline".dimmed());
+        }
+    }
+}
+
+fn format_nanos(nanos: f64) -> String {
+    if nanos < 1_000.0 {
+        format!("{:.2} ns", nanos)
+    } else if nanos < 1_000_000.0 {
+        format!("{:.2} µs", nanos / 1_000.0)
+    } else if nanos < 1_000_000_000.0 {
+        format!("{:.2} ms", nanos / 1_000_000.0)
+    } else {
+        format!("{:.2} s", nanos / 1_000_000_000.0)
+    }
+}
@@ -1,8 +1,270 @@
+use crate::cli::{CacheCommand, CacheKind, CacheLsArgs, CachePruneArgs};
 use crate::helpers::OutputFormatter;
 use crate::helpers::get_default_output_dir;
 use crate::process::ProcessManager;
 use anyhow::Result;
+use colored::*;
+use sheila::cache::{RunCache, cache_dir};
 use std::fs;
+use std::path::{Path, PathBuf};
+use std::time::{Duration, SystemTime};
+use tiny_gradient::Gradient;
+use uuid::Uuid;
+
+pub async fn run(command: CacheCommand) -> color_eyre::Result<()> {
+    match command {
+        CacheCommand::Ls(args) => ls(args),
+        CacheCommand::Stats => stats(),
+        CacheCommand::Prune(args) => prune(args),
+        CacheCommand::Clear => clear().await,
+    }
+}
+
+/// A file in one of sheila's caches
+struct CacheEntry {
+    kind: CacheKind,
+    path: PathBuf,
+    size: u64,
+    modified: SystemTime,
+}
+
+impl CacheEntry {
+    fn age(&self) -> Duration {
+        SystemTime::now()
+            .duration_since(self.modified)
+            .unwrap_or_default()
+    }
+}
+
+fn kind_label(kind: CacheKind) -> &'static str {
+    match kind {
+        CacheKind::Processes => "processes",
+        CacheKind::Results => "results",
+        CacheKind::Baselines => "baselines",
+    }
+}
+
+/// Every cached file of the given kinds, or of all kinds when `kinds` is
+/// empty, ordered by kind and then path
+fn cache_entries(kinds: &[CacheKind]) -> color_eyre::Result<Vec<CacheEntry>> {
+    let wanted = |kind| kinds.is_empty() || kinds.contains(&kind);
+    let mut entries = Vec::new();
+
+    if wanted(CacheKind::Processes) {
+        // Only the files at the top: project caches may live below
+        let process_manager = ProcessManager::new()?;
+        for entry in fs::read_dir(process_manager.cache_dir())? {
+            let entry = entry?;
+            if entry.file_type()?.is_file() {
+                entries.push(cache_entry(CacheKind::Processes, entry.path())?);
+            }
+        }
+    }
+
+    for cache in RunCache::all()? {
+        let baselines = cache.baselines_dir();
+        for path in files_under(cache.dir())? {
+            let kind = if path.starts_with(&baselines) {
+                CacheKind::Baselines
+            } else {
+                CacheKind::Results
+            };
+            if wanted(kind) {
+                entries.push(cache_entry(kind, path)?);
+            }
+        }
+    }
+
+    entries.sort_by(|a, b| (a.kind, &a.path).cmp(&(b.kind, &b.path)));
+    Ok(entries)
+}
+
+fn cache_entry(kind: CacheKind, path: PathBuf) -> color_eyre::Result<CacheEntry> {
+    let metadata = fs::metadata(&path)?;
+    Ok(CacheEntry {
+        kind,
+        size: metadata.len(),
+        modified: metadata.modified()?,
+        path,
+    })
+}
+
+fn files_under(dir: &Path) -> color_eyre::Result<Vec<PathBuf>> {
+    let mut files = Vec::new();
+    for entry in fs::read_dir(dir)? {
+        let entry = entry?;
+        if entry.file_type()?.is_dir() {
+            files.extend(files_under(&entry.path())?);
+        } else {
+            files.push(entry.path());
+        }
+    }
+    Ok(files)
+}
+
+fn ls(args: CacheLsArgs) -> color_eyre::Result<()> {
+    let entries = cache_entries(&args.kind)?;
+    if entries.is_empty() {
+        println!("{}", OutputFormatter::format_info("The cache is empty"));
+        return Ok(());
+    }
+
+    let root = cache_dir();
+    println!(
+        "{}",
+        format!("{:<10} {:>10} {:>8}  {}", "KIND", "SIZE", "AGE", "PATH").dimmed()
+    );
+    for entry in &entries {
+        let path = entry.path.strip_prefix(&root).unwrap_or(&entry.path);
+        println!(
+            "{:<10} {:>10} {:>8}  {}",
+            kind_label(entry.kind),
+            format_size(entry.size),
+            format_age(entry.age()),
+            path.display()
+        );
+    }
+
+    Ok(())
+}
+
+fn stats() -> color_eyre::Result<()> {
+    let entries = cache_entries(&[])?;
+
+    println!(
+        "{}",
+        OutputFormatter::format_header("Cache", Gradient::Mind)
+    );
+    println!("  {}", cache_dir().display().to_string().dimmed());
+    println!(
+        "{}",
+        format!(
+            "{:<10} {:>6} {:>10} {:>8} {:>8}",
+            "KIND", "FILES", "SIZE", "OLDEST", "NEWEST"
+        )
+        .dimmed()
+    );
+
+    for kind in [
+        CacheKind::Processes,
+        CacheKind::Results,
+        CacheKind::Baselines,
+    ] {
+        let of_kind: Vec<&CacheEntry> = entries.iter().filter(|e| e.kind == kind).collect();
+        let age = |entry: Option<&&CacheEntry>| {
+            entry.map_or("-".to_string(), |entry| format_age(entry.age()))
+        };
+        println!(
+            "{:<10} {:>6} {:>10} {:>8} {:>8}",
+            kind_label(kind),
+            of_kind.len(),
+            format_size(of_kind.iter().map(|entry| entry.size).sum()),
+            age(of_kind.iter().min_by_key(|entry| entry.modified)),
+            age(of_kind.iter().max_by_key(|entry| entry.modified)),
+        );
+    }
+    println!(
+        "{}",
+        format!(
+            "{:<10} {:>6} {:>10}",
+            "total",
+            entries.len(),
+            format_size(entries.iter().map(|entry| entry.size).sum())
+        )
+        .bold()
+    );
+
+    Ok(())
+}
+
+fn prune(args: CachePruneArgs) -> color_eyre::Result<()> {
+    let process_manager = ProcessManager::new()?;
+    let mut removed = 0;
+    let mut freed = 0;
+
+    for entry in cache_entries(&args.kind)? {
+        if entry.age() <= args.older_than {
+            continue;
+        }
+        // A run that's still going keeps writing to its record and log
+        if entry.kind == CacheKind::Processes && is_live_run(&process_manager, &entry.path) {
+            continue;
+        }
+
+        if args.dry_run {
+            println!("  {} {}", "would remove".dimmed(), entry.path.display());
+        } else {
+            fs::remove_file(&entry.path)?;
+        }
+        removed += 1;
+        freed += entry.size;
+    }
+
+    if !args.dry_run {
+        for cache in RunCache::all()? {
+            remove_empty_dirs(cache.dir())?;
+        }
+    }
+
+    let message = format!(
+        "{} {} file(s), {}",
+        if args.dry_run {
+            "Would remove"
+        } else {
+            "Removed"
+        },
+        removed,
+        format_size(freed)
+    );
+    println!("{}", OutputFormatter::format_success(&message));
+
+    Ok(())
+}
+
+/// Whether `path` belongs to a background run that's still running
+fn is_live_run(process_manager: &ProcessManager, path: &Path) -> bool {
+    path.file_stem()
+        .and_then(|stem| stem.to_str())
+        .and_then(|stem| Uuid::parse_str(stem).ok())
+        .and_then(|id| process_manager.read_process_info(id))
+        .is_some_and(|process| process.is_active())
+}
+
+fn remove_empty_dirs(dir: &Path) -> color_eyre::Result<()> {
+    for entry in fs::read_dir(dir)? {
+        let entry = entry?;
+        if entry.file_type()?.is_dir() {
+            remove_empty_dirs(&entry.path())?;
+        }
+    }
+    if fs::read_dir(dir)?.next().is_none() {
+        fs::remove_dir(dir)?;
+    }
+    Ok(())
+}
+
+fn format_size(bytes: u64) -> String {
+    if bytes < 1024 {
+        format!("{} B", bytes)
+    } else if bytes < 1024 * 1024 {
+        format!("{:.1} KiB", bytes as f64 / 1024.0)
+    } else {
+        format!("{:.1} MiB", bytes as f64 / (1024.0 * 1024.0))
+    }
+}
+
+fn format_age(age: Duration) -> String {
+    let seconds = age.as_secs();
+    if seconds < 60 {
+        format!("{}s", seconds)
+    } else if seconds < 60 * 60 {
+        format!("{}m", seconds / 60)
+    } else if seconds < 60 * 60 * 24 {
+        format!("{}h", seconds / (60 * 60))
+    } else {
+        format!("{}d", seconds / (60 * 60 * 24))
+    }
+}
 
 pub async fn clear() -> color_eyre::Result<()> {
     println!("{}", OutputFormatter::format_info("Clearing all caches..."));
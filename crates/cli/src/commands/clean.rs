@@ -0,0 +1,69 @@
+use crate::cli::CleanReportsArgs;
+use crate::config::SheilaConfig;
+use crate::helpers::{OutputFormatter, ReportIndex, get_default_output_dir};
+use colored::*;
+
+pub fn run(args: CleanReportsArgs) -> color_eyre::Result<()> {
+    let output_dir = args.output_dir.unwrap_or_else(|| {
+        get_default_output_dir().unwrap_or_else(|_| std::path::PathBuf::from("."))
+    });
+
+    // Limits given on the command line replace the configured ones
+    let mut retention = SheilaConfig::load()
+        .map(|config| config.reporting.retention)
+        .unwrap_or_default();
+    if args.max_age_days.is_some() || args.keep.is_some() || args.max_size_mb.is_some() {
+        retention.max_age_days = args.max_age_days;
+        retention.max_reports = args.keep;
+        retention.max_size_mb = args.max_size_mb;
+    }
+
+    let mut index = ReportIndex::load(&output_dir)?;
+    let removed = index.prune(&retention, args.dry_run)?;
+    let left = index.reports.len() - if args.dry_run { removed.len() } else { 0 };
+
+    if removed.is_empty() {
+        println!(
+            "{}",
+            OutputFormatter::format_info("No reports over the retention limits")
+        );
+        return Ok(());
+    }
+
+    let verb = if args.dry_run {
+        "Would remove"
+    } else {
+        "Removed"
+    };
+    for report in &removed {
+        println!(
+            "  {} {} {}",
+            "-".red(),
+            report.file,
+            format!(
+                "({}, {} bytes)",
+                report.created_at.format("%Y-%m-%d %H:%M"),
+                report.size
+            )
+            .dimmed()
+        );
+    }
+
+    let freed: u64 = removed.iter().map(|report| report.size).sum();
+    println!(
+        "{}",
+        OutputFormatter::format_success(&format!(
+            "{} {} report(s), {:.1} MiB; {} left",
+            verb,
+            removed.len(),
+            freed as f64 / (1024.0 * 1024.0),
+            left,
+        ))
+    );
+
+    if !args.dry_run {
+        index.save()?;
+    }
+
+    Ok(())
+}
@@ -0,0 +1,77 @@
+use std::ffi::OsStr;
+
+use clap::ValueEnum;
+use clap_complete::engine::{CompletionCandidate, PathCompleter, ValueCompleter};
+use clap_complete::env::Shells;
+
+use crate::cli::CompletionsArgs;
+use crate::discovery::DiscoveryIndex;
+
+/// Environment variable the shell sets when asking `sheila` for completions
+pub const COMPLETE_ENV: &str = "COMPLETE";
+
+/// Print the script registering `sheila`'s completions with a shell. The
+/// script calls back into `sheila` for each completion, so tags, suites and
+/// tests are suggested from the project being worked on.
+pub fn run(args: CompletionsArgs) -> color_eyre::Result<()> {
+    let name = args
+        .shell
+        .to_possible_value()
+        .map(|value| value.get_name().to_string())
+        .unwrap_or_default();
+    let shells = Shells::builtins();
+    let shell = shells
+        .completer(&name)
+        .ok_or_else(|| sheila::Error::generic(format!("Unsupported shell: {}", name)))?;
+
+    shell.write_registration(
+        COMPLETE_ENV,
+        "sheila",
+        "sheila",
+        "sheila",
+        &mut std::io::stdout(),
+    )?;
+    Ok(())
+}
+
+/// Complete a test target: `@tag`, a file path, or a suite or test name
+pub fn complete_target(current: &OsStr) -> Vec<CompletionCandidate> {
+    let current = current.to_string_lossy();
+    let index = DiscoveryIndex::current();
+
+    if let Some(tag) = current.strip_prefix('@') {
+        return matching(&index.tags, tag)
+            .map(|tag| CompletionCandidate::new(format!("@{}", tag)))
+            .collect();
+    }
+    if current.contains('/') || current.contains('.') {
+        return PathCompleter::any().complete(OsStr::new(current.as_ref()));
+    }
+
+    matching(&index.suites, &current)
+        .chain(matching(&index.tests, &current))
+        .map(CompletionCandidate::new)
+        .collect()
+}
+
+/// Complete the tag being typed at the end of a tag expression, e.g. the
+/// `sl` of `api & !sl`
+pub fn complete_tag_expr(current: &OsStr) -> Vec<CompletionCandidate> {
+    let current = current.to_string_lossy();
+    let start = current
+        .rfind(|c: char| "&|,!() ".contains(c))
+        .map(|i| i + 1)
+        .unwrap_or(0);
+    let (expr, tag) = current.split_at(start);
+
+    matching(&DiscoveryIndex::current().tags, tag)
+        .map(|tag| CompletionCandidate::new(format!("{}{}", expr, tag)))
+        .collect()
+}
+
+fn matching<'a>(
+    names: &'a std::collections::BTreeSet<String>,
+    prefix: &'a str,
+) -> impl Iterator<Item = &'a String> {
+    names.iter().filter(move |name| name.starts_with(prefix))
+}
@@ -0,0 +1,177 @@
+use std::process::Command;
+
+use colored::*;
+use sheila::TestExecutable;
+use sheila::runners::{LocalLauncher, TestFilter, list_executable_tests, shell_quote};
+use sheila::schemas::{ExecutableBuilder, PackageSelection};
+
+use crate::cli::{DebugArgs, Debugger};
+use crate::helpers::OutputFormatter;
+
+/// How many candidates are listed when a name matches several tests
+const MAX_CANDIDATES: usize = 10;
+
+pub fn run(args: DebugArgs) -> color_eyre::Result<()> {
+    let pb = OutputFormatter::create_spinner(Some("Building tests with debug info..."));
+    // The dev profile may have turned debug info down, so ask for it
+    // explicitly
+    let builder = ExecutableBuilder::new(
+        None,
+        None,
+        vec!["--config".to_string(), "profile.dev.debug=true".to_string()],
+    )
+    .with_packages(PackageSelection {
+        packages: args.package.clone().into_iter().collect(),
+        ..Default::default()
+    });
+    let mut executables = builder.exec()?;
+    executables.retain(|executable| executable.target_kind.as_deref() != Some("proc-macro"));
+
+    pb.set_message("Looking for the test...");
+    let filter = if args.exact {
+        TestFilter::exact(&args.test)
+    } else {
+        TestFilter::new(&args.test)
+    };
+    let mut matches: Vec<(&TestExecutable, String)> = Vec::new();
+    for executable in &executables {
+        for test in list_executable_tests(&LocalLauncher, executable)? {
+            if filter.matches(&executable.name, &test) {
+                matches.push((executable, test));
+            }
+        }
+    }
+    pb.finish_and_clear();
+
+    let (executable, test) = match matches.len() {
+        0 => {
+            return Err(
+                sheila::Error::invalid_config(format!("No test matches '{}'", args.test)).into(),
+            );
+        }
+        1 => matches.remove(0),
+        count => {
+            let mut message = format!(
+                "'{}' matches {} tests, name one of them, or pass --exact:",
+                args.test, count
+            );
+            for (executable, test) in matches.iter().take(MAX_CANDIDATES) {
+                message.push_str(&format!("\n  {} ({})", test, executable.name));
+            }
+            if count > MAX_CANDIDATES {
+                message.push_str(&format!("\n  ...and {} more", count - MAX_CANDIDATES));
+            }
+            return Err(sheila::Error::invalid_config(message).into());
+        }
+    };
+
+    let binary = executable.path.display().to_string();
+    let test_args = [
+        test.clone(),
+        "--exact".to_string(),
+        "--nocapture".to_string(),
+        "--test-threads=1".to_string(),
+    ];
+    let quoted = test_args
+        .iter()
+        .map(|arg| quote(arg))
+        .collect::<Vec<_>>()
+        .join(" ");
+    let dir = executable
+        .package_root
+        .clone()
+        .unwrap_or_else(|| std::path::PathBuf::from("."));
+
+    println!("{} {}", "Test:  ".dimmed(), test.bright_white().bold());
+    println!("{} {}", "Binary:".dimmed(), binary);
+    println!("{} {}", "Args:  ".dimmed(), quoted);
+    println!("{} {}", "Dir:   ".dimmed(), dir.display());
+    println!();
+    println!(
+        "{} gdb --args {} {}",
+        "gdb: ".dimmed(),
+        quote(&binary),
+        quoted
+    );
+    println!("{} lldb -- {} {}", "lldb:".dimmed(), quote(&binary), quoted);
+
+    let debugger = args.debugger.unwrap_or(Debugger::Gdb);
+    let mut command = if let Some(ref address) = args.listen {
+        println!();
+        let attach = match debugger {
+            Debugger::Gdb => format!(
+                "gdb {} -ex {}",
+                quote(&binary),
+                quote(&format!("target remote {}", address))
+            ),
+            Debugger::Lldb => format!(
+                "lldb {} -o {}",
+                quote(&binary),
+                quote(&format!("gdb-remote {}", address))
+            ),
+        };
+        println!(
+            "{}",
+            OutputFormatter::format_info(&format!(
+                "Waiting for a debugger on {}. Attach with: {}",
+                address, attach
+            ))
+        );
+
+        match debugger {
+            Debugger::Gdb => {
+                let mut command = Command::new("gdbserver");
+                command.arg(address).arg(&executable.path);
+                command
+            }
+            Debugger::Lldb => {
+                let mut command = Command::new("lldb-server");
+                command
+                    .args(["gdbserver", address.as_str(), "--"])
+                    .arg(&executable.path);
+                command
+            }
+        }
+    } else if args.debugger.is_some() {
+        match debugger {
+            Debugger::Gdb => {
+                let mut command = Command::new("gdb");
+                command.arg("--args").arg(&executable.path);
+                command
+            }
+            Debugger::Lldb => {
+                let mut command = Command::new("lldb");
+                command.arg("--").arg(&executable.path);
+                command
+            }
+        }
+    } else {
+        return Ok(());
+    };
+
+    let program = command.get_program().to_string_lossy().into_owned();
+    let status = command
+        .args(&test_args)
+        .current_dir(&dir)
+        .status()
+        .map_err(|e| sheila::Error::generic(format!("Failed to run {}: {}", program, e)))?;
+    if !status.success() {
+        std::process::exit(status.code().unwrap_or(1));
+    }
+
+    Ok(())
+}
+
+/// Quote `arg` for a shell if it needs it, keeping the printed commands
+/// readable
+fn quote(arg: &str) -> String {
+    if !arg.is_empty()
+        && arg
+            .chars()
+            .all(|c| c.is_ascii_alphanumeric() || "-_./:=,@".contains(c))
+    {
+        arg.to_string()
+    } else {
+        shell_quote(arg)
+    }
+}
@@ -0,0 +1,300 @@
+use std::fs;
+use std::path::{Component, Path, PathBuf};
+
+use regex::Regex;
+use sheila::schemas::WorkspaceMetadata;
+
+use crate::cli::{GenerateCommand, GenerateTestArgs, SuiteLayout};
+use crate::config::SheilaConfig;
+use crate::helpers::OutputFormatter;
+
+pub fn run(command: GenerateCommand) -> color_eyre::Result<()> {
+    match command {
+        GenerateCommand::Test(args) => test(args),
+    }
+}
+
+fn test(args: GenerateTestArgs) -> color_eyre::Result<()> {
+    let config = SheilaConfig::load()
+        .map(|config| config.generate)
+        .unwrap_or_default();
+    let layout = args.layout.unwrap_or(config.layout);
+
+    if !args.file.is_file() {
+        return Err(invalid(format!("{} is not a file", args.file.display())).into());
+    }
+    let stem = args
+        .file
+        .file_stem()
+        .and_then(|stem| stem.to_str())
+        .ok_or_else(|| invalid(format!("Can't name a suite after {}", args.file.display())))?;
+    let suite = args
+        .suite
+        .clone()
+        .unwrap_or_else(|| format!("{}{}", suite_stem(&args.file, stem), config.suite_suffix));
+
+    let (target, header) = match layout {
+        SuiteLayout::Inline => (args.file.clone(), None),
+        SuiteLayout::TestsDir => tests_dir_target(&args.file, stem)?,
+    };
+    let source = if target.exists() {
+        fs::read_to_string(&target)?
+    } else {
+        String::new()
+    };
+
+    let test_name = format!("test_{}", args.function);
+    let (contents, action) = match find_suite(&source, &suite) {
+        Some(body) => {
+            let existing = Regex::new(&format!(r"\bfn\s+{}\b", regex::escape(&test_name)))?;
+            if existing.is_match(&source[body.clone()]) {
+                return Err(invalid(format!(
+                    "Suite {} in {} already has a test named {}",
+                    suite,
+                    target.display(),
+                    test_name
+                ))
+                .into());
+            }
+            let mut contents = source.clone();
+            let insert = body.end;
+            let before = contents[..insert].trim_end().len();
+            contents.replace_range(before..insert, &format!("\n\n{}", test_fn(&args.function)));
+            (contents, format!("Added {} to suite {}", test_name, suite))
+        }
+        None => {
+            let mut contents = source.clone();
+            if contents.is_empty()
+                && let Some(ref header) = header
+            {
+                contents.push_str(header);
+            }
+            if !contents.is_empty() && !contents.ends_with("\n\n") {
+                contents.push_str(if contents.ends_with('\n') {
+                    "\n"
+                } else {
+                    "\n\n"
+                });
+            }
+            contents.push_str(&new_suite(
+                &suite,
+                &args.function,
+                config.stubs && !args.no_stubs,
+            ));
+            (
+                contents,
+                format!("Created suite {} with {}", suite, test_name),
+            )
+        }
+    };
+
+    if args.dry_run {
+        print!("{}", contents);
+        return Ok(());
+    }
+
+    if let Some(parent) = target.parent() {
+        fs::create_dir_all(parent)?;
+    }
+    fs::write(&target, contents)?;
+    println!(
+        "{}",
+        OutputFormatter::format_success(&format!("{} in {}", action, target.display()))
+    );
+
+    Ok(())
+}
+
+fn invalid(message: String) -> sheila::Error {
+    sheila::Error::invalid_config(message)
+}
+
+/// What a suite for `file` is named after: the file's name, or its
+/// directory's for a `mod.rs`
+fn suite_stem<'a>(file: &'a Path, stem: &'a str) -> &'a str {
+    if stem == "mod" {
+        file.parent()
+            .and_then(|dir| dir.file_name())
+            .and_then(|name| name.to_str())
+            .unwrap_or(stem)
+    } else {
+        stem
+    }
+}
+
+/// The integration test file for `file` in its package's `tests` directory,
+/// with the header a new one starts with
+fn tests_dir_target(file: &Path, stem: &str) -> sheila::Result<(PathBuf, Option<String>)> {
+    let metadata = WorkspaceMetadata::load()?;
+    let path = file.canonicalize()?;
+    let package = metadata
+        .package_containing(&path)
+        .ok_or_else(|| invalid(format!("{} isn't in a workspace package", file.display())))?;
+
+    let module = path
+        .strip_prefix(package.root.join("src"))
+        .ok()
+        .and_then(module_path)
+        .ok_or_else(|| {
+            invalid(format!(
+                "{} isn't part of {}'s library, so a test in tests/ can't reach it",
+                file.display(),
+                package.name
+            ))
+        })?;
+
+    let crate_name = package.name.replace('-', "_");
+    let import = if module.is_empty() {
+        format!("use {}::*;", crate_name)
+    } else {
+        format!("use {}::{}::*;", crate_name, module)
+    };
+    let name = if stem == "lib" {
+        crate_name.as_str()
+    } else {
+        stem
+    };
+
+    Ok((
+        package.root.join("tests").join(format!("{}.rs", name)),
+        Some(format!("{}\n", import)),
+    ))
+}
+
+/// Module path of a library source file relative to `src`, e.g. `a::b` for
+/// `a/b.rs` or `a/b/mod.rs`, and empty for `lib.rs`
+fn module_path(relative: &Path) -> Option<String> {
+    let mut segments: Vec<&str> = relative
+        .components()
+        .map(|component| match component {
+            Component::Normal(name) => name.to_str(),
+            _ => None,
+        })
+        .collect::<Option<_>>()?;
+
+    let file = segments.pop()?.strip_suffix(".rs")?;
+    if segments.first() == Some(&"bin") || (segments.is_empty() && file == "main") {
+        return None;
+    }
+    if !(file == "mod" || segments.is_empty() && file == "lib") {
+        segments.push(file);
+    }
+    Some(segments.join("::"))
+}
+
+/// Byte range of the body of suite module `name` in `source`, from just
+/// after its opening brace to its closing brace
+fn find_suite(source: &str, name: &str) -> Option<std::ops::Range<usize>> {
+    let pattern = Regex::new(&format!(
+        r#"#\[sheila::suite(?:\([^\)]*\))?\]\s*(?:pub(?:\([^\)]*\))?\s+)?mod\s+{}\s*\{{"#,
+        regex::escape(name)
+    ))
+    .ok()?;
+    let start = pattern.find(source)?.end();
+    let end = start + closing_brace(&source[start..])?;
+    Some(start..end)
+}
+
+/// Offset of the brace closing a block whose body starts `source`, skipping
+/// braces in comments, strings and character literals
+fn closing_brace(source: &str) -> Option<usize> {
+    let bytes = source.as_bytes();
+    let mut depth = 0usize;
+    let mut i = 0;
+
+    while i < bytes.len() {
+        match bytes[i] {
+            b'/' if bytes.get(i + 1) == Some(&b'/') => {
+                while i < bytes.len() && bytes[i] != b'\n' {
+                    i += 1;
+                }
+            }
+            b'/' if bytes.get(i + 1) == Some(&b'*') => {
+                let mut nesting = 0usize;
+                while i < bytes.len() {
+                    if bytes[i..].starts_with(b"/*") {
+                        nesting += 1;
+                        i += 2;
+                    } else if bytes[i..].starts_with(b"*/") {
+                        nesting -= 1;
+                        i += 2;
+                        if nesting == 0 {
+                            break;
+                        }
+                    } else {
+                        i += 1;
+                    }
+                }
+                continue;
+            }
+            b'r' if matches!(bytes.get(i + 1), Some(b'"' | b'#'))
+                && (i == 0 || !is_ident(bytes[i - 1])) =>
+            {
+                let hashes = bytes[i + 1..].iter().take_while(|&&b| b == b'#').count();
+                if bytes.get(i + 1 + hashes) == Some(&b'"') {
+                    let close = format!("\"{}", "#".repeat(hashes));
+                    let body = i + 2 + hashes;
+                    i = source[body..]
+                        .find(&close)
+                        .map_or(bytes.len(), |end| body + end + close.len());
+                    continue;
+                }
+            }
+            b'"' => {
+                i += 1;
+                while i < bytes.len() && bytes[i] != b'"' {
+                    i += if bytes[i] == b'\\' { 2 } else { 1 };
+                }
+            }
+            // A character literal rather than a lifetime
+            b'\'' if bytes.get(i + 1) == Some(&b'\\') => {
+                i += 3;
+                while i < bytes.len() && bytes[i] != b'\'' {
+                    i += 1;
+                }
+            }
+            b'\''
+                if source[i + 1..]
+                    .chars()
+                    .next()
+                    .is_some_and(|c| source[i + 1 + c.len_utf8()..].starts_with('\'')) =>
+            {
+                i += 1 + source[i + 1..].chars().next().map_or(0, char::len_utf8);
+            }
+            b'{' => depth += 1,
+            b'}' if depth == 0 => return Some(i),
+            b'}' => depth -= 1,
+            _ => {}
+        }
+        i += 1;
+    }
+
+    None
+}
+
+fn is_ident(byte: u8) -> bool {
+    byte.is_ascii_alphanumeric() || byte == b'_'
+}
+
+fn test_fn(function: &str) -> String {
+    format!(
+        "    #[sheila::test]\n    fn test_{function}() {{\n        todo!(\"test {function}\");\n    }}\n"
+    )
+}
+
+fn new_suite(name: &str, function: &str, stubs: bool) -> String {
+    let mut suite = format!("#[sheila::suite]\nmod {} {{\n    use super::*;\n\n", name);
+    if stubs {
+        suite.push_str(concat!(
+            "    #[sheila::fixture(scope = \"test\")]\n",
+            "    fn fixture() {}\n\n",
+            "    #[sheila::before_all]\n",
+            "    fn setup() {}\n\n",
+            "    #[sheila::after_all]\n",
+            "    fn teardown() {}\n\n",
+        ));
+    }
+    suite.push_str(&test_fn(function));
+    suite.push_str("}\n");
+    suite
+}
@@ -0,0 +1,94 @@
+use colored::*;
+use sheila::golden::{GoldenFile, golden_files};
+use tiny_gradient::Gradient;
+
+use crate::cli::{GoldenCommand, GoldenListArgs, GoldenUpdateArgs};
+use crate::helpers::OutputFormatter;
+
+pub fn run(command: GoldenCommand) -> color_eyre::Result<()> {
+    match command {
+        GoldenCommand::List(args) => list(args),
+        GoldenCommand::Update(args) => update(args),
+    }
+}
+
+fn list(args: GoldenListArgs) -> color_eyre::Result<()> {
+    let files: Vec<GoldenFile> = golden_files(&args.path)?
+        .into_iter()
+        .filter(|file| !args.mismatched || file.is_mismatched())
+        .collect();
+    if files.is_empty() {
+        println!("{}", OutputFormatter::format_info("No golden files found"));
+        return Ok(());
+    }
+
+    println!(
+        "{}",
+        OutputFormatter::format_header("Golden files", Gradient::Mind)
+    );
+    for file in &files {
+        let status = match (file.exists, file.is_mismatched()) {
+            (false, _) => "new".cyan(),
+            (true, true) => "mismatched".red(),
+            (true, false) => "ok".green(),
+        };
+        println!(
+            "{} {} {}",
+            file.name.bright_white(),
+            format!("({})", status).bold(),
+            file.path.display().to_string().dimmed()
+        );
+    }
+
+    let mismatched = files.iter().filter(|file| file.is_mismatched()).count();
+    if mismatched > 0 {
+        println!();
+        println!(
+            "{}",
+            OutputFormatter::format_warning(&format!(
+                "{} golden file(s) to update with `sheila golden update`",
+                mismatched
+            ))
+        );
+    }
+
+    Ok(())
+}
+
+fn update(args: GoldenUpdateArgs) -> color_eyre::Result<()> {
+    let files: Vec<GoldenFile> = golden_files(&args.path)?
+        .into_iter()
+        .filter(|file| file.is_mismatched())
+        .filter(|file| {
+            args.only.is_empty() || args.only.iter().any(|name| file.name.contains(name))
+        })
+        .collect();
+    if files.is_empty() {
+        println!(
+            "{}",
+            OutputFormatter::format_info("No golden files need updating")
+        );
+        return Ok(());
+    }
+
+    for file in &files {
+        if args.discard {
+            file.discard()?;
+        } else {
+            file.update()?;
+        }
+        println!("  {}", file.path.display().to_string().dimmed());
+    }
+
+    let verb = if args.discard {
+        "Discarded output for"
+    } else {
+        "Updated"
+    };
+    println!(
+        "{}",
+        OutputFormatter::format_success(&format!("{} {} golden file(s)", verb, files.len()))
+    );
+
+    Ok(())
+}
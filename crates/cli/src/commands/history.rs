@@ -0,0 +1,33 @@
+use crate::cli::HistoryArgs;
+use crate::helpers::OutputFormatter;
+use sheila::Reporter;
+use sheila::cache::RunCache;
+use sheila::reporting::TrendReporter;
+
+pub fn run(args: HistoryArgs) -> color_eyre::Result<()> {
+    let cache = RunCache::current()?;
+    let history = cache.load_history(args.limit)?;
+
+    let Some(last_run) = cache.load_last_run()? else {
+        println!(
+            "{}",
+            OutputFormatter::format_info("No run history yet. Run some tests first.")
+        );
+        return Ok(());
+    };
+
+    let reporter = TrendReporter::new(history).limit(args.limit);
+
+    if let Some(path) = args.html {
+        let report = reporter.html(true).generate(&last_run)?;
+        std::fs::write(&path, &report.content)?;
+        println!(
+            "{}",
+            OutputFormatter::format_success(&format!("Trend report written to {}", path.display()))
+        );
+        return Ok(());
+    }
+
+    print!("{}", reporter.generate(&last_run)?.content);
+    Ok(())
+}
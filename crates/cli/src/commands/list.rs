@@ -1,5 +1,7 @@
-use crate::cli::ListArgs;
-use crate::discovery::TestDiscovery;
+use sheila::schemas::WorkspaceMetadata;
+
+use crate::cli::{ListArgs, ListGroupBy};
+use crate::discovery::{TestCounts, TestDiscovery, TestGroup};
 use crate::helpers::OutputFormatter;
 
 pub async fn run(args: ListArgs) -> color_eyre::Result<()> {
@@ -12,6 +14,29 @@ pub async fn run(args: ListArgs) -> color_eyre::Result<()> {
         discovery.discover_current()?
     };
 
+    if args.fixtures || args.hooks {
+        mb.clear()?;
+        let output =
+            OutputFormatter::format_setup(&test_files, args.fixtures, args.hooks, args.format)
+                .map_err(|e| sheila::Error::generic(e.to_string()))?;
+        print!("{}", output);
+        return Ok(());
+    }
+
+    if args.doc {
+        mb.clear()?;
+        let output = OutputFormatter::format_doc_tests(&test_files, args.format)
+            .map_err(|e| sheila::Error::generic(e.to_string()))?;
+        print!("{}", output);
+        return Ok(());
+    }
+
+    let mut test_files = match args.tags {
+        Some(ref tags) => discovery.filter_tests(test_files, &[], Some(tags), None)?,
+        None => test_files,
+    };
+    test_files.retain(|file| file.has_tests());
+
     if test_files.is_empty() {
         pb.finish_with_message("No test files found.");
         return Ok(());
@@ -19,8 +44,33 @@ pub async fn run(args: ListArgs) -> color_eyre::Result<()> {
 
     mb.clear()?;
 
-    let output = OutputFormatter::format_test_files(&test_files, args.format)
-        .map_err(|_| sheila::Error::generic("Failed to format test files"))?;
+    let groups = match args.group_by {
+        Some(ListGroupBy::Tag) => Some(TestGroup::by_tag(&test_files)),
+        Some(ListGroupBy::Crate) => {
+            let metadata = WorkspaceMetadata::load()?;
+            Some(TestGroup::by_crate(&test_files, |path| {
+                let path = path.canonicalize().unwrap_or_else(|_| path.to_path_buf());
+                metadata
+                    .package_containing(&path)
+                    .map(|package| package.name.clone())
+                    .unwrap_or_else(|| "unknown".to_string())
+            }))
+        }
+        None => None,
+    };
+
+    let output = if args.count {
+        OutputFormatter::format_test_counts(
+            TestCounts::of(&test_files),
+            groups.as_deref(),
+            args.format,
+        )
+    } else if let Some(ref groups) = groups {
+        OutputFormatter::format_test_groups(groups, args.format)
+    } else {
+        OutputFormatter::format_test_files(&test_files, args.format)
+    }
+    .map_err(|e| sheila::Error::generic(e.to_string()))?;
     print!("{}", output);
 
     Ok(())
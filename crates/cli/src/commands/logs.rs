@@ -0,0 +1,88 @@
+use std::fs::File;
+use std::io::{Read, Write};
+use std::time::Duration;
+
+use crate::cli::LogsArgs;
+use crate::helpers::{OutputFormatter, validate_test_id};
+use crate::process::ProcessManager;
+
+pub async fn run(args: LogsArgs) -> color_eyre::Result<()> {
+    let test_id = validate_test_id(&args.test_id)?;
+    let process_manager = ProcessManager::new()?;
+
+    let Some(process) = process_manager.read_process_info(test_id) else {
+        println!(
+            "{}",
+            OutputFormatter::format_error(&format!("No test process found with ID: {}", test_id))
+        );
+        return Ok(());
+    };
+
+    let path = if args.events {
+        process
+            .event_log
+            .clone()
+            .unwrap_or_else(|| process_manager.event_log_path(test_id))
+    } else {
+        match process.output_file {
+            Some(ref path) => path.clone(),
+            None => {
+                println!(
+                    "{}",
+                    OutputFormatter::format_warning(&format!(
+                        "Test process {} has no output file",
+                        test_id
+                    ))
+                );
+                return Ok(());
+            }
+        }
+    };
+
+    // The event log only appears once the run has started its tests
+    let mut file = loop {
+        match File::open(&path) {
+            Ok(file) => break file,
+            Err(_) if args.follow && is_active(&process_manager, test_id) => {
+                tokio::time::sleep(Duration::from_millis(250)).await;
+            }
+            Err(e) => {
+                println!(
+                    "{}",
+                    OutputFormatter::format_warning(&format!(
+                        "Failed to open {}: {}",
+                        path.display(),
+                        e
+                    ))
+                );
+                return Ok(());
+            }
+        }
+    };
+
+    let mut stdout = std::io::stdout();
+    let mut buf = Vec::new();
+    loop {
+        // Check before reading, so output written just before the run
+        // finished is still printed
+        let active = args.follow && is_active(&process_manager, test_id);
+
+        buf.clear();
+        file.read_to_end(&mut buf)?;
+        stdout.write_all(&buf)?;
+        stdout.flush()?;
+
+        if !active {
+            break;
+        }
+        tokio::time::sleep(Duration::from_millis(250)).await;
+    }
+
+    Ok(())
+}
+
+fn is_active(process_manager: &ProcessManager, id: uuid::Uuid) -> bool {
+    process_manager
+        .read_process_info(id)
+        .is_some_and(|process| process.is_alive())
+}
@@ -1,5 +1,16 @@
+pub mod bench;
 pub mod cache;
+pub mod clean;
+pub mod completions;
 pub mod control;
+pub mod debug;
+pub mod generate;
+pub mod golden;
+pub mod history;
 pub mod list;
+pub mod logs;
 pub mod report;
+pub mod schema;
+pub mod snapshots;
+pub mod status;
 pub mod test;
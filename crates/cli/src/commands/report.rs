@@ -1,15 +1,22 @@
 use crate::cli::{OutputFormat, ReportArgs};
 use crate::helpers::OutputFormatter;
 use crate::helpers::{format_duration, get_default_output_dir, get_most_recent_report};
+use serde::Deserialize;
+use sheila::reporting::{Environment, ReportMetadata, TimingAnalysis};
 use sheila::runners::RunResult;
 use sheila::{ReportFormat, TestReport};
 use std::fs;
 use std::path::{Path, PathBuf};
+use std::sync::Arc;
 use tiny_gradient::{Gradient, GradientStr};
 
 use colored::*;
 
 pub async fn run(mut args: ReportArgs) -> color_eyre::Result<()> {
+    if !args.merge.is_empty() {
+        return merge_reports(&args).await;
+    }
+
     let report_path = if let Some(path) = args.path.take() {
         path
     } else {
@@ -61,6 +68,45 @@ pub async fn run(mut args: ReportArgs) -> color_eyre::Result<()> {
     }
 }
 
+/// Merge the JSON results given with `--merge`, display the merged result
+/// and optionally write it out.
+async fn merge_reports(args: &ReportArgs) -> color_eyre::Result<()> {
+    let mut results = Vec::new();
+    for path in args.merge.iter() {
+        let content = fs::read_to_string(path).map_err(|_| {
+            sheila::Error::generic(format!("Failed to read report file: {}", path.display()))
+        })?;
+
+        let result = if let Ok(test_report) = serde_json::from_str::<TestReport>(&content) {
+            Arc::unwrap_or_clone(test_report.run_result)
+        } else {
+            serde_json::from_str::<RunResult>(&content).map_err(|_| {
+                sheila::Error::generic(format!(
+                    "{} is not a JSON report or run result",
+                    path.display()
+                ))
+            })?
+        };
+        results.push(result);
+    }
+
+    let merged = RunResult::merge(results)?;
+    println!(
+        "{}",
+        OutputFormatter::format_info(&format!("Merged {} result(s)", args.merge.len()))
+    );
+
+    if let Some(ref out) = args.out {
+        fs::write(out, serde_json::to_string_pretty(&merged)?)?;
+        println!(
+            "{}",
+            OutputFormatter::format_success(&format!("Merged result written to {}", out.display()))
+        );
+    }
+
+    display_run_result(&merged, args).await
+}
+
 fn detect_file_format(path: &Path) -> color_eyre::Result<ReportFormat> {
     match path.extension().and_then(|ext| ext.to_str()) {
         Some("json") => Ok(ReportFormat::Json),
@@ -71,9 +117,22 @@ fn detect_file_format(path: &Path) -> color_eyre::Result<ReportFormat> {
     }
 }
 
+/// A JSON report: the run results, with the report metadata alongside them
+#[derive(Deserialize)]
+struct JsonReport {
+    #[serde(default)]
+    metadata: Option<ReportMetadata>,
+    #[serde(flatten)]
+    run_result: RunResult,
+}
+
 async fn display_json_report(content: &str, args: &ReportArgs) -> color_eyre::Result<()> {
     if let Ok(test_report) = serde_json::from_str::<TestReport>(content) {
+        display_environment(test_report.metadata.environment.as_ref());
         display_run_result(&test_report.run_result, args).await
+    } else if let Ok(report) = serde_json::from_str::<JsonReport>(content) {
+        display_environment(report.metadata.and_then(|m| m.environment).as_ref());
+        display_run_result(&report.run_result, args).await
     } else if let Ok(run_result) = serde_json::from_str::<RunResult>(content) {
         display_run_result(&run_result, args).await
     } else {
@@ -99,6 +158,15 @@ async fn display_json_report(content: &str, args: &ReportArgs) -> color_eyre::Re
     }
 }
 
+fn display_environment(environment: Option<&Environment>) {
+    let Some(environment) = environment else {
+        return;
+    };
+    for (label, value) in environment.entries() {
+        println!("  {} {}", format!("{}:", label).dimmed(), value);
+    }
+}
+
 async fn display_csv_report(content: &str, args: &ReportArgs) -> color_eyre::Result<()> {
     match args.format.unwrap_or(OutputFormat::Text) {
         OutputFormat::Text => display_csv_as_table(content, args),
@@ -213,6 +281,10 @@ async fn display_run_result(run_result: &RunResult, args: &ReportArgs) -> color_
         "total".dimmed().bold()
     );
 
+    if let Some(count) = args.slowest {
+        display_timing(&TimingAnalysis::new(run_result, count));
+    }
+
     if args.verbose || args.failures_only {
         println!("\n{}", "Detailed Results:".bright_white());
 
@@ -227,29 +299,36 @@ async fn display_run_result(run_result: &RunResult, args: &ReportArgs) -> color_
                 );
 
                 for test_result in &suite_result.test_results {
-                    let should_show_test =
-                        !args.failures_only || test_result.status == sheila::TestStatus::Failed;
+                    let should_show_test = !args.failures_only || test_result.failed();
 
                     if should_show_test {
                         let status_icon = match test_result.status {
                             sheila::TestStatus::Passed => "✓".green(),
+                            sheila::TestStatus::Cached => "✓".cyan(),
                             sheila::TestStatus::Failed => "✗".red(),
                             sheila::TestStatus::Ignored => "○".yellow(),
+                            sheila::TestStatus::XFail => "x".magenta(),
+                            sheila::TestStatus::XPass => "✗".red(),
+                            sheila::TestStatus::Quarantined => "!".yellow(),
                             _ => "?".dimmed(),
                         };
 
                         println!("  {} {}", status_icon, test_result.name);
 
+                        if let Some(ref reason) = test_result.skip_reason {
+                            println!("    {}", format!("Reason: {}", reason).dimmed());
+                        }
+
                         if args.verbose {
                             if let Some(duration) = test_result.duration {
                                 println!("    Duration: {}", format_duration(duration).dimmed());
                             }
                         }
 
-                        if test_result.status == sheila::TestStatus::Failed {
-                            if let Some(error) = &test_result.error {
-                                println!("    {}", format!("Error: {}", error).red());
-                            }
+                        if test_result.failed()
+                            && let Some(error) = &test_result.error
+                        {
+                            println!("    {}", format!("Error: {}", error).red());
                         }
                     }
                 }
@@ -270,8 +349,51 @@ async fn display_run_result(run_result: &RunResult, args: &ReportArgs) -> color_
     Ok(())
 }
 
+fn display_timing(analysis: &TimingAnalysis) {
+    println!(
+        "\n{}",
+        format!("Slowest {} test(s):", analysis.slowest.len()).bright_white()
+    );
+    for timing in &analysis.slowest {
+        println!(
+            "  {}  {}{}",
+            format!("{:>9}", format_duration(timing.duration)).yellow(),
+            format!("{}::", timing.suite).dimmed(),
+            timing.test
+        );
+    }
+
+    if !analysis.suites.is_empty() {
+        println!("\n{}", "Time per suite:".bright_white());
+        for suite in &analysis.suites {
+            println!(
+                "  {}  {:>5.1}%  {} {}",
+                format!("{:>9}", format_duration(suite.duration)).yellow(),
+                suite.share * 100.0,
+                suite.name,
+                format!("({} tests)", suite.tests).dimmed()
+            );
+        }
+    }
+
+    if let Some(phases) = analysis.phases {
+        println!("\n{}", "Setup / test / teardown:".bright_white());
+        for (label, duration) in [
+            ("setup", phases.setup),
+            ("test", phases.test),
+            ("teardown", phases.teardown),
+        ] {
+            println!(
+                "  {}  {}",
+                format!("{:>9}", format_duration(duration)).yellow(),
+                label
+            );
+        }
+    }
+}
+
 fn display_csv_as_table(content: &str, args: &ReportArgs) -> color_eyre::Result<()> {
-    let mut reader = csv::Reader::from_reader(content.as_bytes());
+    let mut reader = csv_reader(content);
     let headers = reader.headers()?.clone();
 
     println!(
@@ -305,8 +427,15 @@ fn display_csv_as_table(content: &str, args: &ReportArgs) -> color_eyre::Result<
     Ok(())
 }
 
+/// CSV reports start with the run's environment as `#` comments
+fn csv_reader(content: &str) -> csv::Reader<&[u8]> {
+    csv::ReaderBuilder::new()
+        .comment(Some(b'#'))
+        .from_reader(content.as_bytes())
+}
+
 fn csv_to_json(content: &str) -> color_eyre::Result<serde_json::Value> {
-    let mut reader = csv::Reader::from_reader(content.as_bytes());
+    let mut reader = csv_reader(content);
     let headers = reader.headers()?.clone();
     let mut records = Vec::new();
 
@@ -328,7 +457,7 @@ fn csv_to_json(content: &str) -> color_eyre::Result<serde_json::Value> {
 }
 
 fn csv_to_html(content: &str) -> color_eyre::Result<String> {
-    let mut reader = csv::Reader::from_reader(content.as_bytes());
+    let mut reader = csv_reader(content);
     let headers = reader.headers()?.clone();
     let mut html = String::from("<table border=\"1\">\n<thead>\n<tr>\n");
 
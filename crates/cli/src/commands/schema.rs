@@ -0,0 +1,49 @@
+use std::fs;
+
+use serde_json::{Map, Value};
+use sheila::schema::SchemaType;
+
+use crate::cli::{SchemaCommand, SchemaDumpArgs};
+use crate::helpers::OutputFormatter;
+
+pub fn run(command: SchemaCommand) -> color_eyre::Result<()> {
+    match command {
+        SchemaCommand::Dump(args) => dump(args),
+    }
+}
+
+/// Print the schemas asked for, or all of them. A single schema is printed
+/// as is, several as an object keyed by type.
+fn dump(args: SchemaDumpArgs) -> color_eyre::Result<()> {
+    let types = if args.types.is_empty() {
+        SchemaType::ALL.to_vec()
+    } else {
+        args.types
+    };
+
+    if let Some(dir) = args.output {
+        fs::create_dir_all(&dir)?;
+        for schema_type in types {
+            let path = dir.join(format!("{}.schema.json", schema_type));
+            fs::write(&path, serde_json::to_string_pretty(&schema_type.schema()?)?)?;
+            println!(
+                "{}",
+                OutputFormatter::format_success(&format!("Wrote {}", path.display()))
+            );
+        }
+        return Ok(());
+    }
+
+    let output = match types.as_slice() {
+        [schema_type] => schema_type.schema()?,
+        types => {
+            let mut schemas = Map::new();
+            for schema_type in types {
+                schemas.insert(schema_type.to_string(), schema_type.schema()?);
+            }
+            Value::Object(schemas)
+        }
+    };
+    println!("{}", serde_json::to_string_pretty(&output)?);
+    Ok(())
+}
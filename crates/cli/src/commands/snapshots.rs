@@ -0,0 +1,120 @@
+use std::io::{BufRead, Write};
+
+use colored::*;
+use sheila::snapshot::{PendingSnapshot, pending_snapshots};
+use tiny_gradient::Gradient;
+
+use crate::cli::{SnapshotReviewArgs, SnapshotsCommand};
+use crate::helpers::OutputFormatter;
+
+pub fn run(command: SnapshotsCommand) -> color_eyre::Result<()> {
+    match command {
+        SnapshotsCommand::Review(args) => review(args),
+    }
+}
+
+enum Decision {
+    Accept,
+    Reject,
+    Skip,
+    Quit,
+}
+
+fn review(args: SnapshotReviewArgs) -> color_eyre::Result<()> {
+    let pending = pending_snapshots(&args.path)?;
+    if pending.is_empty() {
+        println!(
+            "{}",
+            OutputFormatter::format_info("No snapshots waiting for review")
+        );
+        return Ok(());
+    }
+
+    let (mut accepted, mut rejected) = (0, 0);
+    if args.accept_all || args.reject_all {
+        for snapshot in &pending {
+            if args.accept_all {
+                snapshot.accept()?;
+                accepted += 1;
+            } else {
+                snapshot.reject()?;
+                rejected += 1;
+            }
+        }
+    } else {
+        println!(
+            "{}",
+            OutputFormatter::format_header("Snapshot review", Gradient::Mind)
+        );
+
+        let stdin = std::io::stdin();
+        let mut lines = stdin.lock().lines();
+        for (index, snapshot) in pending.iter().enumerate() {
+            print_snapshot(snapshot, index + 1, pending.len());
+
+            let decision = loop {
+                print!(
+                    "{} ",
+                    "[a]ccept  [r]eject  [s]kip  [q]uit:".bright_white().bold()
+                );
+                std::io::stdout().flush()?;
+                let Some(line) = lines.next().transpose()? else {
+                    break Decision::Quit;
+                };
+                match line.trim() {
+                    "a" | "accept" => break Decision::Accept,
+                    "r" | "reject" => break Decision::Reject,
+                    "s" | "skip" | "" => break Decision::Skip,
+                    "q" | "quit" => break Decision::Quit,
+                    _ => continue,
+                }
+            };
+
+            match decision {
+                Decision::Accept => {
+                    snapshot.accept()?;
+                    accepted += 1;
+                }
+                Decision::Reject => {
+                    snapshot.reject()?;
+                    rejected += 1;
+                }
+                Decision::Skip => {}
+                Decision::Quit => break,
+            }
+            println!();
+        }
+    }
+
+    let skipped = pending.len() - accepted - rejected;
+    println!(
+        "{}",
+        OutputFormatter::format_success(&format!(
+            "Accepted {}, rejected {}, left {} for later",
+            accepted, rejected, skipped
+        ))
+    );
+
+    Ok(())
+}
+
+fn print_snapshot(snapshot: &PendingSnapshot, position: usize, total: usize) {
+    println!(
+        "{} {}",
+        snapshot.name.bright_white().bold(),
+        format!("({}/{})", position, total).dimmed()
+    );
+    println!("  {}", snapshot.path.display().to_string().dimmed());
+
+    if snapshot.old.is_none() {
+        println!("  {}", "new snapshot".cyan());
+    }
+    for line in snapshot.diff().lines() {
+        let line = match line.chars().next() {
+            Some('-') => line.red(),
+            Some('+') => line.green(),
+            _ => line.dimmed(),
+        };
+        println!("  {}", line);
+    }
+}
@@ -0,0 +1,79 @@
+use crate::cli::StatusArgs;
+use crate::control::{self, RunProgress};
+use crate::helpers::OutputFormatter;
+use crate::process::{ProcessManager, ProcessStatus};
+use colored::*;
+use tiny_gradient::Gradient;
+
+pub async fn run(args: StatusArgs) -> color_eyre::Result<()> {
+    let process_manager = ProcessManager::new()?;
+    process_manager.load_from_cache().await?;
+    process_manager.refresh().await?;
+
+    let mut processes = process_manager.list_processes().await;
+    if !args.all {
+        processes.retain(|process| process.is_active());
+    }
+
+    if args.json {
+        println!("{}", serde_json::to_string_pretty(&processes)?);
+        return Ok(());
+    }
+
+    if processes.is_empty() {
+        let message = if args.all {
+            "No background runs. Start one with `sheila test --detach`."
+        } else {
+            "No background runs in progress. Use --all to include finished ones."
+        };
+        println!("{}", OutputFormatter::format_info(message));
+        return Ok(());
+    }
+
+    println!(
+        "{}",
+        OutputFormatter::format_header("Background Runs", Gradient::Mind)
+    );
+
+    for process in &processes {
+        let status = process.status.to_string();
+        let status = match process.status {
+            ProcessStatus::Running => status.green(),
+            ProcessStatus::Paused => status.yellow(),
+            ProcessStatus::Completed { exit_code: 0 } => status.blue(),
+            ProcessStatus::Completed { .. } | ProcessStatus::Failed { .. } => status.red(),
+            ProcessStatus::Stopped => status.dimmed(),
+        };
+
+        println!("{} {}", "●".cyan().bold(), process.id.to_string().bold());
+        println!("  {:<9} {}", "State:", status);
+        println!(
+            "  {:<9} {}",
+            "Uptime:",
+            OutputFormatter::format_duration(process.uptime())
+        );
+        if process.is_active()
+            && let Ok(progress) = control::call::<RunProgress>(
+                &process_manager.control_endpoint(process.id),
+                "progress",
+            )
+            .await
+        {
+            let mut line = format!("{}/{} tests", progress.finished, progress.total);
+            if progress.failed > 0 {
+                line.push_str(&format!(", {} failed", progress.failed).red().to_string());
+            }
+            if let Some(test) = progress.running.first() {
+                line.push_str(&format!(", running {}", test).dimmed().to_string());
+            }
+            println!("  {:<9} {}", "Progress:", line);
+        }
+        println!("  {:<9} {}", "Command:", process.command_line().dimmed());
+        if let Some(ref output_file) = process.output_file {
+            println!("  {:<9} {}", "Output:", output_file.display());
+        }
+        println!();
+    }
+
+    Ok(())
+}
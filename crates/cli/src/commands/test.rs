@@ -1,66 +1,169 @@
-use crate::cli::TestArgs;
+use crate::cli::{FailOn, TestArgs};
 use crate::config::SheilaConfig;
-use crate::discovery::{TestDiscovery, TestFile};
-use crate::helpers::{OutputFormatter, TargetSpec, parse_target};
-use crate::helpers::{display_test_results, generate_report};
-use colored::*;
-use indicatif::ProgressBar;
-use sheila::ProcessOutput;
-use sheila::runners::{CargoRunnerConfig, CargoTestRunner, format_err_context};
-use sheila::schemas::ExecutableBuilder;
-use sheila::{Error, RunnerConfig};
+use crate::control::ControlServer;
+use crate::discovery::{TestDiscovery, TestFile, TestFunction};
+use crate::helpers::{CiReporter, OutputFormatter, ProgressReporter, TargetSpec, parse_target};
+use crate::helpers::{display_ci_results, display_dry_run, display_test_results, generate_report};
+use crate::process::{ProcessManager, ProcessStatus, current_process_id};
+use indicatif::{ProgressBar, ProgressDrawTarget};
+use sheila::cache::RunCache;
+use sheila::reporting::{
+    EventLogReporter, LiveReporter, LiveReporters, MetricsReporter, OtelExporter,
+};
+use sheila::runners::{
+    CargoRunnerConfig, CargoTestRunner, ChangeImpact, ContainerConfig, ContainerRunner,
+    DocTestRunner, RemoteConfig, RemoteRunner, RepeatMode, RunResult, TestFilter, changed_files,
+    format_mod_name,
+};
+use sheila::schemas::{ExecutableBuilder, TestBuild, WorkspaceMetadata};
+use sheila::{CancelReason, CancellationToken, Error, RunnerConfig};
+use sheila::{EventSender, TestExecutable};
+use signal_hook::consts::{SIGINT, SIGTERM};
+use signal_hook::iterator::Signals;
+use std::collections::{HashMap, HashSet};
 use std::path::Path;
-use std::sync::mpsc::{self, TryRecvError};
-use std::time::Instant;
+use std::sync::mpsc::{self, RecvTimeoutError};
+use std::time::{Duration, Instant};
+use tiny_gradient::Gradient;
 
-pub fn run(args: TestArgs) -> color_eyre::Result<()> {
+pub fn run(mut args: TestArgs) -> color_eyre::Result<()> {
     println!();
 
-    if args.headless {
+    let config = SheilaConfig::load()
+        .map_err(|e| Error::generic(format!("Failed to load sheila.toml: {}", e)))?;
+    if let Some(profile) = args
+        .profile
+        .clone()
+        .or_else(|| std::env::var("SHEILA_PROFILE").ok())
+    {
+        config.profile(&profile)?.apply(&mut args)?;
+        if args.ci_mode() {
+            colored::control::set_override(false);
+        }
         println!(
             "{}",
-            OutputFormatter::format_warning("Headless mode not implemented")
+            OutputFormatter::format_info(&format!("Using profile '{}'", profile))
         );
-        return Ok(());
+    }
+
+    if args.ci_mode() {
+        colored::control::set_override(false);
+    }
+
+    // Background runs always keep an event log, for `sheila logs --events`
+    if args.event_log.is_none()
+        && let Some(id) = current_process_id()
+    {
+        args.event_log = Some(ProcessManager::new()?.event_log_path(id));
     }
 
     let (mb, pb) = OutputFormatter::create_multi_progress("", None, true);
+    if args.ci_mode() {
+        mb.set_draw_target(ProgressDrawTarget::hidden());
+    }
     let (args, filtered_files, total_tests) = run_discovery(args, &pb)?;
 
     pb.finish();
     mb.clear()?;
     mb.remove(&pb);
 
-    let run_tests_pb =
-        OutputFormatter::create_progress_bar("Running...", Some((total_tests + 1) as u64));
+    let run_tests_pb = OutputFormatter::create_run_progress_bar("Running...");
     run_tests_pb.set_prefix(format!("[0/{}]", total_tests));
+    if args.ci_mode() {
+        run_tests_pb.set_draw_target(ProgressDrawTarget::hidden());
+    }
 
     run_tests(args, filtered_files, &run_tests_pb, total_tests)?;
 
     Ok(())
 }
 
+/// Start this same run in the background under the process manager and
+/// print its ID
+pub async fn detach(args: &TestArgs) -> color_eyre::Result<()> {
+    let command = std::env::current_exe()?.to_string_lossy().into_owned();
+    let process_args: Vec<String> = std::env::args()
+        .skip(1)
+        .filter(|arg| arg != "--detach" && arg != "--headless")
+        .collect();
+
+    let process_manager = ProcessManager::new()?;
+    let id = process_manager
+        .start_process(command, process_args, None, args.event_log.clone())
+        .await?;
+
+    println!(
+        "{}",
+        OutputFormatter::format_success(&format!("Started background run {}", id))
+    );
+    println!(
+        "{}",
+        OutputFormatter::format_info(&format!(
+            "Follow it with `sheila logs {} --follow`, or stop it with `sheila stop {}`",
+            id, id
+        ))
+    );
+    Ok(())
+}
+
+/// Record how this run finished, if it was started with `--detach`. Its
+/// output goes to the log `sheila logs` shows, so an error is written there
+/// on one plain line, with its causes, rather than as a colored report.
+pub async fn finish_detached(result: color_eyre::Result<()>) -> color_eyre::Result<()> {
+    let Some(id) = current_process_id() else {
+        return result;
+    };
+
+    let status = match result {
+        Ok(()) => ProcessStatus::Completed { exit_code: 0 },
+        Err(ref e) => ProcessStatus::Failed {
+            error: e.to_string(),
+        },
+    };
+    if let Ok(process_manager) = ProcessManager::new() {
+        let _ = process_manager.record_exit(id, status).await;
+    }
+
+    if let Err(e) = result {
+        eprintln!("Error: {:#}", e);
+        std::process::exit(1);
+    }
+    Ok(())
+}
+
 fn run_discovery(
     args: TestArgs,
     pb: &ProgressBar,
 ) -> color_eyre::Result<(TestArgs, Vec<TestFile>, usize)> {
-    let _config = SheilaConfig::load().map_err(|_| Error::generic("Failed to load config"))?;
     let discovery = TestDiscovery::new()?;
-    let test_files = if let Some(target) = &args.target {
-        let target_spec = parse_target(target);
-        match target_spec {
-            TargetSpec::File(file) => discovery.discover(Path::new(&file))?,
-            TargetSpec::FileLine { file, .. } => discovery.discover(Path::new(&file))?,
-            _ => discovery.discover_current()?,
+    let targets = args.all_targets();
+
+    // Only the given files and directories need searching, unless a target
+    // may select tests anywhere
+    let paths: Option<Vec<String>> = targets
+        .iter()
+        .map(|target| match parse_target(target) {
+            TargetSpec::File(file) | TargetSpec::FileLine { file, .. } => Some(file),
+            _ => None,
+        })
+        .collect();
+    let test_files = match paths {
+        Some(paths) if !paths.is_empty() => {
+            let mut files = Vec::new();
+            for path in paths {
+                files.extend(discovery.discover(Path::new(&path))?);
+            }
+            files.sort_by(|a, b| a.path.cmp(&b.path));
+            files.dedup_by(|a, b| a.path == b.path);
+            files
         }
-    } else {
-        discovery.discover_current()?
+        _ => discovery.discover_current()?,
     };
 
     let filtered_files = discovery.filter_tests(
         test_files,
-        args.target.as_deref(),
-        &args.tags,
+        &targets,
+        args.tag_filter().as_ref(),
         args.grep.as_deref(),
     )?;
 
@@ -72,12 +175,46 @@ fn run_discovery(
     let total_tests: usize = filtered_files
         .iter()
         .flat_map(|f| &f.suites)
-        .map(|s| s.tests.len())
+        .flat_map(|s| &s.tests)
+        .filter(|test| test.is_applicable())
+        .map(TestFunction::case_count)
         .sum();
 
     Ok((args, filtered_files, total_tests))
 }
 
+/// The filters narrowing the test executables down to the targeted tests, or
+/// `None` to run everything. Test name targets are passed through as given,
+/// while file, line, glob, case and tag targets select the tests discovery
+/// found. So do `--tags` and `--grep`, which only discovery can tell, and
+/// which name targets then can't bypass.
+fn test_filters(args: &TestArgs, files: &[TestFile]) -> Option<Vec<TestFilter>> {
+    let narrowed = args.tag_filter().is_some() || args.grep.is_some();
+    let targets = args.all_targets();
+    if targets.is_empty() {
+        return narrowed.then(|| files.iter().flat_map(TestFile::test_filters).collect());
+    }
+
+    let mut filters = Vec::new();
+    let mut from_discovery = narrowed;
+    for target in targets {
+        match parse_target(target) {
+            TargetSpec::Function(name) if !narrowed => filters.push(TestFilter {
+                pattern: name,
+                exact: args.exact,
+            }),
+            _ => from_discovery = true,
+        }
+    }
+    if from_discovery {
+        filters.extend(files.iter().flat_map(TestFile::test_filters));
+    }
+    Some(filters)
+}
+
+type ExecuteFn =
+    dyn FnMut(&[TestExecutable], &CancellationToken) -> sheila::Result<RunResult> + Send;
+
 fn run_tests(
     args: TestArgs,
     filtered_files: Vec<TestFile>,
@@ -86,139 +223,621 @@ fn run_tests(
 ) -> color_eyre::Result<()> {
     let mut runner_config = RunnerConfig::default();
     runner_config.fail_fast = args.fail_fast;
+    runner_config.retries = args.retries.unwrap_or_default();
 
     if let Some(ref grep) = args.grep {
         runner_config.include_patterns.push(grep.clone());
     }
 
-    runner_config.include_tags = args.tags.clone();
+    runner_config.tag_filter = args.tag_filter();
+    runner_config.shard = args
+        .shard
+        .map(|shard| shard.with_strategy(args.shard_strategy));
+
+    runner_config.only_failed = args.failed;
+    runner_config.dry_run = args.dry_run;
+    runner_config.repeat = match (args.repeat, args.stress) {
+        (Some(times), _) => Some(RepeatMode::Times(times)),
+        (_, Some(budget)) => Some(RepeatMode::For(budget)),
+        _ => None,
+    };
+    runner_config.max_run_duration = args.max_run_duration;
+    runner_config.seed = Some(args.seed.unwrap_or_else(sheila::random::run_seed));
+    runner_config.env = args.environment()?;
+
+    if args.failed {
+        match RunCache::current()?.last_failed()? {
+            Some(failed) if failed.is_empty() => {
+                pb.finish_and_clear();
+                println!(
+                    "{}",
+                    OutputFormatter::format_success("No failed tests in the previous run")
+                );
+                return Ok(());
+            }
+            Some(failed) => {
+                notice(
+                    pb,
+                    OutputFormatter::format_info(&format!(
+                        "Re-running {} test(s) that failed in the previous run",
+                        failed.len()
+                    )),
+                );
+            }
+            None => {
+                notice(
+                    pb,
+                    OutputFormatter::format_warning("No previous run found, running all tests"),
+                );
+            }
+        }
+    }
 
-    let (output_tx, output_rx) = mpsc::channel();
-    let mut cargo_config = CargoRunnerConfig::default();
+    let previous_run = match args.rerun_failed {
+        Some(_) => match RunCache::current()?.load_last_run()? {
+            Some(previous) if previous.failures().is_empty() => {
+                pb.finish_and_clear();
+                println!(
+                    "{}",
+                    OutputFormatter::format_success("No failed tests in the previous run")
+                );
+                return Ok(());
+            }
+            Some(previous) => Some(previous),
+            None => {
+                pb.finish_and_clear();
+                println!(
+                    "{}",
+                    OutputFormatter::format_warning("No previous run to re-run failed tests from")
+                );
+                return Ok(());
+            }
+        },
+        None => None,
+    };
+
+    if let Some(repeat) = runner_config.repeat {
+        notice(
+            pb,
+            OutputFormatter::format_info(&format!("Repeating tests {} or until one fails", repeat)),
+        );
+    }
+
+    if let Some(ref shard) = runner_config.shard {
+        notice(
+            pb,
+            OutputFormatter::format_info(&format!(
+                "Running shard {} ({} strategy)",
+                shard, shard.strategy
+            )),
+        );
+    }
+
+    let mut cargo_config = CargoRunnerConfig {
+        result_cache: args.cache,
+        format: args.libtest_format,
+        test_args: args.test_args.clone(),
+        ..Default::default()
+    };
+    cargo_config.check_test_args()?;
+    if let Some(jobs) = args.jobs {
+        cargo_config.jobs = jobs;
+    }
+    cargo_config.limits.memory = args.max_memory.map(|mib| mib * 1024 * 1024);
+    cargo_config.limits.cpu_time = args.max_cpu_time.map(Duration::from_secs);
+
+    match test_filters(&args, &filtered_files) {
+        Some(filters) if filters.is_empty() => {
+            pb.finish_and_clear();
+            println!(
+                "{}",
+                OutputFormatter::format_warning("No tests match the given target")
+            );
+            return empty_selection(&args);
+        }
+        Some(filters) => cargo_config.filters = filters,
+        None => {}
+    }
 
     if let Some(timeout) = args.timeout {
-        cargo_config
-            .test_args
-            .push(format!("--timeout={}", timeout));
+        cargo_config.executable_timeout = Some(Duration::from_secs(timeout));
     }
 
-    let mut cargo_runner = CargoTestRunner::new_with_output(runner_config.clone(), output_tx)
-        .with_cargo_config(cargo_config);
+    let cancel = CancellationToken::new();
+    handle_interrupts(&cancel)?;
+
+    // Background runs take pause, resume and stop requests over a control
+    // channel
+    let control = match current_process_id() {
+        Some(id) => Some(ControlServer::start(
+            &ProcessManager::new()?.control_endpoint(id),
+            id,
+            &cancel,
+        )?),
+        None => None,
+    };
 
-    let builder = ExecutableBuilder::new(None, None, vec![]);
+    let builder = ExecutableBuilder::new(None, None, vec![]).with_packages(args.packages());
 
-    let target_executables = builder.exec()?;
+    let build = if args.no_build {
+        builder.last_build()?
+    } else {
+        builder.build()?
+    };
+    report_build(&build, pb, args.verbose)?;
+    let mut target_executables = build.into_executables()?;
+    tracing::debug!(
+        "Test executables: {}",
+        target_executables
+            .iter()
+            .map(|executable| executable.name.as_str())
+            .collect::<Vec<_>>()
+            .join(", ")
+    );
     if target_executables.is_empty() {
         pb.finish_with_message("No tests found.");
-        return Ok(());
+        return empty_selection(&args);
+    }
+
+    if let Some(base) = args.changed_base() {
+        let impact = ChangeImpact::new(
+            &WorkspaceMetadata::load()?,
+            &target_executables,
+            &changed_files(base)?,
+            args.changed_with_deps,
+        );
+        target_executables.retain(|executable| impact.affects(executable));
+
+        if target_executables.is_empty() {
+            pb.finish_and_clear();
+            println!(
+                "{}",
+                OutputFormatter::format_success(&format!(
+                    "No tests affected by changes since {}",
+                    base
+                ))
+            );
+            return empty_selection(&args);
+        }
+        notice(
+            pb,
+            OutputFormatter::format_info(&format!(
+                "Running {} test executable(s) affected by changes since {}",
+                target_executables.len(),
+                base
+            )),
+        );
     }
 
+    let expected = expected_durations(&target_executables, &cargo_config.filters);
+
     let start_time = Instant::now();
-    let result = if args.stream {
-        let executables_clone = target_executables.clone();
-        let handle = std::thread::spawn(move || cargo_runner.execute_tests(&executables_clone));
-
-        let mut completed_tests = 0;
-        let mut done_messages_received = 0;
-
-        while completed_tests < target_executables.len() {
-            pb.tick();
-            match output_rx.try_recv() {
-                Ok(output) => match output {
-                    ProcessOutput::TestStarted { name, suite: _ } => {
-                        pb.set_message(format!("{name}"));
-                    }
-                    ProcessOutput::TestPassed {
-                        result,
-                        duration_ms,
-                    } => {
-                        pb.inc(1);
-
-                        let _ = pb.println(format!(
-                            "{} {} {}",
-                            "✓".bright_green().bold(),
-                            result.name.bright_green(),
-                            format!("({:.2}ms)", duration_ms).dimmed()
-                        ));
-                    }
-                    ProcessOutput::TestFailed {
-                        result,
-                        duration_ms,
-                        error,
-                        location,
-                    } => {
-                        pb.inc(1);
-
-                        let _ = pb.println(format!(
-                            "{} {} {}",
-                            "✗".red().bold(),
-                            result.name.red(),
-                            format!("({:.2}ms)", duration_ms).dimmed()
-                        ));
-
-                        if !error.is_empty() && location.is_some() {
-                            let _ = pb.println(format_err_context(
-                                &result.name,
-                                location.clone(),
-                                Some(&error),
-                            ));
-                        } else if let Some(ref test_error) = result.error {
-                            let _ = pb.println(format!("    {}", test_error.to_string().dimmed()));
-                        }
-                    }
-                    ProcessOutput::TestSkipped { result } => {
-                        pb.inc(1);
-
-                        let _ =
-                            pb.println(format!("{} {}", "○".yellow().bold(), result.name.yellow()));
-                    }
-                    ProcessOutput::SuiteStarted { name, test_count } => {
-                        pb.set_message(format!("Starting {} ({} tests)", name, test_count));
-                    }
-                    ProcessOutput::SuiteCompleted { name: _ } => {
-                        done_messages_received += 1;
-                        if done_messages_received % 2 == 0 {
-                            completed_tests += 1;
-                            pb.set_prefix(format!("[{completed_tests}/{total_tests}]"));
-                        }
-                    }
-                    ProcessOutput::Done => {
-                        done_messages_received += 1;
-                        if done_messages_received % 2 == 0 {
-                            completed_tests += 1;
-                            pb.set_prefix(format!("[{completed_tests}/{total_tests}]"));
-                        }
-                    }
-                },
-                Err(TryRecvError::Disconnected) => {
-                    break;
-                }
-                Err(TryRecvError::Empty) => {
-                    continue;
+    if let (Some(times), Some(previous)) = (args.rerun_failed, previous_run) {
+        let mut live = live_reporters(&args, pb, HashMap::new())?;
+        if let Some(control) = &control {
+            live.push(Box::new(control.tracker()));
+        }
+        // Every attempt's events are numbered as part of one run
+        let (output_tx, output_rx) = mpsc::channel();
+        let output_tx = EventSender::new(output_tx);
+        let mut result = rerun_failed(
+            previous,
+            times,
+            &cargo_config,
+            &cancel,
+            pb,
+            |cargo_config| {
+                let result = executor(&args, &runner_config, cargo_config, output_tx.clone(), pb)?(
+                    &target_executables,
+                    &cancel,
+                )?;
+                for output in output_rx.try_iter() {
+                    live.handle(&output);
                 }
+                Ok(result)
+            },
+        )?;
+        result.id = output_tx.run_id();
+        pb.finish_and_clear();
+        drop(control);
+        return finish_run(result, &args, &cancel, start_time.elapsed());
+    }
+
+    let (output_tx, output_rx) = mpsc::channel();
+    let output_tx = EventSender::new(output_tx);
+    let run_id = output_tx.run_id();
+    let doc_tests = args.doc.then(|| {
+        DocTestRunner::new_with_output(runner_config.clone(), args.packages(), output_tx.clone())
+    });
+    let mut execute = executor(&args, &runner_config, cargo_config, output_tx, pb)?;
+    if let Some(doc_tests) = doc_tests {
+        execute = with_doc_tests(execute, doc_tests);
+    }
+
+    if args.dry_run {
+        let result = execute(&target_executables, &cancel)?;
+        pb.finish_and_clear();
+        display_dry_run(&result);
+        return Ok(());
+    }
+
+    let mut result = if args.stream {
+        let executables_clone = target_executables.clone();
+        let runner_cancel = cancel.clone();
+        let handle = std::thread::spawn(move || execute(&executables_clone, &runner_cancel));
+
+        let mut live = live_reporters(&args, pb, expected)?;
+        if let Some(control) = &control {
+            live.push(Box::new(control.tracker()));
+        }
+        live.on_run_start(total_tests);
+
+        // Read until the runner finishes and hangs up, ticking the bar while
+        // waiting
+        loop {
+            match output_rx.recv_timeout(Duration::from_millis(100)) {
+                Ok(output) => live.handle(&output),
+                Err(RecvTimeoutError::Timeout) => pb.tick(),
+                Err(RecvTimeoutError::Disconnected) => break,
             }
         }
 
         let mut result = handle.join().expect("Failed to complete test execution")?;
 
-        result.finish(None);
+        if result.error.is_none() {
+            result.finish(None);
+        }
+        live.on_run_finished(&result);
         result
     } else {
-        cargo_runner.execute_tests(&target_executables)?
+        execute(&target_executables, &cancel)?
     };
+    // The id the run's events carry
+    result.id = run_id;
 
     let duration = start_time.elapsed();
     pb.finish_and_clear();
+    drop(control);
 
-    display_test_results(&result, &args, duration)?;
+    finish_run(result, &args, &cancel, duration)
+}
 
-    if args.output.is_some() {
-        generate_report(&result, &args)?;
+/// Runs the executables here, in containers, or shared out between
+/// `--remote` hosts
+fn executor(
+    args: &TestArgs,
+    runner_config: &RunnerConfig,
+    cargo_config: CargoRunnerConfig,
+    output_tx: EventSender,
+    pb: &ProgressBar,
+) -> color_eyre::Result<Box<ExecuteFn>> {
+    Ok(if let Some(ref image) = args.container {
+        let container_config = ContainerConfig {
+            image: image.clone(),
+            engine: args.container_engine.clone(),
+            workdir: std::env::current_dir()?,
+            ..Default::default()
+        };
+        let mut runner =
+            ContainerRunner::new_with_output(runner_config.clone(), container_config, output_tx)
+                .with_cargo_config(cargo_config);
+        Box::new(move |executables, cancel| runner.execute_tests_with_cancel(executables, cancel))
+    } else if args.remote.is_empty() {
+        let mut runner = CargoTestRunner::new_with_output(runner_config.clone(), output_tx)
+            .with_cargo_config(cargo_config);
+        Box::new(move |executables, cancel| runner.execute_tests_with_cancel(executables, cancel))
+    } else {
+        let remote_config = RemoteConfig {
+            hosts: args.remote.clone(),
+            remote_dir: args.remote_dir.clone(),
+            ..Default::default()
+        };
+        notice(
+            pb,
+            OutputFormatter::format_info(&format!(
+                "Running on {} remote host(s)",
+                remote_config.hosts.len()
+            )),
+        );
+        let mut runner =
+            RemoteRunner::new_with_output(runner_config.clone(), remote_config, output_tx)
+                .with_cargo_config(cargo_config);
+        Box::new(move |executables, cancel| runner.execute_tests_with_cancel(executables, cancel))
+    })
+}
+
+/// Run the examples in doc comments once `execute` is done, adding a suite
+/// per package to its result
+fn with_doc_tests(mut execute: Box<ExecuteFn>, doc_tests: DocTestRunner) -> Box<ExecuteFn> {
+    Box::new(move |executables, cancel| {
+        let mut result = execute(executables, cancel)?;
+        for suite_result in doc_tests.run(cancel)? {
+            result.add_suite_result(suite_result);
+        }
+        let error = result
+            .error
+            .take()
+            .or_else(|| cancel.reason().map(CancelReason::run_error));
+        result.finish(error);
+        Ok(result)
+    })
+}
+
+/// Re-run the previous run's failures up to `times` times, each time only
+/// the tests still failing, and merge every attempt into its results
+fn rerun_failed(
+    previous: RunResult,
+    times: u32,
+    cargo_config: &CargoRunnerConfig,
+    cancel: &CancellationToken,
+    pb: &ProgressBar,
+    mut run_attempt: impl FnMut(CargoRunnerConfig) -> color_eyre::Result<RunResult>,
+) -> color_eyre::Result<RunResult> {
+    let failed = previous.failures().len();
+    let mut result = previous;
+
+    for attempt in 1..=times {
+        let failures = result.failures();
+        if failures.is_empty() || cancel.is_cancelled() {
+            break;
+        }
+
+        notice(
+            pb,
+            OutputFormatter::format_info(&format!(
+                "Re-running {} failed test(s), attempt {}/{}",
+                failures.len(),
+                attempt,
+                times
+            )),
+        );
+        let mut cargo_config = cargo_config.clone();
+        cargo_config.only_tests = Some(failures);
+        result = result.merge_rerun(run_attempt(cargo_config)?)?;
+    }
+
+    let recovered = failed - result.failures().len();
+    if recovered > 0 {
+        notice(
+            pb,
+            OutputFormatter::format_info(&format!(
+                "{} of {} failed test(s) passed on a re-run and are marked flaky",
+                recovered, failed
+            )),
+        );
+    }
+    Ok(result)
+}
+
+/// Reporters showing a run's progress as it happens: a line per test above
+/// the progress bar, or plain lines in CI mode, plus the `--event-log`
+fn live_reporters(
+    args: &TestArgs,
+    pb: &ProgressBar,
+    expected: HashMap<(String, String), Duration>,
+) -> color_eyre::Result<LiveReporters> {
+    // Tests of executables running side by side finish interleaved
+    let show_suites = args.jobs.is_some_and(|jobs| jobs > 1);
+    let mut live = if args.ci_mode() {
+        LiveReporters::new().with(CiReporter::default().with_suites(show_suites))
+    } else {
+        LiveReporters::new().with(
+            ProgressReporter::new(pb.clone())
+                .with_timings(expected)
+                .with_suites(show_suites),
+        )
+    };
+    if let Some(ref path) = args.event_log {
+        live.push(Box::new(EventLogReporter::create(path)?));
+    }
+    Ok(live)
+}
+
+/// Expected duration of each test the run is likely to include, by suite
+/// and name, from the timings recorded for the target executables' tests
+fn expected_durations(
+    executables: &[TestExecutable],
+    filters: &[TestFilter],
+) -> HashMap<(String, String), Duration> {
+    let Ok(timings) = RunCache::current().and_then(|cache| cache.load_timings()) else {
+        return HashMap::new();
+    };
+    let suites: HashSet<String> = executables
+        .iter()
+        .map(|executable| format_mod_name(&executable.name))
+        .collect();
+
+    timings
+        .durations()
+        .into_iter()
+        .filter(|((suite, test), _)| {
+            suites.contains(suite)
+                && (filters.is_empty() || filters.iter().any(|filter| filter.matches(suite, test)))
+        })
+        .collect()
+}
+
+/// Show what the compiler reported when the executables were rebuilt: the
+/// errors in full if the build failed, and otherwise how many warnings it
+/// gave, in full with `verbose`. Compile errors are shown on their own, as
+/// no test ran to fail.
+fn report_build(build: &TestBuild, pb: &ProgressBar, verbose: bool) -> color_eyre::Result<()> {
+    if !build.success {
+        pb.finish_and_clear();
+        println!(
+            "{}",
+            OutputFormatter::format_header("Build Failed", Gradient::Instagram)
+        );
+        for error in build.errors() {
+            match &error.rendered {
+                Some(rendered) => println!("{}", rendered),
+                None => println!("error: {}\n", error.message),
+            }
+        }
+        let errors = build.errors().count();
+        return Err(Error::test_execution(format!(
+            "The tests didn't compile: {} error{}",
+            errors,
+            if errors == 1 { "" } else { "s" }
+        ))
+        .into());
+    }
+
+    let warnings = build.warnings().count();
+    if build.rebuilt && verbose {
+        for warning in build.warnings() {
+            match &warning.rendered {
+                Some(rendered) => notice(pb, rendered.trim_end().to_string()),
+                None => notice(pb, format!("warning: {}", warning.message)),
+            }
+        }
+    }
+    if build.rebuilt && warnings > 0 {
+        notice(
+            pb,
+            OutputFormatter::format_warning(&format!(
+                "Built the tests with {} compiler warning{}",
+                warnings,
+                if warnings == 1 { "" } else { "s" }
+            )),
+        );
+    }
+    Ok(())
+}
+
+/// Print a message above the progress bar, or straight to stdout when the
+/// bar is hidden, e.g. in CI mode or when stdout isn't a terminal
+fn notice(pb: &ProgressBar, message: String) {
+    if pb.is_hidden() {
+        println!("{}", message);
+    } else {
+        pb.println(message);
+    }
+}
+
+/// Save, show and export a finished run, exiting with an error code if any
+/// test failed
+fn finish_run(
+    result: RunResult,
+    args: &TestArgs,
+    cancel: &CancellationToken,
+    duration: Duration,
+) -> color_eyre::Result<()> {
+    match cancel.reason() {
+        Some(CancelReason::FailFast) => println!(
+            "{}",
+            OutputFormatter::format_warning(
+                "Stopped at the first failure, remaining tests skipped"
+            )
+        ),
+        Some(CancelReason::Timeout) => println!(
+            "{}",
+            OutputFormatter::format_warning(
+                "Stopped after exceeding the run time budget, remaining tests skipped"
+            )
+        ),
+        Some(reason) => println!(
+            "{}",
+            OutputFormatter::format_warning(&format!(
+                "Run cancelled ({}), showing partial results",
+                reason
+            ))
+        ),
+        None => {}
+    }
+
+    if let Err(e) = RunCache::current().and_then(|cache| {
+        cache.save_last_run(&result)?;
+        cache.append_history(&result)?;
+        cache.record_timings(&result)
+    }) {
+        tracing::warn!("Failed to save run results: {}", e);
+    }
+
+    if args.ci_mode() {
+        display_ci_results(&result, args, duration)?;
+    } else {
+        display_test_results(&result, args, duration)?;
+    }
+
+    if let Some(ref endpoint) = args.otel_endpoint {
+        match OtelExporter::new(endpoint).export(&result) {
+            Ok(()) => println!(
+                "{}",
+                OutputFormatter::format_success(&format!("Exported trace to {}", endpoint))
+            ),
+            Err(e) => tracing::warn!("Failed to export trace to {}: {}", endpoint, e),
+        }
+    }
+
+    if let Some(ref gateway) = args.pushgateway {
+        match MetricsReporter::new().push(&result, gateway) {
+            Ok(()) => println!(
+                "{}",
+                OutputFormatter::format_success(&format!("Pushed metrics to {}", gateway))
+            ),
+            Err(e) => tracing::warn!("Failed to push metrics to {}: {}", gateway, e),
+        }
+    }
+
+    if args.output.is_some() || args.template.is_some() {
+        generate_report(&result, args)?;
     }
 
     if !result.all_passed() {
         std::process::exit(1);
     }
 
+    let policy_failure = if args.fails_on(FailOn::Skipped) && result.skipped_tests > 0 {
+        Some(format!("{} test(s) were skipped", result.skipped_tests))
+    } else if args.fails_on(FailOn::Flaky) && result.flaky_tests > 0 {
+        Some(format!(
+            "{} test(s) only passed after retrying",
+            result.flaky_tests
+        ))
+    } else if args.fails_on(FailOn::Empty) && result.total_tests == 0 {
+        Some("No tests ran".to_string())
+    } else {
+        None
+    };
+    if let Some(reason) = policy_failure {
+        println!(
+            "{}",
+            OutputFormatter::format_error(&format!("{}, failing the run (--fail-on)", reason))
+        );
+        std::process::exit(1);
+    }
+
+    Ok(())
+}
+
+/// Finish a run that selected no tests, which fails it under
+/// `--fail-on empty`
+fn empty_selection(args: &TestArgs) -> color_eyre::Result<()> {
+    if args.fails_on(FailOn::Empty) {
+        println!(
+            "{}",
+            OutputFormatter::format_error("No tests selected, failing the run (--fail-on)")
+        );
+        std::process::exit(1);
+    }
+    Ok(())
+}
+
+/// Cancel the run on the first SIGINT/SIGTERM so child processes are stopped
+/// and partial results reported; a second signal exits immediately.
+fn handle_interrupts(cancel: &CancellationToken) -> color_eyre::Result<()> {
+    let mut signals = Signals::new([SIGINT, SIGTERM])?;
+    let cancel = cancel.clone();
+
+    std::thread::spawn(move || {
+        for _ in signals.forever() {
+            if cancel.is_cancelled() {
+                std::process::exit(130);
+            }
+            cancel.cancel(CancelReason::User);
+        }
+    });
+
     Ok(())
 }
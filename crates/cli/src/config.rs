@@ -1,15 +1,26 @@
+use crate::cli::{FailOn, OutputFormat, OutputMode, SuiteLayout, TestArgs};
 use serde::{Deserialize, Serialize};
-use std::path::PathBuf;
+use sheila::runners::parse_duration;
+use std::collections::BTreeMap;
+use std::path::{Path, PathBuf};
 
-#[derive(Debug, Clone, Serialize, Deserialize)]
+/// Settings read from `sheila.toml` in the working directory. Every section
+/// and key is optional.
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+#[serde(default)]
 pub struct SheilaConfig {
     pub build: BuildConfig,
     pub discovery: DiscoveryConfig,
+    pub generate: GenerateConfig,
     pub reporting: ReportingConfig,
     pub runner: RunnerConfig,
+    /// Named sets of `sheila test` options, chosen with `--profile`
+    #[serde(rename = "profile")]
+    pub profiles: BTreeMap<String, ProfileConfig>,
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(default)]
 pub struct BuildConfig {
     pub target_dir: PathBuf,
     pub debug_dir: String,
@@ -18,72 +29,268 @@ pub struct BuildConfig {
     pub profile: String,
 }
 
+impl Default for BuildConfig {
+    fn default() -> Self {
+        Self {
+            target_dir: PathBuf::from("target"),
+            debug_dir: "debug".to_string(),
+            release_dir: "release".to_string(),
+            deps_dir: "deps".to_string(),
+            profile: "debug".to_string(),
+        }
+    }
+}
+
 #[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(default)]
 pub struct DiscoveryConfig {
     pub rust_file_extensions: Vec<String>,
     pub test_patterns: Vec<String>,
     pub suite_patterns: Vec<String>,
+    /// Globs of the only files discovery scans, or every file if empty
+    pub include_patterns: Vec<String>,
+    /// Globs of the files and directories discovery never scans, on top of
+    /// those listed in `.sheilaignore` files
     pub exclude_patterns: Vec<String>,
 }
 
+impl Default for DiscoveryConfig {
+    fn default() -> Self {
+        Self {
+            rust_file_extensions: vec!["rs".to_string()],
+            test_patterns: vec![
+                r#"#\[sheila::test(?:\([^\)]*\))?\]\s*\n\s*(?:pub\s+)?fn\s+(\w+)"#.to_string(),
+            ],
+            suite_patterns: vec![
+                r#"#\[sheila::suite(?:\([^\)]*\))?\]\s*\n\s*(?:pub\s+)?struct\s+(\w+)"#.to_string(),
+            ],
+            include_patterns: Vec::new(),
+            exclude_patterns: vec!["target/**".to_string(), "**/.git/**".to_string()],
+        }
+    }
+}
+
+/// How `sheila generate` lays out the suites it creates
 #[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(default)]
+pub struct GenerateConfig {
+    /// Whether a source file's suite goes in the file itself or in the
+    /// package's `tests` directory
+    pub layout: SuiteLayout,
+    /// Appended to a source file's name to name its suite, e.g. `parser_tests`
+    pub suite_suffix: String,
+    /// Whether new suites start with fixture and hook stubs
+    pub stubs: bool,
+}
+
+impl Default for GenerateConfig {
+    fn default() -> Self {
+        Self {
+            layout: SuiteLayout::Inline,
+            suite_suffix: "_tests".to_string(),
+            stubs: true,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(default)]
 pub struct ReportingConfig {
     pub output_dir: PathBuf,
     pub formats: Vec<String>,
     pub timestamp_format: String,
+    pub retention: RetentionConfig,
 }
 
+impl Default for ReportingConfig {
+    fn default() -> Self {
+        Self {
+            output_dir: PathBuf::from("test-results"),
+            formats: vec!["json".to_string(), "html".to_string()],
+            timestamp_format: "%Y%m%d_%H%M%S".to_string(),
+            retention: RetentionConfig::default(),
+        }
+    }
+}
+
+/// Limits on how many generated reports are kept in the output directory.
+/// Reports over any limit are removed oldest first, but the most recent
+/// report is always kept.
 #[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(default)]
+pub struct RetentionConfig {
+    /// Remove reports older than this many days
+    pub max_age_days: Option<u64>,
+    /// Keep at most this many reports
+    pub max_reports: Option<usize>,
+    /// Keep the reports' combined size under this many MiB
+    pub max_size_mb: Option<u64>,
+}
+
+impl Default for RetentionConfig {
+    fn default() -> Self {
+        Self {
+            max_age_days: None,
+            max_reports: Some(100),
+            max_size_mb: None,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(default)]
 pub struct RunnerConfig {
     pub default_timeout: u64,
     pub max_retries: u32,
     pub parallel_limit: Option<usize>,
 }
 
-impl Default for SheilaConfig {
+impl Default for RunnerConfig {
     fn default() -> Self {
         Self {
-            build: BuildConfig {
-                target_dir: PathBuf::from("target"),
-                debug_dir: "debug".to_string(),
-                release_dir: "release".to_string(),
-                deps_dir: "deps".to_string(),
-                profile: "debug".to_string(),
-            },
-            discovery: DiscoveryConfig {
-                rust_file_extensions: vec!["rs".to_string()],
-                test_patterns: vec![
-                    r#"#\[sheila::test(?:\([^\)]*\))?\]\s*\n\s*(?:pub\s+)?fn\s+(\w+)"#.to_string(),
-                ],
-                suite_patterns: vec![
-                    r#"#\[sheila::suite(?:\([^\)]*\))?\]\s*\n\s*(?:pub\s+)?struct\s+(\w+)"#
-                        .to_string(),
-                ],
-                exclude_patterns: vec!["target/**".to_string(), "**/.git/**".to_string()],
-            },
-            reporting: ReportingConfig {
-                output_dir: PathBuf::from("test-results"),
-                formats: vec!["json".to_string(), "html".to_string()],
-                timestamp_format: "%Y%m%d_%H%M%S".to_string(),
-            },
-            runner: RunnerConfig {
-                default_timeout: 30,
-                max_retries: 3,
-                parallel_limit: None,
-            },
+            default_timeout: 30,
+            max_retries: 3,
+            parallel_limit: None,
+        }
+    }
+}
+
+/// A `[profile.<name>]` section: defaults for `sheila test` options, so a
+/// CI job can run `sheila test --profile ci` instead of a long command line.
+/// Options given on the command line win over the profile.
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+#[serde(default)]
+pub struct ProfileConfig {
+    pub fail_fast: Option<bool>,
+    pub retries: Option<u32>,
+    pub jobs: Option<usize>,
+    /// Shard to run, e.g. `"1/4"`
+    pub shard: Option<String>,
+    /// Timeout in seconds for each test executable
+    pub timeout: Option<u64>,
+    /// Time budget for the whole run, e.g. `"30m"`
+    pub max_run_duration: Option<String>,
+    /// Tag expression, e.g. `"(api & !slow) | smoke"`
+    pub tags: Option<String>,
+    pub exclude_tags: Option<Vec<String>>,
+    /// Besides failures, what fails the run, e.g. `["skipped", "empty"]`
+    pub fail_on: Option<Vec<FailOn>>,
+    pub cache: Option<bool>,
+    /// Environment variables for the test executables, under those given
+    /// with `--env`
+    pub env: BTreeMap<String, String>,
+    pub env_file: Option<PathBuf>,
+    pub output: Option<OutputFormat>,
+    pub output_mode: Option<OutputMode>,
+    pub summary_file: Option<PathBuf>,
+    pub output_dir: Option<PathBuf>,
+    pub template: Option<PathBuf>,
+    pub event_log: Option<PathBuf>,
+    pub otel_endpoint: Option<String>,
+    pub pushgateway: Option<String>,
+}
+
+impl ProfileConfig {
+    /// Fill in the options `args` leaves unset from this profile
+    pub fn apply(&self, args: &mut TestArgs) -> sheila::Result<()> {
+        if self.fail_fast == Some(true) {
+            args.fail_fast = true;
         }
+        if self.cache == Some(true) {
+            args.cache = true;
+        }
+        if args.retries.is_none() {
+            args.retries = self.retries;
+        }
+        if args.jobs.is_none() {
+            args.jobs = self.jobs;
+        }
+        if args.shard.is_none()
+            && let Some(ref shard) = self.shard
+        {
+            args.shard = Some(shard.parse()?);
+        }
+        if args.timeout.is_none() {
+            args.timeout = self.timeout;
+        }
+        if args.max_run_duration.is_none()
+            && let Some(ref duration) = self.max_run_duration
+        {
+            args.max_run_duration = Some(parse_duration(duration)?);
+        }
+        if args.tags.is_none()
+            && let Some(ref tags) = self.tags
+        {
+            args.tags = Some(tags.parse()?);
+        }
+        if args.exclude_tags.is_empty()
+            && let Some(ref tags) = self.exclude_tags
+        {
+            args.exclude_tags = tags.clone();
+        }
+        // Later variables win, so the command line's go last
+        let mut env: Vec<(String, String)> = self.env.clone().into_iter().collect();
+        env.append(&mut args.env);
+        args.env = env;
+        args.env_file = args.env_file.take().or_else(|| self.env_file.clone());
+        if args.fail_on.is_empty()
+            && let Some(ref fail_on) = self.fail_on
+        {
+            args.fail_on = fail_on.clone();
+        }
+        args.output = args.output.or(self.output);
+        args.output_mode = args.output_mode.or(self.output_mode);
+        args.summary_file = args
+            .summary_file
+            .take()
+            .or_else(|| self.summary_file.clone());
+        args.output_dir = args.output_dir.take().or_else(|| self.output_dir.clone());
+        args.template = args.template.take().or_else(|| self.template.clone());
+        args.event_log = args.event_log.take().or_else(|| self.event_log.clone());
+        args.otel_endpoint = args
+            .otel_endpoint
+            .take()
+            .or_else(|| self.otel_endpoint.clone());
+        args.pushgateway = args.pushgateway.take().or_else(|| self.pushgateway.clone());
+
+        Ok(())
     }
 }
 
 impl SheilaConfig {
     pub fn load() -> Result<Self, Box<dyn std::error::Error>> {
-        if let Ok(content) = std::fs::read_to_string("sheila.toml") {
+        Self::load_in(Path::new(""))
+    }
+
+    /// The `sheila.toml` of the project at `dir`, or the defaults if it has
+    /// none
+    pub fn load_in(dir: &Path) -> Result<Self, Box<dyn std::error::Error>> {
+        if let Ok(content) = std::fs::read_to_string(dir.join("sheila.toml")) {
             Ok(toml::from_str(&content)?)
         } else {
             Ok(Self::default())
         }
     }
 
+    /// The profile called `name`
+    pub fn profile(&self, name: &str) -> sheila::Result<&ProfileConfig> {
+        self.profiles.get(name).ok_or_else(|| {
+            let available = self.profiles.keys().cloned().collect::<Vec<_>>();
+            sheila::Error::invalid_config(if available.is_empty() {
+                format!(
+                    "Unknown profile '{}': sheila.toml defines no profiles",
+                    name
+                )
+            } else {
+                format!(
+                    "Unknown profile '{}', expected one of: {}",
+                    name,
+                    available.join(", ")
+                )
+            })
+        })
+    }
+
     pub fn build_target_path(&self, profile: Option<&str>) -> PathBuf {
         let profile = profile.unwrap_or(&self.build.profile);
         self.build
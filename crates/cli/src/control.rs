@@ -0,0 +1,362 @@
+//! The control channel of background runs. A run started with `--detach`
+//! listens on a Unix socket in the cache directory, or a named pipe on
+//! Windows, for JSON-RPC 2.0 requests, one per line. None of the methods
+//! take params:
+//!
+//! - `pause`, `resume`, `stop`: act on the run, answering with its
+//!   [`RunState`]
+//! - `status`: the run's [`RunState`]
+//! - `progress`: the run's [`RunProgress`]
+
+use crate::process::{ProcessManager, ProcessStatus};
+use serde::de::DeserializeOwned;
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+use sheila::reporting::LiveReporter;
+use sheila::test::TestResult;
+use sheila::{CancelReason, CancellationToken};
+use std::io;
+use std::path::{Path, PathBuf};
+use std::sync::{Arc, Mutex, mpsc};
+use std::time::Duration;
+use tokio::io::{AsyncBufReadExt, AsyncRead, AsyncWrite, AsyncWriteExt, BufReader};
+use uuid::Uuid;
+
+/// How long a client waits for a run to answer
+const CALL_TIMEOUT: Duration = Duration::from_secs(5);
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum RunPhase {
+    Running,
+    Paused,
+    /// Asked to stop, and winding down
+    Stopping,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct RunState {
+    pub id: Uuid,
+    pub pid: u32,
+    pub phase: RunPhase,
+}
+
+/// How far a run has got, as answered to `progress`
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct RunProgress {
+    /// Tests the run expects to run, if known
+    pub total: usize,
+    pub finished: usize,
+    pub passed: usize,
+    pub failed: usize,
+    pub skipped: usize,
+    /// Tests running right now
+    pub running: Vec<String>,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+struct Request {
+    jsonrpc: String,
+    #[serde(default)]
+    id: Value,
+    method: String,
+    #[serde(default, skip_serializing_if = "Value::is_null")]
+    params: Value,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+struct Response {
+    jsonrpc: String,
+    id: Value,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    result: Option<Value>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    error: Option<RpcError>,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+struct RpcError {
+    code: i64,
+    message: String,
+}
+
+impl Response {
+    fn new(id: Value, outcome: Result<Value, RpcError>) -> Self {
+        let (result, error) = match outcome {
+            Ok(result) => (Some(result), None),
+            Err(error) => (None, Some(error)),
+        };
+        Self {
+            jsonrpc: "2.0".to_string(),
+            id,
+            result,
+            error,
+        }
+    }
+}
+
+/// The listening end of a run's control channel, which stops listening
+/// when dropped
+pub struct ControlServer {
+    progress: Arc<Mutex<RunProgress>>,
+    endpoint: PathBuf,
+}
+
+impl ControlServer {
+    /// Take requests for run `id` at `endpoint`, acting on `cancel`
+    pub fn start(
+        endpoint: &Path,
+        id: Uuid,
+        cancel: &CancellationToken,
+    ) -> color_eyre::Result<Self> {
+        let progress = Arc::new(Mutex::new(RunProgress::default()));
+        let handler = Arc::new(Handler {
+            id,
+            cancel: cancel.clone(),
+            progress: progress.clone(),
+        });
+
+        // The run itself is synchronous, so the channel gets a thread and
+        // runtime of its own
+        let (ready_tx, ready_rx) = mpsc::channel();
+        let path = endpoint.to_path_buf();
+        std::thread::spawn(move || {
+            let runtime = match tokio::runtime::Builder::new_current_thread()
+                .enable_all()
+                .build()
+            {
+                Ok(runtime) => runtime,
+                Err(e) => {
+                    let _ = ready_tx.send(Err(e));
+                    return;
+                }
+            };
+            runtime.block_on(listen(path, handler, ready_tx));
+        });
+        ready_rx
+            .recv()
+            .map_err(|_| sheila::Error::generic("The control channel failed to start"))??;
+
+        Ok(Self {
+            progress,
+            endpoint: endpoint.to_path_buf(),
+        })
+    }
+
+    /// A reporter keeping the answers to `progress` up to date
+    pub fn tracker(&self) -> ProgressTracker {
+        ProgressTracker(self.progress.clone())
+    }
+}
+
+impl Drop for ControlServer {
+    fn drop(&mut self) {
+        #[cfg(unix)]
+        let _ = std::fs::remove_file(&self.endpoint);
+    }
+}
+
+/// Send `method` to the run listening at `endpoint` and return its answer
+pub async fn call<T: DeserializeOwned>(endpoint: &Path, method: &str) -> io::Result<T> {
+    let exchange = async {
+        #[cfg(unix)]
+        let stream = tokio::net::UnixStream::connect(endpoint).await?;
+        #[cfg(windows)]
+        let stream = tokio::net::windows::named_pipe::ClientOptions::new().open(endpoint)?;
+
+        let (read, mut write) = tokio::io::split(stream);
+        let request = Request {
+            jsonrpc: "2.0".to_string(),
+            id: Value::from(1),
+            method: method.to_string(),
+            params: Value::Null,
+        };
+        let mut line = serde_json::to_vec(&request)?;
+        line.push(b'\n');
+        write.write_all(&line).await?;
+
+        let line = BufReader::new(read)
+            .lines()
+            .next_line()
+            .await?
+            .ok_or_else(|| {
+                io::Error::new(io::ErrorKind::UnexpectedEof, "No answer from the run")
+            })?;
+        let response: Response = serde_json::from_str(&line)?;
+        match (response.result, response.error) {
+            (_, Some(error)) => Err(io::Error::other(error.message)),
+            (Some(result), None) => Ok(serde_json::from_value(result)?),
+            (None, None) => Err(io::Error::other("Empty answer from the run")),
+        }
+    };
+    tokio::time::timeout(CALL_TIMEOUT, exchange)
+        .await
+        .map_err(|_| io::Error::new(io::ErrorKind::TimedOut, "The run didn't answer"))?
+}
+
+struct Handler {
+    id: Uuid,
+    cancel: CancellationToken,
+    progress: Arc<Mutex<RunProgress>>,
+}
+
+impl Handler {
+    async fn handle(&self, method: &str) -> Result<Value, RpcError> {
+        match method {
+            "pause" if !self.cancel.is_cancelled() => {
+                self.cancel.pause();
+                self.record(ProcessStatus::Paused).await;
+            }
+            "resume" if !self.cancel.is_cancelled() => {
+                self.cancel.resume();
+                self.record(ProcessStatus::Running).await;
+            }
+            "stop" => {
+                self.cancel.cancel(CancelReason::User);
+                // A suspended run has to carry on to be able to wind down
+                self.cancel.resume();
+            }
+            "progress" => {
+                let progress = self.progress.lock().unwrap().clone();
+                return Ok(serde_json::to_value(progress).unwrap_or_default());
+            }
+            "pause" | "resume" | "status" => {}
+            _ => {
+                return Err(RpcError {
+                    code: -32601,
+                    message: format!("Unknown method '{}'", method),
+                });
+            }
+        }
+        Ok(serde_json::to_value(self.state()).unwrap_or_default())
+    }
+
+    fn state(&self) -> RunState {
+        let phase = if self.cancel.is_cancelled() {
+            RunPhase::Stopping
+        } else if self.cancel.is_paused() {
+            RunPhase::Paused
+        } else {
+            RunPhase::Running
+        };
+        RunState {
+            id: self.id,
+            pid: std::process::id(),
+            phase,
+        }
+    }
+
+    /// Keep the run's record in line with what it was asked to do, for
+    /// `sheila status`
+    async fn record(&self, status: ProcessStatus) {
+        if let Ok(process_manager) = ProcessManager::new() {
+            let _ = process_manager.record_status(self.id, status).await;
+        }
+    }
+}
+
+#[cfg(unix)]
+async fn listen(path: PathBuf, handler: Arc<Handler>, ready: mpsc::Sender<io::Result<()>>) {
+    // A socket left behind by a run that exited without cleaning up
+    let _ = std::fs::remove_file(&path);
+    let listener = match tokio::net::UnixListener::bind(&path) {
+        Ok(listener) => listener,
+        Err(e) => {
+            let _ = ready.send(Err(e));
+            return;
+        }
+    };
+    let _ = ready.send(Ok(()));
+
+    loop {
+        if let Ok((stream, _)) = listener.accept().await {
+            tokio::spawn(serve(stream, handler.clone()));
+        }
+    }
+}
+
+#[cfg(windows)]
+async fn listen(path: PathBuf, handler: Arc<Handler>, ready: mpsc::Sender<io::Result<()>>) {
+    use tokio::net::windows::named_pipe::ServerOptions;
+
+    let mut server = match ServerOptions::new().first_pipe_instance(true).create(&path) {
+        Ok(server) => server,
+        Err(e) => {
+            let _ = ready.send(Err(e));
+            return;
+        }
+    };
+    let _ = ready.send(Ok(()));
+
+    loop {
+        if server.connect().await.is_err() {
+            continue;
+        }
+        // Have the next instance waiting before handing this one over
+        let Ok(next) = ServerOptions::new().create(&path) else {
+            return;
+        };
+        let client = std::mem::replace(&mut server, next);
+        tokio::spawn(serve(client, handler.clone()));
+    }
+}
+
+/// Answer requests on one connection until the client hangs up
+async fn serve<S: AsyncRead + AsyncWrite>(stream: S, handler: Arc<Handler>) {
+    let (read, mut write) = tokio::io::split(stream);
+    let mut lines = BufReader::new(read).lines();
+    while let Ok(Some(line)) = lines.next_line().await {
+        if line.trim().is_empty() {
+            continue;
+        }
+        let response = match serde_json::from_str::<Request>(&line) {
+            Ok(request) => Response::new(request.id, handler.handle(&request.method).await),
+            Err(e) => Response::new(
+                Value::Null,
+                Err(RpcError {
+                    code: -32700,
+                    message: format!("Parse error: {}", e),
+                }),
+            ),
+        };
+        let Ok(mut line) = serde_json::to_vec(&response) else {
+            continue;
+        };
+        line.push(b'\n');
+        if write.write_all(&line).await.is_err() {
+            break;
+        }
+    }
+}
+
+/// Keeps a [`ControlServer`]'s [`RunProgress`] up to date
+pub struct ProgressTracker(Arc<Mutex<RunProgress>>);
+
+impl LiveReporter for ProgressTracker {
+    fn on_run_start(&mut self, total_tests: usize) {
+        self.0.lock().unwrap().total = total_tests;
+    }
+
+    fn on_test_start(&mut self, name: &str, _suite: &str) {
+        self.0.lock().unwrap().running.push(name.to_string());
+    }
+
+    fn on_test_finished(&mut self, result: &TestResult, _suite: &str) {
+        let mut progress = self.0.lock().unwrap();
+        if let Some(index) = progress
+            .running
+            .iter()
+            .position(|name| *name == result.name)
+        {
+            progress.running.remove(index);
+        }
+        progress.finished += 1;
+        if result.passed() {
+            progress.passed += 1;
+        } else if result.failed() {
+            progress.failed += 1;
+        } else {
+            progress.skipped += 1;
+        }
+    }
+}
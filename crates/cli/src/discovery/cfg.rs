@@ -0,0 +1,233 @@
+//! Best-effort evaluation of `#[cfg(...)]` predicates, to tell which
+//! discovered tests are compiled out of the test build on this machine.
+//!
+//! The target is taken to be the host, `test` and `debug_assertions` to be
+//! set, and a package's features to be its default ones. Predicates that
+//! can't be judged this way count as met, so a test is only marked when it
+//! certainly won't be built.
+
+use std::collections::HashSet;
+use std::fmt;
+use std::fs;
+use std::path::{Path, PathBuf};
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Cfg {
+    /// e.g. `unix`
+    Name(String),
+    /// e.g. `feature = "serde"`
+    KeyValue(String, String),
+    All(Vec<Cfg>),
+    Any(Vec<Cfg>),
+    Not(Box<Cfg>),
+}
+
+impl Cfg {
+    /// Parse the predicate inside `#[cfg(...)]`
+    pub fn parse(text: &str) -> Option<Self> {
+        let tokens = tokenize(text)?;
+        let mut parser = Parser { tokens, next: 0 };
+        let cfg = parser.predicate()?;
+        (parser.next == parser.tokens.len()).then_some(cfg)
+    }
+
+    /// Whether the predicate holds when building a package's tests with
+    /// `features`, if they're known, or `None` if that can't be told
+    pub fn eval(&self, features: Option<&HashSet<String>>) -> Option<bool> {
+        match self {
+            Cfg::Name(name) => match name.as_str() {
+                "test" | "debug_assertions" => Some(true),
+                "unix" => Some(cfg!(unix)),
+                "windows" => Some(cfg!(windows)),
+                "miri" | "doc" | "doctest" => Some(false),
+                _ => None,
+            },
+            Cfg::KeyValue(key, value) => {
+                let host = match key.as_str() {
+                    "feature" => return features.map(|features| features.contains(value)),
+                    "target_os" => std::env::consts::OS.to_string(),
+                    "target_family" => std::env::consts::FAMILY.to_string(),
+                    "target_arch" => std::env::consts::ARCH.to_string(),
+                    "target_pointer_width" => usize::BITS.to_string(),
+                    "target_endian" if cfg!(target_endian = "little") => "little".to_string(),
+                    "target_endian" => "big".to_string(),
+                    _ => return None,
+                };
+                Some(host == *value)
+            }
+            Cfg::All(cfgs) => {
+                let results: Vec<_> = cfgs.iter().map(|cfg| cfg.eval(features)).collect();
+                if results.contains(&Some(false)) {
+                    Some(false)
+                } else {
+                    results
+                        .into_iter()
+                        .collect::<Option<Vec<_>>>()
+                        .map(|_| true)
+                }
+            }
+            Cfg::Any(cfgs) => {
+                let results: Vec<_> = cfgs.iter().map(|cfg| cfg.eval(features)).collect();
+                if results.contains(&Some(true)) {
+                    Some(true)
+                } else {
+                    results
+                        .into_iter()
+                        .collect::<Option<Vec<_>>>()
+                        .map(|_| false)
+                }
+            }
+            Cfg::Not(cfg) => cfg.eval(features).map(|holds| !holds),
+        }
+    }
+}
+
+impl fmt::Display for Cfg {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let list = |f: &mut fmt::Formatter<'_>, name: &str, cfgs: &[Cfg]| {
+            let cfgs: Vec<String> = cfgs.iter().map(ToString::to_string).collect();
+            write!(f, "{}({})", name, cfgs.join(", "))
+        };
+        match self {
+            Cfg::Name(name) => write!(f, "{}", name),
+            Cfg::KeyValue(key, value) => write!(f, "{} = {:?}", key, value),
+            Cfg::All(cfgs) => list(f, "all", cfgs),
+            Cfg::Any(cfgs) => list(f, "any", cfgs),
+            Cfg::Not(cfg) => write!(f, "not({})", cfg),
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+enum Token {
+    Ident(String),
+    Str(String),
+    Punct(char),
+}
+
+fn tokenize(text: &str) -> Option<Vec<Token>> {
+    let mut tokens = Vec::new();
+    let mut chars = text.chars().peekable();
+    while let Some(&c) = chars.peek() {
+        if c.is_whitespace() {
+            chars.next();
+        } else if c.is_alphanumeric() || c == '_' {
+            let mut ident = String::new();
+            while let Some(&c) = chars.peek().filter(|c| c.is_alphanumeric() || **c == '_') {
+                ident.push(c);
+                chars.next();
+            }
+            tokens.push(Token::Ident(ident));
+        } else if c == '"' {
+            chars.next();
+            let mut value = String::new();
+            loop {
+                match chars.next()? {
+                    '"' => break,
+                    '\\' => value.push(chars.next()?),
+                    c => value.push(c),
+                }
+            }
+            tokens.push(Token::Str(value));
+        } else if matches!(c, '(' | ')' | ',' | '=') {
+            tokens.push(Token::Punct(c));
+            chars.next();
+        } else {
+            return None;
+        }
+    }
+    Some(tokens)
+}
+
+struct Parser {
+    tokens: Vec<Token>,
+    next: usize,
+}
+
+impl Parser {
+    fn peek(&self) -> Option<&Token> {
+        self.tokens.get(self.next)
+    }
+
+    fn bump(&mut self) -> Option<Token> {
+        let token = self.tokens.get(self.next).cloned();
+        self.next += 1;
+        token
+    }
+
+    fn eat(&mut self, punct: char) -> bool {
+        let matched = self.peek() == Some(&Token::Punct(punct));
+        if matched {
+            self.next += 1;
+        }
+        matched
+    }
+
+    fn predicate(&mut self) -> Option<Cfg> {
+        let Token::Ident(name) = self.bump()? else {
+            return None;
+        };
+
+        if self.eat('=') {
+            let Token::Str(value) = self.bump()? else {
+                return None;
+            };
+            return Some(Cfg::KeyValue(name, value));
+        }
+        if !self.eat('(') {
+            return Some(Cfg::Name(name));
+        }
+
+        let mut cfgs = Vec::new();
+        while !self.eat(')') {
+            cfgs.push(self.predicate()?);
+            if !self.eat(',') && self.peek() != Some(&Token::Punct(')')) {
+                return None;
+            }
+        }
+        match name.as_str() {
+            "all" => Some(Cfg::All(cfgs)),
+            "any" => Some(Cfg::Any(cfgs)),
+            "not" if cfgs.len() == 1 => Some(Cfg::Not(Box::new(cfgs.remove(0)))),
+            _ => None,
+        }
+    }
+}
+
+/// The manifest of the package `path` is in: the nearest `Cargo.toml` above
+/// it with a `[package]`
+pub fn package_manifest(path: &Path) -> Option<PathBuf> {
+    path.ancestors().skip(1).find_map(|dir| {
+        let manifest = dir.join("Cargo.toml");
+        let content = fs::read_to_string(&manifest).ok()?;
+        let table: toml::Table = content.parse().ok()?;
+        table.contains_key("package").then_some(manifest)
+    })
+}
+
+/// The features the package of `manifest` builds with by default, with the
+/// features they enable in turn
+pub fn default_features(manifest: &Path) -> HashSet<String> {
+    let features = fs::read_to_string(manifest)
+        .ok()
+        .and_then(|content| content.parse::<toml::Table>().ok())
+        .and_then(|table| table.get("features")?.as_table().cloned())
+        .unwrap_or_default();
+
+    let mut enabled = HashSet::new();
+    let mut pending = vec!["default".to_string()];
+    while let Some(feature) = pending.pop() {
+        // `dep:name` and `name/feature` enable things of other packages
+        if feature.contains([':', '/']) || !enabled.insert(feature.clone()) {
+            continue;
+        }
+        let enables = features.get(&feature).and_then(|value| value.as_array());
+        pending.extend(
+            enables
+                .into_iter()
+                .flatten()
+                .filter_map(|value| value.as_str().map(str::to_string)),
+        );
+    }
+    enabled
+}
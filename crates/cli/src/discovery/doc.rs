@@ -0,0 +1,103 @@
+//! Documentation examples: the code blocks in `///` and `//!` comments that
+//! rustdoc compiles and runs as tests. Found by reading the comments line by
+//! line, whichever parser finds the file's tests, so `#[doc = "..."]`
+//! attributes aren't looked into.
+
+use regex::Regex;
+use std::sync::LazyLock;
+
+use super::DocTest;
+
+/// Attributes of a code block that leave it a Rust example
+const RUST_ATTRIBUTES: [&str; 7] = [
+    "rust",
+    "ignore",
+    "should_panic",
+    "no_run",
+    "compile_fail",
+    "test_harness",
+    "standalone_crate",
+];
+
+/// The name of the item on a line that declares one
+static ITEM: LazyLock<Regex> = LazyLock::new(|| {
+    Regex::new(
+        r#"^\s*(?:pub(?:\([^)]*\))?\s+)?(?:(?:const|async|unsafe|extern\s+"[^"]*")\s+)*(?:fn|struct|enum|trait|mod|type|const|static|union)\s+(\w+)|^\s*macro_rules!\s*(\w+)"#,
+    )
+    .unwrap()
+});
+
+/// The Rust code blocks in the doc comments of `content`
+pub(super) fn parse(content: &str) -> Vec<DocTest> {
+    let mut doc_tests: Vec<DocTest> = Vec::new();
+    // Examples waiting for the item their comment documents
+    let mut undocumented = 0;
+    // The fence of the code block being read
+    let mut fence: Option<&str> = None;
+
+    for (index, line) in content.lines().enumerate() {
+        let trimmed = line.trim_start();
+        let (text, outer) = if let Some(text) = trimmed.strip_prefix("//!") {
+            (text, false)
+        } else if let Some(text) = trimmed.strip_prefix("///")
+            && !text.starts_with('/')
+        {
+            (text, true)
+        } else {
+            fence = None;
+            if undocumented > 0 && !trimmed.is_empty() && !trimmed.starts_with("#[") {
+                let item = ITEM
+                    .captures(line)
+                    .and_then(|captures| captures.get(1).or(captures.get(2)))
+                    .map(|name| name.as_str().to_string());
+                let start = doc_tests.len() - undocumented;
+                for doc_test in &mut doc_tests[start..] {
+                    doc_test.item = item.clone();
+                }
+                undocumented = 0;
+            }
+            continue;
+        };
+
+        let text = text.trim();
+        match fence {
+            Some(open) if text.starts_with(open) => fence = None,
+            Some(_) => {}
+            None => {
+                let Some(open) = ["```", "~~~"]
+                    .into_iter()
+                    .find(|open| text.starts_with(open))
+                else {
+                    continue;
+                };
+                let attributes: Vec<String> = text[open.len()..]
+                    .split(|c: char| c == ',' || c.is_whitespace())
+                    .filter(|attribute| !attribute.is_empty())
+                    .map(str::to_string)
+                    .collect();
+                fence = Some(open);
+                if attributes
+                    .iter()
+                    .all(|attribute| is_rust_attribute(attribute))
+                {
+                    doc_tests.push(DocTest {
+                        item: None,
+                        line_number: index + 1,
+                        attributes: attributes.into_iter().filter(|a| a != "rust").collect(),
+                    });
+                    if outer {
+                        undocumented += 1;
+                    }
+                }
+            }
+        }
+    }
+
+    doc_tests
+}
+
+fn is_rust_attribute(attribute: &str) -> bool {
+    RUST_ATTRIBUTES.contains(&attribute)
+        || attribute.starts_with("edition")
+        || attribute.starts_with("ignore-")
+}
@@ -0,0 +1,858 @@
+use globset::{Glob, GlobMatcher};
+use regex::Regex;
+use serde::{Deserialize, Serialize};
+use sheila::cache::RunCache;
+use sheila::runners::{TagExpr, TestFilter};
+use sheila::stable_hash;
+use std::collections::{BTreeMap, BTreeSet, HashMap, HashSet};
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::time::SystemTime;
+
+use crate::config::SheilaConfig;
+use cfg::Cfg;
+use scope::Scope;
+
+mod cfg;
+mod doc;
+#[cfg(not(feature = "syn-discovery"))]
+mod pattern;
+mod scope;
+#[cfg(feature = "syn-discovery")]
+mod syntax;
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct TestFile {
+    pub path: PathBuf,
+    pub suites: Vec<TestSuite>,
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub fixtures: Vec<TestFixture>,
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub hooks: Vec<TestHook>,
+    /// Examples in the file's doc comments, for files of a library
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub doc_tests: Vec<DocTest>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct TestSuite {
+    pub name: String,
+    pub tests: Vec<TestFunction>,
+    pub tags: Vec<String>,
+    pub line_number: Option<usize>,
+    /// Line of the closing brace of the suite's module
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub end_line: Option<usize>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct TestFunction {
+    pub name: String,
+    /// Path of the module the test is declared in within its file, e.g.
+    /// `math_tests` for a test in `mod math_tests { ... }`
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub module: Option<String>,
+    pub tags: Vec<String>,
+    pub line_number: Option<usize>,
+    /// Line of the closing brace of the test function
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub end_line: Option<usize>,
+    pub ignored: bool,
+    pub timeout: Option<u64>,
+    pub retries: Option<u32>,
+    /// Predicates of the `#[cfg(...)]` attributes on the test and the
+    /// modules it's in
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub cfg: Vec<String>,
+    /// The `cfg` predicate that leaves the test out of the test build on
+    /// this machine, if one does
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub disabled_by: Option<String>,
+    /// The cases of a `#[sheila::params(...)]` test, one per parameter
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub cases: Vec<TestCase>,
+}
+
+/// One parameter of a parameterized test, run as a test of its own
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct TestCase {
+    /// Position of the parameter, counting from 1, which cargo names the
+    /// case by: `case_<index>`
+    pub index: usize,
+    /// The parameter as written, or a string parameter's value
+    pub name: String,
+}
+
+impl TestFunction {
+    /// Whether the test is built, and so can run, on this machine
+    pub fn is_applicable(&self) -> bool {
+        self.disabled_by.is_none()
+    }
+
+    /// How many tests cargo runs it as: one per case if it's
+    /// parameterized, otherwise one
+    pub fn case_count(&self) -> usize {
+        self.cases.len().max(1)
+    }
+}
+
+/// A `#[sheila::fixture]` function
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct TestFixture {
+    pub name: String,
+    pub scope: String,
+    pub depends_on: Vec<String>,
+    pub line_number: Option<usize>,
+}
+
+/// A `#[sheila::before_all]`, `after_all`, `before_each` or `after_each`
+/// function
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct TestHook {
+    pub name: String,
+    /// The hook attribute, e.g. `before_all`
+    pub kind: String,
+    pub line_number: Option<usize>,
+}
+
+/// A code block in a doc comment that rustdoc runs as a test
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct DocTest {
+    /// The item the comment documents, or `None` for a module's own
+    /// `//!` comment or an item that couldn't be told
+    pub item: Option<String>,
+    /// Line of the opening fence, which rustdoc names the test by
+    pub line_number: usize,
+    /// Attributes after the fence, e.g. `no_run` or `should_panic`
+    pub attributes: Vec<String>,
+}
+
+impl DocTest {
+    pub fn ignored(&self) -> bool {
+        self.attributes
+            .iter()
+            .any(|attribute| attribute == "ignore" || attribute.starts_with("ignore-"))
+    }
+}
+
+impl TestFile {
+    pub fn has_tests(&self) -> bool {
+        self.suites.iter().any(|suite| !suite.tests.is_empty())
+    }
+
+    /// The module path of this file within its crate, e.g. `api::auth` for
+    /// `src/api/auth.rs`, if it lies under a `src` directory
+    pub fn module_path(&self) -> Option<String> {
+        let components: Vec<String> = self
+            .path
+            .with_extension("")
+            .components()
+            .map(|component| component.as_os_str().to_string_lossy().into_owned())
+            .collect();
+        let src = components
+            .iter()
+            .rposition(|component| component == "src")?;
+
+        let mut segments = components[src + 1..].to_vec();
+        if matches!(
+            segments.last().map(String::as_str),
+            Some("lib" | "main" | "mod")
+        ) {
+            segments.pop();
+        }
+        Some(segments.join("::"))
+    }
+
+    /// Exact filters selecting this file's tests in the test executables.
+    /// Suite modules are left out of the paths, as `#[sheila::suite]`
+    /// renames them to modules the runner doesn't count.
+    pub fn test_filters(&self) -> Vec<TestFilter> {
+        let module_path = self.module_path().filter(|path| !path.is_empty());
+        let suites: HashSet<&str> = self
+            .suites
+            .iter()
+            .map(|suite| suite.name.as_str())
+            .collect();
+        self.suites
+            .iter()
+            .flat_map(|suite| suite.tests.iter())
+            .filter(|test| test.is_applicable())
+            .flat_map(|test| {
+                let modules = test
+                    .module
+                    .iter()
+                    .flat_map(|module| module.split("::"))
+                    .filter(|module| !suites.contains(module));
+                let path = module_path
+                    .as_deref()
+                    .into_iter()
+                    .chain(modules)
+                    .chain(std::iter::once(test.name.as_str()))
+                    .collect::<Vec<_>>()
+                    .join("::");
+                // A parameterized test is selected by its cases
+                if test.cases.is_empty() {
+                    vec![TestFilter::exact(path)]
+                } else {
+                    test.cases
+                        .iter()
+                        .map(|case| TestFilter::exact(format!("{}::case_{}", path, case.index)))
+                        .collect()
+                }
+            })
+            .collect()
+    }
+}
+
+/// Discovered tests gathered under a tag or a crate name
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct TestGroup {
+    pub name: String,
+    pub files: Vec<TestFile>,
+}
+
+impl TestGroup {
+    /// One group per tag holding the tests that carry it, directly or
+    /// through their suite, plus an `untagged` group for the rest. A test
+    /// with several tags shows up in each of their groups.
+    pub fn by_tag(test_files: &[TestFile]) -> Vec<TestGroup> {
+        let tags: BTreeSet<&String> = test_files
+            .iter()
+            .flat_map(|file| &file.suites)
+            .flat_map(|suite| {
+                suite
+                    .tags
+                    .iter()
+                    .chain(suite.tests.iter().flat_map(|test| &test.tags))
+            })
+            .collect();
+
+        let mut groups: Vec<TestGroup> = tags
+            .into_iter()
+            .map(|tag| {
+                Self::select(tag, test_files, |suite_tags, test| {
+                    suite_tags.contains(tag) || test.tags.contains(tag)
+                })
+            })
+            .collect();
+
+        let untagged = Self::select("untagged", test_files, |suite_tags, test| {
+            suite_tags.is_empty() && test.tags.is_empty()
+        });
+        if !untagged.files.is_empty() {
+            groups.push(untagged);
+        }
+        groups
+    }
+
+    /// One group per crate, named by `crate_of` for each file
+    pub fn by_crate(test_files: &[TestFile], crate_of: impl Fn(&Path) -> String) -> Vec<TestGroup> {
+        let mut crates: BTreeMap<String, Vec<TestFile>> = BTreeMap::new();
+        for file in test_files {
+            crates
+                .entry(crate_of(&file.path))
+                .or_default()
+                .push(file.clone());
+        }
+        crates
+            .into_iter()
+            .map(|(name, files)| TestGroup { name, files })
+            .collect()
+    }
+
+    fn select(
+        name: &str,
+        test_files: &[TestFile],
+        keep: impl Fn(&[String], &TestFunction) -> bool,
+    ) -> TestGroup {
+        let files = test_files
+            .iter()
+            .filter_map(|file| {
+                let mut file = file.clone();
+                for suite in &mut file.suites {
+                    let suite_tags = suite.tags.clone();
+                    suite.tests.retain(|test| keep(&suite_tags, test));
+                }
+                file.has_tests().then_some(file)
+            })
+            .collect();
+        TestGroup {
+            name: name.to_string(),
+            files,
+        }
+    }
+}
+
+/// How many tests a set of discovered files holds
+#[derive(Debug, Clone, Copy, Default, Serialize, Deserialize)]
+pub struct TestCounts {
+    pub files: usize,
+    pub suites: usize,
+    pub tests: usize,
+    pub ignored: usize,
+    /// Tests left out of the build on this machine by a `cfg`
+    #[serde(default)]
+    pub disabled: usize,
+}
+
+impl TestCounts {
+    pub fn of(test_files: &[TestFile]) -> Self {
+        let suites = test_files.iter().flat_map(|file| &file.suites);
+        let tests = suites.clone().flat_map(|suite| &suite.tests);
+        Self {
+            files: test_files.len(),
+            suites: suites.filter(|suite| !suite.tests.is_empty()).count(),
+            tests: tests.clone().count(),
+            ignored: tests.clone().filter(|test| test.ignored).count(),
+            disabled: tests.filter(|test| !test.is_applicable()).count(),
+        }
+    }
+}
+
+/// The tags, suites and tests last discovered in the project, cached so
+/// shell completion doesn't have to walk the source tree
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct DiscoveryIndex {
+    pub tags: BTreeSet<String>,
+    pub suites: BTreeSet<String>,
+    pub tests: BTreeSet<String>,
+}
+
+impl DiscoveryIndex {
+    const FILE: &'static str = "discovery.json";
+
+    pub fn from_files(test_files: &[TestFile]) -> Self {
+        let mut index = Self::default();
+        for suite in test_files.iter().flat_map(|file| &file.suites) {
+            index.suites.insert(suite.name.clone());
+            index.tags.extend(suite.tags.iter().cloned());
+            for test in &suite.tests {
+                index.tests.insert(test.name.clone());
+                index.tags.extend(test.tags.iter().cloned());
+            }
+        }
+        index
+    }
+
+    /// The index for the current project, discovering it if it hasn't been
+    /// cached yet
+    pub fn current() -> Self {
+        let cached = RunCache::current()
+            .ok()
+            .and_then(|cache| fs::read_to_string(cache.dir().join(Self::FILE)).ok())
+            .and_then(|content| serde_json::from_str(&content).ok());
+
+        cached.unwrap_or_else(|| {
+            TestDiscovery::new()
+                .and_then(|discovery| discovery.discover_current())
+                .map(|test_files| Self::from_files(&test_files))
+                .unwrap_or_default()
+        })
+    }
+
+    pub fn save(&self) -> sheila::Result<()> {
+        let cache = RunCache::current()?;
+        fs::create_dir_all(cache.dir())?;
+        fs::write(cache.dir().join(Self::FILE), serde_json::to_string(self)?)?;
+        Ok(())
+    }
+}
+
+/// Files parsed by earlier discoveries in the project, kept under its
+/// [`RunCache`] so only the files that changed since are parsed again
+#[derive(Debug, Serialize, Deserialize)]
+struct DiscoveryCache {
+    /// Version of the parser the files were parsed with. A cache from
+    /// another one is discarded, as it may have parsed them differently.
+    parser: String,
+    files: HashMap<PathBuf, CachedFile>,
+    #[serde(skip)]
+    changed: bool,
+    /// Default features of the packages looked up so far, by manifest
+    #[serde(skip)]
+    features: HashMap<PathBuf, HashSet<String>>,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+struct CachedFile {
+    modified: Option<SystemTime>,
+    len: u64,
+    /// Hash of the contents, for telling a file that was only touched from
+    /// one that changed
+    hash: u64,
+    test_file: TestFile,
+}
+
+impl DiscoveryCache {
+    const FILE: &'static str = "discovery-files.json";
+    /// Changed whenever more is recorded of each file, so files parsed
+    /// before are parsed again
+    const FORMAT: u32 = 2;
+
+    fn parser() -> String {
+        let parser = if cfg!(feature = "syn-discovery") {
+            "syn"
+        } else {
+            "pattern"
+        };
+        format!("{}-{}-{}", env!("CARGO_PKG_VERSION"), parser, Self::FORMAT)
+    }
+
+    fn load() -> Self {
+        let cached = RunCache::current()
+            .ok()
+            .and_then(|cache| fs::read_to_string(cache.dir().join(Self::FILE)).ok())
+            .and_then(|content| serde_json::from_str::<Self>(&content).ok())
+            .filter(|cache| cache.parser == Self::parser());
+
+        cached.unwrap_or_else(|| Self {
+            parser: Self::parser(),
+            files: HashMap::new(),
+            changed: false,
+            features: HashMap::new(),
+        })
+    }
+
+    /// Default features of the package `path` is in, if it's in one
+    fn features_for(&mut self, path: &Path) -> Option<&HashSet<String>> {
+        let manifest = cfg::package_manifest(path)?;
+        Some(
+            self.features
+                .entry(manifest)
+                .or_insert_with_key(|manifest| cfg::default_features(manifest)),
+        )
+    }
+
+    /// Drop the files under `dir` that weren't `seen` in it, as they've
+    /// been deleted or moved
+    fn forget_missing(&mut self, dir: &Path, seen: &HashSet<PathBuf>) {
+        let before = self.files.len();
+        self.files
+            .retain(|path, _| !path.starts_with(dir) || seen.contains(path));
+        self.changed |= self.files.len() != before;
+    }
+
+    fn save(&self) -> sheila::Result<()> {
+        if !self.changed {
+            return Ok(());
+        }
+        let cache = RunCache::current()?;
+        fs::create_dir_all(cache.dir())?;
+        fs::write(cache.dir().join(Self::FILE), serde_json::to_string(self)?)?;
+        Ok(())
+    }
+}
+
+/// What one file holds
+#[derive(Debug, Default)]
+struct ParsedFile {
+    suites: Vec<TestSuite>,
+    /// Tests that aren't in a suite
+    tests: Vec<TestFunction>,
+    fixtures: Vec<TestFixture>,
+    hooks: Vec<TestHook>,
+}
+
+impl ParsedFile {
+    fn into_test_file(self, path: &Path) -> TestFile {
+        let mut suites = self.suites;
+        if !self.tests.is_empty() {
+            suites.push(TestSuite {
+                name: "Standalone Tests".to_string(),
+                tests: self.tests,
+                tags: Vec::new(),
+                line_number: None,
+                end_line: None,
+            });
+        }
+
+        TestFile {
+            path: path.to_path_buf(),
+            suites,
+            fixtures: self.fixtures,
+            hooks: self.hooks,
+            doc_tests: Vec::new(),
+        }
+    }
+}
+
+pub struct TestDiscovery {
+    #[cfg(not(feature = "syn-discovery"))]
+    patterns: pattern::Patterns,
+    scope: Scope,
+}
+
+impl TestDiscovery {
+    pub fn new() -> color_eyre::Result<Self> {
+        let config = SheilaConfig::load()
+            .map_err(|e| color_eyre::eyre::eyre!("Failed to load sheila.toml: {}", e))?;
+        Ok(Self {
+            #[cfg(not(feature = "syn-discovery"))]
+            patterns: pattern::Patterns::new()?,
+            scope: Scope::new(&config.discovery, std::env::current_dir().ok())?,
+        })
+    }
+
+    /// Discovery for the project at `root`, with its `sheila.toml`, rather
+    /// than the working directory's
+    pub fn in_dir(root: &Path) -> color_eyre::Result<Self> {
+        let config = SheilaConfig::load_in(root)
+            .map_err(|e| color_eyre::eyre::eyre!("Failed to load sheila.toml: {}", e))?;
+        Ok(Self {
+            #[cfg(not(feature = "syn-discovery"))]
+            patterns: pattern::Patterns::new()?,
+            scope: Scope::new(&config.discovery, Some(root.to_path_buf()))?,
+        })
+    }
+
+    pub fn discover(&self, path: &Path) -> color_eyre::Result<Vec<TestFile>> {
+        if path.is_file() {
+            if self.is_rust_file(path) {
+                let mut cache = DiscoveryCache::load();
+                let test_file = self.parse_test_file(path, &mut cache)?;
+                let _ = cache.save();
+                Ok(vec![test_file])
+            } else {
+                Ok(vec![])
+            }
+        } else if path.is_dir() {
+            self.discover_in_directory(path)
+        } else {
+            Ok(vec![])
+        }
+    }
+
+    pub fn discover_current(&self) -> color_eyre::Result<Vec<TestFile>> {
+        let current_dir = std::env::current_dir()?;
+        let test_files = self.discover_in_directory(&current_dir)?;
+
+        // Keep shell completion in step with the project; a stale index
+        // only means stale suggestions
+        let _ = DiscoveryIndex::from_files(&test_files).save();
+        Ok(test_files)
+    }
+
+    fn is_rust_file(&self, path: &Path) -> bool {
+        path.extension()
+            .and_then(|ext| ext.to_str())
+            .map(|ext| ext == "rs")
+            .unwrap_or(false)
+    }
+
+    fn discover_in_directory(&self, dir: &Path) -> color_eyre::Result<Vec<TestFile>> {
+        let mut test_files = Vec::new();
+        let mut cache = DiscoveryCache::load();
+        let mut seen = HashSet::new();
+
+        for path in self.scope.rust_files(dir) {
+            seen.insert(path.clone());
+            if let Ok(test_file) = self.parse_test_file(&path, &mut cache)
+                && (!test_file.suites.is_empty()
+                    || !test_file.fixtures.is_empty()
+                    || !test_file.hooks.is_empty()
+                    || !test_file.doc_tests.is_empty())
+            {
+                test_files.push(test_file);
+            }
+        }
+
+        cache.forget_missing(dir, &seen);
+        // A cache that can't be written only means parsing again next time
+        let _ = cache.save();
+        Ok(test_files)
+    }
+
+    /// Discover the tests in `path`, reusing what `cache` holds for it if
+    /// the file hasn't changed since
+    fn parse_test_file(
+        &self,
+        path: &Path,
+        cache: &mut DiscoveryCache,
+    ) -> color_eyre::Result<TestFile> {
+        let metadata = fs::metadata(path)?;
+        let modified = metadata.modified().ok();
+        if let Some(cached) = cache.files.get(path)
+            && modified.is_some()
+            && cached.modified == modified
+            && cached.len == metadata.len()
+        {
+            let mut test_file = cached.test_file.clone();
+            mark_disabled(&mut test_file, cache);
+            return Ok(test_file);
+        }
+
+        // Touched but not changed, e.g. by switching branches and back
+        let content = fs::read_to_string(path)?;
+        let hash = stable_hash(content.as_bytes());
+        let mut test_file = match cache.files.get(path) {
+            Some(cached) if cached.hash == hash => cached.test_file.clone(),
+            _ => self.parse_content(path, &content)?,
+        };
+        mark_disabled(&mut test_file, cache);
+
+        cache.files.insert(
+            path.to_path_buf(),
+            CachedFile {
+                modified,
+                len: metadata.len(),
+                hash,
+                test_file: test_file.clone(),
+            },
+        );
+        cache.changed = true;
+        Ok(test_file)
+    }
+
+    fn parse_content(&self, path: &Path, content: &str) -> color_eyre::Result<TestFile> {
+        #[cfg(feature = "syn-discovery")]
+        let parsed = syntax::parse(content)?;
+        #[cfg(not(feature = "syn-discovery"))]
+        let parsed = self.patterns.parse(content);
+
+        let mut test_file = parsed.into_test_file(path);
+        if is_library_source(path) {
+            test_file.doc_tests = doc::parse(content);
+        }
+        Ok(test_file)
+    }
+
+    /// The tests of `test_files` that any of `targets` select, if there are
+    /// some, that match `tags` and `grep`
+    pub fn filter_tests(
+        &self,
+        test_files: Vec<TestFile>,
+        targets: &[&str],
+        tags: Option<&TagExpr>,
+        grep: Option<&str>,
+    ) -> color_eyre::Result<Vec<TestFile>> {
+        let grep_regex = if let Some(pattern) = grep {
+            Some(Regex::new(pattern)?)
+        } else {
+            None
+        };
+
+        let mut filtered_files = Vec::new();
+
+        for mut test_file in test_files {
+            if !targets.is_empty() {
+                test_file = self.filter_by_targets(test_file, targets)?;
+            }
+
+            if let Some(tags) = tags {
+                test_file = self.filter_by_tags(test_file, tags);
+            }
+
+            if let Some(regex) = &grep_regex {
+                test_file = self.filter_by_grep(test_file, regex);
+            }
+
+            if test_file.suites.iter().any(|suite| !suite.tests.is_empty()) {
+                filtered_files.push(test_file);
+            }
+        }
+
+        Ok(filtered_files)
+    }
+
+    /// Keep the tests of `test_file` that at least one of `targets` selects
+    fn filter_by_targets(
+        &self,
+        mut test_file: TestFile,
+        targets: &[&str],
+    ) -> color_eyre::Result<TestFile> {
+        let selections = targets
+            .iter()
+            .map(|target| self.filter_by_target(test_file.clone(), target))
+            .collect::<color_eyre::Result<Vec<_>>>()?;
+
+        for (i, suite) in test_file.suites.iter_mut().enumerate() {
+            suite.tests.retain_mut(|test| {
+                let selected: Vec<&TestFunction> = selections
+                    .iter()
+                    .flat_map(|selection| selection.suites[i].tests.iter())
+                    .filter(|selected| {
+                        selected.name == test.name && selected.line_number == test.line_number
+                    })
+                    .collect();
+                // The cases any of the targets selects
+                test.cases.retain(|case| {
+                    selected
+                        .iter()
+                        .any(|selected| selected.cases.contains(case))
+                });
+                !selected.is_empty()
+            });
+        }
+        Ok(test_file)
+    }
+
+    fn filter_by_target(
+        &self,
+        mut test_file: TestFile,
+        target: &str,
+    ) -> color_eyre::Result<TestFile> {
+        if let Some((name, case)) = target.split_once('#') {
+            for suite in &mut test_file.suites {
+                suite.tests.retain_mut(|test| {
+                    let named = test.name == name
+                        || test
+                            .module
+                            .as_ref()
+                            .is_some_and(|module| format!("{}::{}", module, test.name) == name);
+                    test.cases
+                        .retain(|c| c.index.to_string() == case || c.name == case);
+                    named && !test.cases.is_empty()
+                });
+            }
+        } else if target.contains(':') {
+            let parts: Vec<&str> = target.split(':').collect();
+            if parts.len() == 2 {
+                let file_part = parts[0];
+                if let Ok(line_num) = parts[1].parse::<usize>() {
+                    if test_file.path.to_string_lossy().contains(file_part)
+                        || test_file.path.ends_with(file_part)
+                    {
+                        // The test whose definition the line falls in: the
+                        // last one starting at or before it, unless it's
+                        // known to end before it
+                        let enclosing = test_file
+                            .suites
+                            .iter()
+                            .flat_map(|suite| suite.tests.iter())
+                            .filter(|test| test.end_line.is_none_or(|end| line_num <= end))
+                            .filter_map(|test| test.line_number)
+                            .filter(|ln| *ln <= line_num)
+                            .max();
+                        for suite in &mut test_file.suites {
+                            suite.tests.retain(|test| {
+                                enclosing.is_some() && test.line_number == enclosing
+                            });
+                        }
+                    } else {
+                        for suite in &mut test_file.suites {
+                            suite.tests.clear();
+                        }
+                    }
+                }
+            }
+        } else if is_glob(target) {
+            let glob = Glob::new(target)?.compile_matcher();
+            if target.contains('/') {
+                if !matches_path(&glob, &test_file.path) {
+                    for suite in &mut test_file.suites {
+                        suite.tests.clear();
+                    }
+                }
+            } else {
+                for suite in &mut test_file.suites {
+                    suite.tests.retain(|test| {
+                        glob.is_match(&test.name)
+                            || test.module.as_ref().is_some_and(|module| {
+                                glob.is_match(format!("{}::{}", module, test.name))
+                            })
+                    });
+                }
+            }
+        } else if target.ends_with(".rs") || target.contains('/') {
+            if test_file.path.to_string_lossy().contains(target) || test_file.path.ends_with(target)
+            {
+            } else {
+                for suite in &mut test_file.suites {
+                    suite.tests.clear();
+                }
+            }
+        } else if let Some(tag) = target.strip_prefix('@') {
+            for suite in &mut test_file.suites {
+                let suite_tagged = suite.tags.iter().any(|t| t == tag);
+                suite
+                    .tests
+                    .retain(|test| suite_tagged || test.tags.iter().any(|t| t == tag));
+            }
+        } else {
+            for suite in &mut test_file.suites {
+                suite.tests.retain(|test| {
+                    test.name.contains(target) || test.tags.iter().any(|tag| tag.contains(target))
+                });
+            }
+        }
+
+        Ok(test_file)
+    }
+
+    fn filter_by_tags(&self, mut test_file: TestFile, filter: &TagExpr) -> TestFile {
+        for suite in &mut test_file.suites {
+            let suite_tags = suite.tags.clone();
+            suite.tests.retain(|test| {
+                let tags: Vec<&String> = suite_tags.iter().chain(test.tags.iter()).collect();
+                filter.matches(&tags)
+            });
+        }
+        test_file
+    }
+
+    fn filter_by_grep(&self, mut test_file: TestFile, regex: &Regex) -> TestFile {
+        for suite in &mut test_file.suites {
+            suite.tests.retain(|test| {
+                regex.is_match(&test.name) || test.tags.iter().any(|tag| regex.is_match(tag))
+            });
+        }
+        test_file
+    }
+}
+
+/// Whether `path` is a source file of a library rather than of a binary,
+/// an integration test or an example, so rustdoc tests its doc comments
+fn is_library_source(path: &Path) -> bool {
+    let components: Vec<_> = path.components().map(|c| c.as_os_str()).collect();
+    let Some(src) = components.iter().rposition(|component| *component == "src") else {
+        return false;
+    };
+    match &components[src + 1..] {
+        [file] => *file != "main.rs",
+        [dir, ..] => *dir != "bin",
+        [] => false,
+    }
+}
+
+/// Whether `target` is a glob pattern rather than a path, name or tag
+pub fn is_glob(target: &str) -> bool {
+    target.contains(['*', '?', '[', '{'])
+}
+
+/// Whether `glob` matches `path`, as it is or relative to the working
+/// directory
+fn matches_path(glob: &GlobMatcher, path: &Path) -> bool {
+    glob.is_match(path)
+        || std::env::current_dir()
+            .ok()
+            .and_then(|dir| {
+                path.strip_prefix(dir)
+                    .ok()
+                    .map(|relative| glob.is_match(relative))
+            })
+            .unwrap_or(false)
+}
+
+/// Mark the tests of `test_file` that a `cfg` leaves out of the build here,
+/// judging features by the defaults of the file's package
+fn mark_disabled(test_file: &mut TestFile, cache: &mut DiscoveryCache) {
+    let tests = test_file
+        .suites
+        .iter_mut()
+        .flat_map(|suite| &mut suite.tests);
+    let mut tests = tests.filter(|test| !test.cfg.is_empty()).peekable();
+    if tests.peek().is_none() {
+        return;
+    }
+
+    let features = cache.features_for(&test_file.path);
+    for test in tests {
+        test.disabled_by = test
+            .cfg
+            .iter()
+            .find(|predicate| {
+                Cfg::parse(predicate).and_then(|cfg| cfg.eval(features)) == Some(false)
+            })
+            .map(|predicate| format!("cfg({})", predicate));
+    }
+}
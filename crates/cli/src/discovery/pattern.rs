@@ -0,0 +1,180 @@
+//! Discovery by matching attributes with regexes, for builds without `syn`.
+//! It only sees `#[sheila::...]` attributes written directly above the item
+//! they're on, and arguments given in the attribute itself, and it can't
+//! tell which module a test is in, so it puts every test in the file's
+//! standalone tests.
+
+use super::{ParsedFile, TestFixture, TestFunction, TestHook, TestSuite};
+use regex::Regex;
+use std::collections::HashMap;
+
+pub(super) struct Patterns {
+    test_function: Regex,
+    suite: Regex,
+    fixture: Regex,
+    hook: Regex,
+}
+
+impl Patterns {
+    pub(super) fn new() -> color_eyre::Result<Self> {
+        Ok(Self {
+            test_function: Regex::new(
+                r#"#\[sheila::test(?:\([^\)]*\))?\]\s*\n\s*(?:pub\s+)?fn\s+(\w+)"#,
+            )?,
+            suite: Regex::new(
+                r#"#\[sheila::suite(?:\([^\)]*\))?\]\s*\n\s*(?:pub\s+)?mod\s+(\w+)"#,
+            )?,
+            fixture: Regex::new(
+                r#"#\[sheila::fixture(?:\([^\)]*\))?\]\s*\n\s*(?:pub\s+)?fn\s+(\w+)"#,
+            )?,
+            hook: Regex::new(
+                r#"#\[sheila::(before_all|after_all|before_each|after_each)\]\s*\n\s*(?:pub\s+)?fn\s+(\w+)"#,
+            )?,
+        })
+    }
+
+    pub(super) fn parse(&self, content: &str) -> ParsedFile {
+        ParsedFile {
+            suites: self.parse_suites(content),
+            tests: self.parse_test_functions(content),
+            fixtures: self.parse_fixtures(content),
+            hooks: self.parse_hooks(content),
+        }
+    }
+
+    fn parse_fixtures(&self, content: &str) -> Vec<TestFixture> {
+        self.fixture
+            .captures_iter(content)
+            .map(|fixture_match| {
+                let whole = fixture_match.get(0).unwrap();
+                let attributes = parse_attributes(whole.as_str());
+                TestFixture {
+                    name: fixture_match[1].to_string(),
+                    scope: attributes
+                        .get("scope")
+                        .cloned()
+                        .unwrap_or_else(|| "test".to_string()),
+                    depends_on: attributes
+                        .get("depends_on")
+                        .map(|deps| parse_list(deps))
+                        .unwrap_or_default(),
+                    line_number: Some(line_of(content, whole.start())),
+                }
+            })
+            .collect()
+    }
+
+    fn parse_hooks(&self, content: &str) -> Vec<TestHook> {
+        self.hook
+            .captures_iter(content)
+            .map(|hook_match| TestHook {
+                name: hook_match[2].to_string(),
+                kind: hook_match[1].to_string(),
+                line_number: Some(line_of(content, hook_match.get(0).unwrap().start())),
+            })
+            .collect()
+    }
+
+    fn parse_suites(&self, content: &str) -> Vec<TestSuite> {
+        self.suite
+            .captures_iter(content)
+            .map(|suite_match| {
+                let whole = suite_match.get(0).unwrap();
+                TestSuite {
+                    name: suite_match[1].to_string(),
+                    tests: Vec::new(),
+                    tags: parse_attributes(whole.as_str())
+                        .get("tags")
+                        .map(|tags| parse_list(tags))
+                        .unwrap_or_default(),
+                    line_number: Some(line_of(content, whole.start())),
+                    end_line: None,
+                }
+            })
+            .collect()
+    }
+
+    fn parse_test_functions(&self, content: &str) -> Vec<TestFunction> {
+        self.test_function
+            .captures_iter(content)
+            .map(|test_match| {
+                let whole = test_match.get(0).unwrap();
+                let attributes = parse_attributes(whole.as_str());
+                TestFunction {
+                    name: test_match[1].to_string(),
+                    module: None,
+                    tags: attributes
+                        .get("tags")
+                        .map(|tags| parse_list(tags))
+                        .unwrap_or_default(),
+                    line_number: Some(line_of(content, whole.start())),
+                    end_line: None,
+                    ignored: attributes.contains_key("ignore"),
+                    timeout: attributes.get("timeout").and_then(|t| t.parse().ok()),
+                    retries: attributes.get("retries").and_then(|r| r.parse().ok()),
+                    cfg: Vec::new(),
+                    disabled_by: None,
+                    cases: Vec::new(),
+                }
+            })
+            .collect()
+    }
+}
+
+/// The line, counting from 1, that byte `offset` of `content` is on
+fn line_of(content: &str, offset: usize) -> usize {
+    content[..offset].matches('\n').count() + 1
+}
+
+fn parse_attributes(macro_text: &str) -> HashMap<String, String> {
+    let mut attributes = HashMap::new();
+    if let Some(attrs_start) = macro_text.find('(')
+        && let Some(attrs_end) = macro_text.rfind(')')
+    {
+        let attrs_text = &macro_text[attrs_start + 1..attrs_end];
+
+        for attr in split_top_level(attrs_text) {
+            let attr = attr.trim();
+            if let Some(eq_pos) = attr.find('=') {
+                let key = attr[..eq_pos].trim();
+                let value = attr[eq_pos + 1..].trim().trim_matches('"');
+                attributes.insert(key.to_string(), value.to_string());
+            } else {
+                attributes.insert(attr.to_string(), "true".to_string());
+            }
+        }
+    }
+
+    attributes
+}
+
+/// The items of a `["a", "b"]` attribute value
+fn parse_list(value: &str) -> Vec<String> {
+    value
+        .trim_matches(|c| c == '[' || c == ']')
+        .split(',')
+        .map(|item| item.trim().trim_matches('"').to_string())
+        .filter(|item| !item.is_empty())
+        .collect()
+}
+
+/// Split macro arguments on the commas that aren't inside a `[...]` list,
+/// so `tags = ["a", "b"], retries = 2` yields two arguments
+fn split_top_level(text: &str) -> Vec<&str> {
+    let mut parts = Vec::new();
+    let mut depth = 0usize;
+    let mut start = 0;
+    for (i, c) in text.char_indices() {
+        match c {
+            '[' => depth += 1,
+            ']' => depth = depth.saturating_sub(1),
+            ',' if depth == 0 => {
+                parts.push(&text[start..i]);
+                start = i + 1;
+            }
+            _ => {}
+        }
+    }
+    parts.push(&text[start..]);
+    parts
+}
@@ -0,0 +1,83 @@
+//! Which files discovery scans. `.sheilaignore` files, written like
+//! `.gitignore` and read from the directories searched and those above
+//! them, leave paths out, as do the `exclude_patterns` globs of
+//! `sheila.toml`. When `include_patterns` are given, only the files
+//! matching one of them are scanned.
+//!
+//! Globs are matched against paths as they are and relative to the working
+//! directory, so `target/**` means the project's `target` directory.
+
+use std::path::{Path, PathBuf};
+
+use globset::{Glob, GlobSet, GlobSetBuilder};
+use ignore::WalkBuilder;
+
+use crate::config::DiscoveryConfig;
+
+/// Name of the files listing paths discovery ignores
+const IGNORE_FILE: &str = ".sheilaignore";
+
+#[derive(Debug, Clone)]
+pub(super) struct Scope {
+    include: Option<GlobSet>,
+    exclude: GlobSet,
+    root: Option<PathBuf>,
+}
+
+impl Scope {
+    /// The scope of the project at `root`, if known
+    pub(super) fn new(config: &DiscoveryConfig, root: Option<PathBuf>) -> color_eyre::Result<Self> {
+        let include = if config.include_patterns.is_empty() {
+            None
+        } else {
+            Some(glob_set(&config.include_patterns)?)
+        };
+        Ok(Self {
+            include,
+            exclude: glob_set(&config.exclude_patterns)?,
+            root,
+        })
+    }
+
+    /// The Rust files under `dir` that are in scope
+    pub(super) fn rust_files(&self, dir: &Path) -> impl Iterator<Item = PathBuf> {
+        let scope = self.clone();
+        let files = WalkBuilder::new(dir)
+            .standard_filters(false)
+            .parents(true)
+            .follow_links(true)
+            .add_custom_ignore_filename(IGNORE_FILE)
+            .filter_entry(move |entry| {
+                !matches(&scope.exclude, entry.path(), scope.root.as_deref())
+            })
+            .build();
+
+        let include = self.include.clone();
+        let root = self.root.clone();
+        files
+            .filter_map(|entry| entry.ok())
+            .filter(|entry| entry.file_type().is_some_and(|kind| kind.is_file()))
+            .map(ignore::DirEntry::into_path)
+            .filter(|path| path.extension().is_some_and(|ext| ext == "rs"))
+            .filter(move |path| {
+                include
+                    .as_ref()
+                    .is_none_or(|include| matches(include, path, root.as_deref()))
+            })
+    }
+}
+
+fn glob_set(patterns: &[String]) -> color_eyre::Result<GlobSet> {
+    let mut builder = GlobSetBuilder::new();
+    for pattern in patterns {
+        builder.add(Glob::new(pattern)?);
+    }
+    Ok(builder.build()?)
+}
+
+fn matches(globs: &GlobSet, path: &Path, root: Option<&Path>) -> bool {
+    globs.is_match(path)
+        || root
+            .and_then(|root| path.strip_prefix(root).ok())
+            .is_some_and(|relative| globs.is_match(relative))
+}
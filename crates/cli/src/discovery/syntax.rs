@@ -0,0 +1,288 @@
+//! Discovery by parsing source files with `syn`. Unlike matching patterns,
+//! this sees attributes however they're laid out, including the separate
+//! `#[sheila::tags(...)]`, `timeout`, `retries`, `ignore` and `params`
+//! attributes, and it sees into inline modules, so tests are put in the suite module
+//! they're declared in.
+
+use super::cfg::Cfg;
+use super::{ParsedFile, TestCase, TestFixture, TestFunction, TestHook, TestSuite};
+use proc_macro2::Span;
+use syn::punctuated::Punctuated;
+use syn::spanned::Spanned;
+use syn::{Attribute, Expr, ExprArray, ExprLit, Item, ItemFn, ItemMod, Lit, Meta, Token};
+
+const HOOKS: [&str; 4] = ["before_all", "after_all", "before_each", "after_each"];
+
+pub(super) fn parse(content: &str) -> syn::Result<ParsedFile> {
+    let file = syn::parse_file(content);
+    let mut parsed = ParsedFile::default();
+    if let Ok(ref file) = file {
+        Visitor::default().visit_items(&file.items, &mut parsed);
+    }
+    // The line tables of every file parsed on this thread are kept until
+    // spans are invalidated, and this file's lines have all been read
+    proc_macro2::extra::invalidate_current_thread_spans();
+    file.map(|_| parsed)
+}
+
+/// Where the visitor is in the file
+#[derive(Default)]
+struct Visitor {
+    /// Names of the modules around the items being visited
+    modules: Vec<String>,
+    /// Index in the parsed suites of the innermost suite module around them
+    suite: Option<usize>,
+    /// `cfg` predicates of the modules around them
+    cfgs: Vec<String>,
+}
+
+impl Visitor {
+    fn visit_items(&mut self, items: &[Item], parsed: &mut ParsedFile) {
+        for item in items {
+            match item {
+                Item::Fn(item_fn) => self.visit_fn(item_fn, parsed),
+                Item::Mod(item_mod) => self.visit_mod(item_mod, parsed),
+                _ => {}
+            }
+        }
+    }
+
+    fn visit_mod(&mut self, item_mod: &ItemMod, parsed: &mut ParsedFile) {
+        let Some((ref brace, ref items)) = item_mod.content else {
+            return;
+        };
+
+        let outer_suite = self.suite;
+        if let Some(attr) = find_sheila(&item_mod.attrs, "suite") {
+            let args = Args::of(attr);
+            self.suite = Some(parsed.suites.len());
+            parsed.suites.push(TestSuite {
+                name: item_mod.ident.to_string(),
+                tests: Vec::new(),
+                tags: args.strings("tags"),
+                line_number: Some(start_line(&item_mod.attrs, item_mod.mod_token.span)),
+                end_line: Some(line(brace.span.close())),
+            });
+        }
+
+        let outer_cfgs = self.cfgs.len();
+        self.cfgs.extend(cfgs(&item_mod.attrs));
+        self.modules.push(item_mod.ident.to_string());
+        self.visit_items(items, parsed);
+        self.modules.pop();
+        self.cfgs.truncate(outer_cfgs);
+        self.suite = outer_suite;
+    }
+
+    fn visit_fn(&self, item_fn: &ItemFn, parsed: &mut ParsedFile) {
+        let attrs = &item_fn.attrs;
+        let name = item_fn.sig.ident.to_string();
+        let line_number = Some(start_line(attrs, item_fn.sig.fn_token.span));
+
+        if let Some(attr) = find_sheila(attrs, "test") {
+            let args = Args::of(attr);
+            let mut tags = args.strings("tags");
+            let mut timeout = args.int("timeout");
+            let mut retries = args.int("retries");
+            let mut ignored = args.flag("ignore");
+            let mut cases = Vec::new();
+
+            // Settings given in attributes of their own rather than as
+            // arguments to `test`
+            for attr in attrs {
+                match modifier(attr) {
+                    Some("tags") => tags.extend(list_args(attr).iter().flat_map(strings)),
+                    Some("timeout") => timeout = list_args(attr).first().and_then(int).or(timeout),
+                    Some("retries") => retries = list_args(attr).first().and_then(int).or(retries),
+                    Some("ignore") => ignored = true,
+                    Some("params") => cases.extend(list_args(attr).iter().map(case_name)),
+                    _ => {}
+                }
+            }
+
+            let test = TestFunction {
+                name,
+                module: (!self.modules.is_empty()).then(|| self.modules.join("::")),
+                tags,
+                line_number,
+                end_line: Some(line(item_fn.block.brace_token.span.close())),
+                ignored,
+                timeout,
+                retries: retries.and_then(|retries| u32::try_from(retries).ok()),
+                cfg: self.cfgs.iter().cloned().chain(cfgs(attrs)).collect(),
+                disabled_by: None,
+                cases: cases
+                    .into_iter()
+                    .enumerate()
+                    .map(|(index, name)| TestCase {
+                        index: index + 1,
+                        name,
+                    })
+                    .collect(),
+            };
+            match self.suite {
+                Some(suite) => parsed.suites[suite].tests.push(test),
+                None => parsed.tests.push(test),
+            }
+        } else if let Some(attr) = find_sheila(attrs, "fixture") {
+            let args = Args::of(attr);
+            parsed.fixtures.push(TestFixture {
+                name,
+                scope: args
+                    .strings("scope")
+                    .pop()
+                    .unwrap_or_else(|| "test".to_string()),
+                depends_on: args.strings("depends_on"),
+                line_number,
+            });
+        } else if let Some(kind) = attrs
+            .iter()
+            .filter_map(sheila_attribute)
+            .find(|kind| HOOKS.contains(kind))
+        {
+            parsed.hooks.push(TestHook {
+                name,
+                kind: kind.to_string(),
+                line_number,
+            });
+        }
+    }
+}
+
+/// The name of a `#[sheila::name]` attribute
+fn sheila_attribute(attr: &Attribute) -> Option<&'static str> {
+    let segments = &attr.path().segments;
+    if segments.len() != 2 || segments[0].ident != "sheila" {
+        return None;
+    }
+    let name = segments[1].ident.to_string();
+    [
+        "test", "suite", "fixture", "tags", "timeout", "retries", "ignore", "params",
+    ]
+    .into_iter()
+    .chain(HOOKS)
+    .find(|known| *known == name)
+}
+
+fn find_sheila<'a>(attrs: &'a [Attribute], name: &str) -> Option<&'a Attribute> {
+    attrs
+        .iter()
+        .find(|attr| sheila_attribute(attr) == Some(name))
+}
+
+/// The setting a `#[sheila::tags]`, `timeout`, `retries`, `ignore` or
+/// `params` attribute gives a test, which `ignore` may also do without the
+/// prefix
+fn modifier(attr: &Attribute) -> Option<&'static str> {
+    match sheila_attribute(attr) {
+        Some(name @ ("tags" | "timeout" | "retries" | "ignore" | "params")) => Some(name),
+        _ if attr.path().is_ident("ignore") => Some("ignore"),
+        _ => None,
+    }
+}
+
+/// The predicates of the `#[cfg(...)]` attributes in `attrs`
+fn cfgs(attrs: &[Attribute]) -> Vec<String> {
+    attrs
+        .iter()
+        .filter(|attr| attr.path().is_ident("cfg"))
+        .filter_map(|attr| match attr.meta {
+            Meta::List(ref list) => {
+                let predicate = list.tokens.to_string();
+                Some(Cfg::parse(&predicate).map_or(predicate, |cfg| cfg.to_string()))
+            }
+            _ => None,
+        })
+        .collect()
+}
+
+/// The arguments of an attribute taking a list, e.g. `#[sheila::tags("a")]`
+fn list_args(attr: &Attribute) -> Vec<Expr> {
+    attr.parse_args_with(Punctuated::<Expr, Token![,]>::parse_terminated)
+        .map(|args| args.into_iter().collect())
+        .unwrap_or_default()
+}
+
+/// The `key = value` and flag arguments of an attribute, e.g.
+/// `#[sheila::test(tags = ["a"], ignore)]`
+struct Args(Vec<Meta>);
+
+impl Args {
+    fn of(attr: &Attribute) -> Self {
+        let args = match attr.meta {
+            Meta::List(_) => attr
+                .parse_args_with(Punctuated::<Meta, Token![,]>::parse_terminated)
+                .map(|args| args.into_iter().collect())
+                .unwrap_or_default(),
+            _ => Vec::new(),
+        };
+        Self(args)
+    }
+
+    fn value(&self, key: &str) -> Option<&Expr> {
+        self.0.iter().find_map(|meta| match meta {
+            Meta::NameValue(pair) if pair.path.is_ident(key) => Some(&pair.value),
+            _ => None,
+        })
+    }
+
+    fn strings(&self, key: &str) -> Vec<String> {
+        self.value(key).map(strings).unwrap_or_default()
+    }
+
+    fn int(&self, key: &str) -> Option<u64> {
+        self.value(key).and_then(int)
+    }
+
+    fn flag(&self, key: &str) -> bool {
+        self.0.iter().any(|meta| match meta {
+            Meta::Path(path) => path.is_ident(key),
+            Meta::NameValue(pair) if pair.path.is_ident(key) => {
+                matches!(pair.value, Expr::Lit(ref lit) if matches!(lit.lit, Lit::Bool(ref b) if b.value))
+            }
+            _ => false,
+        })
+    }
+}
+
+/// The strings in a string literal or an array of them
+fn strings(expr: &Expr) -> Vec<String> {
+    match expr {
+        Expr::Lit(lit) => match lit.lit {
+            Lit::Str(ref s) => vec![s.value()],
+            _ => Vec::new(),
+        },
+        Expr::Array(ExprArray { elems, .. }) => elems.iter().flat_map(strings).collect(),
+        _ => Vec::new(),
+    }
+}
+
+/// How a parameter is shown: a string's value, or anything else as written
+fn case_name(expr: &Expr) -> String {
+    match expr {
+        Expr::Lit(ExprLit {
+            lit: Lit::Str(s), ..
+        }) => s.value(),
+        _ => expr.span().source_text().unwrap_or_default(),
+    }
+}
+
+fn int(expr: &Expr) -> Option<u64> {
+    match expr {
+        Expr::Lit(lit) => match lit.lit {
+            Lit::Int(ref n) => n.base10_parse().ok(),
+            _ => None,
+        },
+        _ => None,
+    }
+}
+
+/// The line an item starts on: that of its first attribute, or of `keyword`
+/// if it has none
+fn start_line(attrs: &[Attribute], keyword: Span) -> usize {
+    line(attrs.first().map_or(keyword, |attr| attr.pound_token.span))
+}
+
+fn line(span: Span) -> usize {
+    span.start().line
+}
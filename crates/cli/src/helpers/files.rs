@@ -5,13 +5,20 @@ use std::{
 
 use anyhow::Result;
 
-use crate::helpers::TargetSpec;
+use crate::discovery::is_glob;
+use crate::helpers::{ReportIndex, TargetSpec};
 
+/// The newest readable report in `dir`, going by the report index and
+/// falling back to file modification times for reports put there by hand
 pub fn get_most_recent_report(dir: &Path) -> Result<Option<PathBuf>> {
     if !dir.exists() {
         return Ok(None);
     }
 
+    if let Some(path) = ReportIndex::load(dir)?.latest(&["json", "csv", "html"]) {
+        return Ok(Some(path));
+    }
+
     let mut most_recent: Option<(PathBuf, SystemTime)> = None;
 
     for entry in std::fs::read_dir(dir)? {
@@ -42,6 +49,13 @@ pub fn get_most_recent_report(dir: &Path) -> Result<Option<PathBuf>> {
 }
 
 pub fn parse_target(target: &str) -> TargetSpec {
+    if let Some((test, case)) = target.split_once('#') {
+        return TargetSpec::Case {
+            test: test.to_string(),
+            case: case.to_string(),
+        };
+    }
+
     if target.contains(':') {
         let parts: Vec<&str> = target.splitn(2, ':').collect();
         if parts.len() == 2 {
@@ -56,6 +70,8 @@ pub fn parse_target(target: &str) -> TargetSpec {
 
     if target.starts_with('@') {
         TargetSpec::Tag(target[1..].to_string())
+    } else if is_glob(target) {
+        TargetSpec::Glob(target.to_string())
     } else if target.contains('/') || target.ends_with(".rs") {
         TargetSpec::File(target.to_string())
     } else {
@@ -0,0 +1,278 @@
+use colored::*;
+use indicatif::ProgressBar;
+use sheila::TestStatus;
+use sheila::reporting::LiveReporter;
+use sheila::runners::{format_err_context, format_mod_name};
+use sheila::test::TestResult;
+use std::collections::HashMap;
+use std::time::{Duration, Instant};
+
+use super::format_duration;
+
+/// Tests expected to take at least this long are called out while they are
+/// still to run
+const LONG_TEST: Duration = Duration::from_secs(5);
+
+/// Shows a run's progress on the terminal: a line per finished test above a
+/// progress bar.
+///
+/// Given the tests' durations from earlier runs, the bar advances by how long
+/// each finished test is expected to take rather than by count, which gives
+/// an estimate of the time left and names slow tests still to come.
+///
+/// When several test executables run at once their tests finish
+/// interleaved, so each line can be labelled with the suite it's from.
+pub struct ProgressReporter {
+    pb: ProgressBar,
+    total_tests: usize,
+    completed: usize,
+    started: Instant,
+    /// Expected duration of each test still to finish, by suite and name
+    remaining: HashMap<(String, String), Duration>,
+    /// Expected duration of a test without recorded timings
+    typical: Duration,
+    expected_total: Duration,
+    expected_done: Duration,
+    hint: Option<String>,
+    show_suites: bool,
+}
+
+impl ProgressReporter {
+    pub fn new(pb: ProgressBar) -> Self {
+        Self {
+            pb,
+            total_tests: 0,
+            completed: 0,
+            started: Instant::now(),
+            remaining: HashMap::new(),
+            typical: Duration::ZERO,
+            expected_total: Duration::ZERO,
+            expected_done: Duration::ZERO,
+            hint: None,
+            show_suites: false,
+        }
+    }
+
+    /// Estimate progress from the expected durations of the tests in the
+    /// run, by suite and test name
+    pub fn with_timings(mut self, expected: HashMap<(String, String), Duration>) -> Self {
+        self.remaining = expected;
+        self
+    }
+
+    /// Label each test with the suite it's from
+    pub fn with_suites(mut self, show_suites: bool) -> Self {
+        self.show_suites = show_suites;
+        self
+    }
+
+    /// Share of the run done, by expected time when timings are known and
+    /// by count otherwise
+    fn fraction(&self) -> f64 {
+        if !self.expected_total.is_zero() {
+            self.expected_done.as_secs_f64() / self.expected_total.as_secs_f64()
+        } else if self.total_tests > 0 {
+            self.completed as f64 / self.total_tests as f64
+        } else {
+            0.0
+        }
+        .min(1.0)
+    }
+
+    fn update(&mut self) {
+        let fraction = self.fraction();
+        self.pb
+            .set_position((fraction * self.pb.length().unwrap_or(0) as f64) as u64);
+
+        let mut prefix = format!("[{}/{}]", self.completed, self.total_tests);
+        if !self.expected_total.is_zero() && fraction > 0.0 && fraction < 1.0 {
+            let left = self.started.elapsed().mul_f64((1.0 - fraction) / fraction);
+            prefix.push_str(&format!(" ~{} left", format_duration(left)));
+        }
+        self.pb.set_prefix(prefix);
+
+        let mut long: Vec<(&String, &Duration)> = self
+            .remaining
+            .iter()
+            .filter(|(_, expected)| **expected >= LONG_TEST)
+            .map(|((_, name), expected)| (name, expected))
+            .collect();
+        long.sort_by(|a, b| b.1.cmp(a.1).then_with(|| a.0.cmp(b.0)));
+        self.hint = (!long.is_empty()).then(|| {
+            let names: Vec<String> = long
+                .iter()
+                .take(2)
+                .map(|(name, expected)| format!("{} ~{}", name, format_duration(**expected)))
+                .collect();
+            let more = long.len().saturating_sub(2);
+            if more > 0 {
+                format!(
+                    "long tests remaining: {} and {} more",
+                    names.join(", "),
+                    more
+                )
+            } else {
+                format!("long tests remaining: {}", names.join(", "))
+            }
+        });
+    }
+}
+
+impl LiveReporter for ProgressReporter {
+    fn on_run_start(&mut self, total_tests: usize) {
+        self.started = Instant::now();
+        self.total_tests = total_tests.max(self.remaining.len());
+
+        let known: Duration = self.remaining.values().sum();
+        if !self.remaining.is_empty() {
+            self.typical = known / self.remaining.len() as u32;
+        }
+        let unknown = self.total_tests - self.remaining.len();
+        self.expected_total = known + self.typical * unknown as u32;
+        self.update();
+    }
+
+    fn on_suite_start(&mut self, name: &str, test_count: usize) {
+        self.pb
+            .set_message(format!("Starting {} ({} tests)", name, test_count));
+    }
+
+    fn on_test_start(&mut self, name: &str, suite: &str) {
+        let name = if self.show_suites {
+            format!("{} {}", label(suite), name)
+        } else {
+            name.to_string()
+        };
+        match self.hint {
+            Some(ref hint) => self.pb.set_message(format!("{} ({})", name, hint)),
+            None => self.pb.set_message(name),
+        }
+    }
+
+    fn on_test_finished(&mut self, result: &TestResult, suite: &str) {
+        self.completed += 1;
+        self.total_tests = self.total_tests.max(self.completed);
+        let key = (format_mod_name(suite), result.qualified_name().to_string());
+        let expected = self.remaining.remove(&key).unwrap_or(self.typical);
+        self.expected_done = (self.expected_done + expected).min(self.expected_total);
+        self.update();
+
+        let duration_ms = result.duration.unwrap_or_default().as_secs_f64() * 1000.0;
+        let line = if result.status == TestStatus::Cached {
+            format!(
+                "{} {} {}",
+                "✓".cyan().bold(),
+                result.name.cyan(),
+                "(cached)".dimmed()
+            )
+        } else if result.status == TestStatus::XFail {
+            format!(
+                "{} {} {}",
+                "x".magenta().bold(),
+                result.name.magenta(),
+                "(xfail)".dimmed()
+            )
+        } else if result.status == TestStatus::XPass {
+            format!(
+                "{} {} {}",
+                "✗".red().bold(),
+                result.name.red(),
+                "(xpass)".dimmed()
+            )
+        } else if result.status == TestStatus::Quarantined {
+            format!(
+                "{} {} {}",
+                "!".yellow().bold(),
+                result.name.yellow(),
+                "(quarantined)".dimmed()
+            )
+        } else if result.failed() {
+            format!(
+                "{} {} {}",
+                "✗".red().bold(),
+                result.name.red(),
+                format!("({:.2}ms)", duration_ms).dimmed()
+            )
+        } else if result.passed() {
+            format!(
+                "{} {} {}",
+                "✓".bright_green().bold(),
+                result.name.bright_green(),
+                format!("({:.2}ms)", duration_ms).dimmed()
+            )
+        } else {
+            format!("{} {}", "○".yellow().bold(), result.name.yellow())
+        };
+        if self.show_suites {
+            self.pb.println(format!("{} {}", label(suite), line));
+        } else {
+            self.pb.println(line);
+        }
+
+        if !result.failed() {
+            return;
+        }
+        match (&result.error, &result.location) {
+            (Some(error), Some(_)) if !error.message().is_empty() => {
+                self.pb.println(format_err_context(
+                    &result.name,
+                    result.location.clone(),
+                    Some(error.message()),
+                ));
+            }
+            (Some(error), _) => {
+                self.pb
+                    .println(format!("    {}", error.to_string().dimmed()));
+            }
+            _ => {}
+        }
+    }
+}
+
+/// Prints a plain line per finished test, e.g. `PASSED basic::test_add
+/// (1.20ms)`, for CI logs that don't render colors or redraw progress bars.
+/// Failures are followed by their error, indented.
+#[derive(Default)]
+pub struct CiReporter {
+    show_suites: bool,
+}
+
+impl CiReporter {
+    /// Label each test with the suite it's from, e.g. `PASSED [basic]
+    /// basic::test_add (1.20ms)`
+    pub fn with_suites(mut self, show_suites: bool) -> Self {
+        self.show_suites = show_suites;
+        self
+    }
+}
+
+impl LiveReporter for CiReporter {
+    fn on_test_finished(&mut self, result: &TestResult, suite: &str) {
+        let mut line = result.status.to_string().to_uppercase();
+        if self.show_suites {
+            line.push_str(&format!(" [{}]", suite));
+        }
+        line.push_str(&format!(" {}", result.qualified_name()));
+        if result.passed() || result.failed() {
+            let duration_ms = result.duration.unwrap_or_default().as_secs_f64() * 1000.0;
+            line.push_str(&format!(" ({:.2}ms)", duration_ms));
+        }
+        if result.flaky {
+            line.push_str(&format!(" flaky after {} retries", result.retry_count));
+        }
+        println!("{}", line);
+
+        if result.failed()
+            && let Some(ref error) = result.error
+        {
+            for error_line in error.to_string().lines() {
+                println!("    {}", error_line);
+            }
+        }
+    }
+}
+
+/// The label put in front of the lines of a suite's tests
+fn label(suite: &str) -> ColoredString {
+    format!("[{}]", suite).dimmed()
+}
@@ -1,10 +1,14 @@
 pub mod files;
+pub mod live;
 pub mod output;
 pub mod report;
+pub mod retention;
 
 pub use files::*;
+pub use live::*;
 pub use output::*;
 pub use report::*;
+pub use retention::*;
 
 use colored::Color;
 
@@ -72,7 +76,19 @@ pub fn tag_color(tag: String) -> Color {
 
 pub enum TargetSpec {
     File(String),
-    FileLine { file: String, line: usize },
+    /// A glob pattern, e.g. `crates/core/**/parser*`, matched against the
+    /// paths of test files, or against test names if it has no `/`
+    Glob(String),
+    FileLine {
+        file: String,
+        line: usize,
+    },
     Function(String),
+    /// One case of a parameterized test, e.g. `test_parse#3` by its index
+    /// or `test_parse#empty` by its parameter
+    Case {
+        test: String,
+        case: String,
+    },
     Tag(String),
 }
@@ -7,7 +7,7 @@ use std::time::Duration;
 use tiny_gradient::{Gradient, GradientStr};
 
 use crate::cli::OutputFormat;
-use crate::discovery::TestFile;
+use crate::discovery::{TestCounts, TestFile, TestFunction, TestGroup, TestSuite};
 use crate::helpers::tag_color;
 
 pub struct OutputFormatter;
@@ -103,6 +103,21 @@ impl OutputFormatter {
         pb
     }
 
+    /// Bar for a test run, filled in by [`ProgressReporter`] with the share
+    /// of the run done; its prefix carries the test count and time left
+    ///
+    /// [`ProgressReporter`]: super::ProgressReporter
+    pub fn create_run_progress_bar(message: &str) -> ProgressBar {
+        let pb = ProgressBar::new(1000);
+        pb.set_style(
+            ProgressStyle::with_template("{prefix:.bold} {msg:>12.dim.bold} {bar:100.white/dim}")
+                .unwrap()
+                .progress_chars("█▓▒░  "),
+        );
+        pb.set_message(message.to_string());
+        pb
+    }
+
     pub fn format_test_summary(
         passed: usize,
         failed: usize,
@@ -162,11 +177,339 @@ impl OutputFormatter {
             OutputFormat::Json => Self::format_json(files),
             OutputFormat::Csv => Self::format_csv(files),
             OutputFormat::Html => Self::format_html(files),
+            OutputFormat::Junit => Self::format_junit(files.iter().flat_map(|file| {
+                file.suites
+                    .iter()
+                    .filter(|suite| !suite.tests.is_empty())
+                    .map(move |suite| {
+                        let tests = suite.tests.iter().map(|test| (file, suite, test)).collect();
+                        (suite.name.as_str(), tests)
+                    })
+            })),
             OutputFormat::Text => Ok(Self::format_text(files)),
             _ => anyhow::bail!("Unsupported output format: {}", format),
         }
     }
 
+    pub fn format_test_groups(
+        groups: &[TestGroup],
+        format: OutputFormat,
+    ) -> anyhow::Result<String> {
+        match format {
+            OutputFormat::Json => serde_json::to_string_pretty(groups).map_err(Into::into),
+            OutputFormat::Csv => {
+                let mut output = String::new();
+                writeln!(
+                    output,
+                    "group,file_path,suite_name,test_name,line_number,tags,ignored"
+                )?;
+                for group in groups {
+                    for (file, suite, test) in listed_tests(&group.files) {
+                        writeln!(output, "\"{}\",{}", group.name, csv_row(file, suite, test))?;
+                    }
+                }
+                Ok(output)
+            }
+            OutputFormat::Junit => Self::format_junit(
+                groups
+                    .iter()
+                    .map(|group| (group.name.as_str(), listed_tests(&group.files).collect())),
+            ),
+            OutputFormat::Text => Ok(Self::format_groups_text(groups)),
+            _ => anyhow::bail!("Unsupported output format for grouped tests: {}", format),
+        }
+    }
+
+    /// Test counts for the whole list and, with `groups`, for each group
+    pub fn format_test_counts(
+        total: TestCounts,
+        groups: Option<&[TestGroup]>,
+        format: OutputFormat,
+    ) -> anyhow::Result<String> {
+        let group_counts: Vec<(&str, TestCounts)> = groups
+            .unwrap_or_default()
+            .iter()
+            .map(|group| (group.name.as_str(), TestCounts::of(&group.files)))
+            .collect();
+
+        match format {
+            OutputFormat::Json => {
+                let value = if groups.is_some() {
+                    serde_json::json!({
+                        "total": total,
+                        "groups": group_counts.iter().cloned().collect::<std::collections::BTreeMap<_, _>>(),
+                    })
+                } else {
+                    serde_json::to_value(total)?
+                };
+                Ok(format!("{}\n", serde_json::to_string_pretty(&value)?))
+            }
+            OutputFormat::Csv => {
+                let mut output = String::new();
+                writeln!(output, "group,files,suites,tests,ignored,disabled")?;
+                for (name, counts) in group_counts.iter().chain([&("total", total)]) {
+                    writeln!(
+                        output,
+                        "\"{}\",{},{},{},{},{}",
+                        name,
+                        counts.files,
+                        counts.suites,
+                        counts.tests,
+                        counts.ignored,
+                        counts.disabled
+                    )?;
+                }
+                Ok(output)
+            }
+            OutputFormat::Text => {
+                let mut output = String::new();
+                let width = group_counts
+                    .iter()
+                    .map(|(name, _)| name.len())
+                    .max()
+                    .unwrap_or(0);
+                for (name, counts) in &group_counts {
+                    writeln!(
+                        output,
+                        "  {:<width$}  {} tests {}",
+                        name.bright_white(),
+                        counts.tests,
+                        format!("({} ignored{})", counts.ignored, not_built(counts.disabled))
+                            .dimmed(),
+                        width = width
+                    )?;
+                }
+                writeln!(
+                    output,
+                    "{}",
+                    format!(
+                        "{} files, {} test suites, {} tests ({} ignored{})",
+                        total.files,
+                        total.suites,
+                        total.tests - total.ignored - total.disabled,
+                        total.ignored,
+                        not_built(total.disabled)
+                    )
+                    .bright_white()
+                    .bold()
+                )?;
+                Ok(output)
+            }
+            _ => anyhow::bail!("Unsupported output format for test counts: {}", format),
+        }
+    }
+
+    /// The fixtures and/or hooks defined in `files`
+    pub fn format_setup(
+        files: &[TestFile],
+        fixtures: bool,
+        hooks: bool,
+        format: OutputFormat,
+    ) -> anyhow::Result<String> {
+        let files: Vec<&TestFile> = files
+            .iter()
+            .filter(|file| {
+                (fixtures && !file.fixtures.is_empty()) || (hooks && !file.hooks.is_empty())
+            })
+            .collect();
+
+        match format {
+            OutputFormat::Json => {
+                let entries: Vec<serde_json::Value> = files
+                    .iter()
+                    .map(|file| {
+                        let mut entry = serde_json::json!({ "path": file.path });
+                        if fixtures {
+                            entry["fixtures"] = serde_json::to_value(&file.fixtures)?;
+                        }
+                        if hooks {
+                            entry["hooks"] = serde_json::to_value(&file.hooks)?;
+                        }
+                        Ok(entry)
+                    })
+                    .collect::<serde_json::Result<_>>()?;
+                serde_json::to_string_pretty(&entries).map_err(Into::into)
+            }
+            OutputFormat::Csv => {
+                let mut output = String::new();
+                writeln!(output, "file_path,kind,name,scope,depends_on,line_number")?;
+                for file in &files {
+                    for fixture in file.fixtures.iter().filter(|_| fixtures) {
+                        writeln!(
+                            output,
+                            "\"{}\",fixture,\"{}\",{},\"{}\",{}",
+                            file.path.display(),
+                            fixture.name,
+                            fixture.scope,
+                            fixture.depends_on.join(";"),
+                            fixture.line_number.unwrap_or(0)
+                        )?;
+                    }
+                    for hook in file.hooks.iter().filter(|_| hooks) {
+                        writeln!(
+                            output,
+                            "\"{}\",{},\"{}\",,,{}",
+                            file.path.display(),
+                            hook.kind,
+                            hook.name,
+                            hook.line_number.unwrap_or(0)
+                        )?;
+                    }
+                }
+                Ok(output)
+            }
+            OutputFormat::Text => {
+                let mut output = String::from("\n\n");
+                let (mut total_fixtures, mut total_hooks) = (0, 0);
+
+                for file in &files {
+                    writeln!(
+                        output,
+                        "{}",
+                        format_relative_path(&file.path).gradient(Gradient::Cristal)
+                    )?;
+                    for fixture in file.fixtures.iter().filter(|_| fixtures) {
+                        total_fixtures += 1;
+                        let mut line = format!(
+                            "  {} {} {}",
+                            "◆".bright_magenta(),
+                            fixture.name,
+                            format!("[{}]", fixture.scope).cyan()
+                        );
+                        if !fixture.depends_on.is_empty() {
+                            line.push_str(&format!(
+                                " {}",
+                                format!("depends on {}", fixture.depends_on.join(", ")).dimmed()
+                            ));
+                        }
+                        if let Some(line_num) = fixture.line_number {
+                            line.push_str(&format!(" {}", format!("[line {}]", line_num).dimmed()));
+                        }
+                        writeln!(output, "{}", line)?;
+                    }
+                    for hook in file.hooks.iter().filter(|_| hooks) {
+                        total_hooks += 1;
+                        let mut line = format!(
+                            "  {} {} {}",
+                            "↻".bright_blue(),
+                            hook.name,
+                            format!("[{}]", hook.kind).cyan()
+                        );
+                        if let Some(line_num) = hook.line_number {
+                            line.push_str(&format!(" {}", format!("[line {}]", line_num).dimmed()));
+                        }
+                        writeln!(output, "{}", line)?;
+                    }
+                    output.push('\n');
+                }
+
+                let mut found = Vec::new();
+                if fixtures {
+                    found.push(format!("{} fixtures", total_fixtures));
+                }
+                if hooks {
+                    found.push(format!("{} hooks", total_hooks));
+                }
+                writeln!(
+                    output,
+                    "{}\n",
+                    format!("Found {}", found.join(", ")).bright_white().bold()
+                )?;
+                Ok(output)
+            }
+            _ => anyhow::bail!(
+                "Unsupported output format for fixtures and hooks: {}",
+                format
+            ),
+        }
+    }
+
+    pub fn format_doc_tests(files: &[TestFile], format: OutputFormat) -> anyhow::Result<String> {
+        let files: Vec<&TestFile> = files
+            .iter()
+            .filter(|file| !file.doc_tests.is_empty())
+            .collect();
+
+        match format {
+            OutputFormat::Json => {
+                let entries: Vec<serde_json::Value> = files
+                    .iter()
+                    .map(|file| {
+                        serde_json::json!({ "path": file.path, "doc_tests": file.doc_tests })
+                    })
+                    .collect();
+                serde_json::to_string_pretty(&entries).map_err(Into::into)
+            }
+            OutputFormat::Csv => {
+                let mut output = String::new();
+                writeln!(output, "file_path,item,line_number,attributes")?;
+                for file in &files {
+                    for doc_test in &file.doc_tests {
+                        writeln!(
+                            output,
+                            "\"{}\",\"{}\",{},\"{}\"",
+                            file.path.display(),
+                            doc_test.item.as_deref().unwrap_or_default(),
+                            doc_test.line_number,
+                            doc_test.attributes.join(";")
+                        )?;
+                    }
+                }
+                Ok(output)
+            }
+            OutputFormat::Text => {
+                let mut output = String::from("\n\n");
+                let (mut total, mut ignored) = (0, 0);
+
+                for file in &files {
+                    writeln!(
+                        output,
+                        "{}",
+                        format_relative_path(&file.path).gradient(Gradient::Cristal)
+                    )?;
+                    for doc_test in &file.doc_tests {
+                        total += 1;
+                        let icon = if doc_test.ignored() {
+                            ignored += 1;
+                            "○".yellow()
+                        } else {
+                            "✓".bright_green()
+                        };
+                        let mut line = format!(
+                            "  {} {} {}",
+                            icon,
+                            doc_test.item.as_deref().unwrap_or("(module)"),
+                            format!("[line {}]", doc_test.line_number).dimmed()
+                        );
+                        if !doc_test.attributes.is_empty() {
+                            line.push_str(&format!(
+                                " {}",
+                                format!("[{}]", doc_test.attributes.join(", ")).cyan()
+                            ));
+                        }
+                        writeln!(output, "{}", line)?;
+                    }
+                    output.push('\n');
+                }
+
+                writeln!(
+                    output,
+                    "{}\n",
+                    format!(
+                        "Found {} files, {} doc examples ({} ignored)",
+                        files.len(),
+                        total,
+                        ignored
+                    )
+                    .bright_white()
+                    .bold()
+                )?;
+                Ok(output)
+            }
+            _ => anyhow::bail!("Unsupported output format for doc examples: {}", format),
+        }
+    }
+
     fn format_json(files: &[TestFile]) -> anyhow::Result<String> {
         serde_json::to_string_pretty(files).map_err(Into::into)
     }
@@ -178,24 +521,136 @@ impl OutputFormatter {
             "file_path,suite_name,test_name,line_number,tags,ignored"
         )?;
 
-        for file in files {
-            for suite in &file.suites {
-                for test in &suite.tests {
+        for (file, suite, test) in listed_tests(files) {
+            writeln!(output, "{}", csv_row(file, suite, test))?;
+        }
+
+        Ok(output)
+    }
+
+    /// JUnit XML with a `<testsuite>` for each named set of tests. Nothing
+    /// has run, so every test is a bare `<testcase>`, or `<skipped/>` if
+    /// it's ignored.
+    fn format_junit<'a>(
+        suites: impl IntoIterator<Item = (&'a str, Vec<ListedTest<'a>>)>,
+    ) -> anyhow::Result<String> {
+        let suites: Vec<_> = suites.into_iter().collect();
+        let total = suites.iter().map(|(_, tests)| tests.len()).sum::<usize>();
+        let skipped = |tests: &[ListedTest]| {
+            tests
+                .iter()
+                .filter(|(_, _, test)| test.ignored || !test.is_applicable())
+                .count()
+        };
+
+        let mut xml = String::from("<?xml version=\"1.0\" encoding=\"UTF-8\"?>\n");
+        writeln!(
+            xml,
+            "<testsuites name=\"sheila\" tests=\"{}\" skipped=\"{}\">",
+            total,
+            suites
+                .iter()
+                .map(|(_, tests)| skipped(tests))
+                .sum::<usize>()
+        )?;
+
+        for (name, tests) in &suites {
+            writeln!(
+                xml,
+                "  <testsuite name=\"{}\" tests=\"{}\" skipped=\"{}\">",
+                xml_escape(name),
+                tests.len(),
+                skipped(tests)
+            )?;
+            for (file, suite, test) in tests {
+                write!(
+                    xml,
+                    "    <testcase name=\"{}\" classname=\"{}\" file=\"{}\"",
+                    xml_escape(&test.name),
+                    xml_escape(&suite.name),
+                    xml_escape(&file.path.display().to_string())
+                )?;
+                if let Some(line) = test.line_number {
+                    write!(xml, " line=\"{}\"", line)?;
+                }
+
+                let tags: Vec<&String> = suite.tags.iter().chain(&test.tags).collect();
+                if tags.is_empty() && !test.ignored && test.is_applicable() {
+                    xml.push_str("/>\n");
+                    continue;
+                }
+                xml.push_str(">\n");
+                if !tags.is_empty() {
+                    xml.push_str("      <properties>\n");
+                    for tag in tags {
+                        writeln!(
+                            xml,
+                            "        <property name=\"tag\" value=\"{}\"/>",
+                            xml_escape(tag)
+                        )?;
+                    }
+                    xml.push_str("      </properties>\n");
+                }
+                if let Some(ref cfg) = test.disabled_by {
                     writeln!(
-                        output,
-                        "\"{}\",\"{}\",\"{}\",{},\"{}\",{}",
-                        file.path.display(),
-                        suite.name,
-                        test.name,
-                        test.line_number.unwrap_or(0),
-                        test.tags.join(";"),
-                        test.ignored
+                        xml,
+                        "      <skipped message=\"not built here: {}\"/>",
+                        xml_escape(cfg)
                     )?;
+                } else if test.ignored {
+                    xml.push_str("      <skipped/>\n");
                 }
+                xml.push_str("    </testcase>\n");
             }
+            xml.push_str("  </testsuite>\n");
         }
 
-        Ok(output)
+        xml.push_str("</testsuites>\n");
+        Ok(xml)
+    }
+
+    fn format_groups_text(groups: &[TestGroup]) -> String {
+        let mut output = String::from("\n\n");
+
+        for group in groups {
+            let counts = TestCounts::of(&group.files);
+            output.push_str(&format!(
+                "{} {}\n",
+                group.name.gradient(Gradient::Cristal),
+                format!("({} tests)", counts.tests).dimmed()
+            ));
+
+            for (file, suite, test) in listed_tests(&group.files) {
+                let icon = if !test.is_applicable() {
+                    "⊘".dimmed()
+                } else if test.ignored {
+                    "○".yellow()
+                } else {
+                    "✓".green()
+                };
+                let location = match test.line_number {
+                    Some(line) => format!("{}:{}", format_relative_path(&file.path), line),
+                    None => format_relative_path(&file.path),
+                };
+                output.push_str(&format!(
+                    "  {} {} {}{} {}\n",
+                    icon,
+                    suite.name.dimmed(),
+                    test.name,
+                    case_count(test),
+                    location.dimmed()
+                ));
+            }
+            output.push('\n');
+        }
+
+        output.push_str(&format!(
+            "{}\n\n",
+            format!("Found {} groups", groups.len())
+                .bright_white()
+                .bold()
+        ));
+        output
     }
 
     fn format_html(files: &[TestFile]) -> anyhow::Result<String> {
@@ -233,9 +688,19 @@ impl OutputFormatter {
                     html.push_str("<p><em>No tests in this suite</em></p>\n");
                 } else {
                     for test in &suite.tests {
-                        let ignored_class = if test.ignored { " ignored" } else { "" };
+                        let ignored_class = if test.ignored || !test.is_applicable() {
+                            " ignored"
+                        } else {
+                            ""
+                        };
                         html.push_str(&format!("<div class=\"test{}\">\n", ignored_class));
-                        let icon = if test.ignored { "○" } else { "✓" };
+                        let icon = if !test.is_applicable() {
+                            "⊘"
+                        } else if test.ignored {
+                            "○"
+                        } else {
+                            "✓"
+                        };
                         html.push_str(&format!(
                             "{} {} [line {}]",
                             icon,
@@ -264,6 +729,7 @@ impl OutputFormatter {
         let mut total_suites = 0;
         let mut total_tests = 0;
         let mut ignored_tests = 0;
+        let mut disabled_tests = 0;
 
         output.push_str("\n\n");
 
@@ -286,14 +752,18 @@ impl OutputFormatter {
                 } else {
                     for test in &suite.tests {
                         total_tests += 1;
-                        let icon = if test.ignored {
+                        let icon = if !test.is_applicable() {
+                            disabled_tests += 1;
+                            "⊘".dimmed()
+                        } else if test.ignored {
                             ignored_tests += 1;
                             "○".yellow()
                         } else {
                             "✓".green()
                         };
 
-                        let mut test_line = format!("    {} {}", icon, test.name);
+                        let mut test_line =
+                            format!("    {} {}{}", icon, test.name, case_count(test));
 
                         if let Some(line_num) = test.line_number {
                             test_line
@@ -320,6 +790,9 @@ impl OutputFormatter {
                         if let Some(retries) = test.retries {
                             attributes.push(format!("retries {}", retries));
                         }
+                        if let Some(ref cfg) = test.disabled_by {
+                            attributes.push(format!("not built here: {}", cfg));
+                        }
 
                         if !attributes.is_empty() {
                             test_line.push_str(&format!(
@@ -335,15 +808,16 @@ impl OutputFormatter {
             output.push('\n');
         }
 
-        let active_tests = total_tests - ignored_tests;
+        let active_tests = total_tests - ignored_tests - disabled_tests;
         output.push_str(&format!(
             "{}\n\n",
             format!(
-                "Found {} files, {} test suites, {} tests ({} ignored)",
+                "Found {} files, {} test suites, {} tests ({} ignored{})",
                 files.len(),
                 total_suites,
                 active_tests,
-                ignored_tests
+                ignored_tests,
+                not_built(disabled_tests)
             )
             .bright_white()
             .bold()
@@ -352,3 +826,54 @@ impl OutputFormatter {
         output
     }
 }
+
+/// A discovered test with the file and suite it's in
+type ListedTest<'a> = (&'a TestFile, &'a TestSuite, &'a TestFunction);
+
+fn listed_tests(files: &[TestFile]) -> impl Iterator<Item = ListedTest<'_>> {
+    files.iter().flat_map(|file| {
+        file.suites
+            .iter()
+            .flat_map(move |suite| suite.tests.iter().map(move |test| (file, suite, test)))
+    })
+}
+
+fn csv_row(file: &TestFile, suite: &TestSuite, test: &TestFunction) -> String {
+    format!(
+        "\"{}\",\"{}\",\"{}\",{},\"{}\",{}",
+        file.path.display(),
+        suite.name,
+        test.name,
+        test.line_number.unwrap_or(0),
+        test.tags.join(";"),
+        test.ignored
+    )
+}
+
+/// ` [N cases]` after the name of a parameterized test
+fn case_count(test: &TestFunction) -> String {
+    match test.cases.len() {
+        0 => String::new(),
+        1 => format!(" {}", "[1 case]".cyan()),
+        n => format!(" {}", format!("[{} cases]", n).cyan()),
+    }
+}
+
+/// `, N not built` for a count of tests left out of the build here, if
+/// there are any
+fn not_built(disabled: usize) -> String {
+    if disabled == 0 {
+        String::new()
+    } else {
+        format!(", {} not built", disabled)
+    }
+}
+
+fn xml_escape(value: &str) -> String {
+    value
+        .replace('&', "&amp;")
+        .replace('<', "&lt;")
+        .replace('>', "&gt;")
+        .replace('"', "&quot;")
+        .replace('\'', "&apos;")
+}
@@ -1,17 +1,28 @@
+use std::fs::File;
+use std::io::{BufWriter, Write};
 use std::time::Duration;
 
 use chrono::Utc;
 use colored::Colorize;
 use sheila::{
-    Reporter, TestStatus,
-    reporting::{CsvReporter, HtmlReporter, JsonReporter, TextReporter},
+    Error, Reporter, TestStatus,
+    cache::RunSummary,
+    reporting::ReportLimits,
+    reporting::{
+        AllureReporter, BadgeReporter, CsvReporter, HtmlReporter, JsonReporter, MetricsReporter,
+        SarifReporter, TemplateReporter, TextReporter,
+    },
     runners::RunResult,
 };
+use tiny_gradient::Gradient;
 use uuid::Uuid;
 
 use crate::{
     cli::{OutputFormat, TestArgs},
-    helpers::{OutputFormatter, ensure_dir_exists, get_default_output_dir, result_gradient},
+    config::SheilaConfig,
+    helpers::{
+        OutputFormatter, ReportIndex, ensure_dir_exists, get_default_output_dir, result_gradient,
+    },
 };
 
 pub fn display_test_results(
@@ -52,14 +63,102 @@ pub fn display_test_results(
                     TestStatus::Passed => ("✓", "green"),
                     TestStatus::Failed => ("✗", "red"),
                     TestStatus::Skipped => ("○", "yellow"),
+                    TestStatus::Cached => ("✓", "cyan"),
                     TestStatus::Ignored => ("⊝", "dimmed"),
+                    TestStatus::XFail => ("x", "magenta"),
+                    TestStatus::XPass => ("✗", "red"),
+                    TestStatus::Quarantined => ("!", "yellow"),
                     _ => ("?", "white"),
                 };
 
-                println!("  {} {}", icon.color(color), test_result.name);
+                if test_result.flaky {
+                    println!(
+                        "  {} {} {}",
+                        icon.color(color),
+                        test_result.name,
+                        format!("(flaky-pass after {} retries)", test_result.retry_count).yellow()
+                    );
+                } else if let Some(iterations) = test_result.iterations {
+                    println!(
+                        "  {} {} {}",
+                        icon.color(color),
+                        test_result.name,
+                        format!("({} iterations)", iterations).dimmed()
+                    );
+                } else if matches!(
+                    test_result.status,
+                    TestStatus::XFail | TestStatus::XPass | TestStatus::Quarantined
+                ) {
+                    println!(
+                        "  {} {} {}",
+                        icon.color(color),
+                        test_result.name,
+                        format!("({})", test_result.status).dimmed()
+                    );
+                } else {
+                    println!("  {} {}", icon.color(color), test_result.name);
+                }
+
+                if let Some(ref reason) = test_result.skip_reason {
+                    println!("    {}: {}", "Reason".yellow(), reason.dimmed());
+                }
 
                 if let Some(ref error) = test_result.error {
-                    println!("    {}: {}", "Error".red(), error.to_string().dimmed());
+                    print_error(error, "    ");
+                }
+
+                if test_result.failed() && !test_result.breadcrumbs.is_empty() {
+                    println!(
+                        "    {}: {}",
+                        "Trail".yellow(),
+                        test_result.breadcrumbs.join(" -> ").dimmed()
+                    );
+                }
+
+                if test_result.failed() && !test_result.logs.is_empty() {
+                    println!("    {}:", "Log".yellow());
+                    for line in &test_result.logs {
+                        println!(
+                            "      {} {}",
+                            format!("[{}]", line.level).dimmed(),
+                            line.message.dimmed()
+                        );
+                    }
+                }
+
+                if let Some(ref temp_dir) = test_result.temp_dir {
+                    println!(
+                        "    {}: {}",
+                        "Temp dir".yellow(),
+                        temp_dir.display().to_string().dimmed()
+                    );
+                }
+
+                if test_result.failed()
+                    && let Some(ref output) = test_result.stdout
+                {
+                    println!("    {}:", "Output".yellow());
+                    for line in output.lines() {
+                        println!("      {}", line.dimmed());
+                    }
+                }
+
+                if test_result.failed()
+                    && let Some(ref output) = test_result.stderr
+                {
+                    println!("    {}:", "Stderr".yellow());
+                    for line in output.lines() {
+                        println!("      {}", line.dimmed());
+                    }
+                }
+            }
+
+            if !suite_result.all_passed()
+                && let Some(ref output) = suite_result.stderr
+            {
+                println!("  {}:", "Stderr".yellow());
+                for line in output.lines() {
+                    println!("    {}", line.dimmed());
                 }
             }
             println!();
@@ -71,8 +170,52 @@ pub fn display_test_results(
         OutputFormatter::format_abridged_summary(passed, failed, total, duration)
     );
 
+    if let Some(iterations) = result.iterations {
+        let message = format!("Ran {} iteration(s)", iterations);
+        if result.all_passed() {
+            println!("{}", OutputFormatter::format_info(&message));
+        } else {
+            println!(
+                "{}",
+                OutputFormatter::format_warning(&format!("{}, failed on the last", message))
+            );
+        }
+    }
+
+    if result.flaky_tests > 0 {
+        println!(
+            "{}",
+            OutputFormatter::format_warning(&format!(
+                "{} test(s) only passed after retrying",
+                result.flaky_tests
+            ))
+        );
+    }
+
+    if failed > 0
+        && let Some(seed) = result.config.seed
+    {
+        println!(
+            "{}",
+            OutputFormatter::format_info(&format!(
+                "Tests ran with seed {}; pass --seed {} to replay their random data",
+                seed, seed
+            ))
+        );
+    }
+
     if failed > 0 {
         println!("{}", OutputFormatter::format_error("Some tests failed"));
+    } else if let Some(Error::Cancelled { .. }) = result.error {
+        println!(
+            "{}",
+            OutputFormatter::format_warning("Run was cancelled before all tests finished")
+        );
+    } else if let Some(Error::ResourceExhausted { .. }) = result.error {
+        println!(
+            "{}",
+            OutputFormatter::format_warning("Run time budget ran out before all tests finished")
+        );
     } else {
         println!("{}", OutputFormatter::format_success("All tests passed!"));
     }
@@ -80,6 +223,144 @@ pub fn display_test_results(
     Ok(())
 }
 
+/// Print `error` under a test or suite, with where it was raised and what
+/// caused it
+fn print_error(error: &Error, indent: &str) {
+    println!(
+        "{}{}: {}",
+        indent,
+        "Error".red(),
+        error.to_string().dimmed()
+    );
+    for line in error.trace().lines() {
+        println!("{}  {}", indent, line.dimmed());
+    }
+}
+
+/// Print a finished run for CI logs: the failing tests, a one-line summary,
+/// and the run summary as JSON, on stdout or in `--summary-file`
+pub fn display_ci_results(
+    result: &RunResult,
+    args: &TestArgs,
+    duration: Duration,
+) -> color_eyre::Result<()> {
+    let summary = RunSummary::from(result);
+
+    println!();
+    if !summary.failures.is_empty() {
+        println!("Failed or flaky tests:");
+        for failure in &summary.failures {
+            println!("  {}", failure);
+        }
+    }
+    println!(
+        "{} passed, {} failed, {} skipped, {} flaky, {} total in {}",
+        summary.passed_tests,
+        summary.failed_tests,
+        summary.skipped_tests,
+        summary.flaky_tests,
+        summary.total_tests,
+        OutputFormatter::format_duration(duration)
+    );
+
+    let json = serde_json::to_string(&summary)?;
+    match args.summary_file {
+        Some(ref path) => {
+            if let Some(parent) = path.parent() {
+                std::fs::create_dir_all(parent)?;
+            }
+            std::fs::write(path, json)?;
+            println!("Summary written to {}", path.display());
+        }
+        None => println!("{}", json),
+    }
+
+    Ok(())
+}
+
+/// Print the tests a dry run selected, grouped by suite, with estimated
+/// durations taken from the previous run.
+pub fn display_dry_run(result: &RunResult) {
+    println!(
+        "{}",
+        OutputFormatter::format_header("Dry Run", Gradient::Vice)
+    );
+
+    let mut unknown = 0;
+    for suite_result in &result.suite_results {
+        println!(
+            "{} {}",
+            "●".cyan().bold(),
+            suite_result.name.bright_white().bold()
+        );
+
+        if let Some(ref error) = suite_result.error {
+            print_error(error, "  ");
+        }
+
+        for test_result in &suite_result.test_results {
+            let estimate = match (test_result.status, test_result.duration) {
+                (TestStatus::Cached, _) => "(cached)".cyan().to_string(),
+                (_, Some(duration)) => format!("(~{})", OutputFormatter::format_duration(duration))
+                    .dimmed()
+                    .to_string(),
+                (_, None) => {
+                    unknown += 1;
+                    String::new()
+                }
+            };
+            println!("  {} {} {}", "○".dimmed(), test_result.name, estimate);
+        }
+        println!();
+    }
+
+    let to_run = result
+        .suite_results
+        .iter()
+        .flat_map(|suite| suite.test_results.iter())
+        .filter(|test| test.status == TestStatus::Pending)
+        .count();
+
+    let mut summary = format!(
+        "{} test(s) would run in {} suite(s), estimated {}",
+        to_run,
+        result.suite_results.len(),
+        OutputFormatter::format_duration(result.estimated_duration())
+    );
+    if unknown > 0 {
+        summary.push_str(&format!(" ({} without history)", unknown));
+    }
+    println!("{}", OutputFormatter::format_info(&summary));
+}
+
+/// Stream a report straight to `path`, without building it in memory first
+fn write_report(
+    reporter: &dyn Reporter,
+    result: &RunResult,
+    path: &std::path::Path,
+) -> color_eyre::Result<()> {
+    let mut writer = BufWriter::new(File::create(path)?);
+    reporter.write_to(result, &mut writer)?;
+    writer.flush()?;
+    Ok(())
+}
+
+/// The extension a rendered template should be written with: `summary.md.tera`
+/// renders to markdown, `summary.html` to HTML
+fn template_extension(template: &std::path::Path) -> String {
+    let name = template
+        .file_name()
+        .and_then(|name| name.to_str())
+        .unwrap_or_default();
+    let name = name.strip_suffix(".tera").unwrap_or(name);
+
+    std::path::Path::new(name)
+        .extension()
+        .and_then(|ext| ext.to_str())
+        .unwrap_or("txt")
+        .to_string()
+}
+
 pub fn generate_report(result: &RunResult, args: &TestArgs) -> color_eyre::Result<()> {
     let output_dir = args.output_dir.clone().unwrap_or_else(|| {
         get_default_output_dir().unwrap_or_else(|_| std::path::PathBuf::from("."))
@@ -88,28 +369,112 @@ pub fn generate_report(result: &RunResult, args: &TestArgs) -> color_eyre::Resul
     ensure_dir_exists(&output_dir)
         .map_err(|_| sheila::Error::generic("Failed to create output directory"))?;
 
+    let mut index = ReportIndex::load(&output_dir)?;
+    write_reports(result, args, &output_dir, &mut index)?;
+
+    // Keeping the output directory tidy shouldn't fail the run
+    let retention = SheilaConfig::load()
+        .map(|config| config.reporting.retention)
+        .unwrap_or_default();
+    if let Err(e) = index.prune(&retention, false).and_then(|_| index.save()) {
+        tracing::warn!("Failed to prune old reports: {}", e);
+    }
+
+    Ok(())
+}
+
+/// Write the reports requested by `args`, recording single-file reports in
+/// `index`
+fn write_reports(
+    result: &RunResult,
+    args: &TestArgs,
+    output_dir: &std::path::Path,
+    index: &mut ReportIndex,
+) -> color_eyre::Result<()> {
     let timestamp = Utc::now().format("%Y%m%d_%H%M%S");
     let report_id = Uuid::new_v4().to_string().replace('-', "")[..16].to_string();
 
-    let reporter: Box<dyn Reporter> = match args.output.unwrap() {
+    let mut result = result.clone();
+    ReportLimits::default().apply(&mut result);
+    let result = &result;
+
+    if let Some(ref template) = args.template {
+        let reporter = TemplateReporter::from_file(template)?;
+        let report_path = output_dir.join(format!(
+            "test_report_{}_{}.{}",
+            timestamp,
+            report_id,
+            template_extension(template)
+        ));
+        write_report(&reporter, result, &report_path)?;
+        index.record(&report_path, "template")?;
+
+        println!(
+            "{}",
+            OutputFormatter::format_success(&format!(
+                "Report generated: {}",
+                report_path.display()
+            ))
+        );
+    }
+
+    let Some(output) = args.output else {
+        return Ok(());
+    };
+
+    // Allure reads a directory of result files rather than a single report
+    if output == OutputFormat::Allure {
+        let results_dir = output_dir.join("allure-results");
+        let written = AllureReporter::new().write_results(result, &results_dir)?;
+        println!(
+            "{}",
+            OutputFormatter::format_success(&format!(
+                "Wrote {} Allure file(s) to {}",
+                written.len(),
+                results_dir.display()
+            ))
+        );
+        return Ok(());
+    }
+
+    // Badges keep fixed names so a README can link to them across runs
+    if output == OutputFormat::Badge {
+        let badges_dir = output_dir.join("badges");
+        let written = BadgeReporter::new().write_badges(result, &badges_dir)?;
+        println!(
+            "{}",
+            OutputFormatter::format_success(&format!(
+                "Wrote {} badge file(s) to {}",
+                written.len(),
+                badges_dir.display()
+            ))
+        );
+        return Ok(());
+    }
+
+    let reporter: Box<dyn Reporter> = match output {
         OutputFormat::Json => Box::new(JsonReporter::new()),
         OutputFormat::Csv => Box::new(CsvReporter::new()),
         OutputFormat::Html => Box::new(HtmlReporter::new()),
         OutputFormat::Text => Box::new(TextReporter::new()),
         OutputFormat::Junit => Box::new(TextReporter::new()),
         OutputFormat::Tap => Box::new(TextReporter::new()),
+        OutputFormat::Allure => Box::new(AllureReporter::new()),
+        OutputFormat::Sarif => Box::new(SarifReporter::new()),
+        OutputFormat::Metrics => Box::new(MetricsReporter::new()),
+        OutputFormat::Badge => Box::new(BadgeReporter::new()),
     };
 
-    let report = reporter.generate(result)?;
-    let filename = format!(
-        "test_report_{}_{}.{}",
-        timestamp,
-        report_id,
-        args.output.unwrap()
-    );
+    // `.prom` is what the node exporter's textfile collector picks up
+    let extension = match output {
+        OutputFormat::Metrics => "prom".to_string(),
+        output => output.to_string(),
+    };
+    let filename = format!("test_report_{}_{}.{}", timestamp, report_id, extension);
     let report_path = output_dir.join(filename);
 
-    std::fs::write(&report_path, &report.content)?;
+    write_report(reporter.as_ref(), result, &report_path)?;
+    index.record(&report_path, &extension)?;
 
     println!(
         "{}",
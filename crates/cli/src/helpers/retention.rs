@@ -0,0 +1,191 @@
+use std::io::Result;
+use std::path::{Path, PathBuf};
+
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+
+use crate::config::RetentionConfig;
+
+const INDEX_FILE: &str = ".sheila-reports.json";
+const REPORT_PREFIX: &str = "test_report_";
+
+/// A report written to the output directory
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct IndexedReport {
+    /// File name, relative to the output directory
+    pub file: String,
+    pub format: String,
+    pub created_at: DateTime<Utc>,
+    pub size: u64,
+}
+
+/// The reports in an output directory, oldest first. Written alongside the
+/// reports each time one is generated, so finding the latest report or
+/// pruning old ones doesn't depend on file modification times.
+#[derive(Debug, Default, Serialize, Deserialize)]
+pub struct ReportIndex {
+    #[serde(skip)]
+    dir: PathBuf,
+    pub reports: Vec<IndexedReport>,
+}
+
+impl ReportIndex {
+    /// Load the index for `dir`. Reports that have since been deleted are
+    /// dropped, and report files missing from the index (e.g. written before
+    /// it existed) are added using their modification time.
+    pub fn load(dir: &Path) -> Result<Self> {
+        let mut index = match std::fs::read_to_string(dir.join(INDEX_FILE)) {
+            Ok(content) => serde_json::from_str(&content).unwrap_or_default(),
+            Err(_) => Self::default(),
+        };
+        index.dir = dir.to_path_buf();
+        index
+            .reports
+            .retain(|report| dir.join(&report.file).is_file());
+
+        if dir.is_dir() {
+            for entry in std::fs::read_dir(dir)? {
+                let entry = entry?;
+                let file = entry.file_name().to_string_lossy().into_owned();
+                if !file.starts_with(REPORT_PREFIX)
+                    || index.reports.iter().any(|report| report.file == file)
+                {
+                    continue;
+                }
+
+                let metadata = entry.metadata()?;
+                if !metadata.is_file() {
+                    continue;
+                }
+                index.reports.push(IndexedReport {
+                    format: Path::new(&file)
+                        .extension()
+                        .map(|ext| ext.to_string_lossy().into_owned())
+                        .unwrap_or_default(),
+                    created_at: metadata.modified()?.into(),
+                    size: metadata.len(),
+                    file,
+                });
+            }
+        }
+
+        index.reports.sort_by_key(|report| report.created_at);
+        Ok(index)
+    }
+
+    /// Add a report that was just written to `path`
+    pub fn record(&mut self, path: &Path, format: &str) -> Result<()> {
+        let file = path
+            .file_name()
+            .map(|name| name.to_string_lossy().into_owned())
+            .unwrap_or_default();
+        let size = std::fs::metadata(path)?.len();
+
+        self.reports.retain(|report| report.file != file);
+        self.reports.push(IndexedReport {
+            file,
+            format: format.to_string(),
+            created_at: Utc::now(),
+            size,
+        });
+        Ok(())
+    }
+
+    pub fn save(&self) -> Result<()> {
+        std::fs::write(
+            self.dir.join(INDEX_FILE),
+            serde_json::to_string_pretty(self)?,
+        )?;
+        Ok(())
+    }
+
+    /// Path of the newest report in one of `formats`
+    pub fn latest(&self, formats: &[&str]) -> Option<PathBuf> {
+        self.reports
+            .iter()
+            .rev()
+            .find(|report| formats.contains(&report.format.as_str()))
+            .map(|report| self.dir.join(&report.file))
+    }
+
+    /// Remove the reports over `retention`'s limits, oldest first, and
+    /// return them. The newest report is always kept. With `dry_run` the
+    /// reports are only listed, not deleted.
+    pub fn prune(
+        &mut self,
+        retention: &RetentionConfig,
+        dry_run: bool,
+    ) -> Result<Vec<IndexedReport>> {
+        let Some(newest) = self.reports.len().checked_sub(1) else {
+            return Ok(Vec::new());
+        };
+        let mut keep = vec![true; self.reports.len()];
+
+        if let Some(days) = retention.max_age_days {
+            let cutoff = Utc::now() - chrono::Duration::days(days as i64);
+            for (i, report) in self.reports[..newest].iter().enumerate() {
+                if report.created_at < cutoff {
+                    keep[i] = false;
+                }
+            }
+        }
+
+        if let Some(max_reports) = retention.max_reports {
+            let mut kept = keep.iter().filter(|kept| **kept).count();
+            for kept_report in keep[..newest].iter_mut() {
+                if kept <= max_reports.max(1) {
+                    break;
+                }
+                if *kept_report {
+                    *kept_report = false;
+                    kept -= 1;
+                }
+            }
+        }
+
+        if let Some(max_size_mb) = retention.max_size_mb {
+            let max_size = max_size_mb * 1024 * 1024;
+            let mut size: u64 = self
+                .reports
+                .iter()
+                .zip(keep.iter())
+                .filter(|(_, kept)| **kept)
+                .map(|(report, _)| report.size)
+                .sum();
+            for (report, kept_report) in self.reports[..newest].iter().zip(keep.iter_mut()) {
+                if size <= max_size {
+                    break;
+                }
+                if *kept_report {
+                    *kept_report = false;
+                    size -= report.size;
+                }
+            }
+        }
+
+        let mut removed = Vec::new();
+        let mut kept = Vec::new();
+        for (report, keep) in self.reports.drain(..).zip(keep) {
+            if keep {
+                kept.push(report);
+            } else {
+                removed.push(report);
+            }
+        }
+
+        if dry_run {
+            kept.extend(removed.iter().cloned());
+            kept.sort_by_key(|report| report.created_at);
+        } else {
+            for report in &removed {
+                match std::fs::remove_file(self.dir.join(&report.file)) {
+                    Err(e) if e.kind() != std::io::ErrorKind::NotFound => return Err(e),
+                    _ => {}
+                }
+            }
+        }
+        self.reports = kept;
+
+        Ok(removed)
+    }
+}
@@ -3,6 +3,8 @@
 pub mod cli;
 pub mod commands;
 pub mod config;
+pub mod control;
 pub mod discovery;
 pub mod helpers;
+pub mod logging;
 pub mod process;
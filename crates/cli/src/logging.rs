@@ -0,0 +1,36 @@
+use std::io::IsTerminal;
+
+use tracing_subscriber::EnvFilter;
+
+/// Variable holding a log filter that overrides `-q` and `-v`, e.g.
+/// `sheila=debug` or `sheila::runners=trace,warn`
+pub const LOG_ENV: &str = "SHEILA_LOG";
+
+/// Send sheila's logs to stderr. Without `SHEILA_LOG` only warnings and
+/// errors are shown; `-v` adds debug logs and `-vv` trace logs from sheila
+/// itself, and `-q` leaves just errors.
+pub fn init(verbose: u8, quiet: bool) {
+    let level = match (quiet, verbose) {
+        (true, _) => "error",
+        (false, 0) => "warn",
+        (false, 1) => "debug",
+        (false, _) => "trace",
+    };
+    let filter = EnvFilter::try_from_env(LOG_ENV).unwrap_or_else(|_| {
+        EnvFilter::new(format!(
+            "{},sheila={},sheila_cli={}",
+            if quiet { "error" } else { "warn" },
+            level,
+            level
+        ))
+    });
+
+    // Fails only if a subscriber is already set, in which case that one is used
+    let _ = tracing_subscriber::fmt()
+        .with_env_filter(filter)
+        .with_writer(std::io::stderr)
+        .with_ansi(std::io::stderr().is_terminal())
+        .with_target(verbose > 0)
+        .without_time()
+        .try_init();
+}
@@ -1,21 +1,48 @@
-use clap::Parser;
+use clap::{CommandFactory, Parser};
+use clap_complete::CompleteEnv;
 use sheila_cli::cli::{Cli, Commands};
-use sheila_cli::commands::cache::clear;
+use sheila_cli::commands::cache::{self, clear};
+use sheila_cli::commands::completions::{self, COMPLETE_ENV};
 use sheila_cli::commands::control::{pause, resume, stop};
-use sheila_cli::commands::{list, report, test};
+use sheila_cli::commands::{
+    bench, clean, debug, generate, golden, history, list, logs, report, schema, snapshots, status,
+    test,
+};
+use sheila_cli::logging;
 
 #[tokio::main]
 async fn main() -> color_eyre::Result<()> {
+    // Answers the shell when it asks for completions, before anything else
+    // writes to stdout
+    CompleteEnv::with_factory(Cli::command)
+        .var(COMPLETE_ENV)
+        .complete();
+
     color_eyre::install()?;
-    let cli = Cli::parse();
+    let mut cli = Cli::parse();
+    logging::init(cli.verbose, cli.quiet);
+    cli.command.set_verbose(cli.verbose > 0);
 
     match cli.command {
-        Commands::Test(args) => test::run(args),
+        Commands::Test(args) if args.detach => test::detach(&args).await,
+        Commands::Test(args) => test::finish_detached(test::run(*args)).await,
         Commands::List(args) => list::run(args).await,
         Commands::Report(args) => report::run(args).await,
+        Commands::History(args) => history::run(args),
+        Commands::Bench(args) => bench::run(args),
+        Commands::Debug(args) => debug::run(args),
+        Commands::Generate(command) => generate::run(command),
+        Commands::Snapshots(command) => snapshots::run(command),
+        Commands::Golden(command) => golden::run(command),
+        Commands::Schema(command) => schema::run(command),
+        Commands::Status(args) => status::run(args).await,
+        Commands::Logs(args) => logs::run(args).await,
         Commands::Stop(args) => stop(args).await,
         Commands::Pause(args) => pause(args).await,
         Commands::Resume(args) => resume(args).await,
         Commands::ClearCache => clear().await,
+        Commands::Cache(command) => cache::run(command).await,
+        Commands::CleanReports(args) => clean::run(args),
+        Commands::Completions(args) => completions::run(args),
     }
 }
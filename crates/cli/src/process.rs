@@ -1,12 +1,20 @@
+use crate::control::{self, RunState};
 use serde::{Deserialize, Serialize};
 use std::collections::HashMap;
 use std::fs;
-use std::path::PathBuf;
+use std::path::{Path, PathBuf};
 use std::process::{Child, Command, Stdio};
 use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
 use tokio::sync::RwLock;
 use uuid::Uuid;
 
+/// How long a stopped run gets to wind down before it's killed
+const STOP_GRACE: Duration = Duration::from_secs(10);
+
+/// How often to check whether a stopped run has exited
+const STOP_POLL_INTERVAL: Duration = Duration::from_millis(100);
+
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct TestProcess {
     pub id: Uuid,
@@ -15,6 +23,41 @@ pub struct TestProcess {
     pub started_at: chrono::DateTime<chrono::Utc>,
     pub status: ProcessStatus,
     pub output_file: Option<PathBuf>,
+    /// NDJSON event log the run writes, when not the default one
+    #[serde(default)]
+    pub event_log: Option<PathBuf>,
+    /// OS process id of the run
+    #[serde(default)]
+    pub pid: Option<u32>,
+    /// When the run completed, failed or was stopped
+    #[serde(default)]
+    pub finished_at: Option<chrono::DateTime<chrono::Utc>>,
+}
+
+impl TestProcess {
+    /// Whether the run hasn't finished yet
+    pub fn is_active(&self) -> bool {
+        matches!(self.status, ProcessStatus::Running | ProcessStatus::Paused)
+    }
+
+    /// Whether the run hasn't finished and its process is still around
+    pub fn is_alive(&self) -> bool {
+        self.is_active() && self.pid.is_none_or(is_alive)
+    }
+
+    /// How long the run has been going, or went on for once finished
+    pub fn uptime(&self) -> std::time::Duration {
+        let end = self.finished_at.unwrap_or_else(chrono::Utc::now);
+        (end - self.started_at).to_std().unwrap_or_default()
+    }
+
+    /// The command line the run was started with
+    pub fn command_line(&self) -> String {
+        std::iter::once(self.command.as_str())
+            .chain(self.args.iter().map(String::as_str))
+            .collect::<Vec<_>>()
+            .join(" ")
+    }
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
@@ -26,6 +69,29 @@ pub enum ProcessStatus {
     Stopped,
 }
 
+impl std::fmt::Display for ProcessStatus {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            ProcessStatus::Running => write!(f, "running"),
+            ProcessStatus::Paused => write!(f, "paused"),
+            ProcessStatus::Completed { exit_code } => write!(f, "completed ({})", exit_code),
+            ProcessStatus::Failed { error } => write!(f, "failed: {}", error),
+            ProcessStatus::Stopped => write!(f, "stopped"),
+        }
+    }
+}
+
+/// Set in the environment of runs started by [`ProcessManager`], so the run
+/// can record how it finished
+pub const PROCESS_ID_ENV: &str = "SHEILA_PROCESS_ID";
+
+/// ID of this run, if it was started in the background by [`ProcessManager`]
+pub fn current_process_id() -> Option<Uuid> {
+    std::env::var(PROCESS_ID_ENV)
+        .ok()
+        .and_then(|id| id.parse().ok())
+}
+
 pub struct ProcessManager {
     processes: Arc<RwLock<HashMap<Uuid, TestProcess>>>,
     running_processes: Arc<Mutex<HashMap<Uuid, Child>>>,
@@ -44,40 +110,51 @@ impl ProcessManager {
         })
     }
 
+    /// Start `command` in the background, with its output going to
+    /// `<id>.log` in `output_dir` (the cache directory by default). Runs
+    /// that write their events somewhere other than
+    /// [`event_log_path`](Self::event_log_path) pass it as `event_log`.
     pub async fn start_process(
         &self,
         command: String,
         args: Vec<String>,
         output_dir: Option<PathBuf>,
+        event_log: Option<PathBuf>,
     ) -> color_eyre::Result<Uuid> {
         let id = Uuid::new_v4();
-        let output_file = output_dir.map(|dir| dir.join(format!("{}.json", id)));
-        let test_process = TestProcess {
-            id,
-            command: command.clone(),
-            args: args.clone(),
-            started_at: chrono::Utc::now(),
-            status: ProcessStatus::Running,
-            output_file: output_file.clone(),
-        };
-
-        self.save_process_info(&test_process).await?;
+        let output_dir = output_dir.unwrap_or_else(|| self.cache_dir.clone());
+        fs::create_dir_all(&output_dir)?;
+        let output_file = output_dir.join(format!("{}.log", id));
 
+        let log = fs::File::create(&output_file)?;
         let mut cmd = Command::new(&command);
         cmd.args(&args)
-            .stdout(Stdio::piped())
-            .stderr(Stdio::piped())
+            .env(PROCESS_ID_ENV, id.to_string())
+            .stdout(log.try_clone()?)
+            .stderr(log)
             .stdin(Stdio::null());
 
-        if let Some(output_path) = &output_file {
-            let output_file = fs::File::create(output_path)?;
-            cmd.stdout(output_file);
-        }
+        // Keep the run going when the terminal that started it is closed
+        #[cfg(unix)]
+        std::os::unix::process::CommandExt::process_group(&mut cmd, 0);
 
         let child = cmd.spawn().map_err(|_| {
             sheila::Error::generic(format!("Failed to start process: {} {:?}", command, args))
         })?;
 
+        let test_process = TestProcess {
+            id,
+            command: command.clone(),
+            args: args.clone(),
+            started_at: chrono::Utc::now(),
+            status: ProcessStatus::Running,
+            output_file: Some(output_file),
+            event_log,
+            pid: Some(child.id()),
+            finished_at: None,
+        };
+        self.save_process_info(&test_process).await?;
+
         {
             let mut running = self.running_processes.lock().unwrap();
             running.insert(id, child);
@@ -91,75 +168,100 @@ impl ProcessManager {
         Ok(id)
     }
 
+    /// OS process id of a run, whether started by this manager or loaded
+    /// from the cache
+    async fn pid(&self, id: Uuid) -> Option<u32> {
+        if let Some(child) = self.running_processes.lock().unwrap().get(&id) {
+            return Some(child.id());
+        }
+        self.get_process(id).await.and_then(|process| process.pid)
+    }
+
     pub async fn stop_process(&self, id: Uuid) -> color_eyre::Result<()> {
-        let mut running = self.running_processes.lock().unwrap();
+        let Some(pid) = self.pid(id).await else {
+            return Ok(());
+        };
 
-        if let Some(mut child) = running.remove(&id) {
+        // Ask the run to wind down so it can stop its own children and
+        // report partial results, rather than orphaning them. Runs without
+        // a control channel still take a signal.
+        let asked = control::call::<RunState>(&self.control_endpoint(id), "stop").await;
+        #[cfg(unix)]
+        if asked.is_err() {
+            unsafe {
+                libc::kill(pid as i32, libc::SIGTERM);
+            }
+        }
+
+        let mut child = self.running_processes.lock().unwrap().remove(&id);
+        #[cfg(not(unix))]
+        if let Some(child) = &mut child {
             child
                 .kill()
                 .map_err(|_| sheila::Error::generic(format!("Failed to kill process {}", id)))?;
-
-            let mut processes = self.processes.write().await;
-            if let Some(process) = processes.get_mut(&id) {
-                process.status = ProcessStatus::Stopped;
-                self.save_process_info(process).await?;
-            }
         }
-
-        Ok(())
-    }
-
-    pub async fn pause_process(&self, id: Uuid) -> color_eyre::Result<()> {
-        #[cfg(unix)]
-        {
-            let running = self.running_processes.lock().unwrap();
-            if let Some(child) = running.get(&id) {
+        #[cfg(not(unix))]
+        let _ = asked;
+
+        // Give the run a while to finish what it's doing, then kill it and
+        // whatever it started
+        let deadline = Instant::now() + STOP_GRACE;
+        loop {
+            let exited = match &mut child {
+                Some(child) => !matches!(child.try_wait(), Ok(None)),
+                None => !is_alive(pid),
+            };
+            if exited {
+                break;
+            }
+            if Instant::now() >= deadline {
+                #[cfg(unix)]
                 unsafe {
-                    libc::kill(child.id() as i32, libc::SIGSTOP);
+                    libc::kill(-(pid as i32), libc::SIGKILL);
                 }
-
-                let mut processes = self.processes.write().await;
-                if let Some(process) = processes.get_mut(&id) {
-                    process.status = ProcessStatus::Paused;
-                    self.save_process_info(process).await?;
+                if let Some(mut child) = child {
+                    let _ = child.kill();
+                    let _ = tokio::task::spawn_blocking(move || child.wait()).await;
                 }
+                break;
             }
+            tokio::time::sleep(STOP_POLL_INTERVAL).await;
         }
 
-        #[cfg(not(unix))]
-        {
-            return Err(sheila::Error::generic(
-                "Process pausing is not supported on this platform",
-            ));
+        let mut processes = self.processes.write().await;
+        if let Some(process) = processes.get_mut(&id) {
+            process.status = ProcessStatus::Stopped;
+            process.finished_at = Some(chrono::Utc::now());
+            self.save_process_info(process).await?;
         }
 
         Ok(())
     }
 
+    /// Hold run `id` over its control channel: it starts no more test
+    /// executables and suspends the running ones until resumed
+    pub async fn pause_process(&self, id: Uuid) -> color_eyre::Result<()> {
+        self.control(id, "pause").await?;
+        self.set_status(id, ProcessStatus::Paused).await
+    }
+
     pub async fn resume_process(&self, id: Uuid) -> color_eyre::Result<()> {
-        #[cfg(unix)]
-        {
-            let running = self.running_processes.lock().unwrap();
-            if let Some(child) = running.get(&id) {
-                unsafe {
-                    libc::kill(child.id() as i32, libc::SIGCONT);
-                }
+        self.control(id, "resume").await?;
+        self.set_status(id, ProcessStatus::Running).await
+    }
 
-                let mut processes = self.processes.write().await;
-                if let Some(process) = processes.get_mut(&id) {
-                    process.status = ProcessStatus::Running;
-                    self.save_process_info(process).await?;
-                }
-            }
-        }
+    async fn control(&self, id: Uuid, method: &str) -> color_eyre::Result<RunState> {
+        control::call(&self.control_endpoint(id), method)
+            .await
+            .map_err(|e| sheila::Error::generic(format!("Couldn't reach run {}: {}", id, e)).into())
+    }
 
-        #[cfg(not(unix))]
-        {
-            return Err(sheila::Error::generic(
-                "Process resuming is not supported on this platform",
-            ));
+    async fn set_status(&self, id: Uuid, status: ProcessStatus) -> color_eyre::Result<()> {
+        let mut processes = self.processes.write().await;
+        if let Some(process) = processes.get_mut(&id) {
+            process.status = status;
+            self.save_process_info(process).await?;
         }
-
         Ok(())
     }
 
@@ -168,9 +270,63 @@ impl ProcessManager {
         processes.get(&id).cloned()
     }
 
+    /// Every known run, oldest first
     pub async fn list_processes(&self) -> Vec<TestProcess> {
         let processes = self.processes.read().await;
-        processes.values().cloned().collect()
+        let mut processes: Vec<TestProcess> = processes.values().cloned().collect();
+        processes.sort_by_key(|process| process.started_at);
+        processes
+    }
+
+    /// Mark runs that died without recording how they finished, e.g. after
+    /// being killed, as failed
+    pub async fn refresh(&self) -> color_eyre::Result<()> {
+        let mut processes = self.processes.write().await;
+        for process in processes.values_mut() {
+            if !process.is_active() || process.pid.is_none_or(is_alive) {
+                continue;
+            }
+            // The run may have recorded its result since the cache was read
+            if let Some(recorded) = self.read_process_info(process.id)
+                && !recorded.is_active()
+            {
+                *process = recorded;
+                continue;
+            }
+            process.status = ProcessStatus::Failed {
+                error: "Exited without recording a result".to_string(),
+            };
+            process.finished_at = Some(chrono::Utc::now());
+            self.save_process_info(process).await?;
+        }
+        Ok(())
+    }
+
+    /// Record how the run `id` finished. Called by the run itself, so a run
+    /// that was already stopped keeps that status.
+    pub async fn record_exit(&self, id: Uuid, status: ProcessStatus) -> color_eyre::Result<()> {
+        let Some(mut process) = self.read_process_info(id) else {
+            return Ok(());
+        };
+        if !process.is_active() {
+            return Ok(());
+        }
+        process.status = status;
+        process.finished_at = Some(chrono::Utc::now());
+        self.save_process_info(&process).await
+    }
+
+    /// Record that run `id` was paused or resumed. Called by the run
+    /// itself, so requests from any client are reflected.
+    pub async fn record_status(&self, id: Uuid, status: ProcessStatus) -> color_eyre::Result<()> {
+        let Some(mut process) = self.read_process_info(id) else {
+            return Ok(());
+        };
+        if !process.is_active() {
+            return Ok(());
+        }
+        process.status = status;
+        self.save_process_info(&process).await
     }
 
     pub async fn cleanup_completed(&self) -> color_eyre::Result<()> {
@@ -226,6 +382,32 @@ impl ProcessManager {
         Ok(())
     }
 
+    /// Directory run records and their default logs are kept in
+    pub fn cache_dir(&self) -> &Path {
+        &self.cache_dir
+    }
+
+    /// Where a background run writes its events unless told otherwise
+    pub fn event_log_path(&self, id: Uuid) -> PathBuf {
+        self.cache_dir.join(format!("{}.ndjson", id))
+    }
+
+    /// Where a background run listens for control requests: a socket in
+    /// the cache directory, or a named pipe on Windows
+    pub fn control_endpoint(&self, id: Uuid) -> PathBuf {
+        #[cfg(windows)]
+        return PathBuf::from(format!(r"\\.\pipe\sheila-{}", id));
+
+        #[cfg(not(windows))]
+        self.cache_dir.join(format!("{}.sock", id))
+    }
+
+    /// Read the latest recorded state of run `id`
+    pub fn read_process_info(&self, id: Uuid) -> Option<TestProcess> {
+        let content = fs::read_to_string(self.cache_dir.join(format!("{}.json", id))).ok()?;
+        serde_json::from_str(&content).ok()
+    }
+
     pub async fn load_from_cache(&self) -> color_eyre::Result<()> {
         if !self.cache_dir.exists() {
             return Ok(());
@@ -254,3 +436,15 @@ impl ProcessManager {
         Ok(home.join(".sheila").join("cache"))
     }
 }
+
+/// Whether a process with this id exists
+#[cfg(unix)]
+fn is_alive(pid: u32) -> bool {
+    // SAFETY: signal 0 only checks that the process exists
+    unsafe { libc::kill(pid as i32, 0) == 0 }
+}
+
+#[cfg(not(unix))]
+fn is_alive(_pid: u32) -> bool {
+    true
+}
@@ -1,7 +1,9 @@
+use crate::snapshot::{SnapshotOutcome, check_snapshot};
 use crate::{Error, Result};
 use serde::{Deserialize, Serialize};
 use similar::{ChangeTag, TextDiff};
 use std::fmt::{Debug, Display};
+use std::path::Path;
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct AssertionResult {
@@ -65,6 +67,9 @@ impl AssertionResult {
         self
     }
 
+    /// The assertion as a result. A failure records where the assertion
+    /// was made, through any `#[track_caller]` functions that made it.
+    #[track_caller]
     pub fn into_result(self) -> Result<()> {
         if self.passed {
             Ok(())
@@ -88,7 +93,7 @@ impl AssertionResult {
     }
 }
 
-fn create_diff(expected: &str, actual: &str) -> String {
+pub(crate) fn create_diff(expected: &str, actual: &str) -> String {
     let diff = TextDiff::from_lines(expected, actual);
     let mut result = String::new();
 
@@ -111,6 +116,7 @@ impl Assertion {
         Self
     }
 
+    #[track_caller]
     pub fn is_true(value: bool) -> Result<()> {
         if value {
             AssertionResult::pass("Value is true".to_string()).into_result()
@@ -120,6 +126,7 @@ impl Assertion {
         }
     }
 
+    #[track_caller]
     pub fn is_false(value: bool) -> Result<()> {
         if !value {
             AssertionResult::pass("Value is false".to_string()).into_result()
@@ -129,6 +136,7 @@ impl Assertion {
         }
     }
 
+    #[track_caller]
     pub fn eq<T>(expected: T, actual: T) -> Result<()>
     where
         T: PartialEq + Debug + Display,
@@ -141,6 +149,7 @@ impl Assertion {
         }
     }
 
+    #[track_caller]
     pub fn ne<T>(expected: T, actual: T) -> Result<()>
     where
         T: PartialEq + Debug + Display,
@@ -157,6 +166,7 @@ impl Assertion {
         }
     }
 
+    #[track_caller]
     pub fn gt<T>(actual: T, expected: T) -> Result<()>
     where
         T: PartialOrd + Debug + Display,
@@ -173,6 +183,7 @@ impl Assertion {
         }
     }
 
+    #[track_caller]
     pub fn ge<T>(actual: T, expected: T) -> Result<()>
     where
         T: PartialOrd + Debug + Display,
@@ -190,6 +201,7 @@ impl Assertion {
         }
     }
 
+    #[track_caller]
     pub fn lt<T>(actual: T, expected: T) -> Result<()>
     where
         T: PartialOrd + Debug + Display,
@@ -206,6 +218,7 @@ impl Assertion {
         }
     }
 
+    #[track_caller]
     pub fn le<T>(actual: T, expected: T) -> Result<()>
     where
         T: PartialOrd + Debug + Display,
@@ -223,6 +236,7 @@ impl Assertion {
         }
     }
 
+    #[track_caller]
     pub fn is_none<T>(value: Option<T>) -> Result<()>
     where
         T: Debug,
@@ -238,6 +252,7 @@ impl Assertion {
         }
     }
 
+    #[track_caller]
     pub fn is_some<T>(value: Option<T>) -> Result<()>
     where
         T: Debug,
@@ -253,6 +268,7 @@ impl Assertion {
         }
     }
 
+    #[track_caller]
     pub fn is_ok<T, E>(value: std::result::Result<T, E>) -> Result<()>
     where
         T: Debug,
@@ -269,6 +285,7 @@ impl Assertion {
         }
     }
 
+    #[track_caller]
     pub fn is_err<T, E>(value: std::result::Result<T, E>) -> Result<()>
     where
         T: Debug,
@@ -285,6 +302,7 @@ impl Assertion {
         }
     }
 
+    #[track_caller]
     pub fn contains(haystack: &str, needle: &str) -> Result<()> {
         if haystack.contains(needle) {
             AssertionResult::pass(format!("String contains '{}'", needle)).into_result()
@@ -298,6 +316,7 @@ impl Assertion {
         }
     }
 
+    #[track_caller]
     pub fn starts_with(haystack: &str, prefix: &str) -> Result<()> {
         if haystack.starts_with(prefix) {
             AssertionResult::pass(format!("String starts with '{}'", prefix)).into_result()
@@ -311,6 +330,7 @@ impl Assertion {
         }
     }
 
+    #[track_caller]
     pub fn ends_with(haystack: &str, suffix: &str) -> Result<()> {
         if haystack.ends_with(suffix) {
             AssertionResult::pass(format!("String ends with '{}'", suffix)).into_result()
@@ -325,6 +345,7 @@ impl Assertion {
     }
 
     #[cfg(feature = "regex")]
+    #[track_caller]
     pub fn matches(haystack: &str, pattern: &str) -> Result<()> {
         use regex::Regex;
 
@@ -343,6 +364,7 @@ impl Assertion {
         }
     }
 
+    #[track_caller]
     pub fn is_empty<T>(collection: &[T]) -> Result<()> {
         if collection.is_empty() {
             AssertionResult::pass("Collection is empty".to_string()).into_result()
@@ -356,6 +378,7 @@ impl Assertion {
         }
     }
 
+    #[track_caller]
     pub fn is_not_empty<T>(collection: &[T]) -> Result<()> {
         if !collection.is_empty() {
             AssertionResult::pass("Collection is not empty".to_string()).into_result()
@@ -369,6 +392,7 @@ impl Assertion {
         }
     }
 
+    #[track_caller]
     pub fn has_length<T>(collection: &[T], expected_length: usize) -> Result<()> {
         let actual_length = collection.len();
         if actual_length == expected_length {
@@ -384,6 +408,7 @@ impl Assertion {
         }
     }
 
+    #[track_caller]
     pub fn contains_item<T>(collection: &[T], item: &T) -> Result<()>
     where
         T: PartialEq + Debug,
@@ -400,6 +425,7 @@ impl Assertion {
         }
     }
 
+    #[track_caller]
     pub fn approx_eq(actual: f64, expected: f64, epsilon: f64) -> Result<()> {
         let diff = (actual - expected).abs();
         if diff <= epsilon {
@@ -417,6 +443,35 @@ impl Assertion {
         }
     }
 
+    /// Compare `value` against snapshot `name` in `dir`. A value that
+    /// differs is saved for `sheila snapshots review`.
+    #[track_caller]
+    pub fn snapshot<P: AsRef<Path>, T: Display>(dir: P, name: &str, value: T) -> Result<()> {
+        let value = value.to_string();
+        match check_snapshot(dir.as_ref(), name, &value)? {
+            SnapshotOutcome::Matched | SnapshotOutcome::Updated => {
+                AssertionResult::pass(format!("Value matches snapshot '{}'", name)).into_result()
+            }
+            SnapshotOutcome::Pending(None) => AssertionResult::fail(format!(
+                "No snapshot named '{}' yet. Review it with `sheila snapshots review`",
+                name
+            ))
+            .into_result(),
+            SnapshotOutcome::Pending(Some(accepted)) => {
+                let mut result = AssertionResult::fail(format!(
+                    "Value doesn't match snapshot '{}'. Review it with `sheila snapshots review`",
+                    name
+                ));
+                result.diff = Some(create_diff(
+                    &accepted,
+                    &format!("{}\n", value.trim_end_matches(['\n', '\r'])),
+                ));
+                result.into_result()
+            }
+        }
+    }
+
+    #[track_caller]
     pub fn that<T, F>(value: T, predicate: F, message: &str) -> Result<()>
     where
         T: Debug,
@@ -0,0 +1,302 @@
+//! Benchmarks declared with `#[sheila::bench]` and run by `sheila bench`.
+//!
+//! The macro generates a libtest test that times the benchmark with a
+//! [`Bencher`] and prints its [`BenchResult`] as a line starting with
+//! [`BENCH_OUTPUT_PREFIX`]. `sheila bench` runs those tests one at a time and
+//! collects the lines.
+
+use std::collections::HashMap;
+use std::hint::black_box;
+use std::process::{Command, Stdio};
+use std::time::{Duration, Instant};
+
+use serde::{Deserialize, Serialize};
+
+use crate::runners::{LocalLauncher, list_executable_tests};
+use crate::{Error, Result, TestExecutable};
+
+/// Starts the line a benchmark prints its result on
+pub const BENCH_OUTPUT_PREFIX: &str = "sheila-bench: ";
+
+/// Suffix of the libtest names of the tests `#[sheila::bench]` generates
+pub const BENCH_TEST_SUFFIX: &str = "_sheila_bench";
+
+/// Variable `sheila bench` passes the [`BenchConfig`] to benchmarks in
+const BENCH_CONFIG_ENV: &str = "SHEILA_BENCH_CONFIG";
+
+/// How long a benchmark warms up and is measured for
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+pub struct BenchConfig {
+    pub warm_up: Duration,
+    pub measurement: Duration,
+    /// How many samples the measurement time is split into
+    pub samples: usize,
+}
+
+impl Default for BenchConfig {
+    fn default() -> Self {
+        Self {
+            warm_up: Duration::from_secs(1),
+            measurement: Duration::from_secs(3),
+            samples: 50,
+        }
+    }
+}
+
+impl BenchConfig {
+    /// The configuration `sheila bench` passed in, or the default when the
+    /// benchmark runs on its own
+    pub fn from_env() -> Self {
+        std::env::var(BENCH_CONFIG_ENV)
+            .ok()
+            .and_then(|config| serde_json::from_str(&config).ok())
+            .unwrap_or_default()
+    }
+}
+
+/// Times a benchmark's routine, passed to benchmarks that take an argument
+#[derive(Debug)]
+pub struct Bencher {
+    config: BenchConfig,
+    /// Nanoseconds per iteration of each sample
+    samples: Vec<f64>,
+    iterations: u64,
+}
+
+impl Bencher {
+    pub fn new(config: BenchConfig) -> Self {
+        Self {
+            config,
+            samples: Vec::new(),
+            iterations: 0,
+        }
+    }
+
+    /// Time `routine` by calling it repeatedly. Its return value is kept
+    /// from being optimised away.
+    pub fn iter<T, F: FnMut() -> T>(&mut self, mut routine: F) {
+        // Warm up with growing batches, which also estimates the time per call
+        let start = Instant::now();
+        let mut batch = 1u64;
+        let mut calls = 0u64;
+        let mut elapsed = Duration::ZERO;
+        while start.elapsed() < self.config.warm_up {
+            let batch_start = Instant::now();
+            for _ in 0..batch {
+                black_box(routine());
+            }
+            elapsed += batch_start.elapsed();
+            calls += batch;
+            batch = batch.saturating_mul(2);
+        }
+
+        let samples = self.config.samples.max(1);
+        let per_call = elapsed.as_nanos() as f64 / calls.max(1) as f64;
+        let per_sample = self.config.measurement.as_nanos() as f64 / samples as f64;
+        let iterations = ((per_sample / per_call.max(1.0)) as u64).max(1);
+
+        self.samples.clear();
+        for _ in 0..samples {
+            let sample_start = Instant::now();
+            for _ in 0..iterations {
+                black_box(routine());
+            }
+            self.samples
+                .push(sample_start.elapsed().as_nanos() as f64 / iterations as f64);
+        }
+        self.iterations = iterations * samples as u64;
+    }
+}
+
+/// Summary statistics of a benchmark's samples, in nanoseconds per iteration
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct BenchStats {
+    pub mean: f64,
+    pub median: f64,
+    pub std_dev: f64,
+    pub min: f64,
+    pub max: f64,
+    pub samples: usize,
+    pub iterations: u64,
+}
+
+impl BenchStats {
+    /// Statistics of `samples`, or `None` if there are none
+    pub fn from_samples(samples: &[f64], iterations: u64) -> Option<Self> {
+        if samples.is_empty() {
+            return None;
+        }
+
+        let mut sorted = samples.to_vec();
+        sorted.sort_by(f64::total_cmp);
+        let n = sorted.len();
+        let mean = sorted.iter().sum::<f64>() / n as f64;
+        let median = if n.is_multiple_of(2) {
+            (sorted[n / 2 - 1] + sorted[n / 2]) / 2.0
+        } else {
+            sorted[n / 2]
+        };
+        let variance = if n > 1 {
+            sorted.iter().map(|x| (x - mean).powi(2)).sum::<f64>() / (n - 1) as f64
+        } else {
+            0.0
+        };
+
+        Some(Self {
+            mean,
+            median,
+            std_dev: variance.sqrt(),
+            min: sorted[0],
+            max: sorted[n - 1],
+            samples: n,
+            iterations,
+        })
+    }
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct BenchResult {
+    /// Path of the benchmark function, e.g. `my_crate::parser::parse_config`
+    pub name: String,
+    pub stats: BenchStats,
+}
+
+/// Run a benchmark and print its result for `sheila bench`. Called by the
+/// tests `#[sheila::bench]` generates.
+pub fn run_bench<F: FnOnce(&mut Bencher)>(name: &str, bench: F) {
+    let mut bencher = Bencher::new(BenchConfig::from_env());
+    bench(&mut bencher);
+
+    let stats = BenchStats::from_samples(&bencher.samples, bencher.iterations)
+        .unwrap_or_else(|| panic!("Benchmark {} never called Bencher::iter", name));
+    let result = BenchResult {
+        name: name.to_string(),
+        stats,
+    };
+    println!(
+        "{}{}",
+        BENCH_OUTPUT_PREFIX,
+        serde_json::to_string(&result).expect("benchmark results serialize")
+    );
+}
+
+/// Run the benchmarks in `executable` whose names contain `filter`, one at
+/// a time so they don't compete for the CPU
+pub fn run_benches(
+    executable: &TestExecutable,
+    filter: Option<&str>,
+    config: &BenchConfig,
+) -> Result<Vec<BenchResult>> {
+    let config = serde_json::to_string(config)?;
+    let mut results = Vec::new();
+
+    for test in list_executable_tests(&LocalLauncher, executable)? {
+        let Some(name) = test.strip_suffix(BENCH_TEST_SUFFIX) else {
+            continue;
+        };
+        if filter.is_some_and(|filter| !name.contains(filter)) {
+            continue;
+        }
+
+        let output = Command::new(&executable.path)
+            .args([test.as_str(), "--exact", "--nocapture", "--test-threads=1"])
+            .env(BENCH_CONFIG_ENV, &config)
+            .stdin(Stdio::null())
+            .output()
+            .map_err(|e| {
+                Error::test_execution(format!("Failed to run benchmark {}: {}", name, e))
+            })?;
+
+        let stdout = String::from_utf8_lossy(&output.stdout);
+        if !output.status.success() {
+            return Err(Error::test_execution(format!(
+                "Benchmark {} failed:\n{}{}",
+                name,
+                stdout,
+                String::from_utf8_lossy(&output.stderr)
+            )));
+        }
+
+        // libtest starts the line with the test's name when run on one thread
+        for line in stdout.lines() {
+            if let Some((_, json)) = line.split_once(BENCH_OUTPUT_PREFIX) {
+                results.push(serde_json::from_str(json)?);
+            }
+        }
+    }
+
+    Ok(results)
+}
+
+/// A benchmark's result next to its result in a baseline
+#[derive(Debug, Clone)]
+pub struct BenchComparison {
+    pub name: String,
+    pub stats: BenchStats,
+    /// Stats from the baseline, if the benchmark is in it
+    pub baseline: Option<BenchStats>,
+}
+
+impl BenchComparison {
+    /// Pair each result with the baseline result of the same name
+    pub fn compare(results: &[BenchResult], baseline: &[BenchResult]) -> Vec<Self> {
+        let baseline: HashMap<&str, &BenchStats> = baseline
+            .iter()
+            .map(|result| (result.name.as_str(), &result.stats))
+            .collect();
+
+        results
+            .iter()
+            .map(|result| Self {
+                name: result.name.clone(),
+                stats: result.stats.clone(),
+                baseline: baseline
+                    .get(result.name.as_str())
+                    .map(|&stats| stats.clone()),
+            })
+            .collect()
+    }
+
+    /// Relative change in mean time from the baseline, e.g. `0.1` for 10%
+    /// slower
+    pub fn change(&self) -> Option<f64> {
+        self.baseline
+            .as_ref()
+            .filter(|baseline| baseline.mean > 0.0)
+            .map(|baseline| (self.stats.mean - baseline.mean) / baseline.mean)
+    }
+
+    /// Whether the benchmark got slower by more than `threshold`, a fraction
+    /// of the baseline's mean
+    pub fn regressed(&self, threshold: f64) -> bool {
+        self.change().is_some_and(|change| change > threshold)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_bench_stats_and_comparison() {
+        let stats = BenchStats::from_samples(&[4.0, 1.0, 3.0, 2.0], 40).unwrap();
+        assert_eq!(stats.mean, 2.5);
+        assert_eq!(stats.median, 2.5);
+        assert_eq!((stats.min, stats.max), (1.0, 4.0));
+        assert!((stats.std_dev - 1.2910).abs() < 1e-4);
+        assert!(BenchStats::from_samples(&[], 0).is_none());
+
+        let result = |name: &str, mean: f64| BenchResult {
+            name: name.to_string(),
+            stats: BenchStats::from_samples(&[mean], 1).unwrap(),
+        };
+        let comparisons = BenchComparison::compare(
+            &[result("a", 110.0), result("b", 90.0), result("c", 50.0)],
+            &[result("a", 100.0), result("b", 100.0)],
+        );
+        assert!(comparisons[0].regressed(0.05));
+        assert!(!comparisons[0].regressed(0.2));
+        assert!(!comparisons[1].regressed(0.05));
+        assert_eq!(comparisons[2].change(), None);
+    }
+}
@@ -0,0 +1,456 @@
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+use std::collections::{HashMap, HashSet};
+use std::env;
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::time::Duration;
+
+use crate::bench::BenchResult;
+use crate::runners::RunResult;
+use crate::{Result, TestExecutable, stable_hash, test_id};
+use uuid::Uuid;
+
+const LAST_RUN_FILE: &str = "last_run.json";
+const PROJECTS_DIR: &str = "projects";
+const RESULT_CACHE_FILE: &str = "results.json";
+const HISTORY_FILE: &str = "history.jsonl";
+const BASELINES_DIR: &str = "baselines";
+const TIMINGS_FILE: &str = "timings.json";
+const BUILDS_FILE: &str = "builds.json";
+
+/// How many run summaries are kept in the history
+const HISTORY_LIMIT: usize = 200;
+
+/// Root directory for everything sheila caches between runs.
+///
+/// Defaults to `~/.sheila/cache`, and can be overridden with `SHEILA_CACHE_DIR`.
+pub fn cache_dir() -> PathBuf {
+    if let Some(dir) = env::var_os("SHEILA_CACHE_DIR") {
+        return PathBuf::from(dir);
+    }
+
+    env::var_os("HOME")
+        .map(PathBuf::from)
+        .unwrap_or_else(env::temp_dir)
+        .join(".sheila")
+        .join("cache")
+}
+
+/// Per-project cache of run results, stored under [`cache_dir`] and keyed
+/// by a hash of the project root.
+#[derive(Debug, Clone)]
+pub struct RunCache {
+    dir: PathBuf,
+}
+
+impl RunCache {
+    pub fn new<P: AsRef<Path>>(project_root: P) -> Self {
+        let key = stable_hash(project_root.as_ref().to_string_lossy().as_bytes());
+        Self {
+            dir: cache_dir().join(PROJECTS_DIR).join(format!("{:016x}", key)),
+        }
+    }
+
+    /// The caches of every project that has one
+    pub fn all() -> Result<Vec<Self>> {
+        let projects = cache_dir().join(PROJECTS_DIR);
+        if !projects.exists() {
+            return Ok(Vec::new());
+        }
+
+        let mut caches = Vec::new();
+        for entry in fs::read_dir(projects)? {
+            let entry = entry?;
+            if entry.file_type()?.is_dir() {
+                caches.push(Self { dir: entry.path() });
+            }
+        }
+        caches.sort_by(|a, b| a.dir.cmp(&b.dir));
+        Ok(caches)
+    }
+
+    /// Cache for the project in the current working directory
+    pub fn current() -> Result<Self> {
+        Ok(Self::new(env::current_dir()?))
+    }
+
+    pub fn dir(&self) -> &Path {
+        &self.dir
+    }
+
+    /// Directory the saved benchmark baselines are kept in
+    pub fn baselines_dir(&self) -> PathBuf {
+        self.dir.join(BASELINES_DIR)
+    }
+
+    pub fn save_last_run(&self, run_result: &RunResult) -> Result<()> {
+        fs::create_dir_all(&self.dir)?;
+        let content = serde_json::to_string(run_result)?;
+        fs::write(self.dir.join(LAST_RUN_FILE), content)?;
+        Ok(())
+    }
+
+    pub fn load_last_run(&self) -> Result<Option<RunResult>> {
+        let path = self.dir.join(LAST_RUN_FILE);
+        if !path.exists() {
+            return Ok(None);
+        }
+
+        let content = fs::read_to_string(path)?;
+        Ok(Some(serde_json::from_str(&content)?))
+    }
+
+    /// `(suite, test)` pairs that failed or were flaky in the last run, or
+    /// `None` if there is no previous run to go by.
+    pub fn last_failed(&self) -> Result<Option<HashSet<(String, String)>>> {
+        Ok(self
+            .load_last_run()?
+            .map(|run_result| run_result.tests_to_rerun()))
+    }
+
+    /// Expected duration of each `(suite, test)` pair, used to estimate how
+    /// long a run will take: averaged over recent runs, or taken from the
+    /// last run when no timings were recorded yet.
+    pub fn duration_history(&self) -> Result<HashMap<(String, String), Duration>> {
+        let timings = self.load_timings()?;
+        if !timings.is_empty() {
+            return Ok(timings.durations());
+        }
+
+        Ok(self
+            .load_last_run()?
+            .map(|run_result| run_result.test_durations())
+            .unwrap_or_default())
+    }
+
+    pub fn load_timings(&self) -> Result<TestTimings> {
+        let path = self.dir.join(TIMINGS_FILE);
+        if !path.exists() {
+            return Ok(TestTimings::default());
+        }
+
+        let content = fs::read_to_string(path)?;
+        Ok(serde_json::from_str(&content).unwrap_or_default())
+    }
+
+    /// Fold the durations of the tests that ran in `run_result` into the
+    /// recorded timings
+    pub fn record_timings(&self, run_result: &RunResult) -> Result<()> {
+        let mut timings = self.load_timings()?;
+        timings.record(run_result);
+
+        fs::create_dir_all(&self.dir)?;
+        let content = serde_json::to_string(&timings)?;
+        fs::write(self.dir.join(TIMINGS_FILE), content)?;
+        Ok(())
+    }
+
+    /// Append a summary of `run_result` to the run history, dropping the
+    /// oldest summaries once there are more than the history keeps.
+    pub fn append_history(&self, run_result: &RunResult) -> Result<()> {
+        let mut history = self.load_history(HISTORY_LIMIT - 1)?;
+        history.push(RunSummary::from(run_result));
+
+        let mut content = String::new();
+        for summary in history.iter() {
+            content.push_str(&serde_json::to_string(summary)?);
+            content.push('\n');
+        }
+
+        fs::create_dir_all(&self.dir)?;
+        fs::write(self.dir.join(HISTORY_FILE), content)?;
+        Ok(())
+    }
+
+    /// Summaries of up to `limit` of the most recent runs, oldest first.
+    /// Lines that can't be read, e.g. from an older version, are skipped.
+    pub fn load_history(&self, limit: usize) -> Result<Vec<RunSummary>> {
+        let path = self.dir.join(HISTORY_FILE);
+        if !path.exists() {
+            return Ok(Vec::new());
+        }
+
+        let content = fs::read_to_string(path)?;
+        let history: Vec<RunSummary> = content
+            .lines()
+            .filter_map(|line| serde_json::from_str(line).ok())
+            .collect();
+        let skip = history.len().saturating_sub(limit);
+        Ok(history.into_iter().skip(skip).collect())
+    }
+
+    pub fn load_result_cache(&self) -> Result<ResultCache> {
+        let path = self.dir.join(RESULT_CACHE_FILE);
+        if !path.exists() {
+            return Ok(ResultCache::default());
+        }
+
+        let content = fs::read_to_string(path)?;
+        Ok(serde_json::from_str(&content).unwrap_or_default())
+    }
+
+    pub fn save_result_cache(&self, cache: &ResultCache) -> Result<()> {
+        fs::create_dir_all(&self.dir)?;
+        let content = serde_json::to_string(cache)?;
+        fs::write(self.dir.join(RESULT_CACHE_FILE), content)?;
+        Ok(())
+    }
+
+    /// The last successful build with the cargo arguments and environment
+    /// that `key` stands for
+    pub fn load_build(&self, key: &str) -> Result<Option<CachedBuild>> {
+        Ok(self.load_builds()?.remove(key))
+    }
+
+    /// Record `build` as the last one for `key`, replacing the one before
+    pub fn save_build(&self, key: &str, build: CachedBuild) -> Result<()> {
+        let mut builds = self.load_builds()?;
+        builds.insert(key.to_string(), build);
+
+        fs::create_dir_all(&self.dir)?;
+        fs::write(self.dir.join(BUILDS_FILE), serde_json::to_string(&builds)?)?;
+        Ok(())
+    }
+
+    fn load_builds(&self) -> Result<HashMap<String, CachedBuild>> {
+        let path = self.dir.join(BUILDS_FILE);
+        if !path.exists() {
+            return Ok(HashMap::new());
+        }
+
+        let content = fs::read_to_string(path)?;
+        Ok(serde_json::from_str(&content).unwrap_or_default())
+    }
+
+    /// Save benchmark results as the baseline called `name`, replacing any
+    /// baseline of that name
+    pub fn save_bench_baseline(&self, name: &str, results: &[BenchResult]) -> Result<()> {
+        let dir = self.baselines_dir();
+        fs::create_dir_all(&dir)?;
+        let content = serde_json::to_string_pretty(results)?;
+        fs::write(dir.join(format!("{}.json", name)), content)?;
+        Ok(())
+    }
+
+    /// The benchmark baseline called `name`, or `None` if none was saved
+    pub fn load_bench_baseline(&self, name: &str) -> Result<Option<Vec<BenchResult>>> {
+        let path = self.baselines_dir().join(format!("{}.json", name));
+        if !path.exists() {
+            return Ok(None);
+        }
+
+        let content = fs::read_to_string(path)?;
+        Ok(Some(serde_json::from_str(&content)?))
+    }
+}
+
+/// The test executables a build produced, reused while none of the files
+/// it was built from have changed
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct CachedBuild {
+    /// Fingerprint of the workspace's files when it was built
+    pub sources: u64,
+    pub executables: Vec<TestExecutable>,
+    pub built_at: DateTime<Utc>,
+}
+
+/// The headline numbers of a run, kept in the run history to show how a
+/// project's tests trend over time.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct RunSummary {
+    pub id: Uuid,
+    pub start_time: DateTime<Utc>,
+    pub duration: Option<Duration>,
+    pub total_tests: usize,
+    pub passed_tests: usize,
+    pub failed_tests: usize,
+    pub skipped_tests: usize,
+    pub flaky_tests: usize,
+    /// Failing or flaky tests, as `suite::test`
+    pub failures: Vec<String>,
+}
+
+impl RunSummary {
+    /// Share of the tests that ran which passed, between 0 and 1
+    pub fn pass_rate(&self) -> f64 {
+        let ran = self.passed_tests + self.failed_tests;
+        if ran == 0 {
+            return 1.0;
+        }
+        self.passed_tests as f64 / ran as f64
+    }
+}
+
+impl From<&RunResult> for RunSummary {
+    fn from(run_result: &RunResult) -> Self {
+        let mut failures: Vec<String> = run_result
+            .tests_to_rerun()
+            .into_iter()
+            .map(|(suite, test)| format!("{}::{}", suite, test))
+            .collect();
+        failures.sort();
+
+        Self {
+            id: run_result.id,
+            start_time: run_result.start_time,
+            duration: run_result.duration,
+            total_tests: run_result.total_tests,
+            passed_tests: run_result.passed_tests,
+            failed_tests: run_result.failed_tests,
+            skipped_tests: run_result.skipped_tests,
+            flaky_tests: run_result.flaky_tests,
+            failures,
+        }
+    }
+}
+
+/// How long each test takes, as a moving average over the runs it took
+/// part in, so one slow or fast run doesn't throw off estimates.
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct TestTimings {
+    /// Keyed by the tests' stable ids, so timings follow a test from run to
+    /// run
+    #[serde(default)]
+    tests: HashMap<Uuid, TestTiming>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct TestTiming {
+    pub suite: String,
+    pub test: String,
+    pub mean: Duration,
+    pub runs: u32,
+}
+
+impl TestTimings {
+    /// Weight of the newest duration in the moving average
+    const SMOOTHING: f64 = 0.3;
+
+    /// Fold in the durations of the tests that passed or failed in
+    /// `run_result`; skipped and cached tests say nothing about timing
+    pub fn record(&mut self, run_result: &RunResult) {
+        for suite in &run_result.suite_results {
+            for test in &suite.test_results {
+                let Some(duration) = test.duration.filter(|_| test.passed() || test.failed())
+                else {
+                    continue;
+                };
+
+                self.tests
+                    .entry(test.stable_id)
+                    .and_modify(|timing| {
+                        timing.mean = timing.mean.mul_f64(1.0 - Self::SMOOTHING)
+                            + duration.mul_f64(Self::SMOOTHING);
+                        timing.runs += 1;
+                    })
+                    .or_insert_with(|| TestTiming {
+                        suite: suite.name.clone(),
+                        test: test.qualified_name().to_string(),
+                        mean: duration,
+                        runs: 1,
+                    });
+            }
+        }
+    }
+
+    pub fn get(&self, suite: &str, test: &str) -> Option<&TestTiming> {
+        self.tests.get(&test_id(suite, test))
+    }
+
+    /// Expected duration of each recorded `(suite, test)` pair
+    pub fn durations(&self) -> HashMap<(String, String), Duration> {
+        self.tests
+            .values()
+            .map(|timing| ((timing.suite.clone(), timing.test.clone()), timing.mean))
+            .collect()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.tests.is_empty()
+    }
+}
+
+/// Content-addressed record of tests that passed, keyed by a hash of the
+/// test binary, the test's stable id and an environment fingerprint. A test whose
+/// key is present can be skipped and reported as [`TestStatus::Cached`].
+///
+/// [`TestStatus::Cached`]: crate::TestStatus::Cached
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct ResultCache {
+    entries: HashMap<String, CachedResult>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct CachedResult {
+    pub suite: String,
+    pub test: String,
+    pub recorded_at: DateTime<Utc>,
+}
+
+impl ResultCache {
+    /// Key of the test with [`test_id`](crate::test_id) `test`
+    pub fn key(binary_hash: u64, test: Uuid, env_fingerprint: u64) -> String {
+        let mut bytes = Vec::with_capacity(32);
+        bytes.extend_from_slice(&binary_hash.to_le_bytes());
+        bytes.extend_from_slice(test.as_bytes());
+        bytes.extend_from_slice(&env_fingerprint.to_le_bytes());
+        format!("{:016x}", stable_hash(&bytes))
+    }
+
+    pub fn contains(&self, key: &str) -> bool {
+        self.entries.contains_key(key)
+    }
+
+    pub fn record<S: Into<String>, T: Into<String>>(&mut self, key: String, suite: S, test: T) {
+        self.entries.insert(
+            key,
+            CachedResult {
+                suite: suite.into(),
+                test: test.into(),
+                recorded_at: Utc::now(),
+            },
+        );
+    }
+
+    pub fn remove(&mut self, key: &str) {
+        self.entries.remove(key);
+    }
+
+    pub fn len(&self) -> usize {
+        self.entries.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.entries.is_empty()
+    }
+}
+
+/// Hash of a file's contents
+pub fn hash_file<P: AsRef<Path>>(path: P) -> Result<u64> {
+    Ok(stable_hash(&fs::read(path)?))
+}
+
+/// Fingerprint of the environment a test runs in: the explicitly configured
+/// variables plus any `RUST*` / `SHEILA_*` variables from the current process.
+pub fn env_fingerprint(configured: &HashMap<String, String>, args: &[String]) -> u64 {
+    let mut vars: Vec<(String, String)> = env::vars()
+        .filter(|(name, _)| name.starts_with("RUST") || name.starts_with("SHEILA_"))
+        .chain(configured.iter().map(|(k, v)| (k.clone(), v.clone())))
+        .collect();
+    vars.sort();
+
+    let mut bytes = Vec::new();
+    for (name, value) in vars {
+        bytes.extend_from_slice(name.as_bytes());
+        bytes.push(b'=');
+        bytes.extend_from_slice(value.as_bytes());
+        bytes.push(0);
+    }
+    for arg in args {
+        bytes.extend_from_slice(arg.as_bytes());
+        bytes.push(0);
+    }
+
+    stable_hash(&bytes)
+}
@@ -0,0 +1,592 @@
+use super::Fixture;
+use crate::internal::ArgMatcher;
+use crate::test::TestContext;
+use crate::{Error, Result};
+use indexmap::IndexMap;
+use parking_lot::Mutex;
+use serde::Serialize;
+use serde::de::DeserializeOwned;
+use std::fmt;
+use std::io::{BufRead, BufReader, Read, Write};
+use std::net::{SocketAddr, TcpListener, TcpStream};
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::{Arc, OnceLock};
+use std::thread::{self, JoinHandle};
+use std::time::Duration;
+
+/// Key the URL of the running [`HttpMock`] fixture is stored under in the
+/// suite's [`SharedState`](crate::SharedState)
+const URL_KEY: &str = "sheila.http_mock.url";
+
+/// How long a connection may take to send its request
+const READ_TIMEOUT: Duration = Duration::from_secs(5);
+
+/// A request the mock server received
+#[derive(Debug, Clone, Serialize)]
+pub struct HttpRequest {
+    pub method: String,
+    /// Path without the query string, e.g. `/users/1`
+    pub path: String,
+    /// Query parameters, as sent
+    pub query: IndexMap<String, String>,
+    /// Headers, with lowercased names
+    pub headers: IndexMap<String, String>,
+    /// Body, lossily decoded as UTF-8
+    pub body: String,
+}
+
+impl HttpRequest {
+    pub fn header(&self, name: &str) -> Option<&str> {
+        self.headers
+            .get(&name.to_ascii_lowercase())
+            .map(String::as_str)
+    }
+
+    pub fn json<T: DeserializeOwned>(&self) -> Result<T> {
+        Ok(serde_json::from_str(&self.body)?)
+    }
+
+    /// The body as JSON if it is JSON, or as a JSON string otherwise, for
+    /// checking against an [`ArgMatcher`]
+    fn body_value(&self) -> serde_json::Value {
+        serde_json::from_str(&self.body).unwrap_or_else(|_| self.body.clone().into())
+    }
+}
+
+impl fmt::Display for HttpRequest {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{} {}", self.method, self.path)?;
+        for (i, (name, value)) in self.query.iter().enumerate() {
+            write!(f, "{}{}={}", if i == 0 { '?' } else { '&' }, name, value)?;
+        }
+        Ok(())
+    }
+}
+
+/// A canned response the mock server sends back
+#[derive(Debug, Clone, PartialEq)]
+pub struct HttpResponse {
+    pub status: u16,
+    pub headers: Vec<(String, String)>,
+    pub body: Vec<u8>,
+}
+
+impl HttpResponse {
+    pub fn new(status: u16) -> Self {
+        Self {
+            status,
+            headers: Vec::new(),
+            body: Vec::new(),
+        }
+    }
+
+    pub fn with_header<N: Into<String>, V: Into<String>>(mut self, name: N, value: V) -> Self {
+        self.headers.push((name.into(), value.into()));
+        self
+    }
+
+    pub fn with_body<B: Into<Vec<u8>>>(mut self, body: B) -> Self {
+        self.body = body.into();
+        self
+    }
+
+    /// Respond with `value` as JSON, setting the content type
+    pub fn with_json<T: Serialize>(self, value: &T) -> Result<Self> {
+        Ok(self
+            .with_header("content-type", "application/json")
+            .with_body(serde_json::to_vec(value)?))
+    }
+
+    fn write_to(&self, stream: &mut TcpStream) -> std::io::Result<()> {
+        let mut head = format!("HTTP/1.1 {} {}\r\n", self.status, reason(self.status));
+        for (name, value) in &self.headers {
+            head.push_str(&format!("{}: {}\r\n", name, value));
+        }
+        head.push_str(&format!(
+            "content-length: {}\r\nconnection: close\r\n\r\n",
+            self.body.len()
+        ));
+
+        stream.write_all(head.as_bytes())?;
+        stream.write_all(&self.body)?;
+        stream.flush()
+    }
+}
+
+impl Default for HttpResponse {
+    fn default() -> Self {
+        Self::new(200)
+    }
+}
+
+/// A request the code under test is expected to make, and the response it
+/// gets for it
+#[derive(Debug, Clone)]
+pub struct HttpExpectation {
+    method: String,
+    path: String,
+    query: Vec<(String, String)>,
+    headers: Vec<(String, String)>,
+    body: Option<ArgMatcher>,
+    response: HttpResponse,
+    times: Option<usize>,
+}
+
+impl HttpExpectation {
+    pub fn new<M: Into<String>, P: Into<String>>(method: M, path: P) -> Self {
+        Self {
+            method: method.into().to_ascii_uppercase(),
+            path: path.into(),
+            query: Vec::new(),
+            headers: Vec::new(),
+            body: None,
+            response: HttpResponse::default(),
+            times: None,
+        }
+    }
+
+    pub fn with_query<N: Into<String>, V: Into<String>>(mut self, name: N, value: V) -> Self {
+        self.query.push((name.into(), value.into()));
+        self
+    }
+
+    pub fn with_header<N: Into<String>, V: Into<String>>(mut self, name: N, value: V) -> Self {
+        self.headers
+            .push((name.into().to_ascii_lowercase(), value.into()));
+        self
+    }
+
+    /// Only match requests whose body matches, as JSON if it is JSON and
+    /// as a string otherwise
+    pub fn with_body(mut self, matcher: ArgMatcher) -> Self {
+        self.body = Some(matcher);
+        self
+    }
+
+    pub fn responds_with(mut self, response: HttpResponse) -> Self {
+        self.response = response;
+        self
+    }
+
+    /// Expect exactly `times` matching requests by the time the mock is
+    /// verified
+    pub fn times(mut self, times: usize) -> Self {
+        self.times = Some(times);
+        self
+    }
+
+    fn matches(&self, request: &HttpRequest) -> bool {
+        self.method == request.method
+            && self.path == request.path
+            && self
+                .query
+                .iter()
+                .all(|(name, value)| request.query.get(name) == Some(value))
+            && self
+                .headers
+                .iter()
+                .all(|(name, value)| request.header(name) == Some(value.as_str()))
+            && self
+                .body
+                .as_ref()
+                .is_none_or(|matcher| matcher.matches(&request.body_value()))
+    }
+}
+
+impl fmt::Display for HttpExpectation {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{} {}", self.method, self.path)?;
+        for (i, (name, value)) in self.query.iter().enumerate() {
+            write!(f, "{}{}={}", if i == 0 { '?' } else { '&' }, name, value)?;
+        }
+        if let Some(ref body) = self.body {
+            write!(f, " with body {}", body)?;
+        }
+        Ok(())
+    }
+}
+
+#[derive(Debug, Default)]
+struct Requests {
+    expectations: Vec<(HttpExpectation, usize)>,
+    received: Vec<HttpRequest>,
+    unmatched: Vec<HttpRequest>,
+}
+
+impl Requests {
+    /// Record `request`, answering it with the first expectation it matches
+    /// or a 404 if there is none
+    fn answer(&mut self, request: HttpRequest) -> HttpResponse {
+        let response = match self
+            .expectations
+            .iter_mut()
+            .find(|(expectation, _)| expectation.matches(&request))
+        {
+            Some((expectation, hits)) => {
+                *hits += 1;
+                expectation.response.clone()
+            }
+            None => {
+                let response =
+                    HttpResponse::new(404).with_body(format!("No expectation matches {}", request));
+                self.unmatched.push(request.clone());
+                response
+            }
+        };
+
+        self.received.push(request);
+        response
+    }
+}
+
+#[derive(Debug)]
+struct Server {
+    addr: SocketAddr,
+    requests: Arc<Mutex<Requests>>,
+    stopping: Arc<AtomicBool>,
+    thread: Mutex<Option<JoinHandle<()>>>,
+}
+
+impl Drop for Server {
+    fn drop(&mut self) {
+        self.stopping.store(true, Ordering::SeqCst);
+        // Wake the accept loop so it sees it should stop
+        let _ = TcpStream::connect(self.addr);
+        if let Some(thread) = self.thread.lock().take() {
+            let _ = thread.join();
+        }
+    }
+}
+
+/// A local HTTP server answering the requests a test expects with canned
+/// responses, in the style of `wiremock`.
+///
+/// Point the code under test at [`url`](HttpMock::url), declare what it
+/// should request with [`expect`](HttpMock::expect), and
+/// [`verify`](HttpMock::verify) that it did. Requests that match no
+/// expectation are answered with a 404 and fail verification.
+///
+/// As a fixture, the server is started at setup and verified at teardown,
+/// failing the test (or suite, for suite-scoped fixtures) if expectations
+/// weren't met. Register it at whichever scope it should live for, and
+/// tests get hold of it with [`HttpMock::from_context`]:
+///
+/// ```ignore
+/// let suite = TestSuite::new("client")
+///     .with_fixture(HttpMock::definition("http"))
+///     .add_test(Test::new("fetches_user", |ctx| {
+///         let server = HttpMock::from_context(&ctx)?;
+///         server.expect(
+///             HttpExpectation::new("GET", "/users/1")
+///                 .responds_with(HttpResponse::new(200).with_json(&user)?)
+///                 .times(1),
+///         );
+///         Client::new(server.url()).user(1)?;
+///         Ok(())
+///     }));
+/// ```
+///
+/// The server is stopped once the last handle to it is dropped.
+#[derive(Debug, Clone)]
+pub struct HttpMock {
+    server: Arc<Server>,
+}
+
+impl HttpMock {
+    /// Start a server on a free port on the loopback interface
+    pub fn start() -> Result<Self> {
+        let listener = TcpListener::bind(("127.0.0.1", 0))?;
+        let addr = listener.local_addr()?;
+        let requests = Arc::new(Mutex::new(Requests::default()));
+        let stopping = Arc::new(AtomicBool::new(false));
+
+        let thread = thread::Builder::new()
+            .name(format!("sheila-http-mock-{}", addr.port()))
+            .spawn({
+                let requests = requests.clone();
+                let stopping = stopping.clone();
+                move || serve(listener, requests, stopping)
+            })?;
+
+        Ok(Self {
+            server: Arc::new(Server {
+                addr,
+                requests,
+                stopping,
+                thread: Mutex::new(Some(thread)),
+            }),
+        })
+    }
+
+    /// The server started by the `HttpMock` fixture for this test or its
+    /// suite
+    pub fn from_context(context: &TestContext) -> Result<Self> {
+        let url: String = context
+            .get(URL_KEY)
+            .map_err(|_| Error::fixture("No HttpMock fixture is set up for this test"))?;
+        running()
+            .lock()
+            .get(&url)
+            .cloned()
+            .ok_or_else(|| Error::fixture(format!("The HttpMock at {} has stopped", url)))
+    }
+
+    pub fn addr(&self) -> SocketAddr {
+        self.server.addr
+    }
+
+    /// Base URL of the server, e.g. `http://127.0.0.1:41234`
+    pub fn url(&self) -> String {
+        format!("http://{}", self.server.addr)
+    }
+
+    /// URL of `path` on the server
+    pub fn url_for(&self, path: &str) -> String {
+        format!("{}/{}", self.url(), path.trim_start_matches('/'))
+    }
+
+    /// Answer requests matching `expectation` with its response. When a
+    /// request matches several expectations, the first one added answers.
+    pub fn expect(&self, expectation: HttpExpectation) -> &Self {
+        self.server
+            .requests
+            .lock()
+            .expectations
+            .push((expectation, 0));
+        self
+    }
+
+    /// Every request received so far, in order
+    pub fn received_requests(&self) -> Vec<HttpRequest> {
+        self.server.requests.lock().received.clone()
+    }
+
+    /// Forget all expectations and received requests
+    pub fn reset(&self) {
+        *self.server.requests.lock() = Requests::default();
+    }
+
+    /// Check every expectation was matched as many times as it expects,
+    /// and that no request went unmatched
+    pub fn verify(&self) -> Result<()> {
+        let requests = self.server.requests.lock();
+        let mut problems = Vec::new();
+
+        for (expectation, hits) in &requests.expectations {
+            if let Some(times) = expectation.times
+                && *hits != times
+            {
+                problems.push(format!(
+                    "expected {} request(s) for {}, got {}",
+                    times, expectation, hits
+                ));
+            }
+        }
+        for request in &requests.unmatched {
+            problems.push(format!("no expectation matched {}", request));
+        }
+
+        if problems.is_empty() {
+            Ok(())
+        } else {
+            Err(Error::mock(format!(
+                "HTTP mock verification failed: {}",
+                problems.join("; ")
+            )))
+        }
+    }
+}
+
+impl Fixture for HttpMock {
+    type Output = HttpMock;
+
+    fn setup(context: TestContext) -> Result<Self::Output> {
+        let server = HttpMock::start()?;
+        running().lock().insert(server.url(), server.clone());
+        context.set(URL_KEY, server.url())?;
+        Ok(server)
+    }
+
+    fn teardown(server: Self::Output, context: TestContext) -> Result<()> {
+        running().lock().shift_remove(&server.url());
+        let _ = context.state().remove(URL_KEY);
+        server.verify()
+    }
+}
+
+/// Servers started as fixtures, by URL
+fn running() -> &'static Mutex<IndexMap<String, HttpMock>> {
+    static RUNNING: OnceLock<Mutex<IndexMap<String, HttpMock>>> = OnceLock::new();
+    RUNNING.get_or_init(Default::default)
+}
+
+fn serve(listener: TcpListener, requests: Arc<Mutex<Requests>>, stopping: Arc<AtomicBool>) {
+    for stream in listener.incoming() {
+        if stopping.load(Ordering::SeqCst) {
+            break;
+        }
+        let Ok(mut stream) = stream else {
+            continue;
+        };
+
+        let requests = requests.clone();
+        thread::spawn(move || {
+            let response = match read_request(&mut stream) {
+                Ok(request) => requests.lock().answer(request),
+                Err(e) => HttpResponse::new(400).with_body(e.to_string()),
+            };
+            if let Err(e) = response.write_to(&mut stream) {
+                tracing::debug!("HttpMock could not write a response: {}", e);
+            }
+        });
+    }
+}
+
+fn read_request(stream: &mut TcpStream) -> Result<HttpRequest> {
+    stream.set_read_timeout(Some(READ_TIMEOUT))?;
+    let mut reader = BufReader::new(stream);
+
+    let mut line = String::new();
+    reader.read_line(&mut line)?;
+    let mut parts = line.split_whitespace();
+    let (Some(method), Some(target)) = (parts.next(), parts.next()) else {
+        return Err(Error::generic(format!(
+            "Malformed request line '{}'",
+            line.trim_end()
+        )));
+    };
+    let method = method.to_ascii_uppercase();
+    let (path, query) = target.split_once('?').unwrap_or((target, ""));
+    let path = path.to_string();
+    let query = query
+        .split('&')
+        .filter(|pair| !pair.is_empty())
+        .map(|pair| {
+            let (name, value) = pair.split_once('=').unwrap_or((pair, ""));
+            (name.to_string(), value.to_string())
+        })
+        .collect();
+
+    let mut headers = IndexMap::new();
+    loop {
+        line.clear();
+        if reader.read_line(&mut line)? == 0 {
+            break;
+        }
+        let header = line.trim_end();
+        if header.is_empty() {
+            break;
+        }
+        if let Some((name, value)) = header.split_once(':') {
+            headers.insert(name.trim().to_ascii_lowercase(), value.trim().to_string());
+        }
+    }
+
+    let length = match headers.get("content-length") {
+        Some(length) => length
+            .parse()
+            .map_err(|_| Error::generic(format!("Invalid content-length '{}'", length)))?,
+        None => 0,
+    };
+    let mut body = vec![0; length];
+    reader.read_exact(&mut body)?;
+
+    Ok(HttpRequest {
+        method,
+        path,
+        query,
+        headers,
+        body: String::from_utf8_lossy(&body).into_owned(),
+    })
+}
+
+fn reason(status: u16) -> &'static str {
+    match status {
+        200 => "OK",
+        201 => "Created",
+        202 => "Accepted",
+        204 => "No Content",
+        301 => "Moved Permanently",
+        302 => "Found",
+        304 => "Not Modified",
+        400 => "Bad Request",
+        401 => "Unauthorized",
+        403 => "Forbidden",
+        404 => "Not Found",
+        409 => "Conflict",
+        422 => "Unprocessable Entity",
+        429 => "Too Many Requests",
+        500 => "Internal Server Error",
+        502 => "Bad Gateway",
+        503 => "Service Unavailable",
+        504 => "Gateway Timeout",
+        _ => "",
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::internal::matcher::eq;
+    use crate::{Test, TestSuite};
+    use serde_json::json;
+
+    fn send(server: &HttpMock, request: &str) -> String {
+        let mut stream = TcpStream::connect(server.addr()).unwrap();
+        stream.write_all(request.as_bytes()).unwrap();
+        let mut response = String::new();
+        stream.read_to_string(&mut response).unwrap();
+        response
+    }
+
+    #[test]
+    fn test_http_mock_fixture_answers_and_verifies() {
+        let mut suite = TestSuite::new("client")
+            .with_fixture(HttpMock::definition("http"))
+            .add_test(Test::new("creates_user", |ctx| {
+                let server = HttpMock::from_context(&ctx)?;
+                server.expect(
+                    HttpExpectation::new("post", "/users")
+                        .with_query("notify", "true")
+                        .with_body(eq(json!({"name": "ada"})))
+                        .responds_with(HttpResponse::new(201).with_json(&json!({"id": 1}))?)
+                        .times(1),
+                );
+
+                let body = r#"{"name": "ada"}"#;
+                let response = send(
+                    &server,
+                    &format!(
+                        "POST /users?notify=true HTTP/1.1\r\ncontent-length: {}\r\n\r\n{}",
+                        body.len(),
+                        body
+                    ),
+                );
+                assert!(response.starts_with("HTTP/1.1 201 Created\r\n"));
+                assert!(response.ends_with("\r\n\r\n{\"id\":1}"));
+
+                let response = send(&server, "GET /missing HTTP/1.1\r\n\r\n");
+                assert!(response.starts_with("HTTP/1.1 404 Not Found\r\n"));
+                server.reset();
+                Ok(())
+            }))
+            .add_test(Test::new("never_calls", |ctx| {
+                let server = HttpMock::from_context(&ctx)?;
+                server.expect(HttpExpectation::new("GET", "/health").times(1));
+                Ok(())
+            }));
+
+        let result = suite.execute().unwrap();
+        assert!(
+            result.test_results[0].passed(),
+            "{:?}",
+            result.test_results[0]
+        );
+        let error = result.test_results[1].error.as_ref().unwrap();
+        assert_eq!(
+            error.message(),
+            "HTTP mock verification failed: expected 1 request(s) for GET /health, got 0"
+        );
+        assert!(running().lock().is_empty());
+    }
+}
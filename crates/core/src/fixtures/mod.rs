@@ -1,8 +1,10 @@
 pub mod dependency;
+pub mod http;
 pub mod lifecycle;
 pub mod scope;
 
 pub use dependency::*;
+pub use http::{HttpExpectation, HttpMock, HttpRequest, HttpResponse};
 pub use lifecycle::*;
 pub use scope::*;
 
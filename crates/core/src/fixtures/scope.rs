@@ -144,6 +144,11 @@ impl FixtureDefinition {
         self
     }
 
+    pub fn with_scope(mut self, scope: FixtureScope) -> Self {
+        self.scope = scope;
+        self
+    }
+
     pub fn with_dependencies(mut self, deps: Vec<String>) -> Self {
         self.dependencies = deps;
         self
@@ -0,0 +1,313 @@
+//! Golden files: output compared against a file checked in with the tests.
+//!
+//! Unlike [snapshots](crate::snapshot), golden files keep the output's own
+//! format and extension, so they can be JSON, HTML, images or anything else
+//! a tool might open. They live in a `golden` directory next to the
+//! package's `Cargo.toml`, and paths given to [`compare`] are relative to
+//! it:
+//!
+//! ```ignore
+//! golden::compare("fixtures/output.json", &rendered)?;
+//! ```
+//!
+//! Output that doesn't match is written beside the golden file with an
+//! `.actual` extension added, for `sheila golden list` to show and `sheila
+//! golden update` to promote. Setting `SHEILA_UPDATE_GOLDEN=1` updates
+//! golden files as the tests run. Output that is valid UTF-8 is compared as
+//! text, ignoring line endings and trailing newlines, with a diff on
+//! mismatch; anything else is compared byte for byte.
+
+use std::fs;
+use std::path::{Path, PathBuf};
+
+use serde::Serialize;
+
+use crate::Result;
+use crate::assert::{AssertionResult, create_diff};
+
+/// Directory golden files are kept in, relative to the package root
+pub const GOLDEN_DIR: &str = "golden";
+
+/// Extension added to a golden file's name for output that didn't match it
+pub const ACTUAL_EXTENSION: &str = ".actual";
+
+/// Set to `1` to write output to golden files instead of failing
+const UPDATE_ENV: &str = "SHEILA_UPDATE_GOLDEN";
+
+/// What happened when output was checked against its golden file
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum GoldenOutcome {
+    Matched,
+    /// The output was written to the golden file
+    Updated,
+    /// The output was saved beside the golden file. Holds the golden
+    /// file's contents, if it exists.
+    Mismatched(Option<Vec<u8>>),
+}
+
+/// The `golden` directory of the package under test: next to the
+/// `Cargo.toml` cargo is running the tests of, or in the current directory
+pub fn golden_dir() -> PathBuf {
+    std::env::var_os("CARGO_MANIFEST_DIR")
+        .map(PathBuf::from)
+        .unwrap_or_default()
+        .join(GOLDEN_DIR)
+}
+
+/// Where golden file `path` is: under [`golden_dir`], unless it's absolute
+pub fn resolve<P: AsRef<Path>>(path: P) -> PathBuf {
+    golden_dir().join(path)
+}
+
+/// Compare `actual` against golden file `path`, relative to
+/// [`golden_dir`]. Output that differs is saved for `sheila golden update`.
+#[track_caller]
+pub fn compare<P: AsRef<Path>, A: AsRef<[u8]>>(path: P, actual: A) -> Result<()> {
+    let path = resolve(path);
+    let actual = actual.as_ref();
+
+    match check_golden(&path, actual)? {
+        GoldenOutcome::Matched | GoldenOutcome::Updated => {
+            AssertionResult::pass(format!("Output matches golden file {}", path.display()))
+                .into_result()
+        }
+        GoldenOutcome::Mismatched(None) => AssertionResult::fail(format!(
+            "No golden file at {} yet. Create it with `sheila golden update`",
+            path.display()
+        ))
+        .into_result(),
+        GoldenOutcome::Mismatched(Some(expected)) => {
+            let mut result = AssertionResult::fail(format!(
+                "Output doesn't match golden file {}. Update it with `sheila golden update`",
+                path.display()
+            ));
+            result.diff = Some(match (text(&expected), text(actual)) {
+                (Some(expected), Some(actual)) => {
+                    create_diff(&format!("{}\n", expected), &format!("{}\n", actual))
+                }
+                _ => describe_binary_difference(&expected, actual),
+            });
+            result.into_result()
+        }
+    }
+}
+
+/// Compare `value`, as pretty-printed JSON, against golden file `path`
+#[track_caller]
+pub fn compare_json<P: AsRef<Path>, T: Serialize>(path: P, value: &T) -> Result<()> {
+    compare(path, serde_json::to_string_pretty(value)?)
+}
+
+/// Compare `actual` against the golden file at `path`, saving it beside the
+/// file when it differs
+pub fn check_golden(path: &Path, actual: &[u8]) -> Result<GoldenOutcome> {
+    let actual_path = actual_path(path);
+    let expected = fs::read(path).ok();
+    if expected
+        .as_deref()
+        .is_some_and(|expected| same(expected, actual))
+    {
+        if actual_path.exists() {
+            fs::remove_file(&actual_path)?;
+        }
+        return Ok(GoldenOutcome::Matched);
+    }
+
+    if let Some(parent) = path.parent() {
+        fs::create_dir_all(parent)?;
+    }
+    if std::env::var(UPDATE_ENV).is_ok_and(|update| update == "1") {
+        fs::write(path, actual)?;
+        if actual_path.exists() {
+            fs::remove_file(&actual_path)?;
+        }
+        return Ok(GoldenOutcome::Updated);
+    }
+
+    fs::write(&actual_path, actual)?;
+    Ok(GoldenOutcome::Mismatched(expected))
+}
+
+fn actual_path(path: &Path) -> PathBuf {
+    let mut name = path.file_name().unwrap_or_default().to_os_string();
+    name.push(ACTUAL_EXTENSION);
+    path.with_file_name(name)
+}
+
+/// `bytes` as text with `\n` line endings and no trailing newlines, if
+/// they're UTF-8
+fn text(bytes: &[u8]) -> Option<String> {
+    let text = std::str::from_utf8(bytes).ok()?;
+    Some(
+        text.replace("\r\n", "\n")
+            .trim_end_matches('\n')
+            .to_string(),
+    )
+}
+
+fn same(expected: &[u8], actual: &[u8]) -> bool {
+    match (text(expected), text(actual)) {
+        (Some(expected), Some(actual)) => expected == actual,
+        _ => expected == actual,
+    }
+}
+
+fn describe_binary_difference(expected: &[u8], actual: &[u8]) -> String {
+    let offset = expected
+        .iter()
+        .zip(actual)
+        .position(|(a, b)| a != b)
+        .unwrap_or(expected.len().min(actual.len()));
+    format!(
+        "Binary output differs from byte {}: expected {} bytes, got {}\n",
+        offset,
+        expected.len(),
+        actual.len()
+    )
+}
+
+/// A golden file, and the output that last failed to match it if there is
+/// some
+#[derive(Debug, Clone)]
+pub struct GoldenFile {
+    /// Path relative to the `golden` directory it's in
+    pub name: String,
+    pub path: PathBuf,
+    pub exists: bool,
+    /// Output saved because it didn't match
+    pub actual: Option<PathBuf>,
+}
+
+impl GoldenFile {
+    pub fn is_mismatched(&self) -> bool {
+        self.actual.is_some()
+    }
+
+    /// Replace the golden file with the output that didn't match it
+    pub fn update(&self) -> Result<()> {
+        if let Some(ref actual) = self.actual {
+            fs::rename(actual, &self.path)?;
+        }
+        Ok(())
+    }
+
+    /// Throw away the output that didn't match, keeping the golden file
+    pub fn discard(&self) -> Result<()> {
+        if let Some(ref actual) = self.actual {
+            fs::remove_file(actual)?;
+        }
+        Ok(())
+    }
+}
+
+/// Golden files in `golden` directories under `root`, ordered by path.
+/// Build output and hidden directories are skipped.
+pub fn golden_files(root: &Path) -> Result<Vec<GoldenFile>> {
+    let mut files = Vec::new();
+    collect_golden(root, None, &mut files)?;
+    files.sort_by(|a, b| a.path.cmp(&b.path));
+    Ok(files)
+}
+
+/// Walk `dir`, where `golden_root` is the `golden` directory it's in, if
+/// it's in one
+fn collect_golden(
+    dir: &Path,
+    golden_root: Option<&Path>,
+    files: &mut Vec<GoldenFile>,
+) -> Result<()> {
+    for entry in fs::read_dir(dir)? {
+        let entry = entry?;
+        let path = entry.path();
+        let file_name = entry.file_name();
+        let file_name = file_name.to_string_lossy();
+
+        if entry.file_type()?.is_dir() {
+            if golden_root.is_some() {
+                collect_golden(&path, golden_root, files)?;
+            } else if file_name == GOLDEN_DIR {
+                collect_golden(&path, Some(&path), files)?;
+            } else if file_name != "target" && !file_name.starts_with('.') {
+                collect_golden(&path, None, files)?;
+            }
+            continue;
+        }
+
+        let Some(golden_root) = golden_root else {
+            continue;
+        };
+        let golden = match path.to_string_lossy().strip_suffix(ACTUAL_EXTENSION) {
+            Some(golden) => PathBuf::from(golden),
+            None => path.clone(),
+        };
+        // A golden file with output beside it is listed once, for itself
+        if golden != path && golden.exists() {
+            continue;
+        }
+
+        let actual = actual_path(&golden);
+        files.push(GoldenFile {
+            name: golden
+                .strip_prefix(golden_root)
+                .unwrap_or(&golden)
+                .to_string_lossy()
+                .into_owned(),
+            exists: golden.exists(),
+            actual: actual.exists().then_some(actual),
+            path: golden,
+        });
+    }
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_golden_files_are_compared_listed_and_updated() {
+        let root = std::env::temp_dir().join(format!("sheila-golden-{}", uuid::Uuid::new_v4()));
+        let dir = root.join(GOLDEN_DIR);
+        let json = dir.join("fixtures/output.json");
+        let image = dir.join("logo.png");
+
+        assert_eq!(
+            check_golden(&json, b"{\"a\": 1}").unwrap(),
+            GoldenOutcome::Mismatched(None)
+        );
+        fs::write(&image, [0x89, b'P', b'N', b'G', 0xff]).unwrap();
+        assert_eq!(
+            check_golden(&image, &[0x89, b'P', b'N', b'G', 0xff]).unwrap(),
+            GoldenOutcome::Matched
+        );
+        assert!(matches!(
+            check_golden(&image, &[0x89, b'P', b'N', b'G', 0x00]).unwrap(),
+            GoldenOutcome::Mismatched(Some(_))
+        ));
+
+        let files = golden_files(&root).unwrap();
+        let names: Vec<_> = files.iter().map(|file| file.name.as_str()).collect();
+        assert_eq!(names, ["fixtures/output.json", "logo.png"]);
+        assert!(!files[0].exists && files[0].is_mismatched());
+        files[0].update().unwrap();
+        files[1].discard().unwrap();
+
+        assert_eq!(
+            check_golden(&json, b"{\"a\": 1}\r\n").unwrap(),
+            GoldenOutcome::Matched
+        );
+        assert!(
+            !golden_files(&root)
+                .unwrap()
+                .iter()
+                .any(GoldenFile::is_mismatched)
+        );
+        assert_eq!(
+            describe_binary_difference(&[1, 2, 3], &[1, 2]),
+            "Binary output differs from byte 2: expected 3 bytes, got 2\n"
+        );
+
+        fs::remove_dir_all(root).unwrap();
+    }
+}
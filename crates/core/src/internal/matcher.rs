@@ -0,0 +1,188 @@
+use serde_json::Value;
+use std::fmt;
+
+/// Checks one argument a mock is called with, e.g. `eq(5)`, `any()` or
+/// `json_path("$.user.id", eq(3))`.
+///
+/// Arguments are matched as the JSON they were recorded as, so `eq(5)`
+/// matches any integer argument equal to 5.
+#[derive(Debug, Clone, PartialEq)]
+pub enum ArgMatcher {
+    Any,
+    Eq(Value),
+    Not(Box<ArgMatcher>),
+    /// A string argument matching the pattern
+    #[cfg(feature = "regex")]
+    Regex(String),
+    /// The value at a path into the argument, e.g. `$.items[0].name`,
+    /// matching the inner matcher
+    JsonPath(String, Box<ArgMatcher>),
+}
+
+impl ArgMatcher {
+    pub fn any() -> Self {
+        ArgMatcher::Any
+    }
+
+    pub fn eq<V: Into<Value>>(value: V) -> Self {
+        ArgMatcher::Eq(value.into())
+    }
+
+    #[cfg(feature = "regex")]
+    pub fn regex<S: Into<String>>(pattern: S) -> Self {
+        ArgMatcher::Regex(pattern.into())
+    }
+
+    pub fn json_path<S: Into<String>>(path: S, matcher: ArgMatcher) -> Self {
+        ArgMatcher::JsonPath(path.into(), Box::new(matcher))
+    }
+
+    /// Check `value`, describing how it doesn't match if it doesn't
+    pub fn check(&self, value: &Value) -> Result<(), String> {
+        match self {
+            ArgMatcher::Any => Ok(()),
+            ArgMatcher::Eq(expected) if expected == value => Ok(()),
+            ArgMatcher::Not(matcher) if matcher.check(value).is_err() => Ok(()),
+            #[cfg(feature = "regex")]
+            ArgMatcher::Regex(pattern) => {
+                let regex = regex::Regex::new(pattern)
+                    .map_err(|e| format!("invalid regex pattern '{}': {}", pattern, e))?;
+                match value {
+                    Value::String(string) if regex.is_match(string) => Ok(()),
+                    _ => Err(format!("expected {}, got {}", self, value)),
+                }
+            }
+            ArgMatcher::JsonPath(path, matcher) => {
+                let found = lookup(value, path)
+                    .map_err(|e| format!("invalid JSON path '{}': {}", path, e))?
+                    .ok_or_else(|| format!("nothing at {} in {}", path, value))?;
+                matcher
+                    .check(found)
+                    .map_err(|mismatch| format!("at {}: {}", path, mismatch))
+            }
+            _ => Err(format!("expected {}, got {}", self, value)),
+        }
+    }
+
+    pub fn matches(&self, value: &Value) -> bool {
+        self.check(value).is_ok()
+    }
+}
+
+impl fmt::Display for ArgMatcher {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            ArgMatcher::Any => write!(f, "any value"),
+            ArgMatcher::Eq(value) => write!(f, "{}", value),
+            ArgMatcher::Not(matcher) => write!(f, "not {}", matcher),
+            #[cfg(feature = "regex")]
+            ArgMatcher::Regex(pattern) => write!(f, "a string matching '{}'", pattern),
+            ArgMatcher::JsonPath(path, matcher) => write!(f, "{} at {}", matcher, path),
+        }
+    }
+}
+
+pub fn any() -> ArgMatcher {
+    ArgMatcher::any()
+}
+
+pub fn eq<V: Into<Value>>(value: V) -> ArgMatcher {
+    ArgMatcher::eq(value)
+}
+
+/// Any value `matcher` doesn't match
+pub fn not(matcher: ArgMatcher) -> ArgMatcher {
+    ArgMatcher::Not(Box::new(matcher))
+}
+
+#[cfg(feature = "regex")]
+pub fn regex<S: Into<String>>(pattern: S) -> ArgMatcher {
+    ArgMatcher::regex(pattern)
+}
+
+pub fn json_path<S: Into<String>>(path: S, matcher: ArgMatcher) -> ArgMatcher {
+    ArgMatcher::json_path(path, matcher)
+}
+
+/// The value at `path` in `value`, for paths made of `$` followed by
+/// `.field`, `[index]` and `['field']` steps
+fn lookup<'a>(value: &'a Value, path: &str) -> Result<Option<&'a Value>, String> {
+    let mut rest = path.strip_prefix('$').ok_or("paths start with '$'")?;
+    let mut current = value;
+
+    while !rest.is_empty() {
+        let next = if let Some(after) = rest.strip_prefix('.') {
+            let end = after.find(['.', '[']).unwrap_or(after.len());
+            if end == 0 {
+                return Err("empty field name".to_string());
+            }
+            rest = &after[end..];
+            current.get(&after[..end])
+        } else if let Some(after) = rest.strip_prefix('[') {
+            let end = after.find(']').ok_or("unclosed '['")?;
+            let step = &after[..end];
+            rest = &after[end + 1..];
+            match step.strip_prefix('\'').and_then(|s| s.strip_suffix('\'')) {
+                Some(field) => current.get(field),
+                None => {
+                    let index: usize = step
+                        .parse()
+                        .map_err(|_| format!("'{}' isn't an index", step))?;
+                    current.get(index)
+                }
+            }
+        } else {
+            return Err(format!("unexpected '{}'", rest));
+        };
+
+        match next {
+            Some(next) => current = next,
+            None => return Ok(None),
+        }
+    }
+
+    Ok(Some(current))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::internal::{MockBuilder, MockCollection};
+    use serde_json::json;
+
+    #[test]
+    fn test_mismatched_arguments_are_described() {
+        let mut mocks = MockCollection::new();
+        mocks.register_mock(
+            "create_user",
+            MockBuilder::new()
+                .with_args([
+                    eq(5),
+                    json_path("$.user['id']", eq(3)),
+                    json_path("$.tags[1]", not(eq("admin"))),
+                ])
+                .build(),
+        );
+
+        let user = json!({"user": {"id": 3}, "tags": ["a", "b"]});
+        assert!(
+            mocks
+                .record_call("create_user", vec![json!(5), user.clone(), user])
+                .is_ok()
+        );
+
+        let error = mocks
+            .record_call(
+                "create_user",
+                vec![json!(4), json!({"user": {"id": 7}}), json!({"tags": []})],
+            )
+            .unwrap_err();
+        assert_eq!(
+            error.to_string(),
+            "Mock error: Call to 'create_user' didn't match its arguments: \
+             argument 0: expected 5, got 4; \
+             argument 1: at $.user['id']: expected 3, got 7; \
+             argument 2: nothing at $.tags[1] in {\"tags\":[]}"
+        );
+    }
+}
@@ -1,7 +1,11 @@
+use super::ArgMatcher;
 use crate::{Error, Result};
 use serde::{Deserialize, Serialize};
+use std::any::{Any, type_name};
 use std::collections::HashMap;
 use std::sync::{Arc, Mutex};
+use std::thread;
+use std::time::Duration;
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct MockCall {
@@ -10,12 +14,80 @@ pub struct MockCall {
     pub ts: chrono::DateTime<chrono::Utc>,
 }
 
+/// Produces what a mocked function returns, from the arguments it was
+/// called with
+type Responder = Arc<dyn Fn(&[serde_json::Value]) -> Box<dyn Any + Send> + Send + Sync>;
+
+/// A typed value or error a mocked function returns for a call, set with
+/// [`MockBuilder::returns`], [`MockBuilder::returns_error`] or
+/// [`MockBuilder::returns_with`]
+#[derive(Clone)]
+pub struct MockResponse {
+    respond: Responder,
+    is_error: bool,
+    type_name: &'static str,
+}
+
+impl MockResponse {
+    /// Returns a clone of `value` for every call it answers
+    pub fn value<T: Clone + Send + Sync + 'static>(value: T) -> Self {
+        Self {
+            respond: Arc::new(move |_| Box::new(value.clone())),
+            is_error: false,
+            type_name: type_name::<T>(),
+        }
+    }
+
+    /// Returns `Err` with a clone of `error` from functions returning a
+    /// `Result` with an error type of `E`
+    pub fn error<E: Clone + Send + Sync + 'static>(error: E) -> Self {
+        Self {
+            is_error: true,
+            ..Self::value(error)
+        }
+    }
+
+    /// Returns whatever `respond` makes of the call's arguments
+    pub fn with<R, F>(respond: F) -> Self
+    where
+        R: Send + 'static,
+        F: Fn(&[serde_json::Value]) -> R + Send + Sync + 'static,
+    {
+        Self {
+            respond: Arc::new(move |arguments| Box::new(respond(arguments))),
+            is_error: false,
+            type_name: type_name::<R>(),
+        }
+    }
+}
+
+impl std::fmt::Debug for MockResponse {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("MockResponse")
+            .field("is_error", &self.is_error)
+            .field("type_name", &self.type_name)
+            .finish_non_exhaustive()
+    }
+}
+
 #[derive(Clone)]
 pub struct MockConfig {
     pub expected_calls: Option<usize>,
+    /// Values returned as JSON by [`MockCollection::record_call`]
     pub return_values: Vec<serde_json::Value>,
+    /// Values and errors returned by [`MockCollection::call`] and
+    /// [`MockCollection::call_result`], one call after another with the
+    /// last repeated
+    pub responses: Vec<MockResponse>,
     pub panic_on_unexpected: bool,
     pub validator: Option<Arc<dyn Fn(&[serde_json::Value]) -> Result<()> + Send + Sync>>,
+    /// What the argument at each position must match, with `None` for
+    /// positions that may be anything
+    pub arg_matchers: Vec<Option<ArgMatcher>>,
+    /// How long every call takes, to simulate a slow dependency
+    pub delay: Option<Duration>,
+    /// Fail every nth call with the error given, in place of its response
+    pub fault: Option<(usize, MockResponse)>,
 }
 
 impl Default for MockConfig {
@@ -23,8 +95,12 @@ impl Default for MockConfig {
         Self {
             expected_calls: None,
             return_values: Vec::new(),
+            responses: Vec::new(),
             panic_on_unexpected: false,
             validator: None,
+            arg_matchers: Vec::new(),
+            delay: None,
+            fault: None,
         }
     }
 }
@@ -34,8 +110,12 @@ impl std::fmt::Debug for MockConfig {
         f.debug_struct("MockConfig")
             .field("expected_calls", &self.expected_calls)
             .field("return_values", &self.return_values)
+            .field("responses", &self.responses)
             .field("panic_on_unexpected", &self.panic_on_unexpected)
             .field("validator", &self.validator.as_ref().map(|_| "<function>"))
+            .field("arg_matchers", &self.arg_matchers)
+            .field("delay", &self.delay)
+            .field("fault", &self.fault)
             .finish()
     }
 }
@@ -56,12 +136,31 @@ impl MockCollection {
         self.configs.insert(function_name.into(), config);
     }
 
+    /// Record a call to a mocked function, returning its JSON return value.
+    /// Calls [`MockBuilder::fails_every_nth`] fails return a mock error.
     pub fn record_call<S: Into<String>>(
         &self,
         function_name: S,
         arguments: Vec<serde_json::Value>,
     ) -> Result<serde_json::Value> {
         let function_name = function_name.into();
+        let returned = self.record(&function_name, arguments)?;
+        if self.fault(&function_name).is_some() {
+            return Err(Error::mock(format!(
+                "Injected failure on call {} to '{}'",
+                self.get_call_count(&function_name),
+                function_name
+            )));
+        }
+        Ok(returned)
+    }
+
+    fn record(
+        &self,
+        function_name: &str,
+        arguments: Vec<serde_json::Value>,
+    ) -> Result<serde_json::Value> {
+        let function_name = function_name.to_string();
 
         let call = MockCall {
             fn_name: function_name.clone(),
@@ -82,10 +181,36 @@ impl MockCollection {
         let config = self.configs.get(&function_name);
 
         if let Some(config) = config {
+            if let Some(delay) = config.delay {
+                thread::sleep(delay);
+                crate::test::add_injected_delay(delay);
+            }
+
             if let Some(ref validator) = config.validator {
                 validator(&arguments)?;
             }
 
+            let mismatches: Vec<String> = config
+                .arg_matchers
+                .iter()
+                .enumerate()
+                .filter_map(|(position, matcher)| {
+                    let matcher = matcher.as_ref()?;
+                    let mismatch = match arguments.get(position) {
+                        Some(argument) => matcher.check(argument).err()?,
+                        None => format!("expected {}, got nothing", matcher),
+                    };
+                    Some(format!("argument {}: {}", position, mismatch))
+                })
+                .collect();
+            if !mismatches.is_empty() {
+                return Err(Error::mock(format!(
+                    "Call to '{}' didn't match its arguments: {}",
+                    function_name,
+                    mismatches.join("; ")
+                )));
+            }
+
             if let Some(expected) = config.expected_calls {
                 let current_count = self.get_call_count(&function_name);
                 if current_count > expected {
@@ -116,6 +241,96 @@ impl MockCollection {
         }
     }
 
+    /// Record a call to a mocked function and return its configured
+    /// response as an `R`, as the mocks `#[sheila::automock]` generates do.
+    /// Without responses the function returns `()`, or the JSON return
+    /// value if `R` is `serde_json::Value`.
+    ///
+    /// Functions returning a plain `R` can't hand back the mock's errors,
+    /// so those, and responses of the wrong type, are raised as panics,
+    /// which fail the calling test.
+    pub fn call<R: 'static>(&self, function_name: &str, arguments: Vec<serde_json::Value>) -> R {
+        let returned = self
+            .record(function_name, arguments.clone())
+            .unwrap_or_else(|e| panic!("{}", e));
+
+        let Some(response) = self.response(function_name) else {
+            return downcast(Box::new(returned))
+                .or_else(|_| downcast(Box::new(())))
+                .unwrap_or_else(|_| {
+                    panic!(
+                        "Mock '{}' has no {} to return",
+                        function_name,
+                        type_name::<R>()
+                    )
+                });
+        };
+        if response.is_error {
+            panic!(
+                "Mock '{}' is set to return an error, but returns {}",
+                function_name,
+                type_name::<R>()
+            );
+        }
+        downcast((response.respond)(&arguments))
+            .unwrap_or_else(|_| wrong_type::<R>(function_name, &response))
+    }
+
+    /// Record a call to a mocked function returning `Result<T, E>`, like
+    /// [`MockCollection::call`]. Responses may be a `T`, which is returned
+    /// as `Ok`, or the whole `Result`, and errors set with
+    /// [`MockBuilder::returns_error`] are returned as `Err`. If `E` is
+    /// [`Error`], so are the mock's own errors, e.g. for unexpected calls.
+    pub fn call_result<T: 'static, E: 'static>(
+        &self,
+        function_name: &str,
+        arguments: Vec<serde_json::Value>,
+    ) -> std::result::Result<T, E> {
+        if let Err(e) = self.record(function_name, arguments.clone()) {
+            let message = e.to_string();
+            return Err(downcast(Box::new(e)).unwrap_or_else(|_| panic!("{}", message)));
+        }
+
+        let Some(response) = self.response(function_name) else {
+            return downcast(Box::new(())).map(Ok).unwrap_or_else(|_| {
+                panic!(
+                    "Mock '{}' has no {} to return",
+                    function_name,
+                    type_name::<T>()
+                )
+            });
+        };
+        let returned = (response.respond)(&arguments);
+        if response.is_error {
+            return Err(
+                downcast(returned).unwrap_or_else(|_| wrong_type::<E>(function_name, &response))
+            );
+        }
+        downcast::<T>(returned)
+            .map(Ok)
+            .or_else(downcast::<std::result::Result<T, E>>)
+            .unwrap_or_else(|_| wrong_type::<T>(function_name, &response))
+    }
+
+    /// The response to the latest call to `function_name`
+    fn response(&self, function_name: &str) -> Option<MockResponse> {
+        if let Some(fault) = self.fault(function_name) {
+            return Some(fault);
+        }
+        let responses = &self.configs.get(function_name)?.responses;
+        let call_index = self.get_call_count(function_name).saturating_sub(1);
+        responses.get(call_index).or(responses.last()).cloned()
+    }
+
+    /// The error injected into the latest call to `function_name`, if
+    /// it's one of the calls set to fail
+    fn fault(&self, function_name: &str) -> Option<MockResponse> {
+        let (every, error) = self.configs.get(function_name)?.fault.as_ref()?;
+        self.get_call_count(function_name)
+            .is_multiple_of(*every)
+            .then(|| error.clone())
+    }
+
     pub fn get_call_count(&self, fn_name: &str) -> usize {
         self.call_counts
             .lock()
@@ -160,6 +375,25 @@ impl MockCollection {
     }
 }
 
+/// An argument to a mocked function as it's recorded. Arguments that can't
+/// be represented as JSON are recorded as `null`.
+pub fn mock_arg<T: Serialize + ?Sized>(argument: &T) -> serde_json::Value {
+    serde_json::to_value(argument).unwrap_or(serde_json::Value::Null)
+}
+
+fn downcast<T: 'static>(value: Box<dyn Any + Send>) -> std::result::Result<T, Box<dyn Any + Send>> {
+    value.downcast::<T>().map(|value| *value)
+}
+
+fn wrong_type<T>(function_name: &str, response: &MockResponse) -> ! {
+    panic!(
+        "Mock '{}' is set to return {}, not {}",
+        function_name,
+        response.type_name,
+        type_name::<T>()
+    )
+}
+
 pub struct MockBuilder {
     config: MockConfig,
 }
@@ -181,21 +415,62 @@ impl MockBuilder {
 
     /// Define a return value for a given mock
     ///
-    /// If no return values are defined, the mock will return `null`.
-    pub fn returns<T: Serialize>(mut self, value: T) -> Result<Self> {
-        let json_value = serde_json::to_value(value)?;
-        self.config.return_values.push(json_value);
-        Ok(self)
+    /// Each call returns the next value defined, and once they run out
+    /// the last one again. The value is returned as is, so it needn't be
+    /// serializable.
+    pub fn returns<T: Clone + Send + Sync + 'static>(mut self, value: T) -> Self {
+        self.config.responses.push(MockResponse::value(value));
+        self
+    }
+
+    /// Define multiple return values for a given mock, one per call
+    pub fn returns_sequence<T: Clone + Send + Sync + 'static>(mut self, values: Vec<T>) -> Self {
+        self.config
+            .responses
+            .extend(values.into_iter().map(MockResponse::value));
+        self
+    }
+
+    /// Define an error for a mock returning a `Result` to return, as the
+    /// next of its return values
+    pub fn returns_error<E: Clone + Send + Sync + 'static>(mut self, error: E) -> Self {
+        self.config.responses.push(MockResponse::error(error));
+        self
+    }
+
+    /// Define a function computing the mock's return value from the
+    /// arguments it's called with, as the next of its return values
+    pub fn returns_with<R, F>(mut self, respond: F) -> Self
+    where
+        R: Send + 'static,
+        F: Fn(&[serde_json::Value]) -> R + Send + Sync + 'static,
+    {
+        self.config.responses.push(MockResponse::with(respond));
+        self
+    }
+
+    /// Make every call to the mock take `delay` longer, to simulate a slow
+    /// dependency. The time is reported on the test's result apart from its
+    /// duration.
+    pub fn with_delay(mut self, delay: Duration) -> Self {
+        self.config.delay = Some(delay);
+        self
+    }
+
+    /// Make every `n`th call to the mock fail with `error`, in place of the
+    /// value it would return, to simulate an unreliable dependency
+    pub fn fails_every_nth<E: Clone + Send + Sync + 'static>(mut self, n: usize, error: E) -> Self {
+        self.config.fault = Some((n.max(1), MockResponse::error(error)));
+        self
     }
 
-    /// Define multiple return values for a given mock
+    /// Define a return value for a mock called through
+    /// [`MockCollection::record_call`], which returns it as JSON
     ///
-    /// If no return values are defined, the mock will return `null`.
-    pub fn returns_sequence<T: Serialize>(mut self, values: Vec<T>) -> Result<Self> {
-        for value in values {
-            let json_value = serde_json::to_value(value)?;
-            self.config.return_values.push(json_value);
-        }
+    /// If no JSON return values are defined, the mock will return `null`.
+    pub fn returns_json<T: Serialize>(mut self, value: T) -> Result<Self> {
+        let json_value = serde_json::to_value(value)?;
+        self.config.return_values.push(json_value);
         Ok(self)
     }
 
@@ -222,6 +497,21 @@ impl MockBuilder {
         self
     }
 
+    /// Require the argument at `position` to match `matcher`
+    pub fn with_arg(mut self, position: usize, matcher: ArgMatcher) -> Self {
+        if self.config.arg_matchers.len() <= position {
+            self.config.arg_matchers.resize(position + 1, None);
+        }
+        self.config.arg_matchers[position] = Some(matcher);
+        self
+    }
+
+    /// Require each argument to match the matcher at its position
+    pub fn with_args<I: IntoIterator<Item = ArgMatcher>>(mut self, matchers: I) -> Self {
+        self.config.arg_matchers = matchers.into_iter().map(Some).collect();
+        self
+    }
+
     pub fn build(self) -> MockConfig {
         self.config
     }
@@ -279,3 +569,64 @@ pub mod global {
         registry.clear();
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[derive(Debug, Clone, PartialEq)]
+    struct Connection(u32);
+
+    #[test]
+    fn test_typed_responses_and_errors() {
+        let mut mocks = MockCollection::new();
+        mocks.register_mock(
+            "connect",
+            MockBuilder::new()
+                .returns(Connection(1))
+                .returns_error("refused".to_string())
+                .returns_with(|args: &[serde_json::Value]| {
+                    Connection(args[0].as_u64().unwrap() as u32)
+                })
+                .expect_calls(3)
+                .build(),
+        );
+        let connect =
+            |port: u32| mocks.call_result::<Connection, String>("connect", vec![mock_arg(&port)]);
+
+        assert_eq!(connect(80), Ok(Connection(1)));
+        assert_eq!(connect(80), Err("refused".to_string()));
+        assert_eq!(connect(443), Ok(Connection(443)));
+        assert!(mocks.verify().is_ok());
+
+        let unexpected = mocks.call_result::<Connection, Error>("connect", Vec::new());
+        assert!(matches!(unexpected, Err(Error::Mock { .. })));
+    }
+
+    #[test]
+    fn test_injected_faults_and_delays() {
+        let test = crate::Test::new("slow_and_flaky", |_| {
+            let mut mocks = MockCollection::new();
+            mocks.register_mock(
+                "fetch",
+                MockBuilder::new()
+                    .returns(7u32)
+                    .with_delay(Duration::from_millis(20))
+                    .fails_every_nth(2, "timed out")
+                    .build(),
+            );
+
+            let results: Vec<_> = (0..4)
+                .map(|_| mocks.call_result::<u32, &str>("fetch", Vec::new()))
+                .collect();
+            assert_eq!(results, [Ok(7), Err("timed out"), Ok(7), Err("timed out")]);
+            assert!(mocks.record_call("fetch", Vec::new()).is_ok());
+            assert!(mocks.record_call("fetch", Vec::new()).is_err());
+            Ok(())
+        });
+
+        let result = test.execute(crate::test::TestContext::new(test.id, test.meta.clone()));
+        assert!(result.passed(), "{:?}", result.error);
+        assert!(result.injected_delay >= Some(Duration::from_millis(120)));
+    }
+}
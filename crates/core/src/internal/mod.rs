@@ -1,7 +1,9 @@
 pub mod hook;
+pub mod matcher;
 pub mod mock;
 pub mod param;
 
 pub use hook::*;
+pub use matcher::ArgMatcher;
 pub use mock::*;
 pub use param::*;
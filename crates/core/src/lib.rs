@@ -1,28 +1,43 @@
 #![feature(mpmc_channel)]
 #![feature(duration_millis_float)]
+#![feature(internal_output_capture)]
 
 pub mod assert;
+pub mod bench;
+pub mod cache;
 pub mod fixtures;
+pub mod golden;
 pub mod internal;
 pub mod macros;
 pub mod misc;
+pub mod random;
 pub mod reporting;
 pub mod result;
 pub mod runners;
+pub mod schema;
 pub mod schemas;
+pub mod snapshot;
+pub mod state;
 pub mod suite;
 pub mod test;
+pub mod time;
 pub mod types;
 
 pub use assert::Assertion;
 pub use fixtures::{Fixture, FixtureScope};
-pub use internal::{Hook, HookType, Hooks, MockCollection, MockConfig, ParameterSet};
+pub use internal::{
+    ArgMatcher, Hook, HookType, Hooks, MockBuilder, MockCollection, MockConfig, ParameterSet,
+};
 pub use misc::*;
 pub use reporting::{ReportFormat, Reporter, TestReport};
 pub use result::{Error, ErrorKind, Result};
-pub use runners::{RunnerConfig, TestRunner};
+pub use runners::{CancelReason, CancellationToken, RunnerConfig, TestRunner};
+pub use state::SharedState;
 pub use suite::{SuiteAttributes, TestSuite};
-pub use test::{Test, TestAttributes, TestFn, TestMetadata, TestStatus};
+pub use test::{
+    Artifact, LogLevel, LogLine, Test, TestAttempt, TestAttributes, TestFn, TestMetadata,
+    TestStatus,
+};
 pub use types::*;
 
 #[cfg(feature = "macros")]
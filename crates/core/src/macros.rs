@@ -28,10 +28,7 @@ macro_rules! expect_calls {
 #[macro_export]
 macro_rules! returns {
     ($value:expr) => {
-        $crate::mock::MockBuilder::new()
-            .returns($value)
-            .unwrap()
-            .build()
+        $crate::mock::MockBuilder::new().returns($value).build()
     };
 }
 //endregion
@@ -190,11 +187,43 @@ macro_rules! assert_approx_eq {
         $crate::assertion::Assertion::approx_eq($actual, $expected, $epsilon)
     };
 }
+
+/// Compare a value's `Display` output against a snapshot in the calling
+/// package's `snapshots` directory
+#[macro_export]
+macro_rules! assert_snapshot {
+    ($name:expr, $value:expr) => {
+        $crate::Assertion::snapshot(
+            ::std::path::Path::new(env!("CARGO_MANIFEST_DIR")).join($crate::snapshot::SNAPSHOT_DIR),
+            $name,
+            $value,
+        )
+    };
+}
+
+/// Like `assert_snapshot!`, but snapshots the value's pretty `Debug` output
+#[macro_export]
+macro_rules! assert_debug_snapshot {
+    ($name:expr, $value:expr) => {
+        $crate::assert_snapshot!($name, format!("{:#?}", $value))
+    };
+}
 //endregion
 
 //region DEBUG
+/// Log a line to the test's result, e.g. `debug_log!(ctx, warn, "retrying {}", n)`.
+/// Without the context, e.g. `debug_log!(info, "connected")`, the line goes
+/// to the test running on the current thread.
 #[macro_export]
 macro_rules! debug_log {
+    ($level:ident, $fmt:literal $($arg:tt)*) => {
+        match stringify!($level) {
+            "info" => $crate::test::log($crate::test::LogLevel::Info, format!($fmt $($arg)*)),
+            "warn" => $crate::test::log($crate::test::LogLevel::Warn, format!($fmt $($arg)*)),
+            "error" => $crate::test::log($crate::test::LogLevel::Error, format!($fmt $($arg)*)),
+            _ => $crate::test::log($crate::test::LogLevel::Debug, format!($fmt $($arg)*)),
+        }
+    };
     ($ctx:expr, $level:ident, $($arg:tt)*) => {
         match stringify!($level) {
             "info" => $ctx.info(format!($($arg)*)),
@@ -206,8 +235,13 @@ macro_rules! debug_log {
     };
 }
 
+/// Leave a breadcrumb on the test's result, e.g. `breadcrumb!(ctx, "logged in as {}", user)`,
+/// or on the test running on the current thread with `breadcrumb!("logged in")`
 #[macro_export]
 macro_rules! breadcrumb {
+    ($fmt:literal $($arg:tt)*) => {
+        $crate::test::breadcrumb(format!($fmt $($arg)*))
+    };
     ($ctx:expr, $($arg:tt)*) => {
         $ctx.add_breadcrumb(format!($($arg)*))
     };
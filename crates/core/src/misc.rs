@@ -1,8 +1,8 @@
 use std::{
     collections::HashMap,
     io::{BufReader, Read},
-    path::PathBuf,
-    time::Instant,
+    path::{Path, PathBuf},
+    time::{Duration, Instant},
 };
 
 use nom::{
@@ -10,12 +10,13 @@ use nom::{
     branch::alt,
     bytes::complete::{tag, take_until, take_while1},
     character::complete::digit1,
-    combinator::map,
+    combinator::{map, opt},
+    sequence::delimited,
 };
 use uuid::Uuid;
 
 use crate::{
-    Error, ErrorInfo, ProcessOutput, Result, RunnerConfig, SourceLocation, TestMetadata,
+    Error, ErrorInfo, EventMeta, ProcessOutput, Result, RunnerConfig, SourceLocation, TestMetadata,
     TestOutputLine, TestState, TestStatus,
     runners::{RunResult, format_mod_name},
     suite::SuiteResult,
@@ -76,6 +77,13 @@ impl Default for TestTracker {
 pub struct TestRunState {
     tests: HashMap<String, TestState>,
     pending_errors: HashMap<String, ErrorInfo>,
+    /// Lines printed to stderr by each running test
+    stderr: HashMap<String, Vec<String>>,
+    /// Lines printed to stderr while no test was known to be running
+    unclaimed_stderr: Vec<String>,
+    /// Lines printed to stderr while several tests were running, which
+    /// can't be told apart
+    suite_stderr: Vec<String>,
     current_suite: Option<String>,
 }
 
@@ -84,13 +92,38 @@ impl TestRunState {
         Self {
             tests: HashMap::new(),
             pending_errors: HashMap::new(),
+            stderr: HashMap::new(),
+            unclaimed_stderr: Vec::new(),
+            suite_stderr: Vec::new(),
             current_suite: None,
         }
     }
 
+    /// State for the tests of `suite`, which the events produced name
+    pub fn for_suite(suite: impl Into<String>) -> Self {
+        Self {
+            current_suite: Some(suite.into()),
+            ..Self::new()
+        }
+    }
+
+    /// Name of the suite the tests belong to, if known
+    pub fn suite(&self) -> &str {
+        self.current_suite.as_deref().unwrap_or_default()
+    }
+
     pub fn handle_line(&mut self, line: TestOutputLine) -> Option<ProcessOutput> {
         match line {
+            TestOutputLine::SuiteStart { count } => Some(ProcessOutput::SuiteStarted {
+                name: self.suite().to_string(),
+                test_count: count,
+                meta: EventMeta::default(),
+            }),
             TestOutputLine::TestStart { name } => {
+                if !self.unclaimed_stderr.is_empty() {
+                    let lines = std::mem::take(&mut self.unclaimed_stderr);
+                    self.stderr.insert(name.clone(), lines);
+                }
                 self.tests.insert(
                     name.clone(),
                     TestState::Running {
@@ -99,39 +132,73 @@ impl TestRunState {
                 );
                 Some(ProcessOutput::TestStarted {
                     name,
-                    suite: self.current_suite.clone().unwrap_or_default(),
+                    suite: self.suite().to_string(),
+                    meta: EventMeta::default(),
                 })
             }
-            TestOutputLine::TestResult { name, status, .. } => {
-                if let Some(TestState::Running { started_at }) = self.tests.get(&name) {
-                    let duration_ms = started_at.elapsed().as_millis() as f64;
-                    let error = self.pending_errors.remove(&name);
-
-                    self.tests.insert(
-                        name.clone(),
-                        TestState::Completed {
-                            duration_ms,
-                            status: status.clone(),
-                        },
-                    );
+            TestOutputLine::TestResult {
+                name,
+                status,
+                duration_ms: reported_ms,
+                stdout,
+            } => {
+                // Without JSON output libtest never reports a test starting,
+                // so the result line is the first we hear of it
+                let measured_ms = match self.tests.get(&name) {
+                    Some(TestState::Running { started_at }) => {
+                        Some(started_at.elapsed().as_millis() as f64)
+                    }
+                    Some(TestState::Completed { .. }) => return None,
+                    _ => None,
+                };
+                let duration_ms = reported_ms.or(measured_ms).unwrap_or_default();
+
+                let mut error = self.pending_errors.remove(&name);
+                if error.is_none() && status == TestStatus::Failed {
+                    error = stdout.as_deref().and_then(panic_from_output);
+                }
+
+                self.tests.insert(
+                    name.clone(),
+                    TestState::Completed {
+                        duration_ms,
+                        status,
+                    },
+                );
 
-                    match status {
-                        TestStatus::Failed => Some(ProcessOutput::TestFailed {
-                            result: StandardLineParser::create_test_result(&name, status),
+                let mut result = StandardLineParser::create_test_result(&name, status);
+                result.duration = Some(Duration::from_secs_f64(duration_ms / 1000.0));
+                result.stdout = stdout;
+                result.stderr = self.take_stderr(&name);
+                let suite = self.suite().to_string();
+
+                match status {
+                    TestStatus::Failed => {
+                        if let Some(ref error) = error {
+                            result.error = Some(test_failure(&result.name, error));
+                            result.location = error.location.clone();
+                        }
+
+                        Some(ProcessOutput::TestFailed {
+                            result,
+                            suite,
                             duration_ms,
                             error: error.clone().map(|e| e.to_string()).unwrap_or_default(),
                             location: error.as_ref().and_then(|e| e.location.clone()),
-                        }),
-                        TestStatus::Passed => Some(ProcessOutput::TestPassed {
-                            result: StandardLineParser::create_test_result(&name, status),
-                            duration_ms,
-                        }),
-                        _ => Some(ProcessOutput::TestSkipped {
-                            result: StandardLineParser::create_test_result(&name, status),
-                        }),
+                            meta: EventMeta::default(),
+                        })
                     }
-                } else {
-                    None
+                    TestStatus::Passed => Some(ProcessOutput::TestPassed {
+                        result,
+                        suite,
+                        duration_ms,
+                        meta: EventMeta::default(),
+                    }),
+                    _ => Some(ProcessOutput::TestSkipped {
+                        result,
+                        suite,
+                        meta: EventMeta::default(),
+                    }),
                 }
             }
             TestOutputLine::Panic {
@@ -158,55 +225,218 @@ impl TestRunState {
 
                 None
             }
-            _ => None,
         }
     }
 
+    /// Keep a line printed to stderr for the result of the test printing it.
+    /// Stderr can be read before stdout reports the test starting, so lines
+    /// printed while no test is running go to the next test to start. Lines
+    /// printed while several are running can't be told apart, so they're
+    /// kept for the suite.
+    pub fn push_stderr(&mut self, line: String) {
+        let mut running = self
+            .tests
+            .iter()
+            .filter(|(_, state)| matches!(state, TestState::Running { .. }));
+        match (running.next(), running.next()) {
+            (Some((name, _)), None) => self.stderr.entry(name.clone()).or_default().push(line),
+            (None, _) => self.unclaimed_stderr.push(line),
+            _ => self.suite_stderr.push(line),
+        }
+    }
+
+    /// The stderr kept for `test`, if it printed anything
+    pub fn take_stderr(&mut self, test: &str) -> Option<String> {
+        let lines = self.stderr.remove(test)?;
+        joined_output(&lines)
+    }
+
+    /// The stderr printed since the last test started, which no test has
+    /// claimed, e.g. libtest's complaint about its arguments
+    pub fn take_unclaimed_stderr(&mut self) -> Option<String> {
+        joined_output(&std::mem::take(&mut self.unclaimed_stderr))
+    }
+
+    /// The stderr no test could be given: printed while several tests were
+    /// running, or after the last one finished
+    pub fn take_suite_stderr(&mut self) -> Option<String> {
+        let mut lines = std::mem::take(&mut self.suite_stderr);
+        lines.append(&mut self.unclaimed_stderr);
+        joined_output(&lines)
+    }
+
+    /// Remove and return the tests that started but never reported a result,
+    /// e.g. because their process was killed, along with how long they ran (ms).
+    pub fn take_running(&mut self) -> Vec<(String, f64)> {
+        let running: Vec<(String, f64)> = self
+            .tests
+            .iter()
+            .filter_map(|(name, state)| match state {
+                TestState::Running { started_at } => {
+                    Some((name.clone(), started_at.elapsed().as_millis() as f64))
+                }
+                _ => None,
+            })
+            .collect();
+
+        for (name, _) in running.iter() {
+            self.tests.remove(name);
+        }
+
+        running
+    }
+
     pub fn finalize_pending_errors(&mut self, test_results: &mut [TestResult]) {
         for result in test_results.iter_mut() {
-            if let Some(error_info) = self.pending_errors.remove(&result.name) {
-                if result.error.is_none() {
-                    result.error = Some(Error::test_execution(error_info.to_string()));
-                }
+            if let Some(error_info) = self.pending_errors.remove(result.qualified_name())
+                && (result.error.is_none() || result.status == TestStatus::Failed)
+            {
+                result.error = Some(test_failure(&result.name, &error_info));
+                result.location = error_info.location;
             }
         }
     }
 }
 
-#[derive(Debug, Clone)]
+fn joined_output(lines: &[String]) -> Option<String> {
+    let output = lines.join("\n");
+    let output = output.trim_matches('\n');
+    (!output.is_empty()).then(|| format!("{}\n", output))
+}
+
+/// The error a test failed with, as reported by libtest. The panic's
+/// location is kept apart from its message for reporters to show.
+fn test_failure(test: &str, info: &ErrorInfo) -> Error {
+    let message = match (&info.location, &info.message) {
+        (Some(_), Some(message)) => format!("Test panicked: {}", message),
+        _ => info.to_string(),
+    };
+    let mut error = Error::test_execution(message).with_test(test);
+    error.details_mut().location = info.location.clone();
+    error
+}
+
+/// Error details from the first panic report in a test's captured output
+fn panic_from_output(output: &str) -> Option<ErrorInfo> {
+    let mut panics = PanicGroupCollector::new();
+    let panic = output
+        .lines()
+        .find_map(|line| panics.push_line(line.to_string()))
+        .or_else(|| panics.finish())?;
+
+    match StandardLineParser::parse_error_output(&panic) {
+        Ok(TestOutputLine::Panic {
+            message, location, ..
+        }) => {
+            let mut error = ErrorInfo::new();
+            if let Some(location) = location {
+                error.set_location(location.file, location.line, location.column);
+            }
+            error.set_message(message);
+            Some(error)
+        }
+        _ => None,
+    }
+}
+
+/// FNV-1a hash of `bytes`. Unlike `DefaultHasher`, the result is stable
+/// across processes, machines and Rust versions.
+pub fn stable_hash(bytes: &[u8]) -> u64 {
+    const OFFSET_BASIS: u64 = 0xcbf29ce484222325;
+    const PRIME: u64 = 0x100000001b3;
+
+    bytes.iter().fold(OFFSET_BASIS, |hash, byte| {
+        (hash ^ *byte as u64).wrapping_mul(PRIME)
+    })
+}
+
+/// Namespace of the suite ids [`suite_id`] derives
+const SUITE_ID_NAMESPACE: Uuid = Uuid::from_u128(0x5e1a7e570000400080005e1a7e575e1a);
+
+/// Id of suite `suite` that is the same in every run, unlike the id of a
+/// [`SuiteResult`](crate::suite::SuiteResult), which is new each run
+pub fn suite_id(suite: &str) -> Uuid {
+    Uuid::new_v5(&SUITE_ID_NAMESPACE, suite.as_bytes())
+}
+
+/// Id of test `test` in suite `suite` that is the same in every run. `test`
+/// is the test's qualified name, which includes its module path when known.
+pub fn test_id(suite: &str, test: &str) -> Uuid {
+    Uuid::new_v5(&suite_id(suite), test.as_bytes())
+}
+
+#[derive(Debug, Clone, serde::Serialize, serde::Deserialize)]
 pub struct TestExecutable {
     pub path: PathBuf,
+    /// Name of the cargo target the executable was built from
     pub name: String,
     pub package_name: String,
-    pub target_crate: String,
+    /// Directory containing the package's `Cargo.toml`
+    pub package_root: Option<PathBuf>,
+    /// Kind of the cargo target, e.g. `lib`, `bin` or `test`
+    pub target_kind: Option<String>,
+    /// Root source file of the cargo target
+    pub src_path: Option<PathBuf>,
 }
 
 impl TestExecutable {
     pub fn new(path: PathBuf, name: String, package_name: String) -> Self {
-        let target_crate = Self::determine_target_crate(&path);
         Self {
             path,
             name,
             package_name,
-            target_crate,
+            package_root: None,
+            target_kind: None,
+            src_path: None,
         }
     }
 
-    // TODO: remove this -- fuckin hack
-    pub fn determine_target_crate(path: &PathBuf) -> String {
-        let path_str = path.to_string_lossy();
-        if path_str.contains("examples") {
-            "examples".to_string()
-        } else if path_str.contains("cli") {
-            "cli".to_string()
-        } else if path_str.contains("core") {
-            "core".to_string()
-        } else if path_str.contains("server") {
-            "server".to_string()
-        } else if path_str.contains("proc-macros") || path_str.contains("proc_macros") {
-            "proc_macros".to_string()
-        } else {
-            "examples".to_string()
+    pub fn with_package_root(mut self, package_root: PathBuf) -> Self {
+        self.package_root = Some(package_root);
+        self
+    }
+
+    pub fn with_target(mut self, kind: String, src_path: PathBuf) -> Self {
+        self.target_kind = Some(kind);
+        self.src_path = Some(src_path);
+        self
+    }
+
+    /// Whether `target` names this executable: its package (with `-` and `_`
+    /// treated alike), its target, a directory containing its package or
+    /// source, or a source file compiled into it.
+    pub fn matches_target(&self, target: &str) -> bool {
+        let normalize = |name: &str| name.replace('-', "_");
+        if normalize(target) == normalize(&self.package_name) || target == self.name {
+            return true;
+        }
+
+        let Ok(path) = Path::new(target).canonicalize() else {
+            return false;
+        };
+        let package_root = self
+            .package_root
+            .as_ref()
+            .and_then(|root| root.canonicalize().ok());
+        let src_path = self
+            .src_path
+            .as_ref()
+            .and_then(|src| src.canonicalize().ok());
+
+        if path.is_dir() {
+            return package_root.is_some_and(|root| root.starts_with(&path))
+                || src_path.is_some_and(|src| src.starts_with(&path));
+        }
+
+        match src_path {
+            Some(src) if src == path => true,
+            // Modules of a library live next to its root file, while each
+            // integration test file is its own target
+            Some(src) => {
+                self.target_kind.as_deref() != Some("test")
+                    && src.parent().is_some_and(|dir| path.starts_with(dir))
+            }
+            None => package_root.is_some_and(|root| path.starts_with(root)),
         }
     }
 }
@@ -269,6 +499,7 @@ impl StandardLineParser {
                 name: name.to_string(),
                 status,
                 duration_ms: None,
+                stdout: None,
             },
         ))
     }
@@ -276,7 +507,10 @@ impl StandardLineParser {
     fn parse_panic(input: &str) -> IResult<&str, TestOutputLine> {
         let (input, _) = tag("thread '")(input)?;
         let (input, test_name) = take_until("'")(input)?;
-        let (input, _) = tag("' panicked at ")(input)?;
+        let (input, _) = tag("'")(input)?;
+        // Newer toolchains include the thread id: `thread 'name' (1234) panicked at`
+        let (input, _) = opt(delimited(tag(" ("), digit1, tag(")"))).parse(input)?;
+        let (input, _) = tag(" panicked at ")(input)?;
         let (input, file) = take_until(":")(input)?;
         let (input, _) = tag(":")(input)?;
         let (input, line_str) = digit1(input)?;
@@ -305,17 +539,25 @@ impl StandardLineParser {
 
     pub fn create_test_result(name: &str, status: TestStatus) -> TestResult {
         let test_id = Uuid::new_v4();
+        let qualified_name = name.to_string();
         let name = format_mod_name(name);
 
-        let metadata = TestMetadata::new(name.clone());
+        let mut metadata = TestMetadata::new(name.clone());
+        metadata.module_path = Some(qualified_name);
         let mut test_result = TestResult::new(test_id, name, metadata);
 
         match status {
             TestStatus::Passed => test_result.finish(TestStatus::Passed, None),
+            TestStatus::Cached => test_result.finish(TestStatus::Cached, None),
+            // Not run yet, e.g. listed by a dry run
+            TestStatus::Pending => {}
             TestStatus::Failed => test_result.finish(
                 TestStatus::Failed,
                 Some(Error::test_execution("Test failed")),
             ),
+            TestStatus::Timeout => {
+                test_result.finish(TestStatus::Timeout, Some(Error::timeout("Test timed out")))
+            }
             _ => test_result.finish(TestStatus::Skipped, None),
         }
 
@@ -373,42 +615,32 @@ impl JsonLineParser {
                     Ok(None)
                 }
             }
-            (Some("test"), Some("ok")) => {
-                if let Some(name) = json.get("name").and_then(|v| v.as_str()) {
-                    let duration_ms = json.get("exec_time").and_then(|v| v.as_f64());
-
-                    Ok(Some(TestOutputLine::TestResult {
-                        name: name.to_string(),
-                        status: TestStatus::Passed,
-                        duration_ms,
-                    }))
-                } else {
-                    Ok(None)
-                }
-            }
-            (Some("test"), Some("failed")) => {
-                if let Some(name) = json.get("name").and_then(|v| v.as_str()) {
-                    let duration_ms = json.get("exec_time").and_then(|v| v.as_f64());
-
-                    Ok(Some(TestOutputLine::TestResult {
-                        name: name.to_string(),
-                        status: TestStatus::Failed,
-                        duration_ms,
-                    }))
-                } else {
-                    Ok(None)
-                }
-            }
-            (Some("test"), Some("ignored")) => {
-                if let Some(name) = json.get("name").and_then(|v| v.as_str()) {
-                    Ok(Some(TestOutputLine::TestResult {
-                        name: name.to_string(),
-                        status: TestStatus::Skipped,
-                        duration_ms: None,
-                    }))
-                } else {
-                    Ok(None)
-                }
+            (Some("test"), Some(event @ ("ok" | "failed" | "ignored"))) => {
+                let Some(name) = json.get("name").and_then(|v| v.as_str()) else {
+                    return Ok(None);
+                };
+
+                let status = match event {
+                    "ok" => TestStatus::Passed,
+                    "failed" => TestStatus::Failed,
+                    _ => TestStatus::Skipped,
+                };
+
+                // `exec_time` is reported in seconds
+                let duration_ms = json
+                    .get("exec_time")
+                    .and_then(|v| v.as_f64())
+                    .map(|secs| secs * 1000.0);
+
+                Ok(Some(TestOutputLine::TestResult {
+                    name: name.to_string(),
+                    status,
+                    duration_ms,
+                    stdout: json
+                        .get("stdout")
+                        .and_then(|v| v.as_str())
+                        .map(|s| s.to_string()),
+                }))
             }
             (Some("suite"), Some("failed")) | (Some("suite"), Some("ok")) => Ok(None),
             _ => Ok(None),
@@ -416,11 +648,18 @@ impl JsonLineParser {
     }
 }
 
+/// Splits what a reader yields into lines. Readers may be non-blocking:
+/// reading stops when they would block, and a line written in several
+/// parts is held back until it's complete, so a chatty child can't stall
+/// the poll loop reading it.
 #[derive(Debug)]
 pub struct LineBuffer<R: Read> {
     reader: BufReader<R>,
     buffer: Vec<u8>,
-    partial: String,
+    /// Bytes read after the last complete line, decoded once the line is
+    /// complete so characters split between reads survive
+    partial: Vec<u8>,
+    eof: bool,
 }
 
 impl<R: Read> LineBuffer<R> {
@@ -429,105 +668,171 @@ impl<R: Read> LineBuffer<R> {
         Self {
             reader,
             buffer: vec![0; 4096],
-            partial: String::new(),
+            partial: Vec::new(),
+            eof: false,
         }
     }
 
+    pub fn get_mut(&mut self) -> &mut R {
+        self.reader.get_mut()
+    }
+
+    /// Whether the writing end has been closed and everything has been read
+    pub fn is_eof(&self) -> bool {
+        self.eof && self.partial.is_empty()
+    }
+
+    /// The next complete line, or the unterminated rest once the writing
+    /// end is closed. `None` when nothing more can be read without
+    /// blocking, or at the end.
     pub fn read_line(&mut self) -> std::io::Result<Option<String>> {
         loop {
-            if let Some(pos) = self.partial.find('\n') {
-                let line = self.partial[..pos].to_string();
-                self.partial.drain(..=pos);
-                return Ok(Some(line));
+            if let Some(pos) = self.partial.iter().position(|&b| b == b'\n') {
+                let line: Vec<u8> = self.partial.drain(..=pos).collect();
+                return Ok(Some(String::from_utf8_lossy(&line[..pos]).into_owned()));
+            }
+
+            if self.eof {
+                return Ok(self.flush_remaining());
             }
 
             match self.reader.read(&mut self.buffer) {
-                Ok(0) => {
-                    if !self.partial.is_empty() {
-                        let line = self.partial.clone();
-                        self.partial.clear();
-                        return Ok(Some(line));
-                    }
-                    return Ok(None);
-                }
-                Ok(n) => {
-                    self.partial
-                        .push_str(&String::from_utf8_lossy(&self.buffer[..n]));
-                }
-                Err(e) if e.kind() == std::io::ErrorKind::WouldBlock => {
-                    return Ok(None);
-                }
+                Ok(0) => self.eof = true,
+                Ok(n) => self.partial.extend_from_slice(&self.buffer[..n]),
+                Err(e) if e.kind() == std::io::ErrorKind::Interrupted => {}
+                Err(e) if e.kind() == std::io::ErrorKind::WouldBlock => return Ok(None),
                 Err(e) => return Err(e),
             }
         }
     }
 
+    /// The next two lines, once both can be read. A first line read without
+    /// the second is kept for the next call.
     pub fn read_pair(&mut self) -> Option<(String, String)> {
-        let lines = self.take_lines(2);
-        if lines.len() == 2 {
-            Some((lines[0].clone(), lines[1].clone()))
-        } else {
-            None
+        let first = self.read_line().ok()??;
+        match self.read_line() {
+            Ok(Some(second)) => Some((first, second)),
+            _ => {
+                self.unread(first);
+                None
+            }
         }
     }
 
+    /// Up to `n` lines, stopping early when no more can be read without
+    /// blocking
     pub fn take_lines(&mut self, n: usize) -> Vec<String> {
         let mut lines = Vec::new();
-        for _ in 0..n {
-            if let Ok(Some(line)) = self.read_line() {
-                lines.push(line);
+        while lines.len() < n {
+            match self.read_line() {
+                Ok(Some(line)) => lines.push(line),
+                _ => break,
             }
         }
         lines
     }
 
-    pub fn read_panic_group(&mut self) -> Option<String> {
-        let mut lines = Vec::new();
-        let mut found_panic = false;
+    /// Take the unterminated line read so far, if any
+    pub fn flush_remaining(&mut self) -> Option<String> {
+        if self.partial.is_empty() {
+            return None;
+        }
+        let line = std::mem::take(&mut self.partial);
+        Some(String::from_utf8_lossy(&line).into_owned())
+    }
 
-        for _ in 0..4 {
-            if let Ok(Some(line)) = self.read_line() {
-                let trimmed = line.trim();
-                if trimmed.is_empty() {
-                    continue;
-                }
+    /// Put `line` back to be read again first
+    fn unread(&mut self, line: String) {
+        let mut bytes = line.into_bytes();
+        bytes.push(b'\n');
+        bytes.append(&mut self.partial);
+        self.partial = bytes;
+    }
+}
 
-                if trimmed.starts_with("note: run with `RUST_BACKTRACE=1`")
-                    || trimmed.starts_with("stack backtrace:")
-                {
-                    break;
-                }
+/// Groups stderr lines into panic reports of the form
+///
+/// ```text
+/// thread 'module::test' panicked at src/lib.rs:10:5:
+/// assertion `left == right` failed
+///   left: 1
+///  right: 2
+/// ```
+///
+/// Lines are fed in one at a time as they arrive, so a report split across
+/// reads is never lost. Lines outside of a panic report (e.g. a test's own
+/// `eprintln!` output) are ignored; [`claims`](Self::claims) tells them
+/// apart.
+#[derive(Debug, Clone, Default)]
+pub struct PanicGroupCollector {
+    lines: Vec<String>,
+    /// Whether the backtrace printed after a report is being read
+    in_backtrace: bool,
+}
 
-                if trimmed.starts_with("thread '") && trimmed.contains("' panicked at ") {
-                    found_panic = true;
-                }
+impl PanicGroupCollector {
+    pub fn new() -> Self {
+        Self::default()
+    }
 
-                lines.push(line);
+    /// Feed the next stderr line, returning a panic report once one is complete.
+    pub fn push_line(&mut self, line: String) -> Option<String> {
+        let trimmed = line.trim();
 
-                if found_panic && lines.len() >= 2 {
-                    break;
-                }
-            } else {
-                break;
-            }
+        if is_panic_start(trimmed) {
+            let finished = self.finish();
+            self.lines.push(line);
+            return finished;
         }
 
-        if lines.len() >= 2 && found_panic {
-            Some(lines.join("\n"))
-        } else {
-            None
+        if self.lines.is_empty() {
+            return None;
         }
+
+        if trimmed.starts_with("stack backtrace:") {
+            self.in_backtrace = true;
+            return self.finish();
+        }
+        if trimmed.is_empty() || trimmed.starts_with("note: run with `RUST_BACKTRACE=1`") {
+            return self.finish();
+        }
+
+        self.lines.push(line);
+        None
     }
 
-    pub fn flush_remaining(&mut self) -> Option<String> {
-        if !self.partial.is_empty() {
-            let line = self.partial.clone();
-            self.partial.clear();
-            Some(line)
-        } else {
-            None
+    /// Whether `line` is part of a panic report or the backtrace after one,
+    /// rather than output of its own. Call before feeding it in.
+    pub fn claims(&mut self, line: &str) -> bool {
+        let trimmed = line.trim();
+        if self.in_backtrace {
+            if is_backtrace_frame(trimmed) || trimmed.starts_with("note: Some details are omitted")
+            {
+                return true;
+            }
+            self.in_backtrace = false;
         }
+        !self.lines.is_empty() || is_panic_start(trimmed)
     }
+
+    /// Take the report collected so far, if it has a message.
+    pub fn finish(&mut self) -> Option<String> {
+        let lines = std::mem::take(&mut self.lines);
+        (lines.len() >= 2).then(|| lines.join("\n"))
+    }
+}
+
+fn is_panic_start(line: &str) -> bool {
+    line.starts_with("thread '") && line.contains(" panicked at ")
+}
+
+/// Whether `line` is a `12: function` or `at src/lib.rs:10:5` line of a
+/// backtrace
+fn is_backtrace_frame(line: &str) -> bool {
+    line.starts_with("at ")
+        || line.split_once(':').is_some_and(|(index, _)| {
+            !index.is_empty() && index.bytes().all(|b| b.is_ascii_digit())
+        })
 }
 
 impl<R: Read> Iterator for LineBuffer<R> {
@@ -537,3 +842,110 @@ impl<R: Read> Iterator for LineBuffer<R> {
         self.read_line().unwrap_or(None)
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::collections::VecDeque;
+    use std::io;
+
+    /// A non-blocking reader yielding `chunks` one read at a time, and
+    /// would-block between them
+    struct Chunked {
+        chunks: VecDeque<Option<&'static [u8]>>,
+    }
+
+    impl Read for Chunked {
+        fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+            match self.chunks.pop_front() {
+                Some(Some(chunk)) => {
+                    buf[..chunk.len()].copy_from_slice(chunk);
+                    Ok(chunk.len())
+                }
+                Some(None) => Err(io::ErrorKind::WouldBlock.into()),
+                None => Ok(0),
+            }
+        }
+    }
+
+    fn line_buffer(chunks: &[Option<&'static [u8]>]) -> LineBuffer<Chunked> {
+        LineBuffer::new(Chunked {
+            chunks: chunks.iter().copied().collect(),
+        })
+    }
+
+    #[test]
+    fn test_line_buffer_holds_back_partial_lines() {
+        let mut buffer = line_buffer(&[Some(b"one\ntw"), None, Some(b"o"), None]);
+        assert_eq!(buffer.read_line().unwrap().as_deref(), Some("one"));
+        assert_eq!(buffer.read_line().unwrap(), None);
+        assert_eq!(buffer.read_line().unwrap(), None);
+        assert!(!buffer.is_eof());
+
+        let mut buffer = line_buffer(&[Some(b"two \xc3"), Some(b"\xa9\nend")]);
+        assert_eq!(buffer.read_line().unwrap().as_deref(), Some("two é"));
+        assert_eq!(buffer.read_line().unwrap().as_deref(), Some("end"));
+        assert!(buffer.is_eof());
+    }
+
+    #[test]
+    fn test_read_pair_keeps_first_line_until_second_arrives() {
+        let mut buffer = line_buffer(&[Some(b"first\n"), None, Some(b"second\n")]);
+        assert_eq!(buffer.read_pair(), None);
+        assert_eq!(
+            buffer.read_pair(),
+            Some(("first".to_string(), "second".to_string()))
+        );
+    }
+
+    fn start(state: &mut TestRunState, name: &str) {
+        state.handle_line(TestOutputLine::TestStart {
+            name: name.to_string(),
+        });
+    }
+
+    /// Pass `name`, returning the stderr put on its result
+    fn pass(state: &mut TestRunState, name: &str) -> Option<String> {
+        let output = state.handle_line(TestOutputLine::TestResult {
+            name: name.to_string(),
+            status: TestStatus::Passed,
+            duration_ms: None,
+            stdout: None,
+        });
+        match output {
+            Some(ProcessOutput::TestPassed { result, .. }) => result.stderr,
+            other => panic!("expected a pass, got {:?}", other),
+        }
+    }
+
+    #[test]
+    fn test_stderr_goes_to_the_only_running_test() {
+        let mut state = TestRunState::for_suite("suite");
+        start(&mut state, "a");
+        state.push_stderr("from a".to_string());
+        assert_eq!(pass(&mut state, "a").as_deref(), Some("from a\n"));
+        assert_eq!(state.take_suite_stderr(), None);
+    }
+
+    #[test]
+    fn test_stderr_of_concurrent_tests_is_kept_for_the_suite() {
+        let mut state = TestRunState::for_suite("suite");
+        start(&mut state, "a");
+        start(&mut state, "b");
+        state.push_stderr("from a or b".to_string());
+        assert_eq!(pass(&mut state, "a"), None);
+        assert_eq!(pass(&mut state, "b"), None);
+        assert_eq!(state.take_suite_stderr().as_deref(), Some("from a or b\n"));
+    }
+
+    #[test]
+    fn test_stderr_without_running_tests_goes_to_the_next_one() {
+        let mut state = TestRunState::for_suite("suite");
+        state.push_stderr("before a".to_string());
+        start(&mut state, "a");
+        assert_eq!(pass(&mut state, "a").as_deref(), Some("before a\n"));
+
+        state.push_stderr("after a".to_string());
+        assert_eq!(state.take_suite_stderr().as_deref(), Some("after a\n"));
+    }
+}
@@ -0,0 +1,191 @@
+//! Random test data that can be replayed.
+//!
+//! Every run has a seed, picked at random unless one is given with
+//! `--seed` (or the `SHEILA_SEED` variable). Each test's [`TestRng`] is
+//! seeded from the run seed and the test's name, so a test sees the same
+//! data whenever the run seed is the same, whatever else runs with it.
+//!
+//! ```ignore
+//! let rng = ctx.rng();
+//! let user = User::new(rng.name(), rng.email());
+//! let id = rng.uuid();
+//! let port = rng.with(|rng| rng.gen_range(1024..65535));
+//! ```
+
+use crate::stable_hash;
+use parking_lot::Mutex;
+use rand::rngs::StdRng;
+use rand::{Rng, RngCore, SeedableRng};
+use std::sync::{Arc, OnceLock};
+use uuid::Uuid;
+
+pub use rand;
+
+/// Environment variable the run seed is read from, and that the CLI passes
+/// it to test executables in
+pub const SEED_ENV: &str = "SHEILA_SEED";
+
+const FIRST_NAMES: &[&str] = &[
+    "Ada", "Alan", "Barbara", "Dennis", "Donald", "Edsger", "Frances", "Grace", "Guido", "Hedy",
+    "John", "Ken", "Linus", "Margaret", "Niklaus", "Radia", "Robin", "Shafi", "Tim",
+];
+
+const LAST_NAMES: &[&str] = &[
+    "Allen",
+    "Backus",
+    "Dijkstra",
+    "Hamilton",
+    "Hopper",
+    "Kay",
+    "Knuth",
+    "Lamarr",
+    "Liskov",
+    "Lovelace",
+    "McCarthy",
+    "Milner",
+    "Perlman",
+    "Ritchie",
+    "Shannon",
+    "Thompson",
+    "Torvalds",
+    "Turing",
+    "Wirth",
+    "Goldwasser",
+];
+
+/// Seed of this process's run: the one in [`SEED_ENV`] if it's set,
+/// otherwise one picked at random the first time it's asked for
+pub fn run_seed() -> u64 {
+    static SEED: OnceLock<u64> = OnceLock::new();
+    *SEED.get_or_init(|| {
+        std::env::var(SEED_ENV)
+            .ok()
+            .and_then(|seed| seed.parse().ok())
+            .unwrap_or_else(random_seed)
+    })
+}
+
+/// A new seed, different every time
+pub fn random_seed() -> u64 {
+    rand::random()
+}
+
+/// Seed of the test named `name` in a run seeded with `run_seed`
+pub fn test_seed(run_seed: u64, name: &str) -> u64 {
+    let mut bytes = run_seed.to_le_bytes().to_vec();
+    bytes.extend_from_slice(name.as_bytes());
+    stable_hash(&bytes)
+}
+
+/// A seeded random number generator with helpers for common test data.
+/// Clones draw from the same sequence, so data drawn through one handle is
+/// not repeated through another.
+#[derive(Debug, Clone)]
+pub struct TestRng {
+    seed: u64,
+    rng: Arc<Mutex<StdRng>>,
+}
+
+impl TestRng {
+    pub fn new(seed: u64) -> Self {
+        Self {
+            seed,
+            rng: Arc::new(Mutex::new(StdRng::seed_from_u64(seed))),
+        }
+    }
+
+    pub fn seed(&self) -> u64 {
+        self.seed
+    }
+
+    /// Draw from the generator with anything from [`rand`]
+    pub fn with<T, F: FnOnce(&mut StdRng) -> T>(&self, f: F) -> T {
+        f(&mut self.rng.lock())
+    }
+
+    pub fn first_name(&self) -> String {
+        self.pick(FIRST_NAMES).to_string()
+    }
+
+    pub fn last_name(&self) -> String {
+        self.pick(LAST_NAMES).to_string()
+    }
+
+    /// A full name, e.g. `Grace Hopper`
+    pub fn name(&self) -> String {
+        format!("{} {}", self.first_name(), self.last_name())
+    }
+
+    /// An email address on `example.com`, e.g. `grace.hopper42@example.com`
+    pub fn email(&self) -> String {
+        format!(
+            "{}.{}{}@example.com",
+            self.first_name().to_lowercase(),
+            self.last_name().to_lowercase(),
+            self.with(|rng| rng.gen_range(0..1000))
+        )
+    }
+
+    /// A version 4 UUID
+    pub fn uuid(&self) -> Uuid {
+        let mut bytes = [0; 16];
+        self.fill(&mut bytes);
+        uuid::Builder::from_random_bytes(bytes).into_uuid()
+    }
+
+    pub fn bytes(&self, len: usize) -> Vec<u8> {
+        let mut bytes = vec![0; len];
+        self.fill(&mut bytes);
+        bytes
+    }
+
+    /// A string of `len` ASCII letters and digits
+    pub fn alphanumeric(&self, len: usize) -> String {
+        self.with(|rng| {
+            (0..len)
+                .map(|_| rng.sample(rand::distributions::Alphanumeric) as char)
+                .collect()
+        })
+    }
+
+    /// One of `items`, which must not be empty
+    pub fn pick<'a, T>(&self, items: &'a [T]) -> &'a T {
+        &items[self.with(|rng| rng.gen_range(0..items.len()))]
+    }
+
+    fn fill(&self, bytes: &mut [u8]) {
+        self.rng.lock().fill_bytes(bytes);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{RunnerConfig, Test, TestSuite};
+
+    #[test]
+    fn test_rng_is_replayed_from_the_run_seed() {
+        let drawn = Arc::new(Mutex::new(Vec::new()));
+        let mut suite = TestSuite::new("random").add_test(Test::new("draws", {
+            let drawn = drawn.clone();
+            move |ctx| {
+                let rng = ctx.rng();
+                drawn
+                    .lock()
+                    .push((rng.name(), rng.email(), rng.uuid(), rng.bytes(4)));
+                Ok(())
+            }
+        }));
+
+        for seed in [7, 7, 8] {
+            let config = RunnerConfig::default().seed(seed);
+            suite.execute_with(&config, &Default::default()).unwrap();
+        }
+
+        let drawn = drawn.lock();
+        assert_eq!(drawn[0], drawn[1]);
+        assert_ne!(drawn[0], drawn[2]);
+        assert!(drawn[0].1.ends_with("@example.com"));
+        assert_eq!(drawn[0].2.get_version_num(), 4);
+    }
+}
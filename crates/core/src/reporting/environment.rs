@@ -0,0 +1,226 @@
+use std::process::Command;
+use std::sync::OnceLock;
+
+use indexmap::IndexMap;
+use serde::{Deserialize, Serialize};
+
+/// CI providers, the variable that identifies each, and the variables worth
+/// keeping from it. Only these are captured, so secrets in the environment
+/// never end up in reports.
+const CI_PROVIDERS: &[(&str, &str, &[&str])] = &[
+    (
+        "GitHub Actions",
+        "GITHUB_ACTIONS",
+        &[
+            "GITHUB_REPOSITORY",
+            "GITHUB_WORKFLOW",
+            "GITHUB_JOB",
+            "GITHUB_RUN_ID",
+            "GITHUB_RUN_NUMBER",
+            "GITHUB_RUN_ATTEMPT",
+            "GITHUB_EVENT_NAME",
+        ],
+    ),
+    (
+        "GitLab CI",
+        "GITLAB_CI",
+        &[
+            "CI_PROJECT_PATH",
+            "CI_PIPELINE_ID",
+            "CI_PIPELINE_URL",
+            "CI_JOB_ID",
+            "CI_JOB_NAME",
+            "CI_JOB_URL",
+        ],
+    ),
+    (
+        "CircleCI",
+        "CIRCLECI",
+        &[
+            "CIRCLE_PROJECT_REPONAME",
+            "CIRCLE_JOB",
+            "CIRCLE_BUILD_NUM",
+            "CIRCLE_BUILD_URL",
+        ],
+    ),
+    (
+        "Buildkite",
+        "BUILDKITE",
+        &[
+            "BUILDKITE_PIPELINE_SLUG",
+            "BUILDKITE_BUILD_NUMBER",
+            "BUILDKITE_BUILD_URL",
+            "BUILDKITE_JOB_ID",
+        ],
+    ),
+    (
+        "Jenkins",
+        "JENKINS_URL",
+        &["JOB_NAME", "BUILD_NUMBER", "BUILD_URL"],
+    ),
+];
+
+/// Where CI providers put the commit and branch being built, for checkouts
+/// without a usable `.git` directory
+const CI_COMMIT_VARS: &[&str] = &[
+    "GITHUB_SHA",
+    "CI_COMMIT_SHA",
+    "CIRCLE_SHA1",
+    "BUILDKITE_COMMIT",
+    "GIT_COMMIT",
+];
+const CI_BRANCH_VARS: &[&str] = &[
+    "GITHUB_HEAD_REF",
+    "GITHUB_REF_NAME",
+    "CI_COMMIT_REF_NAME",
+    "CIRCLE_BRANCH",
+    "BUILDKITE_BRANCH",
+    "GIT_BRANCH",
+];
+
+/// The machine and checkout a run happened on, so a report can be traced
+/// back to the commit and host that produced it.
+///
+/// Captured once per process with [`Environment::current`] and attached to
+/// every report through [`ReportMetadata`](super::ReportMetadata). Anything
+/// that can't be determined is left out.
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[cfg_attr(feature = "schemas", derive(schemars::JsonSchema))]
+pub struct Environment {
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub git_commit: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub git_branch: Option<String>,
+    /// Whether the checkout had uncommitted changes
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub git_dirty: Option<bool>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub hostname: Option<String>,
+    pub os: String,
+    pub arch: String,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub rustc_version: Option<String>,
+    /// Name of the CI provider, when running in CI
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub ci: Option<String>,
+    /// Identifying variables from the CI provider, e.g. the build number and URL
+    #[serde(default, skip_serializing_if = "IndexMap::is_empty")]
+    pub ci_env: IndexMap<String, String>,
+}
+
+impl Environment {
+    /// The environment of this process, captured on first use
+    pub fn current() -> &'static Environment {
+        static CURRENT: OnceLock<Environment> = OnceLock::new();
+        CURRENT.get_or_init(Self::capture)
+    }
+
+    /// Capture the environment now
+    pub fn capture() -> Self {
+        let env = |name: &str| std::env::var(name).ok().filter(|value| !value.is_empty());
+
+        let mut ci = None;
+        let mut ci_env = IndexMap::new();
+        if let Some((provider, _, vars)) = CI_PROVIDERS
+            .iter()
+            .find(|(_, marker, _)| env(marker).is_some())
+        {
+            ci = Some(provider.to_string());
+            for var in vars.iter() {
+                if let Some(value) = env(var) {
+                    ci_env.insert(var.to_string(), value);
+                }
+            }
+        } else if env("CI").is_some_and(|value| value != "false" && value != "0") {
+            ci = Some("CI".to_string());
+        }
+
+        let git_head = command_output("git", &["rev-parse", "HEAD"]);
+        let git_dirty = git_head.as_ref().and_then(|_| {
+            Command::new("git")
+                .args(["status", "--porcelain", "--untracked-files=no"])
+                .output()
+                .ok()
+                .filter(|output| output.status.success())
+                .map(|output| !output.stdout.is_empty())
+        });
+
+        Self {
+            git_commit: git_head.or_else(|| CI_COMMIT_VARS.iter().find_map(|var| env(var))),
+            git_branch: command_output("git", &["rev-parse", "--abbrev-ref", "HEAD"])
+                .filter(|branch| branch != "HEAD")
+                .or_else(|| CI_BRANCH_VARS.iter().find_map(|var| env(var))),
+            git_dirty,
+            hostname: hostname(),
+            os: std::env::consts::OS.to_string(),
+            arch: std::env::consts::ARCH.to_string(),
+            rustc_version: command_output(
+                &std::env::var("RUSTC").unwrap_or_else(|_| "rustc".to_string()),
+                &["--version"],
+            ),
+            ci,
+            ci_env,
+        }
+    }
+
+    /// The captured details as labelled values, in display order
+    pub fn entries(&self) -> Vec<(String, String)> {
+        let mut entries = Vec::new();
+        if let Some(commit) = &self.git_commit {
+            let dirty = if self.git_dirty == Some(true) {
+                " (dirty)"
+            } else {
+                ""
+            };
+            entries.push(("Commit".to_string(), format!("{}{}", commit, dirty)));
+        }
+        if let Some(branch) = &self.git_branch {
+            entries.push(("Branch".to_string(), branch.clone()));
+        }
+        if let Some(hostname) = &self.hostname {
+            entries.push(("Host".to_string(), hostname.clone()));
+        }
+        entries.push(("OS".to_string(), format!("{} ({})", self.os, self.arch)));
+        if let Some(rustc) = &self.rustc_version {
+            entries.push(("Rust".to_string(), rustc.clone()));
+        }
+        if let Some(ci) = &self.ci {
+            entries.push(("CI".to_string(), ci.clone()));
+        }
+        for (name, value) in &self.ci_env {
+            entries.push((name.clone(), value.clone()));
+        }
+        entries
+    }
+}
+
+/// Trimmed stdout of a command that succeeded
+fn command_output(program: &str, args: &[&str]) -> Option<String> {
+    let output = Command::new(program).args(args).output().ok()?;
+    if !output.status.success() {
+        return None;
+    }
+    let stdout = String::from_utf8(output.stdout).ok()?;
+    let stdout = stdout.trim();
+    (!stdout.is_empty()).then(|| stdout.to_string())
+}
+
+#[cfg(unix)]
+fn hostname() -> Option<String> {
+    let mut buf = [0u8; 256];
+    // SAFETY: the buffer is valid for its whole length, and a name that
+    // doesn't fit is truncated rather than overflowing it
+    let ret = unsafe { libc::gethostname(buf.as_mut_ptr().cast(), buf.len()) };
+    if ret != 0 {
+        return None;
+    }
+    let len = buf.iter().position(|&b| b == 0).unwrap_or(buf.len());
+    String::from_utf8(buf[..len].to_vec())
+        .ok()
+        .filter(|name| !name.is_empty())
+}
+
+#[cfg(not(unix))]
+fn hostname() -> Option<String> {
+    std::env::var("COMPUTERNAME").ok()
+}
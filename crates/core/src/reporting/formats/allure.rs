@@ -0,0 +1,279 @@
+use super::*;
+use crate::suite::SuiteResult;
+use crate::test::TestResult;
+use crate::{TestStatus, stable_hash};
+use serde::Serialize;
+use std::path::PathBuf;
+
+/// Writes results in the format read by [Allure](https://allurereport.org),
+/// so runs can be browsed in existing Allure dashboards.
+///
+/// Allure reads a directory of files rather than a single report: one
+/// `<uuid>-result.json` per test, plus any attachments it refers to. The
+/// report content is the JSON array of every result, and
+/// [`AllureReporter::write_results`] writes the directory itself.
+pub struct AllureReporter {
+    metadata: ReportMetadata,
+    attach_output: bool,
+}
+
+impl AllureReporter {
+    pub fn new() -> Self {
+        Self {
+            metadata: ReportMetadata::default(),
+            attach_output: true,
+        }
+    }
+
+    pub fn with_metadata(mut self, metadata: ReportMetadata) -> Self {
+        self.metadata = metadata;
+        self
+    }
+
+    /// Attach each test's captured output to its result
+    pub fn attach_output(mut self, attach: bool) -> Self {
+        self.attach_output = attach;
+        self
+    }
+
+    /// Write a result file for every test in `run_result` into `dir`, along
+    /// with their attachments and an `environment.properties` describing
+    /// where the run happened, returning the paths written.
+    pub fn write_results(&self, run_result: &RunResult, dir: &Path) -> Result<Vec<PathBuf>> {
+        std::fs::create_dir_all(dir)?;
+
+        let mut written = Vec::new();
+        for suite_result in &run_result.suite_results {
+            for test_result in &suite_result.test_results {
+                let result = self.allure_result(suite_result, test_result);
+
+                if let (Some(attachment), Some(output)) =
+                    (result.attachments.first(), test_result.stdout.as_ref())
+                {
+                    let path = dir.join(&attachment.source);
+                    std::fs::write(&path, output)?;
+                    written.push(path);
+                }
+
+                let path = dir.join(format!("{}-result.json", result.uuid));
+                std::fs::write(&path, serde_json::to_string(&result)?)?;
+                written.push(path);
+            }
+        }
+
+        if let Some(ref environment) = self.metadata.environment {
+            let properties: String = environment
+                .entries()
+                .into_iter()
+                .map(|(label, value)| {
+                    format!(
+                        "{}={}\n",
+                        label,
+                        value.replace('\\', "\\\\").replace('\n', "\\n")
+                    )
+                })
+                .collect();
+            let path = dir.join("environment.properties");
+            std::fs::write(&path, properties)?;
+            written.push(path);
+        }
+
+        Ok(written)
+    }
+
+    fn allure_result(&self, suite_result: &SuiteResult, test_result: &TestResult) -> AllureResult {
+        let uuid = test_result.id.to_string();
+        let full_name = format!("{}::{}", suite_result.name, test_result.qualified_name());
+        let history_id = format!("{:016x}", stable_hash(full_name.as_bytes()));
+        let status = AllureStatus::from(test_result.status);
+
+        // Results parsed from libtest output are created once the test has
+        // finished, so work back from the end using the reported duration
+        let stop = test_result
+            .end_time
+            .unwrap_or(test_result.start_time)
+            .timestamp_millis();
+        let start = test_result
+            .duration
+            .map_or(stop, |duration| stop - duration.as_millis() as i64);
+
+        let mut labels = vec![
+            AllureLabel::new("suite", &suite_result.name),
+            AllureLabel::new("framework", "sheila"),
+            AllureLabel::new("language", "rust"),
+        ];
+        if let Some((package, _)) = test_result.qualified_name().rsplit_once("::") {
+            labels.push(AllureLabel::new("package", package));
+        }
+        labels.extend(
+            test_result
+                .tags
+                .iter()
+                .map(|tag| AllureLabel::new("tag", tag)),
+        );
+
+        // Breadcrumbs don't record when they were left, so every step spans
+        // the whole test. A failing test failed at its last step.
+        let steps = test_result
+            .breadcrumbs
+            .iter()
+            .enumerate()
+            .map(|(index, crumb)| AllureStep {
+                name: crumb.clone(),
+                status: if index + 1 == test_result.breadcrumbs.len() {
+                    status
+                } else {
+                    AllureStatus::Passed
+                },
+                stage: "finished",
+                start,
+                stop,
+            })
+            .collect();
+
+        let attachments = match test_result.stdout {
+            Some(_) if self.attach_output => vec![AllureAttachment {
+                name: "output".to_string(),
+                source: format!("{}-attachment.txt", uuid),
+                mime_type: "text/plain",
+            }],
+            _ => Vec::new(),
+        };
+
+        AllureResult {
+            uuid,
+            history_id: history_id.clone(),
+            test_case_id: history_id,
+            full_name,
+            name: test_result.name.clone(),
+            description: test_result.meta.description.clone(),
+            status,
+            status_details: test_result.error.as_ref().map(|error| AllureStatusDetails {
+                message: error.to_string(),
+                trace: Some(error.trace()).filter(|trace| !trace.is_empty()),
+                flaky: test_result.flaky,
+            }),
+            stage: "finished",
+            start,
+            stop,
+            labels,
+            steps,
+            attachments,
+        }
+    }
+}
+
+impl Default for AllureReporter {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl Reporter for AllureReporter {
+    fn render(&self, run_result: &RunResult) -> Result<String> {
+        let results: Vec<AllureResult> = run_result
+            .suite_results
+            .iter()
+            .flat_map(|suite_result| {
+                suite_result
+                    .test_results
+                    .iter()
+                    .map(move |test_result| self.allure_result(suite_result, test_result))
+            })
+            .collect();
+
+        Ok(serde_json::to_string_pretty(&results)?)
+    }
+
+    fn format(&self) -> ReportFormat {
+        ReportFormat::Allure
+    }
+
+    fn metadata(&self) -> &ReportMetadata {
+        &self.metadata
+    }
+}
+
+#[derive(Debug, Clone, Copy, Serialize)]
+#[serde(rename_all = "lowercase")]
+enum AllureStatus {
+    Passed,
+    Failed,
+    Skipped,
+    Unknown,
+}
+
+impl From<TestStatus> for AllureStatus {
+    fn from(status: TestStatus) -> Self {
+        match status {
+            TestStatus::Passed | TestStatus::Cached | TestStatus::XFail => AllureStatus::Passed,
+            TestStatus::Failed | TestStatus::Timeout | TestStatus::XPass => AllureStatus::Failed,
+            TestStatus::Skipped
+            | TestStatus::Ignored
+            | TestStatus::Cancelled
+            | TestStatus::Quarantined => AllureStatus::Skipped,
+            TestStatus::Pending | TestStatus::Running => AllureStatus::Unknown,
+        }
+    }
+}
+
+#[derive(Debug, Serialize)]
+#[serde(rename_all = "camelCase")]
+struct AllureResult {
+    uuid: String,
+    history_id: String,
+    test_case_id: String,
+    full_name: String,
+    name: String,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    description: Option<String>,
+    status: AllureStatus,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    status_details: Option<AllureStatusDetails>,
+    stage: &'static str,
+    start: i64,
+    stop: i64,
+    labels: Vec<AllureLabel>,
+    steps: Vec<AllureStep>,
+    attachments: Vec<AllureAttachment>,
+}
+
+#[derive(Debug, Serialize)]
+struct AllureStatusDetails {
+    message: String,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    trace: Option<String>,
+    flaky: bool,
+}
+
+#[derive(Debug, Serialize)]
+struct AllureLabel {
+    name: &'static str,
+    value: String,
+}
+
+impl AllureLabel {
+    fn new(name: &'static str, value: &str) -> Self {
+        Self {
+            name,
+            value: value.to_string(),
+        }
+    }
+}
+
+#[derive(Debug, Serialize)]
+struct AllureStep {
+    name: String,
+    status: AllureStatus,
+    stage: &'static str,
+    start: i64,
+    stop: i64,
+}
+
+#[derive(Debug, Serialize)]
+struct AllureAttachment {
+    name: String,
+    source: String,
+    #[serde(rename = "type")]
+    mime_type: &'static str,
+}
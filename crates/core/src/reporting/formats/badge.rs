@@ -0,0 +1,210 @@
+use super::*;
+use serde::Serialize;
+use std::path::PathBuf;
+
+/// What a badge shows
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum BadgeMetric {
+    /// Percentage of tests that passed
+    PassRate,
+    /// Passed and failed test counts
+    Tests,
+    /// Wall time of the run
+    Duration,
+}
+
+impl BadgeMetric {
+    pub const ALL: [BadgeMetric; 3] = [
+        BadgeMetric::PassRate,
+        BadgeMetric::Tests,
+        BadgeMetric::Duration,
+    ];
+
+    fn file_stem(&self) -> &'static str {
+        match self {
+            BadgeMetric::PassRate => "pass-rate",
+            BadgeMetric::Tests => "tests",
+            BadgeMetric::Duration => "duration",
+        }
+    }
+}
+
+/// Renders test status badges for embedding in READMEs: an SVG in the flat
+/// shields.io style, and a JSON file for shields.io's
+/// [endpoint badges](https://shields.io/badges/endpoint-badge).
+///
+/// The report content is the SVG for the configured metric;
+/// [`BadgeReporter::write_badges`] writes every badge at once.
+pub struct BadgeReporter {
+    metadata: ReportMetadata,
+    metric: BadgeMetric,
+}
+
+impl BadgeReporter {
+    pub fn new() -> Self {
+        Self {
+            metadata: ReportMetadata::default(),
+            metric: BadgeMetric::Tests,
+        }
+    }
+
+    pub fn with_metadata(mut self, metadata: ReportMetadata) -> Self {
+        self.metadata = metadata;
+        self
+    }
+
+    pub fn metric(mut self, metric: BadgeMetric) -> Self {
+        self.metric = metric;
+        self
+    }
+
+    /// Write an SVG and a shields.io endpoint JSON file for every metric
+    /// into `dir`, returning the paths written
+    pub fn write_badges(&self, run_result: &RunResult, dir: &Path) -> Result<Vec<PathBuf>> {
+        std::fs::create_dir_all(dir)?;
+
+        let mut written = Vec::new();
+        for metric in BadgeMetric::ALL {
+            let badge = Badge::new(metric, run_result);
+
+            let svg = dir.join(format!("{}.svg", metric.file_stem()));
+            std::fs::write(&svg, badge.svg())?;
+            written.push(svg);
+
+            let json = dir.join(format!("{}.json", metric.file_stem()));
+            std::fs::write(&json, serde_json::to_string_pretty(&badge.endpoint())?)?;
+            written.push(json);
+        }
+
+        Ok(written)
+    }
+}
+
+impl Default for BadgeReporter {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl Reporter for BadgeReporter {
+    fn render(&self, run_result: &RunResult) -> Result<String> {
+        Ok(Badge::new(self.metric, run_result).svg())
+    }
+
+    fn format(&self) -> ReportFormat {
+        ReportFormat::Badge
+    }
+
+    fn metadata(&self) -> &ReportMetadata {
+        &self.metadata
+    }
+}
+
+struct Badge {
+    label: &'static str,
+    message: String,
+    /// shields.io colour name, and the hex value it renders as
+    color: (&'static str, &'static str),
+}
+
+impl Badge {
+    fn new(metric: BadgeMetric, run_result: &RunResult) -> Self {
+        let pass_rate = run_result.success_rate() * 100.0;
+        let rate_color = if run_result.failed_tests == 0 {
+            ("brightgreen", "#4c1")
+        } else if pass_rate >= 90.0 {
+            ("green", "#97ca00")
+        } else if pass_rate >= 75.0 {
+            ("yellow", "#dfb317")
+        } else {
+            ("red", "#e05d44")
+        };
+
+        match metric {
+            BadgeMetric::PassRate => Self {
+                label: "pass rate",
+                message: format!("{:.0}%", pass_rate.floor()),
+                color: rate_color,
+            },
+            BadgeMetric::Tests => Self {
+                label: "tests",
+                message: if run_result.failed_tests == 0 {
+                    format!("{} passed", run_result.passed_tests)
+                } else {
+                    format!(
+                        "{} passed, {} failed",
+                        run_result.passed_tests, run_result.failed_tests
+                    )
+                },
+                color: if run_result.failed_tests == 0 {
+                    ("brightgreen", "#4c1")
+                } else {
+                    ("red", "#e05d44")
+                },
+            },
+            BadgeMetric::Duration => Self {
+                label: "duration",
+                message: match run_result.duration {
+                    Some(duration) if duration.as_millis() < 1000 => {
+                        format!("{}ms", duration.as_millis())
+                    }
+                    Some(duration) => format!("{:.1}s", duration.as_secs_f64()),
+                    None => "unknown".to_string(),
+                },
+                color: ("blue", "#007ec6"),
+            },
+        }
+    }
+
+    /// Rough Verdana 11px text width; close enough to size the badge
+    fn text_width(text: &str) -> f64 {
+        text.chars().count() as f64 * 6.5 + 10.0
+    }
+
+    fn svg(&self) -> String {
+        let label_width = Self::text_width(self.label);
+        let message_width = Self::text_width(&self.message);
+        let width = label_width + message_width;
+        let label = html_escape::encode_text(self.label);
+        let message = html_escape::encode_text(&self.message);
+
+        format!(
+            concat!(
+                "<svg xmlns=\"http://www.w3.org/2000/svg\" width=\"{w}\" height=\"20\" role=\"img\" aria-label=\"{l}: {m}\">",
+                "<title>{l}: {m}</title>",
+                "<linearGradient id=\"s\" x2=\"0\" y2=\"100%\"><stop offset=\"0\" stop-color=\"#bbb\" stop-opacity=\".1\"/><stop offset=\"1\" stop-opacity=\".1\"/></linearGradient>",
+                "<clipPath id=\"r\"><rect width=\"{w}\" height=\"20\" rx=\"3\" fill=\"#fff\"/></clipPath>",
+                "<g clip-path=\"url(#r)\"><rect width=\"{lw}\" height=\"20\" fill=\"#555\"/><rect x=\"{lw}\" width=\"{mw}\" height=\"20\" fill=\"{c}\"/><rect width=\"{w}\" height=\"20\" fill=\"url(#s)\"/></g>",
+                "<g fill=\"#fff\" text-anchor=\"middle\" font-family=\"Verdana,Geneva,DejaVu Sans,sans-serif\" font-size=\"11\">",
+                "<text x=\"{lx}\" y=\"14\">{l}</text><text x=\"{mx}\" y=\"14\">{m}</text></g></svg>\n"
+            ),
+            w = width,
+            lw = label_width,
+            mw = message_width,
+            lx = label_width / 2.0,
+            mx = label_width + message_width / 2.0,
+            c = self.color.1,
+            l = label,
+            m = message,
+        )
+    }
+
+    fn endpoint(&self) -> BadgeEndpoint {
+        BadgeEndpoint {
+            schema_version: 1,
+            label: self.label,
+            message: self.message.clone(),
+            color: self.color.0,
+        }
+    }
+}
+
+/// The JSON shields.io reads for an endpoint badge
+#[derive(Debug, Serialize)]
+#[serde(rename_all = "camelCase")]
+struct BadgeEndpoint {
+    schema_version: u8,
+    label: &'static str,
+    message: String,
+    color: &'static str,
+}
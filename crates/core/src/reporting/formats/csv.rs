@@ -31,11 +31,22 @@ impl Default for CsvReporter {
 }
 
 impl Reporter for CsvReporter {
-    fn generate(&self, run_result: &RunResult) -> Result<TestReport> {
-        let mut content = String::new();
+    fn render(&self, run_result: &RunResult) -> Result<String> {
+        let mut content = Vec::new();
+        self.write_to(run_result, &mut content)?;
 
+        String::from_utf8(content).map_err(|e| Error::generic(e.to_string()))
+    }
+
+    fn write_to(&self, run_result: &RunResult, writer: &mut dyn Write) -> Result<()> {
         if self.include_headers {
-            content.push_str("suite_name,test_name,status,duration_ms,error\n");
+            // Read back with `#` as the comment character to skip these
+            if let Some(ref environment) = self.metadata.environment {
+                for (label, value) in environment.entries() {
+                    writeln!(writer, "# {}: {}", label, value)?;
+                }
+            }
+            writeln!(writer, "suite_name,test_name,status,duration_ms,error")?;
         }
 
         for suite_result in &run_result.suite_results {
@@ -51,23 +62,22 @@ impl Reporter for CsvReporter {
                     .map(|e| format!("\"{}\"", e.to_string().replace('"', "\"\"")))
                     .unwrap_or_else(|| "".to_string());
 
-                content.push_str(&format!(
-                    "\"{}\",\"{}\",{},{},{}\n",
+                writeln!(
+                    writer,
+                    "\"{}\",\"{}\",{},{},{}",
                     suite_result.name, test_result.name, test_result.status, duration_ms, error
-                ));
+                )?;
             }
         }
 
-        Ok(TestReport {
-            metadata: self.metadata.clone(),
-            run_result: run_result.clone(),
-            format: ReportFormat::Csv,
-            content,
-            created_at: Utc::now(),
-        })
+        Ok(())
     }
 
     fn format(&self) -> ReportFormat {
         ReportFormat::Csv
     }
+
+    fn metadata(&self) -> &ReportMetadata {
+        &self.metadata
+    }
 }
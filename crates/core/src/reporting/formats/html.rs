@@ -1,9 +1,13 @@
 use super::*;
+use crate::Artifact;
+use crate::test::TestResult;
 
 pub struct HtmlReporter {
     metadata: ReportMetadata,
     include_styles: bool,
     show_timing: bool,
+    slowest: usize,
+    interactive: bool,
 }
 
 impl HtmlReporter {
@@ -12,6 +16,8 @@ impl HtmlReporter {
             metadata: ReportMetadata::default(),
             include_styles: true,
             show_timing: true,
+            slowest: 10,
+            interactive: true,
         }
     }
 
@@ -30,6 +36,20 @@ impl HtmlReporter {
         self
     }
 
+    /// How many of the slowest tests to list in the timing section
+    pub fn slowest(mut self, count: usize) -> Self {
+        self.slowest = count;
+        self
+    }
+
+    /// Add search, filtering and sorting controls to the page, and make
+    /// errors and output collapsible. Everything is inlined, so the page
+    /// still works offline.
+    pub fn interactive(mut self, interactive: bool) -> Self {
+        self.interactive = interactive;
+        self
+    }
+
     fn format_duration(duration: &std::time::Duration) -> String {
         let millis = duration.as_millis();
         if millis < 1000 {
@@ -39,6 +59,224 @@ impl HtmlReporter {
         }
     }
 
+    /// The status a test is filtered under
+    fn filter_status(status: crate::TestStatus) -> &'static str {
+        match status {
+            crate::TestStatus::Passed | crate::TestStatus::Cached | crate::TestStatus::XFail => {
+                "passed"
+            }
+            crate::TestStatus::Failed | crate::TestStatus::Timeout | crate::TestStatus::XPass => {
+                "failed"
+            }
+            _ => "skipped",
+        }
+    }
+
+    fn render_toolbar(run_result: &RunResult) -> String {
+        let mut suites: Vec<&str> = Vec::new();
+        let mut tags: Vec<&str> = Vec::new();
+        for suite_result in &run_result.suite_results {
+            if !suite_result.test_results.is_empty() && !suites.contains(&&*suite_result.name) {
+                suites.push(&suite_result.name);
+            }
+            for tag in suite_result.test_results.iter().flat_map(|t| t.tags.iter()) {
+                if !tags.contains(&&**tag) {
+                    tags.push(tag);
+                }
+            }
+        }
+        tags.sort();
+
+        let options = |values: &[&str]| -> String {
+            values
+                .iter()
+                .map(|value| {
+                    format!(
+                        "<option value=\"{}\">{}</option>",
+                        html_escape::encode_double_quoted_attribute(value),
+                        html_escape::encode_text(value)
+                    )
+                })
+                .collect()
+        };
+
+        let mut html = String::new();
+        html.push_str("<div class=\"toolbar\">\n");
+        html.push_str(
+            "<input type=\"search\" id=\"filter-search\" placeholder=\"Search tests...\">\n",
+        );
+        html.push_str("<select id=\"filter-status\"><option value=\"\">All statuses</option><option value=\"passed\">Passed</option><option value=\"failed\">Failed</option><option value=\"skipped\">Skipped</option></select>\n");
+        html.push_str(&format!(
+            "<select id=\"filter-suite\"><option value=\"\">All suites</option>{}</select>\n",
+            options(&suites)
+        ));
+        if !tags.is_empty() {
+            html.push_str(&format!(
+                "<select id=\"filter-tag\"><option value=\"\">All tags</option>{}</select>\n",
+                options(&tags)
+            ));
+        }
+        html.push_str("<button id=\"sort-duration\" type=\"button\">Sort by duration</button>\n");
+        html.push_str("</div>\n");
+        html
+    }
+
+    fn render_script() -> &'static str {
+        r#"<script>
+(function () {
+    var search = document.getElementById('filter-search');
+    var status = document.getElementById('filter-status');
+    var suite = document.getElementById('filter-suite');
+    var tag = document.getElementById('filter-tag');
+    var sort = document.getElementById('sort-duration');
+    var suites = Array.prototype.slice.call(document.querySelectorAll('.suite[data-suite]'));
+
+    function apply() {
+        var query = search.value.toLowerCase();
+        suites.forEach(function (suiteEl) {
+            var visible = 0;
+            suiteEl.querySelectorAll('.test-row').forEach(function (row) {
+                var show = (!query || row.dataset.name.toLowerCase().indexOf(query) !== -1)
+                    && (!status.value || row.dataset.status === status.value)
+                    && (!tag || !tag.value || row.dataset.tags.split(',').indexOf(tag.value) !== -1)
+                    && (!suite.value || suiteEl.dataset.suite === suite.value);
+                row.hidden = !show;
+                if (show) visible++;
+            });
+            suiteEl.hidden = visible === 0;
+        });
+    }
+
+    var direction = 0;
+    sort.addEventListener('click', function () {
+        direction = direction === -1 ? 1 : -1;
+        sort.textContent = direction === -1 ? 'Duration \u2193' : 'Duration \u2191';
+        suites.forEach(function (suiteEl) {
+            var rows = Array.prototype.slice.call(suiteEl.querySelectorAll('.test-row'));
+            rows.sort(function (a, b) {
+                return direction * (Number(a.dataset.duration) - Number(b.dataset.duration));
+            });
+            rows.forEach(function (row) { suiteEl.appendChild(row); });
+        });
+    });
+
+    [search, status, suite, tag].forEach(function (el) {
+        if (el) el.addEventListener('input', apply);
+    });
+})();
+</script>
+"#
+    }
+
+    fn render_timing(analysis: &TimingAnalysis) -> String {
+        let mut html = String::new();
+
+        html.push_str("<div class=\"timing\">\n<h2>Timing</h2>\n");
+        html.push_str("<table>\n<tr><th>Slowest tests</th><th>Duration</th></tr>\n");
+        for timing in &analysis.slowest {
+            html.push_str(&format!(
+                "<tr><td>{}::{}</td><td>{}</td></tr>\n",
+                html_escape::encode_text(&timing.suite),
+                html_escape::encode_text(&timing.test),
+                Self::format_duration(&timing.duration)
+            ));
+        }
+        html.push_str("</table>\n");
+
+        html.push_str("<table>\n<tr><th>Suite</th><th>Duration</th><th>Share</th></tr>\n");
+        for suite in &analysis.suites {
+            html.push_str(&format!(
+                "<tr><td>{}</td><td>{}</td><td><div class=\"bar\" style=\"width: {:.1}%\"></div>{:.1}%</td></tr>\n",
+                html_escape::encode_text(&suite.name),
+                Self::format_duration(&suite.duration),
+                suite.share * 100.0,
+                suite.share * 100.0
+            ));
+        }
+        html.push_str("</table>\n");
+
+        if let Some(phases) = analysis.phases {
+            html.push_str("<table>\n<tr><th>Phase</th><th>Duration</th></tr>\n");
+            for (label, duration) in [
+                ("Setup", phases.setup),
+                ("Test", phases.test),
+                ("Teardown", phases.teardown),
+            ] {
+                html.push_str(&format!(
+                    "<tr><td>{}</td><td>{}</td></tr>\n",
+                    label,
+                    Self::format_duration(&duration)
+                ));
+            }
+            html.push_str("</table>\n");
+        }
+
+        html.push_str("</div>\n");
+        html
+    }
+
+    fn render_environment(environment: &Environment) -> String {
+        let mut html = String::new();
+
+        html.push_str("<div class=\"environment\">\n<h2>Environment</h2>\n<table>\n");
+        for (label, value) in environment.entries() {
+            html.push_str(&format!(
+                "<tr><th>{}</th><td>{}</td></tr>\n",
+                html_escape::encode_text(&label),
+                html_escape::encode_text(&value)
+            ));
+        }
+        html.push_str("</table>\n</div>\n");
+        html
+    }
+
+    /// Text artifacts are embedded, images shown inline and other files linked
+    fn render_artifact(artifact: &Artifact) -> String {
+        let name = html_escape::encode_text(&artifact.name);
+
+        if let Some(ref text) = artifact.text {
+            return format!(
+                "<details><summary>{}</summary><pre>{}</pre></details>\n",
+                name,
+                html_escape::encode_text(text)
+            );
+        }
+
+        let Some(ref path) = artifact.path else {
+            return String::new();
+        };
+        let href = path.display().to_string();
+        let href = html_escape::encode_double_quoted_attribute(&href);
+
+        if artifact.is_image() {
+            format!(
+                "<figure><img src=\"{}\" alt=\"{}\"><figcaption>{}</figcaption></figure>\n",
+                href, name, name
+            )
+        } else {
+            format!("<a href=\"{}\">{}</a>\n", href, name)
+        }
+    }
+
+    /// The breadcrumbs and log lines a failing test left, in the style of
+    /// its output
+    fn render_trail(test_result: &TestResult) -> String {
+        let mut trail = String::new();
+        if !test_result.breadcrumbs.is_empty() {
+            trail.push_str(&format!("Trail: {}\n", test_result.breadcrumbs.join(" → ")));
+        }
+        for line in &test_result.logs {
+            trail.push_str(&format!("[{}] {}\n", line.level, line.message));
+        }
+        if let Some(ref temp_dir) = test_result.temp_dir {
+            trail.push_str(&format!("Temp dir: {}\n", temp_dir.display()));
+        }
+        format!(
+            "<div class=\"output\">{}</div>\n",
+            html_escape::encode_text(&trail)
+        )
+    }
+
     fn generate_styles(&self) -> String {
         if !self.include_styles {
             return String::new();
@@ -78,6 +316,22 @@ impl HtmlReporter {
             .passed { color: #28a745; }
             .failed { color: #dc3545; }
             .skipped { color: #ffc107; }
+            .xfail { color: #6f42c1; }
+            .quarantined { color: #6c757d; }
+            .reason {
+                color: #666;
+                font-size: 0.8em;
+                font-style: italic;
+                margin-left: 8px;
+            }
+            .flaky {
+                background: #fff3cd;
+                color: #856404;
+                padding: 2px 6px;
+                border-radius: 3px;
+                font-size: 0.8em;
+                margin-left: 8px;
+            }
             .suite { 
                 background: white; 
                 margin-bottom: 20px; 
@@ -114,6 +368,47 @@ impl HtmlReporter {
                 color: #dc3545;
                 white-space: pre-wrap;
             }
+            .output { 
+                background: #f8f9fa; 
+                padding: 10px 20px; 
+                font-family: 'Courier New', monospace; 
+                font-size: 0.9em; 
+                color: #333;
+                white-space: pre-wrap;
+            }
+            .artifacts { 
+                padding: 10px 20px; 
+                font-size: 0.9em; 
+            }
+            .artifacts img { max-width: 480px; border: 1px solid #ddd; }
+            .artifacts pre { background: #f8f9fa; padding: 10px; white-space: pre-wrap; }
+            .toolbar {
+                display: flex;
+                flex-wrap: wrap;
+                gap: 10px;
+                margin-bottom: 20px;
+            }
+            .toolbar input, .toolbar select, .toolbar button {
+                padding: 8px 12px;
+                border: 1px solid #dee2e6;
+                border-radius: 6px;
+                font-size: 0.95em;
+                background: white;
+            }
+            .toolbar input { flex: 1; min-width: 200px; }
+            .toolbar button { cursor: pointer; }
+            details > summary {
+                padding: 6px 20px;
+                cursor: pointer;
+                color: #666;
+                font-size: 0.85em;
+            }
+            .environment th {
+                text-align: left;
+                padding-right: 20px;
+                color: #666;
+                font-weight: normal;
+            }
             .footer {
                 text-align: center;
                 margin-top: 40px;
@@ -133,7 +428,7 @@ impl Default for HtmlReporter {
 }
 
 impl Reporter for HtmlReporter {
-    fn generate(&self, run_result: &RunResult) -> Result<TestReport> {
+    fn render(&self, run_result: &RunResult) -> Result<String> {
         let mut html = String::new();
 
         html.push_str("<!DOCTYPE html>\n<html lang=\"en\">\n<head>\n");
@@ -202,6 +497,10 @@ impl Reporter for HtmlReporter {
 
         html.push_str("</div>\n");
 
+        if self.interactive {
+            html.push_str(&Self::render_toolbar(run_result));
+        }
+
         for suite_result in &run_result.suite_results {
             let suite_class = if suite_result.all_passed() {
                 "passed"
@@ -209,7 +508,10 @@ impl Reporter for HtmlReporter {
                 "failed"
             };
 
-            html.push_str("<div class=\"suite\">\n");
+            html.push_str(&format!(
+                "<div class=\"suite\" data-suite=\"{}\">\n",
+                html_escape::encode_double_quoted_attribute(&suite_result.name)
+            ));
             html.push_str(&format!("<div class=\"suite-header {}\">\n", suite_class));
             html.push_str("<div class=\"suite-title\">");
             html.push_str(&format!(
@@ -235,6 +537,13 @@ impl Reporter for HtmlReporter {
             html.push_str("</div>\n");
 
             for test_result in &suite_result.test_results {
+                html.push_str(&format!(
+                    "<div class=\"test-row\" data-name=\"{}\" data-status=\"{}\" data-tags=\"{}\" data-duration=\"{}\">\n",
+                    html_escape::encode_double_quoted_attribute(&test_result.name),
+                    Self::filter_status(test_result.status),
+                    html_escape::encode_double_quoted_attribute(&test_result.tags.join(",")),
+                    test_result.duration.unwrap_or_default().as_millis()
+                ));
                 html.push_str("<div class=\"test\">\n");
                 html.push_str("<div class=\"test-name\">\n");
 
@@ -242,7 +551,11 @@ impl Reporter for HtmlReporter {
                     crate::TestStatus::Passed => ("✓", "passed"),
                     crate::TestStatus::Failed => ("✗", "failed"),
                     crate::TestStatus::Skipped => ("○", "skipped"),
+                    crate::TestStatus::Cached => ("✓", "passed cached"),
                     crate::TestStatus::Ignored => ("⊝", "skipped"),
+                    crate::TestStatus::XFail => ("✗", "xfail"),
+                    crate::TestStatus::XPass => ("!", "failed"),
+                    crate::TestStatus::Quarantined => ("✗", "quarantined"),
                     _ => ("?", ""),
                 };
 
@@ -251,6 +564,19 @@ impl Reporter for HtmlReporter {
                     class, icon
                 ));
                 html.push_str(&format!("<span>{}</span>\n", test_result.name));
+                if test_result.flaky {
+                    html.push_str(&format!(
+                        "<span class=\"flaky\">flaky-pass ({} retries)</span>\n",
+                        test_result.retry_count
+                    ));
+                }
+                if let Some(ref reason) = test_result.skip_reason {
+                    html.push_str(&format!(
+                        "<span class=\"reason\">{}: {}</span>\n",
+                        test_result.status,
+                        html_escape::encode_text(reason)
+                    ));
+                }
                 html.push_str("</div>\n");
 
                 if self.show_timing {
@@ -265,16 +591,73 @@ impl Reporter for HtmlReporter {
                 html.push_str("</div>\n");
 
                 if let Some(ref error) = test_result.error {
-                    html.push_str(&format!(
+                    let error = format!(
                         "<div class=\"error\">{}</div>\n",
-                        html_escape::encode_text(&error.to_string())
-                    ));
+                        html_escape::encode_text(&error.report())
+                    );
+                    if self.interactive {
+                        html.push_str(&format!(
+                            "<details open><summary>Error</summary>{}</details>\n",
+                            error
+                        ));
+                    } else {
+                        html.push_str(&error);
+                    }
+                }
+
+                // Interactive pages embed every test's output, collapsed
+                // unless the test failed
+                if let Some(ref output) = test_result.stdout
+                    && (self.interactive || test_result.failed())
+                {
+                    let output = format!(
+                        "<div class=\"output\">{}</div>\n",
+                        html_escape::encode_text(output)
+                    );
+                    if self.interactive {
+                        html.push_str(&format!(
+                            "<details{}><summary>Output</summary>{}</details>\n",
+                            if test_result.failed() { " open" } else { "" },
+                            output
+                        ));
+                    } else {
+                        html.push_str(&output);
+                    }
                 }
+
+                if test_result.failed()
+                    && (!test_result.breadcrumbs.is_empty()
+                        || !test_result.logs.is_empty()
+                        || test_result.temp_dir.is_some())
+                {
+                    html.push_str(&Self::render_trail(test_result));
+                }
+
+                if !test_result.artifacts.is_empty() {
+                    html.push_str("<div class=\"artifacts\">\n");
+                    for artifact in &test_result.artifacts {
+                        html.push_str(&Self::render_artifact(artifact));
+                    }
+                    html.push_str("</div>\n");
+                }
+
+                html.push_str("</div>\n");
             }
 
             html.push_str("</div>\n");
         }
 
+        if self.show_timing && run_result.total_tests > 0 {
+            html.push_str(&Self::render_timing(&TimingAnalysis::new(
+                run_result,
+                self.slowest,
+            )));
+        }
+
+        if let Some(ref environment) = self.metadata.environment {
+            html.push_str(&Self::render_environment(environment));
+        }
+
         html.push_str("<div class=\"footer\">\n");
         html.push_str(&format!(
             "Generated by {} v{}\n",
@@ -282,18 +665,20 @@ impl Reporter for HtmlReporter {
         ));
         html.push_str("</div>\n");
 
+        if self.interactive {
+            html.push_str(Self::render_script());
+        }
+
         html.push_str("</body>\n</html>\n");
 
-        Ok(TestReport {
-            metadata: self.metadata.clone(),
-            run_result: run_result.clone(),
-            format: ReportFormat::Html,
-            content: html,
-            created_at: Utc::now(),
-        })
+        Ok(html)
     }
 
     fn format(&self) -> ReportFormat {
         ReportFormat::Html
     }
+
+    fn metadata(&self) -> &ReportMetadata {
+        &self.metadata
+    }
 }
@@ -1,5 +1,9 @@
 use super::*;
+use serde::Serialize;
 
+/// Writes the run results as JSON, with the report metadata (including the
+/// environment the run happened in) alongside them under `metadata`. The
+/// output still reads back as a [`RunResult`].
 pub struct JsonReporter {
     metadata: ReportMetadata,
     pretty: bool,
@@ -30,24 +34,47 @@ impl Default for JsonReporter {
     }
 }
 
+#[derive(Serialize)]
+struct JsonReport<'a> {
+    metadata: &'a ReportMetadata,
+    #[serde(flatten)]
+    run: &'a RunResult,
+}
+
 impl Reporter for JsonReporter {
-    fn generate(&self, run_result: &RunResult) -> Result<TestReport> {
+    fn render(&self, run_result: &RunResult) -> Result<String> {
+        let report = JsonReport {
+            metadata: &self.metadata,
+            run: run_result,
+        };
         let content = if self.pretty {
-            serde_json::to_string_pretty(run_result)?
+            serde_json::to_string_pretty(&report)?
         } else {
-            serde_json::to_string(run_result)?
+            serde_json::to_string(&report)?
         };
 
-        Ok(TestReport {
-            metadata: self.metadata.clone(),
-            run_result: run_result.clone(),
-            format: ReportFormat::Json,
-            content,
-            created_at: Utc::now(),
-        })
+        Ok(content)
+    }
+
+    fn write_to(&self, run_result: &RunResult, writer: &mut dyn Write) -> Result<()> {
+        let report = JsonReport {
+            metadata: &self.metadata,
+            run: run_result,
+        };
+        if self.pretty {
+            serde_json::to_writer_pretty(writer, &report)?;
+        } else {
+            serde_json::to_writer(writer, &report)?;
+        }
+
+        Ok(())
     }
 
     fn format(&self) -> ReportFormat {
         ReportFormat::Json
     }
+
+    fn metadata(&self) -> &ReportMetadata {
+        &self.metadata
+    }
 }
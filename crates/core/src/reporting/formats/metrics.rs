@@ -0,0 +1,219 @@
+use super::*;
+use crate::TestStatus;
+use crate::reporting::http;
+use indexmap::IndexMap;
+use std::fmt::Write as _;
+use std::time::Duration;
+
+/// Writes run metrics in the Prometheus/OpenMetrics text format, for
+/// dashboarding test health over time.
+///
+/// The report content can be written to a file for a node exporter's
+/// textfile collector, or sent to a Prometheus pushgateway with
+/// [`MetricsReporter::push`]. Metrics cover test counts by status (overall,
+/// per suite and per tag), flaky tests and run duration.
+pub struct MetricsReporter {
+    metadata: ReportMetadata,
+    prefix: String,
+    job: String,
+}
+
+impl MetricsReporter {
+    pub fn new() -> Self {
+        Self {
+            metadata: ReportMetadata::default(),
+            prefix: "sheila".to_string(),
+            job: "sheila".to_string(),
+        }
+    }
+
+    pub fn with_metadata(mut self, metadata: ReportMetadata) -> Self {
+        self.metadata = metadata;
+        self
+    }
+
+    /// Prefix for every metric name, `sheila` by default
+    pub fn prefix(mut self, prefix: impl Into<String>) -> Self {
+        self.prefix = prefix.into();
+        self
+    }
+
+    /// The pushgateway job metrics are grouped under, `sheila` by default
+    pub fn job(mut self, job: impl Into<String>) -> Self {
+        self.job = job.into();
+        self
+    }
+
+    /// Push the metrics for `run_result` to the pushgateway at `gateway`,
+    /// replacing any previously pushed for this job
+    pub fn push(&self, run_result: &RunResult, gateway: &str) -> Result<()> {
+        let url = format!("{}/metrics/job/{}", gateway.trim_end_matches('/'), self.job);
+        http::send(
+            "PUT",
+            &url,
+            "/",
+            "text/plain; version=0.0.4",
+            self.exposition(run_result).as_bytes(),
+            Duration::from_secs(10),
+        )
+    }
+
+    fn status_label(status: TestStatus) -> &'static str {
+        match status {
+            TestStatus::Passed | TestStatus::Cached => "passed",
+            TestStatus::Failed | TestStatus::Timeout => "failed",
+            TestStatus::XFail => "xfail",
+            TestStatus::XPass => "xpass",
+            TestStatus::Quarantined => "quarantined",
+            _ => "skipped",
+        }
+    }
+
+    /// The metrics for `run_result` in the text exposition format
+    fn exposition(&self, run_result: &RunResult) -> String {
+        let prefix = &self.prefix;
+        let mut suites: IndexMap<&str, IndexMap<&str, usize>> = IndexMap::new();
+        let mut tags: IndexMap<&str, IndexMap<&str, usize>> = IndexMap::new();
+
+        for suite_result in &run_result.suite_results {
+            let suite = suites.entry(suite_result.name.as_str()).or_default();
+            for test_result in &suite_result.test_results {
+                let status = Self::status_label(test_result.status);
+                *suite.entry(status).or_default() += 1;
+                for tag in &test_result.tags {
+                    *tags
+                        .entry(tag.as_str())
+                        .or_default()
+                        .entry(status)
+                        .or_default() += 1;
+                }
+            }
+        }
+
+        let mut out = String::new();
+
+        let _ = writeln!(
+            out,
+            "# HELP {prefix}_tests Tests in the last run by status."
+        );
+        let _ = writeln!(out, "# TYPE {prefix}_tests gauge");
+        for (status, count) in [
+            ("passed", run_result.passed_tests),
+            ("failed", run_result.failed_tests),
+            ("skipped", run_result.skipped_tests),
+        ] {
+            let _ = writeln!(out, "{prefix}_tests{{status=\"{status}\"}} {count}");
+        }
+
+        let _ = writeln!(
+            out,
+            "# HELP {prefix}_flaky_tests Tests that only passed after a retry."
+        );
+        let _ = writeln!(out, "# TYPE {prefix}_flaky_tests gauge");
+        let _ = writeln!(out, "{prefix}_flaky_tests {}", run_result.flaky_tests);
+
+        let _ = writeln!(
+            out,
+            "# HELP {prefix}_run_duration_seconds Wall time of the last run."
+        );
+        let _ = writeln!(out, "# TYPE {prefix}_run_duration_seconds gauge");
+        let _ = writeln!(
+            out,
+            "{prefix}_run_duration_seconds {}",
+            run_result.duration.unwrap_or_default().as_secs_f64()
+        );
+
+        let _ = writeln!(
+            out,
+            "# HELP {prefix}_run_timestamp_seconds When the last run started."
+        );
+        let _ = writeln!(out, "# TYPE {prefix}_run_timestamp_seconds gauge");
+        let _ = writeln!(
+            out,
+            "{prefix}_run_timestamp_seconds {}",
+            run_result.start_time.timestamp()
+        );
+
+        let _ = writeln!(
+            out,
+            "# HELP {prefix}_suite_tests Tests in the last run by suite and status."
+        );
+        let _ = writeln!(out, "# TYPE {prefix}_suite_tests gauge");
+        for (suite, counts) in suites.iter() {
+            for (status, count) in counts.iter() {
+                let _ = writeln!(
+                    out,
+                    "{prefix}_suite_tests{{suite=\"{}\",status=\"{status}\"}} {count}",
+                    escape_label(suite)
+                );
+            }
+        }
+
+        if !tags.is_empty() {
+            let _ = writeln!(
+                out,
+                "# HELP {prefix}_tag_tests Tests in the last run by tag and status."
+            );
+            let _ = writeln!(out, "# TYPE {prefix}_tag_tests gauge");
+            for (tag, counts) in tags.iter() {
+                for (status, count) in counts.iter() {
+                    let _ = writeln!(
+                        out,
+                        "{prefix}_tag_tests{{tag=\"{}\",status=\"{status}\"}} {count}",
+                        escape_label(tag)
+                    );
+                }
+            }
+        }
+
+        if let Some(ref environment) = self.metadata.environment {
+            let labels: Vec<String> = [
+                ("commit", environment.git_commit.as_deref()),
+                ("branch", environment.git_branch.as_deref()),
+                ("host", environment.hostname.as_deref()),
+                ("os", Some(environment.os.as_str())),
+                ("arch", Some(environment.arch.as_str())),
+                ("rustc", environment.rustc_version.as_deref()),
+                ("ci", environment.ci.as_deref()),
+            ]
+            .into_iter()
+            .filter_map(|(name, value)| Some(format!("{name}=\"{}\"", escape_label(value?))))
+            .collect();
+
+            let _ = writeln!(out, "# HELP {prefix}_run_info Where the last run happened.");
+            let _ = writeln!(out, "# TYPE {prefix}_run_info gauge");
+            let _ = writeln!(out, "{prefix}_run_info{{{}}} 1", labels.join(","));
+        }
+
+        out.push_str("# EOF\n");
+        out
+    }
+}
+
+/// Escape a label value as the exposition format requires
+fn escape_label(value: &str) -> String {
+    value
+        .replace('\\', "\\\\")
+        .replace('"', "\\\"")
+        .replace('\n', "\\n")
+}
+
+impl Default for MetricsReporter {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl Reporter for MetricsReporter {
+    fn render(&self, run_result: &RunResult) -> Result<String> {
+        Ok(self.exposition(run_result))
+    }
+
+    fn format(&self) -> ReportFormat {
+        ReportFormat::Metrics
+    }
+
+    fn metadata(&self) -> &ReportMetadata {
+        &self.metadata
+    }
+}
@@ -1,3 +1,13 @@
+#[cfg(feature = "allure")]
+pub mod allure;
+#[cfg(feature = "allure")]
+pub use allure::*;
+
+#[cfg(feature = "badge")]
+pub mod badge;
+#[cfg(feature = "badge")]
+pub use badge::*;
+
 #[cfg(feature = "csv")]
 pub mod csv;
 #[cfg(feature = "csv")]
@@ -13,6 +23,29 @@ pub mod json;
 #[cfg(feature = "json")]
 pub use json::*;
 
+#[cfg(feature = "metrics")]
+pub mod metrics;
+#[cfg(feature = "metrics")]
+pub use metrics::*;
+
+#[cfg(feature = "otel")]
+pub mod otel;
+#[cfg(feature = "otel")]
+pub use otel::*;
+
+#[cfg(feature = "sarif")]
+pub mod sarif;
+#[cfg(feature = "sarif")]
+pub use sarif::*;
+
+#[cfg(feature = "templates")]
+pub mod template;
+#[cfg(feature = "templates")]
+pub use template::*;
+
+pub mod trend;
+pub use trend::*;
+
 use super::*;
 
 pub struct CompositeReporter {
@@ -31,11 +64,17 @@ impl CompositeReporter {
         self
     }
 
+    /// Generate a report from every reporter. The run results are copied
+    /// once and shared between the reports.
     pub fn generate_all(&self, run_result: &RunResult) -> Result<Vec<TestReport>> {
+        self.generate_all_shared(Arc::new(run_result.clone()))
+    }
+
+    pub fn generate_all_shared(&self, run_result: Arc<RunResult>) -> Result<Vec<TestReport>> {
         let mut reports = Vec::new();
 
         for reporter in &self.reporters {
-            reports.push(reporter.generate(run_result)?);
+            reports.push(reporter.generate_shared(Arc::clone(&run_result))?);
         }
 
         Ok(reports)
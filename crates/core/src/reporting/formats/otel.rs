@@ -0,0 +1,351 @@
+use super::*;
+use crate::reporting::http;
+use crate::suite::SuiteResult;
+use crate::test::TestResult;
+use crate::{TestStatus, stable_hash};
+use chrono::{DateTime, Duration as ChronoDuration};
+use serde_json::{Value, json};
+use std::time::Duration;
+
+const SPAN_KIND_INTERNAL: u8 = 1;
+const STATUS_UNSET: u8 = 0;
+const STATUS_OK: u8 = 1;
+const STATUS_ERROR: u8 = 2;
+
+/// Exports a run as OpenTelemetry spans, so test execution shows up in
+/// existing tracing backends.
+///
+/// The run is the root span, with a child span per suite and a grandchild per
+/// test. Test spans carry the test's status, tags and retry count as
+/// attributes, and failures are recorded as `exception` events. The report
+/// content is the OTLP/JSON trace payload, and [`OtelExporter::export`] sends
+/// it to an OTLP/HTTP collector.
+pub struct OtelExporter {
+    metadata: ReportMetadata,
+    endpoint: String,
+    service_name: String,
+    timeout: Duration,
+}
+
+impl OtelExporter {
+    /// Export to the collector at `endpoint`, e.g. `http://localhost:4318`.
+    /// `/v1/traces` is appended unless the endpoint already names a path.
+    pub fn new(endpoint: impl Into<String>) -> Self {
+        Self {
+            metadata: ReportMetadata::default(),
+            endpoint: endpoint.into(),
+            service_name: "sheila".to_string(),
+            timeout: Duration::from_secs(10),
+        }
+    }
+
+    pub fn with_metadata(mut self, metadata: ReportMetadata) -> Self {
+        self.metadata = metadata;
+        self
+    }
+
+    /// The `service.name` resource attribute spans are reported under
+    pub fn service_name(mut self, name: impl Into<String>) -> Self {
+        self.service_name = name.into();
+        self
+    }
+
+    pub fn timeout(mut self, timeout: Duration) -> Self {
+        self.timeout = timeout;
+        self
+    }
+
+    /// Send the spans for `run_result` to the collector.
+    ///
+    /// Only plain `http://` endpoints are supported; point this at a local
+    /// collector to forward to backends that need TLS.
+    pub fn export(&self, run_result: &RunResult) -> Result<()> {
+        let body = serde_json::to_vec(&self.payload(run_result))?;
+        http::send(
+            "POST",
+            &self.endpoint,
+            "/v1/traces",
+            "application/json",
+            &body,
+            self.timeout,
+        )
+    }
+
+    /// The OTLP/JSON `ExportTraceServiceRequest` for `run_result`
+    fn payload(&self, run_result: &RunResult) -> Value {
+        let trace_id = run_result.id.simple().to_string();
+        let run_span_id = span_id(run_result.id.as_bytes());
+
+        let mut spans = vec![span(
+            &trace_id,
+            &run_span_id,
+            None,
+            &self.metadata.title,
+            run_result.start_time,
+            run_result.end_time.unwrap_or(run_result.start_time),
+            vec![
+                attribute(
+                    "test.run.id",
+                    json!({ "stringValue": run_result.id.to_string() }),
+                ),
+                int_attribute("test.run.total", run_result.total_tests),
+                int_attribute("test.run.passed", run_result.passed_tests),
+                int_attribute("test.run.failed", run_result.failed_tests),
+                int_attribute("test.run.skipped", run_result.skipped_tests),
+            ],
+            Vec::new(),
+            if run_result.all_passed() {
+                (STATUS_OK, None)
+            } else {
+                (
+                    STATUS_ERROR,
+                    run_result.error.as_ref().map(|e| e.to_string()),
+                )
+            },
+        )];
+
+        for suite_result in &run_result.suite_results {
+            let suite_span_id = span_id(suite_result.id.as_bytes());
+            spans.push(Self::suite_span(
+                &trace_id,
+                &suite_span_id,
+                &run_span_id,
+                suite_result,
+            ));
+
+            for test_result in &suite_result.test_results {
+                spans.push(Self::test_span(
+                    &trace_id,
+                    &suite_span_id,
+                    suite_result,
+                    test_result,
+                ));
+            }
+        }
+
+        let mut resource = vec![
+            attribute("service.name", json!({ "stringValue": self.service_name })),
+            attribute(
+                "telemetry.sdk.name",
+                json!({ "stringValue": self.metadata.generator }),
+            ),
+            attribute(
+                "telemetry.sdk.version",
+                json!({ "stringValue": self.metadata.version }),
+            ),
+        ];
+        if let Some(ref environment) = self.metadata.environment {
+            resource.extend(Self::environment_attributes(environment));
+        }
+
+        json!({
+            "resourceSpans": [{
+                "resource": { "attributes": resource },
+                "scopeSpans": [{
+                    "scope": { "name": self.metadata.generator, "version": self.metadata.version },
+                    "spans": spans,
+                }]
+            }]
+        })
+    }
+
+    /// Resource attributes for where the run happened, using the semantic
+    /// convention names where there are some
+    fn environment_attributes(environment: &Environment) -> Vec<Value> {
+        let string = |key: &str, value: &str| attribute(key, json!({ "stringValue": value }));
+
+        let mut attributes = vec![
+            string("os.type", &environment.os),
+            string("host.arch", &environment.arch),
+        ];
+        if let Some(ref hostname) = environment.hostname {
+            attributes.push(string("host.name", hostname));
+        }
+        if let Some(ref commit) = environment.git_commit {
+            attributes.push(string("vcs.ref.head.revision", commit));
+        }
+        if let Some(ref branch) = environment.git_branch {
+            attributes.push(string("vcs.ref.head.name", branch));
+        }
+        if let Some(ref rustc) = environment.rustc_version {
+            attributes.push(string("rustc.version", rustc));
+        }
+        if let Some(ref ci) = environment.ci {
+            attributes.push(string("ci.provider", ci));
+        }
+        for (name, value) in &environment.ci_env {
+            attributes.push(string(&format!("ci.env.{}", name), value));
+        }
+        attributes
+    }
+
+    fn suite_span(
+        trace_id: &str,
+        span_id: &str,
+        parent_id: &str,
+        suite_result: &SuiteResult,
+    ) -> Value {
+        span(
+            trace_id,
+            span_id,
+            Some(parent_id),
+            &suite_result.name,
+            suite_result.start_time,
+            suite_result.end_time.unwrap_or(suite_result.start_time),
+            vec![
+                attribute(
+                    "test.suite.name",
+                    json!({ "stringValue": suite_result.name }),
+                ),
+                int_attribute("test.suite.total", suite_result.total_tests),
+                int_attribute("test.suite.failed", suite_result.failed_tests),
+            ],
+            Vec::new(),
+            if suite_result.all_passed() {
+                (STATUS_OK, None)
+            } else {
+                (
+                    STATUS_ERROR,
+                    suite_result.error.as_ref().map(|e| e.to_string()),
+                )
+            },
+        )
+    }
+
+    fn test_span(
+        trace_id: &str,
+        parent_id: &str,
+        suite_result: &SuiteResult,
+        test_result: &TestResult,
+    ) -> Value {
+        // Results parsed from libtest output are created once the test has
+        // finished, so work back from the end using the reported duration
+        let end = test_result.end_time.unwrap_or(test_result.start_time);
+        let start = test_result
+            .duration
+            .and_then(|duration| ChronoDuration::from_std(duration).ok())
+            .map_or(end, |duration| end - duration);
+
+        let mut attributes = vec![
+            attribute(
+                "test.case.name",
+                json!({ "stringValue": test_result.qualified_name() }),
+            ),
+            attribute(
+                "test.suite.name",
+                json!({ "stringValue": suite_result.name }),
+            ),
+            attribute(
+                "test.case.result.status",
+                json!({ "stringValue": test_result.status.to_string() }),
+            ),
+            int_attribute("test.case.retry_count", test_result.retry_count as usize),
+            attribute("test.case.flaky", json!({ "boolValue": test_result.flaky })),
+        ];
+        if !test_result.tags.is_empty() {
+            let tags: Vec<Value> = test_result
+                .tags
+                .iter()
+                .map(|tag| json!({ "stringValue": tag }))
+                .collect();
+            attributes.push(attribute(
+                "test.case.tags",
+                json!({ "arrayValue": { "values": tags } }),
+            ));
+        }
+
+        let events = test_result
+            .error
+            .iter()
+            .map(|error| {
+                json!({
+                    "timeUnixNano": unix_nanos(end),
+                    "name": "exception",
+                    "attributes": [
+                        attribute("exception.type", json!({ "stringValue": format!("{:?}", error.kind()) })),
+                        attribute("exception.message", json!({ "stringValue": error.to_string() })),
+                    ],
+                })
+            })
+            .collect();
+
+        let status = match test_result.status {
+            TestStatus::Passed | TestStatus::Cached | TestStatus::XFail => (STATUS_OK, None),
+            TestStatus::Failed | TestStatus::Timeout | TestStatus::XPass => (
+                STATUS_ERROR,
+                test_result.error.as_ref().map(|e| e.to_string()),
+            ),
+            _ => (STATUS_UNSET, None),
+        };
+
+        span(
+            trace_id,
+            &span_id(test_result.id.as_bytes()),
+            Some(parent_id),
+            &test_result.name,
+            start,
+            end,
+            attributes,
+            events,
+            status,
+        )
+    }
+}
+
+impl Reporter for OtelExporter {
+    fn render(&self, run_result: &RunResult) -> Result<String> {
+        Ok(serde_json::to_string_pretty(&self.payload(run_result))?)
+    }
+
+    fn format(&self) -> ReportFormat {
+        ReportFormat::Otel
+    }
+
+    fn metadata(&self) -> &ReportMetadata {
+        &self.metadata
+    }
+}
+
+/// A 64-bit span id, derived from the id of the run, suite or test it covers
+fn span_id(bytes: &[u8]) -> String {
+    format!("{:016x}", stable_hash(bytes))
+}
+
+fn unix_nanos(time: DateTime<Utc>) -> String {
+    // OTLP/JSON encodes 64-bit integers as strings
+    time.timestamp_nanos_opt().unwrap_or_default().to_string()
+}
+
+fn attribute(key: &str, value: Value) -> Value {
+    json!({ "key": key, "value": value })
+}
+
+fn int_attribute(key: &str, value: usize) -> Value {
+    attribute(key, json!({ "intValue": value.to_string() }))
+}
+
+#[allow(clippy::too_many_arguments)]
+fn span(
+    trace_id: &str,
+    span_id: &str,
+    parent_id: Option<&str>,
+    name: &str,
+    start: DateTime<Utc>,
+    end: DateTime<Utc>,
+    attributes: Vec<Value>,
+    events: Vec<Value>,
+    (code, message): (u8, Option<String>),
+) -> Value {
+    json!({
+        "traceId": trace_id,
+        "spanId": span_id,
+        "parentSpanId": parent_id.unwrap_or_default(),
+        "name": name,
+        "kind": SPAN_KIND_INTERNAL,
+        "startTimeUnixNano": unix_nanos(start),
+        "endTimeUnixNano": unix_nanos(end),
+        "attributes": attributes,
+        "events": events,
+        "status": { "code": code, "message": message.unwrap_or_default() },
+    })
+}
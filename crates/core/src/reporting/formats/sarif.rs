@@ -0,0 +1,246 @@
+use super::*;
+use crate::SourceLocation;
+use crate::test::TestResult;
+use serde::Serialize;
+
+const SARIF_SCHEMA: &str = "https://json.schemastore.org/sarif-2.1.0.json";
+const SARIF_VERSION: &str = "2.1.0";
+
+/// Writes failed tests as [SARIF](https://sarifweb.azurewebsites.net) results,
+/// so failures show up in code scanning UIs and editors that read SARIF.
+///
+/// Each failed test is a rule, and its result points at where the test
+/// panicked, falling back to where the test is declared when no panic
+/// location was reported. Paths relative to the workspace root are resolved
+/// against `%SRCROOT%`.
+pub struct SarifReporter {
+    metadata: ReportMetadata,
+}
+
+impl SarifReporter {
+    pub fn new() -> Self {
+        Self {
+            metadata: ReportMetadata::default(),
+        }
+    }
+
+    pub fn with_metadata(mut self, metadata: ReportMetadata) -> Self {
+        self.metadata = metadata;
+        self
+    }
+
+    fn location(test_result: &TestResult) -> Option<SarifLocation> {
+        let error_location = test_result
+            .error
+            .as_ref()
+            .and_then(|error| error.details().location.clone());
+        let location = test_result
+            .location
+            .clone()
+            .or(error_location)
+            .or_else(|| {
+                let file = test_result.meta.file.clone()?;
+                Some(SourceLocation {
+                    file,
+                    line: test_result.meta.line.unwrap_or(1),
+                    column: 0,
+                })
+            })?;
+
+        let path = location.file.replace('\\', "/");
+        let artifact_location = if Path::new(&location.file).is_absolute() {
+            SarifArtifactLocation {
+                uri: format!("file://{}", path),
+                uri_base_id: None,
+            }
+        } else {
+            SarifArtifactLocation {
+                uri: path.trim_start_matches("./").to_string(),
+                uri_base_id: Some("%SRCROOT%"),
+            }
+        };
+
+        Some(SarifLocation {
+            physical_location: SarifPhysicalLocation {
+                artifact_location,
+                region: SarifRegion {
+                    start_line: location.line.max(1),
+                    start_column: (location.column > 0).then_some(location.column),
+                },
+            },
+        })
+    }
+
+    fn log(&self, run_result: &RunResult) -> SarifLog {
+        let mut rules = Vec::new();
+        let mut results = Vec::new();
+
+        let failures = run_result
+            .suite_results
+            .iter()
+            .flat_map(|suite_result| suite_result.test_results.iter())
+            .filter(|test_result| test_result.failed());
+
+        for test_result in failures {
+            let rule_id = test_result.qualified_name().to_string();
+            let message = test_result
+                .error
+                .as_ref()
+                .map(|error| error.to_string())
+                .unwrap_or_else(|| format!("{} {}", test_result.name, test_result.status));
+
+            results.push(SarifResult {
+                rule_id: rule_id.clone(),
+                rule_index: rules.len(),
+                level: "error",
+                message: SarifMessage { text: message },
+                locations: Self::location(test_result).into_iter().collect(),
+            });
+            rules.push(SarifRule {
+                id: rule_id,
+                name: test_result.name.clone(),
+                short_description: SarifMessage {
+                    text: test_result
+                        .meta
+                        .description
+                        .clone()
+                        .unwrap_or_else(|| format!("Test {} failed", test_result.name)),
+                },
+            });
+        }
+
+        SarifLog {
+            schema: SARIF_SCHEMA,
+            version: SARIF_VERSION,
+            runs: vec![SarifRun {
+                tool: SarifTool {
+                    driver: SarifDriver {
+                        name: self.metadata.generator.clone(),
+                        version: self.metadata.version.clone(),
+                        rules,
+                    },
+                },
+                invocations: vec![SarifInvocation {
+                    execution_successful: run_result.error.is_none(),
+                    machine: self
+                        .metadata
+                        .environment
+                        .as_ref()
+                        .and_then(|environment| environment.hostname.clone()),
+                    properties: self.metadata.environment.clone(),
+                }],
+                results,
+            }],
+        }
+    }
+}
+
+impl Default for SarifReporter {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl Reporter for SarifReporter {
+    fn render(&self, run_result: &RunResult) -> Result<String> {
+        Ok(serde_json::to_string_pretty(&self.log(run_result))?)
+    }
+
+    fn format(&self) -> ReportFormat {
+        ReportFormat::Sarif
+    }
+
+    fn metadata(&self) -> &ReportMetadata {
+        &self.metadata
+    }
+}
+
+#[derive(Debug, Serialize)]
+struct SarifLog {
+    #[serde(rename = "$schema")]
+    schema: &'static str,
+    version: &'static str,
+    runs: Vec<SarifRun>,
+}
+
+#[derive(Debug, Serialize)]
+struct SarifRun {
+    tool: SarifTool,
+    invocations: Vec<SarifInvocation>,
+    results: Vec<SarifResult>,
+}
+
+/// The run itself, with the environment it ran in as its property bag
+#[derive(Debug, Serialize)]
+#[serde(rename_all = "camelCase")]
+struct SarifInvocation {
+    execution_successful: bool,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    machine: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    properties: Option<Environment>,
+}
+
+#[derive(Debug, Serialize)]
+struct SarifTool {
+    driver: SarifDriver,
+}
+
+#[derive(Debug, Serialize)]
+struct SarifDriver {
+    name: String,
+    version: String,
+    rules: Vec<SarifRule>,
+}
+
+#[derive(Debug, Serialize)]
+#[serde(rename_all = "camelCase")]
+struct SarifRule {
+    id: String,
+    name: String,
+    short_description: SarifMessage,
+}
+
+#[derive(Debug, Serialize)]
+#[serde(rename_all = "camelCase")]
+struct SarifResult {
+    rule_id: String,
+    rule_index: usize,
+    level: &'static str,
+    message: SarifMessage,
+    locations: Vec<SarifLocation>,
+}
+
+#[derive(Debug, Serialize)]
+struct SarifMessage {
+    text: String,
+}
+
+#[derive(Debug, Serialize)]
+#[serde(rename_all = "camelCase")]
+struct SarifLocation {
+    physical_location: SarifPhysicalLocation,
+}
+
+#[derive(Debug, Serialize)]
+#[serde(rename_all = "camelCase")]
+struct SarifPhysicalLocation {
+    artifact_location: SarifArtifactLocation,
+    region: SarifRegion,
+}
+
+#[derive(Debug, Serialize)]
+#[serde(rename_all = "camelCase")]
+struct SarifArtifactLocation {
+    uri: String,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    uri_base_id: Option<&'static str>,
+}
+
+#[derive(Debug, Serialize)]
+#[serde(rename_all = "camelCase")]
+struct SarifRegion {
+    start_line: u32,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    start_column: Option<u32>,
+}
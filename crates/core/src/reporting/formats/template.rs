@@ -0,0 +1,104 @@
+use super::*;
+use crate::Error;
+use tera::{Context, Tera};
+
+const TEMPLATE_NAME: &str = "report";
+
+/// Renders a report from a user-supplied [Tera](https://keats.github.io/tera/)
+/// template, for bespoke formats that don't warrant a [`Reporter`] impl.
+///
+/// Templates are given the whole run as context:
+///
+/// - `run`: the serialized [`RunResult`], with its suites and tests
+/// - `metadata`: the report's [`ReportMetadata`]
+/// - `success_rate`: the run's pass rate as a percentage
+///
+/// ```text
+/// {{ metadata.title }}: {{ run.passed_tests }}/{{ run.total_tests }} passed
+/// {% for suite in run.suite_results %}{% for test in suite.test_results %}
+/// - {{ test.name }}: {{ test.status }}{% endfor %}{% endfor %}
+/// ```
+pub struct TemplateReporter {
+    metadata: ReportMetadata,
+    template: String,
+    escape_html: bool,
+}
+
+impl TemplateReporter {
+    pub fn new(template: impl Into<String>) -> Self {
+        Self {
+            metadata: ReportMetadata::default(),
+            template: template.into(),
+            escape_html: false,
+        }
+    }
+
+    /// Load the template from `path`. Templates with an `.html`/`.htm`/`.xml`
+    /// extension have their values HTML-escaped.
+    pub fn from_file(path: &Path) -> Result<Self> {
+        let template = std::fs::read_to_string(path).map_err(|e| {
+            Error::generic(format!(
+                "Failed to read report template {}: {}",
+                path.display(),
+                e
+            ))
+        })?;
+        let escape_html = matches!(
+            path.extension().and_then(|ext| ext.to_str()),
+            Some("html" | "htm" | "xml")
+        );
+
+        Ok(Self::new(template).escape_html(escape_html))
+    }
+
+    pub fn with_metadata(mut self, metadata: ReportMetadata) -> Self {
+        self.metadata = metadata;
+        self
+    }
+
+    /// HTML-escape values inserted into the template
+    pub fn escape_html(mut self, escape: bool) -> Self {
+        self.escape_html = escape;
+        self
+    }
+}
+
+/// Tera keeps the useful part of an error (the line and what went wrong) in
+/// its source chain, so flatten the whole chain into the message
+fn template_error(err: tera::Error) -> Error {
+    let mut message = err.to_string();
+    let mut source = std::error::Error::source(&err);
+    while let Some(cause) = source {
+        message.push_str(&format!(": {}", cause));
+        source = cause.source();
+    }
+    Error::generic(format!("Report template error: {}", message))
+}
+
+impl Reporter for TemplateReporter {
+    fn render(&self, run_result: &RunResult) -> Result<String> {
+        let mut tera = Tera::default();
+        tera.autoescape_on(if self.escape_html {
+            vec![TEMPLATE_NAME]
+        } else {
+            vec![]
+        });
+        tera.add_raw_template(TEMPLATE_NAME, &self.template)
+            .map_err(template_error)?;
+
+        let mut context = Context::new();
+        context.insert("run", run_result);
+        context.insert("metadata", &self.metadata);
+        context.insert("success_rate", &(run_result.success_rate() * 100.0));
+
+        tera.render(TEMPLATE_NAME, &context).map_err(template_error)
+    }
+
+    fn format(&self) -> ReportFormat {
+        ReportFormat::Template
+    }
+
+    fn metadata(&self) -> &ReportMetadata {
+        &self.metadata
+    }
+}
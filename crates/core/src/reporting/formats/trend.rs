@@ -0,0 +1,296 @@
+use super::*;
+use crate::cache::RunSummary;
+use std::collections::HashMap;
+
+const SPARK_BARS: [char; 8] = ['▁', '▂', '▃', '▄', '▅', '▆', '▇', '█'];
+
+/// Renders how pass rate, duration and flakiness have moved over the last
+/// runs, from the summaries kept in the run history.
+///
+/// The run being reported is added to the end of the history if it isn't
+/// already the latest entry. Text reports show a table with sparklines;
+/// HTML reports (with the `html` feature) draw the trends as inline SVG.
+pub struct TrendReporter {
+    metadata: ReportMetadata,
+    history: Vec<RunSummary>,
+    limit: usize,
+    #[cfg_attr(not(feature = "html"), allow(dead_code))]
+    html: bool,
+}
+
+impl TrendReporter {
+    pub fn new(history: Vec<RunSummary>) -> Self {
+        Self {
+            metadata: ReportMetadata {
+                title: "Test Trends".to_string(),
+                ..ReportMetadata::default()
+            },
+            history,
+            limit: 20,
+            html: false,
+        }
+    }
+
+    pub fn with_metadata(mut self, metadata: ReportMetadata) -> Self {
+        self.metadata = metadata;
+        self
+    }
+
+    /// How many of the most recent runs to show
+    pub fn limit(mut self, limit: usize) -> Self {
+        self.limit = limit.max(1);
+        self
+    }
+
+    #[cfg(feature = "html")]
+    pub fn html(mut self, html: bool) -> Self {
+        self.html = html;
+        self
+    }
+
+    /// The runs to report on, oldest first
+    fn runs(&self, run_result: &RunResult) -> Vec<RunSummary> {
+        let mut runs = self.history.clone();
+        if runs.last().is_none_or(|last| last.id != run_result.id) {
+            runs.push(RunSummary::from(run_result));
+        }
+        let skip = runs.len().saturating_sub(self.limit);
+        runs.into_iter().skip(skip).collect()
+    }
+
+    fn format_duration(duration: Option<std::time::Duration>) -> String {
+        match duration {
+            Some(duration) if duration.as_millis() < 1000 => format!("{}ms", duration.as_millis()),
+            Some(duration) => format!("{:.2}s", duration.as_secs_f64()),
+            None => "-".to_string(),
+        }
+    }
+
+    fn sparkline(values: &[f64]) -> String {
+        let max = values.iter().cloned().fold(0.0, f64::max);
+        let min = values.iter().cloned().fold(max, f64::min);
+        values
+            .iter()
+            .map(|value| {
+                if max == min {
+                    return SPARK_BARS[SPARK_BARS.len() / 2];
+                }
+                let scaled = (value - min) / (max - min) * (SPARK_BARS.len() - 1) as f64;
+                SPARK_BARS[scaled.round() as usize]
+            })
+            .collect()
+    }
+
+    /// Tests that failed or were flaky most often across `runs`, with how
+    /// many runs each one failed in
+    fn frequent_failures(runs: &[RunSummary]) -> Vec<(String, usize)> {
+        let mut counts: HashMap<&str, usize> = HashMap::new();
+        for run in runs {
+            for test in run.failures.iter() {
+                *counts.entry(test).or_default() += 1;
+            }
+        }
+
+        let mut failures: Vec<(String, usize)> = counts
+            .into_iter()
+            .map(|(test, count)| (test.to_string(), count))
+            .collect();
+        failures.sort_by(|a, b| b.1.cmp(&a.1).then_with(|| a.0.cmp(&b.0)));
+        failures.truncate(10);
+        failures
+    }
+
+    fn generate_text(&self, runs: &[RunSummary]) -> String {
+        let mut content = String::new();
+
+        content.push_str(&format!("# {}\n\n", self.metadata.title));
+        content.push_str(&format!("Last {} run(s)\n\n", runs.len()));
+
+        content.push_str(&format!(
+            "{:<20} {:>6} {:>7} {:>7} {:>6} {:>10} {:>10}\n",
+            "Run", "Tests", "Passed", "Failed", "Flaky", "Pass rate", "Duration"
+        ));
+        for run in runs {
+            content.push_str(&format!(
+                "{:<20} {:>6} {:>7} {:>7} {:>6} {:>9.1}% {:>10}\n",
+                run.start_time.format("%Y-%m-%d %H:%M:%S"),
+                run.total_tests,
+                run.passed_tests,
+                run.failed_tests,
+                run.flaky_tests,
+                run.pass_rate() * 100.0,
+                Self::format_duration(run.duration)
+            ));
+        }
+
+        let pass_rates: Vec<f64> = runs.iter().map(RunSummary::pass_rate).collect();
+        let durations: Vec<f64> = runs
+            .iter()
+            .map(|run| run.duration.unwrap_or_default().as_secs_f64())
+            .collect();
+        let flaky: Vec<f64> = runs.iter().map(|run| run.flaky_tests as f64).collect();
+
+        content.push_str("\n## Trends\n\n");
+        content.push_str(&format!("Pass rate  {}\n", Self::sparkline(&pass_rates)));
+        content.push_str(&format!("Duration   {}\n", Self::sparkline(&durations)));
+        content.push_str(&format!("Flaky      {}\n", Self::sparkline(&flaky)));
+
+        let failures = Self::frequent_failures(runs);
+        if !failures.is_empty() {
+            content.push_str("\n## Most frequent failures\n\n");
+            for (test, count) in failures {
+                content.push_str(&format!("{:>3}/{} runs  {}\n", count, runs.len(), test));
+            }
+        }
+
+        content
+    }
+
+    /// An SVG line chart of `values`, scaled to fit
+    /// An inline SVG line through `values`, scaled to fit
+    #[cfg(feature = "html")]
+    pub fn svg_sparkline(values: &[f64], color: &str) -> String {
+        const WIDTH: f64 = 300.0;
+        const HEIGHT: f64 = 40.0;
+
+        let max = values.iter().cloned().fold(0.0, f64::max);
+        let min = values.iter().cloned().fold(max, f64::min);
+        let step = if values.len() > 1 {
+            WIDTH / (values.len() - 1) as f64
+        } else {
+            0.0
+        };
+
+        let points: Vec<String> = values
+            .iter()
+            .enumerate()
+            .map(|(index, value)| {
+                let y = if max == min {
+                    HEIGHT / 2.0
+                } else {
+                    HEIGHT - (value - min) / (max - min) * HEIGHT
+                };
+                format!("{:.1},{:.1}", index as f64 * step, y)
+            })
+            .collect();
+
+        format!(
+            "<svg width=\"{}\" height=\"{}\" viewBox=\"-2 -2 {} {}\"><polyline fill=\"none\" stroke=\"{}\" stroke-width=\"2\" points=\"{}\"/></svg>",
+            WIDTH,
+            HEIGHT,
+            WIDTH + 4.0,
+            HEIGHT + 4.0,
+            color,
+            points.join(" ")
+        )
+    }
+
+    #[cfg(feature = "html")]
+    fn generate_html(&self, runs: &[RunSummary]) -> String {
+        let mut html = String::new();
+
+        html.push_str("<!DOCTYPE html>\n<html>\n<head>\n");
+        html.push_str("<meta charset=\"utf-8\">\n");
+        html.push_str(&format!(
+            "<title>{}</title>\n",
+            html_escape::encode_text(&self.metadata.title)
+        ));
+        html.push_str(
+            r#"<style>
+            body { font-family: 'Segoe UI', Tahoma, Geneva, Verdana, sans-serif; margin: 20px; background: #f8f9fa; }
+            .trend { background: white; border-radius: 8px; padding: 15px 20px; margin-bottom: 15px; box-shadow: 0 2px 4px rgba(0,0,0,0.1); }
+            .trend-title { font-weight: bold; margin-bottom: 8px; }
+            table { border-collapse: collapse; background: white; width: 100%; }
+            th, td { padding: 6px 12px; border-bottom: 1px solid #eee; text-align: right; }
+            th:first-child, td:first-child { text-align: left; }
+        </style>"#,
+        );
+        html.push_str("\n</head>\n<body>\n");
+        html.push_str(&format!(
+            "<h1>{}</h1>\n<p>Last {} run(s)</p>\n",
+            html_escape::encode_text(&self.metadata.title),
+            runs.len()
+        ));
+
+        let pass_rates: Vec<f64> = runs.iter().map(RunSummary::pass_rate).collect();
+        let durations: Vec<f64> = runs
+            .iter()
+            .map(|run| run.duration.unwrap_or_default().as_secs_f64())
+            .collect();
+        let flaky: Vec<f64> = runs.iter().map(|run| run.flaky_tests as f64).collect();
+
+        for (title, values, color) in [
+            ("Pass rate", &pass_rates, "#28a745"),
+            ("Duration", &durations, "#007bff"),
+            ("Flaky tests", &flaky, "#ffc107"),
+        ] {
+            html.push_str(&format!(
+                "<div class=\"trend\"><div class=\"trend-title\">{}</div>{}</div>\n",
+                title,
+                Self::svg_sparkline(values, color)
+            ));
+        }
+
+        html.push_str("<table>\n<tr><th>Run</th><th>Tests</th><th>Passed</th><th>Failed</th><th>Flaky</th><th>Pass rate</th><th>Duration</th></tr>\n");
+        for run in runs.iter().rev() {
+            html.push_str(&format!(
+                "<tr><td>{}</td><td>{}</td><td>{}</td><td>{}</td><td>{}</td><td>{:.1}%</td><td>{}</td></tr>\n",
+                run.start_time.format("%Y-%m-%d %H:%M:%S"),
+                run.total_tests,
+                run.passed_tests,
+                run.failed_tests,
+                run.flaky_tests,
+                run.pass_rate() * 100.0,
+                Self::format_duration(run.duration)
+            ));
+        }
+        html.push_str("</table>\n");
+
+        let failures = Self::frequent_failures(runs);
+        if !failures.is_empty() {
+            html.push_str("<h2>Most frequent failures</h2>\n<table>\n");
+            for (test, count) in failures {
+                html.push_str(&format!(
+                    "<tr><td>{}</td><td>{}/{} runs</td></tr>\n",
+                    html_escape::encode_text(&test),
+                    count,
+                    runs.len()
+                ));
+            }
+            html.push_str("</table>\n");
+        }
+
+        html.push_str("</body>\n</html>\n");
+        html
+    }
+}
+
+impl Reporter for TrendReporter {
+    fn render(&self, run_result: &RunResult) -> Result<String> {
+        let runs = self.runs(run_result);
+
+        #[cfg(feature = "html")]
+        let content = if self.html {
+            self.generate_html(&runs)
+        } else {
+            self.generate_text(&runs)
+        };
+        #[cfg(not(feature = "html"))]
+        let content = self.generate_text(&runs);
+
+        Ok(content)
+    }
+
+    fn format(&self) -> ReportFormat {
+        #[cfg(feature = "html")]
+        if self.html {
+            return ReportFormat::Html;
+        }
+
+        ReportFormat::Text
+    }
+
+    fn metadata(&self) -> &ReportMetadata {
+        &self.metadata
+    }
+}
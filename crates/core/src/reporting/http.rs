@@ -0,0 +1,82 @@
+//! A minimal blocking HTTP/1.1 client for reporters that push results to a
+//! collector, so they don't need a full HTTP stack. Only plain `http://`
+//! URLs are supported.
+
+use crate::{Error, Result};
+use std::io::{Read, Write};
+use std::net::{TcpStream, ToSocketAddrs};
+use std::time::Duration;
+
+/// Send `body` to `url` with `method`, failing unless the server responds
+/// with a 2xx status. `default_path` is used when `url` has no path.
+pub(crate) fn send(
+    method: &str,
+    url: &str,
+    default_path: &str,
+    content_type: &str,
+    body: &[u8],
+    timeout: Duration,
+) -> Result<()> {
+    let (host, port, path) = parse_url(url, default_path)?;
+
+    let addr = (host.as_str(), port)
+        .to_socket_addrs()?
+        .next()
+        .ok_or_else(|| Error::generic(format!("Could not resolve {}", host)))?;
+    let mut stream = TcpStream::connect_timeout(&addr, timeout)?;
+    stream.set_read_timeout(Some(timeout))?;
+    stream.set_write_timeout(Some(timeout))?;
+
+    write!(
+        stream,
+        "{} {} HTTP/1.1\r\nHost: {}:{}\r\nContent-Type: {}\r\nContent-Length: {}\r\nConnection: close\r\n\r\n",
+        method,
+        path,
+        host,
+        port,
+        content_type,
+        body.len()
+    )?;
+    stream.write_all(body)?;
+
+    let mut response = String::new();
+    stream.read_to_string(&mut response)?;
+    let status_line = response.lines().next().unwrap_or_default();
+    let status = status_line
+        .split_whitespace()
+        .nth(1)
+        .and_then(|code| code.parse::<u16>().ok());
+
+    match status {
+        Some(200..=299) => Ok(()),
+        _ => Err(Error::generic(format!(
+            "{} {} failed: {}",
+            method, url, status_line
+        ))),
+    }
+}
+
+/// Split an `http://host[:port][/path]` URL into its host, port and path
+fn parse_url(url: &str, default_path: &str) -> Result<(String, u16, String)> {
+    let rest = url.strip_prefix("http://").ok_or_else(|| {
+        Error::generic(format!(
+            "Unsupported URL {}: only http:// URLs are supported",
+            url
+        ))
+    })?;
+
+    let (authority, path) = match rest.split_once('/') {
+        Some((authority, path)) if !path.is_empty() => (authority, format!("/{}", path)),
+        _ => (rest.trim_end_matches('/'), default_path.to_string()),
+    };
+    let (host, port) = match authority.rsplit_once(':') {
+        Some((host, port)) => (
+            host,
+            port.parse()
+                .map_err(|_| Error::generic(format!("Invalid port in {}", url)))?,
+        ),
+        None => (authority, 80),
+    };
+
+    Ok((host.to_string(), port, path))
+}
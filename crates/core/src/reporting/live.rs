@@ -0,0 +1,219 @@
+use std::fs::File;
+use std::io::{BufWriter, Write};
+use std::path::Path;
+
+use chrono::Utc;
+use serde_json::{Value, json};
+
+use crate::runners::RunResult;
+use crate::test::TestResult;
+use crate::{EventMeta, ProcessOutput, Result};
+
+/// Receives events while a run is in progress, for progress UIs and reporters
+/// that write as tests finish rather than once the run is over.
+///
+/// Every hook has an empty default, so implementors only handle the events
+/// they care about. Runner output is fed in with [`LiveReporter::handle`];
+/// use [`LiveReporters`] to drive several at once.
+pub trait LiveReporter: Send {
+    /// The run is starting with `total_tests` tests
+    fn on_run_start(&mut self, _total_tests: usize) {}
+
+    /// A suite (test executable) is starting
+    fn on_suite_start(&mut self, _name: &str, _test_count: usize) {}
+
+    /// A test has started running
+    fn on_test_start(&mut self, _name: &str, _suite: &str) {}
+
+    /// A test of `suite` has passed, failed or been skipped
+    fn on_test_finished(&mut self, _result: &TestResult, _suite: &str) {}
+
+    /// A suite has reported all of its tests
+    fn on_suite_finished(&mut self, _name: &str) {}
+
+    /// A test executable has exited
+    fn on_executable_finished(&mut self) {}
+
+    /// The run is over
+    fn on_run_finished(&mut self, _result: &RunResult) {}
+
+    /// Dispatch runner output to the matching hook
+    fn handle(&mut self, event: &ProcessOutput) {
+        dispatch(self, event);
+    }
+}
+
+/// Call the hook of `reporter` matching `event`, for reporters that handle
+/// events themselves and still want the hooks called
+pub fn dispatch<R: LiveReporter + ?Sized>(reporter: &mut R, event: &ProcessOutput) {
+    match event {
+        ProcessOutput::TestStarted { name, suite, .. } => reporter.on_test_start(name, suite),
+        ProcessOutput::TestPassed { result, suite, .. }
+        | ProcessOutput::TestFailed { result, suite, .. }
+        | ProcessOutput::TestSkipped { result, suite, .. } => {
+            reporter.on_test_finished(result, suite)
+        }
+        ProcessOutput::SuiteStarted {
+            name, test_count, ..
+        } => reporter.on_suite_start(name, *test_count),
+        ProcessOutput::SuiteCompleted { name, .. } => reporter.on_suite_finished(name),
+        ProcessOutput::Done { .. } => reporter.on_executable_finished(),
+    }
+}
+
+/// Fans events out to several live reporters, in the order they were added
+#[derive(Default)]
+pub struct LiveReporters {
+    reporters: Vec<Box<dyn LiveReporter>>,
+}
+
+impl LiveReporters {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn with(mut self, reporter: impl LiveReporter + 'static) -> Self {
+        self.reporters.push(Box::new(reporter));
+        self
+    }
+
+    pub fn push(&mut self, reporter: Box<dyn LiveReporter>) {
+        self.reporters.push(reporter);
+    }
+}
+
+impl LiveReporter for LiveReporters {
+    fn handle(&mut self, event: &ProcessOutput) {
+        for reporter in self.reporters.iter_mut() {
+            reporter.handle(event);
+        }
+    }
+
+    fn on_run_start(&mut self, total_tests: usize) {
+        for reporter in self.reporters.iter_mut() {
+            reporter.on_run_start(total_tests);
+        }
+    }
+
+    fn on_suite_start(&mut self, name: &str, test_count: usize) {
+        for reporter in self.reporters.iter_mut() {
+            reporter.on_suite_start(name, test_count);
+        }
+    }
+
+    fn on_test_start(&mut self, name: &str, suite: &str) {
+        for reporter in self.reporters.iter_mut() {
+            reporter.on_test_start(name, suite);
+        }
+    }
+
+    fn on_test_finished(&mut self, result: &TestResult, suite: &str) {
+        for reporter in self.reporters.iter_mut() {
+            reporter.on_test_finished(result, suite);
+        }
+    }
+
+    fn on_suite_finished(&mut self, name: &str) {
+        for reporter in self.reporters.iter_mut() {
+            reporter.on_suite_finished(name);
+        }
+    }
+
+    fn on_executable_finished(&mut self) {
+        for reporter in self.reporters.iter_mut() {
+            reporter.on_executable_finished();
+        }
+    }
+
+    fn on_run_finished(&mut self, result: &RunResult) {
+        for reporter in self.reporters.iter_mut() {
+            reporter.on_run_finished(result);
+        }
+    }
+}
+
+/// Writes each event as a line of JSON as it happens, so a run can be
+/// followed with `tail -f` or picked up by another tool before it finishes.
+/// Events from the runner carry its [`EventMeta`], e.g. their `sequence`.
+///
+/// Write errors are ignored: a broken log shouldn't stop the run.
+pub struct EventLogReporter<W: Write + Send> {
+    writer: W,
+    /// Meta of the runner event being logged
+    meta: Option<EventMeta>,
+}
+
+impl EventLogReporter<BufWriter<File>> {
+    /// Log events to a new file at `path`
+    pub fn create(path: &Path) -> Result<Self> {
+        Ok(Self::new(BufWriter::new(File::create(path)?)))
+    }
+}
+
+impl<W: Write + Send> EventLogReporter<W> {
+    pub fn new(writer: W) -> Self {
+        Self { writer, meta: None }
+    }
+
+    fn log(&mut self, event: &str, mut fields: Value) {
+        if let Some(Value::Object(meta)) = self.meta.as_ref().map(|meta| json!(meta)) {
+            fields.as_object_mut().unwrap().extend(meta);
+        }
+        fields["event"] = json!(event);
+        fields["time"] = json!(Utc::now());
+        let _ = writeln!(self.writer, "{}", fields);
+        let _ = self.writer.flush();
+    }
+}
+
+impl<W: Write + Send> LiveReporter for EventLogReporter<W> {
+    fn handle(&mut self, event: &ProcessOutput) {
+        self.meta = Some(event.meta().clone());
+        dispatch(self, event);
+        self.meta = None;
+    }
+
+    fn on_run_start(&mut self, total_tests: usize) {
+        self.log("run_started", json!({ "total_tests": total_tests }));
+    }
+
+    fn on_suite_start(&mut self, name: &str, test_count: usize) {
+        self.log(
+            "suite_started",
+            json!({ "suite": name, "test_count": test_count }),
+        );
+    }
+
+    fn on_test_start(&mut self, name: &str, suite: &str) {
+        self.log("test_started", json!({ "test": name, "suite": suite }));
+    }
+
+    fn on_test_finished(&mut self, result: &TestResult, suite: &str) {
+        self.log(
+            "test_finished",
+            json!({
+                "test": result.qualified_name(),
+                "suite": suite,
+                "status": result.status,
+                "duration_ms": result.duration.map(|d| d.as_secs_f64() * 1000.0),
+                "error": result.error.as_ref().map(|e| e.to_string()),
+            }),
+        );
+    }
+
+    fn on_suite_finished(&mut self, name: &str) {
+        self.log("suite_finished", json!({ "suite": name }));
+    }
+
+    fn on_run_finished(&mut self, result: &RunResult) {
+        self.log(
+            "run_finished",
+            json!({
+                "passed": result.passed_tests,
+                "failed": result.failed_tests,
+                "skipped": result.skipped_tests,
+                "duration_ms": result.duration.map(|d| d.as_secs_f64() * 1000.0),
+            }),
+        );
+    }
+}
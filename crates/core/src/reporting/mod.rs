@@ -1,34 +1,80 @@
+pub mod environment;
 pub mod formats;
+#[cfg(any(feature = "otel", feature = "metrics"))]
+mod http;
+pub mod live;
 pub mod logging;
+pub mod timing;
 pub mod types;
 
+pub use environment::*;
 pub use formats::*;
+pub use live::*;
 pub use logging::*;
+pub use timing::*;
 pub use types::*;
 
 use crate::runners::RunResult;
+use crate::schema::SCHEMA_VERSION;
 
 use crate::{Error, Result};
 use chrono::Utc;
 use std::io::Write;
 use std::path::Path;
+use std::sync::Arc;
 
 /// Define a custom reporter that can be used to structure the results of a test run.
 ///
-/// The interface specifies two methods:
+/// Reporters implement three methods:
 ///
-/// - `generate`: used to generate a report from the run results.
+/// - `render`: used to render the report body from the run results.
 /// - `format`: used to return the reporting format that this reporter generates.
+/// - `metadata`: used to return the metadata attached to generated reports.
+///
+/// Reports are built with `generate`, or with `generate_shared` to avoid copying
+/// the run results into every report. Reporters that can produce their output
+/// incrementally also override `write_to`, so large runs can be written out
+/// without buffering the whole report.
 ///
 /// See the [`ReportFormat` enum](./types.rs#ReportFormat) for more details.
 pub trait Reporter: Send + Sync {
-    /// Generate a report from run results
-    fn generate(&self, run_result: &RunResult) -> Result<TestReport>;
+    /// Render the report body for run results
+    fn render(&self, run_result: &RunResult) -> Result<String>;
 
     /// Return the reporting format that this reporter generates
     ///
     /// See the [`ReportFormat` enum](./types.rs#ReportFormat) for more details.
     fn format(&self) -> ReportFormat;
+
+    /// Return the metadata attached to generated reports
+    fn metadata(&self) -> &ReportMetadata;
+
+    /// Write the report body for run results to `writer`
+    fn write_to(&self, run_result: &RunResult, writer: &mut dyn Write) -> Result<()> {
+        writer
+            .write_all(self.render(run_result)?.as_bytes())
+            .map_err(Error::from)
+    }
+
+    /// Generate a report that shares the run results rather than copying them
+    fn generate_shared(&self, run_result: Arc<RunResult>) -> Result<TestReport> {
+        Ok(TestReport {
+            metadata: self.metadata().clone(),
+            content: self.render(&run_result)?,
+            run_result,
+            format: self.format(),
+            created_at: Utc::now(),
+            schema_version: SCHEMA_VERSION,
+        })
+    }
+
+    /// Generate a report from run results
+    ///
+    /// This copies the run results into the report; prefer `generate_shared`
+    /// when generating several reports for the same run.
+    fn generate(&self, run_result: &RunResult) -> Result<TestReport> {
+        self.generate_shared(Arc::new(run_result.clone()))
+    }
 }
 
 /// Reporter extension trait for the purpose of writing reports to some
@@ -54,6 +100,7 @@ pub struct TextReporter {
     metadata: ReportMetadata,
     show_details: bool,
     show_timing: bool,
+    slowest: usize,
 }
 
 impl TextReporter {
@@ -62,6 +109,7 @@ impl TextReporter {
             metadata: ReportMetadata::default(),
             show_details: true,
             show_timing: true,
+            slowest: 10,
         }
     }
 
@@ -75,6 +123,12 @@ impl TextReporter {
         self
     }
 
+    /// How many of the slowest tests to list in the timing section
+    pub fn slowest(mut self, count: usize) -> Self {
+        self.slowest = count;
+        self
+    }
+
     fn format_duration(duration: &std::time::Duration) -> String {
         let millis = duration.as_millis();
         if millis < 1000 {
@@ -92,7 +146,7 @@ impl Default for TextReporter {
 }
 
 impl Reporter for TextReporter {
-    fn generate(&self, run_result: &RunResult) -> Result<TestReport> {
+    fn render(&self, run_result: &RunResult) -> Result<String> {
         let mut content = String::new();
 
         content.push_str(&format!("# {}\n\n", self.metadata.title));
@@ -110,6 +164,9 @@ impl Reporter for TextReporter {
         content.push_str(&format!("Passed Tests: {}\n", run_result.passed_tests));
         content.push_str(&format!("Failed Tests: {}\n", run_result.failed_tests));
         content.push_str(&format!("Skipped Tests: {}\n", run_result.skipped_tests));
+        if run_result.flaky_tests > 0 {
+            content.push_str(&format!("Flaky Tests: {}\n", run_result.flaky_tests));
+        }
         content.push_str(&format!(
             "Success Rate: {:.1}%\n",
             run_result.success_rate() * 100.0
@@ -123,6 +180,14 @@ impl Reporter for TextReporter {
 
         content.push('\n');
 
+        if let Some(ref environment) = self.metadata.environment {
+            content.push_str("## Environment\n\n");
+            for (label, value) in environment.entries() {
+                content.push_str(&format!("{}: {}\n", label, value));
+            }
+            content.push('\n');
+        }
+
         if self.show_details {
             content.push_str("## Suite Results\n\n");
 
@@ -148,22 +213,61 @@ impl Reporter for TextReporter {
                         crate::TestStatus::Passed => "  ✓",
                         crate::TestStatus::Failed => "  ✗",
                         crate::TestStatus::Skipped => "  -",
+                        crate::TestStatus::Cached => "  ✓ (cached)",
                         crate::TestStatus::Ignored => "  ⊝",
+                        crate::TestStatus::XFail => "  x (xfail)",
+                        crate::TestStatus::XPass => "  ✗ (xpass)",
+                        crate::TestStatus::Quarantined => "  ! (quarantined)",
                         _ => "  ?",
                     };
 
                     content.push_str(&format!("{} {}", test_status, test_result.name));
 
+                    if test_result.flaky {
+                        content.push_str(&format!(
+                            " [flaky-pass after {} retries]",
+                            test_result.retry_count
+                        ));
+                    }
+
                     if self.show_timing {
                         if let Some(ref duration) = test_result.duration {
                             content.push_str(&format!(" ({})", Self::format_duration(duration)));
                         }
+                        if let Some(ref delay) = test_result.injected_delay {
+                            content.push_str(&format!(
+                                " (+{} injected delay)",
+                                Self::format_duration(delay)
+                            ));
+                        }
                     }
 
                     content.push('\n');
 
+                    if let Some(ref reason) = test_result.skip_reason {
+                        content.push_str(&format!("    Reason: {}\n", reason));
+                    }
+
                     if let Some(ref error) = test_result.error {
-                        content.push_str(&format!("    Error: {}\n", error));
+                        content.push_str(&format!(
+                            "    Error: {}\n",
+                            error.report().replace('\n', "\n    ")
+                        ));
+                    }
+
+                    if test_result.failed() {
+                        if !test_result.breadcrumbs.is_empty() {
+                            content.push_str(&format!(
+                                "    Trail: {}\n",
+                                test_result.breadcrumbs.join(" -> ")
+                            ));
+                        }
+                        for line in &test_result.logs {
+                            content.push_str(&format!("    [{}] {}\n", line.level, line.message));
+                        }
+                        if let Some(ref temp_dir) = test_result.temp_dir {
+                            content.push_str(&format!("    Temp dir: {}\n", temp_dir.display()));
+                        }
                     }
                 }
 
@@ -171,6 +275,12 @@ impl Reporter for TextReporter {
             }
         }
 
+        if self.show_timing && run_result.total_tests > 0 {
+            content.push_str("## Timing\n\n");
+            content.push_str(&TimingAnalysis::new(run_result, self.slowest).to_text());
+            content.push('\n');
+        }
+
         let overall_status = if run_result.all_passed() {
             "All tests passed!"
         } else {
@@ -179,16 +289,14 @@ impl Reporter for TextReporter {
 
         content.push_str(&format!("\n{}\n", overall_status));
 
-        Ok(TestReport {
-            metadata: self.metadata.clone(),
-            run_result: run_result.clone(),
-            format: ReportFormat::Text,
-            content,
-            created_at: Utc::now(),
-        })
+        Ok(content)
     }
 
     fn format(&self) -> ReportFormat {
         ReportFormat::Text
     }
+
+    fn metadata(&self) -> &ReportMetadata {
+        &self.metadata
+    }
 }
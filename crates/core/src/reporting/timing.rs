@@ -0,0 +1,160 @@
+use std::time::Duration;
+
+use crate::runners::RunResult;
+
+/// Where the time in a run went: the slowest tests, how it splits across
+/// suites, and (when hooks were timed) how much went to setup and teardown
+/// rather than test bodies.
+#[derive(Debug, Clone)]
+pub struct TimingAnalysis {
+    pub slowest: Vec<TestTiming>,
+    pub suites: Vec<SuiteTiming>,
+    pub phases: Option<PhaseTiming>,
+}
+
+#[derive(Debug, Clone)]
+pub struct TestTiming {
+    pub suite: String,
+    pub test: String,
+    pub duration: Duration,
+}
+
+#[derive(Debug, Clone)]
+pub struct SuiteTiming {
+    pub name: String,
+    pub duration: Duration,
+    /// Fraction of the run's total suite time spent in this suite
+    pub share: f64,
+    pub tests: usize,
+}
+
+#[derive(Debug, Clone, Copy, Default)]
+pub struct PhaseTiming {
+    pub setup: Duration,
+    pub test: Duration,
+    pub teardown: Duration,
+}
+
+impl PhaseTiming {
+    pub fn total(&self) -> Duration {
+        self.setup + self.test + self.teardown
+    }
+}
+
+impl TimingAnalysis {
+    /// Analyse `run_result`, keeping the `top_n` slowest tests
+    pub fn new(run_result: &RunResult, top_n: usize) -> Self {
+        let mut slowest = Vec::new();
+        let mut suites = Vec::new();
+        let mut phases = PhaseTiming::default();
+        let mut phases_timed = false;
+
+        for suite_result in &run_result.suite_results {
+            if suite_result.test_results.is_empty() {
+                continue;
+            }
+
+            let mut test_total = Duration::ZERO;
+
+            for test_result in &suite_result.test_results {
+                let Some(duration) = test_result.duration else {
+                    continue;
+                };
+                test_total += duration;
+                slowest.push(TestTiming {
+                    suite: suite_result.name.clone(),
+                    test: test_result.name.clone(),
+                    duration,
+                });
+
+                if test_result.setup_duration.is_some() || test_result.teardown_duration.is_some() {
+                    phases_timed = true;
+                }
+                phases.setup += test_result.setup_duration.unwrap_or_default();
+                phases.test += duration;
+                phases.teardown += test_result.teardown_duration.unwrap_or_default();
+            }
+
+            suites.push(SuiteTiming {
+                name: suite_result.name.clone(),
+                duration: suite_result.duration.unwrap_or(test_total),
+                share: 0.0,
+                tests: suite_result.test_results.len(),
+            });
+        }
+
+        slowest.sort_by_key(|timing| std::cmp::Reverse(timing.duration));
+        slowest.truncate(top_n);
+
+        let total: f64 = suites.iter().map(|s| s.duration.as_secs_f64()).sum();
+        if total > 0.0 {
+            for suite in suites.iter_mut() {
+                suite.share = suite.duration.as_secs_f64() / total;
+            }
+        }
+        suites.sort_by_key(|suite| std::cmp::Reverse(suite.duration));
+
+        Self {
+            slowest,
+            suites,
+            phases: phases_timed.then_some(phases),
+        }
+    }
+
+    pub fn format_duration(duration: &Duration) -> String {
+        let millis = duration.as_millis();
+        if millis < 1000 {
+            format!("{}ms", millis)
+        } else {
+            format!("{:.2}s", duration.as_secs_f64())
+        }
+    }
+
+    /// Render the analysis as the plain text "Timing" section used by the
+    /// text report and `sheila report --slowest`
+    pub fn to_text(&self) -> String {
+        let mut content = String::new();
+
+        content.push_str(&format!("Slowest {} test(s):\n", self.slowest.len()));
+        for timing in &self.slowest {
+            content.push_str(&format!(
+                "  {:>9}  {}::{}\n",
+                Self::format_duration(&timing.duration),
+                timing.suite,
+                timing.test
+            ));
+        }
+
+        if !self.suites.is_empty() {
+            content.push_str("\nTime per suite:\n");
+            for suite in &self.suites {
+                content.push_str(&format!(
+                    "  {:>9}  {:>5.1}%  {} ({} tests)\n",
+                    Self::format_duration(&suite.duration),
+                    suite.share * 100.0,
+                    suite.name,
+                    suite.tests
+                ));
+            }
+        }
+
+        if let Some(phases) = self.phases {
+            let total = phases.total().as_secs_f64().max(f64::EPSILON);
+            content.push_str("\nSetup / test / teardown:\n");
+            for (label, duration) in [
+                ("setup", phases.setup),
+                ("test", phases.test),
+                ("teardown", phases.teardown),
+            ] {
+                content.push_str(&format!(
+                    "  {:>9}  {:>5.1}%  {}\n",
+                    Self::format_duration(&duration),
+                    duration.as_secs_f64() / total * 100.0,
+                    label
+                ));
+            }
+        }
+
+        content
+    }
+}
@@ -2,8 +2,12 @@ use chrono::{DateTime, Utc};
 use serde::{Deserialize, Serialize};
 use std::collections::HashMap;
 use std::fmt;
+use std::sync::Arc;
 
+use super::Environment;
+use crate::Artifact;
 use crate::runners::RunResult;
+use crate::test::TestResult;
 
 /// The format of the report to be generated.
 ///
@@ -23,7 +27,14 @@ use crate::runners::RunResult;
 /// - `Html`: HTML page (requires `html` feature)
 /// - `JUnit`: JUnit XML (requires `junit` feature)
 /// - `Tap`: Test Anything Protocol (requires `tap` feature)
+/// - `Allure`: Allure result files (requires `allure` feature)
+/// - `Badge`: SVG status badge (requires `badge` feature)
+/// - `Metrics`: Prometheus/OpenMetrics text (requires `metrics` feature)
+/// - `Otel`: OpenTelemetry spans as OTLP/JSON (requires `otel` feature)
+/// - `Sarif`: SARIF log of failure locations (requires `sarif` feature)
+/// - `Template`: rendered from a user template (requires `templates` feature)
 #[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[cfg_attr(feature = "schemas", derive(schemars::JsonSchema))]
 pub enum ReportFormat {
     Text,
     #[cfg(feature = "json")]
@@ -36,6 +47,18 @@ pub enum ReportFormat {
     JUnit,
     #[cfg(feature = "tap")]
     Tap,
+    #[cfg(feature = "allure")]
+    Allure,
+    #[cfg(feature = "badge")]
+    Badge,
+    #[cfg(feature = "metrics")]
+    Metrics,
+    #[cfg(feature = "otel")]
+    Otel,
+    #[cfg(feature = "sarif")]
+    Sarif,
+    #[cfg(feature = "templates")]
+    Template,
     Composite(Vec<ReportFormat>),
 }
 
@@ -53,6 +76,18 @@ impl fmt::Display for ReportFormat {
             ReportFormat::JUnit => write!(f, "junit"),
             #[cfg(feature = "tap")]
             ReportFormat::Tap => write!(f, "tap"),
+            #[cfg(feature = "allure")]
+            ReportFormat::Allure => write!(f, "allure"),
+            #[cfg(feature = "badge")]
+            ReportFormat::Badge => write!(f, "badge"),
+            #[cfg(feature = "metrics")]
+            ReportFormat::Metrics => write!(f, "metrics"),
+            #[cfg(feature = "otel")]
+            ReportFormat::Otel => write!(f, "otel"),
+            #[cfg(feature = "sarif")]
+            ReportFormat::Sarif => write!(f, "sarif"),
+            #[cfg(feature = "templates")]
+            ReportFormat::Template => write!(f, "template"),
             ReportFormat::Composite(formats) => write!(
                 f,
                 "composite({})",
@@ -67,21 +102,49 @@ impl fmt::Display for ReportFormat {
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
+#[cfg_attr(feature = "schemas", derive(schemars::JsonSchema))]
 pub struct TestReport {
     pub metadata: ReportMetadata,
-    pub run_result: RunResult,
+    /// Shared between every report generated for the same run
+    pub run_result: Arc<RunResult>,
     pub format: ReportFormat,
     pub content: String,
     pub created_at: DateTime<Utc>,
+    /// Version of the format this report is serialized in, see
+    /// [`SCHEMA_VERSION`](crate::schema::SCHEMA_VERSION)
+    #[serde(default = "crate::schema::schema_version")]
+    pub schema_version: u32,
+}
+
+impl TestReport {
+    /// Every artifact attached to a test in the reported run, along with
+    /// the test it belongs to
+    pub fn artifacts(&self) -> Vec<(&TestResult, &Artifact)> {
+        self.run_result
+            .suite_results
+            .iter()
+            .flat_map(|suite_result| suite_result.test_results.iter())
+            .flat_map(|test_result| {
+                test_result
+                    .artifacts
+                    .iter()
+                    .map(move |artifact| (test_result, artifact))
+            })
+            .collect()
+    }
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
+#[cfg_attr(feature = "schemas", derive(schemars::JsonSchema))]
 pub struct ReportMetadata {
     pub title: String,
     pub description: Option<String>,
     pub version: String,
     pub generator: String,
     pub custom: HashMap<String, String>,
+    /// Where the run happened, captured when the metadata is created
+    #[serde(default)]
+    pub environment: Option<Environment>,
 }
 
 impl Default for ReportMetadata {
@@ -92,6 +155,99 @@ impl Default for ReportMetadata {
             version: "1.0".to_string(),
             generator: "Sheila Testing Framework".to_string(),
             custom: HashMap::new(),
+            environment: Some(Environment::current().clone()),
+        }
+    }
+}
+
+/// Size limits applied to run results before they're reported, so a single
+/// giant panic message, diff or log doesn't balloon every report.
+///
+/// Text over a limit is cut at the limit and marked with how much was
+/// dropped. The defaults keep 16 KiB of each error and 64 KiB of each output
+/// stream or text artifact.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct ReportLimits {
+    /// Longest error message kept, in bytes
+    pub max_error_len: Option<usize>,
+    /// Longest captured stdout/stderr or text artifact kept, in bytes
+    pub max_output_len: Option<usize>,
+}
+
+impl ReportLimits {
+    /// Keep everything
+    pub fn unlimited() -> Self {
+        Self {
+            max_error_len: None,
+            max_output_len: None,
+        }
+    }
+
+    /// Truncate the errors and output in `run_result` to these limits
+    pub fn apply(&self, run_result: &mut RunResult) {
+        let errors = run_result.error.iter_mut().chain(
+            run_result
+                .suite_results
+                .iter_mut()
+                .flat_map(|suite_result| suite_result.error.iter_mut()),
+        );
+        for error in errors {
+            truncate_text(error.message_mut(), self.max_error_len);
         }
+
+        for suite_result in run_result.suite_results.iter_mut() {
+            for test_result in suite_result.test_results.iter_mut() {
+                if let Some(ref mut error) = test_result.error {
+                    truncate_text(error.message_mut(), self.max_error_len);
+                }
+                for attempt in test_result.attempts.iter_mut() {
+                    if let Some(ref mut error) = attempt.error {
+                        truncate_text(error.message_mut(), self.max_error_len);
+                    }
+                }
+
+                let outputs = test_result
+                    .stdout
+                    .iter_mut()
+                    .chain(test_result.stderr.iter_mut())
+                    .chain(
+                        test_result
+                            .artifacts
+                            .iter_mut()
+                            .filter_map(|artifact| artifact.text.as_mut()),
+                    );
+                for output in outputs {
+                    truncate_text(output, self.max_output_len);
+                }
+            }
+        }
+    }
+}
+
+impl Default for ReportLimits {
+    fn default() -> Self {
+        Self {
+            max_error_len: Some(16 * 1024),
+            max_output_len: Some(64 * 1024),
+        }
+    }
+}
+
+/// Cut `text` down to at most `limit` bytes (on a char boundary), noting how
+/// much was dropped
+fn truncate_text(text: &mut String, limit: Option<usize>) {
+    let Some(limit) = limit else {
+        return;
+    };
+    if text.len() <= limit {
+        return;
+    }
+
+    let mut end = limit;
+    while !text.is_char_boundary(end) {
+        end -= 1;
     }
+    let dropped = text.len() - end;
+    text.truncate(end);
+    text.push_str(&format!("\n... [truncated {} bytes]", dropped));
 }
@@ -1,6 +1,12 @@
+use std::backtrace::{Backtrace, BacktraceStatus};
+use std::error::Error as StdError;
+use std::fmt;
+use std::panic::Location;
+
 use serde::{Deserialize, Serialize};
 use strum_macros::EnumDiscriminants;
-use thiserror::Error;
+
+use crate::SourceLocation;
 
 pub type Result<T> = std::result::Result<T, Error>;
 
@@ -42,107 +48,431 @@ where
     }
 }
 
-#[derive(Error, Debug, Clone, Serialize, Deserialize, EnumDiscriminants)]
-#[strum_discriminants(name(ErrorKind), derive(Serialize, Deserialize))]
-pub enum Error {
-    #[error("Test execution failed: {message}")]
-    TestExecution { message: String },
+/// What is known about an error beyond its message: what caused it, where
+/// and in which test it was raised, and the backtrace at the time. Kept
+/// when results are serialized, for reporters to show.
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+#[cfg_attr(feature = "schemas", derive(schemars::JsonSchema))]
+pub struct ErrorDetails {
+    /// The test the error was raised in
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub test: Option<String>,
+    /// Where in the source the error was raised, e.g. the assertion that
+    /// failed
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub location: Option<SourceLocation>,
+    /// The error that caused this one
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub source: Option<ErrorCause>,
+    /// Backtrace to where the error was created, when `RUST_BACKTRACE` or
+    /// `RUST_LIB_BACKTRACE` asks for one
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub backtrace: Option<String>,
+}
 
-    #[error("Test setup failed: {message}")]
-    TestSetup { message: String },
+impl ErrorDetails {
+    fn capture() -> Box<Self> {
+        let backtrace = Backtrace::capture();
+        Box::new(Self {
+            backtrace: (backtrace.status() == BacktraceStatus::Captured)
+                .then(|| backtrace.to_string()),
+            ..Self::default()
+        })
+    }
 
-    #[error("Test teardown failed: {message}")]
-    TestTeardown { message: String },
+    pub fn is_empty(&self) -> bool {
+        self.test.is_none()
+            && self.location.is_none()
+            && self.source.is_none()
+            && self.backtrace.is_none()
+    }
+}
 
-    #[error("Fixture error: {message}")]
-    Fixture { message: String },
+/// An error in the chain of causes of an [`Error`]. Only the message of each
+/// is kept, so the chain survives serialization.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[cfg_attr(feature = "schemas", derive(schemars::JsonSchema))]
+pub struct ErrorCause {
+    pub message: String,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub source: Option<Box<ErrorCause>>,
+}
 
-    #[error("Hook execution failed: {hook_type}: {message}")]
-    Hook { hook_type: String, message: String },
+impl ErrorCause {
+    /// `error` and everything that caused it
+    pub fn new<E: StdError + ?Sized>(error: &E) -> Self {
+        Self {
+            message: error.to_string(),
+            source: error.source().map(|source| Box::new(Self::new(source))),
+        }
+    }
+}
 
-    #[error("Assertion failed: {message}")]
-    Assertion { message: String },
+impl fmt::Display for ErrorCause {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(&self.message)
+    }
+}
 
-    #[error("Mock error: {message}")]
-    Mock { message: String },
+impl StdError for ErrorCause {
+    fn source(&self) -> Option<&(dyn StdError + 'static)> {
+        self.source
+            .as_deref()
+            .map(|source| source as &(dyn StdError + 'static))
+    }
+}
 
-    #[error("Intended failure: {message}")]
-    IntendedFailure { message: String },
+#[derive(Debug, Clone, Serialize, Deserialize, EnumDiscriminants)]
+#[cfg_attr(feature = "schemas", derive(schemars::JsonSchema))]
+#[strum_discriminants(name(ErrorKind), derive(Serialize, Deserialize))]
+pub enum Error {
+    TestExecution {
+        message: String,
+        #[serde(default, skip_serializing_if = "ErrorDetails::is_empty")]
+        details: Box<ErrorDetails>,
+    },
+
+    TestSetup {
+        message: String,
+        #[serde(default, skip_serializing_if = "ErrorDetails::is_empty")]
+        details: Box<ErrorDetails>,
+    },
+
+    TestTeardown {
+        message: String,
+        #[serde(default, skip_serializing_if = "ErrorDetails::is_empty")]
+        details: Box<ErrorDetails>,
+    },
+
+    Fixture {
+        message: String,
+        #[serde(default, skip_serializing_if = "ErrorDetails::is_empty")]
+        details: Box<ErrorDetails>,
+    },
+
+    Hook {
+        hook_type: String,
+        message: String,
+        #[serde(default, skip_serializing_if = "ErrorDetails::is_empty")]
+        details: Box<ErrorDetails>,
+    },
+
+    Assertion {
+        message: String,
+        #[serde(default, skip_serializing_if = "ErrorDetails::is_empty")]
+        details: Box<ErrorDetails>,
+    },
+
+    Mock {
+        message: String,
+        #[serde(default, skip_serializing_if = "ErrorDetails::is_empty")]
+        details: Box<ErrorDetails>,
+    },
+
+    IntendedFailure {
+        message: String,
+        #[serde(default, skip_serializing_if = "ErrorDetails::is_empty")]
+        details: Box<ErrorDetails>,
+    },
+
+    RunnerConfig {
+        message: String,
+        #[serde(default, skip_serializing_if = "ErrorDetails::is_empty")]
+        details: Box<ErrorDetails>,
+    },
+
+    Reporter {
+        message: String,
+        #[serde(default, skip_serializing_if = "ErrorDetails::is_empty")]
+        details: Box<ErrorDetails>,
+    },
+
+    Parameterization {
+        message: String,
+        #[serde(default, skip_serializing_if = "ErrorDetails::is_empty")]
+        details: Box<ErrorDetails>,
+    },
+
+    Timeout {
+        message: String,
+        #[serde(default, skip_serializing_if = "ErrorDetails::is_empty")]
+        details: Box<ErrorDetails>,
+    },
+
+    Cancelled {
+        message: String,
+        #[serde(default, skip_serializing_if = "ErrorDetails::is_empty")]
+        details: Box<ErrorDetails>,
+    },
+
+    ResourceExhausted {
+        message: String,
+        #[serde(default, skip_serializing_if = "ErrorDetails::is_empty")]
+        details: Box<ErrorDetails>,
+    },
+
+    Dependency {
+        message: String,
+        #[serde(default, skip_serializing_if = "ErrorDetails::is_empty")]
+        details: Box<ErrorDetails>,
+    },
+
+    Io {
+        message: String,
+        #[serde(default, skip_serializing_if = "ErrorDetails::is_empty")]
+        details: Box<ErrorDetails>,
+    },
+
+    Serialization {
+        message: String,
+        #[serde(default, skip_serializing_if = "ErrorDetails::is_empty")]
+        details: Box<ErrorDetails>,
+    },
+
+    InvalidConfig {
+        message: String,
+        #[serde(default, skip_serializing_if = "ErrorDetails::is_empty")]
+        details: Box<ErrorDetails>,
+    },
+
+    Generic {
+        message: String,
+        #[serde(default, skip_serializing_if = "ErrorDetails::is_empty")]
+        details: Box<ErrorDetails>,
+    },
+}
 
-    #[error("Runner configuration error: {message}")]
-    RunnerConfig { message: String },
+impl fmt::Display for Error {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let prefix = match self {
+            Error::TestExecution { .. } => "Test execution failed",
+            Error::TestSetup { .. } => "Test setup failed",
+            Error::TestTeardown { .. } => "Test teardown failed",
+            Error::Fixture { .. } => "Fixture error",
+            Error::Hook { hook_type, .. } => {
+                return write!(
+                    f,
+                    "Hook execution failed: {}: {}",
+                    hook_type,
+                    self.message()
+                );
+            }
+            Error::Assertion { .. } => "Assertion failed",
+            Error::Mock { .. } => "Mock error",
+            Error::IntendedFailure { .. } => "Intended failure",
+            Error::RunnerConfig { .. } => "Runner configuration error",
+            Error::Reporter { .. } => "Reporter error",
+            Error::Parameterization { .. } => "Parameterization error",
+            Error::Timeout { .. } => "Operation timed out",
+            Error::Cancelled { .. } => "Run cancelled",
+            Error::ResourceExhausted { .. } => "Resource limit exceeded",
+            Error::Dependency { .. } => "Prerequisite not met",
+            Error::Io { .. } => "IO error",
+            Error::Serialization { .. } => "Serialization error",
+            Error::InvalidConfig { .. } => "Invalid configuration",
+            Error::Generic { .. } => return f.write_str(self.message()),
+        };
+        write!(f, "{}: {}", prefix, self.message())
+    }
+}
 
-    #[error("Reporter error: {message}")]
-    Reporter { message: String },
+impl StdError for Error {
+    fn source(&self) -> Option<&(dyn StdError + 'static)> {
+        self.details()
+            .source
+            .as_ref()
+            .map(|source| source as &(dyn StdError + 'static))
+    }
+}
 
-    #[error("Parameterization error: {message}")]
-    Parameterization { message: String },
+impl Error {
+    /// The error's message, without the prefix naming its kind
+    pub fn message(&self) -> &str {
+        match self {
+            Error::TestExecution { message, .. }
+            | Error::TestSetup { message, .. }
+            | Error::TestTeardown { message, .. }
+            | Error::Fixture { message, .. }
+            | Error::Hook { message, .. }
+            | Error::Assertion { message, .. }
+            | Error::Mock { message, .. }
+            | Error::IntendedFailure { message, .. }
+            | Error::RunnerConfig { message, .. }
+            | Error::Reporter { message, .. }
+            | Error::Parameterization { message, .. }
+            | Error::Timeout { message, .. }
+            | Error::Cancelled { message, .. }
+            | Error::ResourceExhausted { message, .. }
+            | Error::Dependency { message, .. }
+            | Error::Io { message, .. }
+            | Error::Serialization { message, .. }
+            | Error::InvalidConfig { message, .. }
+            | Error::Generic { message, .. } => message,
+        }
+    }
 
-    #[error("Operation timed out: {message}")]
-    Timeout { message: String },
+    /// The error's message, for editing in place
+    pub fn message_mut(&mut self) -> &mut String {
+        match self {
+            Error::TestExecution { message, .. }
+            | Error::TestSetup { message, .. }
+            | Error::TestTeardown { message, .. }
+            | Error::Fixture { message, .. }
+            | Error::Hook { message, .. }
+            | Error::Assertion { message, .. }
+            | Error::Mock { message, .. }
+            | Error::IntendedFailure { message, .. }
+            | Error::RunnerConfig { message, .. }
+            | Error::Reporter { message, .. }
+            | Error::Parameterization { message, .. }
+            | Error::Timeout { message, .. }
+            | Error::Cancelled { message, .. }
+            | Error::ResourceExhausted { message, .. }
+            | Error::Dependency { message, .. }
+            | Error::Io { message, .. }
+            | Error::Serialization { message, .. }
+            | Error::InvalidConfig { message, .. }
+            | Error::Generic { message, .. } => message,
+        }
+    }
 
-    #[error("IO error: {message}")]
-    Io { message: String },
+    pub fn details(&self) -> &ErrorDetails {
+        match self {
+            Error::TestExecution { details, .. }
+            | Error::TestSetup { details, .. }
+            | Error::TestTeardown { details, .. }
+            | Error::Fixture { details, .. }
+            | Error::Hook { details, .. }
+            | Error::Assertion { details, .. }
+            | Error::Mock { details, .. }
+            | Error::IntendedFailure { details, .. }
+            | Error::RunnerConfig { details, .. }
+            | Error::Reporter { details, .. }
+            | Error::Parameterization { details, .. }
+            | Error::Timeout { details, .. }
+            | Error::Cancelled { details, .. }
+            | Error::ResourceExhausted { details, .. }
+            | Error::Dependency { details, .. }
+            | Error::Io { details, .. }
+            | Error::Serialization { details, .. }
+            | Error::InvalidConfig { details, .. }
+            | Error::Generic { details, .. } => details,
+        }
+    }
 
-    #[error("Serialization error: {message}")]
-    Serialization { message: String },
+    pub fn details_mut(&mut self) -> &mut ErrorDetails {
+        match self {
+            Error::TestExecution { details, .. }
+            | Error::TestSetup { details, .. }
+            | Error::TestTeardown { details, .. }
+            | Error::Fixture { details, .. }
+            | Error::Hook { details, .. }
+            | Error::Assertion { details, .. }
+            | Error::Mock { details, .. }
+            | Error::IntendedFailure { details, .. }
+            | Error::RunnerConfig { details, .. }
+            | Error::Reporter { details, .. }
+            | Error::Parameterization { details, .. }
+            | Error::Timeout { details, .. }
+            | Error::Cancelled { details, .. }
+            | Error::ResourceExhausted { details, .. }
+            | Error::Dependency { details, .. }
+            | Error::Io { details, .. }
+            | Error::Serialization { details, .. }
+            | Error::InvalidConfig { details, .. }
+            | Error::Generic { details, .. } => details,
+        }
+    }
 
-    #[error("Invalid configuration: {message}")]
-    InvalidConfig { message: String },
+    pub fn kind(&self) -> ErrorKind {
+        ErrorKind::from(self)
+    }
 
-    #[error("{message}")]
-    Generic { message: String },
-}
+    /// Record `source` as the cause of the error
+    pub fn with_source<E: StdError + ?Sized>(mut self, source: &E) -> Self {
+        self.details_mut().source = Some(ErrorCause::new(source));
+        self
+    }
 
-impl Error {
-    pub fn kind(&self) -> ErrorKind {
-        match self {
-            Error::TestExecution { .. } => ErrorKind::TestExecution,
-            Error::TestSetup { .. } => ErrorKind::TestSetup,
-            Error::TestTeardown { .. } => ErrorKind::TestTeardown,
-            Error::Fixture { .. } => ErrorKind::Fixture,
-            Error::Hook { .. } => ErrorKind::Hook,
-            Error::Assertion { .. } => ErrorKind::Assertion,
-            Error::Mock { .. } => ErrorKind::Mock,
-            Error::IntendedFailure { .. } => ErrorKind::IntendedFailure,
-            Error::RunnerConfig { .. } => ErrorKind::RunnerConfig,
-            Error::Reporter { .. } => ErrorKind::Reporter,
-            Error::Parameterization { .. } => ErrorKind::Parameterization,
-            Error::Timeout { .. } => ErrorKind::Timeout,
-            Error::Io { .. } => ErrorKind::Io,
-            Error::Serialization { .. } => ErrorKind::Serialization,
-            Error::InvalidConfig { .. } => ErrorKind::InvalidConfig,
-            Error::Generic { .. } => ErrorKind::Generic,
+    /// Record the test the error was raised in
+    pub fn with_test<S: Into<String>>(mut self, test: S) -> Self {
+        self.details_mut().test = Some(test.into());
+        self
+    }
+
+    /// Record where in the source the error was raised
+    pub fn with_location(mut self, location: SourceLocation) -> Self {
+        self.details_mut().location = Some(location);
+        self
+    }
+
+    /// The errors that led to this one, starting with its direct cause
+    pub fn causes(&self) -> impl Iterator<Item = &ErrorCause> {
+        std::iter::successors(self.details().source.as_ref(), |cause| {
+            cause.source.as_deref()
+        })
+    }
+
+    /// Where the error was raised and what caused it, one per line, then
+    /// its backtrace if one was captured. Empty if none of it is known.
+    pub fn trace(&self) -> String {
+        let mut lines = Vec::new();
+        if let Some(location) = &self.details().location {
+            lines.push(format!(
+                "at {}:{}:{}",
+                location.file, location.line, location.column
+            ));
+        }
+        for cause in self.causes() {
+            lines.push(format!("Caused by: {}", cause));
         }
+        if let Some(backtrace) = &self.details().backtrace {
+            lines.push(format!("Backtrace:\n{}", backtrace.trim_end()));
+        }
+        lines.join("\n")
+    }
+
+    /// The error followed by its [`trace`](Self::trace)
+    pub fn report(&self) -> String {
+        let trace = self.trace();
+        if trace.is_empty() {
+            return self.to_string();
+        }
+        format!("{}\n{}", self, trace)
     }
 
     pub fn test_execution<S: Into<String>>(message: S) -> Self {
         Error::TestExecution {
             message: message.into(),
+            details: ErrorDetails::capture(),
         }
     }
 
     pub fn intended_failure<S: Into<String>>(message: S) -> Self {
         Error::IntendedFailure {
             message: message.into(),
+            details: ErrorDetails::capture(),
         }
     }
 
     pub fn test_setup<S: Into<String>>(message: S) -> Self {
         Error::TestSetup {
             message: message.into(),
+            details: ErrorDetails::capture(),
         }
     }
 
     pub fn test_teardown<S: Into<String>>(message: S) -> Self {
         Error::TestTeardown {
             message: message.into(),
+            details: ErrorDetails::capture(),
         }
     }
 
     pub fn fixture<S: Into<String>>(message: S) -> Self {
         Error::Fixture {
             message: message.into(),
+            details: ErrorDetails::capture(),
         }
     }
 
@@ -150,54 +480,179 @@ impl Error {
         Error::Hook {
             hook_type: hook_type.into(),
             message: message.into(),
+            details: ErrorDetails::capture(),
         }
     }
 
+    /// An assertion failure, located where this was called from
+    #[track_caller]
     pub fn assertion<S: Into<String>>(message: S) -> Self {
+        let caller = Location::caller();
         Error::Assertion {
             message: message.into(),
+            details: ErrorDetails::capture(),
         }
+        .with_location(SourceLocation {
+            file: caller.file().to_string(),
+            line: caller.line(),
+            column: caller.column(),
+        })
     }
 
     pub fn mock<S: Into<String>>(message: S) -> Self {
         Error::Mock {
             message: message.into(),
+            details: ErrorDetails::capture(),
+        }
+    }
+
+    pub fn runner_config<S: Into<String>>(message: S) -> Self {
+        Error::RunnerConfig {
+            message: message.into(),
+            details: ErrorDetails::capture(),
+        }
+    }
+
+    pub fn reporter<S: Into<String>>(message: S) -> Self {
+        Error::Reporter {
+            message: message.into(),
+            details: ErrorDetails::capture(),
+        }
+    }
+
+    pub fn parameterization<S: Into<String>>(message: S) -> Self {
+        Error::Parameterization {
+            message: message.into(),
+            details: ErrorDetails::capture(),
         }
     }
 
     pub fn timeout<S: Into<String>>(message: S) -> Self {
         Error::Timeout {
             message: message.into(),
+            details: ErrorDetails::capture(),
+        }
+    }
+
+    pub fn cancelled<S: Into<String>>(message: S) -> Self {
+        Error::Cancelled {
+            message: message.into(),
+            details: ErrorDetails::capture(),
+        }
+    }
+
+    pub fn resource_exhausted<S: Into<String>>(message: S) -> Self {
+        Error::ResourceExhausted {
+            message: message.into(),
+            details: ErrorDetails::capture(),
+        }
+    }
+
+    pub fn dependency<S: Into<String>>(message: S) -> Self {
+        Error::Dependency {
+            message: message.into(),
+            details: ErrorDetails::capture(),
+        }
+    }
+
+    pub fn io<S: Into<String>>(message: S) -> Self {
+        Error::Io {
+            message: message.into(),
+            details: ErrorDetails::capture(),
+        }
+    }
+
+    pub fn serialization<S: Into<String>>(message: S) -> Self {
+        Error::Serialization {
+            message: message.into(),
+            details: ErrorDetails::capture(),
+        }
+    }
+
+    pub fn invalid_config<S: Into<String>>(message: S) -> Self {
+        Error::InvalidConfig {
+            message: message.into(),
+            details: ErrorDetails::capture(),
         }
     }
 
     pub fn generic<S: Into<String>>(message: S) -> Self {
         Error::Generic {
             message: message.into(),
+            details: ErrorDetails::capture(),
         }
     }
 }
 
 impl From<std::io::Error> for Error {
     fn from(err: std::io::Error) -> Self {
-        Error::Io {
-            message: err.to_string(),
-        }
+        let mut error = Error::io(err.to_string());
+        error.details_mut().source = err.source().map(ErrorCause::new);
+        error
     }
 }
 
 impl From<serde_json::Error> for Error {
     fn from(err: serde_json::Error) -> Self {
-        Error::Serialization {
-            message: err.to_string(),
-        }
+        let mut error = Error::serialization(err.to_string());
+        error.details_mut().source = err.source().map(ErrorCause::new);
+        error
     }
 }
 
 impl From<anyhow::Error> for Error {
+    /// Keeps the outermost context as the message and the rest of the
+    /// chain as causes
     fn from(err: anyhow::Error) -> Self {
-        Error::Generic {
-            message: err.to_string(),
-        }
+        let mut error = Error::generic(err.to_string());
+        error.details_mut().source = err.chain().nth(1).map(ErrorCause::new);
+        error
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::assert::Assertion;
+
+    #[test]
+    fn test_details_survive_serialization() {
+        let io = std::io::Error::other("disk full");
+        let error = Error::fixture("Failed to write the fixture")
+            .with_source(&io)
+            .with_test("writes_file");
+
+        let json = serde_json::to_string(&error).unwrap();
+        let restored: Error = serde_json::from_str(&json).unwrap();
+
+        assert_eq!(restored.kind(), ErrorKind::Fixture);
+        assert_eq!(restored.details().test.as_deref(), Some("writes_file"));
+        assert_eq!(
+            restored
+                .causes()
+                .map(|cause| cause.message.as_str())
+                .collect::<Vec<_>>(),
+            ["disk full"]
+        );
+        assert_eq!(
+            StdError::source(&restored).unwrap().to_string(),
+            "disk full"
+        );
+    }
+
+    #[test]
+    fn test_errors_without_details_serialize_as_before() {
+        let json = r#"{"Timeout":{"message":"too slow"}}"#;
+        let error: Error = serde_json::from_str(json).unwrap();
+        assert!(error.details().is_empty());
+        assert_eq!(serde_json::to_string(&error).unwrap(), json);
+    }
+
+    #[test]
+    fn test_assertions_record_their_caller() {
+        let error = Assertion::eq(1, 2).unwrap_err();
+        let location = error.details().location.clone().unwrap();
+        assert_eq!(location.file, file!());
+        assert_eq!(location.line, line!() - 3);
     }
 }
@@ -0,0 +1,171 @@
+use serde::{Deserialize, Serialize};
+use std::fmt;
+use std::sync::atomic::{AtomicBool, AtomicU8, Ordering};
+use std::sync::{Arc, mpsc};
+use std::thread;
+use std::time::Duration;
+
+use crate::Error;
+
+/// Why a run was cancelled.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub enum CancelReason {
+    /// Interrupted by the user, e.g. Ctrl-C or `sheila stop`
+    User,
+    /// A failure occurred and the run is configured to fail fast
+    FailFast,
+    /// The run exceeded its time budget
+    Timeout,
+}
+
+impl CancelReason {
+    fn from_u8(value: u8) -> Option<Self> {
+        match value {
+            1 => Some(CancelReason::User),
+            2 => Some(CancelReason::FailFast),
+            3 => Some(CancelReason::Timeout),
+            _ => None,
+        }
+    }
+
+    /// The error a run stopped for this reason finishes with
+    pub fn run_error(self) -> Error {
+        match self {
+            CancelReason::FailFast => Error::test_execution("Failing fast due to test failure"),
+            CancelReason::Timeout => Error::resource_exhausted("Run time budget exceeded"),
+            reason => Error::cancelled(reason.to_string()),
+        }
+    }
+
+    fn as_u8(self) -> u8 {
+        match self {
+            CancelReason::User => 1,
+            CancelReason::FailFast => 2,
+            CancelReason::Timeout => 3,
+        }
+    }
+}
+
+impl fmt::Display for CancelReason {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            CancelReason::User => write!(f, "interrupted by user"),
+            CancelReason::FailFast => write!(f, "failing fast"),
+            CancelReason::Timeout => write!(f, "run time budget exceeded"),
+        }
+    }
+}
+
+/// Shared flag used to stop a run early.
+///
+/// Clones share the same state, so a token can be handed to a signal handler
+/// or another thread and cancelled from there. Cancelling only records the
+/// request; runners check the token between tests and while waiting on child
+/// processes, then clean up and return a partial result. The first reason
+/// given wins, and cancelling is a single atomic store so it is safe to call
+/// from a signal handler.
+///
+/// A token can also hold a run without stopping it: while paused, runners
+/// start no new test executables and suspend the ones in flight where the
+/// platform allows, until the token is resumed.
+#[derive(Debug, Clone, Default)]
+pub struct CancellationToken {
+    reason: Arc<AtomicU8>,
+    paused: Arc<AtomicBool>,
+}
+
+impl CancellationToken {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn cancel(&self, reason: CancelReason) {
+        let _ = self
+            .reason
+            .compare_exchange(0, reason.as_u8(), Ordering::SeqCst, Ordering::SeqCst);
+    }
+
+    pub fn is_cancelled(&self) -> bool {
+        self.reason.load(Ordering::SeqCst) != 0
+    }
+
+    pub fn reason(&self) -> Option<CancelReason> {
+        CancelReason::from_u8(self.reason.load(Ordering::SeqCst))
+    }
+
+    pub fn pause(&self) {
+        self.paused.store(true, Ordering::SeqCst);
+    }
+
+    pub fn resume(&self) {
+        self.paused.store(false, Ordering::SeqCst);
+    }
+
+    pub fn is_paused(&self) -> bool {
+        self.paused.load(Ordering::SeqCst)
+    }
+
+    /// Cancel with `reason` once `delay` has passed, unless the returned
+    /// timer is dropped first.
+    pub fn cancel_after(&self, delay: Duration, reason: CancelReason) -> CancelTimer {
+        let (disarm, disarmed) = mpsc::channel::<()>();
+        let token = self.clone();
+        thread::spawn(move || {
+            if let Err(mpsc::RecvTimeoutError::Timeout) = disarmed.recv_timeout(delay) {
+                token.cancel(reason);
+            }
+        });
+
+        CancelTimer { _disarm: disarm }
+    }
+}
+
+/// Pending cancellation started by [`CancellationToken::cancel_after`],
+/// disarmed when dropped.
+#[derive(Debug)]
+pub struct CancelTimer {
+    _disarm: mpsc::Sender<()>,
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_first_reason_wins_across_clones() {
+        let token = CancellationToken::new();
+        let clone = token.clone();
+        assert!(!token.is_cancelled());
+
+        clone.cancel(CancelReason::FailFast);
+        token.cancel(CancelReason::User);
+
+        assert!(token.is_cancelled());
+        assert_eq!(token.reason(), Some(CancelReason::FailFast));
+    }
+
+    #[test]
+    fn test_cancel_after_is_disarmed_on_drop() {
+        let token = CancellationToken::new();
+        drop(token.cancel_after(Duration::from_millis(10), CancelReason::Timeout));
+        thread::sleep(Duration::from_millis(50));
+        assert!(!token.is_cancelled());
+
+        let _timer = token.cancel_after(Duration::from_millis(10), CancelReason::Timeout);
+        thread::sleep(Duration::from_millis(50));
+        assert_eq!(token.reason(), Some(CancelReason::Timeout));
+    }
+
+    #[test]
+    fn test_pause_is_shared_and_separate_from_cancelling() {
+        let token = CancellationToken::new();
+        let clone = token.clone();
+
+        clone.pause();
+        assert!(token.is_paused());
+        assert!(!token.is_cancelled());
+
+        token.resume();
+        assert!(!clone.is_paused());
+    }
+}
@@ -0,0 +1,437 @@
+use mio::unix::pipe;
+use mio::{Interest, Registry};
+use std::collections::HashMap;
+use std::process::{Child, ExitStatus, Stdio};
+use std::time::{Duration, Instant};
+
+use super::{
+    CargoRunnerConfig, Launcher, PlannedExecutable, ResourceLimits, append_test_args, json_args,
+};
+use crate::suite::SuiteResult;
+use crate::test::{TestResult, TestStatus};
+use crate::{
+    Error, EventMeta, EventSender, JsonLineParser, LineBuffer, PanicGroupCollector, ProcessOutput,
+    Result, StandardLineParser, TestRunState, stderr_token, stdout_token,
+};
+
+/// A spawned test executable whose output is being read from a shared poll
+/// loop. Each child owns its own parser state, so several can be in flight
+/// at once without their test events getting mixed up.
+pub(crate) struct ChildRun {
+    pub planned: PlannedExecutable,
+    child: Child,
+    stdout: LineBuffer<pipe::Receiver>,
+    stderr: LineBuffer<pipe::Receiver>,
+    panics: PanicGroupCollector,
+    /// Panics printed in the failures section of pretty output
+    stdout_panics: PanicGroupCollector,
+    /// Test whose captured output section of pretty output is being read,
+    /// and the lines read so far
+    output_section: Option<(String, Vec<String>)>,
+    state: TestRunState,
+    test_results: Vec<TestResult>,
+    /// Why the executable failed, beyond the failures of its tests
+    error: Option<Error>,
+    started_at: Instant,
+    /// When the child was last suspended, if it still is
+    suspended_at: Option<Instant>,
+    /// Time spent suspended before that
+    suspended_for: Duration,
+}
+
+impl ChildRun {
+    /// Spawn `planned` with the variables in `env` set, and register its
+    /// pipes with `registry` under the tokens for `slot`.
+    pub fn spawn(
+        planned: PlannedExecutable,
+        launcher: &dyn Launcher,
+        args: &[String],
+        env: &HashMap<String, String>,
+        cargo_config: &CargoRunnerConfig,
+        registry: &Registry,
+        slot: usize,
+    ) -> Result<Self> {
+        let mut test_args = Vec::new();
+        if planned.json {
+            test_args.extend(json_args());
+        }
+        // Report captured output for passing tests as well as failures
+        if cargo_config.capture_output {
+            test_args.push("--show-output".to_string());
+        }
+        test_args.extend(planned.filter_args());
+        append_test_args(&mut test_args, args);
+
+        let mut envs: Vec<(&str, &str)> = env
+            .iter()
+            .map(|(key, value)| (key.as_str(), value.as_str()))
+            .collect();
+        if planned.json {
+            envs.push(("RUSTC_BOOTSTRAP", "1"));
+        }
+        if !cargo_config.capture_output {
+            envs.push(("RUST_TEST_NOCAPTURE", "1"));
+        }
+
+        let mut command =
+            launcher.command(&planned.executable, &test_args, &envs, &cargo_config.limits)?;
+        command.stdout(Stdio::piped()).stderr(Stdio::piped());
+
+        // Run in a fresh process group so the whole tree can be killed on
+        // timeout or cancellation
+        #[cfg(unix)]
+        std::os::unix::process::CommandExt::process_group(&mut command, 0);
+
+        tracing::debug!(
+            "Running {} {}",
+            planned.executable.path.display(),
+            test_args.join(" ")
+        );
+        let started_at = Instant::now();
+        let mut child = command.spawn()?;
+
+        let mut stdout_pipe = pipe::Receiver::from(child.stdout.take().unwrap());
+        let mut stderr_pipe = pipe::Receiver::from(child.stderr.take().unwrap());
+
+        stdout_pipe.set_nonblocking(true)?;
+        stderr_pipe.set_nonblocking(true)?;
+
+        registry.register(&mut stdout_pipe, stdout_token(slot), Interest::READABLE)?;
+        registry.register(&mut stderr_pipe, stderr_token(slot), Interest::READABLE)?;
+
+        Ok(Self {
+            state: TestRunState::for_suite(planned.executable.name.clone()),
+            planned,
+            child,
+            stdout: LineBuffer::new(stdout_pipe),
+            stderr: LineBuffer::new(stderr_pipe),
+            panics: PanicGroupCollector::new(),
+            stdout_panics: PanicGroupCollector::new(),
+            output_section: None,
+            test_results: Vec::new(),
+            error: None,
+            started_at,
+            suspended_at: None,
+            suspended_for: Duration::ZERO,
+        })
+    }
+
+    pub fn name(&self) -> &str {
+        &self.planned.executable.name
+    }
+
+    /// How long the child has been running, leaving out time spent
+    /// suspended
+    pub fn running_time(&self) -> Duration {
+        let suspended =
+            self.suspended_for + self.suspended_at.map_or(Duration::ZERO, |at| at.elapsed());
+        self.started_at.elapsed().saturating_sub(suspended)
+    }
+
+    /// Stop or continue the child along with anything it spawned. Only Unix
+    /// can suspend processes, so elsewhere the child keeps running.
+    pub fn set_suspended(&mut self, suspended: bool) {
+        #[cfg(unix)]
+        {
+            let signal = if suspended {
+                libc::SIGSTOP
+            } else {
+                libc::SIGCONT
+            };
+            unsafe {
+                libc::kill(-(self.child.id() as i32), signal);
+            }
+            if suspended {
+                self.suspended_at.get_or_insert_with(Instant::now);
+            } else if let Some(at) = self.suspended_at.take() {
+                self.suspended_for += at.elapsed();
+            }
+        }
+
+        #[cfg(not(unix))]
+        let _ = suspended;
+    }
+
+    /// Parse every complete stdout line available. Pipes are registered
+    /// edge-triggered, so this must read until the pipe would block.
+    pub fn read_stdout(&mut self, output_tx: Option<&EventSender>) -> Result<()> {
+        while let Some(line) = self.stdout.read_line()? {
+            self.handle_stdout_line(&line, output_tx);
+        }
+        Ok(())
+    }
+
+    /// Collect panic reports from every stderr line available, keeping the
+    /// other lines for the result of the test that printed them.
+    pub fn read_stderr(&mut self, output_tx: Option<&EventSender>) -> Result<()> {
+        while let Some(line) = self.stderr.read_line()? {
+            if !self.panics.claims(&line) {
+                self.state.push_stderr(line);
+            } else if let Some(panic) = self.panics.push_line(line) {
+                self.handle_panic(&panic, output_tx);
+            }
+        }
+        Ok(())
+    }
+
+    pub fn try_wait(&mut self) -> Result<Option<ExitStatus>> {
+        Ok(self.child.try_wait()?)
+    }
+
+    /// Kill the child along with anything it spawned, and reap it.
+    pub fn kill(&mut self) {
+        #[cfg(unix)]
+        unsafe {
+            libc::kill(-(self.child.id() as i32), libc::SIGKILL);
+        }
+
+        let _ = self.child.kill();
+        let _ = self.child.wait();
+    }
+
+    /// Drain whatever output is left once the child has exited or been
+    /// killed, then stop watching its pipes. Panic reports are read first so
+    /// they can be attached to the failures reported on stdout.
+    pub fn flush(&mut self, registry: &Registry, output_tx: Option<&EventSender>) {
+        let _ = self.read_stderr(output_tx);
+        if let Some(panic) = self.panics.finish() {
+            self.handle_panic(&panic, output_tx);
+        }
+
+        let _ = self.read_stdout(output_tx);
+        if let Some(panic) = self.stdout_panics.finish() {
+            self.handle_panic(&panic, output_tx);
+        }
+        self.finish_output_section();
+
+        let _ = registry.deregister(self.stdout.get_mut());
+        let _ = registry.deregister(self.stderr.get_mut());
+
+        self.state.finalize_pending_errors(&mut self.test_results);
+    }
+
+    /// Synthesize results for tests the child had in flight when it was
+    /// stopped, since libtest will never report them.
+    pub fn abort_running(
+        &mut self,
+        status: TestStatus,
+        error: Error,
+        output_tx: Option<&EventSender>,
+    ) {
+        let suite = self.name().to_string();
+        for (name, duration_ms) in self.state.take_running() {
+            let mut test_result = StandardLineParser::create_test_result(&name, status);
+            test_result.duration = Some(Duration::from_secs_f64(duration_ms / 1000.0));
+            test_result.error = Some(error.clone());
+            test_result.stderr = self.state.take_stderr(&name);
+            self.push_aborted(test_result, &suite, output_tx);
+        }
+    }
+
+    /// Report a test the child will never finish, and keep its result.
+    fn push_aborted(
+        &mut self,
+        test_result: TestResult,
+        suite: &str,
+        output_tx: Option<&EventSender>,
+    ) {
+        let output = if test_result.failed() {
+            ProcessOutput::TestFailed {
+                result: test_result.clone(),
+                suite: suite.to_string(),
+                duration_ms: test_result
+                    .duration
+                    .map_or(0.0, |duration| duration.as_secs_f64() * 1000.0),
+                error: test_result
+                    .error
+                    .as_ref()
+                    .map(ToString::to_string)
+                    .unwrap_or_default(),
+                location: None,
+                meta: EventMeta::default(),
+            }
+        } else {
+            ProcessOutput::TestSkipped {
+                result: test_result.clone(),
+                suite: suite.to_string(),
+                meta: EventMeta::default(),
+            }
+        };
+        send_event(output_tx, &output);
+        self.test_results.push(test_result);
+    }
+
+    /// Whether any test reported so far failed
+    pub fn has_failures(&self) -> bool {
+        self.test_results.iter().any(TestResult::failed)
+    }
+
+    /// Give each of `tests` the child never reported on `status`, once it
+    /// has stopped.
+    pub fn abort_unreported(
+        &mut self,
+        tests: &[String],
+        status: TestStatus,
+        error: Error,
+        output_tx: Option<&EventSender>,
+    ) {
+        let suite = self.name().to_string();
+        for test in tests {
+            if self
+                .test_results
+                .iter()
+                .any(|result| result.qualified_name() == test)
+            {
+                continue;
+            }
+
+            let mut test_result = StandardLineParser::create_test_result(test, status);
+            test_result.error = Some(error.clone());
+            self.push_aborted(test_result, &suite, output_tx);
+        }
+    }
+
+    /// Fail what the child left unfinished when it exited with an
+    /// unsuccessful `status`: the tests it had in flight and those of
+    /// `tests` it never reported. Unless a failed test accounts for the
+    /// exit, e.g. when libtest rejected its arguments or the process crashed
+    /// between tests, the suite fails too.
+    pub fn fail_exit(
+        &mut self,
+        tests: &[String],
+        status: &ExitStatus,
+        limits: &ResourceLimits,
+        output_tx: Option<&EventSender>,
+    ) {
+        let reported = !self.test_results.is_empty();
+        let error = match limits.exhaustion(status) {
+            Some(error) => error,
+            None => {
+                let mut message = format!("{} exited unsuccessfully ({})", self.name(), status);
+                // What libtest had to say if it gave up before running
+                // anything
+                if !reported && let Some(stderr) = self.state.take_unclaimed_stderr() {
+                    message = format!("{}:\n{}", message, stderr.trim_end());
+                }
+                Error::test_execution(message)
+            }
+        };
+        self.abort_running(TestStatus::Failed, error.clone(), output_tx);
+        self.abort_unreported(tests, TestStatus::Failed, error.clone(), output_tx);
+        if !reported || !self.has_failures() {
+            self.error = Some(error);
+        }
+    }
+
+    pub fn into_suite_result(mut self) -> SuiteResult {
+        let mut suite_result = StandardLineParser::create_suite_result(
+            &self.planned.executable.name,
+            &self.test_results,
+        );
+        suite_result.error = self.error;
+        suite_result.stderr = self.state.take_suite_stderr();
+        suite_result
+    }
+
+    fn handle_panic(&mut self, panic: &str, output_tx: Option<&EventSender>) {
+        if let Ok(parsed) = StandardLineParser::parse_error_output(panic)
+            && let Some(output) = self.state.handle_line(parsed)
+        {
+            send_event(output_tx, &output);
+        }
+    }
+
+    /// Parse a stdout line in whichever format the child was asked for. In
+    /// JSON mode anything that isn't JSON is the tests' own output.
+    fn handle_stdout_line(&mut self, line: &str, output_tx: Option<&EventSender>) {
+        let parsed = if self.planned.json {
+            match JsonLineParser::parse_test_output(line) {
+                Ok(Some(parsed)) => parsed,
+                _ => return,
+            }
+        } else {
+            match StandardLineParser::parse_test_output(line) {
+                Ok((_, parsed)) => parsed,
+                Err(_) => {
+                    self.push_output_line(line);
+                    if let Some(panic) = self.stdout_panics.push_line(line.to_string()) {
+                        self.handle_panic(&panic, output_tx);
+                    }
+                    return;
+                }
+            }
+        };
+
+        if let Some(output) = self.state.handle_line(parsed) {
+            send_event(output_tx, &output);
+
+            match output {
+                ProcessOutput::TestPassed { result, .. }
+                | ProcessOutput::TestFailed { result, .. }
+                | ProcessOutput::TestSkipped { result, .. } => self.test_results.push(result),
+                _ => {}
+            }
+        }
+    }
+
+    /// Split the captured output libtest prints after the results in pretty
+    /// mode into per-test sections, of the form
+    ///
+    /// ```text
+    /// ---- module::test stdout ----
+    /// <output>
+    /// ```
+    ///
+    /// ending at the next section or the `failures:`/`successes:` name list.
+    fn push_output_line(&mut self, line: &str) {
+        let trimmed = line.trim_end();
+        if let Some(name) = trimmed
+            .strip_prefix("---- ")
+            .and_then(|rest| rest.strip_suffix(" stdout ----"))
+        {
+            self.finish_output_section();
+            self.output_section = Some((name.to_string(), Vec::new()));
+            return;
+        }
+
+        if matches!(trimmed, "failures:" | "successes:") {
+            self.finish_output_section();
+            return;
+        }
+
+        if let Some((_, ref mut lines)) = self.output_section {
+            lines.push(line.to_string());
+        }
+    }
+
+    /// Attach the section being read to its test's result.
+    fn finish_output_section(&mut self) {
+        let Some((name, lines)) = self.output_section.take() else {
+            return;
+        };
+
+        let output = lines.join("\n");
+        let output = output.trim_start_matches('\n').trim_end();
+        if output.is_empty() {
+            return;
+        }
+
+        if let Some(result) = self
+            .test_results
+            .iter_mut()
+            .find(|result| result.qualified_name() == name)
+        {
+            result.stdout = Some(format!("{}\n", output));
+        }
+    }
+}
+
+/// Send `output` to whoever is following the run. Once they've hung up the
+/// run carries on without them.
+pub(crate) fn send_event(output_tx: Option<&EventSender>, output: &ProcessOutput) {
+    if let Some(tx) = output_tx
+        && tx.send(output.clone()).is_err()
+    {
+        tracing::trace!("Event receiver hung up, dropping {:?}", output.test());
+    }
+}
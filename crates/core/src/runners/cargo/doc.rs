@@ -0,0 +1,239 @@
+use std::io::{BufRead, BufReader, Read};
+use std::process::{Child, Command, Stdio};
+use std::sync::mpsc::{self, RecvTimeoutError};
+use std::time::Duration;
+
+use super::plan::parse_test_list;
+use super::{json_args, send_event};
+use crate::schemas::{PackageSelection, WorkspaceMetadata};
+use crate::suite::SuiteResult;
+use crate::test::{TestResult, TestStatus};
+use crate::{
+    CancellationToken, Error, EventMeta, EventSender, JsonLineParser, ProcessOutput, Result,
+    RunnerConfig, StandardLineParser, TestRunState,
+};
+
+/// Runs the documentation examples of workspace packages with
+/// `cargo test --doc`.
+///
+/// Packages are run one at a time, since cargo doesn't say which package
+/// the doctests it reports belong to, and each package's examples make up
+/// a suite of their own named by [`doc_suite_name`]. Only packages with a
+/// library have examples rustdoc tests.
+pub struct DocTestRunner {
+    config: RunnerConfig,
+    packages: PackageSelection,
+    output_tx: Option<EventSender>,
+}
+
+/// Name of the suite holding the documentation examples of `package`
+pub fn doc_suite_name(package: &str) -> String {
+    format!("{} (doc)", package)
+}
+
+impl DocTestRunner {
+    pub fn new(config: RunnerConfig, packages: PackageSelection) -> Self {
+        Self {
+            config,
+            packages,
+            output_tx: None,
+        }
+    }
+
+    pub fn new_with_output(
+        config: RunnerConfig,
+        packages: PackageSelection,
+        output_tx: impl Into<EventSender>,
+    ) -> Self {
+        Self {
+            output_tx: Some(output_tx.into()),
+            ..Self::new(config, packages)
+        }
+    }
+
+    /// The selected packages that have documentation examples to run,
+    /// ordered by name
+    pub fn packages(&self) -> Result<Vec<String>> {
+        let metadata = WorkspaceMetadata::load()?;
+        let mut packages: Vec<String> = metadata
+            .packages()
+            .filter(|package| package.library && self.packages.includes(&package.name))
+            .map(|package| package.name.clone())
+            .collect();
+        packages.sort();
+        Ok(packages)
+    }
+
+    /// Run the examples of every selected package until they're done or
+    /// `cancel` is triggered, returning a suite per package. In a dry run
+    /// they're only listed, as pending.
+    pub fn run(&self, cancel: &CancellationToken) -> Result<Vec<SuiteResult>> {
+        let mut suites = Vec::new();
+        for package in self.packages()? {
+            if cancel.is_cancelled() {
+                break;
+            }
+            let suite = if self.config.dry_run {
+                self.list_package(&package)?
+            } else {
+                self.run_package(&package, cancel)?
+            };
+            suites.push(suite);
+        }
+        Ok(suites)
+    }
+
+    fn command(&self, package: &str, test_args: &[String]) -> Command {
+        let mut command = Command::new("cargo");
+        command
+            .args(["test", "--doc", "--package", package, "--"])
+            .args(test_args)
+            .envs(&self.config.env)
+            .stdin(Stdio::null());
+        command
+    }
+
+    fn list_package(&self, package: &str) -> Result<SuiteResult> {
+        let args = ["--list", "--format", "terse"].map(String::from);
+        let output = self.command(package, &args).output()?;
+
+        let test_results: Vec<TestResult> =
+            parse_test_list(&String::from_utf8_lossy(&output.stdout))
+                .iter()
+                .map(|test| StandardLineParser::create_test_result(test, TestStatus::Pending))
+                .collect();
+        let mut suite_result =
+            StandardLineParser::create_suite_result(&doc_suite_name(package), &test_results);
+        if !output.status.success() {
+            suite_result.error = Some(build_failure(package, &output.stderr));
+        }
+        Ok(suite_result)
+    }
+
+    fn run_package(&self, package: &str, cancel: &CancellationToken) -> Result<SuiteResult> {
+        let suite_name = doc_suite_name(package);
+        let output_tx = self.output_tx.as_ref();
+
+        let mut command = self.command(package, &json_args());
+        command
+            .env("RUSTC_BOOTSTRAP", "1")
+            .stdout(Stdio::piped())
+            .stderr(Stdio::piped());
+        // A process group of its own, so cancelling kills the doctests
+        // along with cargo
+        #[cfg(unix)]
+        std::os::unix::process::CommandExt::process_group(&mut command, 0);
+
+        tracing::debug!("Running the doctests of {}", package);
+        let mut child = command.spawn().map_err(|e| {
+            Error::test_execution(format!("Failed to spawn cargo test --doc: {}", e))
+        })?;
+
+        // Build output is only wanted if the build fails, but has to be
+        // drained meanwhile so cargo doesn't stall on a full pipe
+        let stderr = child.stderr.take().map(|mut stderr| {
+            std::thread::spawn(move || {
+                let mut output = Vec::new();
+                let _ = stderr.read_to_end(&mut output);
+                output
+            })
+        });
+
+        let (line_tx, line_rx) = mpsc::channel();
+        let stdout = child.stdout.take().map(|stdout| {
+            std::thread::spawn(move || {
+                for line in BufReader::new(stdout).lines().map_while(|line| line.ok()) {
+                    if line_tx.send(line).is_err() {
+                        break;
+                    }
+                }
+            })
+        });
+
+        let mut state = TestRunState::for_suite(suite_name.clone());
+        let mut test_results = Vec::new();
+        loop {
+            if cancel.is_cancelled() {
+                kill(&mut child);
+                break;
+            }
+            let line = match line_rx.recv_timeout(Duration::from_millis(100)) {
+                Ok(line) => line,
+                Err(RecvTimeoutError::Timeout) => continue,
+                Err(RecvTimeoutError::Disconnected) => break,
+            };
+
+            let Ok(Some(parsed)) = JsonLineParser::parse_test_output(&line) else {
+                continue;
+            };
+            if let Some(output) = state.handle_line(parsed) {
+                send_event(output_tx, &output);
+                match output {
+                    ProcessOutput::TestPassed { result, .. }
+                    | ProcessOutput::TestFailed { result, .. }
+                    | ProcessOutput::TestSkipped { result, .. } => test_results.push(result),
+                    _ => {}
+                }
+            }
+        }
+
+        let status = child.wait()?;
+        if let Some(stdout) = stdout {
+            let _ = stdout.join();
+        }
+        let stderr = stderr
+            .and_then(|stderr| stderr.join().ok())
+            .unwrap_or_default();
+
+        // Doctests in flight when the run was cancelled
+        if let Some(reason) = cancel.reason() {
+            let error = Error::cancelled(reason.to_string());
+            for (name, _) in state.take_running() {
+                let mut test_result =
+                    StandardLineParser::create_test_result(&name, TestStatus::Skipped);
+                test_result.error = Some(error.clone());
+                send_event(
+                    output_tx,
+                    &ProcessOutput::TestSkipped {
+                        result: test_result.clone(),
+                        suite: suite_name.clone(),
+                        meta: EventMeta::default(),
+                    },
+                );
+                test_results.push(test_result);
+            }
+        }
+        state.finalize_pending_errors(&mut test_results);
+
+        let mut suite_result = StandardLineParser::create_suite_result(&suite_name, &test_results);
+        // Failing doctests fail cargo too, so only a failure without any
+        // failed doctest to show for it is the build's
+        if !status.success()
+            && !cancel.is_cancelled()
+            && !test_results.iter().any(TestResult::failed)
+        {
+            suite_result.error = Some(build_failure(package, &stderr));
+        }
+        Ok(suite_result)
+    }
+}
+
+fn kill(child: &mut Child) {
+    #[cfg(unix)]
+    unsafe {
+        libc::kill(-(child.id() as i32), libc::SIGKILL);
+    }
+    let _ = child.kill();
+}
+
+/// The error for doctests of `package` that cargo couldn't build, with the
+/// end of what it printed
+fn build_failure(package: &str, stderr: &[u8]) -> Error {
+    let stderr = String::from_utf8_lossy(stderr);
+    let lines: Vec<&str> = stderr.lines().collect();
+    let tail = lines[lines.len().saturating_sub(20)..].join("\n");
+    Error::test_execution(format!(
+        "Failed to build the doctests of {}:\n{}",
+        package, tail
+    ))
+}
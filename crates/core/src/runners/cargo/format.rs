@@ -0,0 +1,77 @@
+use serde::{Deserialize, Serialize};
+use std::fmt;
+use std::process::Stdio;
+use strum_macros::EnumString;
+
+use super::{Launcher, ResourceLimits};
+use crate::TestExecutable;
+
+/// How test executables are asked to report their results.
+///
+/// - `Json`: libtest's structured `--format json` output. It's unstable, so
+///   the executable is run with `RUSTC_BOOTSTRAP=1` to accept it on stable
+///   toolchains too.
+/// - `Pretty`: the default human-readable output, scraped line by line.
+/// - `Auto`: JSON when the executable accepts it, pretty output otherwise.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize, EnumString)]
+#[strum(serialize_all = "lowercase")]
+pub enum LibtestFormat {
+    #[default]
+    Auto,
+    Json,
+    Pretty,
+}
+
+impl LibtestFormat {
+    /// Whether `executable` should be run with JSON output
+    pub fn use_json(&self, launcher: &dyn Launcher, executable: &TestExecutable) -> bool {
+        match self {
+            LibtestFormat::Auto => supports_json(launcher, executable),
+            LibtestFormat::Json => true,
+            LibtestFormat::Pretty => false,
+        }
+    }
+}
+
+impl fmt::Display for LibtestFormat {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            LibtestFormat::Auto => write!(f, "auto"),
+            LibtestFormat::Json => write!(f, "json"),
+            LibtestFormat::Pretty => write!(f, "pretty"),
+        }
+    }
+}
+
+/// Arguments asking libtest for JSON output
+pub fn json_args() -> Vec<String> {
+    vec![
+        "--format=json".to_string(),
+        "--report-time".to_string(),
+        "-Z".to_string(),
+        "unstable-options".to_string(),
+    ]
+}
+
+/// Check whether an executable accepts `--format json` by listing its tests
+/// with it. Custom harnesses usually don't.
+pub fn supports_json(launcher: &dyn Launcher, executable: &TestExecutable) -> bool {
+    let args = ["--list", "--format=json", "-Z", "unstable-options"].map(String::from);
+    let Ok(mut command) = launcher.command(
+        executable,
+        &args,
+        &[("RUSTC_BOOTSTRAP", "1")],
+        &ResourceLimits::default(),
+    ) else {
+        return false;
+    };
+    let output = command.stdin(Stdio::null()).stderr(Stdio::null()).output();
+
+    match output {
+        Ok(output) if output.status.success() => String::from_utf8_lossy(&output.stdout)
+            .lines()
+            .next()
+            .is_some_and(|line| serde_json::from_str::<serde_json::Value>(line).is_ok()),
+        _ => false,
+    }
+}
@@ -0,0 +1,60 @@
+use std::process::Command;
+
+use super::ResourceLimits;
+use crate::{Result, TestExecutable};
+
+/// Decides where and how test executables are started.
+///
+/// Every process the cargo runner starts for an executable, whether to list
+/// its tests, probe its output format or run it, is built by the launcher,
+/// so the same runner can drive executables on this machine or elsewhere.
+pub trait Launcher: Send + Sync {
+    /// Make `executable` available to run. Called once per executable before
+    /// any command is built for it.
+    fn prepare(&self, _executable: &TestExecutable) -> Result<()> {
+        Ok(())
+    }
+
+    /// A command running `executable` with `args`, the environment variables
+    /// in `envs` and `limits` applied. Fails if `executable` wasn't prepared
+    /// and has to be.
+    fn command(
+        &self,
+        executable: &TestExecutable,
+        args: &[String],
+        envs: &[(&str, &str)],
+        limits: &ResourceLimits,
+    ) -> Result<Command>;
+
+    /// Release anything `prepare` set up. Called once the run is over,
+    /// whether or not it succeeded.
+    fn teardown(&self) {}
+}
+
+/// Runs executables directly on this machine, in their package's directory
+/// when it's known, as `cargo test` does.
+#[derive(Debug, Clone, Copy, Default)]
+pub struct LocalLauncher;
+
+impl Launcher for LocalLauncher {
+    fn command(
+        &self,
+        executable: &TestExecutable,
+        args: &[String],
+        envs: &[(&str, &str)],
+        limits: &ResourceLimits,
+    ) -> Result<Command> {
+        let mut command = Command::new(&executable.path);
+        if let Some(ref package_root) = executable.package_root {
+            command.current_dir(package_root);
+        }
+        command.args(args).envs(envs.iter().copied());
+        limits.apply(&mut command);
+        Ok(command)
+    }
+}
+
+/// Quote `value` for a POSIX shell, for launchers that run commands through one.
+pub fn shell_quote(value: &str) -> String {
+    format!("'{}'", value.replace('\'', r"'\''"))
+}
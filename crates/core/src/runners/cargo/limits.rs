@@ -0,0 +1,106 @@
+use serde::{Deserialize, Serialize};
+use std::process::{Command, ExitStatus};
+use std::time::Duration;
+
+use crate::Error;
+
+/// OS-level limits applied to each spawned test executable.
+///
+/// On Unix these are set with `setrlimit` in the child before it execs, so
+/// they bound the whole test binary (libtest runs its tests as threads of a
+/// single process). Limits are ignored on other platforms.
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct ResourceLimits {
+    /// Maximum address space of the process, in bytes
+    pub memory: Option<u64>,
+    /// Maximum CPU time the process may consume
+    pub cpu_time: Option<Duration>,
+}
+
+impl ResourceLimits {
+    pub fn is_empty(&self) -> bool {
+        self.memory.is_none() && self.cpu_time.is_none()
+    }
+
+    /// Install the limits on `command`, to take effect in the spawned child.
+    pub fn apply(&self, command: &mut Command) {
+        if self.is_empty() {
+            return;
+        }
+
+        #[cfg(unix)]
+        {
+            use std::os::unix::process::CommandExt;
+
+            let limits = self.clone();
+            // SAFETY: the closure only calls `setrlimit`, which is
+            // async-signal-safe, and doesn't allocate.
+            unsafe {
+                command.pre_exec(move || limits.set_rlimits());
+            }
+        }
+    }
+
+    #[cfg(unix)]
+    fn set_rlimits(&self) -> std::io::Result<()> {
+        if let Some(bytes) = self.memory {
+            set_rlimit(libc::RLIMIT_AS, bytes, bytes)?;
+        }
+
+        if let Some(cpu_time) = self.cpu_time {
+            // The soft limit delivers SIGXCPU, which we can recognise; the
+            // hard limit is a SIGKILL backstop in case it's ignored.
+            let seconds = cpu_time.as_secs().max(1);
+            set_rlimit(libc::RLIMIT_CPU, seconds, seconds + 1)?;
+        }
+
+        Ok(())
+    }
+
+    /// Work out whether `status` means the child was stopped for exceeding
+    /// one of these limits.
+    pub fn exhaustion(&self, status: &ExitStatus) -> Option<Error> {
+        #[cfg(unix)]
+        {
+            use std::os::unix::process::ExitStatusExt;
+
+            match (status.signal(), self.memory, self.cpu_time) {
+                (Some(libc::SIGXCPU), _, Some(cpu_time))
+                | (Some(libc::SIGKILL), _, Some(cpu_time)) => Some(Error::resource_exhausted(
+                    format!("CPU time limit of {:?} exceeded", cpu_time),
+                )),
+                // Rust aborts the process when an allocation fails
+                (Some(libc::SIGABRT), Some(bytes), _) => Some(Error::resource_exhausted(format!(
+                    "Memory limit of {} bytes exceeded",
+                    bytes
+                ))),
+                _ => None,
+            }
+        }
+
+        #[cfg(not(unix))]
+        {
+            let _ = status;
+            None
+        }
+    }
+}
+
+#[cfg(all(target_os = "linux", target_env = "gnu"))]
+type RlimitResource = libc::__rlimit_resource_t;
+#[cfg(all(unix, not(all(target_os = "linux", target_env = "gnu"))))]
+type RlimitResource = libc::c_int;
+
+#[cfg(unix)]
+fn set_rlimit(resource: RlimitResource, soft: u64, hard: u64) -> std::io::Result<()> {
+    let limit = libc::rlimit {
+        rlim_cur: soft as libc::rlim_t,
+        rlim_max: hard as libc::rlim_t,
+    };
+
+    if unsafe { libc::setrlimit(resource, &limit) } != 0 {
+        return Err(std::io::Error::last_os_error());
+    }
+
+    Ok(())
+}
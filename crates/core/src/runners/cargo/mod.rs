@@ -1,22 +1,42 @@
+mod child;
+use child::{ChildRun, send_event};
+
+pub mod doc;
+pub use doc::*;
+
+pub mod format;
+pub use format::*;
+
+pub mod launcher;
+pub use launcher::*;
+
+pub mod limits;
+pub use limits::*;
+
+pub mod plan;
+pub use plan::*;
+
 pub mod utils;
 pub use utils::*;
 
-use mio::Interest;
-use mio::unix::pipe;
 use mio::{Events, Poll};
 use serde::{Deserialize, Serialize};
-use std::process::{Command, Stdio};
-use std::sync::mpsc::Sender;
+use std::collections::HashSet;
+use std::sync::Arc;
 use std::time::Duration;
 use uuid::Uuid;
 
+use crate::cache::{ResultCache, RunCache, env_fingerprint, hash_file};
+use crate::random;
 use crate::{
-    Error, Result, RunnerConfig, TestRunner, TestSuite, runners::RunResult, suite::SuiteResult,
-    test::TestResult,
+    CancelReason, CancellationToken, Error, Result, RunnerConfig, TestRunner, TestSuite,
+    runners::{RunResult, repeat_until_failure, run_suites},
+    suite::SuiteResult,
+    test::{TestResult, TestStatus},
 };
 use crate::{
-    JsonLineParser, LineBuffer, ProcessOutput, STDERR_TOKEN, STDOUT_TOKEN, StandardLineParser,
-    TestExecutable, TestMetadata, TestRunState,
+    EventMeta, EventSender, ProcessOutput, StandardLineParser, TestExecutable, TestMetadata,
+    test_id, token_slot,
 };
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
@@ -26,7 +46,74 @@ pub struct CargoRunnerConfig {
     pub executable_timeout: Option<Duration>,
     pub capture_output: bool,
     pub cargo_args: Vec<String>,
+    /// Arguments passed on to each test executable's harness, e.g.
+    /// `--nocapture` or `--test-threads 1`, after those the runner sets
     pub test_args: Vec<String>,
+    /// Skip tests that already passed against an identical binary and environment
+    #[serde(default)]
+    pub result_cache: bool,
+    #[serde(default)]
+    pub limits: ResourceLimits,
+    /// Number of test executables to run at the same time
+    #[serde(default = "default_jobs")]
+    pub jobs: usize,
+    #[serde(default)]
+    pub format: LibtestFormat,
+    /// Only run the tests matching one of these filters
+    #[serde(default)]
+    pub filters: Vec<TestFilter>,
+    /// Only run these `(suite, test)` pairs, as named in an earlier run's
+    /// results
+    #[serde(default)]
+    pub only_tests: Option<HashSet<(String, String)>>,
+}
+
+fn default_jobs() -> usize {
+    1
+}
+
+/// Harness arguments the runner sets itself, as it reads the output they
+/// shape
+const RUNNER_TEST_ARGS: [&str; 5] = ["--format", "--list", "--logfile", "--quiet", "-q"];
+
+/// Switches the runner may set that are harmless to ask for again, and are
+/// left out of `test_args` when it does
+const RUNNER_SWITCHES: [&str; 3] = ["--exact", "--show-output", "--report-time"];
+
+/// Append `test_args` to the harness arguments the runner set, leaving out
+/// those it already gave: libtest rejects an option given twice.
+pub(crate) fn append_test_args(harness_args: &mut Vec<String>, test_args: &[String]) {
+    let unstable = harness_args.iter().any(|arg| arg == "-Z");
+    let mut args = test_args.iter();
+    while let Some(arg) = args.next() {
+        match arg.as_str() {
+            // The runner only ever asks for `-Z unstable-options`, the one
+            // value libtest takes
+            "-Z" if unstable => {
+                args.next();
+            }
+            "-Zunstable-options" if unstable => {}
+            switch if RUNNER_SWITCHES.contains(&switch) && harness_args.contains(arg) => {}
+            _ => harness_args.push(arg.clone()),
+        }
+    }
+}
+
+impl CargoRunnerConfig {
+    /// Check that `test_args` leave the harness arguments the runner relies
+    /// on alone
+    pub fn check_test_args(&self) -> Result<()> {
+        for arg in &self.test_args {
+            let name = arg.split_once('=').map_or(arg.as_str(), |(name, _)| name);
+            if RUNNER_TEST_ARGS.contains(&name) {
+                return Err(Error::runner_config(format!(
+                    "{} is set by sheila and can't be passed to the test executables",
+                    name
+                )));
+            }
+        }
+        Ok(())
+    }
 }
 
 impl Default for CargoRunnerConfig {
@@ -41,6 +128,12 @@ impl Default for CargoRunnerConfig {
             capture_output: true,
             cargo_args: vec![],
             test_args: vec![],
+            result_cache: false,
+            limits: ResourceLimits::default(),
+            jobs: default_jobs(),
+            format: LibtestFormat::default(),
+            filters: Vec::new(),
+            only_tests: None,
         }
     }
 }
@@ -48,10 +141,10 @@ impl Default for CargoRunnerConfig {
 pub struct CargoTestRunner {
     pub poll: Poll,
     pub events: Events,
-    pub state: TestRunState,
-    output_tx: Option<Sender<ProcessOutput>>,
+    output_tx: Option<EventSender>,
     config: RunnerConfig,
     cargo_config: CargoRunnerConfig,
+    launcher: Arc<dyn Launcher>,
 }
 
 impl CargoTestRunner {
@@ -60,20 +153,20 @@ impl CargoTestRunner {
             config,
             poll: Poll::new().unwrap(),
             events: Events::with_capacity(1024),
-            state: TestRunState::new(),
             cargo_config: CargoRunnerConfig::default(),
             output_tx: None,
+            launcher: Arc::new(LocalLauncher),
         }
     }
 
-    pub fn new_with_output(config: RunnerConfig, output_tx: Sender<ProcessOutput>) -> Self {
+    pub fn new_with_output(config: RunnerConfig, output_tx: impl Into<EventSender>) -> Self {
         Self {
             config,
             poll: Poll::new().unwrap(),
             events: Events::with_capacity(1024),
-            state: TestRunState::new(),
             cargo_config: CargoRunnerConfig::default(),
-            output_tx: Some(output_tx),
+            output_tx: Some(output_tx.into()),
+            launcher: Arc::new(LocalLauncher),
         }
     }
 
@@ -82,227 +175,516 @@ impl CargoTestRunner {
         self
     }
 
+    /// Start executables through `launcher` instead of directly on this machine.
+    pub fn with_launcher<L: Launcher + 'static>(mut self, launcher: L) -> Self {
+        self.launcher = Arc::new(launcher);
+        self
+    }
+
     fn args(&self) -> Vec<String> {
-        let mut test_args = vec![
-            "--format=json".to_string(),
-            "--report-time".to_string(),
-            "-Z".to_string(),
-            "unstable-options".to_string(),
-        ];
-        test_args.extend_from_slice(&self.cargo_config.test_args);
-        test_args
+        self.cargo_config.test_args.clone()
     }
 
-    pub fn execute_tests(&mut self, executables: &[TestExecutable]) -> Result<RunResult> {
-        let mut result = RunResult::new(self.config.clone());
+    /// Decide which tests of each executable should run. Executables are run
+    /// whole unless a selection (test filters, only-failed, sharding, result
+    /// cache) requires listing their tests.
+    pub fn plan(&self, executables: &[TestExecutable]) -> Result<Vec<PlannedExecutable>> {
+        let mut plan = self.select_tests(executables)?;
+
+        for planned in plan.iter_mut() {
+            planned.json = self
+                .cargo_config
+                .format
+                .use_json(self.launcher.as_ref(), &planned.executable);
+        }
 
-        for executable in executables {
-            let suite_result = match self.exec_test(executable.clone()) {
-                Ok(result) => result,
-                Err(e) => StandardLineParser::create_suite_result(
-                    &executable.name,
-                    &[TestResult::new(
-                        Uuid::new_v4(),
-                        format!("{}_system_error", executable.name),
-                        TestMetadata::new(format!("{} (system error)", e)),
-                    )],
-                ),
-            };
+        if self.cargo_config.result_cache {
+            let cache = RunCache::current()?.load_result_cache()?;
+            let fingerprint = self.env_fingerprint();
+
+            for planned in plan.iter_mut() {
+                let Some(ref mut tests) = planned.tests else {
+                    continue;
+                };
+                let Ok(binary_hash) = hash_file(&planned.executable.path) else {
+                    continue;
+                };
+
+                planned.binary_hash = Some(binary_hash);
+                let suite = format_mod_name(&planned.executable.name);
+                let (cached, to_run): (Vec<String>, Vec<String>) =
+                    tests.drain(..).partition(|test| {
+                        let key = ResultCache::key(binary_hash, test_id(&suite, test), fingerprint);
+                        cache.contains(&key)
+                    });
+                *tests = to_run;
+                planned.cached = cached;
+            }
+        }
 
-            let all_passed = suite_result.all_passed();
-            result.add_suite_result(suite_result);
+        Ok(plan)
+    }
 
-            if self.config.fail_fast && !all_passed {
-                result.finish(Some(Error::test_execution(
-                    "Failing fast due to test failure",
-                )));
-                break;
+    fn env_fingerprint(&self) -> u64 {
+        env_fingerprint(&self.config.env, &self.args())
+    }
+
+    fn select_tests(&self, executables: &[TestExecutable]) -> Result<Vec<PlannedExecutable>> {
+        let only_tests = match self.cargo_config.only_tests {
+            Some(ref tests) => Some(tests.clone()),
+            None if self.config.only_failed => RunCache::current()?.last_failed()?,
+            None => None,
+        };
+
+        let filters = &self.cargo_config.filters;
+        if filters.is_empty()
+            && only_tests.is_none()
+            && self.config.shard.is_none()
+            && !self.cargo_config.result_cache
+        {
+            return Ok(executables
+                .iter()
+                .cloned()
+                .map(PlannedExecutable::all)
+                .collect());
+        }
+
+        // Executables whose tests can't be listed are treated as a single unit
+        let mut listed = Vec::new();
+        for (index, executable) in executables.iter().enumerate() {
+            match list_executable_tests(self.launcher.as_ref(), executable) {
+                Ok(tests) => listed.extend(tests.into_iter().map(|test| (index, Some(test)))),
+                Err(e) => {
+                    tracing::debug!("{}, running it as a whole", e);
+                    listed.push((index, None));
+                }
             }
         }
 
-        if result.error.is_none() {
-            result.finish(None);
+        // Filters name tests, so executables that can't be listed are dropped
+        if !filters.is_empty() {
+            listed.retain(|(index, test)| {
+                test.as_ref().is_some_and(|test| {
+                    filters
+                        .iter()
+                        .any(|filter| filter.matches(&executables[*index].name, test))
+                })
+            });
         }
 
-        Ok(result)
+        if let Some(ref only) = only_tests {
+            listed.retain(|(index, test)| {
+                let suite = format_mod_name(&executables[*index].name);
+                match test {
+                    Some(test) => only.contains(&(suite, test.clone())),
+                    None => only.iter().any(|(only_suite, _)| *only_suite == suite),
+                }
+            });
+        }
+
+        let selected = match self.config.shard {
+            Some(ref shard) => shard.select(listed, |(index, test)| match test {
+                Some(test) => format!("{}::{}", executables[*index].name, test),
+                None => executables[*index].name.clone(),
+            }),
+            None => listed,
+        };
+
+        Ok(executables
+            .iter()
+            .enumerate()
+            .map(|(index, executable)| {
+                let mut tests = Vec::new();
+                for (_, test) in selected.iter().filter(|(i, _)| *i == index) {
+                    match test {
+                        Some(test) => tests.push(test.clone()),
+                        None => return PlannedExecutable::all(executable.clone()),
+                    }
+                }
+                PlannedExecutable::with_tests(executable.clone(), tests)
+            })
+            .collect())
     }
 
-    pub fn exec_test(&mut self, bin: TestExecutable) -> Result<SuiteResult> {
-        let test_args = self.args();
+    /// Report the tests `plan` would run as pending results, without running
+    /// them. Durations are estimated from the previous run where possible.
+    fn dry_run(&self, plan: &[PlannedExecutable]) -> Result<RunResult> {
+        let history = RunCache::current()?.duration_history().unwrap_or_default();
+        let mut result = RunResult::new(self.config.clone());
+
+        for planned in plan {
+            // An executable that can't be listed is still reported, without tests
+            let (tests, error) = match planned.tests {
+                Some(ref tests) => (tests.clone(), None),
+                None => match list_executable_tests(self.launcher.as_ref(), &planned.executable) {
+                    Ok(tests) => (tests, None),
+                    Err(e) => (Vec::new(), Some(e)),
+                },
+            };
+
+            let mut test_results: Vec<TestResult> = tests
+                .iter()
+                .map(|test| StandardLineParser::create_test_result(test, TestStatus::Pending))
+                .collect();
+            let suite_name = format_mod_name(&planned.executable.name);
+            for test_result in test_results.iter_mut() {
+                test_result.duration = history
+                    .get(&(suite_name.clone(), test_result.qualified_name().to_string()))
+                    .copied();
+            }
 
-        let mut child = Command::new(&bin.path)
-            .args(&test_args)
-            .env("RUST_TEST_NOCAPTURE", "1")
-            .stdout(Stdio::piped())
-            .stderr(Stdio::piped())
-            .spawn()?;
+            let mut suite_result =
+                StandardLineParser::create_suite_result(&planned.executable.name, &test_results);
+            for test in planned.cached.iter() {
+                suite_result.add_test_result(StandardLineParser::create_test_result(
+                    test,
+                    TestStatus::Cached,
+                ));
+            }
+            suite_result.error = error;
+            result.add_suite_result(suite_result);
+        }
 
-        let stdout = child.stdout.take().unwrap();
-        let stderr = child.stderr.take().unwrap();
-        let mut stdout_pipe = pipe::Receiver::from(stdout);
-        let mut stderr_pipe = pipe::Receiver::from(stderr);
+        result.finish(None);
+        Ok(result)
+    }
 
-        stdout_pipe.set_nonblocking(true)?;
-        stderr_pipe.set_nonblocking(true)?;
+    pub fn execute_tests(&mut self, executables: &[TestExecutable]) -> Result<RunResult> {
+        self.execute_tests_with_cancel(executables, &CancellationToken::new())
+    }
 
-        self.poll
-            .registry()
-            .register(&mut stdout_pipe, STDOUT_TOKEN, Interest::READABLE)?;
+    /// Runs the planned executables, up to `jobs` at a time, until they
+    /// finish or `cancel` is triggered. On cancellation running children are
+    /// killed, tests they had in flight or hadn't started are marked as
+    /// skipped and the partial result is returned. With `fail_fast` set, the
+    /// first failing test cancels the run with [`CancelReason::FailFast`].
+    pub fn execute_tests_with_cancel(
+        &mut self,
+        executables: &[TestExecutable],
+        cancel: &CancellationToken,
+    ) -> Result<RunResult> {
+        let result = self.execute_prepared(executables, cancel);
+        self.launcher.teardown();
+        result
+    }
 
-        self.poll
-            .registry()
-            .register(&mut stderr_pipe, STDERR_TOKEN, Interest::READABLE)?;
+    fn execute_prepared(
+        &mut self,
+        executables: &[TestExecutable],
+        cancel: &CancellationToken,
+    ) -> Result<RunResult> {
+        self.cargo_config.check_test_args()?;
+        for executable in executables {
+            self.launcher.prepare(executable)?;
+        }
 
-        let mut stdout_buf = LineBuffer::new(stdout_pipe);
-        let mut stderr_buf = LineBuffer::new(stderr_pipe);
+        let plan: Vec<PlannedExecutable> = self
+            .plan(executables)?
+            .into_iter()
+            .filter(|planned| !planned.is_empty())
+            .collect();
 
-        let mut test_results = Vec::new();
+        if self.config.dry_run {
+            return self.dry_run(&plan);
+        }
 
-        loop {
-            self.poll
-                .poll(&mut self.events, Some(Duration::from_millis(100)))?;
+        let _budget = self.config.start_budget(cancel);
+        match self.config.repeat {
+            Some(mode) => repeat_until_failure(mode, cancel, || self.execute_plan(&plan, cancel)),
+            None => self.execute_plan(&plan, cancel),
+        }
+    }
 
-            for event in self.events.iter() {
-                match event.token() {
-                    STDOUT_TOKEN => {
-                        while let Some(line) = stdout_buf.read_line()? {
-                            match JsonLineParser::parse_test_output(&line) {
-                                Ok(Some(parsed)) => {
-                                    if let Some(output) = self.state.handle_line(parsed) {
-                                        self.send_event(&output);
-
-                                        match output {
-                                            ProcessOutput::TestPassed { result, .. }
-                                            | ProcessOutput::TestFailed { result, .. }
-                                            | ProcessOutput::TestSkipped { result } => {
-                                                test_results.push(result);
-                                            }
-                                            _ => {}
-                                        }
-                                    }
-                                }
-                                Ok(None) => {}
-                                Err(_) => {
-                                    if let Ok((_, parsed)) =
-                                        StandardLineParser::parse_test_output(&line)
-                                    {
-                                        if let Some(output) = self.state.handle_line(parsed) {
-                                            self.send_event(&output);
-
-                                            match output {
-                                                ProcessOutput::TestPassed { result, .. }
-                                                | ProcessOutput::TestFailed { result, .. }
-                                                | ProcessOutput::TestSkipped { result } => {
-                                                    test_results.push(result);
-                                                }
-                                                _ => {}
-                                            }
-                                        }
-                                    }
-                                }
-                            }
-                        }
-                    }
-                    STDERR_TOKEN => {
-                        while let Some(panic_group) = stderr_buf.read_panic_group() {
-                            match StandardLineParser::parse_error_output(&panic_group) {
-                                Ok(parsed) => {
-                                    let output = match self.state.handle_line(parsed) {
-                                        Some(output) => output,
-                                        None => continue,
-                                    };
-                                    self.send_event(&output);
-                                }
-                                Err(_) => {
-                                    continue;
-                                }
-                            }
-                        }
+    /// Run `plan` once, updating the result cache with the outcome.
+    fn execute_plan(
+        &mut self,
+        plan: &[PlannedExecutable],
+        cancel: &CancellationToken,
+    ) -> Result<RunResult> {
+        let mut result = RunResult::new(self.config.clone());
+        let mut result_cache = if self.cargo_config.result_cache {
+            Some(RunCache::current()?.load_result_cache()?)
+        } else {
+            None
+        };
+        let fingerprint = self.env_fingerprint();
+
+        let mut completed = self.run_plan(plan, cancel)?;
+        completed.sort_by_key(|(index, _)| *index);
+
+        for (index, mut suite_result) in completed {
+            let planned = &plan[index];
+
+            if let (Some(cache), Some(binary_hash)) = (result_cache.as_mut(), planned.binary_hash) {
+                for test_result in suite_result.test_results.iter() {
+                    let key = ResultCache::key(binary_hash, test_result.stable_id, fingerprint);
+                    if test_result.status == TestStatus::Passed {
+                        cache.record(key, &suite_result.name, test_result.qualified_name());
+                    } else {
+                        cache.remove(&key);
                     }
-                    _ => unreachable!(),
                 }
             }
 
-            match child.try_wait()? {
-                Some(_status) => {
-                    self.flush_buffers(&mut stdout_buf, &mut stderr_buf, &mut test_results)?;
-
-                    return Ok(StandardLineParser::create_suite_result(
-                        &bin.name,
-                        &test_results,
-                    ));
-                }
-                None => continue,
+            for test in planned.cached.iter() {
+                let test_result = StandardLineParser::create_test_result(test, TestStatus::Cached);
+                send_event(
+                    self.output_tx.as_ref(),
+                    &ProcessOutput::TestSkipped {
+                        result: test_result.clone(),
+                        suite: suite_result.name.clone(),
+                        meta: EventMeta::default(),
+                    },
+                );
+                suite_result.add_test_result(test_result);
             }
+
+            result.add_suite_result(suite_result);
         }
-    }
 
-    fn send_event(&self, output: &ProcessOutput) {
-        if let Some(ref tx) = self.output_tx {
-            if let Err(e) = tx.send(output.clone()) {
-                dbg!(&e);
-            }
+        if let Some(ref cache) = result_cache {
+            RunCache::current()?.save_result_cache(cache)?;
         }
+
+        result.finish(cancel.reason().map(CancelReason::run_error));
+        Ok(result)
     }
 
-    fn flush_buffers(
+    pub fn exec_test(
         &mut self,
-        stdout_buf: &mut LineBuffer<pipe::Receiver>,
-        stderr_buf: &mut LineBuffer<pipe::Receiver>,
-        test_results: &mut Vec<TestResult>,
-    ) -> Result<()> {
-        if let Some(line) = stdout_buf.flush_remaining() {
-            match JsonLineParser::parse_test_output(&line) {
-                Ok(Some(parsed)) => {
-                    if let Some(output) = self.state.handle_line(parsed) {
-                        self.send_event(&output);
-
-                        match output {
-                            ProcessOutput::TestPassed { result, .. }
-                            | ProcessOutput::TestFailed { result, .. }
-                            | ProcessOutput::TestSkipped { result } => {
-                                test_results.push(result);
-                            }
-                            _ => {}
+        planned: &PlannedExecutable,
+        cancel: &CancellationToken,
+    ) -> Result<SuiteResult> {
+        let mut completed = self.run_plan(std::slice::from_ref(planned), cancel)?;
+
+        Ok(match completed.pop() {
+            Some((_, suite_result)) => suite_result,
+            None => StandardLineParser::create_suite_result(&planned.executable.name, &[]),
+        })
+    }
+
+    /// Run the plan over a single poll loop, keeping up to `jobs` children in
+    /// flight. Returns each executable's result along with its index in
+    /// `plan`, in completion order. Executables that never started because
+    /// the run was cancelled are included with their tests skipped. While
+    /// `cancel` is paused no executables start and running ones are
+    /// suspended.
+    fn run_plan(
+        &mut self,
+        plan: &[PlannedExecutable],
+        cancel: &CancellationToken,
+    ) -> Result<Vec<(usize, SuiteResult)>> {
+        let jobs = self.cargo_config.jobs.max(1);
+        let args = self.args();
+        let output_tx = self.output_tx.as_ref();
+
+        // Left out of the environment fingerprint, so cached results hold
+        // across runs with different seeds
+        let mut env = self.config.env.clone();
+        if let Some(seed) = self.config.seed {
+            env.insert(random::SEED_ENV.to_string(), seed.to_string());
+        }
+
+        let mut queue = plan.iter().enumerate();
+        let mut running: Vec<Option<(usize, ChildRun)>> = (0..jobs).map(|_| None).collect();
+        let mut completed = Vec::new();
+        let mut suspended = false;
+
+        loop {
+            if cancel.is_paused() != suspended {
+                suspended = !suspended;
+                for (_, child) in running.iter_mut().flatten() {
+                    child.set_suspended(suspended);
+                }
+            }
+
+            if !cancel.is_cancelled() && !suspended {
+                for (slot, entry) in running.iter_mut().enumerate() {
+                    if entry.is_some() {
+                        continue;
+                    }
+
+                    let Some((index, planned)) = queue.next() else {
+                        break;
+                    };
+
+                    if !planned.has_tests_to_run() {
+                        completed.push((
+                            index,
+                            StandardLineParser::create_suite_result(&planned.executable.name, &[]),
+                        ));
+                        continue;
+                    }
+
+                    match ChildRun::spawn(
+                        planned.clone(),
+                        self.launcher.as_ref(),
+                        &args,
+                        &env,
+                        &self.cargo_config,
+                        self.poll.registry(),
+                        slot,
+                    ) {
+                        Ok(child) => *entry = Some((index, child)),
+                        Err(e) => {
+                            completed.push((index, system_error_suite(&planned.executable.name, e)))
                         }
                     }
                 }
-                Ok(None) | Err(_) => {
-                    if let Ok((_, parsed)) = StandardLineParser::parse_test_output(&line) {
-                        if let Some(output) = self.state.handle_line(parsed) {
-                            self.send_event(&output);
-
-                            match output {
-                                ProcessOutput::TestPassed { result, .. }
-                                | ProcessOutput::TestFailed { result, .. }
-                                | ProcessOutput::TestSkipped { result } => {
-                                    test_results.push(result);
-                                }
-                                _ => {}
-                            }
+            }
+
+            if running.iter().all(Option::is_none) {
+                if cancel.is_cancelled() || queue.len() == 0 {
+                    break;
+                }
+                if suspended {
+                    std::thread::sleep(Duration::from_millis(100));
+                }
+                continue;
+            }
+
+            self.poll
+                .poll(&mut self.events, Some(Duration::from_millis(100)))?;
+
+            // Read stderr before stdout so panic messages are known by the
+            // time libtest reports the failure they belong to
+            for read_stdout in [false, true] {
+                for event in self.events.iter() {
+                    let (slot, is_stdout) = token_slot(event.token());
+                    if is_stdout != read_stdout {
+                        continue;
+                    }
+
+                    if let Some((_, child)) = running.get_mut(slot).and_then(Option::as_mut) {
+                        if is_stdout {
+                            child.read_stdout(output_tx)?;
+                        } else {
+                            child.read_stderr(output_tx)?;
                         }
                     }
                 }
             }
-        }
 
-        if let Some(line) = stderr_buf.flush_remaining() {
-            match StandardLineParser::parse_error_output(&line) {
-                Ok(parsed) => {
-                    if let Some(output) = self.state.handle_line(parsed) {
-                        self.send_event(&output);
+            for slot in running.iter_mut() {
+                let Some((_, child)) = slot.as_mut() else {
+                    continue;
+                };
+
+                // Stop everything as soon as a test fails rather than
+                // waiting for its executable to finish
+                if self.config.fail_fast && child.has_failures() {
+                    cancel.cancel(CancelReason::FailFast);
+                }
+
+                if let Some(reason) = cancel.reason() {
+                    child.kill();
+                    child.flush(self.poll.registry(), output_tx);
+                    let error = Error::cancelled(reason.to_string());
+                    child.abort_running(TestStatus::Skipped, error.clone(), output_tx);
+                    let tests = self.planned_tests(&child.planned);
+                    child.abort_unreported(&tests, TestStatus::Skipped, error, output_tx);
+                } else if let Some(timeout) = self.cargo_config.executable_timeout
+                    && child.running_time() >= timeout
+                {
+                    child.kill();
+                    child.flush(self.poll.registry(), output_tx);
+                    let error = Error::timeout(format!(
+                        "{} exceeded the executable timeout of {:?} and was killed",
+                        child.name(),
+                        timeout
+                    ));
+                    child.abort_running(TestStatus::Timeout, error, output_tx);
+                } else if let Some(status) = child.try_wait()? {
+                    child.flush(self.poll.registry(), output_tx);
+                    // libtest exits 101 when tests fail, but also when it
+                    // rejects its arguments or a test aborts the process
+                    if !status.success() {
+                        let tests = self.planned_tests(&child.planned);
+                        child.fail_exit(&tests, &status, &self.cargo_config.limits, output_tx);
                     }
+                } else {
+                    continue;
+                }
+
+                let (index, child) = slot.take().unwrap();
+                send_event(
+                    output_tx,
+                    &ProcessOutput::SuiteCompleted {
+                        name: child.name().to_string(),
+                        meta: EventMeta::default(),
+                    },
+                );
+                send_event(
+                    output_tx,
+                    &ProcessOutput::Done {
+                        name: child.name().to_string(),
+                        meta: EventMeta::default(),
+                    },
+                );
+                let suite_result = child.into_suite_result();
+                if self.config.fail_fast && !suite_result.all_passed() {
+                    cancel.cancel(CancelReason::FailFast);
                 }
-                Err(_) => {}
+                completed.push((index, suite_result));
             }
         }
 
-        self.state.finalize_pending_errors(test_results);
+        // Report the tests of executables that never got to start
+        if let Some(reason) = cancel.reason() {
+            let error = Error::cancelled(reason.to_string());
+            for (index, planned) in queue {
+                let test_results: Vec<TestResult> = self
+                    .planned_tests(planned)
+                    .iter()
+                    .map(|test| {
+                        let mut test_result =
+                            StandardLineParser::create_test_result(test, TestStatus::Skipped);
+                        test_result.error = Some(error.clone());
+                        send_event(
+                            output_tx,
+                            &ProcessOutput::TestSkipped {
+                                result: test_result.clone(),
+                                suite: planned.executable.name.clone(),
+                                meta: EventMeta::default(),
+                            },
+                        );
+                        test_result
+                    })
+                    .collect();
+                completed.push((
+                    index,
+                    StandardLineParser::create_suite_result(
+                        &planned.executable.name,
+                        &test_results,
+                    ),
+                ));
+            }
+        }
 
-        Ok(())
+        Ok(completed)
+    }
+
+    /// The tests `planned` would run, listing them if the plan didn't
+    fn planned_tests(&self, planned: &PlannedExecutable) -> Vec<String> {
+        match planned.tests {
+            Some(ref tests) => tests.clone(),
+            None => list_executable_tests(self.launcher.as_ref(), &planned.executable)
+                .unwrap_or_default(),
+        }
     }
 }
 
+fn system_error_suite(name: &str, error: Error) -> SuiteResult {
+    StandardLineParser::create_suite_result(
+        name,
+        &[TestResult::new(
+            Uuid::new_v4(),
+            format!("{}_system_error", name),
+            TestMetadata::new(format!("{} (system error)", error)),
+        )],
+    )
+}
+
 impl Default for CargoTestRunner {
     fn default() -> Self {
         Self::new(RunnerConfig::default())
@@ -310,40 +692,18 @@ impl Default for CargoTestRunner {
 }
 
 impl TestRunner for CargoTestRunner {
-    fn run(&self, suites: Vec<TestSuite>) -> Result<RunResult> {
-        let mut result = RunResult::new(self.config.clone());
-        let suites_to_run = self.filter_suites(suites);
-        if suites_to_run.is_empty() {
-            result.finish(None);
-            return Ok(result);
-        }
-
-        for mut suite in suites_to_run {
-            match suite.execute() {
-                Ok(suite_result) => {
-                    let should_fail_fast = self.config.fail_fast && !suite_result.all_passed();
-                    result.add_suite_result(suite_result);
-
-                    if should_fail_fast {
-                        result.finish(Some(Error::test_execution(
-                            "Failing fast due to test failure",
-                        )));
-                        return Ok(result);
-                    }
-                }
-                Err(e) => {
-                    result.finish(Some(e));
-                    return Ok(result);
-                }
-            }
-        }
-
-        result.finish(None);
-        Ok(result)
+    fn run_with_cancel(
+        &self,
+        suites: Vec<TestSuite>,
+        cancel: &CancellationToken,
+    ) -> Result<RunResult> {
+        let mut suites_to_run = self.filter_suites(suites);
+        let _budget = self.config.start_budget(cancel);
+        run_suites(&self.config, &mut suites_to_run, cancel)
     }
 
     fn run_suite(&self, mut suite: TestSuite) -> Result<SuiteResult> {
-        suite.execute()
+        suite.execute_with_config(&self.config)
     }
 
     fn config(&self) -> &RunnerConfig {
@@ -354,3 +714,131 @@ impl TestRunner for CargoTestRunner {
         self.config = config;
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::path::PathBuf;
+    use std::process::Command;
+
+    /// Runs a shell script in place of the test executable, with the
+    /// harness arguments as its own
+    struct Script(&'static str);
+
+    impl Launcher for Script {
+        fn command(
+            &self,
+            _executable: &TestExecutable,
+            args: &[String],
+            _envs: &[(&str, &str)],
+            _limits: &ResourceLimits,
+        ) -> Result<Command> {
+            let mut command = Command::new("sh");
+            command.args(["-c", self.0, "script"]).args(args);
+            Ok(command)
+        }
+    }
+
+    /// Rejects options given twice or unknown ones, as libtest does, and
+    /// otherwise passes test `a`
+    const HARNESS: &str = r#"
+        for arg in "$@"; do
+            case "$arg" in --bogus*) echo "error: Unrecognized option" >&2; exit 101;; esac
+            case " $seen " in *" $arg "*) echo "error: Option given more than once" >&2; exit 101;; esac
+            seen="$seen $arg"
+        done
+        echo '{ "type": "test", "event": "started", "name": "a" }'
+        echo '{ "type": "test", "name": "a", "event": "ok" }'
+    "#;
+
+    fn run_script(script: &'static str, tests: &[&str], test_args: &[&str]) -> SuiteResult {
+        let executable = TestExecutable::new(
+            PathBuf::from("script"),
+            "script".to_string(),
+            "script".to_string(),
+        );
+        let planned = PlannedExecutable::with_tests(
+            executable,
+            tests.iter().map(ToString::to_string).collect(),
+        );
+        let cargo_config = CargoRunnerConfig {
+            test_args: test_args.iter().map(ToString::to_string).collect(),
+            ..Default::default()
+        };
+        CargoTestRunner::new(RunnerConfig::default())
+            .with_cargo_config(cargo_config)
+            .with_launcher(Script(script))
+            .exec_test(&planned, &CancellationToken::new())
+            .unwrap()
+    }
+
+    fn status_of(suite_result: &SuiteResult, test: &str) -> TestStatus {
+        suite_result
+            .test_results
+            .iter()
+            .find(|result| result.qualified_name() == test)
+            .map(|result| result.status)
+            .unwrap()
+    }
+
+    #[test]
+    fn test_crashing_executable_fails_unfinished_tests() {
+        let suite_result = run_script(
+            r#"
+            echo '{ "type": "suite", "event": "started", "test_count": 3 }'
+            echo '{ "type": "test", "event": "started", "name": "a" }'
+            echo '{ "type": "test", "name": "a", "event": "ok" }'
+            echo '{ "type": "test", "event": "started", "name": "b" }'
+            kill -ABRT $$
+            "#,
+            &["a", "b", "c"],
+            &[],
+        );
+
+        assert_eq!(status_of(&suite_result, "a"), TestStatus::Passed);
+        assert_eq!(status_of(&suite_result, "b"), TestStatus::Failed);
+        assert_eq!(status_of(&suite_result, "c"), TestStatus::Failed);
+        assert!(!suite_result.all_passed());
+    }
+
+    #[test]
+    fn test_executable_exiting_before_reporting_fails_its_suite() {
+        let suite_result = run_script(HARNESS, &["a"], &["--bogus-flag"]);
+
+        assert_eq!(status_of(&suite_result, "a"), TestStatus::Failed);
+        assert!(
+            suite_result
+                .error
+                .as_ref()
+                .is_some_and(|error| error.to_string().contains("Unrecognized option"))
+        );
+    }
+
+    #[test]
+    fn test_switches_the_runner_sets_are_not_repeated() {
+        let test_args = [
+            "--exact",
+            "--show-output",
+            "--report-time",
+            "-Z",
+            "unstable-options",
+            "--test-threads",
+            "1",
+        ];
+        let suite_result = run_script(HARNESS, &["a"], &test_args);
+
+        assert!(suite_result.all_passed(), "{:?}", suite_result.error);
+        assert_eq!(status_of(&suite_result, "a"), TestStatus::Passed);
+    }
+
+    #[test]
+    fn test_args_shaping_the_output_are_rejected() {
+        for arg in ["--format=pretty", "--list", "-q"] {
+            let cargo_config = CargoRunnerConfig {
+                test_args: vec![arg.to_string()],
+                ..Default::default()
+            };
+            assert!(cargo_config.check_test_args().is_err(), "{}", arg);
+        }
+    }
+}
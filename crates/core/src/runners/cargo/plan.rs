@@ -0,0 +1,147 @@
+use serde::{Deserialize, Serialize};
+use std::process::Stdio;
+
+use super::{Launcher, ResourceLimits};
+use crate::{Error, Result, TestExecutable};
+
+/// A test executable along with the subset of its tests selected to run.
+#[derive(Debug, Clone)]
+pub struct PlannedExecutable {
+    pub executable: TestExecutable,
+    /// Exact libtest names to run, or `None` to run everything in the executable
+    pub tests: Option<Vec<String>>,
+    /// Tests skipped because an identical passing run is in the result cache
+    pub cached: Vec<String>,
+    /// Content hash of the executable, when the result cache is enabled
+    pub binary_hash: Option<u64>,
+    /// Whether to read libtest's JSON output rather than its pretty output
+    pub json: bool,
+}
+
+impl PlannedExecutable {
+    pub fn all(executable: TestExecutable) -> Self {
+        Self {
+            executable,
+            tests: None,
+            cached: Vec::new(),
+            binary_hash: None,
+            json: true,
+        }
+    }
+
+    pub fn with_tests(executable: TestExecutable, tests: Vec<String>) -> Self {
+        Self {
+            executable,
+            tests: Some(tests),
+            cached: Vec::new(),
+            binary_hash: None,
+            json: true,
+        }
+    }
+
+    /// Whether the plan explicitly selected no tests from this executable
+    pub fn is_empty(&self) -> bool {
+        self.tests.as_ref().is_some_and(|tests| tests.is_empty()) && self.cached.is_empty()
+    }
+
+    /// Whether the executable needs to be spawned at all
+    pub fn has_tests_to_run(&self) -> bool {
+        self.tests.as_ref().is_none_or(|tests| !tests.is_empty())
+    }
+
+    /// Arguments narrowing the executable down to the selected tests
+    pub fn filter_args(&self) -> Vec<String> {
+        match self.tests {
+            Some(ref tests) => {
+                let mut args = tests.clone();
+                args.push("--exact".to_string());
+                args
+            }
+            None => Vec::new(),
+        }
+    }
+}
+
+/// Selects tests by name, so a single test or a handful of them can run
+/// instead of whole executables.
+///
+/// Filters match the test's path as written in the source, prefixed with the
+/// executable's crate, e.g. `my_crate::api::test_login`: the modules and
+/// `_cargo_test` suffix sheila generates are left out. An exact filter must
+/// name whole trailing segments of the path (`test_login`,
+/// `api::test_login`), otherwise any substring matches, like libtest's own
+/// filter.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct TestFilter {
+    pub pattern: String,
+    #[serde(default)]
+    pub exact: bool,
+}
+
+impl TestFilter {
+    pub fn new<S: Into<String>>(pattern: S) -> Self {
+        Self {
+            pattern: pattern.into(),
+            exact: false,
+        }
+    }
+
+    pub fn exact<S: Into<String>>(pattern: S) -> Self {
+        Self {
+            pattern: pattern.into(),
+            exact: true,
+        }
+    }
+
+    /// Whether the test with libtest name `test` in `executable` is selected
+    pub fn matches(&self, executable: &str, test: &str) -> bool {
+        let path = format!("{}::{}", executable, source_test_path(test));
+        if self.exact {
+            path == self.pattern || path.ends_with(&format!("::{}", self.pattern))
+        } else {
+            path.contains(&self.pattern)
+        }
+    }
+}
+
+/// A libtest name with the modules and suffix generated by sheila's macros
+/// removed, leaving the path of the test function as written. A case of a
+/// parameterized test keeps its `case_<n>` segment after the function.
+fn source_test_path(test: &str) -> String {
+    let segments: Vec<&str> = test
+        .split("::")
+        .filter(|segment| !segment.starts_with("__sheila_"))
+        .map(|segment| segment.strip_suffix("_cargo_test").unwrap_or(segment))
+        .collect();
+    segments.join("::")
+}
+
+/// List the tests contained in a libtest executable, using `--list --format terse`.
+pub fn list_executable_tests(
+    launcher: &dyn Launcher,
+    executable: &TestExecutable,
+) -> Result<Vec<String>> {
+    let args = ["--list", "--format", "terse"].map(String::from);
+    let output = launcher
+        .command(executable, &args, &[], &ResourceLimits::default())?
+        .stdin(Stdio::null())
+        .output()?;
+
+    if !output.status.success() {
+        return Err(Error::test_execution(format!(
+            "Failed to list tests in {}: {}",
+            executable.name,
+            String::from_utf8_lossy(&output.stderr).trim()
+        )));
+    }
+
+    Ok(parse_test_list(&String::from_utf8_lossy(&output.stdout)))
+}
+
+pub(super) fn parse_test_list(output: &str) -> Vec<String> {
+    output
+        .lines()
+        .filter_map(|line| line.strip_suffix(": test"))
+        .map(|name| name.to_string())
+        .collect()
+}
@@ -0,0 +1,147 @@
+use std::collections::BTreeSet;
+use std::path::{Path, PathBuf};
+use std::process::{Command, Stdio};
+
+use crate::schemas::WorkspaceMetadata;
+use crate::{Error, Result, TestExecutable};
+
+/// Files at the workspace root that every package is built from, so a
+/// change to one of them affects every test
+const WORKSPACE_FILES: &[&str] = &[
+    "Cargo.toml",
+    "Cargo.lock",
+    "rust-toolchain",
+    "rust-toolchain.toml",
+    ".cargo",
+];
+
+/// Files changed in the git checkout since `base`, as absolute paths. This
+/// includes uncommitted and untracked files, so `HEAD` gives the changes
+/// that haven't been committed yet.
+pub fn changed_files(base: &str) -> Result<Vec<PathBuf>> {
+    let root = PathBuf::from(git(&["rev-parse", "--show-toplevel"])?.trim());
+
+    let diff = git(&["diff", "--name-only", base, "--"])?;
+    let untracked = git(&["ls-files", "--others", "--exclude-standard", "--full-name"])?;
+
+    let files: BTreeSet<PathBuf> = diff
+        .lines()
+        .chain(untracked.lines())
+        .filter(|line| !line.is_empty())
+        .map(|line| root.join(line))
+        .collect();
+    Ok(files.into_iter().collect())
+}
+
+fn git(args: &[&str]) -> Result<String> {
+    let output = Command::new("git")
+        .args(args)
+        .stdin(Stdio::null())
+        .output()
+        .map_err(|e| Error::generic(format!("Failed to run git: {}", e)))?;
+
+    if !output.status.success() {
+        return Err(Error::generic(format!(
+            "git {} failed: {}",
+            args.join(" "),
+            String::from_utf8_lossy(&output.stderr).trim()
+        )));
+    }
+
+    Ok(String::from_utf8_lossy(&output.stdout).into_owned())
+}
+
+/// The test executables a set of changed files can affect.
+///
+/// A change anywhere in a package affects all of its test executables,
+/// except a change to an integration test's own source, which only affects
+/// that test target. Changes outside every package are ignored, apart from
+/// the workspace manifest, lock file and toolchain files, which affect
+/// everything.
+#[derive(Debug, Clone, Default)]
+pub struct ChangeImpact {
+    /// Packages whose code changed, so each of their test executables runs
+    pub packages: BTreeSet<String>,
+    /// Source files of integration test targets that changed
+    pub test_targets: BTreeSet<PathBuf>,
+    /// Whether a workspace-wide file changed
+    pub workspace: bool,
+}
+
+impl ChangeImpact {
+    /// Work out what `changed` affects among `executables`. With
+    /// `with_dependents`, packages depending on a changed package are
+    /// affected too.
+    pub fn new(
+        metadata: &WorkspaceMetadata,
+        executables: &[TestExecutable],
+        changed: &[PathBuf],
+        with_dependents: bool,
+    ) -> Self {
+        let mut impact = Self::default();
+
+        for file in changed {
+            if WORKSPACE_FILES
+                .iter()
+                .any(|name| file.starts_with(metadata.workspace_root.join(name)))
+            {
+                impact.workspace = true;
+                continue;
+            }
+
+            let Some(package) = metadata.package_containing(file) else {
+                continue;
+            };
+
+            match test_target_for(executables, &package.root, file) {
+                Some(src_path) => {
+                    impact.test_targets.insert(src_path);
+                }
+                None => {
+                    impact.packages.insert(package.name.clone());
+                }
+            }
+        }
+
+        if with_dependents {
+            let changed: Vec<String> = impact.packages.iter().cloned().collect();
+            impact.packages.extend(metadata.dependents(&changed));
+        }
+
+        impact
+    }
+
+    pub fn is_empty(&self) -> bool {
+        !self.workspace && self.packages.is_empty() && self.test_targets.is_empty()
+    }
+
+    pub fn affects(&self, executable: &TestExecutable) -> bool {
+        self.workspace
+            || self.packages.contains(&executable.package_name)
+            || executable
+                .src_path
+                .as_ref()
+                .is_some_and(|src| self.test_targets.contains(src))
+    }
+}
+
+/// The source of the integration test target `file` belongs to: either the
+/// target's root file, e.g. `tests/api.rs`, or a file in its directory when
+/// the target is `tests/api/main.rs`
+fn test_target_for(
+    executables: &[TestExecutable],
+    package_root: &Path,
+    file: &Path,
+) -> Option<PathBuf> {
+    executables
+        .iter()
+        .filter(|executable| executable.target_kind.as_deref() == Some("test"))
+        .filter_map(|executable| executable.src_path.as_ref())
+        .filter(|src| src.starts_with(package_root))
+        .find(|src| {
+            *src == file
+                || (src.ends_with("main.rs")
+                    && src.parent().is_some_and(|dir| file.starts_with(dir)))
+        })
+        .cloned()
+}
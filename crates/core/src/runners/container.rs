@@ -0,0 +1,303 @@
+use parking_lot::Mutex;
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use std::path::{Path, PathBuf};
+use std::process::{Command, Stdio};
+
+use crate::runners::{
+    CargoRunnerConfig, CargoTestRunner, Launcher, ResourceLimits, RunResult, shell_quote,
+};
+use crate::{CancellationToken, Error, EventSender, Result, RunnerConfig, TestExecutable};
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ContainerConfig {
+    /// Image the tests run in
+    pub image: String,
+    /// Container CLI to use, e.g. `docker` or `podman`
+    pub engine: String,
+    /// Working directory for the tests, mounted at the same path. Usually the
+    /// workspace root, so tests can find their fixtures.
+    pub workdir: PathBuf,
+    /// Extra arguments for `<engine> run`, e.g. `--network=none`
+    pub run_args: Vec<String>,
+}
+
+impl Default for ContainerConfig {
+    fn default() -> Self {
+        Self {
+            image: "debian:stable-slim".to_string(),
+            engine: "docker".to_string(),
+            workdir: PathBuf::from("."),
+            run_args: Vec::new(),
+        }
+    }
+}
+
+/// Launches executables inside containers, one per executable.
+///
+/// Each executable, which is a suite as far as a run is concerned, gets its
+/// own container, started the first time it's prepared and reused for
+/// listing, format probing and every run of it (including repeated runs), so
+/// its tests share a clean environment that no other suite touches. All containers are removed on teardown, which
+/// also stops anything still running inside them.
+///
+/// Executables are built on the host, so the image must provide a
+/// compatible C library. Resource limits are applied inside the container
+/// with `ulimit`, which needs a shell in the image.
+#[derive(Debug)]
+pub struct ContainerLauncher {
+    config: ContainerConfig,
+    /// executable path -> container id
+    containers: Mutex<HashMap<PathBuf, String>>,
+}
+
+impl ContainerLauncher {
+    pub fn new(config: ContainerConfig) -> Self {
+        Self {
+            config,
+            containers: Mutex::new(HashMap::new()),
+        }
+    }
+
+    /// Start a container with `executable`'s directory mounted read-only at
+    /// the same path, so it can be run by its host path.
+    fn start_container(&self, executable: &TestExecutable) -> Result<String> {
+        let executable_dir = executable
+            .path
+            .parent()
+            .unwrap_or(Path::new("/"))
+            .canonicalize()?;
+        let workdir = self.config.workdir.canonicalize()?;
+
+        let output = Command::new(&self.config.engine)
+            .args(["run", "--detach", "--init"])
+            .arg("--volume")
+            .arg(format!("{}:{}", workdir.display(), workdir.display()))
+            .arg("--volume")
+            .arg(format!(
+                "{}:{}:ro",
+                executable_dir.display(),
+                executable_dir.display()
+            ))
+            .arg("--workdir")
+            .arg(&workdir)
+            .args(&self.config.run_args)
+            .arg(&self.config.image)
+            .args(["sleep", "infinity"])
+            .stdin(Stdio::null())
+            .output()?;
+
+        if !output.status.success() {
+            return Err(Error::runner_config(format!(
+                "Failed to start a {} container: {}",
+                self.config.image,
+                String::from_utf8_lossy(&output.stderr).trim()
+            )));
+        }
+
+        Ok(String::from_utf8_lossy(&output.stdout).trim().to_string())
+    }
+}
+
+impl Launcher for ContainerLauncher {
+    fn prepare(&self, executable: &TestExecutable) -> Result<()> {
+        if self.containers.lock().contains_key(&executable.path) {
+            return Ok(());
+        }
+
+        let container = self.start_container(executable)?;
+        self.containers
+            .lock()
+            .insert(executable.path.clone(), container);
+        Ok(())
+    }
+
+    fn command(
+        &self,
+        executable: &TestExecutable,
+        args: &[String],
+        envs: &[(&str, &str)],
+        limits: &ResourceLimits,
+    ) -> Result<Command> {
+        let container = self
+            .containers
+            .lock()
+            .get(&executable.path)
+            .cloned()
+            .ok_or_else(|| {
+                Error::runner_config(format!("No container was started for {}", executable.name))
+            })?;
+
+        let mut command = Command::new(&self.config.engine);
+        command.args(["exec", "--interactive"]);
+        for (key, value) in envs {
+            command.arg("--env").arg(format!("{}={}", key, value));
+        }
+        command.arg(container);
+
+        if limits.is_empty() {
+            command.arg(&executable.path).args(args);
+            return Ok(command);
+        }
+
+        let mut script = String::new();
+        if let Some(bytes) = limits.memory {
+            script.push_str(&format!("ulimit -v {} && ", bytes / 1024));
+        }
+        if let Some(cpu_time) = limits.cpu_time {
+            script.push_str(&format!("ulimit -t {} && ", cpu_time.as_secs().max(1)));
+        }
+        script.push_str("exec ");
+        script.push_str(&shell_quote(&executable.path.to_string_lossy()));
+        for arg in args {
+            script.push(' ');
+            script.push_str(&shell_quote(arg));
+        }
+
+        command.args(["sh", "-c"]).arg(script);
+        Ok(command)
+    }
+
+    fn teardown(&self) {
+        let containers: Vec<String> = self.containers.lock().drain().map(|(_, id)| id).collect();
+        if containers.is_empty() {
+            return;
+        }
+
+        let _ = Command::new(&self.config.engine)
+            .args(["rm", "--force"])
+            .args(&containers)
+            .stdin(Stdio::null())
+            .stdout(Stdio::null())
+            .stderr(Stdio::null())
+            .status();
+    }
+}
+
+/// Runs test executables inside containers for hermetic integration tests.
+///
+/// A [`CargoTestRunner`] driven through a [`ContainerLauncher`], so output
+/// streams back and results are collected exactly as in a local run.
+pub struct ContainerRunner {
+    runner: CargoTestRunner,
+}
+
+impl ContainerRunner {
+    pub fn new(config: RunnerConfig, container_config: ContainerConfig) -> Self {
+        Self {
+            runner: CargoTestRunner::new(config)
+                .with_launcher(ContainerLauncher::new(container_config)),
+        }
+    }
+
+    pub fn new_with_output(
+        config: RunnerConfig,
+        container_config: ContainerConfig,
+        output_tx: impl Into<EventSender>,
+    ) -> Self {
+        Self {
+            runner: CargoTestRunner::new_with_output(config, output_tx)
+                .with_launcher(ContainerLauncher::new(container_config)),
+        }
+    }
+
+    pub fn with_cargo_config(mut self, cargo_config: CargoRunnerConfig) -> Self {
+        self.runner = self.runner.with_cargo_config(cargo_config);
+        self
+    }
+
+    pub fn execute_tests(&mut self, executables: &[TestExecutable]) -> Result<RunResult> {
+        self.execute_tests_with_cancel(executables, &CancellationToken::new())
+    }
+
+    /// Runs the executables in their containers, removing the containers
+    /// once the run is over.
+    pub fn execute_tests_with_cancel(
+        &mut self,
+        executables: &[TestExecutable],
+        cancel: &CancellationToken,
+    ) -> Result<RunResult> {
+        self.runner.execute_tests_with_cancel(executables, cancel)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::time::Duration;
+
+    fn executable() -> TestExecutable {
+        TestExecutable::new(
+            PathBuf::from("/target/debug/deps/it's-1a2b"),
+            "it's".to_string(),
+            "app".to_string(),
+        )
+    }
+
+    fn args(command: &Command) -> Vec<String> {
+        command
+            .get_args()
+            .map(|arg| arg.to_string_lossy().into_owned())
+            .collect()
+    }
+
+    #[test]
+    fn test_unprepared_executables_have_no_command() {
+        let launcher = ContainerLauncher::new(ContainerConfig::default());
+        let command = launcher.command(&executable(), &[], &[], &ResourceLimits::default());
+        assert!(command.is_err());
+    }
+
+    #[test]
+    fn test_limits_are_applied_by_a_quoted_script() {
+        let launcher = ContainerLauncher::new(ContainerConfig::default());
+        let executable = executable();
+        launcher
+            .containers
+            .lock()
+            .insert(executable.path.clone(), "c0ffee".to_string());
+        let test_args = vec!["--exact".to_string(), "don't panic".to_string()];
+
+        let command = launcher
+            .command(
+                &executable,
+                &test_args,
+                &[("RUST_TEST_NOCAPTURE", "1")],
+                &ResourceLimits::default(),
+            )
+            .unwrap();
+        assert_eq!(command.get_program(), "docker");
+        assert_eq!(
+            args(&command),
+            [
+                "exec",
+                "--interactive",
+                "--env",
+                "RUST_TEST_NOCAPTURE=1",
+                "c0ffee",
+                "/target/debug/deps/it's-1a2b",
+                "--exact",
+                "don't panic",
+            ]
+        );
+
+        let limits = ResourceLimits {
+            memory: Some(64 * 1024 * 1024),
+            cpu_time: Some(Duration::from_millis(1500)),
+        };
+        let command = launcher
+            .command(&executable, &test_args, &[], &limits)
+            .unwrap();
+        assert_eq!(
+            args(&command),
+            [
+                "exec",
+                "--interactive",
+                "c0ffee",
+                "sh",
+                "-c",
+                r"ulimit -v 65536 && ulimit -t 1 && exec '/target/debug/deps/it'\''s-1a2b' '--exact' 'don'\''t panic'",
+            ]
+        );
+    }
+}
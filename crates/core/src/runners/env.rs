@@ -0,0 +1,98 @@
+use std::path::Path;
+
+use crate::{Error, Result};
+
+/// Parse a `KEY=VALUE` environment variable assignment
+pub fn parse_env_var(value: &str) -> Result<(String, String)> {
+    match value.split_once('=') {
+        Some((key, value)) if is_env_key(key.trim()) => {
+            Ok((key.trim().to_string(), value.to_string()))
+        }
+        _ => Err(Error::invalid_config(format!(
+            "Invalid environment variable '{}': expected KEY=VALUE",
+            value
+        ))),
+    }
+}
+
+/// Read the variables assigned in a dotenv file, in order.
+///
+/// Each line is `KEY=VALUE`, optionally starting with `export`. Blank lines
+/// and lines starting with `#` are skipped, and a value wrapped in single or
+/// double quotes has them removed.
+pub fn read_env_file<P: AsRef<Path>>(path: P) -> Result<Vec<(String, String)>> {
+    let path = path.as_ref();
+    let content = std::fs::read_to_string(path).map_err(|e| {
+        Error::invalid_config(format!("Failed to read env file {}: {}", path.display(), e))
+    })?;
+
+    let mut vars = Vec::new();
+    for (index, line) in content.lines().enumerate() {
+        let line = line.trim();
+        if line.is_empty() || line.starts_with('#') {
+            continue;
+        }
+        let line = line.strip_prefix("export ").unwrap_or(line);
+
+        let (key, value) = parse_env_var(line).map_err(|_| {
+            Error::invalid_config(format!(
+                "Invalid line {} in env file {}: expected KEY=VALUE",
+                index + 1,
+                path.display()
+            ))
+        })?;
+        vars.push((key, unquote(value.trim()).to_string()));
+    }
+    Ok(vars)
+}
+
+fn is_env_key(key: &str) -> bool {
+    !key.is_empty()
+        && !key.starts_with(|c: char| c.is_ascii_digit())
+        && key.chars().all(|c| c.is_ascii_alphanumeric() || c == '_')
+}
+
+fn unquote(value: &str) -> &str {
+    ['"', '\'']
+        .iter()
+        .find_map(|quote| {
+            value
+                .strip_prefix(*quote)
+                .and_then(|value| value.strip_suffix(*quote))
+        })
+        .unwrap_or(value)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_read_env_file_handles_comments_exports_and_quotes() {
+        let path = std::env::temp_dir().join(format!("sheila-env-{}", uuid::Uuid::new_v4()));
+        std::fs::write(
+            &path,
+            "# database\nexport DATABASE_URL=\"postgres://localhost/test\"\n\nMODE='ci'\nEMPTY=\nEQUALS=a=b\n",
+        )
+        .unwrap();
+
+        let vars = read_env_file(&path).unwrap();
+        std::fs::remove_file(&path).unwrap();
+
+        let pairs: Vec<(&str, &str)> = vars
+            .iter()
+            .map(|(key, value)| (key.as_str(), value.as_str()))
+            .collect();
+        assert_eq!(
+            pairs,
+            [
+                ("DATABASE_URL", "postgres://localhost/test"),
+                ("MODE", "ci"),
+                ("EMPTY", ""),
+                ("EQUALS", "a=b"),
+            ]
+        );
+        assert!(parse_env_var("NO_VALUE").is_err());
+        assert!(parse_env_var("1BAD=x").is_err());
+    }
+}
@@ -1,20 +1,61 @@
+pub mod cancel;
+pub use cancel::*;
+
 pub mod cargo;
 pub use cargo::*;
 
+pub mod changed;
+pub use changed::*;
+
+pub mod container;
+pub use container::*;
+
+pub mod env;
+pub use env::*;
+
+pub mod remote;
+pub use remote::*;
+
+pub mod repeat;
+pub use repeat::*;
+
+pub mod shard;
+pub use shard::*;
+
+pub mod tags;
+pub use tags::*;
+
 pub mod thin;
 pub use thin::*;
 
+use crate::cache::RunCache;
+use crate::random;
+use crate::schema::SCHEMA_VERSION;
+use crate::state::SharedState;
 use crate::suite::SuiteResult;
-use crate::{Error, Result, TestSuite};
+use crate::test::{TestAttempt, TestResult};
+use crate::{Error, Result, TestStatus, TestSuite};
 use chrono::{DateTime, Utc};
+use indexmap::IndexMap;
 use serde::{Deserialize, Serialize};
-use std::collections::HashMap;
-use std::path::PathBuf;
+use std::collections::{HashMap, HashSet};
+use std::path::{Path, PathBuf};
 use std::time::Duration;
 use uuid::Uuid;
 
 pub trait TestRunner: Send + Sync {
-    fn run(&self, suites: Vec<TestSuite>) -> Result<RunResult>;
+    fn run(&self, suites: Vec<TestSuite>) -> Result<RunResult> {
+        self.run_with_cancel(suites, &CancellationToken::new())
+    }
+
+    /// Runs the suites until they finish or `cancel` is triggered, in which
+    /// case the suites that already started are wound down and a partial
+    /// result is returned.
+    fn run_with_cancel(
+        &self,
+        suites: Vec<TestSuite>,
+        cancel: &CancellationToken,
+    ) -> Result<RunResult>;
 
     fn run_suite(&self, suite: TestSuite) -> Result<SuiteResult>;
 
@@ -25,7 +66,7 @@ pub trait TestRunner: Send + Sync {
     fn filter_suites(&self, suites: Vec<TestSuite>) -> Vec<TestSuite> {
         let config = self.config();
 
-        suites
+        let suites: Vec<TestSuite> = suites
             .into_iter()
             .filter(|suite| {
                 if suite.attributes.ignore {
@@ -90,26 +131,234 @@ pub trait TestRunner: Send + Sync {
 
                 true
             })
-            .collect()
+            .collect();
+
+        let suites = match config.tag_filter {
+            Some(ref filter) => filter_tagged(filter, suites),
+            None => suites,
+        };
+
+        let suites = if config.only_failed {
+            match RunCache::current().and_then(|cache| cache.last_failed()) {
+                Ok(Some(failed)) => retain_tests(suites, |suite, test| {
+                    failed.contains(&(suite.to_string(), test.to_string()))
+                }),
+                _ => suites,
+            }
+        } else {
+            suites
+        };
+
+        match config.shard {
+            Some(ref shard) => shard_suites(shard, suites),
+            None => suites,
+        }
     }
 }
 
+/// Run in-process suites one after another until they finish or `cancel` is
+/// triggered. With `fail_fast` set, the first failing test cancels the run
+/// with [`CancelReason::FailFast`]. Suites that never got to start are
+/// reported with all their tests skipped.
+pub(crate) fn run_suites(
+    config: &RunnerConfig,
+    suites: &mut [TestSuite],
+    cancel: &CancellationToken,
+) -> Result<RunResult> {
+    let config = &RunnerConfig {
+        seed: Some(config.run_seed()),
+        ..config.clone()
+    };
+    let mut result = RunResult::new(config.clone());
+    let state = SharedState::new();
+
+    for suite in suites.iter_mut() {
+        if let Some(reason) = cancel.reason() {
+            result.add_suite_result(skipped_suite_result(
+                suite,
+                Error::cancelled(reason.to_string()),
+            ));
+            continue;
+        }
+
+        match suite.execute_with_state(config, cancel, &state) {
+            Ok(suite_result) => {
+                if config.fail_fast && !suite_result.all_passed() {
+                    cancel.cancel(CancelReason::FailFast);
+                }
+                result.add_suite_result(suite_result);
+            }
+            Err(e) => {
+                result.finish(Some(e));
+                return Ok(result);
+            }
+        }
+    }
+
+    result.finish(cancel.reason().map(CancelReason::run_error));
+
+    if let Some(ref output_dir) = config.output_dir {
+        result.store_artifacts(output_dir);
+    }
+
+    Ok(result)
+}
+
+/// Result for a suite that was never started, with every test skipped
+/// because of `error`.
+fn skipped_suite_result(suite: &TestSuite, error: Error) -> SuiteResult {
+    let mut suite_result = SuiteResult::new(suite.id, suite.name.clone(), suite.meta.clone());
+    for test in suite.get_runnable_tests() {
+        let mut test_result = TestResult::new(test.id, test.meta.name.clone(), test.meta.clone());
+        test_result.finish(TestStatus::Skipped, Some(error.clone()));
+        suite_result.add_test_result(test_result);
+    }
+    suite_result.finish(Some(error));
+    suite_result
+}
+
+/// Result of a dry run over in-process suites: every test that would run is
+/// reported as [`TestStatus::Pending`], with its duration from the previous
+/// run as an estimate where one is known.
+///
+/// [`TestStatus::Pending`]: crate::TestStatus::Pending
+pub fn dry_run_result(config: &RunnerConfig, suites: &[TestSuite]) -> RunResult {
+    let history = RunCache::current()
+        .and_then(|cache| cache.duration_history())
+        .unwrap_or_default();
+
+    let mut result = RunResult::new(config.clone());
+    for suite in suites {
+        let mut suite_result = SuiteResult::new(suite.id, suite.name.clone(), suite.meta.clone());
+
+        for test in suite.get_runnable_tests() {
+            let mut test_result =
+                TestResult::new(test.id, test.meta.name.clone(), test.meta.clone());
+            test_result.status = TestStatus::Pending;
+            test_result.duration = history
+                .get(&(suite.name.clone(), test_result.qualified_name().to_string()))
+                .copied();
+            suite_result.add_test_result(test_result);
+        }
+
+        suite_result.finish(None);
+        result.add_suite_result(suite_result);
+    }
+
+    result.finish(None);
+    result
+}
+
+/// Keep only the tests for which `keep(suite, test)` holds, dropping suites
+/// that end up empty.
+pub fn retain_tests<F>(suites: Vec<TestSuite>, keep: F) -> Vec<TestSuite>
+where
+    F: Fn(&str, &str) -> bool,
+{
+    suites
+        .into_iter()
+        .filter_map(|mut suite| {
+            let suite_name = suite.name.clone();
+            suite.tests.retain(|test, _| keep(&suite_name, test));
+            (!suite.tests.is_empty()).then_some(suite)
+        })
+        .collect()
+}
+
+/// Keep only the tests whose tags, together with their suite's, match
+/// `filter`, dropping suites that end up empty.
+pub fn filter_tagged(filter: &TagExpr, suites: Vec<TestSuite>) -> Vec<TestSuite> {
+    suites
+        .into_iter()
+        .filter_map(|mut suite| {
+            let suite_tags = suite.attributes.tags.clone();
+            suite.tests.retain(|_, test| {
+                let tags: Vec<&String> = suite_tags
+                    .iter()
+                    .chain(test.attributes.tags.iter())
+                    .collect();
+                filter.matches(&tags)
+            });
+            (!suite.tests.is_empty()).then_some(suite)
+        })
+        .collect()
+}
+
+/// Restrict each suite to the tests belonging to `shard`, dropping suites
+/// that end up empty. Tests are keyed by `<suite>::<test>`.
+pub fn shard_suites(shard: &Shard, suites: Vec<TestSuite>) -> Vec<TestSuite> {
+    let keys: Vec<String> = suites
+        .iter()
+        .flat_map(|suite| {
+            suite
+                .tests
+                .keys()
+                .map(move |test| format!("{}::{}", suite.name, test))
+        })
+        .collect();
+
+    let selected: HashSet<String> = shard.select(keys, |key| key.clone()).into_iter().collect();
+
+    retain_tests(suites, |suite, test| {
+        selected.contains(&format!("{}::{}", suite, test))
+    })
+}
+
 #[derive(Debug, Clone, Serialize, Deserialize)]
+#[cfg_attr(feature = "schemas", derive(schemars::JsonSchema))]
 pub struct RunnerConfig {
     pub max_concurrent_suites: Option<usize>,
     pub default_test_timeout: Option<Duration>,
     pub default_suite_timeout: Option<Duration>,
+    /// Stop the whole run at the first failing test, cancelling the run's
+    /// [`CancellationToken`] so work in flight stops too
     pub fail_fast: bool,
     pub parallel: bool,
     pub include_patterns: Vec<String>,
     pub exclude_patterns: Vec<String>,
     pub include_tags: Vec<String>,
     pub exclude_tags: Vec<String>,
+    /// Only run tests whose tags, together with their suite's, match this
+    /// expression
+    #[serde(default)]
+    #[cfg_attr(feature = "schemas", schemars(with = "Option<String>"))]
+    pub tag_filter: Option<TagExpr>,
     pub include_categories: Vec<String>,
     pub exclude_categories: Vec<String>,
     pub output_dir: Option<PathBuf>,
     pub capture_output: bool,
     pub env: HashMap<String, String>,
+    /// Retries for failed tests whose test and suite don't set their own
+    #[serde(default)]
+    pub retries: u32,
+    /// Base delay between retry attempts, doubled after every attempt
+    #[serde(default)]
+    pub retry_backoff: Option<Duration>,
+    /// Randomize retry delays by up to 50% so retries don't run in lockstep
+    #[serde(default)]
+    pub retry_jitter: bool,
+    /// Only run the tests belonging to this shard of the full test set
+    #[serde(default)]
+    pub shard: Option<Shard>,
+    /// Only run tests that failed (or were flaky) in the previous run
+    #[serde(default)]
+    pub only_failed: bool,
+    /// Resolve and report the tests that would run without executing them
+    #[serde(default)]
+    pub dry_run: bool,
+    /// Keep re-running the selected tests until one fails
+    #[serde(default)]
+    pub repeat: Option<RepeatMode>,
+    /// Time budget for the whole run, including every iteration of a
+    /// repeated run. Once it runs out the run is cancelled with
+    /// [`CancelReason::Timeout`]: no new tests start, tests in flight are
+    /// stopped and the result finishes with a resource-exhausted error.
+    #[serde(default)]
+    pub max_run_duration: Option<Duration>,
+    /// Seed the random data tests draw with `ctx.rng()` comes from. Without
+    /// one, a seed is picked for the run.
+    #[serde(default)]
+    pub seed: Option<u64>,
     pub custom: HashMap<String, serde_json::Value>,
 }
 
@@ -125,11 +374,21 @@ impl Default for RunnerConfig {
             exclude_patterns: Vec::new(),
             include_tags: Vec::new(),
             exclude_tags: Vec::new(),
+            tag_filter: None,
             include_categories: Vec::new(),
             exclude_categories: Vec::new(),
             output_dir: None,
             capture_output: true,
             env: HashMap::new(),
+            retries: 0,
+            retry_backoff: None,
+            retry_jitter: false,
+            shard: None,
+            only_failed: false,
+            dry_run: false,
+            repeat: None,
+            max_run_duration: None,
+            seed: None,
             custom: HashMap::new(),
         }
     }
@@ -185,11 +444,89 @@ impl RunnerConfig {
         self
     }
 
+    pub fn tag_filter(mut self, filter: TagExpr) -> Self {
+        self.tag_filter = Some(filter);
+        self
+    }
+
     pub fn output_dir<P: Into<PathBuf>>(mut self, dir: P) -> Self {
         self.output_dir = Some(dir.into());
         self
     }
 
+    pub fn retries(mut self, retries: u32) -> Self {
+        self.retries = retries;
+        self
+    }
+
+    pub fn retry_backoff(mut self, backoff: Duration) -> Self {
+        self.retry_backoff = Some(backoff);
+        self
+    }
+
+    pub fn retry_jitter(mut self, jitter: bool) -> Self {
+        self.retry_jitter = jitter;
+        self
+    }
+
+    pub fn shard(mut self, shard: Shard) -> Self {
+        self.shard = Some(shard);
+        self
+    }
+
+    pub fn seed(mut self, seed: u64) -> Self {
+        self.seed = Some(seed);
+        self
+    }
+
+    /// The configured seed, or the process's own run seed without one
+    pub fn run_seed(&self) -> u64 {
+        self.seed.unwrap_or_else(random::run_seed)
+    }
+
+    pub fn only_failed(mut self, only_failed: bool) -> Self {
+        self.only_failed = only_failed;
+        self
+    }
+
+    pub fn dry_run(mut self, dry_run: bool) -> Self {
+        self.dry_run = dry_run;
+        self
+    }
+
+    pub fn repeat(mut self, repeat: RepeatMode) -> Self {
+        self.repeat = Some(repeat);
+        self
+    }
+
+    pub fn max_run_duration(mut self, budget: Duration) -> Self {
+        self.max_run_duration = Some(budget);
+        self
+    }
+
+    /// Start counting down `max_run_duration` for a run cancelled by `cancel`.
+    /// The budget stops counting once the returned timer is dropped.
+    pub fn start_budget(&self, cancel: &CancellationToken) -> Option<CancelTimer> {
+        self.max_run_duration
+            .map(|budget| cancel.cancel_after(budget, CancelReason::Timeout))
+    }
+
+    /// Delay to wait before the given retry attempt (1-based)
+    pub fn retry_delay(&self, attempt: u32) -> Duration {
+        let Some(backoff) = self.retry_backoff else {
+            return Duration::ZERO;
+        };
+
+        let delay = backoff.saturating_mul(1 << attempt.saturating_sub(1).min(16));
+
+        if self.retry_jitter {
+            let jitter = (Uuid::new_v4().as_u128() % 1000) as f64 / 1000.0;
+            delay.mul_f64(0.5 + jitter * 0.5)
+        } else {
+            delay
+        }
+    }
+
     pub fn env<K, V>(mut self, key: K, value: V) -> Self
     where
         K: Into<String>,
@@ -201,6 +538,7 @@ impl RunnerConfig {
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
+#[cfg_attr(feature = "schemas", derive(schemars::JsonSchema))]
 pub struct RunResult {
     pub id: Uuid,
     pub start_time: DateTime<Utc>,
@@ -216,7 +554,16 @@ pub struct RunResult {
     pub passed_tests: usize,
     pub failed_tests: usize,
     pub skipped_tests: usize,
+    #[serde(default)]
+    pub flaky_tests: usize,
+    /// Number of iterations run in a repeated run
+    #[serde(default)]
+    pub iterations: Option<u32>,
     pub error: Option<Error>,
+    /// Version of the format this result is serialized in, see
+    /// [`SCHEMA_VERSION`](crate::schema::SCHEMA_VERSION)
+    #[serde(default = "crate::schema::schema_version")]
+    pub schema_version: u32,
 }
 
 impl RunResult {
@@ -236,7 +583,10 @@ impl RunResult {
             passed_tests: 0,
             failed_tests: 0,
             skipped_tests: 0,
+            flaky_tests: 0,
+            iterations: None,
             error: None,
+            schema_version: SCHEMA_VERSION,
         }
     }
 
@@ -246,6 +596,7 @@ impl RunResult {
         self.passed_tests += result.passed_tests;
         self.failed_tests += result.failed_tests;
         self.skipped_tests += result.skipped_tests;
+        self.flaky_tests += result.flaky_tests;
 
         if result.all_passed() {
             self.passed_suites += 1;
@@ -280,10 +631,342 @@ impl RunResult {
         self.passed_tests as f64 / self.total_tests as f64
     }
 
+    /// How long each `(suite, test)` pair took in this run
+    pub fn test_durations(&self) -> HashMap<(String, String), Duration> {
+        self.suite_results
+            .iter()
+            .flat_map(|suite| {
+                suite.test_results.iter().filter_map(|test| {
+                    test.duration.map(|duration| {
+                        (
+                            (suite.name.clone(), test.qualified_name().to_string()),
+                            duration,
+                        )
+                    })
+                })
+            })
+            .collect()
+    }
+
+    /// Estimated time for the tests in this run, summed from their durations.
+    /// Only meaningful for a dry run.
+    pub fn estimated_duration(&self) -> Duration {
+        self.suite_results
+            .iter()
+            .flat_map(|suite| suite.test_results.iter())
+            .filter_map(|test| test.duration)
+            .sum()
+    }
+
+    /// `(suite, test)` pairs that failed, timed out or only passed after retrying
+    pub fn tests_to_rerun(&self) -> HashSet<(String, String)> {
+        self.suite_results
+            .iter()
+            .flat_map(|suite| {
+                suite
+                    .test_results
+                    .iter()
+                    .filter(|test| test.failed() || test.flaky)
+                    .map(|test| (suite.name.clone(), test.qualified_name().to_string()))
+            })
+            .collect()
+    }
+
+    /// `(suite, test)` pairs that failed or timed out
+    pub fn failures(&self) -> HashSet<(String, String)> {
+        self.suite_results
+            .iter()
+            .flat_map(|suite| {
+                suite
+                    .test_results
+                    .iter()
+                    .filter(|test| test.failed())
+                    .map(|test| (suite.name.clone(), test.qualified_name().to_string()))
+            })
+            .collect()
+    }
+
     pub fn suite_success_rate(&self) -> f64 {
         if self.total_suites == 0 {
             return 1.0;
         }
         self.passed_suites as f64 / self.total_suites as f64
     }
+
+    /// Copy every test's artifacts into `artifacts/<run id>/<test id>` under
+    /// `output_dir`, writing text artifacts out as files, and point the
+    /// results at the copies. An artifact that can't be stored is left as
+    /// it was, with a warning. Once a test that didn't fail has all its
+    /// artifacts stored, the temp directory kept for them is removed.
+    pub fn store_artifacts(&mut self, output_dir: &Path) {
+        let run_dir = output_dir.join("artifacts").join(self.id.to_string());
+
+        for suite_result in self.suite_results.iter_mut() {
+            for test_result in suite_result.test_results.iter_mut() {
+                if test_result.artifacts.is_empty() {
+                    continue;
+                }
+
+                let test_dir = run_dir.join(test_result.id.to_string());
+                if let Err(e) = std::fs::create_dir_all(&test_dir) {
+                    tracing::warn!("Could not create {}: {}", test_dir.display(), e);
+                    continue;
+                }
+
+                let mut all_stored = true;
+
+                for (index, artifact) in test_result.artifacts.iter_mut().enumerate() {
+                    let file_name: String = artifact
+                        .name
+                        .chars()
+                        .map(|c| {
+                            if c.is_alphanumeric() || c == '.' || c == '-' {
+                                c
+                            } else {
+                                '_'
+                            }
+                        })
+                        .collect();
+                    let (stored, written) = match (&artifact.text, &artifact.path) {
+                        (Some(text), _) => {
+                            let stored = test_dir.join(format!("{}-{}.txt", index, file_name));
+                            let written = std::fs::write(&stored, text);
+                            (stored, written)
+                        }
+                        (None, Some(path)) => {
+                            let stored = test_dir.join(format!("{}-{}", index, file_name));
+                            let written = std::fs::copy(path, &stored).map(|_| ());
+                            (stored, written)
+                        }
+                        (None, None) => continue,
+                    };
+                    match written {
+                        Ok(()) => artifact.path = Some(stored),
+                        Err(e) => {
+                            tracing::warn!(
+                                "Could not store artifact '{}' of {}: {}",
+                                artifact.name,
+                                test_result.name,
+                                e
+                            );
+                            all_stored = false;
+                        }
+                    }
+                }
+
+                if all_stored && !test_result.failed() {
+                    test_result.remove_temp_dir();
+                }
+            }
+        }
+    }
+
+    /// Combine partial results, e.g. one per CI shard, into a single result.
+    ///
+    /// Suites with the same name are merged into one. A test reported by more
+    /// than one result keeps the result where it actually ran, and if it ran
+    /// in several, the one that started last. The merged run spans from the
+    /// earliest start to the latest end, and keeps the first run-level error.
+    pub fn merge(results: Vec<RunResult>) -> Result<RunResult> {
+        let Some(first) = results.first() else {
+            return Err(Error::invalid_config("No results to merge".to_string()));
+        };
+
+        let mut merged = RunResult::new(first.config.clone());
+        merged.start_time = results
+            .iter()
+            .map(|r| r.start_time)
+            .min()
+            .unwrap_or_default();
+        merged.end_time = results.iter().filter_map(|r| r.end_time).max();
+        merged.duration = merged.end_time.map(|end_time| {
+            Duration::from_millis((end_time - merged.start_time).num_milliseconds().max(0) as u64)
+        });
+        merged.error = results.iter().find_map(|r| r.error.clone());
+
+        let mut suites: IndexMap<String, Vec<SuiteResult>> = IndexMap::new();
+        for result in results {
+            for suite_result in result.suite_results {
+                suites
+                    .entry(suite_result.name.clone())
+                    .or_default()
+                    .push(suite_result);
+            }
+        }
+
+        for (name, parts) in suites {
+            let first = &parts[0];
+            let mut suite_result = SuiteResult::new(first.id, name, first.metadata.clone());
+            suite_result.start_time = parts.iter().map(|p| p.start_time).min().unwrap_or_default();
+            let end_time = parts.iter().filter_map(|p| p.end_time).max();
+            let error = parts.iter().find_map(|p| p.error.clone());
+            let stderr: Vec<&str> = parts.iter().filter_map(|p| p.stderr.as_deref()).collect();
+            let stderr = (!stderr.is_empty()).then(|| stderr.concat());
+
+            let mut tests: IndexMap<String, TestResult> = IndexMap::new();
+            for test_result in parts.into_iter().flat_map(|p| p.test_results) {
+                match tests.get(test_result.qualified_name()) {
+                    Some(existing) if !supersedes(&test_result, existing) => {}
+                    _ => {
+                        tests.insert(test_result.qualified_name().to_string(), test_result);
+                    }
+                }
+            }
+
+            for test_result in tests.into_values() {
+                suite_result.add_test_result(test_result);
+            }
+
+            suite_result.end_time = end_time;
+            suite_result.error = error;
+            suite_result.stderr = stderr;
+            suite_result.duration = end_time.map(|end_time| {
+                Duration::from_millis(
+                    (end_time - suite_result.start_time)
+                        .num_milliseconds()
+                        .max(0) as u64,
+                )
+            });
+            merged.add_suite_result(suite_result);
+        }
+
+        Ok(merged)
+    }
+
+    /// Fold the results of re-running some of this run's tests back into it.
+    ///
+    /// A re-run test's result replaces its earlier one, with the earlier
+    /// attempts kept ahead of its own, and a test that failed before but
+    /// passes on the re-run is marked flaky. Tests that weren't re-run keep
+    /// their earlier results.
+    pub fn merge_rerun(self, mut rerun: RunResult) -> Result<RunResult> {
+        let earlier: HashMap<(String, String), &TestResult> = self
+            .suite_results
+            .iter()
+            .flat_map(|suite| {
+                suite.test_results.iter().map(move |test| {
+                    let key = (suite.name.clone(), test.qualified_name().to_string());
+                    (key, test)
+                })
+            })
+            .collect();
+
+        for suite_result in rerun.suite_results.iter_mut() {
+            for test_result in suite_result.test_results.iter_mut() {
+                let key = (
+                    suite_result.name.clone(),
+                    test_result.qualified_name().to_string(),
+                );
+                let Some(before) = earlier.get(&key) else {
+                    continue;
+                };
+                if !test_result.passed() && !test_result.failed() {
+                    continue;
+                }
+
+                let mut attempts = if before.attempts.is_empty() {
+                    vec![TestAttempt::from(*before)]
+                } else {
+                    before.attempts.clone()
+                };
+                if test_result.attempts.is_empty() {
+                    attempts.push(TestAttempt::from(&*test_result));
+                } else {
+                    attempts.append(&mut test_result.attempts);
+                }
+
+                test_result.retry_count = attempts.len() as u32 - 1;
+                test_result.attempts = attempts;
+                test_result.flaky |= before.failed() && test_result.passed();
+            }
+        }
+
+        RunResult::merge(vec![self, rerun])
+    }
+}
+
+/// Whether `candidate` should replace `existing`, a result for the same test
+/// from another partial run, when merging results.
+fn supersedes(candidate: &TestResult, existing: &TestResult) -> bool {
+    let ran = |test: &TestResult| {
+        !matches!(
+            test.status,
+            TestStatus::Skipped | TestStatus::Pending | TestStatus::Cancelled
+        )
+    };
+
+    match (ran(candidate), ran(existing)) {
+        (true, false) => true,
+        (false, true) => false,
+        _ => candidate.start_time >= existing.start_time,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::TestMetadata;
+
+    fn run_with(tests: &[(&str, TestStatus)]) -> RunResult {
+        let mut suite_result = SuiteResult::new(
+            Uuid::new_v4(),
+            "suite".to_string(),
+            TestMetadata::new("suite".to_string()),
+        );
+        for (name, status) in tests {
+            let mut test_result = TestResult::new(
+                Uuid::new_v4(),
+                name.to_string(),
+                TestMetadata::new(name.to_string()),
+            );
+            test_result.finish(*status, None);
+            suite_result.add_test_result(test_result);
+        }
+
+        let mut result = RunResult::new(RunnerConfig::default());
+        result.add_suite_result(suite_result);
+        result.finish(None);
+        result
+    }
+
+    #[test]
+    fn test_merge_combines_suites_and_prefers_tests_that_ran() {
+        let first = run_with(&[("a", TestStatus::Passed), ("b", TestStatus::Skipped)]);
+        let second = run_with(&[("b", TestStatus::Failed), ("c", TestStatus::Passed)]);
+
+        let merged = RunResult::merge(vec![first.clone(), second, first]).unwrap();
+
+        assert_eq!(merged.total_suites, 1);
+        assert_eq!(merged.total_tests, 3);
+        assert_eq!(merged.passed_tests, 2);
+        assert_eq!(merged.failed_tests, 1);
+        assert!(RunResult::merge(Vec::new()).is_err());
+    }
+
+    #[test]
+    fn test_merge_rerun_marks_recovered_tests_flaky() {
+        let first = run_with(&[
+            ("a", TestStatus::Passed),
+            ("b", TestStatus::Failed),
+            ("c", TestStatus::Failed),
+        ]);
+        let rerun = run_with(&[("b", TestStatus::Passed), ("c", TestStatus::Failed)]);
+        assert_eq!(first.failures().len(), 2);
+
+        let merged = first.merge_rerun(rerun).unwrap();
+
+        assert_eq!(merged.total_tests, 3);
+        assert_eq!(merged.passed_tests, 2);
+        assert_eq!(merged.failed_tests, 1);
+        assert_eq!(merged.flaky_tests, 1);
+
+        let b = &merged.suite_results[0].test_results[1];
+        assert!(b.flaky);
+        assert_eq!(b.attempts.len(), 2);
+        assert_eq!(b.retry_count, 1);
+        assert_eq!(
+            merged.failures(),
+            HashSet::from([("suite".to_string(), "c".to_string())])
+        );
+    }
 }
@@ -0,0 +1,392 @@
+use serde::{Deserialize, Serialize};
+use std::fmt;
+use std::process::{Command, Stdio};
+use std::str::FromStr;
+
+use crate::runners::{
+    CargoRunnerConfig, CargoTestRunner, Launcher, ResourceLimits, RunResult, shell_quote,
+};
+use crate::{CancellationToken, Error, EventSender, Result, RunnerConfig, TestExecutable};
+
+/// An SSH destination, written as `[user@]host[:port]`. IPv6 addresses are
+/// written in brackets, `[user@][addr]:port`, or bare when there's no port.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct RemoteHost {
+    /// `host` or `user@host`, with IPv6 addresses unbracketed
+    pub destination: String,
+    pub port: Option<u16>,
+}
+
+impl RemoteHost {
+    /// The destination with an IPv6 address in brackets, as `scp` needs
+    /// before a `:path`
+    fn bracketed(&self) -> String {
+        match self.destination.rsplit_once('@') {
+            Some((user, host)) if host.contains(':') => format!("{}@[{}]", user, host),
+            None if self.destination.contains(':') => format!("[{}]", self.destination),
+            _ => self.destination.clone(),
+        }
+    }
+}
+
+impl FromStr for RemoteHost {
+    type Err = Error;
+
+    fn from_str(s: &str) -> Result<Self> {
+        let invalid = || {
+            Error::invalid_config(format!(
+                "Invalid remote host '{}': expected [user@]host[:port]",
+                s
+            ))
+        };
+
+        let parse_port = |port: &str| port.parse().map(Some).map_err(|_| invalid());
+
+        let (user, address) = match s.rsplit_once('@') {
+            Some((user, address)) => (Some(user), address),
+            None => (None, s),
+        };
+        let (host, port) = if let Some(bracketed) = address.strip_prefix('[') {
+            let (host, rest) = bracketed.split_once(']').ok_or_else(invalid)?;
+            match rest {
+                "" => (host, None),
+                _ => (
+                    host,
+                    parse_port(rest.strip_prefix(':').ok_or_else(invalid)?)?,
+                ),
+            }
+        } else if address.matches(':').count() > 1 {
+            // A bare IPv6 address, which can't be followed by a port
+            (address, None)
+        } else {
+            match address.split_once(':') {
+                Some((host, port)) => (host, parse_port(port)?),
+                None => (address, None),
+            }
+        };
+
+        // A leading `-` would be taken as an option by ssh and scp
+        if host.is_empty() || user.is_some_and(str::is_empty) || s.starts_with('-') {
+            return Err(invalid());
+        }
+
+        Ok(Self {
+            destination: match user {
+                Some(user) => format!("{}@{}", user, host),
+                None => host.to_string(),
+            },
+            port,
+        })
+    }
+}
+
+impl fmt::Display for RemoteHost {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self.port {
+            Some(port) => write!(f, "{}:{}", self.bracketed(), port),
+            None => write!(f, "{}", self.destination),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct RemoteConfig {
+    /// Hosts to run on. Executables are shared out between them round-robin.
+    pub hosts: Vec<RemoteHost>,
+    /// Directory on each host that executables are copied into
+    pub remote_dir: String,
+    /// Extra arguments passed to both `ssh` and `scp`, e.g. `-i <key>`
+    pub ssh_args: Vec<String>,
+}
+
+impl Default for RemoteConfig {
+    fn default() -> Self {
+        Self {
+            hosts: Vec::new(),
+            remote_dir: "/tmp/sheila".to_string(),
+            ssh_args: Vec::new(),
+        }
+    }
+}
+
+/// Launches executables on another machine over SSH, after copying them
+/// there with `scp`.
+///
+/// Only the executable itself is copied, so it must not depend on shared
+/// libraries missing from the host. Resource limits are applied on the host
+/// with `ulimit`. Killing a run stops the local `ssh` process, after which
+/// the remote executable is cut off from its output and exits once it next
+/// writes.
+#[derive(Debug, Clone)]
+pub struct SshLauncher {
+    host: RemoteHost,
+    remote_dir: String,
+    ssh_args: Vec<String>,
+}
+
+impl SshLauncher {
+    pub fn new(host: RemoteHost, config: &RemoteConfig) -> Self {
+        Self {
+            host,
+            remote_dir: config.remote_dir.clone(),
+            ssh_args: config.ssh_args.clone(),
+        }
+    }
+
+    fn ssh(&self) -> Command {
+        let mut command = Command::new("ssh");
+        command.args(["-o", "BatchMode=yes"]).args(&self.ssh_args);
+        if let Some(port) = self.host.port {
+            command.arg("-p").arg(port.to_string());
+        }
+        command.arg("--").arg(&self.host.destination);
+        command
+    }
+
+    fn remote_path(&self, executable: &TestExecutable) -> String {
+        let file_name = executable
+            .path
+            .file_name()
+            .unwrap_or(executable.path.as_os_str())
+            .to_string_lossy();
+        format!("{}/{}", self.remote_dir.trim_end_matches('/'), file_name)
+    }
+
+    fn run(&self, mut command: Command, action: &str) -> Result<()> {
+        let output = command.stdin(Stdio::null()).output()?;
+        if !output.status.success() {
+            return Err(Error::runner_config(format!(
+                "Failed to {} on {}: {}",
+                action,
+                self.host,
+                String::from_utf8_lossy(&output.stderr).trim()
+            )));
+        }
+        Ok(())
+    }
+}
+
+impl Launcher for SshLauncher {
+    fn prepare(&self, executable: &TestExecutable) -> Result<()> {
+        let mut mkdir = self.ssh();
+        mkdir.arg(format!("mkdir -p {}", shell_quote(&self.remote_dir)));
+        self.run(mkdir, "create the remote directory")?;
+
+        let mut scp = Command::new("scp");
+        scp.args(["-q", "-o", "BatchMode=yes"]).args(&self.ssh_args);
+        if let Some(port) = self.host.port {
+            scp.arg("-P").arg(port.to_string());
+        }
+        scp.arg("--").arg(&executable.path).arg(format!(
+            "{}:{}",
+            self.host.bracketed(),
+            self.remote_path(executable)
+        ));
+        self.run(scp, &format!("copy {}", executable.name))
+    }
+
+    fn command(
+        &self,
+        executable: &TestExecutable,
+        args: &[String],
+        envs: &[(&str, &str)],
+        limits: &ResourceLimits,
+    ) -> Result<Command> {
+        let mut script = format!("cd {}", shell_quote(&self.remote_dir));
+        if let Some(bytes) = limits.memory {
+            script.push_str(&format!(" && ulimit -v {}", bytes / 1024));
+        }
+        if let Some(cpu_time) = limits.cpu_time {
+            script.push_str(&format!(" && ulimit -t {}", cpu_time.as_secs().max(1)));
+        }
+
+        script.push_str(" && exec env");
+        for (key, value) in envs {
+            script.push_str(&format!(" {}={}", key, shell_quote(value)));
+        }
+        script.push(' ');
+        script.push_str(&shell_quote(&self.remote_path(executable)));
+        for arg in args {
+            script.push(' ');
+            script.push_str(&shell_quote(arg));
+        }
+
+        let mut command = self.ssh();
+        command.arg(script);
+        Ok(command)
+    }
+}
+
+/// Runs test executables on one or more remote hosts over SSH.
+///
+/// Executables are shared out between the hosts round-robin, and each host
+/// is driven by its own [`CargoTestRunner`] with an [`SshLauncher`], so
+/// output streams back as [`ProcessOutput`](crate::ProcessOutput) just like a local run. Results
+/// from every host are merged into a single [`RunResult`]. The result cache
+/// isn't used for remote runs.
+pub struct RemoteRunner {
+    config: RunnerConfig,
+    cargo_config: CargoRunnerConfig,
+    remote_config: RemoteConfig,
+    /// Shared by the hosts' runners, so their events are numbered as one
+    output_tx: Option<EventSender>,
+}
+
+impl RemoteRunner {
+    pub fn new(config: RunnerConfig, remote_config: RemoteConfig) -> Self {
+        Self {
+            config,
+            cargo_config: CargoRunnerConfig::default(),
+            remote_config,
+            output_tx: None,
+        }
+    }
+
+    pub fn new_with_output(
+        config: RunnerConfig,
+        remote_config: RemoteConfig,
+        output_tx: impl Into<EventSender>,
+    ) -> Self {
+        Self {
+            output_tx: Some(output_tx.into()),
+            ..Self::new(config, remote_config)
+        }
+    }
+
+    pub fn with_cargo_config(mut self, cargo_config: CargoRunnerConfig) -> Self {
+        self.cargo_config = cargo_config;
+        self
+    }
+
+    pub fn execute_tests(&mut self, executables: &[TestExecutable]) -> Result<RunResult> {
+        self.execute_tests_with_cancel(executables, &CancellationToken::new())
+    }
+
+    /// Runs each host's share of `executables` at the same time and merges
+    /// the results. A host that can't be reached fails the run, but results
+    /// from the other hosts are still returned.
+    pub fn execute_tests_with_cancel(
+        &mut self,
+        executables: &[TestExecutable],
+        cancel: &CancellationToken,
+    ) -> Result<RunResult> {
+        let hosts = &self.remote_config.hosts;
+        if hosts.is_empty() {
+            return Err(Error::invalid_config(
+                "No remote hosts configured".to_string(),
+            ));
+        }
+
+        let mut cargo_config = self.cargo_config.clone();
+        cargo_config.result_cache = false;
+
+        let host_results: Vec<Result<RunResult>> = std::thread::scope(|scope| {
+            let handles: Vec<_> = hosts
+                .iter()
+                .enumerate()
+                .map(|(index, host)| {
+                    let share: Vec<TestExecutable> = executables
+                        .iter()
+                        .skip(index)
+                        .step_by(hosts.len())
+                        .cloned()
+                        .collect();
+
+                    let launcher = SshLauncher::new(host.clone(), &self.remote_config);
+                    let runner = match self.output_tx {
+                        Some(ref output_tx) => {
+                            CargoTestRunner::new_with_output(self.config.clone(), output_tx.clone())
+                        }
+                        None => CargoTestRunner::new(self.config.clone()),
+                    };
+                    let mut runner = runner
+                        .with_cargo_config(cargo_config.clone())
+                        .with_launcher(launcher);
+
+                    scope.spawn(move || runner.execute_tests_with_cancel(&share, cancel))
+                })
+                .collect();
+
+            handles
+                .into_iter()
+                .map(|handle| {
+                    handle.join().unwrap_or_else(|_| {
+                        Err(Error::test_execution("Remote runner thread panicked"))
+                    })
+                })
+                .collect()
+        });
+
+        let mut result = RunResult::new(self.config.clone());
+        let mut error = None;
+        for (host, host_result) in hosts.iter().zip(host_results) {
+            match host_result {
+                Ok(host_result) => {
+                    for suite_result in host_result.suite_results {
+                        result.add_suite_result(suite_result);
+                    }
+                    error = error.or(host_result.error);
+                }
+                Err(e) => {
+                    error = error.or(Some(Error::test_execution(format!("{}: {}", host, e))));
+                }
+            }
+        }
+
+        result.finish(error);
+        Ok(result)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_remote_host_parsing() {
+        let host: RemoteHost = "ci@builder:2222".parse().unwrap();
+        assert_eq!(host.destination, "ci@builder");
+        assert_eq!(host.port, Some(2222));
+
+        let host: RemoteHost = "builder".parse().unwrap();
+        assert_eq!(host.port, None);
+
+        assert!("ci@".parse::<RemoteHost>().is_err());
+        assert!("builder:ssh".parse::<RemoteHost>().is_err());
+    }
+
+    #[test]
+    fn test_ipv6_remote_hosts() {
+        let host: RemoteHost = "ci@[2001:db8::1]:2222".parse().unwrap();
+        assert_eq!(host.destination, "ci@2001:db8::1");
+        assert_eq!(host.port, Some(2222));
+        assert_eq!(host.bracketed(), "ci@[2001:db8::1]");
+        assert_eq!(host.to_string(), "ci@[2001:db8::1]:2222");
+
+        let host: RemoteHost = "[::1]".parse().unwrap();
+        assert_eq!((host.destination.as_str(), host.port), ("::1", None));
+        let host: RemoteHost = "fe80::1".parse().unwrap();
+        assert_eq!((host.destination.as_str(), host.port), ("fe80::1", None));
+        assert_eq!(host.bracketed(), "[fe80::1]");
+
+        assert!("[::1".parse::<RemoteHost>().is_err());
+        assert!("[::1]2222".parse::<RemoteHost>().is_err());
+        assert!("[]:22".parse::<RemoteHost>().is_err());
+    }
+
+    #[test]
+    fn test_remote_hosts_cant_pass_for_options() {
+        assert!("-oProxyCommand=touch pwned".parse::<RemoteHost>().is_err());
+        assert!("-p:22".parse::<RemoteHost>().is_err());
+        assert!("@builder".parse::<RemoteHost>().is_err());
+
+        let launcher = SshLauncher::new("builder".parse().unwrap(), &RemoteConfig::default());
+        let args: Vec<_> = launcher
+            .ssh()
+            .get_args()
+            .map(|arg| arg.to_owned())
+            .collect();
+        let destination = args.iter().position(|arg| arg == "builder").unwrap();
+        assert_eq!(args[destination - 1], "--");
+    }
+}
@@ -0,0 +1,113 @@
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use std::fmt;
+use std::time::{Duration, Instant};
+
+use crate::runners::RunResult;
+use crate::{CancellationToken, Error, Result, TestStatus};
+
+/// How long to keep re-running the selected tests while hunting for a flaky
+/// failure. Either way the run stops at the first iteration with a failure.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[cfg_attr(feature = "schemas", derive(schemars::JsonSchema))]
+pub enum RepeatMode {
+    /// Run the tests at most this many times
+    Times(u32),
+    /// Start new iterations until this much time has passed
+    For(Duration),
+}
+
+impl RepeatMode {
+    fn should_continue(&self, iterations: u32, started_at: Instant) -> bool {
+        match *self {
+            RepeatMode::Times(times) => iterations < times,
+            RepeatMode::For(budget) => started_at.elapsed() < budget,
+        }
+    }
+}
+
+impl fmt::Display for RepeatMode {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            RepeatMode::Times(times) => write!(f, "up to {} times", times),
+            RepeatMode::For(budget) => write!(f, "for {:?}", budget),
+        }
+    }
+}
+
+/// Call `run` repeatedly until it returns a failing result, `cancel` is
+/// triggered or `mode` runs out.
+///
+/// The result of the last iteration is returned, so after a failure it
+/// describes the run that failed. Its `iterations` fields record how many
+/// iterations were run in total and how many each test took part in.
+pub fn repeat_until_failure(
+    mode: RepeatMode,
+    cancel: &CancellationToken,
+    mut run: impl FnMut() -> Result<RunResult>,
+) -> Result<RunResult> {
+    let started_at = Instant::now();
+    let mut counts: HashMap<(String, String), u32> = HashMap::new();
+    let mut iterations = 0;
+
+    loop {
+        let mut result = run()?;
+        iterations += 1;
+
+        for suite in result.suite_results.iter() {
+            for test in suite.test_results.iter() {
+                if matches!(
+                    test.status,
+                    TestStatus::Skipped | TestStatus::Cached | TestStatus::Pending
+                ) {
+                    continue;
+                }
+                *counts
+                    .entry((suite.name.clone(), test.qualified_name().to_string()))
+                    .or_default() += 1;
+            }
+        }
+
+        if !result.all_passed()
+            || cancel.is_cancelled()
+            || !mode.should_continue(iterations, started_at)
+        {
+            result.iterations = Some(iterations);
+            for suite in result.suite_results.iter_mut() {
+                for test in suite.test_results.iter_mut() {
+                    test.iterations = counts
+                        .get(&(suite.name.clone(), test.qualified_name().to_string()))
+                        .copied();
+                }
+            }
+            return Ok(result);
+        }
+    }
+}
+
+/// Parse a duration such as `90`, `90s`, `500ms`, `5m`, `1h` or `7d`. A bare
+/// number is taken as seconds.
+pub fn parse_duration(value: &str) -> Result<Duration> {
+    let value = value.trim();
+    let split = value
+        .find(|c: char| !c.is_ascii_digit())
+        .unwrap_or(value.len());
+    let (amount, unit) = value.split_at(split);
+
+    let invalid = || {
+        Error::invalid_config(format!(
+            "Invalid duration '{}': expected a number followed by ms, s, m, h or d",
+            value
+        ))
+    };
+
+    let amount: u64 = amount.parse().map_err(|_| invalid())?;
+    match unit.trim() {
+        "ms" => Ok(Duration::from_millis(amount)),
+        "" | "s" => Ok(Duration::from_secs(amount)),
+        "m" => Ok(Duration::from_secs(amount * 60)),
+        "h" => Ok(Duration::from_secs(amount * 60 * 60)),
+        "d" => Ok(Duration::from_secs(amount * 60 * 60 * 24)),
+        _ => Err(invalid()),
+    }
+}
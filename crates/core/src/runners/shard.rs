@@ -0,0 +1,157 @@
+use serde::{Deserialize, Serialize};
+use std::fmt;
+use std::str::FromStr;
+use strum_macros::EnumString;
+
+use crate::{Error, Result, stable_hash};
+
+/// How tests are distributed between shards.
+///
+/// - `Count`: tests are sorted by name and dealt out round-robin, which gives
+///   evenly sized shards as long as every machine sees the same test list.
+/// - `Hash`: each test is assigned by a stable hash of its name, so a test
+///   stays on the same shard even when other tests are added or removed.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize, EnumString)]
+#[cfg_attr(feature = "schemas", derive(schemars::JsonSchema))]
+#[strum(serialize_all = "lowercase")]
+pub enum ShardStrategy {
+    #[default]
+    Count,
+    Hash,
+}
+
+impl fmt::Display for ShardStrategy {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            ShardStrategy::Count => write!(f, "count"),
+            ShardStrategy::Hash => write!(f, "hash"),
+        }
+    }
+}
+
+/// A single partition of the test set, e.g. `3/8` for the third of eight shards.
+///
+/// Shard indices are 1-based to match how CI matrices are usually written.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[cfg_attr(feature = "schemas", derive(schemars::JsonSchema))]
+pub struct Shard {
+    pub index: usize,
+    pub total: usize,
+    pub strategy: ShardStrategy,
+}
+
+impl Shard {
+    pub fn new(index: usize, total: usize) -> Result<Self> {
+        if total == 0 || index == 0 || index > total {
+            return Err(Error::invalid_config(format!(
+                "Invalid shard {}/{}: expected 1 <= index <= total",
+                index, total
+            )));
+        }
+
+        Ok(Self {
+            index,
+            total,
+            strategy: ShardStrategy::default(),
+        })
+    }
+
+    pub fn with_strategy(mut self, strategy: ShardStrategy) -> Self {
+        self.strategy = strategy;
+        self
+    }
+
+    /// Keep only the items belonging to this shard, using `key` to identify
+    /// each item. Every item ends up in exactly one of the `total` shards.
+    pub fn select<T, F>(&self, items: Vec<T>, key: F) -> Vec<T>
+    where
+        F: Fn(&T) -> String,
+    {
+        match self.strategy {
+            ShardStrategy::Count => {
+                let mut keyed: Vec<(String, T)> =
+                    items.into_iter().map(|item| (key(&item), item)).collect();
+                keyed.sort_by(|(a, _), (b, _)| a.cmp(b));
+
+                keyed
+                    .into_iter()
+                    .enumerate()
+                    .filter(|(position, _)| position % self.total == self.index - 1)
+                    .map(|(_, (_, item))| item)
+                    .collect()
+            }
+            ShardStrategy::Hash => items
+                .into_iter()
+                .filter(|item| self.contains_key(&key(item)))
+                .collect(),
+        }
+    }
+
+    fn contains_key(&self, key: &str) -> bool {
+        (stable_hash(key.as_bytes()) % self.total as u64) as usize == self.index - 1
+    }
+}
+
+impl fmt::Display for Shard {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}/{}", self.index, self.total)
+    }
+}
+
+impl FromStr for Shard {
+    type Err = Error;
+
+    fn from_str(s: &str) -> Result<Self> {
+        let (index, total) = s.split_once('/').ok_or_else(|| {
+            Error::invalid_config(format!(
+                "Invalid shard '{}': expected <index>/<total>, e.g. 3/8",
+                s
+            ))
+        })?;
+
+        let parse = |value: &str| {
+            value.trim().parse::<usize>().map_err(|_| {
+                Error::invalid_config(format!(
+                    "Invalid shard '{}': '{}' is not a number",
+                    s, value
+                ))
+            })
+        };
+
+        Shard::new(parse(index)?, parse(total)?)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn names() -> Vec<String> {
+        (0..50).map(|i| format!("suite::test_{}", i)).collect()
+    }
+
+    #[test]
+    fn test_shard_parsing() {
+        let shard: Shard = "3/8".parse().unwrap();
+        assert_eq!((shard.index, shard.total), (3, 8));
+        assert!("0/8".parse::<Shard>().is_err());
+        assert!("9/8".parse::<Shard>().is_err());
+        assert!("three".parse::<Shard>().is_err());
+    }
+
+    #[test]
+    fn test_shards_are_disjoint_and_complete() {
+        for strategy in [ShardStrategy::Count, ShardStrategy::Hash] {
+            let mut seen = Vec::new();
+            for index in 1..=4 {
+                let shard = Shard::new(index, 4).unwrap().with_strategy(strategy);
+                seen.extend(shard.select(names(), |name| name.clone()));
+            }
+
+            seen.sort();
+            let mut expected = names();
+            expected.sort();
+            assert_eq!(seen, expected);
+        }
+    }
+}
@@ -0,0 +1,232 @@
+use serde::{Deserialize, Serialize};
+use std::fmt;
+use std::str::FromStr;
+
+use nom::{
+    IResult, Parser,
+    branch::alt,
+    bytes::complete::take_while1,
+    character::complete::{char, multispace0},
+    combinator::map,
+    multi::many0,
+    sequence::{delimited, preceded},
+};
+
+use crate::{Error, Result};
+
+/// A boolean expression over test tags, e.g. `(api & !slow) | smoke`.
+///
+/// `!` negates, `&` binds tighter than `|`, and parentheses group. A comma
+/// reads as `|`, so a plain `api,smoke` list still selects tests with any
+/// of the tags. Tag names may contain letters, digits and `_ - . : /`.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(try_from = "String", into = "String")]
+pub enum TagExpr {
+    Tag(String),
+    Not(Box<TagExpr>),
+    And(Box<TagExpr>, Box<TagExpr>),
+    Or(Box<TagExpr>, Box<TagExpr>),
+}
+
+impl TagExpr {
+    pub fn tag<S: Into<String>>(tag: S) -> Self {
+        TagExpr::Tag(tag.into())
+    }
+
+    /// An expression matching any of `tags`, or `None` if there are none
+    pub fn any_of<I, S>(tags: I) -> Option<Self>
+    where
+        I: IntoIterator<Item = S>,
+        S: Into<String>,
+    {
+        tags.into_iter().map(TagExpr::tag).reduce(TagExpr::or)
+    }
+
+    pub fn and(self, other: TagExpr) -> Self {
+        TagExpr::And(Box::new(self), Box::new(other))
+    }
+
+    pub fn or(self, other: TagExpr) -> Self {
+        TagExpr::Or(Box::new(self), Box::new(other))
+    }
+
+    /// Whether a test carrying `tags` is selected by this expression
+    pub fn matches<S: AsRef<str>>(&self, tags: &[S]) -> bool {
+        match self {
+            TagExpr::Tag(tag) => tags.iter().any(|t| t.as_ref() == tag),
+            TagExpr::Not(expr) => !expr.matches(tags),
+            TagExpr::And(lhs, rhs) => lhs.matches(tags) && rhs.matches(tags),
+            TagExpr::Or(lhs, rhs) => lhs.matches(tags) || rhs.matches(tags),
+        }
+    }
+
+    fn precedence(&self) -> u8 {
+        match self {
+            TagExpr::Or(..) => 0,
+            TagExpr::And(..) => 1,
+            TagExpr::Not(_) | TagExpr::Tag(_) => 2,
+        }
+    }
+
+    fn fmt_operand(&self, f: &mut fmt::Formatter<'_>, parent: u8) -> fmt::Result {
+        if self.precedence() < parent {
+            write!(f, "({})", self)
+        } else {
+            write!(f, "{}", self)
+        }
+    }
+}
+
+impl std::ops::Not for TagExpr {
+    type Output = TagExpr;
+
+    fn not(self) -> TagExpr {
+        TagExpr::Not(Box::new(self))
+    }
+}
+
+impl fmt::Display for TagExpr {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            TagExpr::Tag(tag) => write!(f, "{}", tag),
+            TagExpr::Not(expr) => {
+                write!(f, "!")?;
+                expr.fmt_operand(f, 2)
+            }
+            TagExpr::And(lhs, rhs) => {
+                lhs.fmt_operand(f, 1)?;
+                write!(f, " & ")?;
+                rhs.fmt_operand(f, 1)
+            }
+            TagExpr::Or(lhs, rhs) => {
+                lhs.fmt_operand(f, 0)?;
+                write!(f, " | ")?;
+                rhs.fmt_operand(f, 0)
+            }
+        }
+    }
+}
+
+fn token<'a>(c: char) -> impl Parser<&'a str, Output = char, Error = nom::error::Error<&'a str>> {
+    delimited(multispace0, char(c), multispace0)
+}
+
+fn tag_name(input: &str) -> IResult<&str, TagExpr> {
+    map(
+        delimited(
+            multispace0,
+            take_while1(|c: char| c.is_alphanumeric() || "_-.:/".contains(c)),
+            multispace0,
+        ),
+        TagExpr::tag,
+    )
+    .parse(input)
+}
+
+fn unary(input: &str) -> IResult<&str, TagExpr> {
+    alt((
+        map(preceded(token('!'), unary), |expr| !expr),
+        delimited(token('('), or_expr, token(')')),
+        tag_name,
+    ))
+    .parse(input)
+}
+
+fn and_expr(input: &str) -> IResult<&str, TagExpr> {
+    let (input, (first, rest)) = (unary, many0(preceded(token('&'), unary))).parse(input)?;
+    Ok((input, rest.into_iter().fold(first, TagExpr::and)))
+}
+
+fn or_expr(input: &str) -> IResult<&str, TagExpr> {
+    let (input, (first, rest)) = (
+        and_expr,
+        many0(preceded(alt((token('|'), token(','))), and_expr)),
+    )
+        .parse(input)?;
+    Ok((input, rest.into_iter().fold(first, TagExpr::or)))
+}
+
+impl FromStr for TagExpr {
+    type Err = Error;
+
+    fn from_str(s: &str) -> Result<Self> {
+        let rest = match or_expr(s) {
+            Ok(("", expr)) => return Ok(expr),
+            Ok((rest, _)) => rest,
+            Err(nom::Err::Error(e) | nom::Err::Failure(e)) => e.input,
+            Err(nom::Err::Incomplete(_)) => "",
+        };
+
+        let problem = match rest.trim() {
+            "" => "unexpected end of expression".to_string(),
+            rest => format!("unexpected '{}'", rest),
+        };
+        Err(Error::invalid_config(format!(
+            "Invalid tag expression '{}': {}",
+            s, problem
+        )))
+    }
+}
+
+impl TryFrom<String> for TagExpr {
+    type Error = Error;
+
+    fn try_from(value: String) -> Result<Self> {
+        value.parse()
+    }
+}
+
+impl From<TagExpr> for String {
+    fn from(expr: TagExpr) -> Self {
+        expr.to_string()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_tag_expr_precedence() {
+        let expr: TagExpr = "(api & !slow) | smoke".parse().unwrap();
+        assert!(expr.matches(&["api"]));
+        assert!(!expr.matches(&["api", "slow"]));
+        assert!(expr.matches(&["slow", "smoke"]));
+        assert!(!expr.matches::<&str>(&[]));
+
+        let expr: TagExpr = "a | b & c".parse().unwrap();
+        assert_eq!(
+            expr,
+            TagExpr::tag("a").or(TagExpr::tag("b").and(TagExpr::tag("c")))
+        );
+        assert_eq!(
+            "a,b".parse::<TagExpr>().unwrap(),
+            TagExpr::any_of(["a", "b"]).unwrap()
+        );
+    }
+
+    #[test]
+    fn test_tag_expr_display_round_trips() {
+        for input in [
+            "api & !slow | smoke",
+            "!(a | b) & c",
+            "a & (b | c)",
+            "db:postgres",
+        ] {
+            let expr: TagExpr = input.parse().unwrap();
+            assert_eq!(expr.to_string(), input);
+            assert_eq!(expr.to_string().parse::<TagExpr>().unwrap(), expr);
+        }
+    }
+
+    #[test]
+    fn test_tag_expr_rejects_malformed_input() {
+        for input in ["", "a &", "(a | b", "a b", "!"] {
+            assert!(
+                input.parse::<TagExpr>().is_err(),
+                "{:?} should not parse",
+                input
+            );
+        }
+    }
+}
@@ -1,5 +1,7 @@
 use crate::{
-    Error, Result, RunnerConfig, TestRunner, TestSuite, runners::RunResult, suite::SuiteResult,
+    CancellationToken, Result, RunnerConfig, TestRunner, TestSuite,
+    runners::{RunResult, dry_run_result, repeat_until_failure, run_suites},
+    suite::SuiteResult,
 };
 
 pub struct DefaultTestRunner {
@@ -19,42 +21,28 @@ impl Default for DefaultTestRunner {
 }
 
 impl TestRunner for DefaultTestRunner {
-    fn run(&self, suites: Vec<TestSuite>) -> Result<RunResult> {
-        let mut result = RunResult::new(self.config.clone());
-
-        let suites_to_run = self.filter_suites(suites);
-
-        if suites_to_run.is_empty() {
-            result.finish(None);
-            return Ok(result);
+    fn run_with_cancel(
+        &self,
+        suites: Vec<TestSuite>,
+        cancel: &CancellationToken,
+    ) -> Result<RunResult> {
+        let mut suites_to_run = self.filter_suites(suites);
+
+        if self.config.dry_run {
+            return Ok(dry_run_result(&self.config, &suites_to_run));
         }
 
-        for mut suite in suites_to_run {
-            match suite.execute() {
-                Ok(suite_result) => {
-                    let should_fail_fast = self.config.fail_fast && !suite_result.all_passed();
-                    result.add_suite_result(suite_result);
-
-                    if should_fail_fast {
-                        result.finish(Some(Error::test_execution(
-                            "Failing fast due to test failure",
-                        )));
-                        return Ok(result);
-                    }
-                }
-                Err(e) => {
-                    result.finish(Some(e));
-                    return Ok(result);
-                }
-            }
+        let _budget = self.config.start_budget(cancel);
+        match self.config.repeat {
+            Some(mode) => repeat_until_failure(mode, cancel, || {
+                run_suites(&self.config, &mut suites_to_run, cancel)
+            }),
+            None => run_suites(&self.config, &mut suites_to_run, cancel),
         }
-
-        result.finish(None);
-        Ok(result)
     }
 
     fn run_suite(&self, mut suite: TestSuite) -> Result<SuiteResult> {
-        suite.execute()
+        suite.execute_with_config(&self.config)
     }
 
     fn config(&self) -> &RunnerConfig {
@@ -0,0 +1,92 @@
+//! Versioning of the JSON sheila writes for runs and reports, and with the
+//! `schemas` feature, [JSON Schemas](https://json-schema.org) describing it
+//! so other tools can validate that output or generate typed clients for it.
+
+/// Version of the JSON format of [`RunResult`], [`TestReport`] and
+/// everything in them, written to their `schema_version` field.
+///
+/// Within a version, changes are additive: new fields may show up, always
+/// with a default so older documents still read. The version goes up when a
+/// field is removed or renamed, its type or meaning changes, or an enum
+/// loses or renames a variant. A consumer that ignores fields it doesn't
+/// know can read every document written with the version it was built for.
+///
+/// [`RunResult`]: crate::runners::RunResult
+/// [`TestReport`]: crate::TestReport
+pub const SCHEMA_VERSION: u32 = 1;
+
+/// Version of documents without a `schema_version`, which were all written
+/// in the first version's format
+pub(crate) fn schema_version() -> u32 {
+    1
+}
+
+#[cfg(feature = "schemas")]
+pub use types::SchemaType;
+
+#[cfg(feature = "schemas")]
+mod types {
+    use strum_macros::{Display, EnumString};
+
+    use super::SCHEMA_VERSION;
+    use crate::runners::RunResult;
+    use crate::suite::SuiteResult;
+    use crate::test::TestResult;
+    use crate::{ProcessOutput, Result, TestReport};
+
+    /// The output types a schema can be generated for
+    #[derive(Debug, Clone, Copy, PartialEq, Eq, EnumString, Display)]
+    #[strum(serialize_all = "kebab-case")]
+    pub enum SchemaType {
+        RunResult,
+        SuiteResult,
+        TestResult,
+        TestReport,
+        ProcessOutput,
+    }
+
+    impl SchemaType {
+        pub const ALL: [SchemaType; 5] = [
+            SchemaType::RunResult,
+            SchemaType::SuiteResult,
+            SchemaType::TestResult,
+            SchemaType::TestReport,
+            SchemaType::ProcessOutput,
+        ];
+
+        /// The type's JSON Schema, noting the [`SCHEMA_VERSION`] it
+        /// describes as `x-sheila-schema-version`
+        pub fn schema(self) -> Result<serde_json::Value> {
+            let schema = match self {
+                SchemaType::RunResult => schemars::schema_for!(RunResult),
+                SchemaType::SuiteResult => schemars::schema_for!(SuiteResult),
+                SchemaType::TestResult => schemars::schema_for!(TestResult),
+                SchemaType::TestReport => schemars::schema_for!(TestReport),
+                SchemaType::ProcessOutput => schemars::schema_for!(ProcessOutput),
+            };
+
+            let mut schema = serde_json::to_value(schema)?;
+            if let Some(object) = schema.as_object_mut() {
+                object.insert("x-sheila-schema-version".to_string(), SCHEMA_VERSION.into());
+            }
+            Ok(schema)
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::RunnerConfig;
+    use crate::runners::RunResult;
+
+    #[test]
+    fn test_results_without_a_version_read_as_the_first() {
+        let mut json = serde_json::to_value(RunResult::new(RunnerConfig::default())).unwrap();
+        assert_eq!(json["schema_version"], SCHEMA_VERSION);
+
+        json.as_object_mut().unwrap().remove("schema_version");
+        let result: RunResult = serde_json::from_value(json).unwrap();
+        assert_eq!(result.schema_version, 1);
+    }
+}
@@ -1,12 +1,20 @@
 use std::{
-    io::{BufRead, BufReader},
-    path::PathBuf,
+    collections::{HashMap, HashSet},
+    fs,
+    io::BufReader,
+    path::{Path, PathBuf},
     process::Stdio,
+    time::UNIX_EPOCH,
 };
 
+use cargo_metadata::diagnostic::DiagnosticLevel;
+use cargo_metadata::{Artifact, CompilerMessage, Message};
+use chrono::Utc;
 use cmdstruct::Command;
+use serde::{Deserialize, Serialize};
 
-use crate::{Error, Result, TestExecutable};
+use crate::cache::{CachedBuild, RunCache, env_fingerprint};
+use crate::{Error, Result, TestExecutable, stable_hash};
 
 #[derive(Command)]
 #[command(executable = "cargo")]
@@ -22,6 +30,48 @@ pub struct ExecutableBuilder {
 
     #[arg(option = "--cargo")]
     cargo: Vec<String>,
+
+    packages: PackageSelection,
+
+    /// Workspace to build, the working directory's if not set
+    root: Option<PathBuf>,
+}
+
+/// Which workspace packages to build test executables for, mirroring cargo's
+/// `--package`, `--workspace` and `--exclude`. With no packages named, the
+/// whole workspace is built.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct PackageSelection {
+    pub packages: Vec<String>,
+    pub workspace: bool,
+    /// Packages left out of a workspace build
+    pub exclude: Vec<String>,
+}
+
+impl PackageSelection {
+    pub fn args(&self) -> Vec<String> {
+        if self.workspace || self.packages.is_empty() {
+            let mut args = vec!["--workspace".to_string()];
+            for package in &self.exclude {
+                args.extend(["--exclude".to_string(), package.clone()]);
+            }
+            args
+        } else {
+            self.packages
+                .iter()
+                .flat_map(|package| ["--package".to_string(), package.clone()])
+                .collect()
+        }
+    }
+
+    /// Whether the workspace package `name` is selected
+    pub fn includes(&self, name: &str) -> bool {
+        if self.workspace || self.packages.is_empty() {
+            !self.exclude.iter().any(|excluded| excluded == name)
+        } else {
+            self.packages.iter().any(|package| package == name)
+        }
+    }
 }
 
 impl ExecutableBuilder {
@@ -31,9 +81,31 @@ impl ExecutableBuilder {
             filter,
             profile,
             cargo,
+            packages: PackageSelection::default(),
+            root: None,
+        }
+    }
+
+    /// Build the workspace at `root` rather than the working directory's
+    pub fn in_dir<P: Into<PathBuf>>(mut self, root: P) -> Self {
+        self.root = Some(root.into());
+        self
+    }
+
+    /// Cache of the workspace being built
+    fn cache(&self) -> Result<RunCache> {
+        match self.root {
+            Some(ref root) => Ok(RunCache::new(root)),
+            None => RunCache::current(),
         }
     }
 
+    /// Only build the test executables of the selected packages
+    pub fn with_packages(mut self, packages: PackageSelection) -> Self {
+        self.packages = packages;
+        self
+    }
+
     pub fn args(&self) -> Result<Vec<String>> {
         let mut cargo_args = vec![self.sub.clone()];
 
@@ -45,9 +117,9 @@ impl ExecutableBuilder {
             "--no-run".to_string(),
             "--tests".to_string(),
             "--verbose".to_string(),
-            "--workspace".to_string(),
             "--message-format=json-diagnostic-rendered-ansi".to_string(),
         ]);
+        cargo_args.extend(self.packages.args());
 
         if let Some(ref profile) = self.profile {
             cargo_args.extend_from_slice(&["--profile".to_string(), profile.clone()]);
@@ -66,10 +138,91 @@ impl ExecutableBuilder {
         Ok(cargo_args)
     }
 
+    /// Build the test executables, or reuse those of the last build if
+    /// nothing in the workspace changed since, and fail if they don't build
     pub fn exec(&self) -> Result<Vec<TestExecutable>> {
+        self.build()?.into_executables()
+    }
+
+    /// Build the test executables, unless the last build with the same
+    /// arguments can be reused as none of the workspace's files changed
+    /// since. A build that fails isn't an error here, so its diagnostics
+    /// can be shown.
+    pub fn build(&self) -> Result<TestBuild> {
+        // Without metadata, executables are still found but aren't mapped to
+        // their package directories, and builds aren't reused
+        let metadata = match self.root {
+            Some(ref root) => WorkspaceMetadata::load_in(root),
+            None => WorkspaceMetadata::load(),
+        }
+        .ok();
         let args = self.args()?;
-        let mut child = self
-            .command()
+        let key = build_key(&args);
+        let cache = self.cache().ok();
+        let sources = metadata
+            .as_ref()
+            .map(|metadata| sources_fingerprint(&metadata.workspace_root));
+
+        if let (Some(cache), Some(sources)) = (&cache, sources)
+            && let Ok(Some(cached)) = cache.load_build(&key)
+            && cached.sources == sources
+            && cached
+                .executables
+                .iter()
+                .all(|executable| executable.path.exists())
+        {
+            tracing::debug!("Nothing changed since the last build, reusing its executables");
+            return Ok(TestBuild::reused(cached.executables));
+        }
+
+        let build = self.run_cargo(&args, metadata.as_ref())?;
+        if build.success
+            && let (Some(cache), Some(sources)) = (&cache, sources)
+        {
+            let cached = CachedBuild {
+                sources,
+                executables: build.executables.clone(),
+                built_at: Utc::now(),
+            };
+            // A build that can't be recorded only means building next time
+            if let Err(e) = cache.save_build(&key, cached) {
+                tracing::warn!("Failed to record the build: {}", e);
+            }
+        }
+        Ok(build)
+    }
+
+    /// The executables of the last successful build with the same arguments,
+    /// without building them again even if the workspace changed since
+    pub fn last_build(&self) -> Result<TestBuild> {
+        let key = build_key(&self.args()?);
+        let cached = self.cache()?.load_build(&key)?.ok_or_else(|| {
+            Error::test_execution("No previous build to reuse, run without --no-build first")
+        })?;
+        if let Some(missing) = cached
+            .executables
+            .iter()
+            .find(|executable| !executable.path.exists())
+        {
+            return Err(Error::test_execution(format!(
+                "The last build's executable {} is gone, run without --no-build to rebuild",
+                missing.path.display()
+            )));
+        }
+        Ok(TestBuild::reused(cached.executables))
+    }
+
+    fn run_cargo(
+        &self,
+        args: &[String],
+        metadata: Option<&WorkspaceMetadata>,
+    ) -> Result<TestBuild> {
+        tracing::debug!("Building test executables: cargo {}", args.join(" "));
+        let mut command = self.command();
+        if let Some(ref root) = self.root {
+            command.current_dir(root);
+        }
+        let mut child = command
             .args(args)
             .stdout(Stdio::piped())
             .stderr(Stdio::piped())
@@ -81,123 +234,471 @@ impl ExecutableBuilder {
             .take()
             .ok_or_else(|| Error::test_execution("Failed to capture cargo build stdout"))?;
 
-        let mut executables = Vec::new();
-        let reader = BufReader::new(stdout);
-
-        for line in reader.lines() {
-            let line = line.map_err(|e| {
+        // Drained on its own thread, so verbose build output can't fill the
+        // pipe and stall cargo
+        let stderr = child.stderr.take().map(|mut stderr| {
+            std::thread::spawn(move || std::io::copy(&mut stderr, &mut std::io::sink()))
+        });
+
+        let mut build = TestBuild {
+            rebuilt: true,
+            ..TestBuild::default()
+        };
+        for message in Message::parse_stream(BufReader::new(stdout)) {
+            let message = message.map_err(|e| {
                 Error::test_execution(format!("Failed to read cargo output: {}", e))
             })?;
 
-            if let Ok(message) = serde_json::from_str::<serde_json::Value>(&line) {
-                if let Some(executable) = self.extract_test_executable(&message)? {
-                    executables.push(executable);
+            match message {
+                // A library's messages come again when its tests are built
+                Message::CompilerMessage(message) => {
+                    let diagnostic = compiler_diagnostic(message, metadata);
+                    if !build.diagnostics.contains(&diagnostic) {
+                        build.diagnostics.push(diagnostic);
+                    }
                 }
+                Message::CompilerArtifact(artifact) => {
+                    if let Some(executable) = test_executable(artifact, metadata) {
+                        build.executables.push(executable);
+                    }
+                }
+                _ => {}
             }
         }
 
         let exit_status = child
             .wait()
             .map_err(|e| Error::test_execution(format!("Failed to wait for cargo build: {}", e)))?;
-
-        if !exit_status.success() {
-            return Err(Error::test_execution(format!(
-                "Cargo build failed with exit code: {:?}",
-                exit_status.code()
-            )));
+        if let Some(stderr) = stderr {
+            let _ = stderr.join();
         }
 
-        Ok(executables)
+        tracing::debug!("Built {} test executable(s)", build.executables.len());
+        build.success = exit_status.success();
+        build.exit_code = exit_status.code();
+        Ok(build)
     }
 
-    fn extract_test_executable(
+    pub fn filter_executables(
         &self,
-        message: &serde_json::Value,
-    ) -> Result<Option<TestExecutable>> {
-        let reason = message
-            .get("reason")
-            .and_then(|r| r.as_str())
-            .ok_or_else(|| Error::test_execution("Missing reason field in cargo output"))?;
+        executables: &[TestExecutable],
+        target_filter: Option<&str>,
+    ) -> Vec<TestExecutable> {
+        match target_filter {
+            Some(target) => executables
+                .iter()
+                .filter(|exe| exe.matches_target(target))
+                .cloned()
+                .collect(),
+            None => executables.to_vec(),
+        }
+    }
+}
+
+/// A message the compiler gave while building the test executables, e.g. a
+/// type error or an unused variable
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct CompilerDiagnostic {
+    pub package_name: String,
+    /// Usually `error` or `warning`, or `note` or `help` on their own
+    pub level: DiagnosticLevel,
+    pub message: String,
+    /// The message as the compiler prints it, with the source it points at
+    pub rendered: Option<String>,
+}
 
-        if reason != "compiler-artifact" {
-            return Ok(None);
+impl CompilerDiagnostic {
+    pub fn is_error(&self) -> bool {
+        matches!(self.level, DiagnosticLevel::Error | DiagnosticLevel::Ice)
+    }
+
+    pub fn is_warning(&self) -> bool {
+        self.level == DiagnosticLevel::Warning
+    }
+}
+
+/// The test executables from building, or from reusing an earlier build,
+/// and what the compiler had to say
+#[derive(Debug, Clone, Default)]
+pub struct TestBuild {
+    pub executables: Vec<TestExecutable>,
+    pub diagnostics: Vec<CompilerDiagnostic>,
+    /// Whether cargo ran, rather than an earlier build being reused
+    pub rebuilt: bool,
+    pub success: bool,
+    /// Cargo's exit code, if it ran and exited normally
+    pub exit_code: Option<i32>,
+}
+
+impl TestBuild {
+    fn reused(executables: Vec<TestExecutable>) -> Self {
+        Self {
+            executables,
+            success: true,
+            ..Self::default()
         }
+    }
 
-        let package_id = message
-            .get("package_id")
-            .and_then(|p| p.as_str())
-            .ok_or_else(|| Error::test_execution("Missing package_id field"))?;
+    pub fn errors(&self) -> impl Iterator<Item = &CompilerDiagnostic> {
+        self.diagnostics
+            .iter()
+            .filter(|diagnostic| diagnostic.is_error())
+    }
 
-        let package_name = package_id
-            .split_whitespace()
-            .next()
-            .ok_or_else(|| Error::test_execution("Invalid package_id format"))?
-            .to_string();
-
-        let profile = message
-            .get("profile")
-            .ok_or_else(|| Error::test_execution("Missing profile field"))?;
-
-        let is_test = profile
-            .get("test")
-            .and_then(|t| t.as_bool())
-            .ok_or_else(|| Error::test_execution("Missing test field in profile"))?;
-
-        if !is_test {
-            return Ok(None);
-        }
-
-        if let Some(executable_path) = message.get("executable").and_then(|e| e.as_str()) {
-            let target = message
-                .get("target")
-                .ok_or_else(|| Error::test_execution("Missing target field"))?;
-
-            let name = target
-                .get("name")
-                .and_then(|n| n.as_str())
-                .ok_or_else(|| Error::test_execution("Missing name field in target"))?
-                .to_string();
-
-            Ok(Some(TestExecutable::new(
-                PathBuf::from(executable_path),
-                name,
-                package_name,
-            )))
+    pub fn warnings(&self) -> impl Iterator<Item = &CompilerDiagnostic> {
+        self.diagnostics
+            .iter()
+            .filter(|diagnostic| diagnostic.is_warning())
+    }
+
+    /// The executables, or an error listing the compile errors if the build
+    /// failed
+    pub fn into_executables(self) -> Result<Vec<TestExecutable>> {
+        if self.success {
+            return Ok(self.executables);
+        }
+
+        let errors: Vec<String> = self
+            .errors()
+            .map(|error| format!("{}: {}", error.package_name, error.message))
+            .collect();
+        Err(Error::test_execution(if errors.is_empty() {
+            format!("Cargo build failed with exit code: {:?}", self.exit_code)
         } else {
-            Ok(None)
+            format!(
+                "Failed to build the test executables:\n{}",
+                errors.join("\n")
+            )
+        }))
+    }
+}
+
+/// A workspace package, as reported by `cargo metadata`.
+#[derive(Debug, Clone)]
+pub struct WorkspacePackage {
+    pub id: String,
+    pub name: String,
+    /// Directory containing the package's `Cargo.toml`
+    pub root: PathBuf,
+    /// Names of the packages it depends on, including dev and build
+    /// dependencies
+    pub dependencies: Vec<String>,
+    /// Whether it has a library target, whose documentation examples
+    /// rustdoc can test
+    pub library: bool,
+}
+
+/// The packages of the current cargo workspace, used to map the test
+/// executables cargo builds back to the package they came from.
+#[derive(Debug, Clone)]
+pub struct WorkspaceMetadata {
+    pub workspace_root: PathBuf,
+    packages: HashMap<String, WorkspacePackage>,
+}
+
+impl WorkspaceMetadata {
+    /// Read the workspace from `cargo metadata`.
+    pub fn load() -> Result<Self> {
+        Self::load_in(Path::new("."))
+    }
+
+    /// Read the workspace at `dir` from `cargo metadata`.
+    pub fn load_in(dir: &Path) -> Result<Self> {
+        let output = std::process::Command::new("cargo")
+            .current_dir(dir)
+            .args(["metadata", "--format-version", "1", "--no-deps"])
+            .stdin(Stdio::null())
+            .output()
+            .map_err(|e| Error::test_execution(format!("Failed to run cargo metadata: {}", e)))?;
+
+        if !output.status.success() {
+            return Err(Error::test_execution(format!(
+                "cargo metadata failed: {}",
+                String::from_utf8_lossy(&output.stderr).trim()
+            )));
         }
+
+        Self::parse(&serde_json::from_slice(&output.stdout)?)
     }
 
-    pub fn filter_executables(
-        &self,
-        executables: &[TestExecutable],
-        target_filter: Option<&str>,
-    ) -> Vec<TestExecutable> {
-        if let Some(target) = target_filter {
-            let target_crate = if target.contains('/') || target.ends_with(".rs") {
-                TestExecutable::determine_target_crate(&PathBuf::from(target))
-            } else {
-                target.to_string()
+    pub fn parse(metadata: &serde_json::Value) -> Result<Self> {
+        let workspace_root = metadata
+            .get("workspace_root")
+            .and_then(|root| root.as_str())
+            .ok_or_else(|| Error::test_execution("Missing workspace_root in cargo metadata"))?;
+
+        let mut packages = HashMap::new();
+        for package in metadata
+            .get("packages")
+            .and_then(|packages| packages.as_array())
+            .into_iter()
+            .flatten()
+        {
+            let field = |name: &str| package.get(name).and_then(|value| value.as_str());
+            let (Some(id), Some(name), Some(manifest_path)) =
+                (field("id"), field("name"), field("manifest_path"))
+            else {
+                continue;
             };
 
-            let filtered = executables
-                .iter()
-                .filter(|exe| {
-                    let matches = exe.target_crate == target_crate
-                        || exe.name.contains(&target_crate)
-                        || exe.path.to_string_lossy().contains(&target_crate)
-                        || (target_crate == "examples"
-                            && (exe.name.contains("sheila_examples")
-                                || exe.path.to_string_lossy().contains("sheila_examples")));
-
-                    matches
-                })
-                .cloned()
-                .collect::<Vec<_>>();
+            let root = Path::new(manifest_path)
+                .parent()
+                .map(Path::to_path_buf)
+                .unwrap_or_default();
+            let dependencies = package
+                .get("dependencies")
+                .and_then(|dependencies| dependencies.as_array())
+                .into_iter()
+                .flatten()
+                .filter_map(|dependency| dependency.get("name")?.as_str())
+                .map(str::to_string)
+                .collect();
+            let library = package
+                .get("targets")
+                .and_then(|targets| targets.as_array())
+                .into_iter()
+                .flatten()
+                .filter_map(|target| target.get("kind")?.as_array())
+                .flatten()
+                .filter_map(|kind| kind.as_str())
+                .any(|kind| matches!(kind, "lib" | "rlib" | "proc-macro"));
+            packages.insert(
+                id.to_string(),
+                WorkspacePackage {
+                    id: id.to_string(),
+                    name: name.to_string(),
+                    root,
+                    dependencies,
+                    library,
+                },
+            );
+        }
 
-            filtered
-        } else {
-            executables.to_vec()
+        Ok(Self {
+            workspace_root: PathBuf::from(workspace_root),
+            packages,
+        })
+    }
+
+    pub fn package(&self, id: &str) -> Option<&WorkspacePackage> {
+        self.packages.get(id)
+    }
+
+    pub fn packages(&self) -> impl Iterator<Item = &WorkspacePackage> {
+        self.packages.values()
+    }
+
+    /// The package whose directory contains `path`, preferring the innermost
+    /// one when packages are nested
+    pub fn package_containing(&self, path: &Path) -> Option<&WorkspacePackage> {
+        self.packages
+            .values()
+            .filter(|package| path.starts_with(&package.root))
+            .max_by_key(|package| package.root.components().count())
+    }
+
+    /// Names of the workspace packages that depend on any of `names`,
+    /// directly or through other workspace packages, not counting `names`
+    /// themselves
+    pub fn dependents(&self, names: &[String]) -> Vec<String> {
+        let mut affected: HashSet<String> = names.iter().cloned().collect();
+        let mut dependents = Vec::new();
+
+        loop {
+            let found: Vec<String> = self
+                .packages
+                .values()
+                .filter(|package| !affected.contains(&package.name))
+                .filter(|package| package.dependencies.iter().any(|d| affected.contains(d)))
+                .map(|package| package.name.clone())
+                .collect();
+            if found.is_empty() {
+                break;
+            }
+            affected.extend(found.iter().cloned());
+            dependents.extend(found);
         }
+
+        dependents.sort();
+        dependents
+    }
+}
+
+/// The diagnostic of a `compiler-message` line of cargo's JSON output
+fn compiler_diagnostic(
+    message: CompilerMessage,
+    metadata: Option<&WorkspaceMetadata>,
+) -> CompilerDiagnostic {
+    CompilerDiagnostic {
+        package_name: package_name(&message.package_id.repr, metadata),
+        level: message.message.level,
+        message: message.message.message,
+        rendered: message.message.rendered,
+    }
+}
+
+/// The test executable a `compiler-artifact` line of cargo's JSON output
+/// reports, if it is one
+fn test_executable(
+    artifact: Artifact,
+    metadata: Option<&WorkspaceMetadata>,
+) -> Option<TestExecutable> {
+    if !artifact.profile.test {
+        return None;
+    }
+    let path = artifact.executable?;
+
+    let package_id = &artifact.package_id.repr;
+    let mut executable = TestExecutable::new(
+        path.into_std_path_buf(),
+        artifact.target.name,
+        package_name(package_id, metadata),
+    );
+    if let Some(kind) = artifact.target.kind.first() {
+        executable = executable.with_target(
+            kind.to_string(),
+            artifact.target.src_path.into_std_path_buf(),
+        );
+    }
+    if let Some(package) = metadata.and_then(|metadata| metadata.package(package_id)) {
+        executable = executable.with_package_root(package.root.clone());
+    }
+    Some(executable)
+}
+
+/// Name of the package with ID `id`, from the workspace metadata if it's
+/// known and otherwise from the ID itself
+fn package_name(id: &str, metadata: Option<&WorkspaceMetadata>) -> String {
+    metadata
+        .and_then(|metadata| metadata.package(id))
+        .map(|package| package.name.clone())
+        .or_else(|| package_name_from_id(id))
+        .unwrap_or_else(|| id.to_string())
+}
+
+/// Key of the builds made with cargo arguments `args` in the current
+/// environment
+fn build_key(args: &[String]) -> String {
+    format!("{:016x}", env_fingerprint(&HashMap::new(), args))
+}
+
+/// Fingerprint of the files in the workspace at `root` that a build may
+/// read: the path, size and modification time of each. Hidden directories
+/// other than `.cargo` are left out, as are cargo's target directories,
+/// which it marks with a `CACHEDIR.TAG`.
+fn sources_fingerprint(root: &Path) -> u64 {
+    let mut files = Vec::new();
+    let mut dirs = vec![root.to_path_buf()];
+    while let Some(dir) = dirs.pop() {
+        if dir.join("CACHEDIR.TAG").exists() {
+            continue;
+        }
+        let Ok(entries) = fs::read_dir(&dir) else {
+            continue;
+        };
+        for entry in entries.flatten() {
+            let Ok(metadata) = entry.metadata() else {
+                continue;
+            };
+            if metadata.is_dir() {
+                let name = entry.file_name();
+                if !name.to_string_lossy().starts_with('.') || name == ".cargo" {
+                    dirs.push(entry.path());
+                }
+            } else {
+                let modified = metadata
+                    .modified()
+                    .ok()
+                    .and_then(|modified| modified.duration_since(UNIX_EPOCH).ok())
+                    .unwrap_or_default();
+                files.push((entry.path(), metadata.len(), modified));
+            }
+        }
+    }
+    files.sort();
+
+    let mut bytes = Vec::new();
+    for (path, len, modified) in files {
+        bytes.extend_from_slice(path.to_string_lossy().as_bytes());
+        bytes.push(0);
+        bytes.extend_from_slice(&len.to_le_bytes());
+        bytes.extend_from_slice(&modified.as_nanos().to_le_bytes());
+    }
+    stable_hash(&bytes)
+}
+
+/// Package name from a cargo package ID, in either the old
+/// `name version (source)` format or the newer `source#name@version` one,
+/// where the name is left out when it matches the source's last path segment.
+fn package_name_from_id(id: &str) -> Option<String> {
+    let Some((source, fragment)) = id.split_once('#') else {
+        return id.split_whitespace().next().map(str::to_string);
+    };
+
+    match fragment.split_once('@') {
+        Some((name, _)) => Some(name.to_string()),
+        None => source
+            .trim_end_matches('/')
+            .rsplit('/')
+            .next()
+            .map(str::to_string),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_package_name_from_id() {
+        assert_eq!(
+            package_name_from_id("path+file:///ws/crates/core#sheila@0.1.0").as_deref(),
+            Some("sheila")
+        );
+        assert_eq!(
+            package_name_from_id("path+file:///ws/crates/sheila#0.1.0").as_deref(),
+            Some("sheila")
+        );
+        assert_eq!(
+            package_name_from_id("sheila 0.1.0 (path+file:///ws/crates/core)").as_deref(),
+            Some("sheila")
+        );
+    }
+
+    #[test]
+    fn test_dependents_include_transitive_workspace_packages() {
+        let package = |name: &str, dependencies: &[&str]| {
+            serde_json::json!({
+                "id": format!("path+file:///ws/{}#0.1.0", name),
+                "name": name,
+                "manifest_path": format!("/ws/{}/Cargo.toml", name),
+                "dependencies": dependencies
+                    .iter()
+                    .map(|name| serde_json::json!({ "name": name }))
+                    .collect::<Vec<_>>(),
+            })
+        };
+        let metadata = WorkspaceMetadata::parse(&serde_json::json!({
+            "workspace_root": "/ws",
+            "packages": [
+                package("core", &["serde"]),
+                package("cli", &["core"]),
+                package("server", &["cli"]),
+                package("macros", &[]),
+            ],
+        }))
+        .unwrap();
+
+        assert_eq!(
+            metadata.dependents(&["core".to_string()]),
+            vec!["cli".to_string(), "server".to_string()]
+        );
+        assert!(metadata.dependents(&["server".to_string()]).is_empty());
+        assert_eq!(
+            metadata
+                .package_containing(Path::new("/ws/cli/src/main.rs"))
+                .map(|package| package.name.as_str()),
+            Some("cli")
+        );
     }
 }
@@ -0,0 +1,188 @@
+//! Snapshot testing: a value is compared against the copy of it accepted
+//! last time.
+//!
+//! Accepted snapshots live in a `snapshots` directory next to the package's
+//! `Cargo.toml`, as `<name>.snap`. A value that doesn't match its snapshot,
+//! or has none yet, is written beside it as `<name>.snap.new` for `sheila
+//! snapshots review` to accept or reject. Setting `SHEILA_UPDATE_SNAPSHOTS=1`
+//! accepts new values as the tests run.
+
+use std::fs;
+use std::path::{Path, PathBuf};
+
+use crate::Result;
+use crate::assert::create_diff;
+
+/// Directory snapshots are kept in, relative to the package root
+pub const SNAPSHOT_DIR: &str = "snapshots";
+
+/// Extension of accepted snapshots
+pub const SNAPSHOT_EXTENSION: &str = ".snap";
+
+/// Extension of snapshots waiting for review
+pub const PENDING_EXTENSION: &str = ".snap.new";
+
+/// Set to `1` to accept new snapshot values instead of failing
+const UPDATE_ENV: &str = "SHEILA_UPDATE_SNAPSHOTS";
+
+/// What happened when a value was checked against its snapshot
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum SnapshotOutcome {
+    Matched,
+    /// The value was saved as the accepted snapshot
+    Updated,
+    /// The value was saved for review. Holds the accepted snapshot, if
+    /// there is one.
+    Pending(Option<String>),
+}
+
+/// Compare `value` against snapshot `name` in `dir`, saving it for review
+/// when it differs
+pub fn check_snapshot(dir: &Path, name: &str, value: &str) -> Result<SnapshotOutcome> {
+    let path = dir.join(format!("{}{}", name, SNAPSHOT_EXTENSION));
+    let pending = dir.join(format!("{}{}", name, PENDING_EXTENSION));
+    let value = normalize(value);
+
+    let accepted = fs::read_to_string(&path).ok();
+    if accepted.as_deref().map(normalize) == Some(value) {
+        if pending.exists() {
+            fs::remove_file(&pending)?;
+        }
+        return Ok(SnapshotOutcome::Matched);
+    }
+
+    fs::create_dir_all(dir)?;
+    if std::env::var(UPDATE_ENV).is_ok_and(|update| update == "1") {
+        fs::write(&path, format!("{}\n", value))?;
+        if pending.exists() {
+            fs::remove_file(&pending)?;
+        }
+        return Ok(SnapshotOutcome::Updated);
+    }
+
+    fs::write(&pending, format!("{}\n", value))?;
+    Ok(SnapshotOutcome::Pending(accepted))
+}
+
+/// Snapshots are compared without trailing newlines, which editors tend to
+/// add or remove
+fn normalize(value: &str) -> &str {
+    value.trim_end_matches(['\n', '\r'])
+}
+
+/// A value saved for review, next to the snapshot it would replace
+#[derive(Debug, Clone)]
+pub struct PendingSnapshot {
+    pub name: String,
+    /// Where the snapshot goes once accepted
+    pub path: PathBuf,
+    /// The accepted snapshot, if there is one
+    pub old: Option<String>,
+    pub new: String,
+}
+
+impl PendingSnapshot {
+    pub fn pending_path(&self) -> PathBuf {
+        self.path
+            .with_file_name(format!("{}{}", self.name, PENDING_EXTENSION))
+    }
+
+    /// Line diff from the accepted snapshot to the new value
+    pub fn diff(&self) -> String {
+        create_diff(self.old.as_deref().unwrap_or_default(), &self.new)
+    }
+
+    /// Replace the accepted snapshot with the new value
+    pub fn accept(&self) -> Result<()> {
+        fs::rename(self.pending_path(), &self.path)?;
+        Ok(())
+    }
+
+    /// Throw the new value away, keeping the accepted snapshot
+    pub fn reject(&self) -> Result<()> {
+        fs::remove_file(self.pending_path())?;
+        Ok(())
+    }
+}
+
+/// Snapshots waiting for review in `snapshots` directories under `root`,
+/// ordered by path. Build output and hidden directories are skipped.
+pub fn pending_snapshots(root: &Path) -> Result<Vec<PendingSnapshot>> {
+    let mut pending = Vec::new();
+    collect_pending(root, &mut pending)?;
+    pending.sort_by(|a, b| a.path.cmp(&b.path));
+    Ok(pending)
+}
+
+fn collect_pending(dir: &Path, pending: &mut Vec<PendingSnapshot>) -> Result<()> {
+    let in_snapshot_dir = dir.file_name().is_some_and(|name| name == SNAPSHOT_DIR);
+
+    for entry in fs::read_dir(dir)? {
+        let entry = entry?;
+        let path = entry.path();
+        let file_name = entry.file_name();
+        let file_name = file_name.to_string_lossy();
+
+        if entry.file_type()?.is_dir() {
+            if file_name != "target" && !file_name.starts_with('.') {
+                collect_pending(&path, pending)?;
+            }
+            continue;
+        }
+
+        if !in_snapshot_dir {
+            continue;
+        }
+        let Some(name) = file_name.strip_suffix(PENDING_EXTENSION) else {
+            continue;
+        };
+        let accepted = dir.join(format!("{}{}", name, SNAPSHOT_EXTENSION));
+        pending.push(PendingSnapshot {
+            name: name.to_string(),
+            old: fs::read_to_string(&accepted).ok(),
+            new: fs::read_to_string(&path)?,
+            path: accepted,
+        });
+    }
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_snapshot_review_cycle() {
+        let root = std::env::temp_dir().join(format!("sheila-snapshots-{}", uuid::Uuid::new_v4()));
+        let dir = root.join(SNAPSHOT_DIR);
+
+        assert_eq!(
+            check_snapshot(&dir, "greeting", "hello").unwrap(),
+            SnapshotOutcome::Pending(None)
+        );
+        let pending = pending_snapshots(&root).unwrap();
+        assert_eq!(pending.len(), 1);
+        assert_eq!(pending[0].name, "greeting");
+        pending[0].accept().unwrap();
+        assert_eq!(
+            check_snapshot(&dir, "greeting", "hello\n").unwrap(),
+            SnapshotOutcome::Matched
+        );
+
+        assert_eq!(
+            check_snapshot(&dir, "greeting", "goodbye").unwrap(),
+            SnapshotOutcome::Pending(Some("hello\n".to_string()))
+        );
+        let pending = pending_snapshots(&root).unwrap();
+        assert_eq!(pending[0].diff(), "-hello\n+goodbye\n");
+        pending[0].reject().unwrap();
+        assert!(pending_snapshots(&root).unwrap().is_empty());
+        assert_eq!(
+            fs::read_to_string(dir.join("greeting.snap")).unwrap(),
+            "hello\n"
+        );
+
+        fs::remove_dir_all(root).unwrap();
+    }
+}
@@ -0,0 +1,105 @@
+//! State shared between a suite's hooks and tests while it runs.
+//!
+//! Values are stored as JSON, like a suite's `shared_data` and a test's
+//! parameters, in two scopes: the suite's, which starts out as the suite's
+//! `shared_data` and is dropped once the suite is done, and the run's, which
+//! every suite in the run sees. Reads look in the suite's scope first.
+
+use indexmap::IndexMap;
+use serde::Serialize;
+use serde::de::DeserializeOwned;
+use std::sync::{Arc, RwLock, RwLockReadGuard, RwLockWriteGuard};
+
+use crate::{Error, Result};
+
+type Store = Arc<RwLock<IndexMap<String, serde_json::Value>>>;
+
+/// Handle on the state of a suite and the run it's in. Clones share the
+/// same state, so it can be handed to hooks and to the threads tests run on.
+#[derive(Debug, Clone, Default)]
+pub struct SharedState {
+    suite: Store,
+    run: Store,
+}
+
+impl SharedState {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// State for a suite in the same run as `self`, starting out with
+    /// `values` in the suite's scope
+    pub fn for_suite(&self, values: &IndexMap<String, serde_json::Value>) -> Self {
+        Self {
+            suite: Arc::new(RwLock::new(values.clone())),
+            run: Arc::clone(&self.run),
+        }
+    }
+
+    /// The value stored under `key` in the suite's scope, or failing that
+    /// the run's
+    pub fn get<T: DeserializeOwned>(&self, key: &str) -> Result<T> {
+        let value = read(&self.suite)
+            .get(key)
+            .cloned()
+            .or_else(|| read(&self.run).get(key).cloned())
+            .ok_or_else(|| Error::generic(format!("Shared data '{}' not found", key)))?;
+        Ok(serde_json::from_value(value)?)
+    }
+
+    /// Store `value` under `key` for the rest of the suite
+    pub fn set<T: Serialize>(&self, key: &str, value: T) -> Result<()> {
+        let value = serde_json::to_value(value)?;
+        write(&self.suite).insert(key.to_string(), value);
+        Ok(())
+    }
+
+    /// Store `value` under `key` for the rest of the run, where suites that
+    /// haven't set `key` themselves will see it
+    pub fn set_for_run<T: Serialize>(&self, key: &str, value: T) -> Result<()> {
+        let value = serde_json::to_value(value)?;
+        write(&self.run).insert(key.to_string(), value);
+        Ok(())
+    }
+
+    pub fn contains(&self, key: &str) -> bool {
+        read(&self.suite).contains_key(key) || read(&self.run).contains_key(key)
+    }
+
+    /// Remove `key` from the suite's scope, returning what was stored
+    pub fn remove(&self, key: &str) -> Option<serde_json::Value> {
+        write(&self.suite).shift_remove(key)
+    }
+}
+
+// A test that panicked while holding the lock can't have left a value half
+// written, so the state is still usable
+fn read(store: &Store) -> RwLockReadGuard<'_, IndexMap<String, serde_json::Value>> {
+    store.read().unwrap_or_else(|e| e.into_inner())
+}
+
+fn write(store: &Store) -> RwLockWriteGuard<'_, IndexMap<String, serde_json::Value>> {
+    store.write().unwrap_or_else(|e| e.into_inner())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_suite_values_shadow_run_values() {
+        let run = SharedState::new();
+        let a = run.for_suite(&IndexMap::from([("seed".to_string(), 1.into())]));
+        let b = run.for_suite(&IndexMap::new());
+
+        a.set_for_run("url", "http://localhost").unwrap();
+        b.set("url", "http://b").unwrap();
+
+        assert_eq!(a.get::<u32>("seed").unwrap(), 1);
+        assert!(b.get::<u32>("seed").is_err());
+        assert_eq!(a.get::<String>("url").unwrap(), "http://localhost");
+        assert_eq!(b.get::<String>("url").unwrap(), "http://b");
+        assert_eq!(b.remove("url"), Some("http://b".into()));
+        assert_eq!(b.get::<String>("url").unwrap(), "http://localhost");
+    }
+}
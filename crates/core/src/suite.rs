@@ -1,13 +1,16 @@
-use crate::fixtures::FixtureRegistry;
+use crate::fixtures::{FixtureDefinition, FixtureRegistry};
 use crate::internal::HookFn;
-use crate::test::{TestContext, TestResult};
-use crate::{Error, Result, Test, TestMetadata, TestStatus};
+use crate::runners::{CancelReason, CancellationToken};
+use crate::state::SharedState;
+use crate::test::{TestAttempt, TestContext, TestResult};
+use crate::{Error, Result, RunnerConfig, Test, TestMetadata, TestStatus, suite_id, test_id};
 use chrono::{DateTime, Utc};
-use indexmap::IndexMap;
+use indexmap::{IndexMap, IndexSet};
 use serde::{Deserialize, Serialize};
-use std::collections::HashMap;
+use std::collections::{HashMap, HashSet};
 use std::ops::Deref;
-use std::time::Duration;
+use std::thread;
+use std::time::{Duration, Instant};
 use uuid::Uuid;
 
 #[derive(Debug)]
@@ -20,7 +23,10 @@ pub struct TestSuite {
     pub meta: TestMetadata,
     pub hooks: SuiteHooks,
     pub fixtures: FixtureRegistry,
+    /// Values the suite's hooks and tests start out sharing
     pub shared_data: IndexMap<String, serde_json::Value>,
+    /// State shared while the suite runs
+    state: SharedState,
 }
 
 impl TestSuite {
@@ -36,6 +42,7 @@ impl TestSuite {
             hooks: SuiteHooks::new(),
             fixtures: FixtureRegistry::new(),
             shared_data: IndexMap::new(),
+            state: SharedState::new(),
         }
     }
 
@@ -52,6 +59,7 @@ impl TestSuite {
             hooks: SuiteHooks::new(),
             fixtures: FixtureRegistry::new(),
             shared_data: IndexMap::new(),
+            state: SharedState::new(),
         }
     }
 
@@ -68,6 +76,7 @@ impl TestSuite {
             hooks,
             fixtures: FixtureRegistry::new(),
             shared_data: IndexMap::new(),
+            state: SharedState::new(),
         }
     }
 
@@ -96,6 +105,11 @@ impl TestSuite {
         self
     }
 
+    pub fn with_fixture(mut self, definition: FixtureDefinition) -> Self {
+        self.fixtures.register_fixture(definition);
+        self
+    }
+
     pub fn module_path(&self) -> &str {
         &self.module_path
     }
@@ -125,6 +139,82 @@ impl TestSuite {
             .collect()
     }
 
+    /// Runnable tests in the order they should run: every test comes after
+    /// the tests it depends on, and declaration order is kept otherwise.
+    ///
+    /// Dependencies on tests that exist but aren't runnable don't affect the
+    /// order; the dependent is skipped when it comes up. Dependencies on
+    /// unknown tests and dependency cycles are configuration errors.
+    pub fn get_scheduled_tests(&self) -> Result<Vec<&Test>> {
+        let runnable = self.get_runnable_tests();
+        let runnable_names: HashSet<&str> = runnable
+            .iter()
+            .map(|test| test.meta.name.as_str())
+            .collect();
+
+        let mut visited = HashSet::new();
+        let mut visiting = IndexSet::new();
+        let mut order = Vec::new();
+        for test in runnable {
+            self.visit_test(
+                test,
+                &runnable_names,
+                &mut visited,
+                &mut visiting,
+                &mut order,
+            )?;
+        }
+
+        Ok(order)
+    }
+
+    fn visit_test<'a>(
+        &'a self,
+        test: &'a Test,
+        runnable: &HashSet<&str>,
+        visited: &mut HashSet<&'a str>,
+        visiting: &mut IndexSet<&'a str>,
+        order: &mut Vec<&'a Test>,
+    ) -> Result<()> {
+        let name = test.meta.name.as_str();
+        if visited.contains(name) {
+            return Ok(());
+        }
+
+        if !visiting.insert(name) {
+            let cycle: Vec<&str> = visiting
+                .iter()
+                .skip_while(|visiting| **visiting != name)
+                .copied()
+                .chain(std::iter::once(name))
+                .collect();
+            return Err(Error::invalid_config(format!(
+                "Circular test dependency in suite '{}': {}",
+                self.name,
+                cycle.join(" -> ")
+            )));
+        }
+
+        for dependency in test.attributes.depends_on.iter() {
+            let Some(prerequisite) = self.tests.get(dependency) else {
+                return Err(Error::invalid_config(format!(
+                    "Test '{}' depends on unknown test '{}' in suite '{}'",
+                    name, dependency, self.name
+                )));
+            };
+
+            if runnable.contains(dependency.as_str()) {
+                self.visit_test(prerequisite, runnable, visited, visiting, order)?;
+            }
+        }
+
+        visiting.shift_remove(name);
+        visited.insert(name);
+        order.push(test);
+
+        Ok(())
+    }
+
     pub fn ignore(mut self) -> Self {
         self.attributes.ignore = true;
         self
@@ -136,7 +226,7 @@ impl TestSuite {
     }
 
     pub fn retries(mut self, count: u32) -> Self {
-        self.attributes.retries = count;
+        self.attributes.retries = Some(count);
         self
     }
 
@@ -174,8 +264,66 @@ impl TestSuite {
     }
 
     pub fn execute(&mut self) -> Result<SuiteResult> {
+        self.execute_with_config(&RunnerConfig::default())
+    }
+
+    /// Executes the suite, enforcing per-test timeouts (falling back to
+    /// `default_test_timeout`) and an overall suite deadline (falling back
+    /// to `default_suite_timeout`).
+    ///
+    /// Failed tests are retried up to `retries` times (the test's own value,
+    /// or the suite's when unset, or the config's when neither is), waiting [`RunnerConfig::retry_delay`]
+    /// between attempts.
+    pub fn execute_with_config(&mut self, config: &RunnerConfig) -> Result<SuiteResult> {
+        self.execute_with(config, &CancellationToken::new())
+    }
+
+    /// Executes the suite like [`TestSuite::execute_with_config`], stopping
+    /// early once `cancel` is triggered. Tests that haven't run yet are marked
+    /// as skipped, and `after_all` hooks and suite fixture teardown still run.
+    pub fn execute_with(
+        &mut self,
+        config: &RunnerConfig,
+        cancel: &CancellationToken,
+    ) -> Result<SuiteResult> {
+        self.execute_with_state(config, cancel, &SharedState::new())
+    }
+
+    /// Executes the suite like [`TestSuite::execute_with`], as part of the
+    /// run `state` belongs to. The suite's hooks and tests share a state
+    /// starting out as its `shared_data`, and see the run's values.
+    pub fn execute_with_state(
+        &mut self,
+        config: &RunnerConfig,
+        cancel: &CancellationToken,
+        state: &SharedState,
+    ) -> Result<SuiteResult> {
+        self.state = state.for_suite(&self.shared_data);
+        let suite_timeout = self.get_timeout().or(config.default_suite_timeout);
+        let started_at = Instant::now();
         let mut result = SuiteResult::new(self.id, self.name.clone(), self.meta.clone());
-        let suite_context = TestContext::new(self.id, self.meta.clone());
+        let suite_context = TestContext::new(self.id, self.meta.clone())
+            .with_state(self.state.clone())
+            .with_seed(config.run_seed());
+
+        let scheduled_test_info: Vec<(Uuid, String, TestMetadata, Vec<String>)> =
+            match self.get_scheduled_tests() {
+                Ok(tests) => tests
+                    .iter()
+                    .map(|test| {
+                        (
+                            test.id,
+                            test.meta.name.clone(),
+                            test.meta.clone(),
+                            test.attributes.depends_on.clone(),
+                        )
+                    })
+                    .collect(),
+                Err(e) => {
+                    result.finish(Some(e));
+                    return Ok(result);
+                }
+            };
 
         if let Err(e) = self.fixtures.setup_suite_fixtures(&suite_context) {
             result.finish(Some(e));
@@ -190,57 +338,82 @@ impl TestSuite {
             return Ok(result);
         }
 
-        let runnable_test_info: Vec<(Uuid, String, TestMetadata)> = self
-            .get_runnable_tests()
-            .iter()
-            .map(|test| (test.id, test.meta.name.clone(), test.meta.clone()))
-            .collect();
-
-        for (test_id, test_name, test_meta) in runnable_test_info {
-            let test_context = TestContext::new(test_id, test_meta.clone());
+        let mut outcomes: HashMap<String, TestStatus> = HashMap::new();
 
-            if let Err(e) = self.fixtures.setup_test_fixtures(&test_context) {
+        for (test_id, test_name, test_meta, depends_on) in scheduled_test_info {
+            if let Some(reason) = cancel.reason() {
                 let mut test_result =
                     TestResult::new(test_id, test_name.clone(), test_meta.clone());
-                test_result.finish(TestStatus::Failed, Some(e));
+                test_result.skip(
+                    format!("Run stopped early: {}", reason),
+                    Some(Error::cancelled(reason.to_string())),
+                );
                 result.add_test_result(test_result);
                 continue;
             }
 
-            if let Err(e) =
-                self.hooks
-                    .execute_hooks(&self.hooks.before_each, &test_context, "before_each")
-            {
+            if suite_timeout.is_some_and(|timeout| started_at.elapsed() >= timeout) {
                 let mut test_result =
                     TestResult::new(test_id, test_name.clone(), test_meta.clone());
-                test_result.finish(TestStatus::Failed, Some(e));
+                test_result.skip("Suite ran out of time", None);
                 result.add_test_result(test_result);
+                continue;
+            }
 
-                let _ = self.fixtures.teardown_test_fixtures(&test_context);
+            if let Some(error) = unmet_dependency(&depends_on, &outcomes) {
+                let mut test_result =
+                    TestResult::new(test_id, test_name.clone(), test_meta.clone());
+                test_result.skip(error.message().to_string(), Some(error));
+                outcomes.insert(test_name, TestStatus::Skipped);
+                result.add_test_result(test_result);
                 continue;
             }
 
-            let mut test_result = if let Some(test) = self.tests.get(&test_name) {
-                test.execute(test_context.clone())
+            let attributes = self
+                .tests
+                .get(&test_name)
+                .map(|test| test.attributes.clone())
+                .unwrap_or_default();
+            let retries = attributes
+                .retries
+                .or(self.attributes.retries)
+                .unwrap_or(config.retries);
+            let retries = if attributes.xfail.is_some() {
+                0
             } else {
-                let mut result = TestResult::new(test_id, test_name.clone(), test_meta.clone());
-                result.finish(TestStatus::Failed, Some(Error::generic("Test not found")));
-                result
+                retries
             };
 
-            if let Err(e) =
-                self.hooks
-                    .execute_hooks(&self.hooks.after_each, &test_context, "after_each")
-            {
-                if test_result.passed() {
-                    test_result.finish(TestStatus::Failed, Some(e));
+            let mut attempts = Vec::new();
+            let mut test_result = loop {
+                let remaining =
+                    suite_timeout.map(|timeout| timeout.saturating_sub(started_at.elapsed()));
+                let mut test_result =
+                    self.run_test_once(test_id, &test_name, &test_meta, config, remaining, cancel);
+                attempts.push(TestAttempt::from(&test_result));
+
+                let attempt = attempts.len() as u32;
+                let exhausted =
+                    suite_timeout.is_some_and(|timeout| started_at.elapsed() >= timeout);
+                if !test_result.failed() || attempt > retries || exhausted || cancel.is_cancelled()
+                {
+                    break test_result;
                 }
-            }
 
-            if let Err(e) = self.fixtures.teardown_test_fixtures(&test_context) {
-                eprintln!("Warning: fixture teardown failed: {}", e);
+                test_result.remove_temp_dir();
+                thread::sleep(config.retry_delay(attempt));
+            };
+
+            test_result.retry_count = attempts.len() as u32 - 1;
+            test_result.flaky = test_result.passed() && test_result.retry_count > 0;
+            test_result.attempts = attempts;
+            test_result.apply_expectations(&attributes);
+
+            if config.fail_fast && test_result.failed() {
+                cancel.cancel(CancelReason::FailFast);
             }
 
+            outcomes.insert(test_name, test_result.status);
             result.add_test_result(test_result);
         }
 
@@ -257,9 +430,131 @@ impl TestSuite {
             return Ok(result);
         }
 
-        result.finish(None);
+        match suite_timeout {
+            _ if cancel.is_cancelled() => {
+                let reason = cancel.reason().unwrap_or(CancelReason::User);
+                result.finish(Some(Error::cancelled(format!(
+                    "Suite '{}' stopped early: {}",
+                    self.name, reason
+                ))));
+            }
+            Some(timeout) if started_at.elapsed() >= timeout => {
+                result.finish(Some(Error::timeout(format!(
+                    "Suite '{}' exceeded its timeout of {:?}",
+                    self.name, timeout
+                ))));
+            }
+            _ => result.finish(None),
+        }
+
         Ok(result)
     }
+
+    /// Runs a single attempt of a test: test-scoped fixtures, `before_each`
+    /// hooks, the test body itself, `after_each` hooks and fixture teardown.
+    fn run_test_once(
+        &mut self,
+        test_id: Uuid,
+        test_name: &str,
+        test_meta: &TestMetadata,
+        config: &RunnerConfig,
+        remaining: Option<Duration>,
+        cancel: &CancellationToken,
+    ) -> TestResult {
+        let test_context = TestContext::new(test_id, test_meta.clone())
+            .with_state(self.state.clone())
+            .with_seed(config.run_seed());
+        let setup_started = Instant::now();
+
+        if let Err(e) = self.fixtures.setup_test_fixtures(&test_context) {
+            let mut test_result =
+                TestResult::new(test_id, test_name.to_string(), test_meta.clone());
+            test_result.finish(TestStatus::Failed, Some(e));
+            test_context.clean_up_temp_dir(&mut test_result);
+            return test_result;
+        }
+
+        if let Err(e) =
+            self.hooks
+                .execute_hooks(&self.hooks.before_each, &test_context, "before_each")
+        {
+            let mut test_result =
+                TestResult::new(test_id, test_name.to_string(), test_meta.clone());
+            test_result.finish(TestStatus::Failed, Some(e));
+
+            let _ = self.fixtures.teardown_test_fixtures(&test_context);
+            test_context.clean_up_temp_dir(&mut test_result);
+            return test_result;
+        }
+
+        let setup_duration = setup_started.elapsed();
+
+        let mut test_result = if let Some(test) = self.tests.get(test_name) {
+            let test_timeout = match (
+                test.get_timeout().or(config.default_test_timeout),
+                remaining,
+            ) {
+                (Some(test_timeout), Some(remaining)) => Some(test_timeout.min(remaining)),
+                (test_timeout, remaining) => test_timeout.or(remaining),
+            };
+            test.execute_with_cancel(
+                test_context.clone(),
+                test_timeout,
+                cancel,
+                config.capture_output,
+            )
+        } else {
+            let mut result = TestResult::new(test_id, test_name.to_string(), test_meta.clone());
+            result.finish(TestStatus::Failed, Some(Error::generic("Test not found")));
+            result
+        };
+
+        let teardown_started = Instant::now();
+        let test_duration = test_result.duration;
+
+        if let Err(e) =
+            self.hooks
+                .execute_hooks(&self.hooks.after_each, &test_context, "after_each")
+            && test_result.passed()
+        {
+            test_result.finish(TestStatus::Failed, Some(e));
+            test_result.duration = test_duration;
+        }
+
+        if let Err(e) = self.fixtures.teardown_test_fixtures(&test_context) {
+            tracing::warn!("Fixture teardown failed for {}: {}", test_name, e);
+            if test_result.passed() {
+                test_result.finish(TestStatus::Failed, Some(e));
+                test_result.duration = test_duration;
+            }
+        }
+
+        test_context.clean_up_temp_dir(&mut test_result);
+        test_result.setup_duration = Some(setup_duration);
+        test_result.teardown_duration = Some(teardown_started.elapsed());
+        test_result
+    }
+}
+
+/// The first of `depends_on` that hasn't passed, as the reason to skip the
+/// test depending on it.
+fn unmet_dependency(
+    depends_on: &[String],
+    outcomes: &HashMap<String, TestStatus>,
+) -> Option<Error> {
+    depends_on
+        .iter()
+        .find_map(|dependency| match outcomes.get(dependency) {
+            Some(TestStatus::Passed) => None,
+            Some(status) => Some(Error::dependency(format!(
+                "'{}' {}, so this test was skipped",
+                dependency, status
+            ))),
+            None => Some(Error::dependency(format!(
+                "'{}' was not run, so this test was skipped",
+                dependency
+            ))),
+        })
 }
 
 impl Deref for TestSuite {
@@ -274,7 +569,7 @@ impl Deref for TestSuite {
 pub struct SuiteAttributes {
     pub ignore: bool,
     pub only: bool,
-    pub retries: u32,
+    pub retries: Option<u32>,
     pub timeout: Option<Duration>,
     pub tags: Vec<String>,
     pub category: Option<String>,
@@ -288,7 +583,7 @@ impl Default for SuiteAttributes {
         Self {
             ignore: false,
             only: false,
-            retries: 0,
+            retries: None,
             timeout: None,
             tags: Vec::new(),
             category: None,
@@ -300,8 +595,15 @@ impl Default for SuiteAttributes {
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
+#[cfg_attr(feature = "schemas", derive(schemars::JsonSchema))]
 pub struct SuiteResult {
+    /// Identifies this run of the suite
     pub id: Uuid,
+    /// Identifies the suite across runs, as given by [`suite_id`]
+    ///
+    /// [`suite_id`]: crate::suite_id
+    #[serde(default)]
+    pub stable_id: Uuid,
     pub name: String,
     pub start_time: DateTime<Utc>,
     pub end_time: Option<DateTime<Utc>>,
@@ -312,13 +614,20 @@ pub struct SuiteResult {
     pub passed_tests: usize,
     pub failed_tests: usize,
     pub skipped_tests: usize,
+    #[serde(default)]
+    pub flaky_tests: usize,
     pub error: Option<Error>,
+    /// Stderr the suite's tests printed that couldn't be attached to any
+    /// one of them, e.g. while several ran at once
+    #[serde(default)]
+    pub stderr: Option<String>,
 }
 
 impl SuiteResult {
     pub fn new(id: Uuid, name: String, metadata: TestMetadata) -> Self {
         Self {
             id,
+            stable_id: suite_id(&name),
             name,
             start_time: Utc::now(),
             end_time: None,
@@ -329,18 +638,26 @@ impl SuiteResult {
             passed_tests: 0,
             failed_tests: 0,
             skipped_tests: 0,
+            flaky_tests: 0,
             error: None,
+            stderr: None,
         }
     }
 
-    pub fn add_test_result(&mut self, result: TestResult) {
+    pub fn add_test_result(&mut self, mut result: TestResult) {
+        result.stable_id = test_id(&self.name, result.qualified_name());
         self.total_tests += 1;
 
-        match result.status {
-            TestStatus::Passed => self.passed_tests += 1,
-            TestStatus::Failed | TestStatus::Timeout => self.failed_tests += 1,
-            TestStatus::Skipped | TestStatus::Ignored => self.skipped_tests += 1,
-            _ => {}
+        if result.flaky {
+            self.flaky_tests += 1;
+        }
+
+        if result.passed() {
+            self.passed_tests += 1;
+        } else if result.failed() {
+            self.failed_tests += 1;
+        } else if result.skipped() {
+            self.skipped_tests += 1;
         }
 
         self.test_results.push(result);
@@ -435,3 +752,348 @@ impl SuiteHooks {
         Ok(())
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::runners::RunResult;
+    use crate::test::LogLevel;
+    use std::sync::{Arc, Mutex};
+
+    #[test]
+    fn test_dependents_run_after_prerequisites_and_skip_on_failure() {
+        let mut suite = TestSuite::new("deps")
+            .add_test(Test::new("uses_login", |_| Ok(())).depends_on("login"))
+            .add_test(Test::new("login", |_| {
+                Err(Error::assertion("bad credentials"))
+            }))
+            .add_test(Test::new("standalone", |_| Ok(())));
+
+        let order: Vec<&str> = suite
+            .get_scheduled_tests()
+            .unwrap()
+            .iter()
+            .map(|test| test.meta.name.as_str())
+            .collect();
+        assert_eq!(order, ["login", "uses_login", "standalone"]);
+
+        let result = suite.execute().unwrap();
+        let dependent = result
+            .test_results
+            .iter()
+            .find(|test| test.name == "uses_login")
+            .unwrap();
+        assert_eq!(dependent.status, TestStatus::Skipped);
+        assert!(matches!(dependent.error, Some(Error::Dependency { .. })));
+    }
+
+    #[test]
+    fn test_expected_failures_and_quarantined_tests_get_their_own_outcomes() {
+        let mut suite = TestSuite::new("outcomes")
+            .add_test(
+                Test::new("known_bug", |_| Err(Error::assertion("off by one")))
+                    .xfail("issue 12")
+                    .retries(3),
+            )
+            .add_test(Test::new("fixed_bug", |_| Ok(())).xfail("issue 13"))
+            .add_test(
+                Test::new("wobbly", |_| Err(Error::assertion("timed out"))).quarantine("flaky"),
+            )
+            .add_test(Test::new("blocked", |_| Ok(())).depends_on("fixed_bug"));
+
+        let result = suite.execute().unwrap();
+        let outcome = |name: &str| {
+            let test = result.test_results.iter().find(|test| test.name == name);
+            let test = test.unwrap();
+            (test.status, test.skip_reason.clone(), test.retry_count)
+        };
+
+        assert_eq!(
+            outcome("known_bug"),
+            (TestStatus::XFail, Some("issue 12".into()), 0)
+        );
+        assert_eq!(
+            outcome("fixed_bug"),
+            (TestStatus::XPass, Some("issue 13".into()), 0)
+        );
+        assert_eq!(
+            outcome("wobbly"),
+            (TestStatus::Quarantined, Some("flaky".into()), 0)
+        );
+        assert_eq!(outcome("blocked").0, TestStatus::Skipped);
+        assert!(outcome("blocked").1.is_some());
+        assert_eq!(
+            (
+                result.passed_tests,
+                result.failed_tests,
+                result.skipped_tests
+            ),
+            (1, 1, 2)
+        );
+    }
+
+    #[test]
+    fn test_circular_dependencies_are_rejected() {
+        let suite = TestSuite::new("cycle")
+            .add_test(Test::new("a", |_| Ok(())).depends_on("b"))
+            .add_test(Test::new("b", |_| Ok(())).depends_on("a"));
+
+        assert!(suite.get_scheduled_tests().is_err());
+    }
+
+    #[test]
+    fn test_config_retries_apply_when_test_and_suite_set_none() {
+        use std::sync::Arc;
+        use std::sync::atomic::{AtomicU32, Ordering};
+
+        let calls = Arc::new(AtomicU32::new(0));
+        let counter = Arc::clone(&calls);
+        let mut suite = TestSuite::new("retries").add_test(Test::new("flaky", move |_| {
+            if counter.fetch_add(1, Ordering::SeqCst) < 2 {
+                Err(Error::assertion("not yet"))
+            } else {
+                Ok(())
+            }
+        }));
+
+        let result = suite
+            .execute_with_config(&RunnerConfig::default().retries(2))
+            .unwrap();
+        let test = &result.test_results[0];
+        assert_eq!(test.status, TestStatus::Passed);
+        assert_eq!(test.retry_count, 2);
+        assert!(test.flaky);
+    }
+
+    #[test]
+    fn test_explicit_zero_retries_override_the_suite() {
+        use std::sync::Arc;
+        use std::sync::atomic::{AtomicU32, Ordering};
+
+        let calls = Arc::new(AtomicU32::new(0));
+        let counter = Arc::clone(&calls);
+        let mut suite = TestSuite::new("retries").retries(2).add_test(
+            Test::new("once", move |_| {
+                counter.fetch_add(1, Ordering::SeqCst);
+                Err(Error::assertion("always"))
+            })
+            .retries(0),
+        );
+
+        let result = suite.execute().unwrap();
+        assert_eq!(result.test_results[0].status, TestStatus::Failed);
+        assert_eq!(result.test_results[0].retry_count, 0);
+        assert_eq!(calls.load(Ordering::SeqCst), 1);
+    }
+
+    #[test]
+    fn test_captured_output_lands_on_the_result() {
+        let test = Test::new("chatty", |_| {
+            println!("to stdout");
+            eprintln!("to stderr");
+            Ok(())
+        });
+        let context = TestContext::new(Uuid::new_v4(), test.meta.clone());
+
+        let result = test.execute_with_cancel(context, None, &CancellationToken::new(), true);
+        assert_eq!(result.stdout.as_deref(), Some("to stdout\nto stderr\n"));
+    }
+
+    #[test]
+    fn test_stable_ids_are_kept_across_runs() {
+        let run = || {
+            let mut suite = TestSuite::new("ids")
+                .add_test(Test::new("first", |_| Ok(())))
+                .add_test(Test::new("second", |_| Ok(())));
+            suite.execute().unwrap()
+        };
+        let (a, b) = (run(), run());
+
+        assert_ne!(a.id, b.id);
+        assert_eq!(a.stable_id, b.stable_id);
+        for (x, y) in a.test_results.iter().zip(&b.test_results) {
+            assert_ne!(x.id, y.id);
+            assert_eq!(x.stable_id, y.stable_id);
+            assert_eq!(x.stable_id, test_id("ids", &x.name));
+        }
+        assert_ne!(a.test_results[0].stable_id, a.test_results[1].stable_id);
+    }
+
+    #[test]
+    fn test_breadcrumbs_and_logs_end_up_on_the_result() {
+        fn helper() {
+            crate::breadcrumb!("in helper");
+            crate::debug_log!(warn, "helper saw {}", 3);
+        }
+
+        let mut suite = TestSuite::new("trail").add_test(Test::new("leaves_a_trail", |ctx| {
+            crate::breadcrumb!(ctx, "started");
+            crate::debug_log!(ctx, info, "about to call {}", "helper");
+            helper();
+            Err(Error::assertion("lost"))
+        }));
+
+        let result = suite.execute().unwrap();
+        let test = &result.test_results[0];
+        assert_eq!(test.breadcrumbs, ["started", "in helper"]);
+        let logs: Vec<(LogLevel, &str)> = test
+            .logs
+            .iter()
+            .map(|line| (line.level, line.message.as_str()))
+            .collect();
+        assert_eq!(
+            logs,
+            [
+                (LogLevel::Info, "about to call helper"),
+                (LogLevel::Warn, "helper saw 3")
+            ]
+        );
+    }
+
+    #[test]
+    fn test_temp_dirs_are_removed_unless_the_test_failed() {
+        let used = Arc::new(Mutex::new(Vec::new()));
+        let (passing, failing) = (Arc::clone(&used), Arc::clone(&used));
+        let mut suite = TestSuite::new("temp")
+            .add_test(Test::new("passes", move |ctx| {
+                let dir = ctx.temp_dir()?;
+                assert_eq!(dir, ctx.temp_dir()?);
+                std::fs::write(dir.join("scratch"), "ok")?;
+                passing.lock().unwrap().push(dir);
+                Ok(())
+            }))
+            .add_test(Test::new("fails", move |ctx| {
+                failing.lock().unwrap().push(ctx.temp_dir()?);
+                Err(Error::assertion("no"))
+            }));
+
+        let result = suite.execute().unwrap();
+        let used = used.lock().unwrap();
+        assert_ne!(used[0], used[1]);
+        assert!(!used[0].exists());
+        assert!(used[1].is_dir());
+        assert_eq!(result.test_results[0].temp_dir, None);
+        assert_eq!(result.test_results[1].temp_dir.as_ref(), Some(&used[1]));
+        std::fs::remove_dir_all(&used[1]).unwrap();
+    }
+
+    #[test]
+    fn test_temp_dirs_of_retried_attempts_are_removed() {
+        let used = Arc::new(Mutex::new(Vec::new()));
+        let attempts = Arc::clone(&used);
+        let mut suite = TestSuite::new("temp").add_test(
+            Test::new("fails_twice", move |ctx| {
+                let mut attempts = attempts.lock().unwrap();
+                attempts.push(ctx.temp_dir()?);
+                if attempts.len() < 3 {
+                    Err(Error::assertion("not yet"))
+                } else {
+                    Ok(())
+                }
+            })
+            .retries(2),
+        );
+
+        let result = suite.execute().unwrap();
+        assert_eq!(result.test_results[0].status, TestStatus::Passed);
+        let used = used.lock().unwrap();
+        assert_eq!(used.len(), 3);
+        assert!(used.iter().all(|dir| !dir.exists()));
+    }
+
+    #[test]
+    fn test_hooks_and_tests_share_state() {
+        let hooks = SuiteHooks::new().before_all("seed", |ctx| ctx.set("count", 1));
+        let mut suite = TestSuite::new_with_hooks("state", hooks)
+            .set_shared_data("name".to_string(), "state")
+            .unwrap()
+            .add_test(Test::new("increments", |ctx| {
+                let count: u32 = ctx.get("count")?;
+                ctx.set("count", count + 1)
+            }))
+            .add_test(Test::new("sees_increment", |ctx| {
+                assert_eq!(ctx.get::<u32>("count")?, 2);
+                assert_eq!(ctx.get::<String>("name")?, "state");
+                Ok(())
+            }));
+
+        let result = suite.execute().unwrap();
+        assert!(result.all_passed(), "{:?}", result.test_results);
+        assert!(!suite.shared_data.contains_key("count"));
+    }
+
+    #[test]
+    fn test_artifacts_are_collected_and_stored() {
+        let mut suite = TestSuite::new("artifacts").add_test(Test::new("attaches", |ctx| {
+            ctx.attach_text("response body", "{\"ok\":true}");
+            Ok(())
+        }));
+
+        let mut run_result = RunResult::new(RunnerConfig::default());
+        run_result.add_suite_result(suite.execute().unwrap());
+
+        let output_dir = std::env::temp_dir().join(format!("sheila-artifacts-{}", Uuid::new_v4()));
+        run_result.store_artifacts(&output_dir);
+
+        let artifact = &run_result.suite_results[0].test_results[0].artifacts[0];
+        assert_eq!(artifact.name, "response body");
+        let stored = artifact.path.as_ref().unwrap();
+        assert_eq!(std::fs::read_to_string(stored).unwrap(), "{\"ok\":true}");
+
+        let _ = std::fs::remove_dir_all(output_dir);
+    }
+
+    #[test]
+    fn test_files_attached_from_temp_dirs_are_stored_before_removal() {
+        let mut suite = TestSuite::new("artifacts").add_test(Test::new("attaches", |ctx| {
+            let shot = ctx.temp_dir()?.join("shot.png");
+            std::fs::write(&shot, "png")?;
+            ctx.attach_file(&shot)
+        }));
+
+        let mut run_result = RunResult::new(RunnerConfig::default());
+        run_result.add_suite_result(suite.execute().unwrap());
+        let temp_dir = run_result.suite_results[0].test_results[0]
+            .temp_dir
+            .clone()
+            .unwrap();
+        assert!(temp_dir.join("shot.png").is_file());
+
+        let output_dir = std::env::temp_dir().join(format!("sheila-artifacts-{}", Uuid::new_v4()));
+        run_result.store_artifacts(&output_dir);
+
+        let test_result = &run_result.suite_results[0].test_results[0];
+        let stored = test_result.artifacts[0].path.as_ref().unwrap();
+        assert_eq!(std::fs::read_to_string(stored).unwrap(), "png");
+        assert_eq!(test_result.temp_dir, None);
+        assert!(!temp_dir.exists());
+
+        let _ = std::fs::remove_dir_all(output_dir);
+    }
+
+    #[test]
+    fn test_artifacts_that_cannot_be_stored_are_skipped() {
+        let gone = std::env::temp_dir().join(format!("sheila-gone-{}.log", Uuid::new_v4()));
+        let removed = gone.clone();
+        let mut suite = TestSuite::new("artifacts").add_test(Test::new("attaches", move |ctx| {
+            std::fs::write(&removed, "log")?;
+            ctx.attach_file(&removed)?;
+            std::fs::remove_file(&removed)?;
+            ctx.attach_text("note", "kept");
+            Ok(())
+        }));
+
+        let mut run_result = RunResult::new(RunnerConfig::default());
+        run_result.add_suite_result(suite.execute().unwrap());
+
+        let output_dir = std::env::temp_dir().join(format!("sheila-artifacts-{}", Uuid::new_v4()));
+        run_result.store_artifacts(&output_dir);
+
+        let artifacts = &run_result.suite_results[0].test_results[0].artifacts;
+        assert_eq!(artifacts[0].path.as_ref(), Some(&gone));
+        let stored = artifacts[1].path.as_ref().unwrap();
+        assert_eq!(std::fs::read_to_string(stored).unwrap(), "kept");
+
+        let _ = std::fs::remove_dir_all(output_dir);
+    }
+}
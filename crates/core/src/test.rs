@@ -1,19 +1,40 @@
-use crate::{Error, Result};
+use crate::random::{TestRng, run_seed, test_seed};
+use crate::runners::{CancelReason, CancellationToken};
+use crate::state::SharedState;
+use crate::{Error, Result, SourceLocation};
 use chrono::{DateTime, Utc};
 use indexmap::IndexMap;
+use serde::de::DeserializeOwned;
 use serde::{Deserialize, Serialize};
 use strum_macros::EnumString;
 
+use std::any::Any;
+use std::cell::RefCell;
 use std::collections::{HashMap, HashSet};
 use std::fmt;
-use std::time::Duration;
+use std::io;
+use std::panic::{self, AssertUnwindSafe};
+use std::path::{Path, PathBuf};
+use std::sync::{Arc, Mutex, MutexGuard, mpsc};
+use std::thread;
+use std::time::{Duration, Instant};
 use uuid::Uuid;
 
+/// How often a waiting watchdog checks for cancellation
+const CANCEL_POLL_INTERVAL: Duration = Duration::from_millis(50);
+
+thread_local! {
+    /// Trail of the test running on this thread, for code that isn't handed
+    /// its context
+    static CURRENT_TRAIL: RefCell<Option<Trail>> = const { RefCell::new(None) };
+}
+
 pub type TestFn = Box<dyn Fn(TestContext) -> Result<()> + Send + Sync + 'static>;
 
 pub struct Test {
     pub id: Uuid,
-    pub test_fn: TestFn,
+    /// Shared so the body can be moved onto a watchdog-monitored worker thread
+    pub test_fn: Arc<dyn Fn(TestContext) -> Result<()> + Send + Sync + 'static>,
     pub attributes: TestAttributes,
     pub meta: TestMetadata,
     pub params: Option<IndexMap<String, serde_json::Value>>,
@@ -27,7 +48,7 @@ impl Test {
         let name = name.into();
         Self {
             id: Uuid::new_v4(),
-            test_fn: Box::new(test_fn),
+            test_fn: Arc::new(test_fn),
             attributes: TestAttributes::default(),
             meta: TestMetadata::new(name),
             params: None,
@@ -60,7 +81,7 @@ impl Test {
     }
 
     pub fn retries(mut self, count: u32) -> Self {
-        self.attributes.retries = count;
+        self.attributes.retries = Some(count);
         self
     }
 
@@ -79,8 +100,64 @@ impl Test {
         self
     }
 
-    pub fn execute(&self, mut context: TestContext) -> TestResult {
+    /// Only run this test after `test` has passed, skipping it otherwise.
+    pub fn depends_on<S: Into<String>>(mut self, test: S) -> Self {
+        self.attributes.depends_on.push(test.into());
+        self
+    }
+
+    /// Expect the test to fail, for `reason`. It's reported as
+    /// [`TestStatus::XFail`] when it does, and fails as
+    /// [`TestStatus::XPass`] when it passes. Expected failures aren't
+    /// retried.
+    pub fn xfail<S: Into<String>>(mut self, reason: S) -> Self {
+        self.attributes.xfail = Some(reason.into());
+        self
+    }
+
+    /// Run the test but don't let it fail the run, for `reason`, e.g. while
+    /// it's too flaky to trust. A failure is reported as
+    /// [`TestStatus::Quarantined`].
+    pub fn quarantine<S: Into<String>>(mut self, reason: S) -> Self {
+        self.attributes.quarantine = Some(reason.into());
+        self
+    }
+
+    pub fn execute(&self, context: TestContext) -> TestResult {
+        self.execute_with_timeout(context, self.get_timeout())
+    }
+
+    /// Runs the test body on a dedicated worker thread while the calling thread
+    /// acts as a watchdog. If the body doesn't finish within `timeout`, the test
+    /// is marked as [`TestStatus::Timeout`] and the worker is abandoned so the
+    /// rest of the run can continue.
+    pub fn execute_with_timeout(
+        &self,
+        context: TestContext,
+        timeout: Option<Duration>,
+    ) -> TestResult {
+        self.execute_with_cancel(context, timeout, &CancellationToken::new(), false)
+    }
+
+    /// Like [`Test::execute_with_timeout`], but also stops waiting on the worker
+    /// once `cancel` is triggered, marking the test as skipped.
+    ///
+    /// With `capture_output`, anything the body prints to stdout or stderr
+    /// (including its panic message) is captured instead of written out, and
+    /// attached to the result as `stdout`, interleaved as it was printed.
+    /// Capture goes through the standard library's print machinery, so it only
+    /// sees `print!`-style output from the body's own thread: threads the body
+    /// spawns, and anything writing to fds 1 and 2 directly (child processes,
+    /// C code, `libc::write`), still go straight to the terminal.
+    pub fn execute_with_cancel(
+        &self,
+        mut context: TestContext,
+        timeout: Option<Duration>,
+        cancel: &CancellationToken,
+        capture_output: bool,
+    ) -> TestResult {
         let mut result = TestResult::new(self.id, self.meta.name.clone(), self.meta.clone());
+        result.tags = self.attributes.tags.clone();
 
         if let Some(ref params) = self.params {
             context.params = params.clone();
@@ -88,13 +165,82 @@ impl Test {
 
         result.start();
 
-        let execution_result = (self.test_fn)(context);
+        let capture = capture_output.then(|| Arc::new(Mutex::new(Vec::new())));
+        let worker_capture = capture.clone();
+        let trail = context.trail.clone();
+        let artifacts = Arc::clone(&context.artifacts);
+        let (tx, rx) = mpsc::channel();
+        let test_fn = Arc::clone(&self.test_fn);
+        let spawned = thread::Builder::new()
+            .name(format!("sheila-test-{}", self.meta.name))
+            .spawn(move || {
+                io::set_output_capture(worker_capture);
+                CURRENT_TRAIL.set(Some(context.trail.clone()));
+                let outcome = panic::catch_unwind(AssertUnwindSafe(|| test_fn(context)));
+                let _ = tx.send(outcome);
+            });
+
+        if let Err(e) = spawned {
+            result.finish(TestStatus::Failed, Some(Error::from(e)));
+            return result;
+        }
+
+        let deadline = timeout.map(|timeout| Instant::now() + timeout);
+        let outcome = loop {
+            let wait = deadline.map_or(CANCEL_POLL_INTERVAL, |deadline| {
+                deadline
+                    .saturating_duration_since(Instant::now())
+                    .min(CANCEL_POLL_INTERVAL)
+            });
+
+            match rx.recv_timeout(wait) {
+                Err(mpsc::RecvTimeoutError::Timeout) if cancel.is_cancelled() => {
+                    let reason = cancel.reason().unwrap_or(CancelReason::User);
+                    result.finish(
+                        TestStatus::Skipped,
+                        Some(Error::cancelled(reason.to_string())),
+                    );
+                    result.stdout = captured_output(capture.as_ref());
+                    trail.record(&mut result);
+                    result.artifacts = locked(&artifacts).clone();
+                    return result;
+                }
+                Err(mpsc::RecvTimeoutError::Timeout)
+                    if deadline.is_none_or(|deadline| Instant::now() < deadline) =>
+                {
+                    continue;
+                }
+                outcome => break outcome,
+            }
+        };
 
-        match execution_result {
-            Ok(()) => result.finish(TestStatus::Passed, None),
-            Err(error) => result.finish(TestStatus::Failed, Some(error)),
+        match outcome {
+            Ok(Ok(Ok(()))) => result.finish(TestStatus::Passed, None),
+            Ok(Ok(Err(error))) => result.finish(TestStatus::Failed, Some(error)),
+            Ok(Err(payload)) => result.finish(
+                TestStatus::Failed,
+                Some(Error::test_execution(format!(
+                    "Test panicked: {}",
+                    panic_message(payload.as_ref())
+                ))),
+            ),
+            Err(mpsc::RecvTimeoutError::Timeout) => result.finish(
+                TestStatus::Timeout,
+                Some(Error::timeout(format!(
+                    "Test '{}' exceeded its timeout of {:?}",
+                    self.meta.name,
+                    timeout.unwrap_or_default()
+                ))),
+            ),
+            Err(mpsc::RecvTimeoutError::Disconnected) => result.finish(
+                TestStatus::Failed,
+                Some(Error::test_execution("Test worker exited without a result")),
+            ),
         }
 
+        result.stdout = captured_output(capture.as_ref());
+        trail.record(&mut result);
+        result.artifacts = locked(&artifacts).clone();
         result
     }
 
@@ -111,6 +257,100 @@ impl Test {
     }
 }
 
+pub(crate) fn panic_message(payload: &(dyn Any + Send)) -> String {
+    if let Some(message) = payload.downcast_ref::<&str>() {
+        message.to_string()
+    } else if let Some(message) = payload.downcast_ref::<String>() {
+        message.clone()
+    } else {
+        "<non-string panic payload>".to_string()
+    }
+}
+
+/// Breadcrumbs and log lines a test leaves, shared between its context's
+/// clones and the thread it runs on
+#[derive(Debug, Clone, Default)]
+struct Trail {
+    breadcrumbs: Arc<Mutex<Vec<String>>>,
+    logs: Arc<Mutex<Vec<LogLine>>>,
+    /// Time spent in delays mocks injected, see [`add_injected_delay`]
+    injected_delay: Arc<Mutex<Duration>>,
+}
+
+impl Trail {
+    fn log(&self, level: LogLevel, message: String) {
+        trace(level, &message);
+        locked(&self.logs).push(LogLine {
+            level,
+            message,
+            time: Utc::now(),
+        });
+    }
+
+    fn record(&self, result: &mut TestResult) {
+        result.breadcrumbs = locked(&self.breadcrumbs).clone();
+        result.logs = locked(&self.logs).clone();
+
+        let injected_delay = *locked(&self.injected_delay);
+        if !injected_delay.is_zero() {
+            result.injected_delay = Some(injected_delay);
+            result.duration = result
+                .duration
+                .map(|duration| duration.saturating_sub(injected_delay));
+        }
+    }
+}
+
+/// Leave a breadcrumb on the test running on this thread. Does nothing
+/// outside of a test, or in tests run by cargo.
+pub fn breadcrumb<S: Into<String>>(crumb: S) {
+    CURRENT_TRAIL.with_borrow(|trail| {
+        if let Some(trail) = trail {
+            locked(&trail.breadcrumbs).push(crumb.into());
+        }
+    });
+}
+
+/// Log `message` to the test running on this thread, like
+/// [`TestContext::log`]. Outside of a test it only goes to `tracing`.
+pub fn log<S: Into<String>>(level: LogLevel, message: S) {
+    CURRENT_TRAIL.with_borrow(|trail| match trail {
+        Some(trail) => trail.log(level, message.into()),
+        None => trace(level, &message.into()),
+    });
+}
+
+/// Count `delay`, spent sleeping in a mock, against the test running on
+/// this thread, so it's reported apart from the test's own duration
+pub(crate) fn add_injected_delay(delay: Duration) {
+    CURRENT_TRAIL.with_borrow(|trail| {
+        if let Some(trail) = trail {
+            *locked(&trail.injected_delay) += delay;
+        }
+    });
+}
+
+fn trace(level: LogLevel, message: &str) {
+    match level {
+        LogLevel::Debug => tracing::debug!("{}", message),
+        LogLevel::Info => tracing::info!("{}", message),
+        LogLevel::Warn => tracing::warn!("{}", message),
+        LogLevel::Error => tracing::error!("{}", message),
+    }
+}
+
+/// Lock state shared with a test's worker thread, which may have panicked
+/// while holding it
+fn locked<T>(mutex: &Mutex<T>) -> MutexGuard<'_, T> {
+    mutex.lock().unwrap_or_else(|e| e.into_inner())
+}
+
+/// Output captured from a test's worker thread so far, if it printed anything
+fn captured_output(capture: Option<&Arc<Mutex<Vec<u8>>>>) -> Option<String> {
+    let buffer = locked(capture?);
+    (!buffer.is_empty()).then(|| String::from_utf8_lossy(&buffer).into_owned())
+}
+
 impl fmt::Debug for Test {
     fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
         f.debug_struct("Test")
@@ -132,17 +372,189 @@ pub struct TestContext {
     pub fixtures: IndexMap<String, String>,
     pub params: IndexMap<String, serde_json::Value>,
     pub shared: IndexMap<String, serde_json::Value>,
+    /// Shared with clones, so breadcrumbs and log lines left on the worker
+    /// thread can be collected into the result
+    trail: Trail,
+    /// Shared with clones, like `trail`
+    artifacts: Arc<Mutex<Vec<Artifact>>>,
+    /// Created the first time the test asks for it
+    temp_dir: Arc<Mutex<Option<PathBuf>>>,
+    /// State of the suite and run, shared with their hooks and other tests
+    state: SharedState,
+    /// Seeded from the run seed and the test's name
+    rng: TestRng,
 }
 
 impl TestContext {
     pub fn new(id: Uuid, meta: TestMetadata) -> Self {
         Self {
             id,
-            meta,
             fixtures: IndexMap::new(),
             params: IndexMap::new(),
             shared: IndexMap::new(),
+            trail: Trail::default(),
+            artifacts: Arc::new(Mutex::new(Vec::new())),
+            temp_dir: Arc::new(Mutex::new(None)),
+            state: SharedState::new(),
+            rng: TestRng::new(test_seed(run_seed(), &meta.name)),
+            meta,
+        }
+    }
+
+    /// Share `state` with the test, in place of its own
+    pub fn with_state(mut self, state: SharedState) -> Self {
+        self.state = state;
+        self
+    }
+
+    pub fn state(&self) -> &SharedState {
+        &self.state
+    }
+
+    /// Seed the test's random data from `run_seed`, in place of the
+    /// process's own run seed
+    pub fn with_seed(mut self, run_seed: u64) -> Self {
+        self.rng = TestRng::new(test_seed(run_seed, &self.meta.name));
+        self
+    }
+
+    /// Random data for the test, the same every time the run has the same
+    /// seed. See [`crate::random`].
+    pub fn rng(&self) -> TestRng {
+        self.rng.clone()
+    }
+
+    /// The value a hook or test stored under `key`, in this suite or
+    /// failing that for the run
+    pub fn get<T: DeserializeOwned>(&self, key: &str) -> Result<T> {
+        self.state.get(key)
+    }
+
+    /// Store `value` under `key` for the hooks and tests that run after
+    /// this one in the suite
+    pub fn set<T: Serialize>(&self, key: &str, value: T) -> Result<()> {
+        self.state.set(key, value)
+    }
+
+    /// Store `value` under `key` for the rest of the run, like
+    /// [`SharedState::set_for_run`]
+    pub fn set_for_run<T: Serialize>(&self, key: &str, value: T) -> Result<()> {
+        self.state.set_for_run(key, value)
+    }
+
+    /// A directory only this test uses, created on first use. It's removed
+    /// once the test and its `after_each` hooks are done, unless the test
+    /// failed, in which case it's kept for a look and its path is put on
+    /// the result. A directory holding files the test attached is kept
+    /// until the run's artifacts are stored.
+    pub fn temp_dir(&self) -> Result<PathBuf> {
+        let mut temp_dir = locked(&self.temp_dir);
+        if let Some(ref path) = *temp_dir {
+            return Ok(path.clone());
+        }
+
+        let name: String = self
+            .meta
+            .name
+            .chars()
+            .map(|c| if c.is_ascii_alphanumeric() { c } else { '_' })
+            .collect();
+        let path =
+            std::env::temp_dir().join(format!("sheila-{}-{}", name, Uuid::new_v4().simple()));
+        std::fs::create_dir_all(&path)?;
+        Ok(temp_dir.insert(path).clone())
+    }
+
+    /// Remove the test's temp directory if it passed or was skipped, or
+    /// point its result at the directory if it failed or holds files the
+    /// test attached
+    pub(crate) fn clean_up_temp_dir(&self, result: &mut TestResult) {
+        let Some(path) = locked(&self.temp_dir).take() else {
+            return;
+        };
+
+        let attached = result.artifacts.iter().any(|artifact| {
+            artifact.text.is_none()
+                && artifact
+                    .path
+                    .as_ref()
+                    .is_some_and(|file| file.starts_with(&path))
+        });
+        if result.failed() || attached {
+            result.temp_dir = Some(path);
+        } else if let Err(e) = std::fs::remove_dir_all(&path) {
+            tracing::warn!("Could not remove {}: {}", path.display(), e);
+        }
+    }
+
+    /// Attach a file the test produced, such as a screenshot or a log, to
+    /// its result. The file is copied into the run's output directory once
+    /// the run is over, so it may be removed after the test.
+    pub fn attach_file<P: AsRef<Path>>(&self, path: P) -> Result<()> {
+        let path = path.as_ref();
+        if !path.is_file() {
+            return Err(Error::generic(format!(
+                "Cannot attach '{}': not a file",
+                path.display()
+            )));
         }
+
+        let name = path
+            .file_name()
+            .map(|name| name.to_string_lossy().into_owned())
+            .unwrap_or_else(|| path.display().to_string());
+        locked(&self.artifacts).push(Artifact {
+            name,
+            path: Some(path.to_path_buf()),
+            text: None,
+        });
+        Ok(())
+    }
+
+    /// Attach a piece of text, such as a request body or a rendered diff,
+    /// to the test's result under `label`.
+    pub fn attach_text<L: Into<String>, B: Into<String>>(&self, label: L, body: B) {
+        locked(&self.artifacts).push(Artifact {
+            name: label.into(),
+            path: None,
+            text: Some(body.into()),
+        });
+    }
+
+    /// Record a step the test has reached. Breadcrumbs end up on the
+    /// test's result, where reporters can show them as steps.
+    pub fn add_breadcrumb<S: Into<String>>(&self, crumb: S) {
+        locked(&self.trail.breadcrumbs).push(crumb.into());
+    }
+
+    pub fn breadcrumbs(&self) -> Vec<String> {
+        locked(&self.trail.breadcrumbs).clone()
+    }
+
+    /// Log `message` to the test's result, and through `tracing`
+    pub fn log<S: Into<String>>(&self, level: LogLevel, message: S) {
+        self.trail.log(level, message.into());
+    }
+
+    pub fn debug<S: Into<String>>(&self, message: S) {
+        self.log(LogLevel::Debug, message);
+    }
+
+    pub fn info<S: Into<String>>(&self, message: S) {
+        self.log(LogLevel::Info, message);
+    }
+
+    pub fn warn<S: Into<String>>(&self, message: S) {
+        self.log(LogLevel::Warn, message);
+    }
+
+    pub fn error<S: Into<String>>(&self, message: S) {
+        self.log(LogLevel::Error, message);
+    }
+
+    /// What the test has logged so far
+    pub fn logs(&self) -> Vec<LogLine> {
+        locked(&self.trail.logs).clone()
     }
 
     pub fn get_fixture<T: 'static>(&self, name: &str) -> Result<&str> {
@@ -183,6 +595,7 @@ impl TestContext {
 }
 
 #[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize, EnumString)]
+#[cfg_attr(feature = "schemas", derive(schemars::JsonSchema))]
 pub enum TestStatus {
     #[strum(serialize = "pending")]
     Pending,
@@ -200,6 +613,19 @@ pub enum TestStatus {
     Timeout,
     #[strum(serialize = "cancelled")]
     Cancelled,
+    /// Passed in a previous run with an identical binary and environment,
+    /// so it wasn't executed again
+    #[strum(serialize = "cached")]
+    Cached,
+    /// Failed, as it was expected to
+    #[strum(serialize = "xfail")]
+    XFail,
+    /// Passed despite being expected to fail
+    #[strum(serialize = "xpass")]
+    XPass,
+    /// Failed while quarantined, so the failure doesn't count
+    #[strum(serialize = "quarantined")]
+    Quarantined,
 }
 
 impl fmt::Display for TestStatus {
@@ -213,6 +639,10 @@ impl fmt::Display for TestStatus {
             TestStatus::Ignored => write!(f, "ignored"),
             TestStatus::Timeout => write!(f, "timeout"),
             TestStatus::Cancelled => write!(f, "cancelled"),
+            TestStatus::Cached => write!(f, "cached"),
+            TestStatus::XFail => write!(f, "xfail"),
+            TestStatus::XPass => write!(f, "xpass"),
+            TestStatus::Quarantined => write!(f, "quarantined"),
         }
     }
 }
@@ -238,6 +668,7 @@ impl fmt::Display for TestStatus {
 /// This is a modifier that can be applied to the test, and will cause the test to
 /// be skipped if the fixture result is none.
 #[derive(Debug, Clone, PartialEq, Eq, Hash, Serialize, Deserialize)]
+#[cfg_attr(feature = "schemas", derive(schemars::JsonSchema))]
 pub enum TestModifier {
     Skip(String),
     Fail(String),
@@ -251,14 +682,23 @@ pub struct TestAttributes {
     pub ignore: bool,
     /// Only run this test
     pub only: bool,
-    /// Number of retries on failure
-    pub retries: u32,
+    /// Number of retries on failure, or `None` to leave it to the suite
+    pub retries: Option<u32>,
     /// Timeout in seconds
     pub timeout: Option<Duration>,
     /// Tags for identifying the test
     pub tags: Vec<String>,
     /// Category (unit, integration, e2e, etc.)
     pub category: Option<String>,
+    /// Names of tests in the same suite that must pass before this one runs
+    #[serde(default)]
+    pub depends_on: Vec<String>,
+    /// Why the test is expected to fail, if it is
+    #[serde(default)]
+    pub xfail: Option<String>,
+    /// Why the test is quarantined, if it is
+    #[serde(default)]
+    pub quarantine: Option<String>,
     /// Arbitrary custom attributes
     pub custom: HashMap<String, serde_json::Value>,
 }
@@ -268,16 +708,20 @@ impl Default for TestAttributes {
         Self {
             ignore: false,
             only: false,
-            retries: 0,
+            retries: None,
             timeout: None,
             tags: Vec::new(),
             category: None,
+            depends_on: Vec::new(),
+            xfail: None,
+            quarantine: None,
             custom: HashMap::new(),
         }
     }
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
+#[cfg_attr(feature = "schemas", derive(schemars::JsonSchema))]
 pub struct TestMetadata {
     pub name: String,
     pub description: Option<String>,
@@ -337,8 +781,16 @@ impl TestMetadata {
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
+#[cfg_attr(feature = "schemas", derive(schemars::JsonSchema))]
 pub struct TestResult {
+    /// Identifies this execution of the test
     pub id: Uuid,
+    /// Identifies the test across runs, as given by [`test_id`]. Set when
+    /// the result is added to its suite.
+    ///
+    /// [`test_id`]: crate::test_id
+    #[serde(default)]
+    pub stable_id: Uuid,
     pub name: String,
     pub meta: TestMetadata,
     pub status: TestStatus,
@@ -346,26 +798,90 @@ pub struct TestResult {
     pub start_time: DateTime<Utc>,
     pub end_time: Option<DateTime<Utc>>,
     pub duration: Option<Duration>,
+    /// Time spent in test-scoped fixture setup and `before_each` hooks
+    #[serde(default)]
+    pub setup_duration: Option<Duration>,
+    /// Time spent in `after_each` hooks and test-scoped fixture teardown
+    #[serde(default)]
+    pub teardown_duration: Option<Duration>,
 
     pub error: Option<Error>,
+    /// Where the test failed, when a panic location was reported
+    #[serde(default)]
+    pub location: Option<SourceLocation>,
     pub stdout: Option<String>,
     pub stderr: Option<String>,
     pub retry_count: u32,
+
+    /// Every attempt made at running this test, in order
+    #[serde(default)]
+    pub attempts: Vec<TestAttempt>,
+
+    /// Whether the test only passed after one or more retries
+    #[serde(default)]
+    pub flaky: bool,
+
+    /// How many iterations of a repeated run included this test
+    #[serde(default)]
+    pub iterations: Option<u32>,
+
+    /// Tags the test was declared with
+    #[serde(default)]
+    pub tags: Vec<String>,
+
+    /// Breadcrumbs the test left on its context, in order
+    #[serde(default)]
+    pub breadcrumbs: Vec<String>,
+
+    /// Lines the test logged through its context, in order
+    #[serde(default)]
+    pub logs: Vec<LogLine>,
+
+    /// Files and text the test attached to its context
+    #[serde(default)]
+    pub artifacts: Vec<Artifact>,
+
+    /// The test's temp directory, kept because the test failed, or until
+    /// the files it attached from there are stored
+    #[serde(default)]
+    pub temp_dir: Option<PathBuf>,
+
+    /// Time the test spent in delays injected by mocks, which isn't
+    /// counted in its `duration`
+    #[serde(default)]
+    pub injected_delay: Option<Duration>,
+    /// Why the test was skipped, or expected to fail, or quarantined
+    #[serde(default)]
+    pub skip_reason: Option<String>,
 }
 
 impl TestResult {
     pub fn new(id: Uuid, name: String, meta: TestMetadata) -> Self {
         Self {
             id,
+            stable_id: Uuid::nil(),
             name,
             status: TestStatus::Pending,
             start_time: Utc::now(),
             end_time: None,
             duration: None,
+            setup_duration: None,
+            teardown_duration: None,
             error: None,
+            location: None,
             stdout: None,
             stderr: None,
             retry_count: 0,
+            attempts: Vec::new(),
+            flaky: false,
+            iterations: None,
+            tags: Vec::new(),
+            breadcrumbs: Vec::new(),
+            logs: Vec::new(),
+            artifacts: Vec::new(),
+            temp_dir: None,
+            injected_delay: None,
+            skip_reason: None,
             meta,
         }
     }
@@ -378,7 +894,7 @@ impl TestResult {
     pub fn finish(&mut self, status: TestStatus, error: Option<Error>) {
         self.status = status;
         self.end_time = Some(Utc::now());
-        self.error = error;
+        self.error = error.map(|error| error.with_test(&self.name));
 
         if let Some(end_time) = self.end_time {
             self.duration = Some(Duration::from_millis(
@@ -387,15 +903,153 @@ impl TestResult {
         }
     }
 
+    /// Skip the test, noting `reason` and the error that caused it, if one
+    /// did
+    pub fn skip<S: Into<String>>(&mut self, reason: S, error: Option<Error>) {
+        self.skip_reason = Some(reason.into());
+        self.finish(TestStatus::Skipped, error);
+    }
+
+    /// Turn the outcome of an expected failure or a quarantined test into
+    /// [`TestStatus::XFail`], [`TestStatus::XPass`] or
+    /// [`TestStatus::Quarantined`]
+    pub(crate) fn apply_expectations(&mut self, attributes: &TestAttributes) {
+        if let Some(ref reason) = attributes.xfail {
+            if self.failed() {
+                self.skip_reason = Some(reason.clone());
+                self.status = TestStatus::XFail;
+            } else if self.status == TestStatus::Passed {
+                self.skip_reason = Some(reason.clone());
+                self.status = TestStatus::XPass;
+                self.error = Some(
+                    Error::assertion(format!("Expected to fail ({}), but passed", reason))
+                        .with_test(&self.name),
+                );
+            }
+        } else if let Some(ref reason) = attributes.quarantine
+            && self.failed()
+        {
+            self.skip_reason = Some(reason.clone());
+            self.status = TestStatus::Quarantined;
+        }
+    }
+
+    /// Passed, or failed as it was expected to
     pub fn passed(&self) -> bool {
-        self.status == TestStatus::Passed
+        matches!(
+            self.status,
+            TestStatus::Passed | TestStatus::Cached | TestStatus::XFail
+        )
+    }
+
+    /// Name identifying the test to the runner that executed it: the full
+    /// libtest path for tests run through cargo, or the test name otherwise.
+    pub fn qualified_name(&self) -> &str {
+        self.meta.module_path.as_deref().unwrap_or(&self.name)
     }
 
     pub fn failed(&self) -> bool {
-        matches!(self.status, TestStatus::Failed | TestStatus::Timeout)
+        matches!(
+            self.status,
+            TestStatus::Failed | TestStatus::Timeout | TestStatus::XPass
+        )
     }
 
+    /// Skipped, or failed while quarantined
     pub fn skipped(&self) -> bool {
-        matches!(self.status, TestStatus::Skipped | TestStatus::Ignored)
+        matches!(
+            self.status,
+            TestStatus::Skipped | TestStatus::Ignored | TestStatus::Quarantined
+        )
+    }
+
+    /// Remove the temp directory kept for the test, e.g. once a retry
+    /// replaces this attempt
+    pub(crate) fn remove_temp_dir(&mut self) {
+        if let Some(path) = self.temp_dir.take()
+            && let Err(e) = std::fs::remove_dir_all(&path)
+        {
+            tracing::warn!("Could not remove {}: {}", path.display(), e);
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Serialize, Deserialize)]
+#[cfg_attr(feature = "schemas", derive(schemars::JsonSchema))]
+#[serde(rename_all = "lowercase")]
+pub enum LogLevel {
+    Debug,
+    Info,
+    Warn,
+    Error,
+}
+
+impl fmt::Display for LogLevel {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            LogLevel::Debug => write!(f, "debug"),
+            LogLevel::Info => write!(f, "info"),
+            LogLevel::Warn => write!(f, "warn"),
+            LogLevel::Error => write!(f, "error"),
+        }
+    }
+}
+
+/// A line a test logged, as kept on its result
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[cfg_attr(feature = "schemas", derive(schemars::JsonSchema))]
+pub struct LogLine {
+    pub level: LogLevel,
+    pub message: String,
+    pub time: DateTime<Utc>,
+}
+
+/// A file or piece of text attached to a test's result.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[cfg_attr(feature = "schemas", derive(schemars::JsonSchema))]
+pub struct Artifact {
+    /// File name, or the label of a text artifact
+    pub name: String,
+    /// Where the artifact is stored. For an attached file this is its
+    /// original path until the run's artifacts are stored; text artifacts
+    /// only get a path once stored.
+    pub path: Option<PathBuf>,
+    /// Body of a text artifact
+    pub text: Option<String>,
+}
+
+impl Artifact {
+    /// Whether the artifact is an image a report can show inline
+    pub fn is_image(&self) -> bool {
+        let extension = self
+            .path
+            .as_ref()
+            .and_then(|path| path.extension())
+            .map(|extension| extension.to_string_lossy().to_lowercase());
+        matches!(
+            extension.as_deref(),
+            Some("png" | "jpg" | "jpeg" | "gif" | "svg" | "webp")
+        )
+    }
+}
+
+/// A single attempt at running a test, recorded by the retry engine.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[cfg_attr(feature = "schemas", derive(schemars::JsonSchema))]
+pub struct TestAttempt {
+    pub status: TestStatus,
+    pub start_time: DateTime<Utc>,
+    pub duration: Option<Duration>,
+    pub error: Option<Error>,
+}
+
+impl From<&TestResult> for TestAttempt {
+    fn from(result: &TestResult) -> Self {
+        Self {
+            status: result.status,
+            start_time: result.start_time,
+            duration: result.duration,
+            error: result.error.clone(),
+        }
     }
 }
@@ -0,0 +1,325 @@
+//! A clock tests can control, so code that depends on the time can be
+//! tested without waiting on it.
+//!
+//! Code under test reads the time from a [`Clock`] it's given rather than
+//! from the system. In production that's [`Clock::system`]; in tests it's
+//! usually a frozen clock the test [`advance`](Clock::advance)s by hand:
+//!
+//! ```ignore
+//! let clock = Clock::frozen();
+//! let cache = Cache::new(clock.clone(), Duration::from_secs(60));
+//! cache.insert("key", 1);
+//!
+//! clock.advance(Duration::from_secs(61));
+//! assert!(cache.get("key").is_none());
+//! ```
+//!
+//! [`Clock::eventually`] and [`Clock::retry`] wait by sleeping on the clock,
+//! which for a mocked clock moves it forward instead of blocking.
+
+use crate::fixtures::Fixture;
+use crate::test::TestContext;
+use crate::{Error, Result};
+use chrono::{DateTime, TimeDelta, Utc};
+use indexmap::IndexMap;
+use parking_lot::Mutex;
+use std::sync::{Arc, OnceLock};
+use std::time::{Duration, Instant};
+use uuid::Uuid;
+
+/// Key the id of the clock set up by the `Clock` fixture is stored under in
+/// the suite's [`SharedState`](crate::SharedState)
+const ID_KEY: &str = "sheila.clock.id";
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Mode {
+    /// Ticks with the system clock
+    Running,
+    /// Only moves when advanced
+    Frozen,
+    /// Moves by the step every time it's read
+    AutoAdvance(Duration),
+}
+
+#[derive(Debug)]
+struct State {
+    mode: Mode,
+    /// The time when the clock was last frozen, set or advanced
+    base: DateTime<Utc>,
+    /// When `base` was last changed, for running clocks
+    since: Instant,
+}
+
+impl State {
+    fn now(&self) -> DateTime<Utc> {
+        match self.mode {
+            Mode::Running => self.base + delta(self.since.elapsed()),
+            Mode::Frozen | Mode::AutoAdvance(_) => self.base,
+        }
+    }
+
+    fn set(&mut self, time: DateTime<Utc>) {
+        self.base = time;
+        self.since = Instant::now();
+    }
+}
+
+/// A handle to a clock, shared with its clones. Advancing, freezing or
+/// setting it through one handle is seen through all of them.
+#[derive(Debug, Clone)]
+pub struct Clock {
+    id: Uuid,
+    state: Arc<Mutex<State>>,
+}
+
+impl Clock {
+    fn new(mode: Mode, base: DateTime<Utc>) -> Self {
+        Self {
+            id: Uuid::new_v4(),
+            state: Arc::new(Mutex::new(State {
+                mode,
+                base,
+                since: Instant::now(),
+            })),
+        }
+    }
+
+    /// A clock telling the system time
+    pub fn system() -> Self {
+        Self::new(Mode::Running, Utc::now())
+    }
+
+    /// A clock stopped at the current time
+    pub fn frozen() -> Self {
+        Self::frozen_at(Utc::now())
+    }
+
+    /// A clock stopped at `time`
+    pub fn frozen_at(time: DateTime<Utc>) -> Self {
+        Self::new(Mode::Frozen, time)
+    }
+
+    /// The clock set up by the `Clock` fixture for this test or its suite
+    pub fn from_context(context: &TestContext) -> Result<Self> {
+        let id: Uuid = context
+            .get(ID_KEY)
+            .map_err(|_| Error::fixture("No Clock fixture is set up for this test"))?;
+        clocks()
+            .lock()
+            .get(&id)
+            .cloned()
+            .ok_or_else(|| Error::fixture(format!("Clock {} has been torn down", id)))
+    }
+
+    /// The current time. An auto-advancing clock moves forward by its step
+    /// after being read.
+    pub fn now(&self) -> DateTime<Utc> {
+        let mut state = self.state.lock();
+        let now = state.now();
+        if let Mode::AutoAdvance(step) = state.mode {
+            state.base = now + delta(step);
+        }
+        now
+    }
+
+    /// How long it's been since `earlier` by this clock, or zero if
+    /// `earlier` is still to come
+    pub fn elapsed_since(&self, earlier: DateTime<Utc>) -> Duration {
+        let now = self.state.lock().now();
+        (now - earlier).to_std().unwrap_or_default()
+    }
+
+    /// Stop the clock where it is
+    pub fn freeze(&self) {
+        let mut state = self.state.lock();
+        let now = state.now();
+        state.set(now);
+        state.mode = Mode::Frozen;
+    }
+
+    /// Start the clock ticking with the system clock again, from where it
+    /// is
+    pub fn resume(&self) {
+        let mut state = self.state.lock();
+        let now = state.now();
+        state.set(now);
+        state.mode = Mode::Running;
+    }
+
+    /// Move the clock forward by `step` every time it's read, so each read
+    /// sees a later time than the last
+    pub fn auto_advance(&self, step: Duration) {
+        let mut state = self.state.lock();
+        let now = state.now();
+        state.set(now);
+        state.mode = Mode::AutoAdvance(step);
+    }
+
+    /// Whether the clock is frozen or auto-advancing rather than ticking
+    /// with the system clock
+    pub fn is_mocked(&self) -> bool {
+        self.state.lock().mode != Mode::Running
+    }
+
+    /// Move the clock forward by `by`
+    pub fn advance(&self, by: Duration) {
+        let mut state = self.state.lock();
+        let now = state.now();
+        state.set(now + delta(by));
+    }
+
+    /// Put the clock at `time`, which may be in its past
+    pub fn set(&self, time: DateTime<Utc>) {
+        self.state.lock().set(time);
+    }
+
+    /// Wait for `duration` by this clock: a running clock blocks the thread,
+    /// a frozen or auto-advancing one is advanced by `duration` at once
+    pub fn sleep(&self, duration: Duration) {
+        if self.is_mocked() {
+            self.advance(duration);
+        } else {
+            std::thread::sleep(duration);
+        }
+    }
+
+    /// Check `condition` every `interval` until it passes, failing with its
+    /// last error once `timeout` has passed on this clock. On a frozen
+    /// clock the wait is instant, as each interval advances the clock.
+    pub fn eventually<F>(
+        &self,
+        timeout: Duration,
+        interval: Duration,
+        mut condition: F,
+    ) -> Result<()>
+    where
+        F: FnMut() -> Result<()>,
+    {
+        let started = self.state.lock().now();
+        loop {
+            let error = match condition() {
+                Ok(()) => return Ok(()),
+                Err(e) => e,
+            };
+            if self.elapsed_since(started) >= timeout {
+                return Err(Error::timeout(format!(
+                    "Condition still not met after {:?}: {}",
+                    timeout,
+                    error.message()
+                ))
+                .with_source(&error));
+            }
+            self.sleep(interval);
+        }
+    }
+
+    /// Run `operation` up to `attempts` times until it succeeds, sleeping
+    /// on this clock for `backoff` after the first failure and twice as long
+    /// after each one after that
+    pub fn retry<T, F>(&self, attempts: u32, backoff: Duration, mut operation: F) -> Result<T>
+    where
+        F: FnMut() -> Result<T>,
+    {
+        let mut delay = backoff;
+        for attempt in 1.. {
+            match operation() {
+                Ok(value) => return Ok(value),
+                Err(e) if attempt >= attempts => return Err(e),
+                Err(_) => {
+                    self.sleep(delay);
+                    delay = delay.saturating_mul(2);
+                }
+            }
+        }
+        unreachable!()
+    }
+}
+
+impl Default for Clock {
+    fn default() -> Self {
+        Self::system()
+    }
+}
+
+/// Sets up a clock frozen at the time the fixture is set up, for tests to
+/// fetch with [`Clock::from_context`] and hand to the code they test
+impl Fixture for Clock {
+    type Output = Clock;
+
+    fn setup(context: TestContext) -> Result<Self::Output> {
+        let clock = Clock::frozen();
+        clocks().lock().insert(clock.id, clock.clone());
+        context.set(ID_KEY, clock.id)?;
+        Ok(clock)
+    }
+
+    fn teardown(clock: Self::Output, context: TestContext) -> Result<()> {
+        clocks().lock().shift_remove(&clock.id);
+        let _ = context.state().remove(ID_KEY);
+        Ok(())
+    }
+}
+
+/// Clocks set up as fixtures, by id
+fn clocks() -> &'static Mutex<IndexMap<Uuid, Clock>> {
+    static CLOCKS: OnceLock<Mutex<IndexMap<Uuid, Clock>>> = OnceLock::new();
+    CLOCKS.get_or_init(Default::default)
+}
+
+fn delta(duration: Duration) -> TimeDelta {
+    TimeDelta::from_std(duration).unwrap_or(TimeDelta::MAX)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{Test, TestSuite};
+
+    #[test]
+    fn test_clock_fixture_and_waiting_on_mocked_time() {
+        let mut suite = TestSuite::new("clock")
+            .with_fixture(Clock::definition("clock"))
+            .add_test(Test::new("expires", |ctx| {
+                let clock = Clock::from_context(&ctx)?;
+                let start = clock.now();
+                let expires_at = start + TimeDelta::seconds(30);
+
+                clock.eventually(Duration::from_secs(60), Duration::from_secs(1), || {
+                    if clock.now() >= expires_at {
+                        Ok(())
+                    } else {
+                        Err(Error::assertion("not expired yet"))
+                    }
+                })?;
+                assert_eq!(clock.elapsed_since(start), Duration::from_secs(30));
+
+                let error = clock
+                    .eventually(Duration::from_secs(5), Duration::from_secs(2), || {
+                        Err(Error::assertion("never"))
+                    })
+                    .unwrap_err();
+                assert_eq!(error.message(), "Condition still not met after 5s: never");
+
+                let mut calls = 0;
+                let value = clock.retry(4, Duration::from_secs(1), || {
+                    calls += 1;
+                    if calls < 3 {
+                        Err(Error::generic("flaky"))
+                    } else {
+                        Ok(calls)
+                    }
+                })?;
+                assert_eq!(value, 3);
+                assert_eq!(clock.elapsed_since(start), Duration::from_secs(39));
+
+                clock.auto_advance(Duration::from_millis(10));
+                let first = clock.now();
+                assert_eq!(clock.now() - first, TimeDelta::milliseconds(10));
+                Ok(())
+            }));
+
+        let result = suite.execute().unwrap();
+        assert!(result.all_passed(), "{:?}", result.test_results);
+        assert!(clocks().lock().is_empty());
+    }
+}
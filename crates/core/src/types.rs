@@ -1,47 +1,218 @@
+use std::sync::Arc;
+use std::sync::mpsc::{SendError, Sender};
 use std::time::Instant;
 
 use mio::Token;
+use parking_lot::Mutex;
+use serde::{Deserialize, Serialize};
 use strum_macros::{EnumDiscriminants, EnumString};
+use uuid::Uuid;
 
-use crate::{TestStatus, runners::format_err_context, test::TestResult};
+use crate::{TestStatus, runners::format_err_context, suite_id, test::TestResult, test_id};
 
 pub const STDOUT_TOKEN: Token = Token(0);
 pub const STDERR_TOKEN: Token = Token(1);
 
-#[derive(Debug, Clone, EnumDiscriminants)]
+/// Token for the stdout pipe of the child in `slot` when several executables
+/// share one poll loop. Slot 0 uses [`STDOUT_TOKEN`].
+pub fn stdout_token(slot: usize) -> Token {
+    Token(slot * 2 + STDOUT_TOKEN.0)
+}
+
+/// Token for the stderr pipe of the child in `slot`. Slot 0 uses [`STDERR_TOKEN`].
+pub fn stderr_token(slot: usize) -> Token {
+    Token(slot * 2 + STDERR_TOKEN.0)
+}
+
+/// Slot a pipe token belongs to, and whether it is the stdout pipe.
+pub fn token_slot(token: Token) -> (usize, bool) {
+    (token.0 / 2, token.0 % 2 == STDOUT_TOKEN.0)
+}
+
+/// Progress reported while test executables run, serialized tagged by
+/// `event`, e.g. `{"event": "suite_completed", "name": "...", "sequence": 3}`.
+/// Every event carries an [`EventMeta`], filled in by the [`EventSender`] it
+/// goes through.
+#[derive(Debug, Clone, Serialize, Deserialize, EnumDiscriminants)]
+#[cfg_attr(feature = "schemas", derive(schemars::JsonSchema))]
+#[serde(tag = "event", rename_all = "snake_case")]
 #[strum_discriminants(name(ProcessOutputType), derive(EnumString))]
 pub enum ProcessOutput {
     #[strum(serialize = "test_started")]
     TestStarted {
         name: String,
         suite: String,
+        #[serde(flatten)]
+        meta: EventMeta,
     },
     #[strum(serialize = "ok")]
     TestPassed {
         result: TestResult,
+        suite: String,
         duration_ms: f64,
+        #[serde(flatten)]
+        meta: EventMeta,
     },
     #[strum(serialize = "FAILED")]
     TestFailed {
         result: TestResult,
+        suite: String,
         duration_ms: f64,
         error: String,
         location: Option<SourceLocation>,
+        #[serde(flatten)]
+        meta: EventMeta,
     },
     #[strum(serialize = "test_skipped")]
     TestSkipped {
         result: TestResult,
+        suite: String,
+        #[serde(flatten)]
+        meta: EventMeta,
     },
     #[strum(serialize = "running")]
     SuiteStarted {
         name: String,
         test_count: usize,
+        #[serde(flatten)]
+        meta: EventMeta,
     },
     #[strum(serialize = "suite_completed")]
     SuiteCompleted {
         name: String,
+        #[serde(flatten)]
+        meta: EventMeta,
+    },
+    /// The executable of suite `name` has exited
+    Done {
+        name: String,
+        #[serde(flatten)]
+        meta: EventMeta,
     },
-    Done,
+}
+
+impl ProcessOutput {
+    pub fn meta(&self) -> &EventMeta {
+        match self {
+            ProcessOutput::TestStarted { meta, .. }
+            | ProcessOutput::TestPassed { meta, .. }
+            | ProcessOutput::TestFailed { meta, .. }
+            | ProcessOutput::TestSkipped { meta, .. }
+            | ProcessOutput::SuiteStarted { meta, .. }
+            | ProcessOutput::SuiteCompleted { meta, .. }
+            | ProcessOutput::Done { meta, .. } => meta,
+        }
+    }
+
+    fn meta_mut(&mut self) -> &mut EventMeta {
+        match self {
+            ProcessOutput::TestStarted { meta, .. }
+            | ProcessOutput::TestPassed { meta, .. }
+            | ProcessOutput::TestFailed { meta, .. }
+            | ProcessOutput::TestSkipped { meta, .. }
+            | ProcessOutput::SuiteStarted { meta, .. }
+            | ProcessOutput::SuiteCompleted { meta, .. }
+            | ProcessOutput::Done { meta, .. } => meta,
+        }
+    }
+
+    /// Name of the suite the event is about
+    pub fn suite(&self) -> &str {
+        match self {
+            ProcessOutput::TestStarted { suite, .. }
+            | ProcessOutput::TestPassed { suite, .. }
+            | ProcessOutput::TestFailed { suite, .. }
+            | ProcessOutput::TestSkipped { suite, .. } => suite,
+            ProcessOutput::SuiteStarted { name, .. }
+            | ProcessOutput::SuiteCompleted { name, .. }
+            | ProcessOutput::Done { name, .. } => name,
+        }
+    }
+
+    /// Qualified name of the test the event is about, if it's about one
+    pub fn test(&self) -> Option<&str> {
+        match self {
+            ProcessOutput::TestStarted { name, .. } => Some(name),
+            ProcessOutput::TestPassed { result, .. }
+            | ProcessOutput::TestFailed { result, .. }
+            | ProcessOutput::TestSkipped { result, .. } => Some(result.qualified_name()),
+            _ => None,
+        }
+    }
+}
+
+/// Which run an event belongs to, where it falls in it and what it's about,
+/// so events from executables running at the same time can be correlated
+/// and put in order
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[cfg_attr(feature = "schemas", derive(schemars::JsonSchema))]
+#[serde(default)]
+pub struct EventMeta {
+    pub run_id: Uuid,
+    /// Counts the events of the run from 1, in the order they were sent
+    pub sequence: u64,
+    /// The [`suite_id`] of the suite the event is about
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub suite_id: Option<Uuid>,
+    /// The [`test_id`] of the test the event is about, if any
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub test_id: Option<Uuid>,
+}
+
+/// Passes runner events on to a channel, filling in their [`EventMeta`].
+/// Clones share the run id and the numbering, so runners working side by
+/// side, e.g. one per remote host, send their events in a single sequence.
+#[derive(Debug, Clone)]
+pub struct EventSender {
+    tx: Sender<ProcessOutput>,
+    run_id: Uuid,
+    /// Last sequence number given out, locked while sending so events
+    /// arrive in sequence
+    sequence: Arc<Mutex<u64>>,
+}
+
+impl EventSender {
+    /// Send events for a new run to `tx`
+    pub fn new(tx: Sender<ProcessOutput>) -> Self {
+        Self {
+            tx,
+            run_id: Uuid::new_v4(),
+            sequence: Arc::default(),
+        }
+    }
+
+    /// Give events the id of a run tracked elsewhere, e.g. by the server
+    pub fn with_run_id(mut self, run_id: Uuid) -> Self {
+        self.run_id = run_id;
+        self
+    }
+
+    pub fn run_id(&self) -> Uuid {
+        self.run_id
+    }
+
+    /// Send `output` with the next sequence number. Fails once the receiving
+    /// end has hung up, dropping `output`.
+    pub fn send(&self, mut output: ProcessOutput) -> Result<(), SendError<()>> {
+        let suite_id = suite_id(output.suite());
+        let test_id = output.test().map(|test| test_id(output.suite(), test));
+
+        let mut sequence = self.sequence.lock();
+        *sequence += 1;
+        *output.meta_mut() = EventMeta {
+            run_id: self.run_id,
+            sequence: *sequence,
+            suite_id: Some(suite_id),
+            test_id,
+        };
+        self.tx.send(output).map_err(|_| SendError(()))
+    }
+}
+
+impl From<Sender<ProcessOutput>> for EventSender {
+    fn from(tx: Sender<ProcessOutput>) -> Self {
+        Self::new(tx)
+    }
 }
 
 #[derive(Debug, Clone)]
@@ -53,6 +224,8 @@ pub enum TestOutputLine {
         name: String,
         status: TestStatus,
         duration_ms: Option<f64>,
+        /// Output libtest captured for the test, when not run with `--nocapture`
+        stdout: Option<String>,
     },
     SuiteStart {
         count: usize,
@@ -76,7 +249,8 @@ pub enum TestState {
     },
 }
 
-#[derive(Debug, Clone)]
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[cfg_attr(feature = "schemas", derive(schemars::JsonSchema))]
 pub struct SourceLocation {
     pub file: String,
     pub line: u32,
@@ -1,11 +1,12 @@
 use proc_macro::TokenStream;
 use proc_macro2::TokenStream as TokenStream2;
 use quote::{ToTokens, quote};
-use syn::{Attribute, DeriveInput, Item, ItemFn, ItemMod, parse_macro_input};
+use syn::punctuated::Punctuated;
+use syn::{Attribute, DeriveInput, Expr, Item, ItemFn, ItemMod, Token, parse_macro_input};
 
 #[proc_macro_attribute]
 pub fn test(_args: TokenStream, input: TokenStream) -> TokenStream {
-    let input_fn = parse_macro_input!(input as ItemFn);
+    let mut input_fn = parse_macro_input!(input as ItemFn);
     let fn_name = &input_fn.sig.ident;
     let fn_name_str = fn_name.to_string();
     let test_name = fn_name_str.replace('_', " ");
@@ -15,9 +16,39 @@ pub fn test(_args: TokenStream, input: TokenStream) -> TokenStream {
 
     let mut ignore = false;
     let mut only = false;
-    let mut retries = 0u32;
+    let mut retries = None::<u32>;
     let mut timeout_seconds = 0u64;
     let mut tags = Vec::<String>::new();
+    let mut xfail = None::<String>;
+    let mut quarantine = None::<String>;
+    let mut cases = Vec::<Expr>::new();
+
+    // `#[sheila::xfail]`, `#[sheila::quarantine]` and `#[sheila::params]`
+    // are read here and dropped, as they only mean something to the test
+    // they're on
+    let mut errors = Vec::new();
+    input_fn.attrs.retain(|attr| {
+        let outcome = match last_segment(attr.path()).as_deref() {
+            Some("params") => {
+                match attr.parse_args_with(Punctuated::<Expr, Token![,]>::parse_terminated) {
+                    Ok(params) => cases.extend(params),
+                    Err(e) => errors.push(e.to_compile_error()),
+                }
+                return false;
+            }
+            Some("xfail") => &mut xfail,
+            Some("quarantine") => &mut quarantine,
+            _ => return true,
+        };
+        match parse_reason(attr) {
+            Ok(reason) => *outcome = Some(reason),
+            Err(e) => errors.push(e.to_compile_error()),
+        }
+        false
+    });
+    if !errors.is_empty() {
+        return quote! { #(#errors)* }.into();
+    }
 
     for attr in &input_fn.attrs {
         if attr.path().is_ident("ignore") {
@@ -30,7 +61,7 @@ pub fn test(_args: TokenStream, input: TokenStream) -> TokenStream {
                 .strip_prefix("retries (")
                 .and_then(|s| s.strip_suffix(')'))
             {
-                retries = num_str.trim().parse().unwrap_or(0);
+                retries = num_str.trim().parse().ok();
             }
         } else if attr.path().is_ident("timeout") {
             let meta_str = attr.meta.to_token_stream().to_string();
@@ -55,31 +86,90 @@ pub fn test(_args: TokenStream, input: TokenStream) -> TokenStream {
         }
     }
 
-    let cargo_test_ignore = if ignore {
+    // Only the crate's own tests are marked `#[ignore]` for cargo
+    let cargo_test_ignore = if ignore && cfg!(feature = "__sheila_test") {
         quote! { #[ignore] }
     } else {
         quote! {}
     };
 
-    let output_fn = if cfg!(feature = "__sheila_test") {
+    // Under cargo an expected failure is a test that should panic, and a
+    // quarantined test's failure is only printed
+    let cargo_test_should_panic = if xfail.is_some() {
+        quote! { #[should_panic] }
+    } else {
+        quote! {}
+    };
+    let cargo_test_body = |call: TokenStream2| match quarantine {
+        Some(ref reason) => quote! {
+            if ::std::panic::catch_unwind(|| #call).is_err() {
+                eprintln!("{} failed while quarantined: {}", #fn_name_str, #reason);
+            }
+        },
+        None => quote! { #call; },
+    };
+
+    // A parameterized test is a module of its cases under cargo, one test
+    // per parameter named `case_<n>` counting from 1, so each passes or
+    // fails and can be picked on its own
+    let output_fn = if !cfg!(feature = "__sheila_test") && !cfg!(feature = "cargo-test") {
+        quote! {}
+    } else if cases.is_empty() {
+        let body = cargo_test_body(quote! { #fn_name() });
         quote! {
             #[test]
             #cargo_test_ignore
+            #cargo_test_should_panic
             #[allow(non_snake_case)]
             fn #cargo_test_name() {
-                #fn_name();
+                #body
             }
         }
-    } else if cfg!(feature = "cargo-test") {
+    } else {
+        let case_fns = cases.iter().enumerate().map(|(index, case)| {
+            let case_name = syn::Ident::new(&format!("case_{}", index + 1), fn_name.span());
+            let body = cargo_test_body(quote! { super::#fn_name(#case) });
+            quote! {
+                #[test]
+                #cargo_test_ignore
+                #cargo_test_should_panic
+                fn #case_name() {
+                    #body
+                }
+            }
+        });
         quote! {
-            #[test]
             #[allow(non_snake_case)]
-            fn #cargo_test_name() {
-                #fn_name();
+            mod #cargo_test_name {
+                #[allow(unused_imports)]
+                use super::*;
+
+                #(#case_fns)*
             }
         }
+    };
+
+    // Natively the cases run one after another as a single test
+    let calls = if cases.is_empty() {
+        vec![quote! { #fn_name() }]
     } else {
-        quote! {}
+        cases
+            .iter()
+            .map(|case| quote! { #fn_name(#case) })
+            .collect()
+    };
+
+    let xfail = match xfail {
+        Some(reason) => quote! { Some(#reason.to_string()) },
+        None => quote! { None },
+    };
+    let quarantine = match quarantine {
+        Some(reason) => quote! { Some(#reason.to_string()) },
+        None => quote! { None },
+    };
+    let retries = match retries {
+        Some(count) => quote! { Some(#count) },
+        None => quote! { None },
     };
 
     let expanded = quote! {
@@ -89,7 +179,7 @@ pub fn test(_args: TokenStream, input: TokenStream) -> TokenStream {
         #[allow(non_snake_case)]
         pub fn #wrapper_name() -> ::sheila::prelude::Test {
             let test_fn: ::sheila::prelude::TestFn = Box::new(|_ctx: ::sheila::prelude::TestContext| -> ::sheila::prelude::Result<()> {
-                #fn_name();
+                #(#calls;)*
                 Ok(())
             });
 
@@ -98,6 +188,8 @@ pub fn test(_args: TokenStream, input: TokenStream) -> TokenStream {
             test.attributes.ignore = #ignore;
             test.attributes.only = #only;
             test.attributes.retries = #retries;
+            test.attributes.xfail = #xfail;
+            test.attributes.quarantine = #quarantine;
 
             if #timeout_seconds > 0 {
                 test.attributes.timeout = Some(std::time::Duration::from_secs(#timeout_seconds));
@@ -186,6 +278,58 @@ pub fn suite(_args: TokenStream, input: TokenStream) -> TokenStream {
     }
 }
 
+/// Define a benchmark, run with `sheila bench`
+///
+/// # Basic Usage
+/// ```ignore
+/// #[sheila::bench]
+/// fn sort_small_vec() {
+///     let mut values = vec![3, 1, 2];
+///     values.sort();
+/// }
+///
+/// #[sheila::bench]
+/// fn parse_config(b: &mut sheila::bench::Bencher) {
+///     let input = std::fs::read_to_string("sheila.toml").unwrap();
+///     b.iter(|| parse(&input));
+/// }
+/// ```
+#[proc_macro_attribute]
+pub fn bench(_args: TokenStream, input: TokenStream) -> TokenStream {
+    let input_fn = parse_macro_input!(input as ItemFn);
+    let fn_name = &input_fn.sig.ident;
+    let fn_name_str = fn_name.to_string();
+
+    let bench_test_name = syn::Ident::new(&format!("{}_sheila_bench", fn_name), fn_name.span());
+
+    let bench = if input_fn.sig.inputs.is_empty() {
+        quote! { |b: &mut ::sheila::bench::Bencher| b.iter(#fn_name) }
+    } else {
+        quote! { #fn_name }
+    };
+
+    let output_fn = if cfg!(feature = "__sheila_bench") {
+        quote! {
+            #[test]
+            #[allow(non_snake_case)]
+            fn #bench_test_name() {
+                ::sheila::bench::run_bench(concat!(module_path!(), "::", #fn_name_str), #bench);
+            }
+        }
+    } else {
+        quote! {}
+    };
+
+    let expanded = quote! {
+        #[allow(dead_code)]
+        #input_fn
+
+        #output_fn
+    };
+
+    expanded.into()
+}
+
 /// Define a fixture with Sheila
 ///
 /// # Basic Usage
@@ -302,7 +446,8 @@ pub fn tags(args: TokenStream, input: TokenStream) -> TokenStream {
     expanded.into()
 }
 
-/// Add parameters to a test for parameterized testing
+/// Run a test once per parameter, passing it as the test's argument. Under
+/// cargo each parameter is a case of its own, `case_1` for the first.
 ///
 /// # Usage
 /// ```ignore
@@ -314,11 +459,63 @@ pub fn tags(args: TokenStream, input: TokenStream) -> TokenStream {
 /// ```
 #[proc_macro_attribute]
 pub fn params(args: TokenStream, input: TokenStream) -> TokenStream {
-    let input_fn = parse_macro_input!(input as ItemFn);
-    let param_list = parse_attribute_args(args);
+    outcome_attribute("params", args, input)
+}
+
+/// Expect a test to fail. It's reported as `xfail` when it does, and fails
+/// the run as `xpass` when it passes.
+///
+/// # Usage
+/// ```ignore
+/// #[sheila::test]
+/// #[sheila::xfail(reason = "rounding bug, see #42")]
+/// fn rounds_half_to_even() {
+///     // test code
+/// }
+/// ```
+#[proc_macro_attribute]
+pub fn xfail(args: TokenStream, input: TokenStream) -> TokenStream {
+    outcome_attribute("xfail", args, input)
+}
 
+/// Run a test without letting its failure fail the run. A failure is
+/// reported as `quarantined`.
+///
+/// # Usage
+/// ```ignore
+/// #[sheila::test]
+/// #[sheila::quarantine(reason = "times out on CI")]
+/// fn uploads_large_file() {
+///     // test code
+/// }
+/// ```
+#[proc_macro_attribute]
+pub fn quarantine(args: TokenStream, input: TokenStream) -> TokenStream {
+    outcome_attribute("quarantine", args, input)
+}
+
+/// `#[sheila::test]` reads `xfail`, `quarantine` and `params` off the test
+/// itself when it's the outer attribute. When one of them is outside it instead, put it
+/// back inside so `test` sees it.
+fn outcome_attribute(name: &str, args: TokenStream, input: TokenStream) -> TokenStream {
+    let mut input_fn = parse_macro_input!(input as ItemFn);
+    let Some(position) = input_fn
+        .attrs
+        .iter()
+        .position(|attr| last_segment(attr.path()).as_deref() == Some("test"))
+    else {
+        let message = format!("#[sheila::{}] can only be used on a #[sheila::test]", name);
+        return syn::Error::new_spanned(&input_fn.sig.ident, message)
+            .to_compile_error()
+            .into();
+    };
+
+    let test_attr = input_fn.attrs.remove(position);
+    let name = syn::Ident::new(name, proc_macro2::Span::call_site());
+    let args = TokenStream2::from(args);
     let expanded = quote! {
-        #[params(#(#param_list),*)]
+        #test_attr
+        #[::sheila::#name(#args)]
         #input_fn
     };
 
@@ -447,6 +644,155 @@ pub fn derive_test_suite(input: TokenStream) -> TokenStream {
     expanded.into()
 }
 
+/// Generate a mock implementation of a trait, named after the trait with a
+/// `Mock` prefix. Calls are recorded in the mock's `MockCollection` under
+/// the method's name, and return what the method is configured to return.
+///
+/// Arguments must be `Serialize`, as calls are recorded as JSON. Methods
+/// returning `Result<T, E>` return `Err` for errors set with
+/// `MockBuilder::returns_error`.
+///
+/// # Basic Usage
+/// ```ignore
+/// #[sheila::automock]
+/// trait Store {
+///     fn get(&self, key: &str) -> Option<String>;
+/// }
+///
+/// let mut store = MockStore::new();
+/// store.expect("get", MockBuilder::new().returns(Some("value".to_string())).build());
+/// assert_eq!(store.get("key"), Some("value".to_string()));
+/// store.verify()?;
+/// ```
+#[proc_macro_attribute]
+pub fn automock(_args: TokenStream, input: TokenStream) -> TokenStream {
+    let input_trait = parse_macro_input!(input as syn::ItemTrait);
+    let trait_name = &input_trait.ident;
+    let vis = &input_trait.vis;
+    let mock_name = syn::Ident::new(&format!("Mock{}", trait_name), trait_name.span());
+
+    if !input_trait.generics.params.is_empty() {
+        return syn::Error::new_spanned(
+            &input_trait.generics,
+            "#[automock] doesn't support generic traits",
+        )
+        .to_compile_error()
+        .into();
+    }
+
+    let mut methods = Vec::new();
+    for item in &input_trait.items {
+        let syn::TraitItem::Fn(method) = item else {
+            continue;
+        };
+        if method.sig.receiver().is_none() {
+            return syn::Error::new_spanned(
+                &method.sig,
+                "#[automock] can only mock methods taking `self`",
+            )
+            .to_compile_error()
+            .into();
+        }
+
+        let mut sig = method.sig.clone();
+        let method_name = sig.ident.to_string();
+        let mut args = Vec::new();
+        for (index, input) in sig.inputs.iter_mut().enumerate() {
+            if let syn::FnArg::Typed(pat_type) = input {
+                let arg = syn::Ident::new(&format!("arg{}", index), proc_macro2::Span::call_site());
+                *pat_type.pat = syn::parse_quote! { #arg };
+                args.push(arg);
+            }
+        }
+        let arguments = quote! { vec![#(::sheila::internal::mock_arg(&#args)),*] };
+        let body = match &sig.output {
+            syn::ReturnType::Default => quote! {
+                self.mocks.call::<()>(#method_name, #arguments)
+            },
+            syn::ReturnType::Type(_, ty) => match result_types(ty) {
+                Some((ok, err)) => quote! {
+                    self.mocks.call_result::<#ok, #err>(#method_name, #arguments)
+                },
+                None => quote! {
+                    self.mocks.call::<#ty>(#method_name, #arguments)
+                },
+            },
+        };
+
+        methods.push(quote! {
+            #sig {
+                #body
+            }
+        });
+    }
+
+    let expanded = quote! {
+        #input_trait
+
+        #[derive(Default)]
+        #vis struct #mock_name {
+            mocks: ::sheila::MockCollection,
+        }
+
+        impl #mock_name {
+            #vis fn new() -> Self {
+                Self::default()
+            }
+
+            /// Configure how `method` behaves when called
+            #vis fn expect(&mut self, method: &str, config: ::sheila::MockConfig) -> &mut Self {
+                self.mocks.register_mock(method, config);
+                self
+            }
+
+            #vis fn mocks(&self) -> &::sheila::MockCollection {
+                &self.mocks
+            }
+
+            #vis fn call_count(&self, method: &str) -> usize {
+                self.mocks.get_call_count(method)
+            }
+
+            #vis fn calls(&self, method: &str) -> Vec<::sheila::internal::MockCall> {
+                self.mocks.get_calls(method)
+            }
+
+            /// Check every method was called as many times as expected
+            #vis fn verify(&self) -> ::sheila::Result<()> {
+                self.mocks.verify()
+            }
+        }
+
+        impl #trait_name for #mock_name {
+            #(#methods)*
+        }
+    };
+
+    expanded.into()
+}
+
+/// The `T` and `E` of a `Result<T, E>` type
+fn result_types(ty: &syn::Type) -> Option<(&syn::Type, &syn::Type)> {
+    let syn::Type::Path(path) = ty else {
+        return None;
+    };
+    let segment = path.path.segments.last()?;
+    if segment.ident != "Result" {
+        return None;
+    }
+    let syn::PathArguments::AngleBracketed(args) = &segment.arguments else {
+        return None;
+    };
+    let mut types = args.args.iter().filter_map(|arg| match arg {
+        syn::GenericArgument::Type(ty) => Some(ty),
+        _ => None,
+    });
+    match (types.next(), types.next(), types.next()) {
+        (Some(ok), Some(err), None) => Some((ok, err)),
+        _ => None,
+    }
+}
+
 #[derive(Default)]
 struct DiscoveredItems {
     tests: Vec<TestInfo>,
@@ -458,6 +804,8 @@ struct TestInfo {
     name: String,
     fn_ident: syn::Ident,
     tags: Vec<String>,
+    /// The parameters of a `#[sheila::params(...)]` test
+    cases: Vec<Expr>,
 }
 
 struct FixtureInfo {
@@ -504,6 +852,7 @@ fn discover_sheila_items(items: &[Item]) -> DiscoveredItems {
                                 name: func.sig.ident.to_string(),
                                 fn_ident: func.sig.ident.clone(),
                                 tags,
+                                cases: param_cases(&func.attrs),
                             });
                         }
                         SheilaAttribute::Fixture { scope, depends_on } => {
@@ -522,8 +871,6 @@ fn discover_sheila_items(items: &[Item]) -> DiscoveredItems {
                             });
                         }
                     }
-                } else {
-                    println!("Warning: Unknown sheila attribute: {:?}", attr.path());
                 }
             }
         }
@@ -532,19 +879,41 @@ fn discover_sheila_items(items: &[Item]) -> DiscoveredItems {
     discovered
 }
 
+/// The parameters of the `#[sheila::params(...)]` attributes in `attrs`
+fn param_cases(attrs: &[Attribute]) -> Vec<Expr> {
+    attrs
+        .iter()
+        .filter(|attr| last_segment(attr.path()).as_deref() == Some("params"))
+        .filter_map(|attr| {
+            attr.parse_args_with(Punctuated::<Expr, Token![,]>::parse_terminated)
+                .ok()
+        })
+        .flatten()
+        .collect()
+}
+
 fn generate_test_registrations(tests: &[TestInfo]) -> Vec<TokenStream2> {
     tests
         .iter()
         .map(|test| {
             let fn_ident = &test.fn_ident;
             let test_name = &test.name;
+            let calls = if test.cases.is_empty() {
+                vec![quote! { #fn_ident() }]
+            } else {
+                let cases = &test.cases;
+                cases
+                    .iter()
+                    .map(|case| quote! { #fn_ident(#case) })
+                    .collect()
+            };
 
             if test.tags.is_empty() {
                 quote! {
                     suite = suite.add_test(::sheila::Test::new(
                         #test_name,
                         |_ctx| {
-                            #fn_ident();
+                            #(#calls;)*
                             Ok(())
                         }
                     ));
@@ -555,7 +924,7 @@ fn generate_test_registrations(tests: &[TestInfo]) -> Vec<TokenStream2> {
                     suite = suite.add_test(::sheila::Test::new(
                         #test_name,
                         |_ctx| {
-                            #fn_ident();
+                            #(#calls;)*
                             Ok(())
                         }
                     ).with_attributes(::sheila::TestAttributes {
@@ -706,6 +1075,33 @@ fn parse_fixture_attribute(attr: &Attribute) -> (String, Vec<String>) {
     (scope, depends_on)
 }
 
+fn last_segment(path: &syn::Path) -> Option<String> {
+    path.segments
+        .last()
+        .map(|segment| segment.ident.to_string())
+}
+
+/// The reason given to `#[sheila::xfail]` or `#[sheila::quarantine]`, as
+/// either `reason = "..."` or just `"..."`
+fn parse_reason(attr: &Attribute) -> syn::Result<String> {
+    if let syn::Meta::Path(_) = attr.meta {
+        return Err(syn::Error::new_spanned(
+            attr,
+            "expected a `reason = \"...\"`",
+        ));
+    }
+    attr.parse_args_with(|input: syn::parse::ParseStream| {
+        if input.peek(syn::Ident) {
+            let key: syn::Ident = input.parse()?;
+            if key != "reason" {
+                return Err(syn::Error::new(key.span(), "expected `reason = \"...\"`"));
+            }
+            input.parse::<syn::Token![=]>()?;
+        }
+        Ok(input.parse::<syn::LitStr>()?.value())
+    })
+}
+
 fn parse_attribute_args(args: TokenStream) -> Vec<String> {
     if args.is_empty() {
         return vec![];
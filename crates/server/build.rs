@@ -0,0 +1,42 @@
+//! Generates the gRPC service of `proto/sheila/v1/sheila.proto`. Its messages
+//! are written out in `src/grpc/proto.rs` rather than generated, so building
+//! doesn't need `protoc`, and the two have to be kept in step by hand.
+
+use tonic_build::manual::{Builder, Method, Service};
+
+fn main() {
+    println!("cargo:rerun-if-changed=build.rs");
+
+    let method = |name: &str, route_name: &str, input: &str, output: &str| {
+        Method::builder()
+            .name(name)
+            .route_name(route_name)
+            .input_type(format!("super::{}", input))
+            .output_type(format!("super::{}", output))
+            .codec_path("tonic_prost::ProstCodec")
+    };
+    let service = Service::builder()
+        .name("Runs")
+        .package("sheila.v1")
+        .method(method("submit_run", "SubmitRun", "SubmitRunRequest", "Run").build())
+        .method(method("get_run", "GetRun", "RunRequest", "Run").build())
+        .method(
+            method(
+                "list_runs",
+                "ListRuns",
+                "ListRunsRequest",
+                "ListRunsResponse",
+            )
+            .build(),
+        )
+        .method(method("cancel_run", "CancelRun", "RunRequest", "Run").build())
+        .method(
+            method("stream_events", "StreamEvents", "RunRequest", "RunEvent")
+                .server_streaming()
+                .build(),
+        )
+        .method(method("get_result", "GetResult", "RunRequest", "RunResult").build())
+        .build();
+
+    Builder::new().build_client(false).compile(&[service]);
+}
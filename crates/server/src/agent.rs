@@ -0,0 +1,272 @@
+//! The worker side of distributed runs, started with `sheilad agent`.
+//!
+//! An agent registers with a server, then polls it for shards to run. Each
+//! shard runs in the agent's own checkout, which must match the server's
+//! [`source_checksum`], and its events and result are posted back to the
+//! server as they come.
+
+use std::path::PathBuf;
+use std::sync::mpsc;
+use std::time::Duration;
+
+use reqwest::{Client, Response, StatusCode};
+use serde::Serialize;
+use serde::de::DeserializeOwned;
+use sheila::{CancelReason, CancellationToken, Error, EventSender, ProcessOutput};
+use tokio::sync::mpsc::unbounded_channel;
+use uuid::Uuid;
+
+use crate::agents::{
+    AgentInfo, EventAck, EventBatch, Registration, ShardAssignment, ShardOutcome, ShardReport,
+    source_checksum,
+};
+use crate::events::RunEvent;
+use crate::runs;
+
+/// How long to wait before polling again when there's no work, or before
+/// retrying when the server can't be reached
+const POLL_INTERVAL: Duration = Duration::from_secs(2);
+
+/// How often a running shard's events are posted. This doubles as the
+/// agent's heartbeat, so it must stay well under the server's
+/// [`AGENT_TIMEOUT`](crate::agents::AGENT_TIMEOUT).
+const EVENT_INTERVAL: Duration = Duration::from_millis(500);
+
+#[derive(Debug, Clone)]
+pub struct AgentConfig {
+    /// Base URL of the server to join, e.g. `http://ci-host:7878`
+    pub server: String,
+    /// Name the agent shows up as on the server
+    pub name: String,
+    /// Checkout of the project the server runs
+    pub project_root: PathBuf,
+    /// Name of that project on the server, if it isn't the server's own
+    pub project: Option<String>,
+    /// API token to send, if the server requires one
+    pub token: Option<String>,
+}
+
+struct Agent {
+    config: AgentConfig,
+    client: Client,
+    id: Uuid,
+}
+
+/// Join the server in `config` and run the shards it hands out until the
+/// process is stopped. Shards build and run in `config.project_root`.
+pub async fn run(config: AgentConfig) -> sheila::Result<()> {
+    let mut agent = Agent {
+        config,
+        client: Client::new(),
+        id: Uuid::nil(),
+    };
+    agent.register().await;
+
+    loop {
+        match agent.poll().await {
+            Ok(Some(assignment)) => agent.run_shard(assignment).await,
+            Ok(None) => tokio::time::sleep(POLL_INTERVAL).await,
+            Err(PollError::Unknown) => {
+                tracing::warn!("The server no longer knows this agent, joining again");
+                agent.register().await;
+            }
+            Err(PollError::Failed(e)) => {
+                tracing::warn!("Failed to poll {}: {}", agent.config.server, e);
+                tokio::time::sleep(POLL_INTERVAL).await;
+            }
+        }
+    }
+}
+
+enum PollError {
+    /// The server doesn't know the agent, e.g. because it restarted
+    Unknown,
+    Failed(Error),
+}
+
+impl Agent {
+    fn url(&self, path: &str) -> String {
+        format!("{}{}", self.config.server.trim_end_matches('/'), path)
+    }
+
+    async fn send<B: Serialize>(&self, path: &str, body: &B) -> Result<Response, PollError> {
+        let mut request = self.client.post(self.url(path)).json(body);
+        if let Some(token) = &self.config.token {
+            request = request.bearer_auth(token);
+        }
+        let response = request
+            .send()
+            .await
+            .map_err(|e| PollError::Failed(http_error(e)))?;
+        if response.status() == StatusCode::NOT_FOUND {
+            return Err(PollError::Unknown);
+        }
+        response
+            .error_for_status()
+            .map_err(|e| PollError::Failed(http_error(e)))
+    }
+
+    async fn post<B: Serialize, T: DeserializeOwned>(
+        &self,
+        path: &str,
+        body: &B,
+    ) -> Result<T, PollError> {
+        self.send(path, body)
+            .await?
+            .json()
+            .await
+            .map_err(|e| PollError::Failed(http_error(e)))
+    }
+
+    /// Register with the server, retrying until it answers
+    async fn register(&mut self) {
+        let registration = Registration {
+            name: self.config.name.clone(),
+            project: self.config.project.clone(),
+        };
+        loop {
+            match self.post::<_, AgentInfo>("/agents", &registration).await {
+                Ok(info) => {
+                    self.id = info.id;
+                    tracing::info!("Joined {} as {}", self.config.server, info.id);
+                    return;
+                }
+                Err(PollError::Failed(e)) => {
+                    tracing::warn!("Failed to join {}: {}", self.config.server, e)
+                }
+                Err(PollError::Unknown) => {
+                    tracing::warn!("{} doesn't accept agents", self.config.server)
+                }
+            }
+            tokio::time::sleep(POLL_INTERVAL).await;
+        }
+    }
+
+    async fn poll(&self) -> Result<Option<ShardAssignment>, PollError> {
+        self.post(&format!("/agents/{}/poll", self.id), &()).await
+    }
+
+    async fn run_shard(&self, assignment: ShardAssignment) {
+        let ShardAssignment {
+            run_id,
+            spec,
+            shard,
+            checksum,
+        } = assignment;
+        tracing::info!(
+            "Running shard {}/{} of run {}",
+            shard.index,
+            shard.total,
+            run_id
+        );
+
+        let root = self.config.project_root.clone();
+        let revision = spec.revision.clone();
+        let prepare = move || {
+            if let Some(revision) = &revision {
+                runs::checkout(&root, revision)?;
+            }
+            source_checksum(&root)
+        };
+        let outcome = match tokio::task::spawn_blocking(prepare).await {
+            Ok(Ok(ours)) if ours == checksum => None,
+            Ok(Ok(ours)) => Some(ShardOutcome::Errored {
+                message: format!(
+                    "agent {} has different sources ({}) than the server ({})",
+                    self.config.name, ours, checksum
+                ),
+            }),
+            Ok(Err(e)) => Some(ShardOutcome::Errored {
+                message: e.to_string(),
+            }),
+            Err(e) => Some(ShardOutcome::Errored {
+                message: e.to_string(),
+            }),
+        };
+
+        let outcome = match outcome {
+            Some(outcome) => outcome,
+            None => {
+                let cancel = CancellationToken::new();
+                let (output_tx, output_rx) = mpsc::channel::<ProcessOutput>();
+                let (events_tx, mut events_rx) = unbounded_channel::<RunEvent>();
+                let forward = tokio::task::spawn_blocking(move || {
+                    for output in output_rx {
+                        let _ = events_tx.send(output.into());
+                    }
+                });
+
+                let token = cancel.clone();
+                let output_tx = EventSender::new(output_tx).with_run_id(run_id);
+                let root = self.config.project_root.clone();
+                let mut execution = tokio::task::spawn_blocking(move || {
+                    runs::execute(&spec, &root, &token, output_tx, Some(shard))
+                });
+                let mut interval = tokio::time::interval(EVENT_INTERVAL);
+                let result = loop {
+                    tokio::select! {
+                        result = &mut execution => break result,
+                        _ = interval.tick() => {
+                            self.send_events(run_id, &mut events_rx, &cancel).await;
+                        }
+                    }
+                };
+                let _ = forward.await;
+                self.send_events(run_id, &mut events_rx, &cancel).await;
+
+                match result.map_err(|e| Error::generic(e.to_string())) {
+                    Ok(Ok(result)) => ShardOutcome::Finished {
+                        result: Box::new(result),
+                    },
+                    Ok(Err(e)) | Err(e) => ShardOutcome::Errored {
+                        message: e.to_string(),
+                    },
+                }
+            }
+        };
+
+        let report = ShardReport {
+            run_id,
+            index: shard.index,
+            outcome,
+        };
+        let path = format!("/agents/{}/result", self.id);
+        if let Err(PollError::Failed(e)) = self.send(&path, &report).await {
+            tracing::warn!(
+                "Failed to report shard {} of run {}: {}",
+                shard.index,
+                run_id,
+                e
+            );
+        }
+    }
+
+    /// Post the events gathered so far, stopping the shard if the server
+    /// says the run was cancelled
+    async fn send_events(
+        &self,
+        run_id: Uuid,
+        events_rx: &mut tokio::sync::mpsc::UnboundedReceiver<RunEvent>,
+        cancel: &CancellationToken,
+    ) {
+        let mut events = Vec::new();
+        while let Ok(event) = events_rx.try_recv() {
+            events.push(event);
+        }
+        let batch = EventBatch { run_id, events };
+        let path = format!("/agents/{}/events", self.id);
+        match self.post::<_, EventAck>(&path, &batch).await {
+            Ok(ack) if ack.cancelled && !cancel.is_cancelled() => {
+                tracing::info!("Run {} was cancelled", run_id);
+                cancel.cancel(CancelReason::User);
+            }
+            Ok(_) => {}
+            Err(PollError::Unknown) => cancel.cancel(CancelReason::User),
+            Err(PollError::Failed(e)) => tracing::warn!("Failed to send events: {}", e),
+        }
+    }
+}
+
+fn http_error(e: reqwest::Error) -> Error {
+    Error::io(e.to_string())
+}
@@ -0,0 +1,405 @@
+use std::collections::{HashMap, VecDeque};
+use std::fs;
+use std::path::Path;
+use std::sync::{Arc, Mutex, MutexGuard};
+use std::time::Duration;
+
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+use sheila::runners::{RunResult, Shard};
+use sheila::{CancellationToken, Error};
+use tokio::sync::{broadcast, mpsc};
+use uuid::Uuid;
+
+use crate::events::RunEvent;
+use crate::runs::RunSpec;
+use crate::{ApiError, ApiResult};
+
+/// Agents that haven't been heard from for this long are dropped, and the
+/// shard they were running goes back on the queue
+pub const AGENT_TIMEOUT: Duration = Duration::from_secs(30);
+
+/// A worker machine that runs shards of distributed runs, as listed by
+/// `GET /agents`
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct AgentInfo {
+    pub id: Uuid,
+    pub name: String,
+    /// The project the agent runs shards of
+    pub project: String,
+    pub registered_at: DateTime<Utc>,
+    pub last_seen: DateTime<Utc>,
+    /// The shard the agent is running, if any
+    pub assignment: Option<ShardRef>,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub struct ShardRef {
+    pub run_id: Uuid,
+    pub index: usize,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Registration {
+    pub name: String,
+    /// The project whose checkout the agent has, the server's own if not
+    /// given
+    #[serde(default)]
+    pub project: Option<String>,
+}
+
+/// A shard handed to an agent. The agent only runs it if its checkout
+/// matches `checksum`, as computed by [`source_checksum`].
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ShardAssignment {
+    pub run_id: Uuid,
+    pub spec: RunSpec,
+    pub shard: Shard,
+    pub checksum: String,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(tag = "outcome", rename_all = "snake_case")]
+pub enum ShardOutcome {
+    Finished { result: Box<RunResult> },
+    Errored { message: String },
+}
+
+/// What an agent posts when it finishes a shard
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ShardReport {
+    pub run_id: Uuid,
+    pub index: usize,
+    #[serde(flatten)]
+    pub outcome: ShardOutcome,
+}
+
+/// Events from a shard an agent is running. Agents post these regularly,
+/// even with no events, to show they are still alive.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct EventBatch {
+    pub run_id: Uuid,
+    pub events: Vec<RunEvent>,
+}
+
+/// The answer to an [`EventBatch`]
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct EventAck {
+    /// Whether the agent should stop running the shard
+    pub cancelled: bool,
+}
+
+struct ShardedRun {
+    spec: RunSpec,
+    checksum: String,
+    events: broadcast::Sender<RunEvent>,
+    cancel: CancellationToken,
+    outcomes: mpsc::UnboundedSender<(usize, Option<ShardOutcome>)>,
+}
+
+#[derive(Default)]
+struct Registry {
+    agents: HashMap<Uuid, AgentInfo>,
+    /// Shards waiting for an agent, in the order they'll be handed out
+    pending: VecDeque<(Uuid, Shard)>,
+    runs: HashMap<Uuid, ShardedRun>,
+}
+
+/// The agents that joined the server, and the shards of distributed runs
+/// waiting for or being run by them. Agents pull work by polling, so the
+/// server never has to reach them.
+#[derive(Clone, Default)]
+pub struct AgentRegistry {
+    inner: Arc<Mutex<Registry>>,
+}
+
+impl AgentRegistry {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    fn lock(&self) -> MutexGuard<'_, Registry> {
+        self.inner.lock().unwrap_or_else(|e| e.into_inner())
+    }
+
+    /// Add an agent running shards of `project`
+    pub fn register(&self, name: String, project: String) -> AgentInfo {
+        let now = Utc::now();
+        let agent = AgentInfo {
+            id: Uuid::new_v4(),
+            name,
+            project,
+            registered_at: now,
+            last_seen: now,
+            assignment: None,
+        };
+        tracing::info!(
+            "Agent {} ({}) joined for {}",
+            agent.name,
+            agent.id,
+            agent.project
+        );
+        self.lock().agents.insert(agent.id, agent.clone());
+        agent
+    }
+
+    /// Agent `id`, as it was last heard from
+    pub fn get(&self, id: Uuid) -> ApiResult<AgentInfo> {
+        self.lock()
+            .agents
+            .get(&id)
+            .cloned()
+            .ok_or_else(|| ApiError::NotFound(format!("No agent {}", id)))
+    }
+
+    pub fn list(&self) -> Vec<AgentInfo> {
+        let mut agents: Vec<AgentInfo> = self.lock().agents.values().cloned().collect();
+        agents.sort_by_key(|agent| agent.registered_at);
+        agents
+    }
+
+    /// The next shard of agent `id`'s project for it to run, if there is
+    /// one
+    pub fn poll(&self, id: Uuid) -> ApiResult<Option<ShardAssignment>> {
+        let mut registry = self.lock();
+        let registry = &mut *registry;
+        let agent = registry
+            .agents
+            .get_mut(&id)
+            .ok_or_else(|| ApiError::NotFound(format!("No agent {}", id)))?;
+        agent.last_seen = Utc::now();
+
+        let runs = &registry.runs;
+        registry
+            .pending
+            .retain(|(run_id, _)| runs.contains_key(run_id));
+        let next = registry.pending.iter().position(|(run_id, _)| {
+            runs.get(run_id)
+                .is_some_and(|run| run.spec.project.as_deref() == Some(agent.project.as_str()))
+        });
+        if let Some((run_id, shard)) = next.and_then(|index| registry.pending.remove(index))
+            && let Some(run) = registry.runs.get(&run_id)
+        {
+            agent.assignment = Some(ShardRef {
+                run_id,
+                index: shard.index,
+            });
+            tracing::info!(
+                "Sending shard {}/{} of run {} to agent {}",
+                shard.index,
+                shard.total,
+                run_id,
+                agent.name
+            );
+            return Ok(Some(ShardAssignment {
+                run_id,
+                spec: run.spec.clone(),
+                shard,
+                checksum: run.checksum.clone(),
+            }));
+        }
+        Ok(None)
+    }
+
+    /// Pass on events from a shard agent `id` is running
+    pub fn events(&self, id: Uuid, batch: EventBatch) -> ApiResult<EventAck> {
+        let mut registry = self.lock();
+        let agent = registry
+            .agents
+            .get_mut(&id)
+            .ok_or_else(|| ApiError::NotFound(format!("No agent {}", id)))?;
+        agent.last_seen = Utc::now();
+        if agent
+            .assignment
+            .is_none_or(|shard| shard.run_id != batch.run_id)
+        {
+            return Err(ApiError::Conflict(format!(
+                "Agent {} isn't running a shard of run {}",
+                id, batch.run_id
+            )));
+        }
+
+        let Some(run) = registry.runs.get(&batch.run_id) else {
+            return Ok(EventAck { cancelled: true });
+        };
+        for event in batch.events {
+            // Only the server starts and finishes runs
+            if !matches!(event, RunEvent::RunStarted | RunEvent::RunFinished { .. }) {
+                let _ = run.events.send(event);
+            }
+        }
+        Ok(EventAck {
+            cancelled: run.cancel.is_cancelled(),
+        })
+    }
+
+    /// Take in the outcome of a shard agent `id` ran
+    pub fn report(&self, id: Uuid, report: ShardReport) -> ApiResult<()> {
+        let mut registry = self.lock();
+        let agent = registry
+            .agents
+            .get_mut(&id)
+            .ok_or_else(|| ApiError::NotFound(format!("No agent {}", id)))?;
+        agent.last_seen = Utc::now();
+        let shard = ShardRef {
+            run_id: report.run_id,
+            index: report.index,
+        };
+        if agent.assignment != Some(shard) {
+            return Err(ApiError::Conflict(format!(
+                "Agent {} isn't running shard {} of run {}",
+                id, report.index, report.run_id
+            )));
+        }
+        agent.assignment = None;
+
+        let run = registry.runs.get(&report.run_id).ok_or_else(|| {
+            ApiError::Conflict(format!("Run {} is no longer running", report.run_id))
+        })?;
+        let _ = run.outcomes.send((report.index, Some(report.outcome)));
+        Ok(())
+    }
+
+    /// Drop agents not heard from within [`AGENT_TIMEOUT`], putting the
+    /// shards they were running back at the front of the queue
+    pub fn reap(&self) {
+        let mut registry = self.lock();
+        let cutoff = Utc::now() - AGENT_TIMEOUT;
+        let lost: Vec<AgentInfo> = registry
+            .agents
+            .values()
+            .filter(|agent| agent.last_seen < cutoff)
+            .cloned()
+            .collect();
+
+        for agent in lost {
+            registry.agents.remove(&agent.id);
+            tracing::warn!("Agent {} ({}) stopped responding", agent.name, agent.id);
+            let Some(assignment) = agent.assignment else {
+                continue;
+            };
+            let Some(run) = registry.runs.get(&assignment.run_id) else {
+                continue;
+            };
+            let Ok(shard) = Shard::new(assignment.index, run.spec.shards.unwrap_or(1)) else {
+                continue;
+            };
+            registry.pending.push_front((assignment.run_id, shard));
+        }
+    }
+
+    /// Split run `id` into `spec.shards` shards, wait for agents to run
+    /// them all, and merge what they report into one result. Agents must
+    /// have the same sources as the project's checkout at `root`. Shards
+    /// not yet handed out when `cancel` fires are dropped, and agents
+    /// running the others are told to stop.
+    pub async fn run_sharded(
+        &self,
+        id: Uuid,
+        spec: &RunSpec,
+        root: &Path,
+        cancel: &CancellationToken,
+        events: broadcast::Sender<RunEvent>,
+    ) -> sheila::Result<RunResult> {
+        let total = spec.shards.unwrap_or(1).max(1);
+        let root = root.to_path_buf();
+        let checksum = tokio::task::spawn_blocking(move || source_checksum(&root))
+            .await
+            .map_err(|e| Error::generic(e.to_string()))??;
+
+        let (outcomes, mut rx) = mpsc::unbounded_channel();
+        {
+            let mut registry = self.lock();
+            registry.runs.insert(
+                id,
+                ShardedRun {
+                    spec: spec.clone(),
+                    checksum,
+                    events,
+                    cancel: cancel.clone(),
+                    outcomes: outcomes.clone(),
+                },
+            );
+            for index in 1..=total {
+                registry.pending.push_back((id, Shard::new(index, total)?));
+            }
+        }
+
+        let mut results = Vec::new();
+        let mut errors = Vec::new();
+        let mut remaining = total;
+        let mut check = tokio::time::interval(Duration::from_secs(1));
+        while remaining > 0 {
+            tokio::select! {
+                Some((index, outcome)) = rx.recv() => {
+                    remaining -= 1;
+                    match outcome {
+                        Some(ShardOutcome::Finished { result }) => results.push(*result),
+                        Some(ShardOutcome::Errored { message }) => {
+                            errors.push(format!("shard {}/{}: {}", index, total, message));
+                        }
+                        None => {}
+                    }
+                }
+                _ = check.tick() => {
+                    if cancel.is_cancelled() {
+                        let mut registry = self.lock();
+                        registry.pending.retain(|(run_id, shard)| {
+                            let dropped = *run_id == id;
+                            if dropped {
+                                let _ = outcomes.send((shard.index, None));
+                            }
+                            !dropped
+                        });
+                    }
+                }
+            }
+        }
+        self.lock().runs.remove(&id);
+
+        if !errors.is_empty() {
+            return Err(Error::generic(format!(
+                "Distributed run failed: {}",
+                errors.join("; ")
+            )));
+        }
+        if results.is_empty() {
+            return Ok(RunResult::new(spec.runner_config()));
+        }
+        RunResult::merge(results)
+    }
+}
+
+/// A checksum of the sources under `root`: every `.rs` file and cargo
+/// manifest or lock file, outside hidden and `target` directories. Agents
+/// compare it with their own checkout's before running a shard.
+pub fn source_checksum(root: &Path) -> sheila::Result<String> {
+    let mut files = Vec::new();
+    collect_sources(root, root, &mut files)?;
+    files.sort();
+
+    let mut digest = String::new();
+    for path in files {
+        let hash = sheila::cache::hash_file(root.join(&path))?;
+        digest.push_str(&format!("{}:{:016x}\n", path, hash));
+    }
+    Ok(format!("{:016x}", sheila::stable_hash(digest.as_bytes())))
+}
+
+fn collect_sources(root: &Path, dir: &Path, files: &mut Vec<String>) -> sheila::Result<()> {
+    for entry in fs::read_dir(dir)? {
+        let entry = entry?;
+        let path = entry.path();
+        let name = entry.file_name().to_string_lossy().to_string();
+        if entry.file_type()?.is_dir() {
+            if !name.starts_with('.') && name != "target" {
+                collect_sources(root, &path, files)?;
+            }
+        } else if name.ends_with(".rs") || name == "Cargo.toml" || name == "Cargo.lock" {
+            let relative = path.strip_prefix(root).unwrap_or(&path);
+            // Separators differ between platforms, the layout doesn't
+            files.push(relative.to_string_lossy().replace('\\', "/"));
+        }
+    }
+    Ok(())
+}
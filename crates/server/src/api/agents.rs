@@ -0,0 +1,176 @@
+use axum::extract::{Path, State};
+use axum::http::StatusCode;
+use axum::{Extension, Json};
+use uuid::Uuid;
+
+use crate::agents::{AgentInfo, EventAck, EventBatch, Registration, ShardAssignment, ShardReport};
+use crate::auth::Caller;
+use crate::{ApiResult, AppState};
+
+/// The agents of the projects the caller may use
+pub async fn list(
+    State(state): State<AppState>,
+    Extension(caller): Extension<Caller>,
+) -> Json<Vec<AgentInfo>> {
+    let mut agents = state.agents.list();
+    agents.retain(|agent| caller.allows(&agent.project));
+    Json(agents)
+}
+
+pub async fn register(
+    State(state): State<AppState>,
+    Extension(caller): Extension<Caller>,
+    Json(registration): Json<Registration>,
+) -> ApiResult<(StatusCode, Json<AgentInfo>)> {
+    let project = state.projects.get(registration.project.as_deref())?;
+    caller.check_project(&project.name)?;
+    Ok((
+        StatusCode::CREATED,
+        Json(state.agents.register(registration.name, project.name)),
+    ))
+}
+
+pub async fn poll(
+    State(state): State<AppState>,
+    Extension(caller): Extension<Caller>,
+    Path(id): Path<Uuid>,
+) -> ApiResult<Json<Option<ShardAssignment>>> {
+    check_agent(&state, &caller, id)?;
+    Ok(Json(state.agents.poll(id)?))
+}
+
+pub async fn events(
+    State(state): State<AppState>,
+    Extension(caller): Extension<Caller>,
+    Path(id): Path<Uuid>,
+    Json(batch): Json<EventBatch>,
+) -> ApiResult<Json<EventAck>> {
+    check_agent(&state, &caller, id)?;
+    Ok(Json(state.agents.events(id, batch)?))
+}
+
+pub async fn result(
+    State(state): State<AppState>,
+    Extension(caller): Extension<Caller>,
+    Path(id): Path<Uuid>,
+    Json(report): Json<ShardReport>,
+) -> ApiResult<StatusCode> {
+    check_agent(&state, &caller, id)?;
+    state.agents.report(id, report)?;
+    Ok(StatusCode::NO_CONTENT)
+}
+
+/// Fail unless the caller may act as agent `id`, which only runs shards of
+/// its own project
+fn check_agent(state: &AppState, caller: &Caller, id: Uuid) -> ApiResult<()> {
+    caller.check_project(&state.agents.get(id)?.project)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::ApiError;
+    use crate::ServerConfig;
+    use crate::agents::ShardOutcome;
+    use crate::auth::{ApiToken, Role};
+    use chrono::Utc;
+
+    fn state() -> AppState {
+        let root = std::env::temp_dir().join(format!("sheila-agents-{}", Uuid::new_v4()));
+        std::fs::create_dir_all(&root).unwrap();
+        AppState::new(ServerConfig::new(root)).unwrap()
+    }
+
+    /// A trigger token limited to `project`
+    fn caller(project: &str) -> Caller {
+        Caller(Some(ApiToken {
+            id: Uuid::new_v4(),
+            name: format!("{}-token", project),
+            role: Role::Trigger,
+            projects: vec![project.to_string()],
+            created_at: Utc::now(),
+            last_used: None,
+        }))
+    }
+
+    #[tokio::test]
+    async fn test_token_of_another_project_cant_act_as_agent() {
+        let state = state();
+        let agent = state.agents.register("a-1".to_string(), "a".to_string());
+        let other = caller("b");
+        let run_id = Uuid::new_v4();
+
+        let polled = poll(
+            State(state.clone()),
+            Extension(other.clone()),
+            Path(agent.id),
+        )
+        .await;
+        assert!(matches!(polled, Err(ApiError::Forbidden(_))));
+
+        let batch = EventBatch {
+            run_id,
+            events: Vec::new(),
+        };
+        let posted = events(
+            State(state.clone()),
+            Extension(other.clone()),
+            Path(agent.id),
+            Json(batch),
+        )
+        .await;
+        assert!(matches!(posted, Err(ApiError::Forbidden(_))));
+
+        let report = ShardReport {
+            run_id,
+            index: 0,
+            outcome: ShardOutcome::Errored {
+                message: "forged".to_string(),
+            },
+        };
+        let reported = result(
+            State(state.clone()),
+            Extension(other.clone()),
+            Path(agent.id),
+            Json(report),
+        )
+        .await;
+        assert!(matches!(reported, Err(ApiError::Forbidden(_))));
+
+        let polled = poll(State(state.clone()), Extension(caller("a")), Path(agent.id)).await;
+        assert!(matches!(polled, Ok(Json(None))));
+    }
+
+    #[tokio::test]
+    async fn test_agents_are_listed_for_their_projects_only() {
+        let state = state();
+        state.agents.register("a-1".to_string(), "a".to_string());
+        state.agents.register("b-1".to_string(), "b".to_string());
+
+        let Json(agents) = list(State(state.clone()), Extension(caller("b"))).await;
+        let names: Vec<&str> = agents.iter().map(|agent| agent.name.as_str()).collect();
+        assert_eq!(names, ["b-1"]);
+
+        let Json(agents) = list(State(state), Extension(Caller(None))).await;
+        assert_eq!(agents.len(), 2);
+    }
+
+    #[tokio::test]
+    async fn test_agent_cant_post_events_for_a_run_it_isnt_running() {
+        let state = state();
+        let agent = state.agents.register("b-1".to_string(), "b".to_string());
+        let batch = EventBatch {
+            run_id: Uuid::new_v4(),
+            events: Vec::new(),
+        };
+
+        let posted = events(
+            State(state),
+            Extension(caller("b")),
+            Path(agent.id),
+            Json(batch),
+        )
+        .await;
+        assert!(matches!(posted, Err(ApiError::Conflict(_))));
+    }
+}
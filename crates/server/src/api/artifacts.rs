@@ -0,0 +1,106 @@
+use axum::Json;
+use axum::body::Bytes;
+use axum::extract::{Path, Query, State};
+use axum::http::{StatusCode, header};
+use axum::response::IntoResponse;
+use uuid::Uuid;
+
+use crate::artifacts::{ArtifactUpload, StoredArtifact};
+use crate::{ApiError, ApiResult, AppState};
+
+pub async fn list(
+    State(state): State<AppState>,
+    Path(run_id): Path<Uuid>,
+) -> ApiResult<Json<Vec<StoredArtifact>>> {
+    Ok(Json(state.db.run_artifacts(run_id)?))
+}
+
+pub async fn upload(
+    State(state): State<AppState>,
+    Path(run_id): Path<Uuid>,
+    Query(mut upload): Query<ArtifactUpload>,
+    body: Bytes,
+) -> ApiResult<(StatusCode, Json<StoredArtifact>)> {
+    if state.runs.get(run_id).await.is_none() && !state.db.contains(run_id)? {
+        return Err(ApiError::NotFound(format!("No run {}", run_id)));
+    }
+    if upload.name.trim().is_empty() {
+        return Err(ApiError::BadRequest("Artifacts need a name".into()));
+    }
+
+    // Name artifacts given by test id after their test, so the test's
+    // history can find them
+    if let Some(test_id) = upload.test_id
+        && upload.test.is_none()
+        && let Some(result) = state.reports.load(run_id)?
+    {
+        upload.test = result
+            .suite_results
+            .iter()
+            .flat_map(|suite_result| &suite_result.test_results)
+            .find(|test_result| test_result.id == test_id)
+            .map(|test_result| test_result.name.clone());
+    }
+
+    let artifact = state.artifacts.save(run_id, upload, &body)?;
+    Ok((StatusCode::CREATED, Json(artifact)))
+}
+
+fn find(state: &AppState, id: Uuid) -> ApiResult<StoredArtifact> {
+    state
+        .db
+        .artifact(id)?
+        .ok_or_else(|| ApiError::NotFound(format!("No artifact {}", id)))
+}
+
+pub async fn get(
+    State(state): State<AppState>,
+    Path(id): Path<Uuid>,
+) -> ApiResult<impl IntoResponse> {
+    let artifact = find(&state, id)?;
+    let content = match tokio::fs::read(state.artifacts.path(&artifact)).await {
+        Ok(content) => content,
+        Err(e) if e.kind() == std::io::ErrorKind::NotFound => {
+            return Err(ApiError::NotFound(format!("No artifact {}", id)));
+        }
+        Err(e) => return Err(e.into()),
+    };
+
+    let content_type = artifact.content_type();
+    let disposition = if content_type == "application/octet-stream" {
+        "attachment"
+    } else {
+        "inline"
+    };
+    let file_name: String = artifact
+        .name
+        .chars()
+        .map(|c| {
+            if c.is_ascii_graphic() && c != '"' && c != '\\' {
+                c
+            } else {
+                '_'
+            }
+        })
+        .collect();
+    Ok((
+        [
+            (header::CONTENT_TYPE, content_type.to_string()),
+            (
+                header::CONTENT_DISPOSITION,
+                format!("{}; filename=\"{}\"", disposition, file_name),
+            ),
+            // Artifacts come from the tests, so they don't get to run
+            // anything in the dashboard's origin
+            (header::X_CONTENT_TYPE_OPTIONS, "nosniff".to_string()),
+            (header::CONTENT_SECURITY_POLICY, "sandbox".to_string()),
+        ],
+        content,
+    ))
+}
+
+pub async fn delete(State(state): State<AppState>, Path(id): Path<Uuid>) -> ApiResult<StatusCode> {
+    let artifact = find(&state, id)?;
+    state.artifacts.delete(&artifact)?;
+    Ok(StatusCode::NO_CONTENT)
+}
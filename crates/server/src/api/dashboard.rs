@@ -0,0 +1,73 @@
+use axum::extract::{Query, State};
+use axum::response::{Html, Redirect};
+use serde::Deserialize;
+
+use super::ProjectQuery;
+use crate::dashboard::{self, Overview};
+use crate::db::TestSelector;
+use crate::{ApiResult, AppState};
+
+/// Runs the dashboard looks back over
+const RUNS: usize = 30;
+
+/// Tests listed in each of the dashboard's tables
+const TESTS: usize = 15;
+
+pub async fn root() -> Redirect {
+    Redirect::to("/dashboard")
+}
+
+pub async fn overview(
+    State(state): State<AppState>,
+    Query(query): Query<ProjectQuery>,
+) -> ApiResult<Html<String>> {
+    let project = query.resolve(&state)?;
+    let projects = state.projects.list()?;
+    let active: Vec<_> = state
+        .runs
+        .list(Some(&project.name))
+        .await
+        .into_iter()
+        .filter(|run| !run.status.is_finished())
+        .collect();
+    let trends = state.db.trends(&project.name, RUNS)?;
+    let mut flaky = state.db.flaky_tests(&project.name, RUNS)?;
+    flaky.truncate(TESTS);
+    let slowest = state.db.slowest_tests(&project.name, RUNS, TESTS)?;
+
+    Ok(Html(dashboard::overview(&Overview {
+        project: &project,
+        projects: &projects,
+        active: &active,
+        trends: &trends,
+        flaky: &flaky,
+        slowest: &slowest,
+    })))
+}
+
+#[derive(Debug, Deserialize)]
+pub struct TestQuery {
+    name: String,
+    suite: Option<String>,
+}
+
+pub async fn test(
+    State(state): State<AppState>,
+    Query(scope): Query<ProjectQuery>,
+    Query(query): Query<TestQuery>,
+) -> ApiResult<Html<String>> {
+    let project = scope.resolve(&state)?;
+    let test = TestSelector::Name {
+        name: query.name.clone(),
+        suite: query.suite.clone(),
+    };
+    let history = state.db.test_history(&project.name, &test, 100)?;
+    let artifacts = state.db.test_artifacts(&project.name, &query.name)?;
+    Ok(Html(dashboard::test_history(
+        &project.name,
+        query.suite.as_deref(),
+        &query.name,
+        &history,
+        &artifacts,
+    )))
+}
@@ -0,0 +1,54 @@
+use std::convert::Infallible;
+
+use axum::extract::ws::rejection::WebSocketUpgradeRejection;
+use axum::extract::ws::{Message, WebSocket, WebSocketUpgrade};
+use axum::extract::{Path, State};
+use axum::response::sse::{Event, KeepAlive, Sse};
+use axum::response::{IntoResponse, Response};
+use futures_util::{Stream, StreamExt};
+use uuid::Uuid;
+
+use crate::events::{self, RunEvent};
+use crate::{ApiError, ApiResult, AppState};
+
+pub async fn stream(
+    State(state): State<AppState>,
+    Path(id): Path<Uuid>,
+    ws: Result<WebSocketUpgrade, WebSocketUpgradeRejection>,
+) -> ApiResult<Response> {
+    let (record, rx) = state
+        .runs
+        .subscribe(id)
+        .await
+        .ok_or_else(|| ApiError::NotFound(format!("No run {}", id)))?;
+    let events = events::follow(record, rx);
+
+    Ok(match ws {
+        Ok(ws) => ws
+            .on_upgrade(move |socket| send_events(socket, events))
+            .into_response(),
+        Err(_) => Sse::new(events.map(|event| {
+            Ok::<_, Infallible>(
+                Event::default()
+                    .event(event.name())
+                    .data(serde_json::to_string(&event).unwrap_or_default()),
+            )
+        }))
+        .keep_alive(KeepAlive::default())
+        .into_response(),
+    })
+}
+
+async fn send_events(mut socket: WebSocket, events: impl Stream<Item = RunEvent>) {
+    let mut events = std::pin::pin!(events);
+    while let Some(event) = events.next().await {
+        let Ok(text) = serde_json::to_string(&event) else {
+            continue;
+        };
+        if socket.send(Message::Text(text.into())).await.is_err() {
+            // The client went away
+            return;
+        }
+    }
+    let _ = socket.send(Message::Close(None)).await;
+}
@@ -0,0 +1,43 @@
+use axum::Json;
+use axum::extract::State;
+use axum::http::header;
+use axum::response::IntoResponse;
+use serde_json::{Value, json};
+
+use crate::metrics::Gauges;
+use crate::runs::RunStatus;
+use crate::{ApiError, ApiResult, AppState};
+
+pub async fn healthz() -> Json<Value> {
+    Json(json!({ "status": "ok" }))
+}
+
+/// Ready once the database answers and the reports can be stored
+pub async fn readyz(State(state): State<AppState>) -> ApiResult<Json<Value>> {
+    state
+        .db
+        .ping()
+        .map_err(|e| ApiError::Unavailable(format!("The database isn't available: {}", e)))?;
+    let reports_dir = state.config.reports_dir();
+    if !reports_dir.is_dir() {
+        return Err(ApiError::Unavailable(format!(
+            "The reports directory {} is missing",
+            reports_dir.display()
+        )));
+    }
+    Ok(Json(json!({ "status": "ready" })))
+}
+
+pub async fn metrics(State(state): State<AppState>) -> impl IntoResponse {
+    let runs = state.runs.list(None).await;
+    let count = |status: RunStatus| runs.iter().filter(|run| run.status == status).count();
+    let gauges = Gauges {
+        queued_runs: count(RunStatus::Queued),
+        active_runs: count(RunStatus::Running),
+        agents: state.agents.list().len(),
+    };
+    (
+        [(header::CONTENT_TYPE, "text/plain; version=0.0.4")],
+        state.metrics.render(gauges),
+    )
+}
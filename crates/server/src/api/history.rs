@@ -0,0 +1,96 @@
+use axum::Json;
+use axum::extract::{Query, State};
+use serde::Deserialize;
+use uuid::Uuid;
+
+use super::ProjectQuery;
+use crate::db::{FlakyTest, SlowTest, TestRecord, TestSelector, TrendPoint};
+use crate::{ApiError, ApiResult, AppState};
+
+/// Number of runs looked back over unless the query says otherwise
+const DEFAULT_LIMIT: usize = 50;
+
+#[derive(Debug, Deserialize)]
+pub struct TestQuery {
+    name: Option<String>,
+    suite: Option<String>,
+    /// The test's stable id, instead of its name
+    id: Option<Uuid>,
+    limit: Option<usize>,
+}
+
+pub async fn tests(
+    State(state): State<AppState>,
+    Query(scope): Query<ProjectQuery>,
+    Query(query): Query<TestQuery>,
+) -> ApiResult<Json<Vec<TestRecord>>> {
+    let project = scope.resolve(&state)?;
+    let test = match (query.id, query.name) {
+        (Some(id), _) => TestSelector::Id(id),
+        (None, Some(name)) => TestSelector::Name {
+            name,
+            suite: query.suite,
+        },
+        (None, None) => {
+            return Err(ApiError::BadRequest("Give the test's name or id".into()));
+        }
+    };
+    Ok(Json(state.db.test_history(
+        &project.name,
+        &test,
+        query.limit.unwrap_or(DEFAULT_LIMIT),
+    )?))
+}
+
+#[derive(Debug, Deserialize)]
+pub struct FlakyQuery {
+    runs: Option<usize>,
+}
+
+pub async fn flaky(
+    State(state): State<AppState>,
+    Query(scope): Query<ProjectQuery>,
+    Query(query): Query<FlakyQuery>,
+) -> ApiResult<Json<Vec<FlakyTest>>> {
+    let project = scope.resolve(&state)?;
+    Ok(Json(state.db.flaky_tests(
+        &project.name,
+        query.runs.unwrap_or(DEFAULT_LIMIT),
+    )?))
+}
+
+#[derive(Debug, Deserialize)]
+pub struct SlowestQuery {
+    runs: Option<usize>,
+    limit: Option<usize>,
+}
+
+pub async fn slowest(
+    State(state): State<AppState>,
+    Query(scope): Query<ProjectQuery>,
+    Query(query): Query<SlowestQuery>,
+) -> ApiResult<Json<Vec<SlowTest>>> {
+    let project = scope.resolve(&state)?;
+    Ok(Json(state.db.slowest_tests(
+        &project.name,
+        query.runs.unwrap_or(DEFAULT_LIMIT),
+        query.limit.unwrap_or(20),
+    )?))
+}
+
+#[derive(Debug, Deserialize)]
+pub struct TrendQuery {
+    limit: Option<usize>,
+}
+
+pub async fn trends(
+    State(state): State<AppState>,
+    Query(scope): Query<ProjectQuery>,
+    Query(query): Query<TrendQuery>,
+) -> ApiResult<Json<Vec<TrendPoint>>> {
+    let project = scope.resolve(&state)?;
+    Ok(Json(state.db.trends(
+        &project.name,
+        query.limit.unwrap_or(DEFAULT_LIMIT),
+    )?))
+}
@@ -0,0 +1,171 @@
+//! The REST API. Bodies are JSON, and errors come back as
+//! `{"error": "..."}` with a matching status code.
+//!
+//! Listing runs, reports and history, and the dashboard, are scoped to a
+//! project by a `project` query parameter, and to the server's own project
+//! without one. Runs are started in the project their spec names.
+//!
+//! - `GET /projects`, `POST /projects`: list the server's projects, or
+//!   register one from a [`NewProject`](crate::projects::NewProject),
+//!   cloning its repository unless it's given a path on the server
+//! - `GET /projects/{name}`: a project
+//! - `DELETE /projects/{name}`: forget a project, keeping its stored runs
+//! - `GET /runs`, `POST /runs`: list runs, or queue one from a
+//!   [`RunSpec`](crate::runs::RunSpec)
+//! - `GET /queue`: the queued runs, in the order they'll start in
+//! - `GET /runs/{id}`: a run's status
+//! - `GET /runs/{id}/result`: a finished run's `RunResult`
+//! - `POST /runs/{id}/cancel`: stop a run, or take it off the queue
+//! - `PUT /runs/{id}/priority`: change a queued run's priority, given as
+//!   `{"priority": 10}`
+//! - `GET /runs/{id}/events`: a run's [`RunEvent`](crate::events::RunEvent)s
+//!   as they happen, over a WebSocket when the request asks to upgrade and
+//!   as server-sent events otherwise
+//! - `GET /runs/{id}/artifacts`: the artifacts stored for a run
+//! - `POST /runs/{id}/artifacts?name=...&test_id=...&test=...`: store the
+//!   request body as an artifact of a run, optionally of one of its tests,
+//!   given by id or name. Bodies over the size limit, or over the run's
+//!   quota, get a 413.
+//! - `GET /artifacts/{id}`: an artifact's content. Images and text are shown
+//!   in the browser, anything else is downloaded.
+//! - `DELETE /artifacts/{id}`: remove an artifact
+//! - `POST /results?project=...&commit=...`: store the result of a run
+//!   carried out elsewhere, e.g. by `sheila` in a CI job, given as a
+//!   `RunResult` or a `TestReport`. The commit defaults to the one in the
+//!   report's environment. Publishing a run again answers `200` without
+//!   storing it twice, and publishing it for another project or commit is a
+//!   conflict.
+//! - `GET /reports`: summaries of the stored runs
+//! - `GET /reports/{id}?format=html`: a stored run as a `TestReport`
+//! - `GET /reports/{id}/content?format=html`: just the rendered report
+//! - `DELETE /reports/{id}`: remove a stored run, along with its artifacts
+//! - `GET /history/tests?name=...&suite=...&limit=50`: a test's recent
+//!   results, newest first. `?id=...` picks the test by its stable id
+//!   instead
+//! - `GET /history/flaky?runs=50`: tests that were flaky across recent runs
+//! - `GET /history/slowest?runs=50&limit=20`: tests that took longest on
+//!   average across recent runs
+//! - `GET /history/trends?limit=50`: recent runs' headline numbers, oldest
+//!   first
+//! - `GET /agents`, `POST /agents`: list agents, or register one
+//! - `POST /agents/{id}/poll`: the next shard for an agent to run, or `null`
+//! - `POST /agents/{id}/events`: events from the shard an agent is running
+//! - `POST /agents/{id}/result`: the outcome of a shard an agent ran
+//! - `GET /tokens`, `POST /tokens`: list API tokens, or create one from a
+//!   [`NewToken`](crate::auth::NewToken), getting its secret back
+//! - `DELETE /tokens/{id}`: revoke a token
+//! - `POST /webhooks/github`, `POST /webhooks/gitlab`: queue runs for a
+//!   push or pull request, as configured in [`webhooks`](crate::webhooks).
+//!   Answers with the queued runs.
+//!
+//! - `GET /healthz`: whether the server is up
+//! - `GET /readyz`: whether it can take runs, answering `503` if not
+//! - `GET /metrics`: queue depth, active runs and counts of finished runs
+//!   and tests, in the Prometheus text format. See
+//!   [`metrics`](crate::metrics). With auth enabled this needs a token not
+//!   limited to any project.
+//!
+//! `GET /dashboard` serves a dashboard of recent runs, trends, flaky and
+//! slow tests, with a history page per test at `GET /dashboard/tests`.
+//!
+//! When the server is started with auth enabled, every route needs an API
+//! token: `GET` routes a read token, the rest a trigger token, and `/tokens`
+//! and changes to `/projects` an admin token, except the webhooks, which
+//! check the webhook secret, and the health checks. See
+//! [`auth`](crate::auth).
+
+mod agents;
+mod artifacts;
+mod dashboard;
+mod events;
+mod health;
+mod history;
+mod projects;
+mod reports;
+mod results;
+mod runs;
+mod tokens;
+mod webhooks;
+
+use axum::Router;
+use axum::extract::DefaultBodyLimit;
+use axum::handler::Handler;
+use axum::middleware;
+use axum::routing::{delete, get, post, put};
+use serde::Deserialize;
+
+use crate::projects::Project;
+use crate::{ApiResult, AppState, auth};
+
+/// Largest result `POST /results` accepts, in bytes
+const MAX_RESULT_SIZE: usize = 64 * 1024 * 1024;
+
+/// The `project` query parameter of routes scoped to a project
+#[derive(Debug, Default, Deserialize)]
+#[serde(default)]
+pub struct ProjectQuery {
+    project: Option<String>,
+}
+
+impl ProjectQuery {
+    /// The project asked for, or the server's own
+    fn resolve(&self, state: &AppState) -> ApiResult<Project> {
+        state.projects.get(self.project.as_deref())
+    }
+}
+
+pub fn router(state: AppState) -> Router {
+    let max_artifact_size = state.artifacts.limits().max_size as usize;
+    Router::new()
+        .route("/projects", get(projects::list).post(projects::register))
+        .route(
+            "/projects/{name}",
+            get(projects::get).delete(projects::remove),
+        )
+        .route("/runs", get(runs::list).post(runs::start))
+        .route("/runs/{id}", get(runs::get))
+        .route("/runs/{id}/result", get(runs::result))
+        .route("/runs/{id}/cancel", post(runs::cancel))
+        .route("/runs/{id}/priority", put(runs::reprioritize))
+        .route("/queue", get(runs::queue))
+        .route("/runs/{id}/events", get(events::stream))
+        .route(
+            "/runs/{id}/artifacts",
+            get(artifacts::list)
+                .post(artifacts::upload.layer(DefaultBodyLimit::max(max_artifact_size))),
+        )
+        .route(
+            "/artifacts/{id}",
+            get(artifacts::get).delete(artifacts::delete),
+        )
+        .route(
+            "/results",
+            post(results::publish.layer(DefaultBodyLimit::max(MAX_RESULT_SIZE))),
+        )
+        .route("/reports", get(reports::list))
+        .route("/reports/{id}", get(reports::get).delete(reports::delete))
+        .route("/reports/{id}/content", get(reports::content))
+        .route("/history/tests", get(history::tests))
+        .route("/history/flaky", get(history::flaky))
+        .route("/history/slowest", get(history::slowest))
+        .route("/history/trends", get(history::trends))
+        .route("/agents", get(agents::list).post(agents::register))
+        .route("/agents/{id}/poll", post(agents::poll))
+        .route("/agents/{id}/events", post(agents::events))
+        .route("/agents/{id}/result", post(agents::result))
+        .route("/healthz", get(health::healthz))
+        .route("/readyz", get(health::readyz))
+        .route("/metrics", get(health::metrics))
+        .route("/", get(dashboard::root))
+        .route("/dashboard", get(dashboard::overview))
+        .route("/dashboard/tests", get(dashboard::test))
+        .route("/tokens", get(tokens::list).post(tokens::create))
+        .route("/tokens/{id}", delete(tokens::revoke))
+        .route("/webhooks/github", post(webhooks::github))
+        .route("/webhooks/gitlab", post(webhooks::gitlab))
+        .layer(middleware::from_fn_with_state(
+            state.clone(),
+            auth::authorize,
+        ))
+        .with_state(state)
+}
@@ -0,0 +1,48 @@
+use axum::Json;
+use axum::extract::{Path, State};
+use axum::http::StatusCode;
+
+use crate::projects::{NewProject, Project};
+use crate::{ApiError, ApiResult, AppState};
+
+pub async fn list(State(state): State<AppState>) -> ApiResult<Json<Vec<Project>>> {
+    Ok(Json(state.projects.list()?))
+}
+
+pub async fn get(
+    State(state): State<AppState>,
+    Path(name): Path<String>,
+) -> ApiResult<Json<Project>> {
+    Ok(Json(state.projects.get(Some(&name))?))
+}
+
+pub async fn register(
+    State(state): State<AppState>,
+    Json(project): Json<NewProject>,
+) -> ApiResult<(StatusCode, Json<Project>)> {
+    // Cloning can take a while
+    let project = tokio::task::spawn_blocking(move || state.projects.register(project))
+        .await
+        .map_err(|e| ApiError::Sheila(sheila::Error::generic(e.to_string())))??;
+    Ok((StatusCode::CREATED, Json(project)))
+}
+
+pub async fn remove(
+    State(state): State<AppState>,
+    Path(name): Path<String>,
+) -> ApiResult<StatusCode> {
+    if state
+        .runs
+        .list(Some(&name))
+        .await
+        .iter()
+        .any(|run| !run.status.is_finished())
+    {
+        return Err(ApiError::Conflict(format!(
+            "Project '{}' has runs that haven't finished",
+            name
+        )));
+    }
+    state.projects.remove(&name)?;
+    Ok(StatusCode::NO_CONTENT)
+}
@@ -0,0 +1,71 @@
+use std::collections::HashSet;
+
+use axum::Json;
+use axum::extract::{Path, Query, State};
+use axum::http::{StatusCode, header};
+use axum::response::IntoResponse;
+use serde::Deserialize;
+use sheila::TestReport;
+use sheila::cache::RunSummary;
+use sheila::runners::RunResult;
+use uuid::Uuid;
+
+use super::ProjectQuery;
+use crate::reports::ReportKind;
+use crate::{ApiError, ApiResult, AppState};
+
+#[derive(Debug, Default, Deserialize)]
+#[serde(default)]
+pub struct FormatQuery {
+    format: ReportKind,
+}
+
+pub async fn list(
+    State(state): State<AppState>,
+    Query(query): Query<ProjectQuery>,
+) -> ApiResult<Json<Vec<RunSummary>>> {
+    let project = query.resolve(&state)?;
+    let runs: HashSet<Uuid> = state.db.project_runs(&project.name)?.into_iter().collect();
+    let mut summaries = state.reports.list()?;
+    summaries.retain(|summary| runs.contains(&summary.id));
+    Ok(Json(summaries))
+}
+
+fn load(state: &AppState, id: Uuid) -> ApiResult<RunResult> {
+    let mut result = state
+        .reports
+        .load(id)?
+        .ok_or_else(|| ApiError::NotFound(format!("No report for run {}", id)))?;
+    state.artifacts.attach(&mut result)?;
+    Ok(result)
+}
+
+pub async fn get(
+    State(state): State<AppState>,
+    Path(id): Path<Uuid>,
+    Query(query): Query<FormatQuery>,
+) -> ApiResult<Json<TestReport>> {
+    Ok(Json(query.format.render(&load(&state, id)?)?))
+}
+
+pub async fn content(
+    State(state): State<AppState>,
+    Path(id): Path<Uuid>,
+    Query(query): Query<FormatQuery>,
+) -> ApiResult<impl IntoResponse> {
+    let report = query.format.render(&load(&state, id)?)?;
+    Ok((
+        [(header::CONTENT_TYPE, query.format.content_type())],
+        report.content,
+    ))
+}
+
+pub async fn delete(State(state): State<AppState>, Path(id): Path<Uuid>) -> ApiResult<StatusCode> {
+    let stored = state.reports.delete(id)?;
+    state.artifacts.delete_run(id)?;
+    if state.db.delete(id)? || stored {
+        Ok(StatusCode::NO_CONTENT)
+    } else {
+        Err(ApiError::NotFound(format!("No report for run {}", id)))
+    }
+}
@@ -0,0 +1,84 @@
+use axum::extract::{Query, State};
+use axum::http::StatusCode;
+use axum::{Extension, Json};
+use serde::Deserialize;
+use serde_json::Value;
+use sheila::TestReport;
+use sheila::cache::RunSummary;
+use sheila::runners::RunResult;
+
+use crate::auth::Caller;
+use crate::{ApiError, ApiResult, AppState};
+
+#[derive(Debug, Default, Deserialize)]
+#[serde(default)]
+pub struct ResultQuery {
+    /// Project the run was of, the server's own if not given
+    project: Option<String>,
+    /// Commit the run was at, instead of the one in the report's environment
+    commit: Option<String>,
+}
+
+/// Store the result of a run carried out elsewhere, given as a `RunResult`
+/// or a `TestReport`. Publishing a run again is a no-op, as long as it's
+/// for the same project and commit.
+pub async fn publish(
+    State(state): State<AppState>,
+    Extension(caller): Extension<Caller>,
+    Query(query): Query<ResultQuery>,
+    Json(body): Json<Value>,
+) -> ApiResult<(StatusCode, Json<RunSummary>)> {
+    let project = state.projects.get(query.project.as_deref())?;
+    caller.check_project(&project.name)?;
+
+    let (result, reported_commit) = if body.get("run_result").is_some() {
+        let report: TestReport = serde_json::from_value(body)
+            .map_err(|e| ApiError::BadRequest(format!("Invalid report: {}", e)))?;
+        let commit = report
+            .metadata
+            .environment
+            .and_then(|environment| environment.git_commit);
+        (RunResult::clone(&report.run_result), commit)
+    } else {
+        let result: RunResult = serde_json::from_value(body)
+            .map_err(|e| ApiError::BadRequest(format!("Invalid run result: {}", e)))?;
+        (result, None)
+    };
+    let commit = query.commit.or(reported_commit);
+    let id = result.id;
+
+    if state.runs.get(id).await.is_some() {
+        return Err(ApiError::Conflict(format!(
+            "Run {} was started by this server",
+            id
+        )));
+    }
+    if let Some(stored_project) = state.db.run_project(id)?
+        && stored_project != project.name
+    {
+        return Err(ApiError::Conflict(format!(
+            "Run {} is already stored for project '{}'",
+            id, stored_project
+        )));
+    }
+    if let Some(stored_commit) = state.db.run_commit(id)? {
+        match (stored_commit, &commit) {
+            (Some(stored), Some(commit)) if stored != *commit => {
+                return Err(ApiError::Conflict(format!(
+                    "Run {} is already stored for commit {}",
+                    id, stored
+                )));
+            }
+            (None, Some(commit)) => state.db.set_run_commit(id, commit)?,
+            _ => {}
+        }
+        return Ok((StatusCode::OK, Json(RunSummary::from(&result))));
+    }
+
+    // Artifacts the result points at are on the machine that ran it, so
+    // they're left to be uploaded separately
+    state.reports.save(&result)?;
+    state.db.record(&result, &project.name, commit.as_deref())?;
+    tracing::info!("Stored published run {}", id);
+    Ok((StatusCode::CREATED, Json(RunSummary::from(&result))))
+}
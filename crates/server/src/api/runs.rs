@@ -0,0 +1,90 @@
+use axum::extract::{Path, Query, State};
+use axum::http::StatusCode;
+use axum::{Extension, Json};
+use serde::Deserialize;
+use sheila::runners::RunResult;
+use uuid::Uuid;
+
+use super::ProjectQuery;
+use crate::auth::Caller;
+use crate::runs::{RunRecord, RunSpec};
+use crate::{ApiError, ApiResult, AppState};
+
+pub async fn list(
+    State(state): State<AppState>,
+    Query(query): Query<ProjectQuery>,
+) -> ApiResult<Json<Vec<RunRecord>>> {
+    let project = query.resolve(&state)?;
+    Ok(Json(state.runs.list(Some(&project.name)).await))
+}
+
+pub async fn start(
+    State(state): State<AppState>,
+    Extension(caller): Extension<Caller>,
+    Json(spec): Json<RunSpec>,
+) -> ApiResult<(StatusCode, Json<RunRecord>)> {
+    caller.check_project(
+        spec.project
+            .as_deref()
+            .unwrap_or(state.projects.default_name()),
+    )?;
+    let record = state.runs.submit(spec).await?;
+    Ok((StatusCode::ACCEPTED, Json(record)))
+}
+
+pub async fn get(
+    State(state): State<AppState>,
+    Path(id): Path<Uuid>,
+) -> ApiResult<Json<RunRecord>> {
+    state
+        .runs
+        .get(id)
+        .await
+        .map(Json)
+        .ok_or_else(|| ApiError::NotFound(format!("No run {}", id)))
+}
+
+pub async fn result(
+    State(state): State<AppState>,
+    Path(id): Path<Uuid>,
+) -> ApiResult<Json<RunResult>> {
+    if let Some(record) = state.runs.get(id).await
+        && !record.status.is_finished()
+    {
+        return Err(ApiError::Conflict(format!("Run {} hasn't finished", id)));
+    }
+    let mut result = state
+        .reports
+        .load(id)?
+        .ok_or_else(|| ApiError::NotFound(format!("No result for run {}", id)))?;
+    state.artifacts.attach(&mut result)?;
+    Ok(Json(result))
+}
+
+pub async fn queue(
+    State(state): State<AppState>,
+    Query(query): Query<ProjectQuery>,
+) -> ApiResult<Json<Vec<RunRecord>>> {
+    let project = query.resolve(&state)?;
+    Ok(Json(state.runs.queue(Some(&project.name)).await))
+}
+
+#[derive(Debug, Deserialize)]
+pub struct Priority {
+    priority: i32,
+}
+
+pub async fn reprioritize(
+    State(state): State<AppState>,
+    Path(id): Path<Uuid>,
+    Json(body): Json<Priority>,
+) -> ApiResult<Json<RunRecord>> {
+    Ok(Json(state.runs.reprioritize(id, body.priority).await?))
+}
+
+pub async fn cancel(
+    State(state): State<AppState>,
+    Path(id): Path<Uuid>,
+) -> ApiResult<Json<RunRecord>> {
+    Ok(Json(state.runs.cancel(id).await?))
+}
@@ -0,0 +1,159 @@
+use axum::extract::{Path, State};
+use axum::http::StatusCode;
+use axum::{Extension, Json};
+use uuid::Uuid;
+
+use crate::auth::{ApiToken, Caller, CreatedToken, NewToken};
+use crate::{ApiError, ApiResult, AppState};
+
+/// The tokens the caller could have created themselves
+pub async fn list(
+    State(state): State<AppState>,
+    Extension(caller): Extension<Caller>,
+) -> ApiResult<Json<Vec<ApiToken>>> {
+    let mut tokens = state.db.tokens()?;
+    tokens.retain(|token| caller.covers(token));
+    Ok(Json(tokens))
+}
+
+pub async fn create(
+    State(state): State<AppState>,
+    Extension(caller): Extension<Caller>,
+    Json(token): Json<NewToken>,
+) -> ApiResult<(StatusCode, Json<CreatedToken>)> {
+    if let Some(own) = &caller.0 {
+        if token.role > own.role {
+            return Err(ApiError::Forbidden(format!(
+                "Token '{}' has the {} role, so can't create {} tokens",
+                own.name, own.role, token.role
+            )));
+        }
+        if !own.projects.is_empty() && token.projects.is_empty() {
+            return Err(ApiError::Forbidden(format!(
+                "Token '{}' is limited to some projects, so can only create tokens that are too",
+                own.name
+            )));
+        }
+        for project in &token.projects {
+            caller.check_project(project)?;
+        }
+    }
+    Ok((StatusCode::CREATED, Json(state.db.create_token(token)?)))
+}
+
+pub async fn revoke(
+    State(state): State<AppState>,
+    Extension(caller): Extension<Caller>,
+    Path(id): Path<Uuid>,
+) -> ApiResult<StatusCode> {
+    // Tokens beyond the caller's reach are as good as missing to them
+    let covered = state
+        .db
+        .tokens()?
+        .iter()
+        .any(|token| token.id == id && caller.covers(token));
+    if covered && state.db.revoke_token(id)? {
+        Ok(StatusCode::NO_CONTENT)
+    } else {
+        Err(ApiError::NotFound(format!("No token {}", id)))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::ServerConfig;
+    use crate::auth::Role;
+    use chrono::Utc;
+
+    fn state() -> AppState {
+        let root = std::env::temp_dir().join(format!("sheila-tokens-{}", Uuid::new_v4()));
+        std::fs::create_dir_all(&root).unwrap();
+        AppState::new(ServerConfig::new(root)).unwrap()
+    }
+
+    /// An admin token limited to `project`
+    fn admin_of(project: &str) -> Caller {
+        Caller(Some(ApiToken {
+            id: Uuid::new_v4(),
+            name: format!("{}-admin", project),
+            role: Role::Admin,
+            projects: vec![project.to_string()],
+            created_at: Utc::now(),
+            last_used: None,
+        }))
+    }
+
+    fn new_token(name: &str, role: Role, projects: &[&str]) -> NewToken {
+        NewToken {
+            name: name.to_string(),
+            role,
+            projects: projects.iter().map(|p| p.to_string()).collect(),
+        }
+    }
+
+    async fn create_as(
+        state: &AppState,
+        caller: Caller,
+        token: NewToken,
+    ) -> ApiResult<(StatusCode, Json<CreatedToken>)> {
+        create(State(state.clone()), Extension(caller), Json(token)).await
+    }
+
+    #[tokio::test]
+    async fn test_scoped_admins_only_create_tokens_within_their_projects() {
+        let state = state();
+        let admin = admin_of("a");
+
+        let unscoped = create_as(&state, admin.clone(), new_token("all", Role::Read, &[])).await;
+        assert!(matches!(unscoped, Err(ApiError::Forbidden(_))));
+        let other = create_as(
+            &state,
+            admin.clone(),
+            new_token("ab", Role::Read, &["a", "b"]),
+        )
+        .await;
+        assert!(matches!(other, Err(ApiError::Forbidden(_))));
+        let own = create_as(&state, admin.clone(), new_token("a", Role::Trigger, &["a"])).await;
+        assert!(own.is_ok());
+
+        let reader = Caller(Some(ApiToken {
+            role: Role::Read,
+            ..admin.0.unwrap()
+        }));
+        let above = create_as(&state, reader, new_token("up", Role::Trigger, &["a"])).await;
+        assert!(matches!(above, Err(ApiError::Forbidden(_))));
+    }
+
+    #[tokio::test]
+    async fn test_scoped_admins_only_see_and_revoke_their_projects_tokens() {
+        let state = state();
+        let unscoped = Caller(None);
+        let (_, Json(own)) =
+            create_as(&state, unscoped.clone(), new_token("a", Role::Read, &["a"]))
+                .await
+                .unwrap();
+        let (_, Json(other)) =
+            create_as(&state, unscoped.clone(), new_token("b", Role::Read, &["b"]))
+                .await
+                .unwrap();
+        let (_, Json(all)) = create_as(&state, unscoped, new_token("all", Role::Read, &[]))
+            .await
+            .unwrap();
+
+        let admin = admin_of("a");
+        let Json(listed) = list(State(state.clone()), Extension(admin.clone()))
+            .await
+            .unwrap();
+        let ids: Vec<Uuid> = listed.iter().map(|token| token.id).collect();
+        assert_eq!(ids, vec![own.token.id]);
+
+        for id in [other.token.id, all.token.id] {
+            let revoked = revoke(State(state.clone()), Extension(admin.clone()), Path(id)).await;
+            assert!(matches!(revoked, Err(ApiError::NotFound(_))));
+        }
+        let revoked = revoke(State(state.clone()), Extension(admin), Path(own.token.id)).await;
+        assert_eq!(revoked.unwrap(), StatusCode::NO_CONTENT);
+        assert_eq!(state.db.tokens().unwrap().len(), 2);
+    }
+}
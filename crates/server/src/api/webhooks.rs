@@ -0,0 +1,127 @@
+use axum::Json;
+use axum::body::Bytes;
+use axum::extract::State;
+use axum::http::{HeaderMap, StatusCode};
+
+use crate::runs::RunRecord;
+use crate::webhooks::{self, RepoEvent};
+use crate::{ApiError, ApiResult, AppState};
+
+pub async fn github(
+    State(state): State<AppState>,
+    headers: HeaderMap,
+    body: Bytes,
+) -> ApiResult<(StatusCode, Json<Vec<RunRecord>>)> {
+    if let Some(secret) = webhook_secret(&state)?
+        && !webhooks::verify_github(secret, &headers, &body)
+    {
+        return Err(ApiError::Unauthorized("Bad webhook signature".into()));
+    }
+    let kind = event_header(&headers, "x-github-event")?;
+    let event = webhooks::github_event(kind, &body)
+        .map_err(|e| ApiError::BadRequest(format!("Bad {} payload: {}", kind, e)))?;
+    Ok((StatusCode::ACCEPTED, Json(trigger(&state, event).await?)))
+}
+
+pub async fn gitlab(
+    State(state): State<AppState>,
+    headers: HeaderMap,
+    body: Bytes,
+) -> ApiResult<(StatusCode, Json<Vec<RunRecord>>)> {
+    if let Some(secret) = webhook_secret(&state)?
+        && !webhooks::verify_gitlab(secret, &headers)
+    {
+        return Err(ApiError::Unauthorized("Bad webhook token".into()));
+    }
+    let kind = event_header(&headers, "x-gitlab-event")?;
+    let event = webhooks::gitlab_event(kind, &body)
+        .map_err(|e| ApiError::BadRequest(format!("Bad {} payload: {}", kind, e)))?;
+    Ok((StatusCode::ACCEPTED, Json(trigger(&state, event).await?)))
+}
+
+/// The secret deliveries must carry. Webhooks can't send API tokens, so
+/// with auth enabled they're only accepted once there is one.
+fn webhook_secret(state: &AppState) -> ApiResult<Option<&str>> {
+    match state.config.webhook_secret.as_deref() {
+        None if state.config.auth => Err(ApiError::Unauthorized(
+            "Webhooks need a secret when auth is enabled".into(),
+        )),
+        secret => Ok(secret),
+    }
+}
+
+fn event_header<'a>(headers: &'a HeaderMap, name: &str) -> ApiResult<&'a str> {
+    headers
+        .get(name)
+        .and_then(|value| value.to_str().ok())
+        .ok_or_else(|| ApiError::BadRequest(format!("Missing {} header", name)))
+}
+
+/// Queue a run for every rule `event` matches, in the project of the
+/// repository it happened in. Events from a repository no project has are
+/// turned away, rather than checking out a foreign commit.
+async fn trigger(state: &AppState, event: Option<RepoEvent>) -> ApiResult<Vec<RunRecord>> {
+    let Some(event) = event else {
+        return Ok(Vec::new());
+    };
+    let project = state
+        .projects
+        .find_repository(&event.repository)?
+        .ok_or_else(|| {
+            ApiError::NotFound(format!(
+                "No project for repository {}",
+                event.repository.first().map_or("(unknown)", String::as_str)
+            ))
+        })?;
+    let rules = webhooks::load_rules(&project.config_file())?;
+
+    let mut runs = Vec::new();
+    for rule in rules.iter().filter(|rule| rule.matches(&event)) {
+        let mut spec = rule.spec.clone();
+        spec.project = Some(project.name.clone());
+        spec.revision = Some(event.commit.clone());
+        let record = state.runs.submit(spec).await?;
+        tracing::info!(
+            "Webhook '{}' of {} queued run {} of {} ({})",
+            rule.name,
+            project.name,
+            record.id,
+            event.commit,
+            event.name
+        );
+        runs.push(record);
+    }
+    Ok(runs)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::ServerConfig;
+    use uuid::Uuid;
+
+    #[tokio::test]
+    async fn test_deliveries_from_unknown_repositories_are_turned_away() {
+        let root = std::env::temp_dir().join(format!("sheila-webhooks-{}", Uuid::new_v4()));
+        std::fs::create_dir_all(&root).unwrap();
+        std::fs::write(
+            root.join("sheila.toml"),
+            "[[server.webhooks]]\nname = \"all\"\n",
+        )
+        .unwrap();
+        let state = AppState::new(ServerConfig::new(&root)).unwrap();
+
+        let mut headers = HeaderMap::new();
+        headers.insert("x-github-event", "push".parse().unwrap());
+        let body = r#"{
+            "ref": "refs/heads/main",
+            "after": "4f2a9c1",
+            "repository": {"clone_url": "https://example.com/someone/else.git"}
+        }"#;
+        let delivered = github(State(state.clone()), headers, Bytes::from(body)).await;
+
+        assert!(matches!(delivered, Err(ApiError::NotFound(_))));
+        assert!(state.runs.list(None).await.is_empty());
+        std::fs::remove_dir_all(&root).unwrap();
+    }
+}
@@ -0,0 +1,264 @@
+//! Files attached to tests, e.g. logs, screenshots or core dumps. They're
+//! either uploaded through the API or attached by the tests themselves, and
+//! kept under the data directory's `artifacts/<run id>` with an index in
+//! the [`Database`], until they outlive the retention period.
+
+use std::collections::HashSet;
+use std::fs;
+use std::path::PathBuf;
+use std::time::Duration;
+
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+use sheila::runners::RunResult;
+use sheila::{Artifact, Result};
+use uuid::Uuid;
+
+use crate::db::Database;
+use crate::{ApiError, ApiResult};
+
+const MIB: u64 = 1024 * 1024;
+
+#[derive(Debug, Clone, Copy)]
+pub struct ArtifactLimits {
+    /// Largest single artifact, in bytes
+    pub max_size: u64,
+    /// Most a run's artifacts may take up together, in bytes
+    pub run_quota: u64,
+    /// How long artifacts are kept, or forever if `None`
+    pub retention: Option<Duration>,
+}
+
+impl Default for ArtifactLimits {
+    fn default() -> Self {
+        Self {
+            max_size: 64 * MIB,
+            run_quota: 512 * MIB,
+            retention: Some(Duration::from_secs(30 * 24 * 60 * 60)),
+        }
+    }
+}
+
+/// An artifact as listed by `GET /runs/{id}/artifacts`
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct StoredArtifact {
+    pub id: Uuid,
+    pub run_id: Uuid,
+    pub test_id: Option<Uuid>,
+    pub test_name: Option<String>,
+    pub name: String,
+    /// Size in bytes
+    pub size: u64,
+    pub created_at: DateTime<Utc>,
+}
+
+impl StoredArtifact {
+    /// Where the server serves the artifact
+    pub fn url(&self) -> String {
+        format!("/artifacts/{}", self.id)
+    }
+
+    /// Content type to serve the artifact with, going by its name. Only
+    /// images and plain text are shown in the browser.
+    pub fn content_type(&self) -> &'static str {
+        let extension = self
+            .name
+            .rsplit_once('.')
+            .map(|(_, extension)| extension.to_lowercase());
+        match extension.as_deref() {
+            Some("png") => "image/png",
+            Some("jpg" | "jpeg") => "image/jpeg",
+            Some("gif") => "image/gif",
+            Some("webp") => "image/webp",
+            Some("svg") => "image/svg+xml",
+            Some("txt" | "log" | "out" | "err" | "json" | "xml" | "csv") => {
+                "text/plain; charset=utf-8"
+            }
+            _ => "application/octet-stream",
+        }
+    }
+}
+
+/// Which test an uploaded artifact belongs to, and what it's called
+#[derive(Debug, Clone, Deserialize)]
+pub struct ArtifactUpload {
+    pub name: String,
+    pub test_id: Option<Uuid>,
+    /// The test's name, if its id isn't known
+    pub test: Option<String>,
+}
+
+#[derive(Clone)]
+pub struct ArtifactStore {
+    dir: PathBuf,
+    db: Database,
+    limits: ArtifactLimits,
+}
+
+impl ArtifactStore {
+    pub fn open<P: Into<PathBuf>>(dir: P, db: Database, limits: ArtifactLimits) -> Result<Self> {
+        let dir = dir.into();
+        fs::create_dir_all(&dir)?;
+        Ok(Self { dir, db, limits })
+    }
+
+    pub fn limits(&self) -> ArtifactLimits {
+        self.limits
+    }
+
+    pub fn path(&self, artifact: &StoredArtifact) -> PathBuf {
+        self.dir
+            .join(artifact.run_id.to_string())
+            .join(artifact.id.to_string())
+    }
+
+    /// Store `content` as an artifact of run `run_id`, within the limits
+    pub fn save(
+        &self,
+        run_id: Uuid,
+        upload: ArtifactUpload,
+        content: &[u8],
+    ) -> ApiResult<StoredArtifact> {
+        let size = content.len() as u64;
+        if size > self.limits.max_size {
+            return Err(ApiError::PayloadTooLarge(format!(
+                "Artifacts can be at most {} bytes",
+                self.limits.max_size
+            )));
+        }
+        let used = self.db.artifacts_size(run_id)?;
+        if used + size > self.limits.run_quota {
+            return Err(ApiError::PayloadTooLarge(format!(
+                "Run {} has {} of its {} bytes of artifacts left",
+                run_id,
+                self.limits.run_quota.saturating_sub(used),
+                self.limits.run_quota
+            )));
+        }
+
+        let artifact = StoredArtifact {
+            id: Uuid::new_v4(),
+            run_id,
+            test_id: upload.test_id,
+            test_name: upload.test,
+            name: upload.name,
+            size,
+            created_at: Utc::now(),
+        };
+        let path = self.path(&artifact);
+        if let Some(parent) = path.parent() {
+            fs::create_dir_all(parent)?;
+        }
+        fs::write(&path, content)?;
+        if let Err(e) = self.db.add_artifact(&artifact) {
+            let _ = fs::remove_file(&path);
+            return Err(e.into());
+        }
+        Ok(artifact)
+    }
+
+    pub fn delete(&self, artifact: &StoredArtifact) -> ApiResult<()> {
+        self.db.delete_artifact(artifact.id)?;
+        let path = self.path(artifact);
+        if let Err(e) = fs::remove_file(&path)
+            && e.kind() != std::io::ErrorKind::NotFound
+        {
+            return Err(e.into());
+        }
+        // Only goes once the run has no artifacts left
+        if let Some(run_dir) = path.parent() {
+            let _ = fs::remove_dir(run_dir);
+        }
+        Ok(())
+    }
+
+    /// Remove every artifact of run `run_id`
+    pub fn delete_run(&self, run_id: Uuid) -> ApiResult<()> {
+        self.db.delete_run_artifacts(run_id)?;
+        let dir = self.dir.join(run_id.to_string());
+        if dir.exists() {
+            fs::remove_dir_all(dir)?;
+        }
+        Ok(())
+    }
+
+    /// Take in the files `result`'s tests attached, pointing the results at
+    /// the stored copies. Files that can't be read here, e.g. ones attached
+    /// on an agent, or that don't fit in the limits are left as they are.
+    pub fn ingest(&self, result: &mut RunResult) {
+        let run_id = result.id;
+        let tests = result
+            .suite_results
+            .iter_mut()
+            .flat_map(|suite_result| suite_result.test_results.iter_mut());
+        for test_result in tests {
+            for artifact in test_result.artifacts.iter_mut() {
+                let Some(path) = artifact.path.as_ref().filter(|_| artifact.text.is_none()) else {
+                    continue;
+                };
+                let Ok(content) = fs::read(path) else {
+                    continue;
+                };
+                let upload = ArtifactUpload {
+                    name: artifact.name.clone(),
+                    test_id: Some(test_result.id),
+                    test: Some(test_result.name.clone()),
+                };
+                match self.save(run_id, upload, &content) {
+                    Ok(stored) => artifact.path = Some(PathBuf::from(stored.url())),
+                    Err(e) => tracing::warn!(
+                        "Failed to store artifact {} of {}: {}",
+                        artifact.name,
+                        test_result.name,
+                        e
+                    ),
+                }
+            }
+        }
+    }
+
+    /// Add the artifacts uploaded for `result`'s run to the tests they
+    /// belong to, so reports link to them
+    pub fn attach(&self, result: &mut RunResult) -> rusqlite::Result<()> {
+        let stored = self.db.run_artifacts(result.id)?;
+        let tests = result
+            .suite_results
+            .iter_mut()
+            .flat_map(|suite_result| suite_result.test_results.iter_mut());
+        for test_result in tests {
+            let attached: HashSet<PathBuf> = test_result
+                .artifacts
+                .iter()
+                .filter_map(|artifact| artifact.path.clone())
+                .collect();
+            for artifact in &stored {
+                let belongs = match artifact.test_id {
+                    Some(test_id) => test_id == test_result.id,
+                    None => artifact.test_name.as_deref() == Some(test_result.name.as_str()),
+                };
+                let url = PathBuf::from(artifact.url());
+                if belongs && !attached.contains(&url) {
+                    test_result.artifacts.push(Artifact {
+                        name: artifact.name.clone(),
+                        path: Some(url),
+                        text: None,
+                    });
+                }
+            }
+        }
+        Ok(())
+    }
+
+    /// Remove artifacts older than the retention period, returning how many
+    pub fn prune(&self) -> ApiResult<usize> {
+        let Some(retention) = self.limits.retention else {
+            return Ok(0);
+        };
+        let cutoff = Utc::now() - retention;
+        let expired = self.db.artifacts_before(cutoff)?;
+        for artifact in &expired {
+            self.delete(artifact)?;
+        }
+        Ok(expired.len())
+    }
+}
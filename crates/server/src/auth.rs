@@ -0,0 +1,426 @@
+//! API tokens. With auth enabled every request needs a token, sent as
+//! `Authorization: Bearer <token>`, a `sheila_token` cookie, or a `token`
+//! query parameter. The last is for opening the dashboard in a browser, and
+//! is swapped for the cookie so the dashboard's own links keep working.
+//! Webhooks are the exception, and check their own secret instead, as are
+//! the health checks, `/healthz` and `/readyz`.
+//!
+//! Tokens limited to some projects can only be used on routes about those
+//! projects: the project of the run, report, artifact or agent in the path,
+//! the `project` query parameter, or the server's own project otherwise.
+//! Listings across projects, like `GET /agents`, leave the others out. An
+//! admin token limited to some projects only sees and manages tokens limited
+//! to those same projects, and can't hand out a role above its own.
+//! `GET /metrics` counts runs of every project, so needs a token that isn't
+//! limited to any.
+
+use axum::extract::{Request, State};
+use axum::http::{HeaderValue, Method, Uri, header};
+use axum::middleware::Next;
+use axum::response::Response;
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+use uuid::Uuid;
+
+use crate::{ApiError, AppState};
+
+const COOKIE: &str = "sheila_token";
+
+/// What a token may do. Each role can do everything the ones before it can.
+#[derive(
+    Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Serialize, Deserialize, clap::ValueEnum,
+)]
+#[serde(rename_all = "lowercase")]
+pub enum Role {
+    /// Read runs, reports, history and the dashboard
+    Read,
+    /// Also start and cancel runs, delete reports, and act as an agent
+    Trigger,
+    /// Also manage tokens and projects
+    Admin,
+}
+
+impl Role {
+    /// The role needed to make a `method` request to `path`
+    pub fn required(method: &Method, path: &str) -> Self {
+        let under = |prefix: &str| path == prefix || path.starts_with(&format!("{}/", prefix));
+        if under("/tokens") {
+            Role::Admin
+        } else if method == Method::GET || method == Method::HEAD {
+            Role::Read
+        } else if under("/projects") {
+            Role::Admin
+        } else {
+            Role::Trigger
+        }
+    }
+}
+
+impl std::fmt::Display for Role {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let name = match self {
+            Role::Read => "read",
+            Role::Trigger => "trigger",
+            Role::Admin => "admin",
+        };
+        f.pad(name)
+    }
+}
+
+/// A token as listed by `GET /tokens`. The secret itself is only shown
+/// when the token is created.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ApiToken {
+    pub id: Uuid,
+    pub name: String,
+    pub role: Role,
+    /// Projects the token may be used for, or every project if empty
+    pub projects: Vec<String>,
+    pub created_at: DateTime<Utc>,
+    pub last_used: Option<DateTime<Utc>>,
+}
+
+impl ApiToken {
+    pub fn allows(&self, project: &str) -> bool {
+        self.projects.is_empty() || self.projects.iter().any(|p| p == project)
+    }
+}
+
+/// The token a request was made with, or `None` with auth disabled. The
+/// middleware adds it to every request, for handlers acting on a project
+/// named in the body.
+#[derive(Debug, Clone)]
+pub struct Caller(pub Option<ApiToken>);
+
+impl Caller {
+    /// Whether the caller may use `project`
+    pub fn allows(&self, project: &str) -> bool {
+        self.0.as_ref().is_none_or(|token| token.allows(project))
+    }
+
+    /// Whether `token` gives no more than the caller has: every project it
+    /// is limited to is one the caller may use, and it's limited to some if
+    /// the caller is
+    pub fn covers(&self, token: &ApiToken) -> bool {
+        match &self.0 {
+            None => true,
+            Some(caller) if caller.projects.is_empty() => true,
+            Some(caller) => {
+                !token.projects.is_empty() && token.projects.iter().all(|p| caller.allows(p))
+            }
+        }
+    }
+
+    /// Fail unless the caller may use `project`
+    pub fn check_project(&self, project: &str) -> Result<(), ApiError> {
+        match &self.0 {
+            Some(token) if !token.allows(project) => Err(ApiError::Forbidden(format!(
+                "Token '{}' can't be used for project '{}'",
+                token.name, project
+            ))),
+            _ => Ok(()),
+        }
+    }
+}
+
+/// A token to create, as posted to `POST /tokens`
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct NewToken {
+    pub name: String,
+    pub role: Role,
+    #[serde(default)]
+    pub projects: Vec<String>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct CreatedToken {
+    #[serde(flatten)]
+    pub token: ApiToken,
+    pub secret: String,
+}
+
+pub fn generate_secret() -> String {
+    format!(
+        "sheila_{}{}",
+        Uuid::new_v4().simple(),
+        Uuid::new_v4().simple()
+    )
+}
+
+/// Secrets are only ever stored hashed
+pub fn hash_secret(secret: &str) -> String {
+    Sha256::digest(secret.as_bytes())
+        .iter()
+        .map(|byte| format!("{:02x}", byte))
+        .collect()
+}
+
+/// Middleware rejecting requests without a token allowed to make them, when
+/// auth is enabled
+pub async fn authorize(
+    State(state): State<AppState>,
+    mut request: Request,
+    next: Next,
+) -> Result<Response, ApiError> {
+    if !state.config.auth || exempt(request.uri().path()) {
+        request.extensions_mut().insert(Caller(None));
+        return Ok(next.run(request).await);
+    }
+
+    let (secret, from_query) = request_secret(&request).unzip();
+    let required = Role::required(request.method(), request.uri().path());
+    let caller = Caller(Some(check(&state, secret.as_deref(), required)?));
+    let (method, uri) = (request.method().clone(), request.uri().clone());
+    if let Some(project) = request_project(&state, &method, &uri).await? {
+        caller.check_project(&project)?;
+    }
+    if uri.path() == "/metrics"
+        && let Some(token) = caller.0.as_ref().filter(|token| !token.projects.is_empty())
+    {
+        return Err(ApiError::Forbidden(format!(
+            "Token '{}' is limited to some projects, metrics cover them all",
+            token.name
+        )));
+    }
+    request.extensions_mut().insert(caller);
+
+    let mut response = next.run(request).await;
+    if from_query == Some(true)
+        && let Some(secret) = secret
+        && let Ok(cookie) = HeaderValue::from_str(&format!(
+            "{}={}; Path=/; HttpOnly; SameSite=Strict",
+            COOKIE, secret
+        ))
+    {
+        response.headers_mut().append(header::SET_COOKIE, cookie);
+    }
+    Ok(response)
+}
+
+/// Whether requests to `path` go without a token
+fn exempt(path: &str) -> bool {
+    path.starts_with("/webhooks/") || path == "/healthz" || path == "/readyz"
+}
+
+/// The token with secret `secret`, if it has at least the `required` role
+pub fn check(state: &AppState, secret: Option<&str>, required: Role) -> Result<ApiToken, ApiError> {
+    let secret = secret.ok_or_else(|| ApiError::Unauthorized("An API token is required".into()))?;
+    let token = state
+        .db
+        .authenticate(secret)?
+        .ok_or_else(|| ApiError::Unauthorized("Unknown API token".into()))?;
+
+    if token.role < required {
+        return Err(ApiError::Forbidden(format!(
+            "Token '{}' has the {} role, this needs {}",
+            token.name, token.role, required
+        )));
+    }
+    Ok(token)
+}
+
+/// The project a request is about, if it's about one: the project of the
+/// run, report or artifact in its path, the one in its query, or the
+/// server's own for reads that default to it
+async fn request_project(
+    state: &AppState,
+    method: &Method,
+    uri: &Uri,
+) -> Result<Option<String>, ApiError> {
+    let path = uri.path();
+    let segments: Vec<&str> = path.trim_matches('/').split('/').collect();
+    let run_id = match segments.as_slice() {
+        ["runs" | "reports", id, ..] => Uuid::parse_str(id).ok(),
+        ["artifacts", id] => match Uuid::parse_str(id) {
+            Ok(id) => state.db.artifact(id)?.map(|artifact| artifact.run_id),
+            Err(_) => None,
+        },
+        ["projects", name] => return Ok(Some(name.to_string())),
+        _ => None,
+    };
+    if let Some(run_id) = run_id {
+        // Unknown runs are left for the handler to answer
+        return state.run_project(run_id).await;
+    }
+
+    let query: Vec<(String, String)> =
+        serde_urlencoded::from_str(uri.query().unwrap_or_default()).unwrap_or_default();
+    if let Some((_, project)) = query.into_iter().find(|(name, _)| name == "project") {
+        return Ok(Some(project));
+    }
+    // Routes taking a project in their body check it themselves
+    let read = method == Method::GET || method == Method::HEAD;
+    let scoped = matches!(
+        segments.as_slice(),
+        [""] | ["runs" | "queue" | "reports" | "history" | "dashboard", ..]
+    );
+    Ok((read && scoped).then(|| state.projects.default_name().to_string()))
+}
+
+/// The token a request carries, and whether it came from the query string
+fn request_secret(request: &Request) -> Option<(String, bool)> {
+    let headers = request.headers();
+    if let Some(bearer) = headers
+        .get(header::AUTHORIZATION)
+        .and_then(|value| value.to_str().ok())
+        .and_then(|value| value.strip_prefix("Bearer "))
+    {
+        return Some((bearer.trim().to_string(), false));
+    }
+
+    let cookie = headers
+        .get_all(header::COOKIE)
+        .iter()
+        .filter_map(|value| value.to_str().ok())
+        .flat_map(|value| value.split(';'))
+        .find_map(|pair| {
+            let (name, value) = pair.trim().split_once('=')?;
+            (name == COOKIE).then(|| value.to_string())
+        });
+    if let Some(cookie) = cookie {
+        return Some((cookie, false));
+    }
+
+    let query: Vec<(String, String)> =
+        serde_urlencoded::from_str(request.uri().query().unwrap_or_default()).ok()?;
+    query
+        .into_iter()
+        .find(|(name, _)| name == "token")
+        .map(|(_, secret)| (secret, true))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::ServerConfig;
+    use crate::artifacts::ArtifactUpload;
+    use axum::body::Body;
+    use sheila::RunnerConfig;
+    use sheila::runners::RunResult;
+
+    fn state() -> AppState {
+        let root = std::env::temp_dir().join(format!("sheila-auth-{}", Uuid::new_v4()));
+        std::fs::create_dir_all(&root).unwrap();
+        AppState::new(ServerConfig::new(root)).unwrap()
+    }
+
+    fn request(uri: &str, headers: &[(header::HeaderName, &str)]) -> Request {
+        let mut builder = Request::builder().uri(uri);
+        for (name, value) in headers {
+            builder = builder.header(name, *value);
+        }
+        builder.body(Body::empty()).unwrap()
+    }
+
+    #[test]
+    fn test_roles_required_by_method_and_path() {
+        let cases = [
+            (Method::GET, "/runs", Role::Read),
+            (Method::HEAD, "/reports/1", Role::Read),
+            (Method::POST, "/runs", Role::Trigger),
+            (Method::DELETE, "/reports/1", Role::Trigger),
+            (Method::POST, "/agents/1/poll", Role::Trigger),
+            (Method::GET, "/tokens", Role::Admin),
+            (Method::DELETE, "/tokens/1", Role::Admin),
+            (Method::GET, "/projects", Role::Read),
+            (Method::POST, "/projects", Role::Admin),
+            (Method::DELETE, "/projects/app", Role::Admin),
+            (Method::POST, "/tokensmith", Role::Trigger),
+        ];
+        for (method, path, role) in cases {
+            assert_eq!(Role::required(&method, path), role, "{} {}", method, path);
+        }
+    }
+
+    #[test]
+    fn test_webhooks_and_health_checks_go_without_a_token() {
+        for path in [
+            "/webhooks/github",
+            "/webhooks/gitlab",
+            "/healthz",
+            "/readyz",
+        ] {
+            assert!(exempt(path), "{}", path);
+        }
+        for path in ["/webhooks", "/metrics", "/runs", "/healthz/x", "/"] {
+            assert!(!exempt(path), "{}", path);
+        }
+    }
+
+    #[test]
+    fn test_token_is_taken_from_header_then_cookie_then_query() {
+        let bearer = (header::AUTHORIZATION, "Bearer from-header");
+        let cookie = (header::COOKIE, "theme=dark; sheila_token=from-cookie");
+
+        let secret = |request: Request| request_secret(&request);
+        assert_eq!(
+            secret(request(
+                "/runs?token=from-query",
+                &[bearer.clone(), cookie.clone()]
+            )),
+            Some(("from-header".to_string(), false))
+        );
+        assert_eq!(
+            secret(request("/runs?token=from-query", &[cookie])),
+            Some(("from-cookie".to_string(), false))
+        );
+        assert_eq!(
+            secret(request("/runs?token=from-query", &[])),
+            Some(("from-query".to_string(), true))
+        );
+        assert_eq!(secret(request("/runs", &[])), None);
+    }
+
+    #[tokio::test]
+    async fn test_project_is_resolved_from_ids_and_query() {
+        let state = state();
+        let mut result = RunResult::new(RunnerConfig::default());
+        result.id = Uuid::new_v4();
+        state.db.record(&result, "app", None).unwrap();
+        let upload = ArtifactUpload {
+            name: "log.txt".to_string(),
+            test_id: None,
+            test: None,
+        };
+        let artifact = state.artifacts.save(result.id, upload, b"log").unwrap();
+
+        let project = |method: Method, uri: String| {
+            let state = state.clone();
+            async move {
+                let uri: Uri = uri.parse().unwrap();
+                request_project(&state, &method, &uri).await.unwrap()
+            }
+        };
+        let app = Some("app".to_string());
+        assert_eq!(
+            project(Method::GET, format!("/runs/{}", result.id)).await,
+            app
+        );
+        assert_eq!(
+            project(Method::DELETE, format!("/reports/{}", result.id)).await,
+            app
+        );
+        assert_eq!(
+            project(Method::GET, format!("/artifacts/{}", artifact.id)).await,
+            app
+        );
+        assert_eq!(
+            project(Method::GET, "/history?project=lib".into()).await,
+            Some("lib".into())
+        );
+        assert_eq!(
+            project(Method::DELETE, "/projects/lib".into()).await,
+            Some("lib".into())
+        );
+
+        let default = Some(state.projects.default_name().to_string());
+        assert_eq!(project(Method::GET, "/runs".into()).await, default);
+        assert_eq!(project(Method::POST, "/runs".into()).await, None);
+        assert_eq!(project(Method::GET, "/agents".into()).await, None);
+        assert_eq!(
+            project(Method::GET, format!("/runs/{}", Uuid::new_v4())).await,
+            None
+        );
+    }
+}
@@ -0,0 +1,70 @@
+use std::net::{Ipv4Addr, SocketAddr};
+use std::path::{Path, PathBuf};
+
+use crate::artifacts::ArtifactLimits;
+
+/// Port the API listens on unless told otherwise
+pub const DEFAULT_PORT: u16 = 7878;
+
+#[derive(Debug, Clone)]
+pub struct ServerConfig {
+    /// Address the API listens on
+    pub bind: SocketAddr,
+    /// Address the gRPC API listens on, if it's served
+    pub grpc_bind: Option<SocketAddr>,
+    /// Cargo project whose tests are run, unless a run names another
+    pub project_root: PathBuf,
+    /// Name the project is registered under, the project directory's by
+    /// default
+    pub project_name: String,
+    /// Where the server keeps what it stores, e.g. reports
+    pub data_dir: PathBuf,
+    /// Runs carried out at the same time, with the rest queued
+    pub max_concurrent_runs: usize,
+    /// Require an API token for every request
+    pub auth: bool,
+    /// Secret webhook deliveries must be signed with, or carry as their
+    /// token
+    pub webhook_secret: Option<String>,
+    /// Size limits and retention of stored artifacts
+    pub artifact_limits: ArtifactLimits,
+}
+
+impl ServerConfig {
+    /// Serve `project_root` on localhost, storing data in its `.sheila`
+    /// directory
+    pub fn new<P: AsRef<Path>>(project_root: P) -> Self {
+        let project_root = project_root.as_ref().to_path_buf();
+        Self {
+            bind: SocketAddr::from((Ipv4Addr::LOCALHOST, DEFAULT_PORT)),
+            grpc_bind: None,
+            data_dir: project_root.join(".sheila").join("server"),
+            project_name: project_root
+                .file_name()
+                .map(|name| name.to_string_lossy().to_string())
+                .unwrap_or_else(|| "default".to_string()),
+            project_root,
+            max_concurrent_runs: 1,
+            auth: false,
+            webhook_secret: None,
+            artifact_limits: ArtifactLimits::default(),
+        }
+    }
+
+    pub fn reports_dir(&self) -> PathBuf {
+        self.data_dir.join("reports")
+    }
+
+    pub fn artifacts_dir(&self) -> PathBuf {
+        self.data_dir.join("artifacts")
+    }
+
+    /// Where projects registered through the API are cloned to
+    pub fn projects_dir(&self) -> PathBuf {
+        self.data_dir.join("projects")
+    }
+
+    pub fn database_path(&self) -> PathBuf {
+        self.data_dir.join("sheilad.db")
+    }
+}
@@ -0,0 +1,328 @@
+//! The dashboard served at `/dashboard`: server-rendered pages built from
+//! the [`Database`](crate::db::Database), so they need no JavaScript.
+
+use html_escape::encode_text;
+use sheila::TestStatus;
+use sheila::reporting::TrendReporter;
+
+use crate::artifacts::StoredArtifact;
+use crate::db::{FlakyTest, SlowTest, TestRecord, TrendPoint};
+use crate::projects::Project;
+use crate::runs::{RunRecord, RunStatus};
+
+const STYLE: &str = r#"<style>
+    body { font-family: 'Segoe UI', Tahoma, Geneva, Verdana, sans-serif; margin: 20px; background: #f8f9fa; }
+    a { color: #007bff; text-decoration: none; }
+    .cards { display: flex; gap: 15px; flex-wrap: wrap; }
+    .card { background: white; border-radius: 8px; padding: 15px 20px; margin-bottom: 15px; box-shadow: 0 2px 4px rgba(0,0,0,0.1); }
+    .card-title { font-weight: bold; margin-bottom: 8px; }
+    .card-value { font-size: 1.4em; margin-bottom: 8px; }
+    table { border-collapse: collapse; background: white; width: 100%; margin-bottom: 20px; }
+    th, td { padding: 6px 12px; border-bottom: 1px solid #eee; text-align: right; }
+    th:first-child, td:first-child { text-align: left; }
+    .passed { color: #28a745; } .failed { color: #dc3545; } .other { color: #6c757d; }
+    .error { font-family: monospace; white-space: pre-wrap; text-align: left; color: #dc3545; }
+    .projects a { margin-right: 12px; } .projects .current { font-weight: bold; color: inherit; }
+</style>"#;
+
+/// What the dashboard's front page shows
+pub struct Overview<'a> {
+    pub project: &'a Project,
+    /// Every project, to switch between
+    pub projects: &'a [Project],
+    /// Queued and running runs
+    pub active: &'a [RunRecord],
+    /// Recent runs, oldest first
+    pub trends: &'a [TrendPoint],
+    pub flaky: &'a [FlakyTest],
+    pub slowest: &'a [SlowTest],
+}
+
+pub fn overview(overview: &Overview) -> String {
+    let project = overview.project;
+    let mut html = page_start(&format!("{} - Sheila dashboard", project.name));
+    html.push_str(&format!(
+        "<h1>{}</h1>\n<p>{}</p>\n",
+        encode_text(&project.name),
+        encode_text(
+            project
+                .repo_url
+                .as_deref()
+                .unwrap_or(&project.root.to_string_lossy())
+        )
+    ));
+    if overview.projects.len() > 1 {
+        html.push_str("<p class=\"projects\">Projects: ");
+        for other in overview.projects {
+            let class = if other.name == project.name {
+                " class=\"current\""
+            } else {
+                ""
+            };
+            html.push_str(&format!(
+                "<a href=\"/dashboard?{}\"{}>{}</a>",
+                html_escape::encode_double_quoted_attribute(&project_query(&other.name)),
+                class,
+                encode_text(&other.name)
+            ));
+        }
+        html.push_str("</p>\n");
+    }
+
+    let trends = overview.trends;
+    if let Some(latest) = trends.last() {
+        let pass_rates: Vec<f64> = trends.iter().map(pass_rate).collect();
+        let durations: Vec<f64> = trends
+            .iter()
+            .map(|run| run.duration_ms.unwrap_or_default() as f64 / 1000.0)
+            .collect();
+        let flaky: Vec<f64> = trends.iter().map(|run| run.flaky_tests as f64).collect();
+
+        html.push_str("<div class=\"cards\">\n");
+        for (title, value, values, color) in [
+            (
+                "Pass rate",
+                format!("{:.1}%", pass_rate(latest) * 100.0),
+                &pass_rates,
+                "#28a745",
+            ),
+            (
+                "Duration",
+                format_ms(latest.duration_ms),
+                &durations,
+                "#007bff",
+            ),
+            (
+                "Flaky tests",
+                latest.flaky_tests.to_string(),
+                &flaky,
+                "#ffc107",
+            ),
+        ] {
+            html.push_str(&format!(
+                "<div class=\"card\"><div class=\"card-title\">{}</div><div class=\"card-value\">{}</div>{}</div>\n",
+                title,
+                value,
+                TrendReporter::svg_sparkline(values, color)
+            ));
+        }
+        html.push_str("</div>\n");
+    }
+
+    if !overview.active.is_empty() {
+        html.push_str("<h2>Active runs</h2>\n<table>\n<tr><th>Run</th><th>Status</th><th>Priority</th><th>Submitted</th></tr>\n");
+        for run in overview.active {
+            let status = match run.status {
+                RunStatus::Queued => "queued",
+                _ => "running",
+            };
+            html.push_str(&format!(
+                "<tr><td>{}</td><td>{}</td><td>{}</td><td>{}</td></tr>\n",
+                run.id,
+                status,
+                run.spec.priority,
+                run.created_at.format("%Y-%m-%d %H:%M:%S")
+            ));
+        }
+        html.push_str("</table>\n");
+    }
+
+    html.push_str("<h2>Recent runs</h2>\n");
+    if trends.is_empty() {
+        html.push_str("<p>No runs yet.</p>\n");
+    } else {
+        html.push_str("<table>\n<tr><th>Run</th><th>Commit</th><th>Tests</th><th>Passed</th><th>Failed</th><th>Flaky</th><th>Pass rate</th><th>Duration</th></tr>\n");
+        for run in trends.iter().rev() {
+            let commit: String = run
+                .commit
+                .as_deref()
+                .unwrap_or_default()
+                .chars()
+                .take(10)
+                .collect();
+            html.push_str(&format!(
+                "<tr><td><a href=\"/reports/{}/content?format=html\">{}</a></td><td>{}</td><td>{}</td><td>{}</td><td>{}</td><td>{}</td><td>{:.1}%</td><td>{}</td></tr>\n",
+                run.run_id,
+                run.start_time.format("%Y-%m-%d %H:%M:%S"),
+                encode_text(&commit),
+                run.total_tests,
+                run.passed_tests,
+                run.failed_tests,
+                run.flaky_tests,
+                pass_rate(run) * 100.0,
+                format_ms(run.duration_ms)
+            ));
+        }
+        html.push_str("</table>\n");
+    }
+
+    if !overview.flaky.is_empty() {
+        html.push_str("<h2>Flaky tests</h2>\n<table>\n<tr><th>Test</th><th>Runs</th><th>Passed</th><th>Failed</th><th>Passed on retry</th><th>Flakiness</th></tr>\n");
+        for test in overview.flaky {
+            html.push_str(&format!(
+                "<tr><td>{}</td><td>{}</td><td>{}</td><td>{}</td><td>{}</td><td>{:.0}%</td></tr>\n",
+                test_link(&project.name, &test.suite, &test.name),
+                test.runs,
+                test.passed,
+                test.failed,
+                test.flaky,
+                test.flakiness() * 100.0
+            ));
+        }
+        html.push_str("</table>\n");
+    }
+
+    if !overview.slowest.is_empty() {
+        html.push_str("<h2>Slowest tests</h2>\n<table>\n<tr><th>Test</th><th>Runs</th><th>Mean</th><th>Max</th></tr>\n");
+        for test in overview.slowest {
+            html.push_str(&format!(
+                "<tr><td>{}</td><td>{}</td><td>{}</td><td>{}</td></tr>\n",
+                test_link(&project.name, &test.suite, &test.name),
+                test.runs,
+                format_ms(Some(test.mean_ms.round() as u64)),
+                format_ms(Some(test.max_ms))
+            ));
+        }
+        html.push_str("</table>\n");
+    }
+
+    html.push_str("</body>\n</html>\n");
+    html
+}
+
+/// The history page of `project`'s test `name` in `suite`, from its
+/// results newest first, with links to the artifacts it left in each run
+pub fn test_history(
+    project: &str,
+    suite: Option<&str>,
+    name: &str,
+    history: &[TestRecord],
+    artifacts: &[StoredArtifact],
+) -> String {
+    let mut html = page_start(name);
+    html.push_str(&format!(
+        "<p><a href=\"/dashboard?{}\">&larr; {}</a></p>\n",
+        html_escape::encode_double_quoted_attribute(&project_query(project)),
+        encode_text(project)
+    ));
+    html.push_str(&format!("<h1>{}</h1>\n", encode_text(name)));
+    if let Some(suite) = suite {
+        html.push_str(&format!("<p>Suite {}</p>\n", encode_text(suite)));
+    }
+
+    if history.is_empty() {
+        html.push_str("<p>No results for this test.</p>\n</body>\n</html>\n");
+        return html;
+    }
+
+    let passed = history
+        .iter()
+        .filter(|record| record.status == TestStatus::Passed && !record.flaky)
+        .count();
+    let durations: Vec<f64> = history
+        .iter()
+        .rev()
+        .map(|record| record.duration_ms.unwrap_or_default() as f64)
+        .collect();
+    html.push_str("<div class=\"cards\">\n");
+    html.push_str(&format!(
+        "<div class=\"card\"><div class=\"card-title\">Passed first time</div><div class=\"card-value\">{}/{}</div></div>\n",
+        passed,
+        history.len()
+    ));
+    html.push_str(&format!(
+        "<div class=\"card\"><div class=\"card-title\">Duration</div><div class=\"card-value\">{}</div>{}</div>\n",
+        format_ms(history[0].duration_ms),
+        TrendReporter::svg_sparkline(&durations, "#007bff")
+    ));
+    html.push_str("</div>\n");
+
+    html.push_str("<table>\n<tr><th>Run</th><th>Suite</th><th>Status</th><th>Duration</th><th>Retries</th></tr>\n");
+    for record in history {
+        let class = match record.status {
+            TestStatus::Passed if record.flaky => "other",
+            TestStatus::Passed => "passed",
+            TestStatus::Failed | TestStatus::Timeout => "failed",
+            _ => "other",
+        };
+        let status = if record.flaky {
+            format!("{} (flaky)", record.status)
+        } else {
+            record.status.to_string()
+        };
+        html.push_str(&format!(
+            "<tr><td><a href=\"/reports/{}/content?format=html\">{}</a></td><td>{}</td><td class=\"{}\">{}</td><td>{}</td><td>{}</td></tr>\n",
+            record.run_id,
+            record.start_time.format("%Y-%m-%d %H:%M:%S"),
+            encode_text(&record.suite),
+            class,
+            status,
+            format_ms(record.duration_ms),
+            record.retry_count
+        ));
+        if let Some(error) = &record.error {
+            html.push_str(&format!(
+                "<tr><td colspan=\"5\" class=\"error\">{}</td></tr>\n",
+                encode_text(error)
+            ));
+        }
+        let links: Vec<String> = artifacts
+            .iter()
+            .filter(|artifact| artifact.run_id == record.run_id)
+            .map(|artifact| {
+                format!(
+                    "<a href=\"{}\">{}</a>",
+                    artifact.url(),
+                    encode_text(&artifact.name)
+                )
+            })
+            .collect();
+        if !links.is_empty() {
+            html.push_str(&format!(
+                "<tr><td colspan=\"5\">Artifacts: {}</td></tr>\n",
+                links.join(", ")
+            ));
+        }
+    }
+    html.push_str("</table>\n</body>\n</html>\n");
+    html
+}
+
+fn page_start(title: &str) -> String {
+    format!(
+        "<!DOCTYPE html>\n<html>\n<head>\n<meta charset=\"utf-8\">\n<title>{}</title>\n{}\n</head>\n<body>\n",
+        encode_text(title),
+        STYLE
+    )
+}
+
+fn project_query(project: &str) -> String {
+    serde_urlencoded::to_string([("project", project)]).unwrap_or_default()
+}
+
+fn test_link(project: &str, suite: &str, name: &str) -> String {
+    let query =
+        serde_urlencoded::to_string([("project", project), ("name", name), ("suite", suite)])
+            .unwrap_or_default();
+    format!(
+        "<a href=\"/dashboard/tests?{}\">{}</a>",
+        html_escape::encode_double_quoted_attribute(&query),
+        encode_text(name)
+    )
+}
+
+fn pass_rate(run: &TrendPoint) -> f64 {
+    let ran = run.passed_tests + run.failed_tests;
+    if ran == 0 {
+        return 1.0;
+    }
+    run.passed_tests as f64 / ran as f64
+}
+
+fn format_ms(ms: Option<u64>) -> String {
+    match ms {
+        Some(ms) if ms >= 1000 => format!("{:.2}s", ms as f64 / 1000.0),
+        Some(ms) => format!("{}ms", ms),
+        None => "-".to_string(),
+    }
+}
@@ -0,0 +1,760 @@
+use std::path::{Path, PathBuf};
+use std::sync::{Arc, Mutex, MutexGuard};
+
+use chrono::{DateTime, Utc};
+use rusqlite::{Connection, OptionalExtension, params};
+use serde::{Deserialize, Serialize};
+use sheila::TestStatus;
+use sheila::runners::RunResult;
+use uuid::Uuid;
+
+use crate::artifacts::StoredArtifact;
+use crate::auth::{ApiToken, CreatedToken, NewToken, Role, generate_secret, hash_secret};
+use crate::projects::Project;
+
+/// Schema changes, applied in order. A database's `user_version` is the
+/// number of them it has had applied, so new ones only ever go at the end.
+const MIGRATIONS: &[&str] = &[
+    r#"
+    CREATE TABLE runs (
+        id TEXT PRIMARY KEY,
+        start_time TEXT NOT NULL,
+        duration_ms INTEGER,
+        total_tests INTEGER NOT NULL,
+        passed_tests INTEGER NOT NULL,
+        failed_tests INTEGER NOT NULL,
+        skipped_tests INTEGER NOT NULL,
+        flaky_tests INTEGER NOT NULL
+    );
+    CREATE INDEX runs_by_start_time ON runs (start_time);
+
+    CREATE TABLE suites (
+        id TEXT PRIMARY KEY,
+        run_id TEXT NOT NULL REFERENCES runs (id) ON DELETE CASCADE,
+        name TEXT NOT NULL,
+        duration_ms INTEGER,
+        total_tests INTEGER NOT NULL,
+        passed_tests INTEGER NOT NULL,
+        failed_tests INTEGER NOT NULL,
+        skipped_tests INTEGER NOT NULL
+    );
+    CREATE INDEX suites_by_run ON suites (run_id);
+
+    CREATE TABLE tests (
+        run_id TEXT NOT NULL REFERENCES runs (id) ON DELETE CASCADE,
+        suite TEXT NOT NULL,
+        name TEXT NOT NULL,
+        status TEXT NOT NULL,
+        duration_ms INTEGER,
+        retry_count INTEGER NOT NULL,
+        flaky INTEGER NOT NULL,
+        error TEXT
+    );
+    CREATE INDEX tests_by_run ON tests (run_id);
+    CREATE INDEX tests_by_name ON tests (name, suite);
+"#,
+    r#"
+    CREATE TABLE tokens (
+        id TEXT PRIMARY KEY,
+        name TEXT NOT NULL,
+        secret_hash TEXT NOT NULL UNIQUE,
+        role TEXT NOT NULL,
+        projects TEXT NOT NULL,
+        created_at TEXT NOT NULL,
+        last_used TEXT
+    );
+"#,
+    r#"
+    CREATE TABLE artifacts (
+        id TEXT PRIMARY KEY,
+        run_id TEXT NOT NULL,
+        test_id TEXT,
+        test_name TEXT,
+        name TEXT NOT NULL,
+        size INTEGER NOT NULL,
+        created_at TEXT NOT NULL
+    );
+    CREATE INDEX artifacts_by_run ON artifacts (run_id);
+    CREATE INDEX artifacts_by_test_name ON artifacts (test_name);
+    CREATE INDEX artifacts_by_created_at ON artifacts (created_at);
+"#,
+    r#"
+    ALTER TABLE runs ADD COLUMN commit_sha TEXT;
+"#,
+    r#"
+    CREATE TABLE projects (
+        name TEXT PRIMARY KEY,
+        repo_url TEXT,
+        default_branch TEXT NOT NULL,
+        config_path TEXT NOT NULL,
+        root TEXT NOT NULL,
+        cloned INTEGER NOT NULL,
+        created_at TEXT NOT NULL
+    );
+    ALTER TABLE runs ADD COLUMN project TEXT;
+    CREATE INDEX runs_by_project ON runs (project, start_time);
+"#,
+    r#"
+    ALTER TABLE tests ADD COLUMN test_id TEXT;
+    CREATE INDEX tests_by_test_id ON tests (test_id);
+"#,
+];
+
+/// What tests are grouped by across runs: their stable id, or their suite
+/// and name for tests stored before they had one
+const TEST_IDENTITY: &str = "COALESCE(test_id, suite || char(0) || name)";
+
+/// Statuses stored for tests that count as failures
+const FAILED: &str = "('failed', 'timeout')";
+
+/// Every finished run broken down into its suites and tests, for the
+/// history, flakiness and trend queries. Full results stay in the
+/// [`ReportStore`](crate::reports::ReportStore).
+#[derive(Clone)]
+pub struct Database {
+    conn: Arc<Mutex<Connection>>,
+}
+
+impl Database {
+    /// Open the database at `path`, creating it and bringing its schema up
+    /// to date as needed
+    pub fn open<P: AsRef<Path>>(path: P) -> sheila::Result<Self> {
+        let path = path.as_ref();
+        if let Some(parent) = path.parent() {
+            std::fs::create_dir_all(parent)?;
+        }
+        let open = || -> rusqlite::Result<Connection> {
+            let mut conn = Connection::open(path)?;
+            conn.pragma_update(None, "foreign_keys", true)?;
+            migrate(&mut conn)?;
+            Ok(conn)
+        };
+        let conn = open()
+            .map_err(|e| sheila::Error::io(format!("Failed to open {}: {}", path.display(), e)))?;
+        Ok(Self {
+            conn: Arc::new(Mutex::new(conn)),
+        })
+    }
+
+    /// Check the database can be queried
+    pub fn ping(&self) -> rusqlite::Result<()> {
+        self.conn().query_row("SELECT 1", [], |_| Ok(()))
+    }
+
+    fn conn(&self) -> MutexGuard<'_, Connection> {
+        self.conn.lock().unwrap_or_else(|e| e.into_inner())
+    }
+
+    /// Store `result`, a run of `project` at `commit` if known, replacing
+    /// anything stored for a run with the same id
+    pub fn record(
+        &self,
+        result: &RunResult,
+        project: &str,
+        commit: Option<&str>,
+    ) -> rusqlite::Result<()> {
+        let mut conn = self.conn();
+        let tx = conn.transaction()?;
+        let run_id = result.id.to_string();
+        tx.execute("DELETE FROM runs WHERE id = ?1", [&run_id])?;
+        tx.execute(
+            "INSERT INTO runs (id, start_time, duration_ms, total_tests, passed_tests,
+                failed_tests, skipped_tests, flaky_tests, commit_sha, project)
+             VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7, ?8, ?9, ?10)",
+            params![
+                run_id,
+                result.start_time,
+                result.duration.map(|d| d.as_millis() as i64),
+                result.total_tests,
+                result.passed_tests,
+                result.failed_tests,
+                result.skipped_tests,
+                result.flaky_tests,
+                commit,
+                project,
+            ],
+        )?;
+
+        {
+            let mut insert_suite = tx.prepare(
+                "INSERT OR REPLACE INTO suites (id, run_id, name, duration_ms, total_tests,
+                    passed_tests, failed_tests, skipped_tests)
+                 VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7, ?8)",
+            )?;
+            let mut insert_test = tx.prepare(
+                "INSERT INTO tests (run_id, suite, name, status, duration_ms, retry_count,
+                    flaky, error, test_id)
+                 VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7, ?8, ?9)",
+            )?;
+            for suite in &result.suite_results {
+                insert_suite.execute(params![
+                    suite.id.to_string(),
+                    run_id,
+                    suite.name,
+                    suite.duration.map(|d| d.as_millis() as i64),
+                    suite.total_tests,
+                    suite.passed_tests,
+                    suite.failed_tests,
+                    suite.skipped_tests,
+                ])?;
+                for test in &suite.test_results {
+                    insert_test.execute(params![
+                        run_id,
+                        suite.name,
+                        test.name,
+                        test.status.to_string(),
+                        test.duration.map(|d| d.as_millis() as i64),
+                        test.retry_count,
+                        test.flaky,
+                        test.error.as_ref().map(|e| e.message().to_string()),
+                        (!test.stable_id.is_nil()).then(|| test.stable_id.to_string()),
+                    ])?;
+                }
+            }
+        }
+
+        tx.commit()
+    }
+
+    /// Remove run `id`, returning whether it was stored
+    pub fn delete(&self, id: Uuid) -> rusqlite::Result<bool> {
+        Ok(self
+            .conn()
+            .execute("DELETE FROM runs WHERE id = ?1", [id.to_string()])?
+            > 0)
+    }
+
+    pub fn contains(&self, id: Uuid) -> rusqlite::Result<bool> {
+        Ok(self
+            .conn()
+            .query_row("SELECT 1 FROM runs WHERE id = ?1", [id.to_string()], |_| {
+                Ok(())
+            })
+            .optional()?
+            .is_some())
+    }
+
+    /// The commit run `id` was recorded at, or `None` if it isn't stored
+    pub fn run_commit(&self, id: Uuid) -> rusqlite::Result<Option<Option<String>>> {
+        self.conn()
+            .query_row(
+                "SELECT commit_sha FROM runs WHERE id = ?1",
+                [id.to_string()],
+                |row| row.get(0),
+            )
+            .optional()
+    }
+
+    /// The project run `id` belongs to, or `None` if it isn't stored
+    pub fn run_project(&self, id: Uuid) -> rusqlite::Result<Option<String>> {
+        Ok(self
+            .conn()
+            .query_row(
+                "SELECT project FROM runs WHERE id = ?1",
+                [id.to_string()],
+                |row| row.get::<_, Option<String>>(0),
+            )
+            .optional()?
+            .flatten())
+    }
+
+    /// Ids of the runs of `project`
+    pub fn project_runs(&self, project: &str) -> rusqlite::Result<Vec<Uuid>> {
+        let conn = self.conn();
+        let mut statement = conn.prepare("SELECT id FROM runs WHERE project = ?1")?;
+        statement
+            .query_map([project], |row| Ok(parse_uuid(row.get(0)?)))?
+            .collect()
+    }
+
+    /// Give runs that belong to no project to `project`
+    pub fn adopt_runs(&self, project: &str) -> rusqlite::Result<usize> {
+        self.conn().execute(
+            "UPDATE runs SET project = ?1 WHERE project IS NULL",
+            [project],
+        )
+    }
+
+    pub fn set_run_commit(&self, id: Uuid, commit: &str) -> rusqlite::Result<()> {
+        self.conn().execute(
+            "UPDATE runs SET commit_sha = ?1 WHERE id = ?2",
+            params![commit, id.to_string()],
+        )?;
+        Ok(())
+    }
+
+    /// The last `limit` results of `project`'s tests that `test` selects,
+    /// newest first
+    pub fn test_history(
+        &self,
+        project: &str,
+        test: &TestSelector,
+        limit: usize,
+    ) -> rusqlite::Result<Vec<TestRecord>> {
+        let conn = self.conn();
+        let mut statement = conn.prepare(
+            "SELECT tests.run_id, runs.start_time, tests.suite, tests.name, tests.status,
+                tests.duration_ms, tests.retry_count, tests.flaky, tests.error, tests.test_id
+             FROM tests JOIN runs ON runs.id = tests.run_id
+             WHERE (?1 IS NULL OR tests.name = ?1) AND (?2 IS NULL OR tests.suite = ?2)
+                AND (?5 IS NULL OR tests.test_id = ?5) AND runs.project = ?4
+             ORDER BY runs.start_time DESC
+             LIMIT ?3",
+        )?;
+        let (name, suite, id) = match test {
+            TestSelector::Name { name, suite } => (Some(name.as_str()), suite.as_deref(), None),
+            TestSelector::Id(id) => (None, None, Some(id.to_string())),
+        };
+        statement
+            .query_map(params![name, suite, limit as i64, project, id], |row| {
+                Ok(TestRecord {
+                    run_id: parse_uuid(row.get(0)?),
+                    test_id: row.get::<_, Option<String>>(9)?.map(parse_uuid),
+                    start_time: row.get(1)?,
+                    suite: row.get(2)?,
+                    name: row.get(3)?,
+                    status: row
+                        .get::<_, String>(4)?
+                        .parse()
+                        .unwrap_or(TestStatus::Pending),
+                    duration_ms: row.get(5)?,
+                    retry_count: row.get(6)?,
+                    flaky: row.get(7)?,
+                    error: row.get(8)?,
+                })
+            })?
+            .collect()
+    }
+
+    /// Tests that were flaky, or both passed and failed, across the last
+    /// `runs` runs of `project`, most flaky first
+    pub fn flaky_tests(&self, project: &str, runs: usize) -> rusqlite::Result<Vec<FlakyTest>> {
+        let conn = self.conn();
+        let mut statement = conn.prepare(&format!(
+            "SELECT suite, name, COUNT(*),
+                SUM(status = 'passed' AND NOT flaky),
+                SUM(status IN {FAILED}),
+                SUM(flaky),
+                test_id
+             FROM tests
+             WHERE run_id IN (
+                SELECT id FROM runs WHERE project = ?2 ORDER BY start_time DESC LIMIT ?1
+             )
+             GROUP BY {TEST_IDENTITY}
+             HAVING SUM(flaky) > 0
+                OR (SUM(status = 'passed') > 0 AND SUM(status IN {FAILED}) > 0)"
+        ))?;
+        let mut tests = statement
+            .query_map(params![runs as i64, project], |row| {
+                Ok(FlakyTest {
+                    test_id: row.get::<_, Option<String>>(6)?.map(parse_uuid),
+                    suite: row.get(0)?,
+                    name: row.get(1)?,
+                    runs: row.get(2)?,
+                    passed: row.get(3)?,
+                    failed: row.get(4)?,
+                    flaky: row.get(5)?,
+                })
+            })?
+            .collect::<rusqlite::Result<Vec<_>>>()?;
+        tests.sort_by(|a, b| b.flakiness().total_cmp(&a.flakiness()));
+        Ok(tests)
+    }
+
+    /// The `limit` tests that took longest on average across the last
+    /// `runs` runs of `project`, slowest first
+    pub fn slowest_tests(
+        &self,
+        project: &str,
+        runs: usize,
+        limit: usize,
+    ) -> rusqlite::Result<Vec<SlowTest>> {
+        let conn = self.conn();
+        let mut statement = conn.prepare(&format!(
+            "SELECT suite, name, COUNT(*), AVG(duration_ms), MAX(duration_ms), test_id
+             FROM tests
+             WHERE duration_ms IS NOT NULL
+                AND run_id IN (
+                    SELECT id FROM runs WHERE project = ?3 ORDER BY start_time DESC LIMIT ?1
+                )
+             GROUP BY {TEST_IDENTITY}
+             ORDER BY AVG(duration_ms) DESC
+             LIMIT ?2"
+        ))?;
+        statement
+            .query_map(params![runs as i64, limit as i64, project], |row| {
+                Ok(SlowTest {
+                    test_id: row.get::<_, Option<String>>(5)?.map(parse_uuid),
+                    suite: row.get(0)?,
+                    name: row.get(1)?,
+                    runs: row.get(2)?,
+                    mean_ms: row.get(3)?,
+                    max_ms: row.get(4)?,
+                })
+            })?
+            .collect()
+    }
+
+    /// Headline numbers of the last `limit` runs of `project`, oldest first
+    pub fn trends(&self, project: &str, limit: usize) -> rusqlite::Result<Vec<TrendPoint>> {
+        let conn = self.conn();
+        let mut statement = conn.prepare(
+            "SELECT * FROM (
+                SELECT id, start_time, duration_ms, total_tests, passed_tests, failed_tests,
+                    skipped_tests, flaky_tests, commit_sha
+                FROM runs WHERE project = ?2 ORDER BY start_time DESC LIMIT ?1
+             ) ORDER BY start_time",
+        )?;
+        statement
+            .query_map(params![limit as i64, project], |row| {
+                Ok(TrendPoint {
+                    run_id: parse_uuid(row.get(0)?),
+                    start_time: row.get(1)?,
+                    duration_ms: row.get(2)?,
+                    total_tests: row.get(3)?,
+                    passed_tests: row.get(4)?,
+                    failed_tests: row.get(5)?,
+                    skipped_tests: row.get(6)?,
+                    flaky_tests: row.get(7)?,
+                    commit: row.get(8)?,
+                })
+            })?
+            .collect()
+    }
+
+    /// Create a token, returning it along with its secret
+    pub fn create_token(&self, new: NewToken) -> rusqlite::Result<CreatedToken> {
+        let token = ApiToken {
+            id: Uuid::new_v4(),
+            name: new.name,
+            role: new.role,
+            projects: new.projects,
+            created_at: Utc::now(),
+            last_used: None,
+        };
+        let secret = generate_secret();
+        self.conn().execute(
+            "INSERT INTO tokens (id, name, secret_hash, role, projects, created_at)
+             VALUES (?1, ?2, ?3, ?4, ?5, ?6)",
+            params![
+                token.id.to_string(),
+                token.name,
+                hash_secret(&secret),
+                token.role.to_string(),
+                serde_json::to_string(&token.projects).unwrap_or_default(),
+                token.created_at,
+            ],
+        )?;
+        Ok(CreatedToken { token, secret })
+    }
+
+    /// Every token, oldest first
+    pub fn tokens(&self) -> rusqlite::Result<Vec<ApiToken>> {
+        let conn = self.conn();
+        let mut statement = conn.prepare(
+            "SELECT id, name, role, projects, created_at, last_used
+             FROM tokens ORDER BY created_at",
+        )?;
+        statement.query_map([], token_from_row)?.collect()
+    }
+
+    /// Remove token `id`, returning whether there was one
+    pub fn revoke_token(&self, id: Uuid) -> rusqlite::Result<bool> {
+        Ok(self
+            .conn()
+            .execute("DELETE FROM tokens WHERE id = ?1", [id.to_string()])?
+            > 0)
+    }
+
+    /// The token with secret `secret`, noting that it was used
+    pub fn authenticate(&self, secret: &str) -> rusqlite::Result<Option<ApiToken>> {
+        let conn = self.conn();
+        let hash = hash_secret(secret);
+        let token = conn
+            .query_row(
+                "SELECT id, name, role, projects, created_at, last_used
+                 FROM tokens WHERE secret_hash = ?1",
+                [&hash],
+                token_from_row,
+            )
+            .optional()?;
+        if token.is_some() {
+            conn.execute(
+                "UPDATE tokens SET last_used = ?1 WHERE secret_hash = ?2",
+                params![Utc::now(), hash],
+            )?;
+        }
+        Ok(token)
+    }
+
+    pub fn add_artifact(&self, artifact: &StoredArtifact) -> rusqlite::Result<()> {
+        self.conn().execute(
+            "INSERT INTO artifacts (id, run_id, test_id, test_name, name, size, created_at)
+             VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7)",
+            params![
+                artifact.id.to_string(),
+                artifact.run_id.to_string(),
+                artifact.test_id.map(|id| id.to_string()),
+                artifact.test_name,
+                artifact.name,
+                artifact.size as i64,
+                artifact.created_at,
+            ],
+        )?;
+        Ok(())
+    }
+
+    pub fn artifact(&self, id: Uuid) -> rusqlite::Result<Option<StoredArtifact>> {
+        self.conn()
+            .query_row(
+                &format!("SELECT {ARTIFACT_COLUMNS} FROM artifacts WHERE id = ?1"),
+                [id.to_string()],
+                artifact_from_row,
+            )
+            .optional()
+    }
+
+    /// Artifacts of run `run_id`, oldest first
+    pub fn run_artifacts(&self, run_id: Uuid) -> rusqlite::Result<Vec<StoredArtifact>> {
+        let conn = self.conn();
+        let mut statement = conn.prepare(&format!(
+            "SELECT {ARTIFACT_COLUMNS} FROM artifacts WHERE run_id = ?1 ORDER BY created_at"
+        ))?;
+        statement
+            .query_map([run_id.to_string()], artifact_from_row)?
+            .collect()
+    }
+
+    /// Artifacts of `project`'s tests named `name`, across every run
+    pub fn test_artifacts(
+        &self,
+        project: &str,
+        name: &str,
+    ) -> rusqlite::Result<Vec<StoredArtifact>> {
+        let conn = self.conn();
+        let mut statement = conn.prepare(&format!(
+            "SELECT {ARTIFACT_COLUMNS} FROM artifacts
+             WHERE test_name = ?1 AND run_id IN (SELECT id FROM runs WHERE project = ?2)
+             ORDER BY created_at"
+        ))?;
+        statement
+            .query_map([name, project], artifact_from_row)?
+            .collect()
+    }
+
+    /// Bytes taken up by run `run_id`'s artifacts
+    pub fn artifacts_size(&self, run_id: Uuid) -> rusqlite::Result<u64> {
+        self.conn().query_row(
+            "SELECT COALESCE(SUM(size), 0) FROM artifacts WHERE run_id = ?1",
+            [run_id.to_string()],
+            |row| row.get(0),
+        )
+    }
+
+    /// Artifacts stored before `cutoff`
+    pub fn artifacts_before(&self, cutoff: DateTime<Utc>) -> rusqlite::Result<Vec<StoredArtifact>> {
+        let conn = self.conn();
+        let mut statement = conn.prepare(&format!(
+            "SELECT {ARTIFACT_COLUMNS} FROM artifacts WHERE created_at < ?1"
+        ))?;
+        statement.query_map([cutoff], artifact_from_row)?.collect()
+    }
+
+    pub fn delete_artifact(&self, id: Uuid) -> rusqlite::Result<bool> {
+        Ok(self
+            .conn()
+            .execute("DELETE FROM artifacts WHERE id = ?1", [id.to_string()])?
+            > 0)
+    }
+
+    /// Add `project`, or replace what's stored for a project of its name
+    pub fn save_project(&self, project: &Project) -> rusqlite::Result<()> {
+        self.conn().execute(
+            "INSERT OR REPLACE INTO projects (name, repo_url, default_branch, config_path, root,
+                cloned, created_at)
+             VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7)",
+            params![
+                project.name,
+                project.repo_url,
+                project.default_branch,
+                project.config_path.to_string_lossy(),
+                project.root.to_string_lossy(),
+                project.cloned,
+                project.created_at,
+            ],
+        )?;
+        Ok(())
+    }
+
+    pub fn project(&self, name: &str) -> rusqlite::Result<Option<Project>> {
+        self.conn()
+            .query_row(
+                &format!("SELECT {PROJECT_COLUMNS} FROM projects WHERE name = ?1"),
+                [name],
+                project_from_row,
+            )
+            .optional()
+    }
+
+    /// Every project, in order of name
+    pub fn projects(&self) -> rusqlite::Result<Vec<Project>> {
+        let conn = self.conn();
+        let mut statement = conn.prepare(&format!(
+            "SELECT {PROJECT_COLUMNS} FROM projects ORDER BY name"
+        ))?;
+        statement.query_map([], project_from_row)?.collect()
+    }
+
+    pub fn delete_project(&self, name: &str) -> rusqlite::Result<bool> {
+        Ok(self
+            .conn()
+            .execute("DELETE FROM projects WHERE name = ?1", [name])?
+            > 0)
+    }
+
+    pub fn delete_run_artifacts(&self, run_id: Uuid) -> rusqlite::Result<usize> {
+        self.conn().execute(
+            "DELETE FROM artifacts WHERE run_id = ?1",
+            [run_id.to_string()],
+        )
+    }
+}
+
+const ARTIFACT_COLUMNS: &str = "id, run_id, test_id, test_name, name, size, created_at";
+
+fn artifact_from_row(row: &rusqlite::Row) -> rusqlite::Result<StoredArtifact> {
+    Ok(StoredArtifact {
+        id: parse_uuid(row.get(0)?),
+        run_id: parse_uuid(row.get(1)?),
+        test_id: row.get::<_, Option<String>>(2)?.map(parse_uuid),
+        test_name: row.get(3)?,
+        name: row.get(4)?,
+        size: row.get(5)?,
+        created_at: row.get(6)?,
+    })
+}
+
+const PROJECT_COLUMNS: &str =
+    "name, repo_url, default_branch, config_path, root, cloned, created_at";
+
+fn project_from_row(row: &rusqlite::Row) -> rusqlite::Result<Project> {
+    Ok(Project {
+        name: row.get(0)?,
+        repo_url: row.get(1)?,
+        default_branch: row.get(2)?,
+        config_path: PathBuf::from(row.get::<_, String>(3)?),
+        root: PathBuf::from(row.get::<_, String>(4)?),
+        cloned: row.get(5)?,
+        created_at: row.get(6)?,
+    })
+}
+
+fn token_from_row(row: &rusqlite::Row) -> rusqlite::Result<ApiToken> {
+    let role = match row.get::<_, String>(2)?.as_str() {
+        "admin" => Role::Admin,
+        "trigger" => Role::Trigger,
+        _ => Role::Read,
+    };
+    Ok(ApiToken {
+        id: parse_uuid(row.get(0)?),
+        name: row.get(1)?,
+        role,
+        projects: serde_json::from_str(&row.get::<_, String>(3)?).unwrap_or_default(),
+        created_at: row.get(4)?,
+        last_used: row.get(5)?,
+    })
+}
+
+fn migrate(conn: &mut Connection) -> rusqlite::Result<()> {
+    let applied: usize = conn.pragma_query_value(None, "user_version", |row| row.get(0))?;
+    for (version, migration) in MIGRATIONS.iter().enumerate().skip(applied) {
+        let tx = conn.transaction()?;
+        tx.execute_batch(migration)?;
+        tx.pragma_update(None, "user_version", version + 1)?;
+        tx.commit()?;
+    }
+    Ok(())
+}
+
+fn parse_uuid(id: String) -> Uuid {
+    Uuid::parse_str(&id).unwrap_or_default()
+}
+
+/// Which test to look up the history of
+#[derive(Debug, Clone)]
+pub enum TestSelector {
+    /// Tests named `name`, optionally only those in suite `suite`
+    Name { name: String, suite: Option<String> },
+    /// The test with this stable id
+    Id(Uuid),
+}
+
+/// One run of a test, as returned by `GET /history/tests`
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct TestRecord {
+    pub run_id: Uuid,
+    /// The test's stable id, if it was stored with one
+    pub test_id: Option<Uuid>,
+    pub start_time: DateTime<Utc>,
+    pub suite: String,
+    pub name: String,
+    pub status: TestStatus,
+    pub duration_ms: Option<u64>,
+    pub retry_count: u32,
+    /// Whether the test only passed after retrying
+    pub flaky: bool,
+    pub error: Option<String>,
+}
+
+/// How a test fared across recent runs, as returned by `GET /history/flaky`
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct FlakyTest {
+    pub test_id: Option<Uuid>,
+    pub suite: String,
+    pub name: String,
+    /// Runs the test took part in
+    pub runs: u32,
+    /// Runs it passed first time in
+    pub passed: u32,
+    pub failed: u32,
+    /// Runs it only passed in after retrying
+    pub flaky: u32,
+}
+
+impl FlakyTest {
+    /// Share of the test's runs that it didn't pass first time in, between 0
+    /// and 1
+    pub fn flakiness(&self) -> f64 {
+        if self.runs == 0 {
+            return 0.0;
+        }
+        (self.failed + self.flaky) as f64 / self.runs as f64
+    }
+}
+
+/// How long a test takes, as returned by `GET /history/slowest`
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct SlowTest {
+    pub test_id: Option<Uuid>,
+    pub suite: String,
+    pub name: String,
+    /// Runs with a duration for the test
+    pub runs: u32,
+    pub mean_ms: f64,
+    pub max_ms: u64,
+}
+
+/// A run's headline numbers, as returned by `GET /history/trends`
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct TrendPoint {
+    pub run_id: Uuid,
+    pub start_time: DateTime<Utc>,
+    pub duration_ms: Option<u64>,
+    pub total_tests: usize,
+    pub passed_tests: usize,
+    pub failed_tests: usize,
+    pub skipped_tests: usize,
+    pub flaky_tests: usize,
+    /// The commit the run was at, if known
+    pub commit: Option<String>,
+}
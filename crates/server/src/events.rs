@@ -0,0 +1,201 @@
+use futures_util::{Stream, stream};
+use serde::{Deserialize, Serialize};
+use sheila::cache::RunSummary;
+use sheila::test::TestResult;
+use sheila::{EventMeta, ProcessOutput, SourceLocation};
+use tokio::sync::broadcast::Receiver;
+use tokio::sync::broadcast::error::RecvError;
+
+use crate::runs::{RunRecord, RunStatus};
+
+/// Progress of a run as streamed from `GET /runs/{id}/events`, tagged by
+/// `event`, e.g. `{"event": "test_started", "name": "...", "suite": "..."}`
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(tag = "event", rename_all = "snake_case")]
+pub enum RunEvent {
+    SuiteStarted {
+        name: String,
+        test_count: usize,
+        #[serde(flatten)]
+        meta: EventMeta,
+    },
+    SuiteCompleted {
+        name: String,
+        #[serde(flatten)]
+        meta: EventMeta,
+    },
+    TestStarted {
+        name: String,
+        suite: String,
+        #[serde(flatten)]
+        meta: EventMeta,
+    },
+    TestPassed {
+        result: TestResult,
+        suite: String,
+        duration_ms: f64,
+        #[serde(flatten)]
+        meta: EventMeta,
+    },
+    TestFailed {
+        result: TestResult,
+        suite: String,
+        duration_ms: f64,
+        error: String,
+        location: Option<SourceLocation>,
+        #[serde(flatten)]
+        meta: EventMeta,
+    },
+    TestSkipped {
+        result: TestResult,
+        suite: String,
+        #[serde(flatten)]
+        meta: EventMeta,
+    },
+    /// The run left the queue and started
+    RunStarted,
+    /// The test executable of suite `name` exited
+    ExecutableFinished {
+        name: String,
+        #[serde(flatten)]
+        meta: EventMeta,
+    },
+    /// The last event of every run
+    RunFinished {
+        status: RunStatus,
+        summary: Option<RunSummary>,
+    },
+}
+
+impl From<ProcessOutput> for RunEvent {
+    fn from(output: ProcessOutput) -> Self {
+        match output {
+            ProcessOutput::SuiteStarted {
+                name,
+                test_count,
+                meta,
+            } => RunEvent::SuiteStarted {
+                name,
+                test_count,
+                meta,
+            },
+            ProcessOutput::SuiteCompleted { name, meta } => RunEvent::SuiteCompleted { name, meta },
+            ProcessOutput::TestStarted { name, suite, meta } => {
+                RunEvent::TestStarted { name, suite, meta }
+            }
+            ProcessOutput::TestPassed {
+                result,
+                suite,
+                duration_ms,
+                meta,
+            } => RunEvent::TestPassed {
+                result,
+                suite,
+                duration_ms,
+                meta,
+            },
+            ProcessOutput::TestFailed {
+                result,
+                suite,
+                duration_ms,
+                error,
+                location,
+                meta,
+            } => RunEvent::TestFailed {
+                result,
+                suite,
+                duration_ms,
+                error,
+                location,
+                meta,
+            },
+            ProcessOutput::TestSkipped {
+                result,
+                suite,
+                meta,
+            } => RunEvent::TestSkipped {
+                result,
+                suite,
+                meta,
+            },
+            ProcessOutput::Done { name, meta } => RunEvent::ExecutableFinished { name, meta },
+        }
+    }
+}
+
+impl RunEvent {
+    /// Name of the event, used as the SSE `event:` field
+    pub fn name(&self) -> &'static str {
+        match self {
+            RunEvent::SuiteStarted { .. } => "suite_started",
+            RunEvent::SuiteCompleted { .. } => "suite_completed",
+            RunEvent::TestStarted { .. } => "test_started",
+            RunEvent::TestPassed { .. } => "test_passed",
+            RunEvent::TestFailed { .. } => "test_failed",
+            RunEvent::TestSkipped { .. } => "test_skipped",
+            RunEvent::RunStarted => "run_started",
+            RunEvent::ExecutableFinished { .. } => "executable_finished",
+            RunEvent::RunFinished { .. } => "run_finished",
+        }
+    }
+
+    /// Which run the event belongs to and its place in it, for the events
+    /// the runner sent
+    pub fn meta(&self) -> Option<&EventMeta> {
+        match self {
+            RunEvent::SuiteStarted { meta, .. }
+            | RunEvent::SuiteCompleted { meta, .. }
+            | RunEvent::TestStarted { meta, .. }
+            | RunEvent::TestPassed { meta, .. }
+            | RunEvent::TestFailed { meta, .. }
+            | RunEvent::TestSkipped { meta, .. }
+            | RunEvent::ExecutableFinished { meta, .. } => Some(meta),
+            RunEvent::RunStarted | RunEvent::RunFinished { .. } => None,
+        }
+    }
+
+    pub fn is_last(&self) -> bool {
+        matches!(self, RunEvent::RunFinished { .. })
+    }
+}
+
+/// The events of the run in `record` from now until it finishes, or just the
+/// final event if it already did
+pub fn follow(record: RunRecord, rx: Receiver<RunEvent>) -> impl Stream<Item = RunEvent> {
+    let id = record.id;
+    let finished = record
+        .status
+        .is_finished()
+        .then_some(RunEvent::RunFinished {
+            status: record.status,
+            summary: record.summary,
+        });
+
+    stream::unfold(
+        (rx, finished, false),
+        move |(mut rx, finished, done)| async move {
+            if done {
+                return None;
+            }
+            if let Some(event) = finished {
+                return Some((event, (rx, None, true)));
+            }
+            loop {
+                match rx.recv().await {
+                    Ok(event) => {
+                        let last = event.is_last();
+                        return Some((event, (rx, None, last)));
+                    }
+                    Err(RecvError::Lagged(skipped)) => {
+                        tracing::warn!(
+                            "A subscriber to run {} fell behind and missed {} events",
+                            id,
+                            skipped
+                        );
+                    }
+                    Err(RecvError::Closed) => return None,
+                }
+            }
+        },
+    )
+}
@@ -0,0 +1,252 @@
+//! Conversions between the server's types and the gRPC messages
+
+use chrono::{DateTime, Utc};
+use prost_types::Timestamp;
+use sheila::TestStatus;
+use sheila::cache::RunSummary;
+use sheila::runners::{RunResult, TagExpr};
+use sheila::suite::SuiteResult;
+use sheila::test::TestResult;
+use tonic::Status;
+
+use super::proto::{self, run_event::Event};
+use crate::ApiError;
+use crate::events::RunEvent;
+use crate::runs::{RunRecord, RunSpec, RunStatus};
+
+fn timestamp(time: DateTime<Utc>) -> Timestamp {
+    Timestamp {
+        seconds: time.timestamp(),
+        nanos: time.timestamp_subsec_nanos() as i32,
+    }
+}
+
+fn millis(duration: Option<std::time::Duration>) -> Option<u64> {
+    duration.map(|duration| duration.as_millis() as u64)
+}
+
+/// A run's state, along with why it errored if it did
+fn run_state(status: &RunStatus) -> (proto::RunState, Option<String>) {
+    match status {
+        RunStatus::Queued => (proto::RunState::Queued, None),
+        RunStatus::Running => (proto::RunState::Running, None),
+        RunStatus::Passed => (proto::RunState::Passed, None),
+        RunStatus::Failed => (proto::RunState::Failed, None),
+        RunStatus::Cancelled => (proto::RunState::Cancelled, None),
+        RunStatus::Errored { message } => (proto::RunState::Errored, Some(message.clone())),
+    }
+}
+
+impl From<ApiError> for Status {
+    fn from(error: ApiError) -> Self {
+        let message = error.to_string();
+        match error {
+            ApiError::NotFound(_) => Status::not_found(message),
+            ApiError::BadRequest(_) => Status::invalid_argument(message),
+            ApiError::Conflict(_) => Status::failed_precondition(message),
+            ApiError::Unauthorized(_) => Status::unauthenticated(message),
+            ApiError::Forbidden(_) => Status::permission_denied(message),
+            ApiError::PayloadTooLarge(_) => Status::resource_exhausted(message),
+            ApiError::Unavailable(_) => Status::unavailable(message),
+            ApiError::Sheila(_) | ApiError::Io(_) | ApiError::Database(_) => {
+                tracing::error!("{}", message);
+                Status::internal(message)
+            }
+        }
+    }
+}
+
+impl TryFrom<proto::RunSpec> for RunSpec {
+    type Error = Status;
+
+    fn try_from(spec: proto::RunSpec) -> Result<Self, Status> {
+        let tags = spec
+            .tags
+            .map(|tags| tags.parse::<TagExpr>())
+            .transpose()
+            .map_err(|e| Status::invalid_argument(e.to_string()))?;
+        Ok(RunSpec {
+            project: spec.project,
+            targets: spec.targets,
+            exact: spec.exact,
+            tags,
+            packages: spec.packages,
+            shards: spec.shards.map(|shards| shards as usize),
+            priority: spec.priority,
+            fail_fast: spec.fail_fast,
+            retries: spec.retries,
+            jobs: spec.jobs.map(|jobs| jobs as usize),
+            timeout: spec.timeout,
+            revision: spec.revision,
+        })
+    }
+}
+
+impl From<&RunSpec> for proto::RunSpec {
+    fn from(spec: &RunSpec) -> Self {
+        Self {
+            targets: spec.targets.clone(),
+            exact: spec.exact,
+            tags: spec.tags.as_ref().map(ToString::to_string),
+            packages: spec.packages.clone(),
+            shards: spec.shards.map(|shards| shards as u32),
+            priority: spec.priority,
+            fail_fast: spec.fail_fast,
+            retries: spec.retries,
+            jobs: spec.jobs.map(|jobs| jobs as u32),
+            timeout: spec.timeout,
+            revision: spec.revision.clone(),
+            project: spec.project.clone(),
+        }
+    }
+}
+
+impl From<&RunSummary> for proto::RunSummary {
+    fn from(summary: &RunSummary) -> Self {
+        Self {
+            total_tests: summary.total_tests as u64,
+            passed_tests: summary.passed_tests as u64,
+            failed_tests: summary.failed_tests as u64,
+            skipped_tests: summary.skipped_tests as u64,
+            flaky_tests: summary.flaky_tests as u64,
+            duration_ms: millis(summary.duration),
+            failures: summary.failures.clone(),
+        }
+    }
+}
+
+impl From<&RunRecord> for proto::Run {
+    fn from(record: &RunRecord) -> Self {
+        let (state, error) = run_state(&record.status);
+        Self {
+            id: record.id.to_string(),
+            spec: Some((&record.spec).into()),
+            state: state.into(),
+            error,
+            created_at: Some(timestamp(record.created_at)),
+            started_at: record.started_at.map(timestamp),
+            finished_at: record.finished_at.map(timestamp),
+            summary: record.summary.as_ref().map(Into::into),
+        }
+    }
+}
+
+impl From<TestStatus> for proto::TestStatus {
+    fn from(status: TestStatus) -> Self {
+        match status {
+            TestStatus::Pending => Self::Pending,
+            TestStatus::Running => Self::Running,
+            TestStatus::Passed => Self::Passed,
+            TestStatus::Failed => Self::Failed,
+            TestStatus::Skipped => Self::Skipped,
+            TestStatus::Ignored => Self::Ignored,
+            TestStatus::Timeout => Self::Timeout,
+            TestStatus::Cancelled => Self::Cancelled,
+            TestStatus::Cached => Self::Cached,
+            TestStatus::XFail => Self::Xfail,
+            TestStatus::XPass => Self::Xpass,
+            TestStatus::Quarantined => Self::Quarantined,
+        }
+    }
+}
+
+impl From<&TestResult> for proto::TestResult {
+    fn from(result: &TestResult) -> Self {
+        Self {
+            id: result.id.to_string(),
+            name: result.name.clone(),
+            status: proto::TestStatus::from(result.status).into(),
+            duration_ms: millis(result.duration),
+            error: result.error.as_ref().map(|e| e.message().to_string()),
+            retry_count: result.retry_count,
+            flaky: result.flaky,
+            tags: result.tags.clone(),
+            stdout: result.stdout.clone(),
+            stderr: result.stderr.clone(),
+            stable_id: result.stable_id.to_string(),
+            skip_reason: result.skip_reason.clone(),
+        }
+    }
+}
+
+impl From<&SuiteResult> for proto::SuiteResult {
+    fn from(result: &SuiteResult) -> Self {
+        Self {
+            id: result.id.to_string(),
+            name: result.name.clone(),
+            duration_ms: millis(result.duration),
+            test_results: result.test_results.iter().map(Into::into).collect(),
+            stable_id: result.stable_id.to_string(),
+        }
+    }
+}
+
+impl From<&RunResult> for proto::RunResult {
+    fn from(result: &RunResult) -> Self {
+        Self {
+            id: result.id.to_string(),
+            start_time: Some(timestamp(result.start_time)),
+            duration_ms: millis(result.duration),
+            suite_results: result.suite_results.iter().map(Into::into).collect(),
+            total_tests: result.total_tests as u64,
+            passed_tests: result.passed_tests as u64,
+            failed_tests: result.failed_tests as u64,
+            skipped_tests: result.skipped_tests as u64,
+            flaky_tests: result.flaky_tests as u64,
+            error: result.error.as_ref().map(|e| e.message().to_string()),
+        }
+    }
+}
+
+impl From<RunEvent> for proto::RunEvent {
+    fn from(event: RunEvent) -> Self {
+        let meta = event.meta().map(|meta| proto::EventMeta {
+            run_id: meta.run_id.to_string(),
+            sequence: meta.sequence,
+            suite_id: meta.suite_id.map(|id| id.to_string()),
+            test_id: meta.test_id.map(|id| id.to_string()),
+        });
+        let event = match event {
+            RunEvent::SuiteStarted {
+                name, test_count, ..
+            } => Event::SuiteStarted(proto::SuiteStarted {
+                name,
+                test_count: test_count as u64,
+            }),
+            RunEvent::SuiteCompleted { name, .. } => {
+                Event::SuiteCompleted(proto::SuiteCompleted { name })
+            }
+            RunEvent::TestStarted { name, suite, .. } => {
+                Event::TestStarted(proto::TestStarted { name, suite })
+            }
+            RunEvent::TestPassed { result, .. } | RunEvent::TestSkipped { result, .. } => {
+                Event::TestFinished(proto::TestFinished {
+                    result: Some((&result).into()),
+                })
+            }
+            RunEvent::TestFailed { result, error, .. } => {
+                let mut result = proto::TestResult::from(&result);
+                result.error.get_or_insert(error);
+                Event::TestFinished(proto::TestFinished {
+                    result: Some(result),
+                })
+            }
+            RunEvent::RunStarted => Event::RunStarted(proto::RunStarted {}),
+            RunEvent::ExecutableFinished { .. } => {
+                Event::ExecutableFinished(proto::ExecutableFinished {})
+            }
+            RunEvent::RunFinished { status, summary } => {
+                let (state, error) = run_state(&status);
+                Event::RunFinished(proto::RunFinished {
+                    state: state.into(),
+                    error,
+                    summary: summary.as_ref().map(Into::into),
+                })
+            }
+        };
+        Self {
+            event: Some(event),
+            meta,
+        }
+    }
+}
@@ -0,0 +1,307 @@
+//! The gRPC API, described by `proto/sheila/v1/sheila.proto`. It's a typed
+//! counterpart to the REST API's runs routes, for clients generated from the
+//! schema, and is served on a port of its own when `grpc_bind` is set.
+
+mod convert;
+pub mod proto;
+
+use std::net::SocketAddr;
+use std::pin::Pin;
+
+use futures_util::{Stream, StreamExt};
+use tonic::{Request, Response, Status};
+use uuid::Uuid;
+
+use crate::auth::{self, Caller, Role};
+use crate::runs::RunSpec;
+use crate::{ApiError, AppState, events};
+use proto::runs_server::{Runs, RunsServer};
+
+pub struct RunsService {
+    state: AppState,
+}
+
+impl RunsService {
+    pub fn new(state: AppState) -> Self {
+        Self { state }
+    }
+
+    /// Check the request's `authorization` metadata allows it to act on
+    /// `project`, when auth is enabled
+    fn authorize<T>(
+        &self,
+        request: &Request<T>,
+        required: Role,
+        project: Option<&str>,
+    ) -> Result<(), Status> {
+        if !self.state.config.auth {
+            return Ok(());
+        }
+        let secret = request
+            .metadata()
+            .get("authorization")
+            .and_then(|value| value.to_str().ok())
+            .and_then(|value| value.strip_prefix("Bearer "))
+            .map(str::trim);
+        let caller = Caller(Some(auth::check(&self.state, secret, required)?));
+        caller.check_project(project.unwrap_or(self.state.projects.default_name()))?;
+        Ok(())
+    }
+
+    /// [`authorize`](Self::authorize) a request about the run it names,
+    /// returning the run's id
+    async fn authorize_run(
+        &self,
+        request: &Request<proto::RunRequest>,
+        required: Role,
+    ) -> Result<Uuid, Status> {
+        let id = run_id(request.get_ref())?;
+        let project = self.state.run_project(id).await?;
+        self.authorize(request, required, project.as_deref())?;
+        Ok(id)
+    }
+}
+
+fn run_id(request: &proto::RunRequest) -> Result<Uuid, Status> {
+    Uuid::parse_str(&request.id)
+        .map_err(|_| Status::invalid_argument(format!("Invalid run id '{}'", request.id)))
+}
+
+fn no_run(id: Uuid) -> Status {
+    Status::not_found(format!("No run {}", id))
+}
+
+type EventStream = Pin<Box<dyn Stream<Item = Result<proto::RunEvent, Status>> + Send>>;
+
+#[tonic::async_trait]
+impl Runs for RunsService {
+    async fn submit_run(
+        &self,
+        request: Request<proto::SubmitRunRequest>,
+    ) -> Result<Response<proto::Run>, Status> {
+        let spec = match request.get_ref().spec.clone() {
+            Some(spec) => RunSpec::try_from(spec)?,
+            None => RunSpec::default(),
+        };
+        self.authorize(&request, Role::Trigger, spec.project.as_deref())?;
+        let record = self.state.runs.submit(spec).await?;
+        Ok(Response::new((&record).into()))
+    }
+
+    async fn get_run(
+        &self,
+        request: Request<proto::RunRequest>,
+    ) -> Result<Response<proto::Run>, Status> {
+        let id = self.authorize_run(&request, Role::Read).await?;
+        let record = self.state.runs.get(id).await.ok_or_else(|| no_run(id))?;
+        Ok(Response::new((&record).into()))
+    }
+
+    async fn list_runs(
+        &self,
+        request: Request<proto::ListRunsRequest>,
+    ) -> Result<Response<proto::ListRunsResponse>, Status> {
+        let project = self
+            .state
+            .projects
+            .get(request.get_ref().project.as_deref())?;
+        self.authorize(&request, Role::Read, Some(&project.name))?;
+        let runs = self.state.runs.list(Some(&project.name)).await;
+        Ok(Response::new(proto::ListRunsResponse {
+            runs: runs.iter().map(Into::into).collect(),
+        }))
+    }
+
+    async fn cancel_run(
+        &self,
+        request: Request<proto::RunRequest>,
+    ) -> Result<Response<proto::Run>, Status> {
+        let id = self.authorize_run(&request, Role::Trigger).await?;
+        let record = self.state.runs.cancel(id).await?;
+        Ok(Response::new((&record).into()))
+    }
+
+    type StreamEventsStream = EventStream;
+
+    async fn stream_events(
+        &self,
+        request: Request<proto::RunRequest>,
+    ) -> Result<Response<EventStream>, Status> {
+        let id = self.authorize_run(&request, Role::Read).await?;
+        let (record, rx) = self
+            .state
+            .runs
+            .subscribe(id)
+            .await
+            .ok_or_else(|| no_run(id))?;
+        let events = events::follow(record, rx).map(|event| Ok(event.into()));
+        Ok(Response::new(Box::pin(events)))
+    }
+
+    async fn get_result(
+        &self,
+        request: Request<proto::RunRequest>,
+    ) -> Result<Response<proto::RunResult>, Status> {
+        let id = self.authorize_run(&request, Role::Read).await?;
+        if let Some(record) = self.state.runs.get(id).await
+            && !record.status.is_finished()
+        {
+            return Err(ApiError::Conflict(format!("Run {} hasn't finished", id)).into());
+        }
+        let result = self
+            .state
+            .reports
+            .load(id)
+            .map_err(ApiError::from)?
+            .ok_or_else(|| Status::not_found(format!("No result for run {}", id)))?;
+        Ok(Response::new((&result).into()))
+    }
+}
+
+/// Serve the gRPC API on `addr` until the process is stopped
+pub async fn serve(state: AppState, addr: SocketAddr) -> sheila::Result<()> {
+    tracing::info!("Serving gRPC on {}", addr);
+    tonic::transport::Server::builder()
+        .add_service(RunsServer::new(RunsService::new(state)))
+        .serve(addr)
+        .await
+        .map_err(|e| sheila::Error::generic(format!("The gRPC server failed: {}", e)))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::ServerConfig;
+    use crate::auth::NewToken;
+    use std::time::Duration;
+    use tonic::Code;
+
+    fn state(auth: bool) -> AppState {
+        let root = std::env::temp_dir().join(format!("sheila-grpc-{}", Uuid::new_v4()));
+        std::fs::create_dir_all(&root).unwrap();
+        let mut config = ServerConfig::new(root);
+        config.auth = auth;
+        AppState::new(config).unwrap()
+    }
+
+    /// A sharded run, which waits for agents until it's cancelled
+    fn submit_request() -> Request<proto::SubmitRunRequest> {
+        Request::new(proto::SubmitRunRequest {
+            spec: Some(proto::RunSpec {
+                shards: Some(1),
+                ..Default::default()
+            }),
+        })
+    }
+
+    fn run_request(id: &str) -> Request<proto::RunRequest> {
+        Request::new(proto::RunRequest { id: id.to_string() })
+    }
+
+    /// `request` with `secret` as its bearer token
+    fn with_token<T>(mut request: Request<T>, secret: &str) -> Request<T> {
+        let value = format!("Bearer {}", secret).parse().unwrap();
+        request.metadata_mut().insert("authorization", value);
+        request
+    }
+
+    async fn wait_until_finished(service: &RunsService, id: &str, secret: &str) -> proto::Run {
+        for _ in 0..50 {
+            let run = service
+                .get_run(with_token(run_request(id), secret))
+                .await
+                .unwrap()
+                .into_inner();
+            if run.finished_at.is_some() {
+                return run;
+            }
+            tokio::time::sleep(Duration::from_millis(100)).await;
+        }
+        panic!("Run {} didn't finish", id);
+    }
+
+    #[tokio::test]
+    async fn test_submit_get_and_cancel_a_run() {
+        let service = RunsService::new(state(false));
+
+        let submitted = service
+            .submit_run(submit_request())
+            .await
+            .unwrap()
+            .into_inner();
+        let fetched = service
+            .get_run(run_request(&submitted.id))
+            .await
+            .unwrap()
+            .into_inner();
+        assert_eq!(fetched.id, submitted.id);
+        assert_eq!(fetched.spec.and_then(|spec| spec.shards), Some(1));
+
+        service
+            .cancel_run(run_request(&submitted.id))
+            .await
+            .unwrap();
+        let run = wait_until_finished(&service, &submitted.id, "").await;
+        assert_eq!(run.state(), proto::RunState::Cancelled);
+
+        let cancelled = service.cancel_run(run_request(&submitted.id)).await;
+        assert_eq!(cancelled.unwrap_err().code(), Code::FailedPrecondition);
+        let unknown = service
+            .get_run(run_request(&Uuid::new_v4().to_string()))
+            .await;
+        assert_eq!(unknown.unwrap_err().code(), Code::NotFound);
+    }
+
+    #[tokio::test]
+    async fn test_calls_need_a_token_with_the_role_and_project() {
+        let state = state(true);
+        let token = |role, projects: &[&str]| {
+            let new = NewToken {
+                name: format!("{}-token", role),
+                role,
+                projects: projects.iter().map(|p| p.to_string()).collect(),
+            };
+            state.db.create_token(new).unwrap().secret
+        };
+        let reader = token(Role::Read, &[]);
+        let trigger = token(Role::Trigger, &[]);
+        let elsewhere = token(Role::Trigger, &["elsewhere"]);
+        let service = RunsService::new(state.clone());
+
+        let anonymous = service.submit_run(submit_request()).await;
+        assert_eq!(anonymous.unwrap_err().code(), Code::Unauthenticated);
+        let read_only = service
+            .submit_run(with_token(submit_request(), &reader))
+            .await;
+        assert_eq!(read_only.unwrap_err().code(), Code::PermissionDenied);
+        let other_project = service
+            .submit_run(with_token(submit_request(), &elsewhere))
+            .await;
+        assert_eq!(other_project.unwrap_err().code(), Code::PermissionDenied);
+
+        let submitted = service
+            .submit_run(with_token(submit_request(), &trigger))
+            .await
+            .unwrap()
+            .into_inner();
+        let fetched = service
+            .get_run(with_token(run_request(&submitted.id), &reader))
+            .await;
+        assert_eq!(fetched.unwrap().into_inner().id, submitted.id);
+        let hidden = service
+            .get_run(with_token(run_request(&submitted.id), &elsewhere))
+            .await;
+        assert_eq!(hidden.unwrap_err().code(), Code::PermissionDenied);
+
+        let read_cancel = service
+            .cancel_run(with_token(run_request(&submitted.id), &reader))
+            .await;
+        assert_eq!(read_cancel.unwrap_err().code(), Code::PermissionDenied);
+        service
+            .cancel_run(with_token(run_request(&submitted.id), &trigger))
+            .await
+            .unwrap();
+        let run = wait_until_finished(&service, &submitted.id, &reader).await;
+        assert_eq!(run.state(), proto::RunState::Cancelled);
+    }
+}
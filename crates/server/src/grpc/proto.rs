@@ -0,0 +1,274 @@
+//! The messages of `proto/sheila/v1/sheila.proto`, written out the way
+//! `prost-build` would generate them, along with the generated service.
+
+#[derive(Clone, PartialEq, ::prost::Message)]
+pub struct RunSpec {
+    #[prost(string, repeated, tag = "1")]
+    pub targets: Vec<String>,
+    #[prost(bool, tag = "2")]
+    pub exact: bool,
+    #[prost(string, optional, tag = "3")]
+    pub tags: Option<String>,
+    #[prost(string, repeated, tag = "4")]
+    pub packages: Vec<String>,
+    #[prost(uint32, optional, tag = "5")]
+    pub shards: Option<u32>,
+    #[prost(int32, tag = "6")]
+    pub priority: i32,
+    #[prost(bool, tag = "7")]
+    pub fail_fast: bool,
+    #[prost(uint32, optional, tag = "8")]
+    pub retries: Option<u32>,
+    #[prost(uint32, optional, tag = "9")]
+    pub jobs: Option<u32>,
+    #[prost(uint64, optional, tag = "10")]
+    pub timeout: Option<u64>,
+    #[prost(string, optional, tag = "11")]
+    pub revision: Option<String>,
+    #[prost(string, optional, tag = "12")]
+    pub project: Option<String>,
+}
+
+#[derive(Clone, PartialEq, ::prost::Message)]
+pub struct SubmitRunRequest {
+    #[prost(message, optional, tag = "1")]
+    pub spec: Option<RunSpec>,
+}
+
+#[derive(Clone, PartialEq, ::prost::Message)]
+pub struct RunRequest {
+    #[prost(string, tag = "1")]
+    pub id: String,
+}
+
+#[derive(Clone, PartialEq, ::prost::Message)]
+pub struct ListRunsRequest {
+    #[prost(string, optional, tag = "1")]
+    pub project: Option<String>,
+}
+
+#[derive(Clone, PartialEq, ::prost::Message)]
+pub struct ListRunsResponse {
+    #[prost(message, repeated, tag = "1")]
+    pub runs: Vec<Run>,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash, PartialOrd, Ord, ::prost::Enumeration)]
+#[repr(i32)]
+pub enum RunState {
+    Unspecified = 0,
+    Queued = 1,
+    Running = 2,
+    Passed = 3,
+    Failed = 4,
+    Cancelled = 5,
+    Errored = 6,
+}
+
+#[derive(Clone, PartialEq, ::prost::Message)]
+pub struct Run {
+    #[prost(string, tag = "1")]
+    pub id: String,
+    #[prost(message, optional, tag = "2")]
+    pub spec: Option<RunSpec>,
+    #[prost(enumeration = "RunState", tag = "3")]
+    pub state: i32,
+    #[prost(string, optional, tag = "4")]
+    pub error: Option<String>,
+    #[prost(message, optional, tag = "5")]
+    pub created_at: Option<::prost_types::Timestamp>,
+    #[prost(message, optional, tag = "6")]
+    pub started_at: Option<::prost_types::Timestamp>,
+    #[prost(message, optional, tag = "7")]
+    pub finished_at: Option<::prost_types::Timestamp>,
+    #[prost(message, optional, tag = "8")]
+    pub summary: Option<RunSummary>,
+}
+
+#[derive(Clone, PartialEq, ::prost::Message)]
+pub struct RunSummary {
+    #[prost(uint64, tag = "1")]
+    pub total_tests: u64,
+    #[prost(uint64, tag = "2")]
+    pub passed_tests: u64,
+    #[prost(uint64, tag = "3")]
+    pub failed_tests: u64,
+    #[prost(uint64, tag = "4")]
+    pub skipped_tests: u64,
+    #[prost(uint64, tag = "5")]
+    pub flaky_tests: u64,
+    #[prost(uint64, optional, tag = "6")]
+    pub duration_ms: Option<u64>,
+    #[prost(string, repeated, tag = "7")]
+    pub failures: Vec<String>,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash, PartialOrd, Ord, ::prost::Enumeration)]
+#[repr(i32)]
+pub enum TestStatus {
+    Unspecified = 0,
+    Pending = 1,
+    Running = 2,
+    Passed = 3,
+    Failed = 4,
+    Skipped = 5,
+    Ignored = 6,
+    Timeout = 7,
+    Cancelled = 8,
+    Cached = 9,
+    Xfail = 10,
+    Xpass = 11,
+    Quarantined = 12,
+}
+
+#[derive(Clone, PartialEq, ::prost::Message)]
+pub struct TestResult {
+    #[prost(string, tag = "1")]
+    pub id: String,
+    #[prost(string, tag = "2")]
+    pub name: String,
+    #[prost(enumeration = "TestStatus", tag = "3")]
+    pub status: i32,
+    #[prost(uint64, optional, tag = "4")]
+    pub duration_ms: Option<u64>,
+    #[prost(string, optional, tag = "5")]
+    pub error: Option<String>,
+    #[prost(uint32, tag = "6")]
+    pub retry_count: u32,
+    #[prost(bool, tag = "7")]
+    pub flaky: bool,
+    #[prost(string, repeated, tag = "8")]
+    pub tags: Vec<String>,
+    #[prost(string, optional, tag = "9")]
+    pub stdout: Option<String>,
+    #[prost(string, optional, tag = "10")]
+    pub stderr: Option<String>,
+    #[prost(string, tag = "11")]
+    pub stable_id: String,
+    #[prost(string, optional, tag = "12")]
+    pub skip_reason: Option<String>,
+}
+
+#[derive(Clone, PartialEq, ::prost::Message)]
+pub struct SuiteResult {
+    #[prost(string, tag = "1")]
+    pub id: String,
+    #[prost(string, tag = "2")]
+    pub name: String,
+    #[prost(uint64, optional, tag = "3")]
+    pub duration_ms: Option<u64>,
+    #[prost(message, repeated, tag = "4")]
+    pub test_results: Vec<TestResult>,
+    #[prost(string, tag = "5")]
+    pub stable_id: String,
+}
+
+#[derive(Clone, PartialEq, ::prost::Message)]
+pub struct RunResult {
+    #[prost(string, tag = "1")]
+    pub id: String,
+    #[prost(message, optional, tag = "2")]
+    pub start_time: Option<::prost_types::Timestamp>,
+    #[prost(uint64, optional, tag = "3")]
+    pub duration_ms: Option<u64>,
+    #[prost(message, repeated, tag = "4")]
+    pub suite_results: Vec<SuiteResult>,
+    #[prost(uint64, tag = "5")]
+    pub total_tests: u64,
+    #[prost(uint64, tag = "6")]
+    pub passed_tests: u64,
+    #[prost(uint64, tag = "7")]
+    pub failed_tests: u64,
+    #[prost(uint64, tag = "8")]
+    pub skipped_tests: u64,
+    #[prost(uint64, tag = "9")]
+    pub flaky_tests: u64,
+    #[prost(string, optional, tag = "10")]
+    pub error: Option<String>,
+}
+
+#[derive(Clone, PartialEq, ::prost::Message)]
+pub struct RunEvent {
+    #[prost(oneof = "run_event::Event", tags = "1, 2, 3, 4, 5, 6, 7")]
+    pub event: Option<run_event::Event>,
+    #[prost(message, optional, tag = "8")]
+    pub meta: Option<EventMeta>,
+}
+
+#[derive(Clone, PartialEq, ::prost::Message)]
+pub struct EventMeta {
+    #[prost(string, tag = "1")]
+    pub run_id: String,
+    #[prost(uint64, tag = "2")]
+    pub sequence: u64,
+    #[prost(string, optional, tag = "3")]
+    pub suite_id: Option<String>,
+    #[prost(string, optional, tag = "4")]
+    pub test_id: Option<String>,
+}
+
+pub mod run_event {
+    #[derive(Clone, PartialEq, ::prost::Oneof)]
+    pub enum Event {
+        #[prost(message, tag = "1")]
+        SuiteStarted(super::SuiteStarted),
+        #[prost(message, tag = "2")]
+        SuiteCompleted(super::SuiteCompleted),
+        #[prost(message, tag = "3")]
+        TestStarted(super::TestStarted),
+        #[prost(message, tag = "4")]
+        TestFinished(super::TestFinished),
+        #[prost(message, tag = "5")]
+        RunStarted(super::RunStarted),
+        #[prost(message, tag = "6")]
+        ExecutableFinished(super::ExecutableFinished),
+        #[prost(message, tag = "7")]
+        RunFinished(super::RunFinished),
+    }
+}
+
+#[derive(Clone, PartialEq, ::prost::Message)]
+pub struct SuiteStarted {
+    #[prost(string, tag = "1")]
+    pub name: String,
+    #[prost(uint64, tag = "2")]
+    pub test_count: u64,
+}
+
+#[derive(Clone, PartialEq, ::prost::Message)]
+pub struct SuiteCompleted {
+    #[prost(string, tag = "1")]
+    pub name: String,
+}
+
+#[derive(Clone, PartialEq, ::prost::Message)]
+pub struct TestStarted {
+    #[prost(string, tag = "1")]
+    pub name: String,
+    #[prost(string, tag = "2")]
+    pub suite: String,
+}
+
+#[derive(Clone, PartialEq, ::prost::Message)]
+pub struct TestFinished {
+    #[prost(message, optional, tag = "1")]
+    pub result: Option<TestResult>,
+}
+
+#[derive(Clone, PartialEq, ::prost::Message)]
+pub struct RunStarted {}
+
+#[derive(Clone, PartialEq, ::prost::Message)]
+pub struct ExecutableFinished {}
+
+#[derive(Clone, PartialEq, ::prost::Message)]
+pub struct RunFinished {
+    #[prost(enumeration = "RunState", tag = "1")]
+    pub state: i32,
+    #[prost(string, optional, tag = "2")]
+    pub error: Option<String>,
+    #[prost(message, optional, tag = "3")]
+    pub summary: Option<RunSummary>,
+}
+
+include!(concat!(env!("OUT_DIR"), "/sheila.v1.Runs.rs"));
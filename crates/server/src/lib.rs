@@ -1 +1,85 @@
-//! TODO
+//! `sheilad`, a daemon that runs the tests of one or more projects on
+//! request and keeps their results. See [`projects`] for how it tracks
+//! several.
+//!
+//! The REST API speaks the core crate's types, so a run started through it
+//! finishes with the same [`RunResult`](sheila::runners::RunResult) the CLI
+//! produces, and stored reports render to the same
+//! [`TestReport`](sheila::TestReport)s. The runs routes also have a typed
+//! gRPC counterpart in [`grpc`], for clients generated from its schema.
+
+pub mod agent;
+pub mod agents;
+pub mod api;
+pub mod artifacts;
+pub mod auth;
+pub mod config;
+pub mod dashboard;
+pub mod db;
+pub mod error;
+pub mod events;
+pub mod grpc;
+pub mod metrics;
+pub mod projects;
+pub mod reports;
+pub mod runs;
+pub mod state;
+pub mod webhooks;
+
+pub use config::ServerConfig;
+pub use error::{ApiError, ApiResult};
+pub use state::AppState;
+
+/// How often artifacts past their retention are looked for
+const ARTIFACT_PRUNE_INTERVAL: std::time::Duration = std::time::Duration::from_secs(60 * 60);
+
+/// Serve the API on `config.bind` until the process is stopped.
+///
+/// Runs build and execute their project's tests in its checkout, starting
+/// with `config.project_root`. A relative `config.data_dir` is taken to be
+/// relative to that.
+pub async fn serve(mut config: ServerConfig) -> sheila::Result<()> {
+    config.data_dir = std::path::absolute(config.project_root.join(&config.data_dir))?;
+    let state = AppState::new(config.clone())?;
+
+    let listener = tokio::net::TcpListener::bind(config.bind).await?;
+    tracing::info!(
+        "Serving {} on {}",
+        config.project_root.display(),
+        listener.local_addr()?
+    );
+
+    let agents = state.agents.clone();
+    tokio::spawn(async move {
+        let mut interval = tokio::time::interval(agents::AGENT_TIMEOUT / 3);
+        loop {
+            interval.tick().await;
+            agents.reap();
+        }
+    });
+
+    let artifacts = state.artifacts.clone();
+    tokio::spawn(async move {
+        let mut interval = tokio::time::interval(ARTIFACT_PRUNE_INTERVAL);
+        loop {
+            interval.tick().await;
+            match artifacts.prune() {
+                Ok(0) => {}
+                Ok(pruned) => tracing::info!("Removed {} expired artifacts", pruned),
+                Err(e) => tracing::warn!("Failed to remove expired artifacts: {}", e),
+            }
+        }
+    });
+
+    if let Some(grpc_bind) = config.grpc_bind {
+        let state = state.clone();
+        tokio::spawn(async move {
+            if let Err(e) = grpc::serve(state, grpc_bind).await {
+                tracing::error!("{}", e);
+            }
+        });
+    }
+
+    axum::serve(listener, api::router(state)).await?;
+    Ok(())
+}
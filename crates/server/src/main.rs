@@ -0,0 +1,236 @@
+use std::net::SocketAddr;
+use std::path::PathBuf;
+use std::time::Duration;
+
+use clap::{Args, Parser, Subcommand};
+use sheila_server::ServerConfig;
+use sheila_server::agent::AgentConfig;
+use sheila_server::artifacts::ArtifactLimits;
+use sheila_server::auth::{NewToken, Role};
+use sheila_server::db::Database;
+use tracing_subscriber::EnvFilter;
+use uuid::Uuid;
+
+const MIB: u64 = 1024 * 1024;
+
+/// Runs a project's tests on request and keeps their results
+#[derive(Parser)]
+#[command(name = "sheilad", version)]
+struct Cli {
+    #[command(subcommand)]
+    command: Option<Command>,
+
+    /// Address to serve the API on
+    #[arg(long, default_value = "127.0.0.1:7878")]
+    bind: SocketAddr,
+
+    /// Address to serve the gRPC API on, e.g. `127.0.0.1:7879`. It isn't
+    /// served unless given.
+    #[arg(long)]
+    grpc_bind: Option<SocketAddr>,
+
+    /// Cargo project whose tests are run, unless a run names another
+    #[arg(long, default_value = ".")]
+    project: PathBuf,
+
+    /// Where to keep stored reports, instead of the project's
+    /// `.sheila/server` directory
+    #[arg(long)]
+    data_dir: Option<PathBuf>,
+
+    /// Runs to carry out at the same time, queueing the rest
+    #[arg(long, default_value_t = 1)]
+    max_runs: usize,
+
+    /// Require an API token for every request. Create one first with
+    /// `sheilad token create`.
+    #[arg(long)]
+    auth: bool,
+
+    /// Name to register the project under, instead of the project
+    /// directory's
+    #[arg(long)]
+    project_name: Option<String>,
+
+    /// Secret GitHub webhooks are signed with, or GitLab webhooks send as
+    /// their token
+    #[arg(long, env = "SHEILA_WEBHOOK_SECRET")]
+    webhook_secret: Option<String>,
+
+    /// Largest artifact to accept, in MiB
+    #[arg(long, default_value_t = 64)]
+    artifact_max_size: u64,
+
+    /// Most a run's artifacts may take up together, in MiB
+    #[arg(long, default_value_t = 512)]
+    artifact_quota: u64,
+
+    /// How long to keep artifacts, e.g. `30d` or `12h`. `0` keeps them
+    /// forever.
+    #[arg(long, default_value = "30d", value_parser = parse_retention)]
+    artifact_retention: Duration,
+}
+
+#[derive(Subcommand)]
+enum Command {
+    /// Join a server as an agent and run shards of its distributed runs
+    Agent(AgentArgs),
+    /// Manage the API tokens in the server's data directory
+    #[command(subcommand)]
+    Token(TokenCommand),
+}
+
+#[derive(Subcommand)]
+enum TokenCommand {
+    /// Create a token and print its secret, which isn't shown again
+    Create {
+        /// What the token is for
+        #[arg(long)]
+        name: String,
+
+        #[arg(long, value_enum, default_value = "read")]
+        role: Role,
+
+        /// Project the token may be used for, repeatable. Defaults to all.
+        #[arg(long = "for-project")]
+        projects: Vec<String>,
+    },
+    /// List tokens
+    List,
+    /// Revoke a token
+    Revoke { id: Uuid },
+}
+
+#[derive(Args)]
+struct AgentArgs {
+    /// URL of the server to join, e.g. `http://ci-host:7878`
+    #[arg(long)]
+    join: String,
+
+    /// Name to show up as on the server, instead of the host name
+    #[arg(long)]
+    name: Option<String>,
+
+    /// Checkout of the project the server runs. It must match the server's.
+    #[arg(long, default_value = ".")]
+    project: PathBuf,
+
+    /// Name of the project on the server, if it isn't the server's own
+    #[arg(long)]
+    project_name: Option<String>,
+
+    /// API token to send, if the server requires one
+    #[arg(long, env = "SHEILA_TOKEN")]
+    token: Option<String>,
+}
+
+#[tokio::main]
+async fn main() -> sheila::Result<()> {
+    tracing_subscriber::fmt()
+        .with_env_filter(
+            EnvFilter::try_from_env("SHEILA_LOG")
+                .unwrap_or_else(|_| EnvFilter::new("warn,sheila_server=info")),
+        )
+        .init();
+
+    let cli = Cli::parse();
+    let command = match cli.command {
+        Some(Command::Agent(args)) => {
+            let name = args.name.unwrap_or_else(host_name);
+            return sheila_server::agent::run(AgentConfig {
+                server: args.join,
+                name,
+                project_root: args.project.canonicalize()?,
+                project: args.project_name,
+                token: args.token,
+            })
+            .await;
+        }
+        command => command,
+    };
+
+    let project = cli.project.canonicalize()?;
+    let mut config = ServerConfig::new(&project);
+    config.bind = cli.bind;
+    config.grpc_bind = cli.grpc_bind;
+    config.max_concurrent_runs = cli.max_runs;
+    config.auth = cli.auth;
+    config.webhook_secret = cli.webhook_secret;
+    config.artifact_limits = ArtifactLimits {
+        max_size: cli.artifact_max_size * MIB,
+        run_quota: cli.artifact_quota * MIB,
+        retention: (!cli.artifact_retention.is_zero()).then_some(cli.artifact_retention),
+    };
+    if let Some(data_dir) = cli.data_dir {
+        config.data_dir = data_dir;
+    }
+    if let Some(project_name) = cli.project_name {
+        config.project_name = project_name;
+    }
+
+    if let Some(Command::Token(command)) = command {
+        return manage_tokens(&Database::open(config.database_path())?, command);
+    }
+    sheila_server::serve(config).await
+}
+
+fn manage_tokens(db: &Database, command: TokenCommand) -> sheila::Result<()> {
+    let db_error = |e: rusqlite::Error| sheila::Error::io(e.to_string());
+    match command {
+        TokenCommand::Create {
+            name,
+            role,
+            projects,
+        } => {
+            let created = db
+                .create_token(NewToken {
+                    name,
+                    role,
+                    projects,
+                })
+                .map_err(db_error)?;
+            println!("Created token {}", created.token.id);
+            println!("{}", created.secret);
+        }
+        TokenCommand::List => {
+            for token in db.tokens().map_err(db_error)? {
+                let projects = if token.projects.is_empty() {
+                    "all projects".to_string()
+                } else {
+                    token.projects.join(", ")
+                };
+                let last_used = token
+                    .last_used
+                    .map(|time| time.format("%Y-%m-%d %H:%M:%S").to_string())
+                    .unwrap_or_else(|| "never".to_string());
+                println!(
+                    "{}  {:<20} {:<8} {:<24} last used {}",
+                    token.id, token.name, token.role, projects, last_used
+                );
+            }
+        }
+        TokenCommand::Revoke { id } => {
+            if !db.revoke_token(id).map_err(db_error)? {
+                return Err(sheila::Error::invalid_config(format!("No token {}", id)));
+            }
+            println!("Revoked token {}", id);
+        }
+    }
+    Ok(())
+}
+
+fn parse_retention(value: &str) -> Result<Duration, String> {
+    sheila::runners::parse_duration(value).map_err(|e| e.to_string())
+}
+
+fn host_name() -> String {
+    std::env::var("HOSTNAME")
+        .ok()
+        .or_else(|| {
+            std::fs::read_to_string("/etc/hostname")
+                .ok()
+                .map(|name| name.trim().to_string())
+        })
+        .filter(|name| !name.is_empty())
+        .unwrap_or_else(|| "agent".to_string())
+}
@@ -0,0 +1,152 @@
+//! What `GET /metrics` reports, in the Prometheus text exposition format.
+//! Counters cover the runs the server carried out since it came up, and
+//! gauges what it's doing right now.
+
+use std::fmt::Write as _;
+use std::sync::{Arc, Mutex};
+use std::time::Instant;
+
+use sheila::cache::RunSummary;
+
+use crate::runs::RunStatus;
+
+/// What the server's doing right now, as sampled when the metrics are read
+#[derive(Debug, Clone, Copy, Default)]
+pub struct Gauges {
+    pub queued_runs: usize,
+    pub active_runs: usize,
+    pub agents: usize,
+}
+
+#[derive(Debug, Default)]
+struct Counters {
+    runs_passed: u64,
+    runs_failed: u64,
+    runs_cancelled: u64,
+    runs_errored: u64,
+    tests_passed: u64,
+    tests_failed: u64,
+    tests_skipped: u64,
+    flaky_tests: u64,
+    run_seconds: f64,
+    timed_runs: u64,
+}
+
+#[derive(Clone)]
+pub struct ServerMetrics {
+    started: Instant,
+    counters: Arc<Mutex<Counters>>,
+}
+
+impl ServerMetrics {
+    pub fn new() -> Self {
+        Self {
+            started: Instant::now(),
+            counters: Arc::default(),
+        }
+    }
+
+    /// Count a run that finished with `status`, and the tests in it
+    pub fn record_run(&self, status: &RunStatus, summary: Option<&RunSummary>) {
+        let mut counters = self.counters.lock().unwrap_or_else(|e| e.into_inner());
+        match status {
+            RunStatus::Passed => counters.runs_passed += 1,
+            RunStatus::Failed => counters.runs_failed += 1,
+            RunStatus::Cancelled => counters.runs_cancelled += 1,
+            RunStatus::Errored { .. } => counters.runs_errored += 1,
+            RunStatus::Queued | RunStatus::Running => {}
+        }
+        if let Some(summary) = summary {
+            counters.tests_passed += summary.passed_tests as u64;
+            counters.tests_failed += summary.failed_tests as u64;
+            counters.tests_skipped += summary.skipped_tests as u64;
+            counters.flaky_tests += summary.flaky_tests as u64;
+            if let Some(duration) = summary.duration {
+                counters.run_seconds += duration.as_secs_f64();
+                counters.timed_runs += 1;
+            }
+        }
+    }
+
+    /// The metrics in the text exposition format
+    pub fn render(&self, gauges: Gauges) -> String {
+        let counters = self.counters.lock().unwrap_or_else(|e| e.into_inner());
+        let mut out = String::new();
+
+        let mut metric = |name: &str, kind: &str, help: &str, samples: &[(&str, f64)]| {
+            let _ = writeln!(out, "# HELP sheila_{name} {help}");
+            let _ = writeln!(out, "# TYPE sheila_{name} {kind}");
+            for (labels, value) in samples {
+                let _ = writeln!(out, "sheila_{name}{labels} {value}");
+            }
+        };
+
+        metric(
+            "up_seconds",
+            "gauge",
+            "Time since the server came up.",
+            &[("", self.started.elapsed().as_secs_f64())],
+        );
+        metric(
+            "runs_queued",
+            "gauge",
+            "Runs waiting for a free slot.",
+            &[("", gauges.queued_runs as f64)],
+        );
+        metric(
+            "runs_active",
+            "gauge",
+            "Runs being carried out.",
+            &[("", gauges.active_runs as f64)],
+        );
+        metric(
+            "agents",
+            "gauge",
+            "Agents joined to the server.",
+            &[("", gauges.agents as f64)],
+        );
+        metric(
+            "runs_finished_total",
+            "counter",
+            "Runs finished, by outcome.",
+            &[
+                ("{status=\"passed\"}", counters.runs_passed as f64),
+                ("{status=\"failed\"}", counters.runs_failed as f64),
+                ("{status=\"cancelled\"}", counters.runs_cancelled as f64),
+                ("{status=\"errored\"}", counters.runs_errored as f64),
+            ],
+        );
+        metric(
+            "tests_total",
+            "counter",
+            "Tests run, by status.",
+            &[
+                ("{status=\"passed\"}", counters.tests_passed as f64),
+                ("{status=\"failed\"}", counters.tests_failed as f64),
+                ("{status=\"skipped\"}", counters.tests_skipped as f64),
+            ],
+        );
+        metric(
+            "flaky_tests_total",
+            "counter",
+            "Tests that only passed after a retry.",
+            &[("", counters.flaky_tests as f64)],
+        );
+        metric(
+            "run_duration_seconds",
+            "summary",
+            "Wall time of finished runs.",
+            &[
+                ("_sum", counters.run_seconds),
+                ("_count", counters.timed_runs as f64),
+            ],
+        );
+        out
+    }
+}
+
+impl Default for ServerMetrics {
+    fn default() -> Self {
+        Self::new()
+    }
+}
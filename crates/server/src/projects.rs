@@ -0,0 +1,290 @@
+//! The codebases a server tracks. The one it was started in is always
+//! there, under the server's project name, and others are registered
+//! through `POST /projects`, either cloned from their repository into the
+//! data directory's `projects/<name>` or pointed at an existing checkout.
+//!
+//! Runs, history, the dashboard and API tokens are all scoped by project.
+
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::process::Command;
+
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+
+use crate::db::Database;
+use crate::{ApiError, ApiResult, ServerConfig};
+
+/// Branch recorded for projects whose checkout doesn't say
+const DEFAULT_BRANCH: &str = "main";
+
+/// A codebase the server runs the tests of, as listed by `GET /projects`
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Project {
+    pub name: String,
+    /// Where the project is cloned from, and which webhook deliveries are
+    /// for it
+    pub repo_url: Option<String>,
+    /// Branch runs without a revision run the tip of, in projects the
+    /// server cloned
+    pub default_branch: String,
+    /// The project's `sheila.toml`, relative to its root
+    pub config_path: PathBuf,
+    /// The project's checkout on the server
+    pub root: PathBuf,
+    /// Whether the server cloned the project, and so keeps its checkout up
+    /// to date
+    pub cloned: bool,
+    pub created_at: DateTime<Utc>,
+}
+
+impl Project {
+    pub fn config_file(&self) -> PathBuf {
+        self.root.join(&self.config_path)
+    }
+
+    /// Whether `url` is this project's repository, however it's spelled
+    pub fn is_repository(&self, url: &str) -> bool {
+        self.repo_url
+            .as_deref()
+            .is_some_and(|repo_url| normalize_url(repo_url) == normalize_url(url))
+    }
+
+    /// Check out the tip of the default branch, fetched from `origin`. The
+    /// checkout is the server's, so whatever runs left in it, e.g. a
+    /// `Cargo.lock` the repository doesn't have, is overwritten.
+    pub fn update(&self) -> sheila::Result<()> {
+        git(
+            &self.root,
+            &["fetch", "--quiet", "origin", &self.default_branch],
+        )?;
+        git(
+            &self.root,
+            &["checkout", "--quiet", "--force", "--detach", "FETCH_HEAD"],
+        )?;
+        Ok(())
+    }
+}
+
+/// A project to register, as posted to `POST /projects`. The project is
+/// cloned from `repo_url` unless `path` points at a checkout already on
+/// the server.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct NewProject {
+    pub name: String,
+    #[serde(default)]
+    pub repo_url: Option<String>,
+    /// The repository's default branch if not given
+    #[serde(default)]
+    pub default_branch: Option<String>,
+    /// `sheila.toml` if not given
+    #[serde(default)]
+    pub config_path: Option<PathBuf>,
+    #[serde(default)]
+    pub path: Option<PathBuf>,
+}
+
+#[derive(Clone)]
+pub struct ProjectRegistry {
+    db: Database,
+    /// Where registered projects are cloned to
+    dir: PathBuf,
+    default: String,
+}
+
+impl ProjectRegistry {
+    /// The projects in `db`, recording the one `config` serves as the
+    /// default
+    pub fn open(config: &ServerConfig, db: Database) -> sheila::Result<Self> {
+        let root = &config.project_root;
+        let stored = db.project(&config.project_name).map_err(db_error)?;
+        let project = Project {
+            name: config.project_name.clone(),
+            repo_url: git(root, &["remote", "get-url", "origin"]).ok(),
+            default_branch: git(root, &["rev-parse", "--abbrev-ref", "HEAD"])
+                .ok()
+                .filter(|branch| branch != "HEAD")
+                .unwrap_or_else(|| DEFAULT_BRANCH.to_string()),
+            config_path: PathBuf::from("sheila.toml"),
+            root: root.clone(),
+            cloned: false,
+            created_at: stored.map_or_else(Utc::now, |stored| stored.created_at),
+        };
+        db.save_project(&project).map_err(db_error)?;
+        // Runs stored before there were projects are the default project's
+        db.adopt_runs(&project.name).map_err(db_error)?;
+
+        Ok(Self {
+            db,
+            dir: config.projects_dir(),
+            default: project.name,
+        })
+    }
+
+    /// Name of the project the server was started in
+    pub fn default_name(&self) -> &str {
+        &self.default
+    }
+
+    /// Project `name`, or the default project if `None`
+    pub fn get(&self, name: Option<&str>) -> ApiResult<Project> {
+        let name = name.unwrap_or(&self.default);
+        self.db
+            .project(name)?
+            .ok_or_else(|| ApiError::NotFound(format!("No project '{}'", name)))
+    }
+
+    /// Every project, in order of name
+    pub fn list(&self) -> ApiResult<Vec<Project>> {
+        Ok(self.db.projects()?)
+    }
+
+    /// The project whose repository is one of `urls`
+    pub fn find_repository(&self, urls: &[String]) -> ApiResult<Option<Project>> {
+        Ok(self
+            .list()?
+            .into_iter()
+            .find(|project| urls.iter().any(|url| project.is_repository(url))))
+    }
+
+    /// Register `new`, cloning it if it isn't on the server yet. Cloning
+    /// blocks until it's done.
+    pub fn register(&self, new: NewProject) -> ApiResult<Project> {
+        let valid_name = !new.name.is_empty()
+            && new
+                .name
+                .chars()
+                .all(|c| c.is_ascii_alphanumeric() || "-_.".contains(c))
+            && !new.name.starts_with('.');
+        if !valid_name {
+            return Err(ApiError::BadRequest(format!(
+                "Invalid project name '{}', use letters, digits, '-', '_' and '.'",
+                new.name
+            )));
+        }
+        if self.db.project(&new.name)?.is_some() {
+            return Err(ApiError::Conflict(format!(
+                "There's already a project '{}'",
+                new.name
+            )));
+        }
+        let config_path = new
+            .config_path
+            .unwrap_or_else(|| PathBuf::from("sheila.toml"));
+        if config_path.is_absolute() || config_path.components().any(|c| c.as_os_str() == "..") {
+            return Err(ApiError::BadRequest(
+                "The config path must be inside the project".into(),
+            ));
+        }
+
+        let (root, cloned) = match (new.path, &new.repo_url) {
+            (Some(path), _) => {
+                let root = path.canonicalize().map_err(|e| {
+                    ApiError::BadRequest(format!("Can't use {}: {}", path.display(), e))
+                })?;
+                if !root.is_dir() {
+                    return Err(ApiError::BadRequest(format!(
+                        "{} isn't a directory",
+                        root.display()
+                    )));
+                }
+                (root, false)
+            }
+            (None, Some(repo_url)) => {
+                let root = self.dir.join(&new.name);
+                if root.exists() {
+                    return Err(ApiError::Conflict(format!(
+                        "{} is already there",
+                        root.display()
+                    )));
+                }
+                fs::create_dir_all(&self.dir)?;
+                let mut args = vec!["clone", "--quiet"];
+                if let Some(branch) = &new.default_branch {
+                    args.extend(["--branch", branch]);
+                }
+                let destination = root.to_string_lossy();
+                args.extend(["--", repo_url, &destination]);
+                tracing::info!("Cloning {} into {}", repo_url, root.display());
+                git(&self.dir, &args).map_err(|e| {
+                    let _ = fs::remove_dir_all(&root);
+                    ApiError::BadRequest(e.to_string())
+                })?;
+                (root, true)
+            }
+            (None, None) => {
+                return Err(ApiError::BadRequest(
+                    "Projects need a repo_url or a path".into(),
+                ));
+            }
+        };
+
+        let default_branch = new.default_branch.unwrap_or_else(|| {
+            git(&root, &["rev-parse", "--abbrev-ref", "HEAD"])
+                .ok()
+                .filter(|branch| branch != "HEAD")
+                .unwrap_or_else(|| DEFAULT_BRANCH.to_string())
+        });
+        let project = Project {
+            name: new.name,
+            repo_url: new.repo_url,
+            default_branch,
+            config_path,
+            root,
+            cloned,
+            created_at: Utc::now(),
+        };
+        self.db.save_project(&project)?;
+        tracing::info!("Registered project {}", project.name);
+        Ok(project)
+    }
+
+    /// Forget project `name`, removing its checkout if the server cloned
+    /// it. Its stored runs are kept.
+    pub fn remove(&self, name: &str) -> ApiResult<Project> {
+        if name == self.default {
+            return Err(ApiError::Conflict(format!(
+                "'{}' is the server's own project",
+                name
+            )));
+        }
+        let project = self.get(Some(name))?;
+        self.db.delete_project(name)?;
+        if project.cloned && project.root.starts_with(&self.dir) {
+            fs::remove_dir_all(&project.root)?;
+        }
+        tracing::info!("Removed project {}", name);
+        Ok(project)
+    }
+}
+
+/// Run git in `dir`, returning what it printed
+fn git(dir: &Path, args: &[&str]) -> sheila::Result<String> {
+    let output = Command::new("git").current_dir(dir).args(args).output()?;
+    if output.status.success() {
+        return Ok(String::from_utf8_lossy(&output.stdout).trim().to_string());
+    }
+    Err(sheila::Error::generic(format!(
+        "git {} failed: {}",
+        args.join(" "),
+        String::from_utf8_lossy(&output.stderr).trim()
+    )))
+}
+
+/// `url` as host and path, so the HTTPS and SSH URLs of a repository
+/// compare equal
+fn normalize_url(url: &str) -> String {
+    let url = url.trim().trim_end_matches('/');
+    let url = url.strip_suffix(".git").unwrap_or(url);
+    let url = match url.split_once("://") {
+        Some((_, rest)) => rest.to_string(),
+        // `git@host:owner/repo`
+        None => url.replacen(':', "/", 1),
+    };
+    let url = url.split_once('@').map_or(url.as_str(), |(_, rest)| rest);
+    url.to_lowercase()
+}
+
+fn db_error(e: rusqlite::Error) -> sheila::Error {
+    sheila::Error::io(e.to_string())
+}
@@ -0,0 +1,103 @@
+use std::cmp::Reverse;
+use std::fs;
+use std::path::PathBuf;
+
+use serde::{Deserialize, Serialize};
+use sheila::cache::RunSummary;
+use sheila::reporting::{CsvReporter, HtmlReporter, JsonReporter, TextReporter};
+use sheila::runners::RunResult;
+use sheila::{Reporter, Result, TestReport};
+use uuid::Uuid;
+
+/// Finished runs' results, one JSON file per run named after its id
+#[derive(Debug, Clone)]
+pub struct ReportStore {
+    dir: PathBuf,
+}
+
+impl ReportStore {
+    pub fn open<P: Into<PathBuf>>(dir: P) -> Result<Self> {
+        let dir = dir.into();
+        fs::create_dir_all(&dir)?;
+        Ok(Self { dir })
+    }
+
+    fn path(&self, id: Uuid) -> PathBuf {
+        self.dir.join(format!("{}.json", id))
+    }
+
+    pub fn save(&self, result: &RunResult) -> Result<()> {
+        fs::write(self.path(result.id), serde_json::to_string(result)?)?;
+        Ok(())
+    }
+
+    pub fn load(&self, id: Uuid) -> Result<Option<RunResult>> {
+        let path = self.path(id);
+        if !path.exists() {
+            return Ok(None);
+        }
+        Ok(Some(serde_json::from_str(&fs::read_to_string(path)?)?))
+    }
+
+    /// Summaries of the stored runs, newest first. Files that can't be read
+    /// are skipped.
+    pub fn list(&self) -> Result<Vec<RunSummary>> {
+        let mut summaries = Vec::new();
+        for entry in fs::read_dir(&self.dir)? {
+            let path = entry?.path();
+            if path.extension().is_none_or(|extension| extension != "json") {
+                continue;
+            }
+            let Ok(result) = fs::read_to_string(&path)
+                .map_err(sheila::Error::from)
+                .and_then(|content| Ok(serde_json::from_str::<RunResult>(&content)?))
+            else {
+                continue;
+            };
+            summaries.push(RunSummary::from(&result));
+        }
+        summaries.sort_by_key(|summary| Reverse(summary.start_time));
+        Ok(summaries)
+    }
+
+    /// Remove the stored run `id`, returning whether there was one
+    pub fn delete(&self, id: Uuid) -> Result<bool> {
+        let path = self.path(id);
+        if !path.exists() {
+            return Ok(false);
+        }
+        fs::remove_file(path)?;
+        Ok(true)
+    }
+}
+
+/// Formats a stored run can be rendered in
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum ReportKind {
+    #[default]
+    Json,
+    Html,
+    Csv,
+    Text,
+}
+
+impl ReportKind {
+    pub fn render(self, result: &RunResult) -> Result<TestReport> {
+        match self {
+            ReportKind::Json => JsonReporter::new().generate(result),
+            ReportKind::Html => HtmlReporter::new().generate(result),
+            ReportKind::Csv => CsvReporter::new().generate(result),
+            ReportKind::Text => TextReporter::new().generate(result),
+        }
+    }
+
+    pub fn content_type(self) -> &'static str {
+        match self {
+            ReportKind::Json => "application/json",
+            ReportKind::Html => "text/html; charset=utf-8",
+            ReportKind::Csv => "text/csv",
+            ReportKind::Text => "text/plain; charset=utf-8",
+        }
+    }
+}
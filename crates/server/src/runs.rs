@@ -0,0 +1,670 @@
+use std::cmp::Reverse;
+use std::collections::{HashMap, HashSet};
+use std::path::Path;
+use std::process::Command;
+use std::sync::Arc;
+use std::sync::mpsc;
+use std::time::Duration;
+
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+use sheila::cache::RunSummary;
+use sheila::runners::{CargoRunnerConfig, CargoTestRunner, RunResult, Shard, TagExpr, TestFilter};
+use sheila::schemas::{ExecutableBuilder, PackageSelection};
+use sheila::{CancelReason, CancellationToken, EventSender, ProcessOutput, RunnerConfig};
+use sheila_cli::discovery::{TestDiscovery, TestFile};
+use tokio::sync::{RwLock, broadcast};
+use uuid::Uuid;
+
+use crate::agents::AgentRegistry;
+use crate::artifacts::ArtifactStore;
+use crate::db::Database;
+use crate::events::RunEvent;
+use crate::metrics::ServerMetrics;
+use crate::projects::{Project, ProjectRegistry};
+use crate::reports::ReportStore;
+use crate::{ApiError, ApiResult};
+
+/// What to run, as posted to `POST /runs`. Every field is optional, and an
+/// empty spec runs every test in the workspace.
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+#[serde(default)]
+pub struct RunSpec {
+    /// Project whose tests to run, the server's own if not given
+    pub project: Option<String>,
+    /// Names of the tests to run, or parts of their paths
+    pub targets: Vec<String>,
+    /// Match targets against whole path segments rather than any substring
+    pub exact: bool,
+    /// Only run tests whose tags match, e.g. `"db & !slow"`. Targets are
+    /// then read the way `sheila test` reads them, as files or test names.
+    pub tags: Option<TagExpr>,
+    /// Packages to run the tests of, instead of the whole workspace
+    pub packages: Vec<String>,
+    /// Split the run into this many shards and have agents run them,
+    /// rather than running it on the server
+    pub shards: Option<usize>,
+    /// Queued runs with a higher priority start first
+    pub priority: i32,
+    pub fail_fast: bool,
+    pub retries: Option<u32>,
+    /// Number of test executables to run at the same time
+    pub jobs: Option<usize>,
+    /// Timeout in seconds for each test executable
+    pub timeout: Option<u64>,
+    /// Commit to check out in the project before running, fetched from
+    /// `origin` if the clone doesn't have it. The checkout is shared, so
+    /// runs with a revision run on their own rather than alongside other
+    /// runs of the same project. Runs of projects the server cloned run the
+    /// tip of the default branch if not given.
+    pub revision: Option<String>,
+}
+
+impl RunSpec {
+    /// Whether running this in `project` checks out another commit in its
+    /// tree first
+    pub fn moves_tree(&self, project: &Project) -> bool {
+        self.revision.is_some() || project.cloned
+    }
+
+    pub fn runner_config(&self) -> RunnerConfig {
+        RunnerConfig {
+            fail_fast: self.fail_fast,
+            retries: self.retries.unwrap_or_default(),
+            tag_filter: self.tags.clone(),
+            ..Default::default()
+        }
+    }
+
+    pub fn cargo_config(&self) -> CargoRunnerConfig {
+        let defaults = CargoRunnerConfig::default();
+        CargoRunnerConfig {
+            filters: self
+                .targets
+                .iter()
+                .map(|target| TestFilter {
+                    pattern: target.clone(),
+                    exact: self.exact,
+                })
+                .collect(),
+            jobs: self.jobs.unwrap_or(defaults.jobs),
+            executable_timeout: self
+                .timeout
+                .map(Duration::from_secs)
+                .or(defaults.executable_timeout),
+            ..defaults
+        }
+    }
+
+    fn packages(&self) -> PackageSelection {
+        PackageSelection {
+            packages: self.packages.clone(),
+            ..Default::default()
+        }
+    }
+
+    /// Filters for the tests discovery finds in the project at `root` with
+    /// tags matching `tags`, narrowed down to the targets if there are any
+    fn tagged_filters(&self, tags: &TagExpr, root: &Path) -> sheila::Result<Vec<TestFilter>> {
+        let discovery_error = |e: color_eyre::Report| {
+            sheila::Error::invalid_config(format!("Failed to discover tests: {}", e))
+        };
+        let discovery = TestDiscovery::in_dir(root).map_err(discovery_error)?;
+        let files = discovery.discover(root).map_err(discovery_error)?;
+
+        let targets: Vec<&str> = self.targets.iter().map(String::as_str).collect();
+        let matched = discovery
+            .filter_tests(files, &targets, Some(tags), None)
+            .map_err(discovery_error)?;
+        let filters: Vec<TestFilter> = matched.iter().flat_map(TestFile::test_filters).collect();
+
+        if filters.is_empty() {
+            return Err(sheila::Error::invalid_config(format!(
+                "No tests match the tags '{}'",
+                tags
+            )));
+        }
+        Ok(filters)
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(tag = "state", rename_all = "snake_case")]
+pub enum RunStatus {
+    /// Waiting for a free slot
+    Queued,
+    Running,
+    Passed,
+    Failed,
+    Cancelled,
+    /// The run couldn't be carried out, e.g. the build failed
+    Errored {
+        message: String,
+    },
+}
+
+impl RunStatus {
+    pub fn is_finished(&self) -> bool {
+        !matches!(self, RunStatus::Queued | RunStatus::Running)
+    }
+}
+
+/// A run submitted through the API, as reported by `GET /runs/{id}`
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct RunRecord {
+    pub id: Uuid,
+    pub spec: RunSpec,
+    pub status: RunStatus,
+    pub created_at: DateTime<Utc>,
+    pub started_at: Option<DateTime<Utc>>,
+    pub finished_at: Option<DateTime<Utc>>,
+    /// Headline numbers, once the run finished
+    pub summary: Option<RunSummary>,
+}
+
+/// Events a subscriber may fall behind by before it starts missing some
+const EVENT_CAPACITY: usize = 1024;
+
+struct ActiveRun {
+    record: RunRecord,
+    cancel: CancellationToken,
+    events: broadcast::Sender<RunEvent>,
+}
+
+/// The runs submitted since the server came up. Runs wait in a queue, in
+/// order of priority and then submission, until one of the
+/// `max_concurrent` slots is free. Runs build and run in their project's
+/// checkout, which runs of the same project share. Finished runs' results
+/// go to the [`ReportStore`] and the [`Database`], and the files their
+/// tests attached to the [`ArtifactStore`].
+#[derive(Clone)]
+pub struct RunManager {
+    runs: Arc<RwLock<HashMap<Uuid, ActiveRun>>>,
+    projects: ProjectRegistry,
+    reports: ReportStore,
+    db: Database,
+    artifacts: ArtifactStore,
+    agents: AgentRegistry,
+    metrics: ServerMetrics,
+    max_concurrent: usize,
+}
+
+impl RunManager {
+    pub fn new(
+        projects: ProjectRegistry,
+        reports: ReportStore,
+        db: Database,
+        artifacts: ArtifactStore,
+        agents: AgentRegistry,
+        metrics: ServerMetrics,
+        max_concurrent: usize,
+    ) -> Self {
+        Self {
+            runs: Arc::default(),
+            projects,
+            reports,
+            db,
+            artifacts,
+            agents,
+            metrics,
+            max_concurrent: max_concurrent.max(1),
+        }
+    }
+
+    /// Queue `spec`, starting it straight away if a slot is free
+    pub async fn submit(&self, mut spec: RunSpec) -> ApiResult<RunRecord> {
+        spec.project = Some(self.projects.get(spec.project.as_deref())?.name);
+        if let Some(revision) = &spec.revision {
+            check_revision(revision).map_err(|e| ApiError::BadRequest(e.to_string()))?;
+        }
+        let record = RunRecord {
+            id: Uuid::new_v4(),
+            spec,
+            status: RunStatus::Queued,
+            created_at: Utc::now(),
+            started_at: None,
+            finished_at: None,
+            summary: None,
+        };
+        let (events, _) = broadcast::channel(EVENT_CAPACITY);
+        self.runs.write().await.insert(
+            record.id,
+            ActiveRun {
+                record: record.clone(),
+                cancel: CancellationToken::new(),
+                events,
+            },
+        );
+
+        self.dispatch().await;
+        Ok(self.get(record.id).await.unwrap_or(record))
+    }
+
+    /// Start queued runs until every slot is taken. A run that moves its
+    /// project's shared tree, to its revision or the tip of a cloned
+    /// project, only starts when nothing else of the project is running,
+    /// and nothing of the project starts alongside it. A run that has to
+    /// wait holds back the later runs of its project, but not those of
+    /// other projects.
+    async fn dispatch(&self) {
+        let mut runs = self.runs.write().await;
+        // Projects with runs going, and whether one of them moves the tree
+        let mut busy: HashMap<String, bool> = HashMap::new();
+        for record in runs
+            .values()
+            .map(|run| &run.record)
+            .filter(|record| record.status == RunStatus::Running)
+        {
+            let moves_tree = self
+                .projects
+                .get(record.spec.project.as_deref())
+                .is_ok_and(|project| record.spec.moves_tree(&project));
+            *busy
+                .entry(record.spec.project.clone().unwrap_or_default())
+                .or_default() |= moves_tree;
+        }
+        let running = runs
+            .values()
+            .filter(|run| run.record.status == RunStatus::Running)
+            .count();
+        let mut free = self.max_concurrent.saturating_sub(running);
+        let mut held: HashSet<String> = HashSet::new();
+
+        for id in queue_order(&runs) {
+            if free == 0 {
+                break;
+            }
+            let Some(run) = runs.get_mut(&id) else {
+                continue;
+            };
+            let project = match self.projects.get(run.record.spec.project.as_deref()) {
+                Ok(project) => project,
+                Err(e) => {
+                    tracing::warn!("Can't start run {}: {}", id, e);
+                    let status = RunStatus::Errored {
+                        message: e.to_string(),
+                    };
+                    self.metrics.record_run(&status, None);
+                    close(run, status);
+                    continue;
+                }
+            };
+            let key = run.record.spec.project.clone().unwrap_or_default();
+            if held.contains(&key) {
+                continue;
+            }
+            let moves_tree = run.record.spec.moves_tree(&project);
+            if busy
+                .get(&key)
+                .is_some_and(|exclusive| *exclusive || moves_tree)
+            {
+                held.insert(key);
+                continue;
+            }
+            busy.insert(key, moves_tree);
+            free -= 1;
+
+            run.record.status = RunStatus::Running;
+            run.record.started_at = Some(Utc::now());
+            let _ = run.events.send(RunEvent::RunStarted);
+            tracing::info!("Starting run {}", id);
+            self.launch(
+                id,
+                project,
+                run.record.spec.clone(),
+                run.cancel.clone(),
+                run.events.clone(),
+            );
+        }
+    }
+
+    fn launch(
+        &self,
+        id: Uuid,
+        project: Project,
+        spec: RunSpec,
+        cancel: CancellationToken,
+        events: broadcast::Sender<RunEvent>,
+    ) {
+        let runs = self.clone();
+        tokio::spawn(async move {
+            let root = project.root.clone();
+            let revision = spec.revision.clone();
+            let checked_out = tokio::task::spawn_blocking(move || match revision {
+                Some(revision) => checkout(&project.root, &revision),
+                None if project.cloned => project.update(),
+                None => Ok(()),
+            })
+            .await
+            .unwrap_or_else(|e| Err(sheila::Error::generic(e.to_string())));
+            let outcome = if let Err(e) = checked_out {
+                Err(e)
+            } else if spec.shards.is_some() {
+                runs.agents
+                    .run_sharded(id, &spec, &root, &cancel, events)
+                    .await
+            } else {
+                let (output_tx, output_rx) = mpsc::channel::<ProcessOutput>();
+                let forward = tokio::task::spawn_blocking(move || {
+                    for output in output_rx {
+                        // No subscribers is fine, the events are just dropped
+                        let _ = events.send(output.into());
+                    }
+                });
+
+                let token = cancel.clone();
+                let output_tx = EventSender::new(output_tx).with_run_id(id);
+                let outcome = tokio::task::spawn_blocking(move || {
+                    execute(&spec, &root, &token, output_tx, None)
+                })
+                .await
+                .unwrap_or_else(|e| Err(sheila::Error::generic(e.to_string())));
+                let _ = forward.await;
+                outcome
+            };
+            runs.finish(id, outcome, &cancel).await;
+            runs.dispatch().await;
+        });
+    }
+
+    async fn finish(
+        &self,
+        id: Uuid,
+        outcome: sheila::Result<RunResult>,
+        cancel: &CancellationToken,
+    ) {
+        let (status, summary) = match outcome {
+            Ok(mut result) => {
+                result.id = id;
+                let spec = self
+                    .runs
+                    .read()
+                    .await
+                    .get(&id)
+                    .map(|run| run.record.spec.clone())
+                    .unwrap_or_default();
+                // Sharded results come from agents, whose paths mean nothing
+                // here; they upload their files instead
+                if spec.shards.is_none() {
+                    self.artifacts.ingest(&mut result);
+                }
+                if let Err(e) = self.reports.save(&result) {
+                    tracing::warn!("Failed to store the result of run {}: {}", id, e);
+                }
+                let project = spec
+                    .project
+                    .unwrap_or_else(|| self.projects.default_name().to_string());
+                if let Err(e) = self.db.record(&result, &project, spec.revision.as_deref()) {
+                    tracing::warn!("Failed to record run {} in the database: {}", id, e);
+                }
+                let status = if cancel.reason() == Some(CancelReason::User) {
+                    RunStatus::Cancelled
+                } else if result.all_passed() {
+                    RunStatus::Passed
+                } else {
+                    RunStatus::Failed
+                };
+                (status, Some(RunSummary::from(&result)))
+            }
+            Err(e) => (
+                RunStatus::Errored {
+                    message: e.to_string(),
+                },
+                None,
+            ),
+        };
+        tracing::info!("Run {} finished: {:?}", id, status);
+        self.metrics.record_run(&status, summary.as_ref());
+
+        if let Some(run) = self.runs.write().await.get_mut(&id) {
+            run.record.summary = summary;
+            close(run, status);
+        }
+    }
+
+    /// Every run, or just `project`'s, newest first
+    pub async fn list(&self, project: Option<&str>) -> Vec<RunRecord> {
+        let mut records: Vec<RunRecord> = self
+            .runs
+            .read()
+            .await
+            .values()
+            .map(|run| run.record.clone())
+            .filter(|record| project.is_none() || record.spec.project.as_deref() == project)
+            .collect();
+        records.sort_by_key(|record| Reverse(record.created_at));
+        records
+    }
+
+    /// The queued runs, or just `project`'s, in the order they'll start in
+    pub async fn queue(&self, project: Option<&str>) -> Vec<RunRecord> {
+        let runs = self.runs.read().await;
+        queue_order(&runs)
+            .into_iter()
+            .filter_map(|id| runs.get(&id).map(|run| run.record.clone()))
+            .filter(|record| project.is_none() || record.spec.project.as_deref() == project)
+            .collect()
+    }
+
+    pub async fn get(&self, id: Uuid) -> Option<RunRecord> {
+        self.runs
+            .read()
+            .await
+            .get(&id)
+            .map(|run| run.record.clone())
+    }
+
+    /// Follow run `id`'s events from now on. The run's record is returned
+    /// alongside, and a subscriber to a run that already finished receives
+    /// nothing further.
+    pub async fn subscribe(&self, id: Uuid) -> Option<(RunRecord, broadcast::Receiver<RunEvent>)> {
+        self.runs
+            .read()
+            .await
+            .get(&id)
+            .map(|run| (run.record.clone(), run.events.subscribe()))
+    }
+
+    /// Stop run `id`, or take it off the queue if it hasn't started. Tests
+    /// it already ran keep their results.
+    pub async fn cancel(&self, id: Uuid) -> ApiResult<RunRecord> {
+        let mut runs = self.runs.write().await;
+        let run = runs
+            .get_mut(&id)
+            .ok_or_else(|| ApiError::NotFound(format!("No run {}", id)))?;
+        match run.record.status {
+            RunStatus::Queued => {
+                tracing::info!("Run {} cancelled before it started", id);
+                close(run, RunStatus::Cancelled);
+            }
+            RunStatus::Running => run.cancel.cancel(CancelReason::User),
+            _ => return Err(ApiError::Conflict(format!("Run {} already finished", id))),
+        }
+        Ok(run.record.clone())
+    }
+
+    /// Change the priority of queued run `id`
+    pub async fn reprioritize(&self, id: Uuid, priority: i32) -> ApiResult<RunRecord> {
+        let mut runs = self.runs.write().await;
+        let run = runs
+            .get_mut(&id)
+            .ok_or_else(|| ApiError::NotFound(format!("No run {}", id)))?;
+        if run.record.status != RunStatus::Queued {
+            return Err(ApiError::Conflict(format!(
+                "Run {} is no longer queued",
+                id
+            )));
+        }
+        run.record.spec.priority = priority;
+        Ok(run.record.clone())
+    }
+}
+
+/// Ids of the queued runs, highest priority first and then oldest first
+fn queue_order(runs: &HashMap<Uuid, ActiveRun>) -> Vec<Uuid> {
+    let mut queued: Vec<&RunRecord> = runs
+        .values()
+        .map(|run| &run.record)
+        .filter(|record| record.status == RunStatus::Queued)
+        .collect();
+    queued.sort_by_key(|record| (Reverse(record.spec.priority), record.created_at));
+    queued.into_iter().map(|record| record.id).collect()
+}
+
+/// Mark `run` finished with `status` and tell its subscribers
+fn close(run: &mut ActiveRun, status: RunStatus) {
+    run.record.status = status;
+    run.record.finished_at = Some(Utc::now());
+    let _ = run.events.send(RunEvent::RunFinished {
+        status: run.record.status.clone(),
+        summary: run.record.summary.clone(),
+    });
+}
+
+/// Fail unless git can only take `revision` as a revision, rather than as
+/// an option or several arguments
+pub fn check_revision(revision: &str) -> sheila::Result<()> {
+    if revision.is_empty()
+        || revision.starts_with('-')
+        || revision
+            .chars()
+            .any(|c| c.is_whitespace() || c.is_control())
+    {
+        return Err(sheila::Error::generic(format!(
+            "Invalid revision {:?}",
+            revision
+        )));
+    }
+    Ok(())
+}
+
+/// Check out `revision` in the checkout at `root`, fetching it from
+/// `origin` first if it isn't there
+pub fn checkout(root: &Path, revision: &str) -> sheila::Result<()> {
+    check_revision(revision)?;
+    let git = |args: &[&str]| -> sheila::Result<()> {
+        let output = Command::new("git").current_dir(root).args(args).output()?;
+        if output.status.success() {
+            return Ok(());
+        }
+        Err(sheila::Error::generic(format!(
+            "git {} failed: {}",
+            args.join(" "),
+            String::from_utf8_lossy(&output.stderr).trim()
+        )))
+    };
+    let commit = format!("{}^{{commit}}", revision);
+    if git(&["cat-file", "-e", &commit]).is_err() {
+        git(&["fetch", "--quiet", "origin", revision])?;
+    }
+    git(&["checkout", "--quiet", "--detach", revision])
+}
+
+/// Build the test executables `spec` selects in the project at `root` and
+/// run them, or just `shard` of their tests, sending their output to
+/// `output_tx` as it happens
+pub fn execute(
+    spec: &RunSpec,
+    root: &Path,
+    cancel: &CancellationToken,
+    output_tx: EventSender,
+    shard: Option<Shard>,
+) -> sheila::Result<RunResult> {
+    let mut cargo_config = spec.cargo_config();
+    if let Some(tags) = &spec.tags {
+        cargo_config.filters = spec.tagged_filters(tags, root)?;
+    }
+    let executables = ExecutableBuilder::new(None, None, vec![])
+        .with_packages(spec.packages())
+        .in_dir(root)
+        .exec()?;
+    let runner_config = RunnerConfig {
+        shard,
+        ..spec.runner_config()
+    };
+    CargoTestRunner::new_with_output(runner_config, output_tx)
+        .with_cargo_config(cargo_config)
+        .execute_tests_with_cancel(&executables, cancel)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use sheila::suite::SuiteResult;
+    use sheila::test::TestResult;
+    use sheila::{Artifact, TestMetadata};
+
+    #[test]
+    fn test_revisions_that_git_could_take_as_options_are_rejected() {
+        for revision in ["--upload-pack=touch pwned", "-b", "main branch", "a\nb", ""] {
+            assert!(check_revision(revision).is_err(), "{:?}", revision);
+        }
+        for revision in ["main", "v1.2.0", "4f2a9c1", "origin/feature-x", "HEAD~2"] {
+            assert!(check_revision(revision).is_ok(), "{:?}", revision);
+        }
+    }
+
+    #[test]
+    fn test_checkout_happens_in_the_given_root() {
+        let root = std::env::temp_dir().join(format!("sheila-checkout-{}", Uuid::new_v4()));
+        std::fs::create_dir_all(&root).unwrap();
+        let git = |args: &[&str]| {
+            let output = Command::new("git")
+                .current_dir(&root)
+                .args(["-c", "user.name=test", "-c", "user.email=test@example.com"])
+                .args(args)
+                .output()
+                .unwrap();
+            assert!(output.status.success(), "git {:?}", args);
+            String::from_utf8(output.stdout).unwrap().trim().to_string()
+        };
+        git(&["init", "--quiet"]);
+        git(&["commit", "--quiet", "--allow-empty", "-m", "first"]);
+        let first = git(&["rev-parse", "HEAD"]);
+        git(&["commit", "--quiet", "--allow-empty", "-m", "second"]);
+
+        checkout(&root, &first).unwrap();
+        assert_eq!(git(&["rev-parse", "HEAD"]), first);
+        std::fs::remove_dir_all(&root).unwrap();
+    }
+
+    #[tokio::test]
+    async fn test_sharded_results_dont_pull_in_server_files() {
+        let root = std::env::temp_dir().join(format!("sheila-ingest-{}", Uuid::new_v4()));
+        std::fs::create_dir_all(&root).unwrap();
+        let state = crate::AppState::new(crate::ServerConfig::new(root.clone())).unwrap();
+        let outside = std::env::temp_dir().join(format!("sheila-secret-{}", Uuid::new_v4()));
+        std::fs::write(&outside, "not for agents").unwrap();
+
+        let spec = RunSpec {
+            shards: Some(1),
+            ..RunSpec::default()
+        };
+        let record = state.runs.submit(spec).await.unwrap();
+        let mut test_result = TestResult::new(
+            Uuid::new_v4(),
+            "leaks".to_string(),
+            TestMetadata::new("leaks".to_string()),
+        );
+        test_result.artifacts.push(Artifact {
+            name: "secret".to_string(),
+            path: Some(outside.clone()),
+            text: None,
+        });
+        let mut suite_result = SuiteResult::new(
+            Uuid::new_v4(),
+            "suite".to_string(),
+            TestMetadata::new("suite".to_string()),
+        );
+        suite_result.add_test_result(test_result);
+        let mut result = RunResult::new(RunnerConfig::default());
+        result.add_suite_result(suite_result);
+
+        let cancel = CancellationToken::new();
+        state.runs.finish(record.id, Ok(result), &cancel).await;
+        assert!(state.db.run_artifacts(record.id).unwrap().is_empty());
+
+        std::fs::remove_file(&outside).unwrap();
+        std::fs::remove_dir_all(&root).unwrap();
+    }
+}
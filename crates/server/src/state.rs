@@ -0,0 +1,90 @@
+use std::sync::Arc;
+
+use crate::ServerConfig;
+use crate::agents::AgentRegistry;
+use crate::artifacts::ArtifactStore;
+use crate::db::Database;
+use uuid::Uuid;
+
+use crate::metrics::ServerMetrics;
+use crate::projects::ProjectRegistry;
+use crate::reports::ReportStore;
+use crate::runs::RunManager;
+use crate::{ApiResult, webhooks};
+
+/// What the API's handlers share
+#[derive(Clone)]
+pub struct AppState {
+    pub config: Arc<ServerConfig>,
+    pub projects: ProjectRegistry,
+    pub runs: RunManager,
+    pub reports: ReportStore,
+    pub db: Database,
+    pub artifacts: ArtifactStore,
+    pub agents: AgentRegistry,
+    pub metrics: ServerMetrics,
+}
+
+impl AppState {
+    pub fn new(config: ServerConfig) -> sheila::Result<Self> {
+        let reports = ReportStore::open(config.reports_dir())?;
+        let db = Database::open(config.database_path())?;
+        let projects = ProjectRegistry::open(&config, db.clone())?;
+        import_reports(&reports, &db, projects.default_name())?;
+        let artifacts =
+            ArtifactStore::open(config.artifacts_dir(), db.clone(), config.artifact_limits)?;
+        let agents = AgentRegistry::new();
+        let metrics = ServerMetrics::new();
+        // Rules are read again for every delivery, this just catches
+        // mistakes in them early
+        let default = projects
+            .get(None)
+            .map_err(|e| sheila::Error::generic(e.to_string()))?;
+        let webhooks = webhooks::load_rules(&default.config_file())?;
+        if !webhooks.is_empty() {
+            tracing::info!("Loaded {} webhook rules", webhooks.len());
+        }
+        Ok(Self {
+            runs: RunManager::new(
+                projects.clone(),
+                reports.clone(),
+                db.clone(),
+                artifacts.clone(),
+                agents.clone(),
+                metrics.clone(),
+                config.max_concurrent_runs,
+            ),
+            projects,
+            reports,
+            db,
+            artifacts,
+            agents,
+            metrics,
+            config: Arc::new(config),
+        })
+    }
+
+    /// The project run `id` belongs to, if it's one the server knows
+    pub async fn run_project(&self, id: Uuid) -> ApiResult<Option<String>> {
+        if let Some(record) = self.runs.get(id).await {
+            return Ok(record.spec.project);
+        }
+        Ok(self.db.run_project(id)?)
+    }
+}
+
+/// Add stored runs the database doesn't know about yet, e.g. ones stored
+/// before it existed, as runs of `project`
+fn import_reports(reports: &ReportStore, db: &Database, project: &str) -> sheila::Result<()> {
+    let db_error = |e: rusqlite::Error| sheila::Error::io(e.to_string());
+    for summary in reports.list()? {
+        if db.contains(summary.id).map_err(db_error)? {
+            continue;
+        }
+        if let Some(result) = reports.load(summary.id)? {
+            db.record(&result, project, None).map_err(db_error)?;
+            tracing::info!("Imported run {} into the database", summary.id);
+        }
+    }
+    Ok(())
+}
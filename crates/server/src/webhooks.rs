@@ -0,0 +1,467 @@
+//! Runs triggered by a repository's webhooks, which turn the server into a
+//! small CI for the projects it serves.
+//!
+//! Deliveries are for the project whose `repo_url` is the repository they
+//! came from, and those from a repository no project has are turned away.
+//! Rules go in that
+//! project's config file, `sheila.toml` unless it was registered with
+//! another, one `[[server.webhooks]]` table each, and are read for every
+//! delivery. Every rule an event matches queues a run of its `spec` at the
+//! event's commit:
+//!
+//! ```toml
+//! [[server.webhooks]]
+//! name = "main"
+//! events = ["push", "pull_request"]
+//! branches = ["main", "release/*"]
+//!
+//! [server.webhooks.spec]
+//! tags = "!slow"
+//! priority = 10
+//! ```
+
+use std::path::Path;
+
+use axum::http::HeaderMap;
+use globset::Glob;
+use hmac::{Hmac, Mac};
+use serde::{Deserialize, Serialize};
+use sha2::Sha256;
+
+use crate::runs::RunSpec;
+
+/// The kinds of repository event a rule can trigger on
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum EventKind {
+    /// Commits pushed to a branch
+    Push,
+    /// A tag pushed
+    Tag,
+    /// A pull or merge request opened or updated
+    PullRequest,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct WebhookRule {
+    pub name: String,
+    #[serde(default = "default_events")]
+    pub events: Vec<EventKind>,
+    /// Branches pushes must be to, and pull requests must target, as globs.
+    /// Every branch if empty.
+    #[serde(default)]
+    pub branches: Vec<String>,
+    /// Tags a pushed tag must match, as globs. Every tag if empty.
+    #[serde(default)]
+    pub tags: Vec<String>,
+    /// The run to queue. Its `revision` is set to the event's commit.
+    #[serde(default)]
+    pub spec: RunSpec,
+}
+
+fn default_events() -> Vec<EventKind> {
+    vec![EventKind::Push, EventKind::PullRequest]
+}
+
+impl WebhookRule {
+    pub fn matches(&self, event: &RepoEvent) -> bool {
+        if !self.events.contains(&event.kind) {
+            return false;
+        }
+        let patterns = match event.kind {
+            EventKind::Tag => &self.tags,
+            EventKind::Push | EventKind::PullRequest => &self.branches,
+        };
+        patterns.is_empty()
+            || patterns.iter().any(|pattern| {
+                Glob::new(pattern)
+                    .map(|glob| glob.compile_matcher().is_match(&event.name))
+                    .unwrap_or(false)
+            })
+    }
+}
+
+#[derive(Debug, Default, Deserialize)]
+struct ProjectSettings {
+    #[serde(default)]
+    server: ServerSettings,
+}
+
+#[derive(Debug, Default, Deserialize)]
+struct ServerSettings {
+    #[serde(default)]
+    webhooks: Vec<WebhookRule>,
+}
+
+/// The rules in the project config file at `path`, or none if there's no
+/// such file
+pub fn load_rules(path: &Path) -> sheila::Result<Vec<WebhookRule>> {
+    let Ok(content) = std::fs::read_to_string(path) else {
+        return Ok(Vec::new());
+    };
+    let settings: ProjectSettings = toml::from_str(&content)
+        .map_err(|e| sheila::Error::invalid_config(format!("Invalid {}: {}", path.display(), e)))?;
+    for rule in &settings.server.webhooks {
+        for pattern in rule.branches.iter().chain(&rule.tags) {
+            Glob::new(pattern).map_err(|e| {
+                sheila::Error::invalid_config(format!(
+                    "Invalid pattern in webhook '{}': {}",
+                    rule.name, e
+                ))
+            })?;
+        }
+    }
+    Ok(settings.server.webhooks)
+}
+
+/// A push or pull request, whichever service it came from
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct RepoEvent {
+    pub kind: EventKind,
+    /// The branch pushed to, the tag pushed, or the branch a pull request
+    /// targets
+    pub name: String,
+    /// The commit to run the tests of
+    pub commit: String,
+    /// URLs of the repository the event happened in
+    pub repository: Vec<String>,
+}
+
+#[derive(Deserialize)]
+struct GitHubPush {
+    #[serde(rename = "ref")]
+    git_ref: String,
+    after: String,
+    #[serde(default)]
+    deleted: bool,
+    #[serde(default)]
+    repository: GitHubRepository,
+}
+
+#[derive(Deserialize)]
+struct GitHubPullRequest {
+    action: String,
+    pull_request: GitHubPull,
+    #[serde(default)]
+    repository: GitHubRepository,
+}
+
+#[derive(Default, Deserialize)]
+struct GitHubRepository {
+    clone_url: Option<String>,
+    ssh_url: Option<String>,
+    html_url: Option<String>,
+}
+
+impl GitHubRepository {
+    fn urls(self) -> Vec<String> {
+        [self.clone_url, self.ssh_url, self.html_url]
+            .into_iter()
+            .flatten()
+            .collect()
+    }
+}
+
+#[derive(Deserialize)]
+struct GitHubPull {
+    head: GitHubBranch,
+    base: GitHubBranch,
+}
+
+#[derive(Deserialize)]
+struct GitHubBranch {
+    #[serde(rename = "ref")]
+    git_ref: String,
+    sha: String,
+}
+
+#[derive(Deserialize)]
+struct GitLabPush {
+    #[serde(rename = "ref")]
+    git_ref: String,
+    checkout_sha: Option<String>,
+    #[serde(default)]
+    project: GitLabProject,
+}
+
+#[derive(Deserialize)]
+struct GitLabMergeRequest {
+    object_attributes: GitLabMergeAttributes,
+    #[serde(default)]
+    project: GitLabProject,
+}
+
+#[derive(Default, Deserialize)]
+struct GitLabProject {
+    git_http_url: Option<String>,
+    git_ssh_url: Option<String>,
+    web_url: Option<String>,
+}
+
+impl GitLabProject {
+    fn urls(self) -> Vec<String> {
+        [self.git_http_url, self.git_ssh_url, self.web_url]
+            .into_iter()
+            .flatten()
+            .collect()
+    }
+}
+
+#[derive(Deserialize)]
+struct GitLabMergeAttributes {
+    action: Option<String>,
+    target_branch: String,
+    last_commit: GitLabCommit,
+}
+
+#[derive(Deserialize)]
+struct GitLabCommit {
+    id: String,
+}
+
+/// The event in a GitHub delivery of type `event`, if it's one runs can be
+/// triggered by. Deleted refs and closed pull requests aren't.
+pub fn github_event(event: &str, body: &[u8]) -> serde_json::Result<Option<RepoEvent>> {
+    match event {
+        "push" => {
+            let push: GitHubPush = serde_json::from_slice(body)?;
+            if push.deleted {
+                return Ok(None);
+            }
+            Ok(ref_event(&push.git_ref, push.after, push.repository.urls()))
+        }
+        "pull_request" => {
+            let request: GitHubPullRequest = serde_json::from_slice(body)?;
+            let updated = matches!(
+                request.action.as_str(),
+                "opened" | "reopened" | "synchronize"
+            );
+            Ok(updated.then_some(RepoEvent {
+                kind: EventKind::PullRequest,
+                name: request.pull_request.base.git_ref,
+                commit: request.pull_request.head.sha,
+                repository: request.repository.urls(),
+            }))
+        }
+        _ => Ok(None),
+    }
+}
+
+/// The event in a GitLab delivery of type `event`, if it's one runs can be
+/// triggered by
+pub fn gitlab_event(event: &str, body: &[u8]) -> serde_json::Result<Option<RepoEvent>> {
+    match event {
+        "Push Hook" | "Tag Push Hook" => {
+            let push: GitLabPush = serde_json::from_slice(body)?;
+            // Deleting a ref leaves nothing to check out
+            let repository = push.project.urls();
+            Ok(push
+                .checkout_sha
+                .and_then(|commit| ref_event(&push.git_ref, commit, repository)))
+        }
+        "Merge Request Hook" => {
+            let request: GitLabMergeRequest = serde_json::from_slice(body)?;
+            let attributes = request.object_attributes;
+            let updated = matches!(
+                attributes.action.as_deref(),
+                Some("open" | "reopen" | "update")
+            );
+            Ok(updated.then_some(RepoEvent {
+                kind: EventKind::PullRequest,
+                name: attributes.target_branch,
+                commit: attributes.last_commit.id,
+                repository: request.project.urls(),
+            }))
+        }
+        _ => Ok(None),
+    }
+}
+
+fn ref_event(git_ref: &str, commit: String, repository: Vec<String>) -> Option<RepoEvent> {
+    let (kind, name) = match git_ref.strip_prefix("refs/heads/") {
+        Some(branch) => (EventKind::Push, branch),
+        None => (EventKind::Tag, git_ref.strip_prefix("refs/tags/")?),
+    };
+    Some(RepoEvent {
+        kind,
+        name: name.to_string(),
+        commit,
+        repository,
+    })
+}
+
+/// Whether a GitHub delivery was signed with `secret`
+pub fn verify_github(secret: &str, headers: &HeaderMap, body: &[u8]) -> bool {
+    let Some(signature) = headers
+        .get("x-hub-signature-256")
+        .and_then(|value| value.to_str().ok())
+        .and_then(|value| value.strip_prefix("sha256="))
+        .and_then(decode_hex)
+    else {
+        return false;
+    };
+    let Ok(mut mac) = Hmac::<Sha256>::new_from_slice(secret.as_bytes()) else {
+        return false;
+    };
+    mac.update(body);
+    mac.verify_slice(&signature).is_ok()
+}
+
+/// Whether a GitLab delivery carries `secret` as its token
+pub fn verify_gitlab(secret: &str, headers: &HeaderMap) -> bool {
+    let Some(token) = headers.get("x-gitlab-token") else {
+        return false;
+    };
+    // Compare every byte, so the time taken doesn't give the secret away
+    let (token, secret) = (token.as_bytes(), secret.as_bytes());
+    token.len() == secret.len()
+        && token
+            .iter()
+            .zip(secret)
+            .fold(0, |diff, (a, b)| diff | (a ^ b))
+            == 0
+}
+
+fn decode_hex(hex: &str) -> Option<Vec<u8>> {
+    if !hex.len().is_multiple_of(2) {
+        return None;
+    }
+    (0..hex.len())
+        .step_by(2)
+        .map(|i| u8::from_str_radix(hex.get(i..i + 2)?, 16).ok())
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const SECRET: &str = "It's a Secret to Everybody";
+
+    fn signed(body: &[u8]) -> HeaderMap {
+        let mut mac = Hmac::<Sha256>::new_from_slice(SECRET.as_bytes()).unwrap();
+        mac.update(body);
+        let hex: String = mac
+            .finalize()
+            .into_bytes()
+            .iter()
+            .map(|byte| format!("{:02x}", byte))
+            .collect();
+        let mut headers = HeaderMap::new();
+        headers.insert(
+            "x-hub-signature-256",
+            format!("sha256={}", hex).parse().unwrap(),
+        );
+        headers
+    }
+
+    fn rule(events: Vec<EventKind>, branches: &[&str], tags: &[&str]) -> WebhookRule {
+        WebhookRule {
+            name: "rule".to_string(),
+            events,
+            branches: branches.iter().map(|b| b.to_string()).collect(),
+            tags: tags.iter().map(|t| t.to_string()).collect(),
+            spec: RunSpec::default(),
+        }
+    }
+
+    fn event(kind: EventKind, name: &str) -> RepoEvent {
+        RepoEvent {
+            kind,
+            name: name.to_string(),
+            commit: "4f2a9c1".to_string(),
+            repository: Vec::new(),
+        }
+    }
+
+    #[test]
+    fn test_github_signatures_must_match_the_body() {
+        let body = br#"{"ref":"refs/heads/main"}"#;
+        let headers = signed(body);
+        assert!(verify_github(SECRET, &headers, body));
+        assert!(!verify_github(
+            SECRET,
+            &headers,
+            br#"{"ref":"refs/heads/evil"}"#
+        ));
+        assert!(!verify_github("another secret", &headers, body));
+        assert!(!verify_github(SECRET, &HeaderMap::new(), body));
+
+        let mut odd = HeaderMap::new();
+        odd.insert("x-hub-signature-256", "sha256=abc".parse().unwrap());
+        assert!(!verify_github(SECRET, &odd, body));
+    }
+
+    #[test]
+    fn test_hex_is_decoded_in_pairs() {
+        assert_eq!(decode_hex("00ff7f"), Some(vec![0x00, 0xff, 0x7f]));
+        assert_eq!(decode_hex("abc"), None);
+        assert_eq!(decode_hex("zz"), None);
+    }
+
+    #[test]
+    fn test_gitlab_token_must_equal_the_secret() {
+        let with_token = |token: &str| {
+            let mut headers = HeaderMap::new();
+            headers.insert("x-gitlab-token", token.parse().unwrap());
+            headers
+        };
+        assert!(verify_gitlab(SECRET, &with_token(SECRET)));
+        assert!(!verify_gitlab(SECRET, &with_token("It's a Secret")));
+        assert!(!verify_gitlab(
+            SECRET,
+            &with_token("It's a Secret to Everybodz")
+        ));
+        assert!(!verify_gitlab(SECRET, &HeaderMap::new()));
+    }
+
+    #[test]
+    fn test_deleted_refs_and_closed_requests_trigger_nothing() {
+        let deleted = br#"{"ref":"refs/heads/main","after":"0000000","deleted":true}"#;
+        assert_eq!(github_event("push", deleted).unwrap(), None);
+
+        let closed = br#"{
+            "action": "closed",
+            "pull_request": {
+                "head": {"ref": "feature", "sha": "4f2a9c1"},
+                "base": {"ref": "main", "sha": "1c9a2f4"}
+            }
+        }"#;
+        assert_eq!(github_event("pull_request", closed).unwrap(), None);
+
+        let deleted = br#"{"ref":"refs/heads/main","checkout_sha":null}"#;
+        assert_eq!(gitlab_event("Push Hook", deleted).unwrap(), None);
+
+        let closed = br#"{
+            "object_attributes": {
+                "action": "close",
+                "target_branch": "main",
+                "last_commit": {"id": "4f2a9c1"}
+            }
+        }"#;
+        assert_eq!(gitlab_event("Merge Request Hook", closed).unwrap(), None);
+
+        let pushed = br#"{"ref":"refs/tags/v1.0.0","after":"4f2a9c1"}"#;
+        assert_eq!(
+            github_event("push", pushed).unwrap(),
+            Some(event(EventKind::Tag, "v1.0.0"))
+        );
+    }
+
+    #[test]
+    fn test_rules_match_branches_and_tags_by_glob() {
+        let branches = rule(default_events(), &["main", "release/*"], &[]);
+        assert!(branches.matches(&event(EventKind::Push, "main")));
+        assert!(branches.matches(&event(EventKind::PullRequest, "release/1.2")));
+        assert!(!branches.matches(&event(EventKind::Push, "feature/x")));
+        assert!(!branches.matches(&event(EventKind::Tag, "main")));
+
+        let tags = rule(vec![EventKind::Tag], &["main"], &["v*"]);
+        assert!(tags.matches(&event(EventKind::Tag, "v1.0.0")));
+        assert!(!tags.matches(&event(EventKind::Tag, "nightly")));
+        assert!(!tags.matches(&event(EventKind::Push, "main")));
+
+        let every = rule(default_events(), &[], &[]);
+        assert!(every.matches(&event(EventKind::Push, "anything")));
+    }
+}
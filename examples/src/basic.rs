@@ -45,6 +45,18 @@ pub mod math_tests {
         std::thread::sleep(std::time::Duration::from_secs(7));
         assert_eq!(calc.divide(10, 2), 5);
     }
+
+    #[sheila::test]
+    #[sheila::params(0, 7, -3)]
+    pub fn test_add_zero(n: i32) {
+        assert_eq!(calculator().add(n, 0), n);
+    }
+}
+
+#[sheila::bench]
+fn bench_divide() {
+    let calc = Calculator::new();
+    std::hint::black_box(calc.divide(std::hint::black_box(84), 2));
 }
 
 #[derive(Debug)]
@@ -67,3 +79,35 @@ impl Calculator {
         a / b
     }
 }
+
+#[sheila::automock]
+pub trait RateSource {
+    fn rate(&self, from: &str, to: &str) -> Option<f64>;
+}
+
+/// Converts amounts between currencies with rates from a [`RateSource`]
+pub fn convert(rates: &impl RateSource, amount: f64, from: &str, to: &str) -> Option<f64> {
+    rates.rate(from, to).map(|rate| amount * rate)
+}
+
+#[sheila::suite]
+pub mod conversion_tests {
+    use super::{MockRateSource, convert};
+    use sheila::{ArgMatcher, MockBuilder};
+
+    #[sheila::test]
+    pub fn test_conversion_uses_the_rate() {
+        let mut rates = MockRateSource::new();
+        rates.expect(
+            "rate",
+            MockBuilder::new()
+                .with_args([ArgMatcher::eq("EUR"), ArgMatcher::eq("USD")])
+                .expect_calls(1)
+                .returns(Some(1.5))
+                .build(),
+        );
+
+        assert_eq!(convert(&rates, 10.0, "EUR", "USD"), Some(15.0));
+        rates.verify().unwrap();
+    }
+}